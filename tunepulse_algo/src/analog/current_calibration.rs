@@ -0,0 +1,306 @@
+// Implements the current sense calibration, a startup stage measuring the offset of every phase
+// current channel with the outputs disabled and the gain mismatch between the phase channels.
+
+// Key Features:
+// - Offset stage: averages every channel with the outputs off once the winding current has decayed.
+// - Gain stage (three-phase motors): two DC test vectors with known current ratios between phases.
+// - Out-of-range offsets or a missing test current raise a fault (broken shunt or amplifier).
+// - Results are Q14 gains and raw ADC offsets, applied to `CurrentSense` before the current loop.
+
+// Detailed Operation:
+// The stage first keeps all outputs off for `settle_ticks` and then averages 2^samples_log2
+// readings of every channel. An offset further than `max_offset` from mid-scale can't come from a
+// healthy bipolar amplifier (open shunt, shorted input or dead amplifier), so it fails the
+// calibration with the channel recorded in the report. For three-phase motors two test vectors are
+// then applied at `test_current_ma`, each held for `settle_ticks` before averaging:
+//   1. Electrical 0°: current flows from B to C only, so |i_b| = |i_c| gives the C/B gain ratio.
+//   2. Electrical 90°: current enters A and returns through B and C, so |i_a| = |i_b| + |i_c|
+//      (with C already corrected) gives the A/B ratio.
+// The three ratios are normalized to an average of 1.0, so the gains correct the mismatch without
+// changing the overall current scale. A test current below `min_signal` on any phase (the
+// amplifier doesn't respond) or a gain outside +/- `max_gain_error_pct` fails the calibration.
+// The rotor may align to the test vector; sampling starts only after the settle time so the
+// alignment transient does not disturb the measurement. Channels 0..2 are phases A, B, C.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Unity gain of a current channel (Q14)
+pub const GAIN_ONE: u16 = 1 << 14;
+
+/// Mid-scale reading of a bipolar amplifier (left-aligned ADC)
+const MID_SCALE: i32 = 1 << 15;
+
+/// Settings of the current sense calibration.
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentCalConfig {
+    /// Ticks to wait after the outputs change before sampling
+    pub settle_ticks: u32,
+    /// Number of averaged samples per measurement: 2^samples_log2
+    pub samples_log2: u8,
+    /// Largest accepted distance of an offset from mid-scale (raw ADC units)
+    pub max_offset: u16,
+    /// Test current of the gain stage (mA), 0 skips the gain stage
+    pub test_current_ma: i16,
+    /// Smallest accepted test current reading on every phase (raw ADC units)
+    pub min_signal: u16,
+    /// Largest accepted gain deviation from the average (%)
+    pub max_gain_error_pct: u8,
+}
+
+impl CurrentCalConfig {
+    /// Creates default settings for the given tick frequency.
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            settle_ticks: frequency as u32 / 10, // 100 ms
+            samples_log2: 10,                    // 1024 samples, ~50 ms at 20 kHz
+            max_offset: 4096,                    // 6.25% of the ADC range
+            test_current_ma: 500,
+            min_signal: 256,
+            max_gain_error_pct: 20,
+        }
+    }
+}
+
+/// Represents the current stage of the calibration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurrentCalStage {
+    /// Calibration is not running
+    Idle,
+    /// Outputs off, measuring offsets
+    Offsets,
+    /// Test vector from B to C applied
+    GainBC,
+    /// Test vector into A applied
+    GainA,
+    /// Calibration finished, results are valid
+    Done,
+    /// An offset or gain is out of range
+    Failed,
+}
+
+/// Result of the calibration.
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentCalReport {
+    /// Channel offsets (raw ADC units)
+    pub offsets: [u16; 4],
+    /// Channel gains (Q14, GAIN_ONE = 1.0)
+    pub gains: [u16; 4],
+    /// Channels that failed the calibration (bit per channel)
+    pub failed_channels: u8,
+}
+
+impl Default for CurrentCalReport {
+    fn default() -> Self {
+        Self {
+            offsets: [MID_SCALE as u16; 4],
+            gains: [GAIN_ONE; 4],
+            failed_channels: 0,
+        }
+    }
+}
+
+/// Startup offset and gain calibration of the phase current channels.
+pub struct CurrentCalibration {
+    config: CurrentCalConfig,
+    stage: CurrentCalStage,
+    three_phase: bool, // Run the gain stage after the offsets
+
+    ticks: u32,            // Ticks elapsed in the current stage
+    sums: [i64; 4],        // Accumulated readings of the current measurement
+    samples: u32,          // Accumulated samples of the current measurement
+    magnitudes: [i64; 3],  // |i_b|, |i_c| of the first vector and |i_a| of the second (x2^samples_log2)
+    second: [i64; 2],      // |i_b|, |i_c| of the second vector (x2^samples_log2)
+    report: CurrentCalReport,
+}
+
+impl CurrentCalibration {
+    /// Creates an idle calibration with default settings.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            config: CurrentCalConfig::new(frequency),
+            stage: CurrentCalStage::Idle,
+            three_phase: false,
+            ticks: 0,
+            sums: [0; 4],
+            samples: 0,
+            magnitudes: [0; 3],
+            second: [0; 2],
+            report: CurrentCalReport::default(),
+        }
+    }
+
+    /// Sets calibration settings used by the next start.
+    pub fn configure(&mut self, config: CurrentCalConfig) {
+        self.config = config;
+    }
+
+    /// Starts the calibration.
+    ///
+    /// # Arguments
+    /// * `three_phase` - Measure the gain mismatch of phases A, B, C after the offsets
+    pub fn start(&mut self, three_phase: bool) {
+        self.three_phase = three_phase;
+        self.report = CurrentCalReport::default();
+        self.enter(CurrentCalStage::Offsets);
+    }
+
+    /// Advances the calibration by one tick.
+    ///
+    /// # Arguments
+    /// * `adc` - Raw current ADC readings of the 4 channels
+    ///
+    /// Returns the commanded (electrical angle, current amplitude) pair, amplitude 0 keeps the
+    /// outputs off.
+    pub fn tick(&mut self, adc: [u16; 4]) -> (u16, i16) {
+        let command = match self.stage {
+            CurrentCalStage::GainBC => (0, self.config.test_current_ma),
+            CurrentCalStage::GainA => (1 << 14, self.config.test_current_ma),
+            _ => return (0, 0),
+        };
+        if !self.sample(adc) {
+            return command;
+        }
+
+        match self.stage {
+            // ####### Offsets #######
+            CurrentCalStage::Offsets => {
+                let shift = self.config.samples_log2;
+                for (channel, offset) in self.report.offsets.iter_mut().enumerate() {
+                    let average = (self.sums[channel] >> shift) as i32;
+                    *offset = average as u16;
+                    if (average - MID_SCALE).unsigned_abs() > self.config.max_offset as u32 {
+                        self.report.failed_channels |= 1 << channel;
+                    }
+                }
+                if self.report.failed_channels != 0 {
+                    defmt::error!(
+                        "CURRENT CAL: Offset out of range, channels {:04b}, offsets {}",
+                        self.report.failed_channels,
+                        self.report.offsets
+                    );
+                    self.enter(CurrentCalStage::Failed);
+                } else if self.three_phase && self.config.test_current_ma > 0 {
+                    self.enter(CurrentCalStage::GainBC);
+                } else {
+                    self.finish();
+                }
+            }
+
+            // ####### Gains #######
+            CurrentCalStage::GainBC => {
+                self.magnitudes[0] = self.magnitude(1);
+                self.magnitudes[1] = self.magnitude(2);
+                self.enter(CurrentCalStage::GainA);
+            }
+            CurrentCalStage::GainA => {
+                self.magnitudes[2] = self.magnitude(0);
+                self.second = [self.magnitude(1), self.magnitude(2)];
+                self.evaluate_gains();
+            }
+            _ => {}
+        }
+        command
+    }
+
+    /// Returns the current stage.
+    pub fn stage(&self) -> CurrentCalStage {
+        self.stage
+    }
+
+    /// Returns true while the calibration is running.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.stage,
+            CurrentCalStage::Offsets | CurrentCalStage::GainBC | CurrentCalStage::GainA
+        )
+    }
+
+    /// Returns the calibration result (defaults until the calibration is done).
+    pub fn report(&self) -> CurrentCalReport {
+        self.report
+    }
+
+    /// Waits for the settle time and accumulates samples, returns true when the average is complete.
+    fn sample(&mut self, adc: [u16; 4]) -> bool {
+        self.ticks = self.ticks.saturating_add(1);
+        if self.ticks <= self.config.settle_ticks {
+            return false;
+        }
+        for (sum, &value) in self.sums.iter_mut().zip(adc.iter()) {
+            *sum += value as i64;
+        }
+        self.samples += 1;
+        self.samples >= 1 << self.config.samples_log2
+    }
+
+    /// Returns |average - offset| of a channel, scaled by the number of samples.
+    fn magnitude(&self, channel: usize) -> i64 {
+        let offset = (self.report.offsets[channel] as i64) << self.config.samples_log2;
+        (self.sums[channel] - offset).abs()
+    }
+
+    /// Computes the gains from both test vectors and checks them.
+    fn evaluate_gains(&mut self) {
+        let min_signal = (self.config.min_signal as i64) << self.config.samples_log2;
+        let [b1, c1, a2] = self.magnitudes;
+        let [b2, c2] = self.second;
+        for (channel, &magnitude) in [a2, b1, c1].iter().enumerate() {
+            if magnitude < min_signal {
+                self.report.failed_channels |= 1 << channel;
+            }
+        }
+
+        if self.report.failed_channels == 0 {
+            // Corrections relative to B (Q14): C from |i_b| = |i_c|, A from |i_a| = |i_b| + |i_c|
+            let one = GAIN_ONE as i64;
+            let corr_c = b1 * one / c1;
+            let corr_a = (b2 * one + c2 * corr_c) / a2;
+            let corrections = [corr_a, one, corr_c];
+            let average = corrections.iter().sum::<i64>() / 3;
+
+            let max_error = self.config.max_gain_error_pct as i64;
+            for (channel, &correction) in corrections.iter().enumerate() {
+                let gain = correction * one / average;
+                self.report.gains[channel] = gain.clamp(0, u16::MAX as i64) as u16;
+                if (gain - one).abs() * 100 > max_error * one {
+                    self.report.failed_channels |= 1 << channel;
+                }
+            }
+        }
+
+        if self.report.failed_channels != 0 {
+            defmt::error!(
+                "CURRENT CAL: Gain out of range, channels {:04b}, gains {}",
+                self.report.failed_channels,
+                self.report.gains
+            );
+            self.enter(CurrentCalStage::Failed);
+        } else {
+            self.finish();
+        }
+    }
+
+    /// Ends the calibration successfully.
+    fn finish(&mut self) {
+        defmt::info!(
+            "CURRENT CAL: Offsets {}, gains {}",
+            self.report.offsets,
+            self.report.gains
+        );
+        self.enter(CurrentCalStage::Done);
+    }
+
+    /// Switches the stage and restarts the measurement.
+    fn enter(&mut self, stage: CurrentCalStage) {
+        self.stage = stage;
+        self.ticks = 0;
+        self.sums = [0; 4];
+        self.samples = 0;
+    }
+}
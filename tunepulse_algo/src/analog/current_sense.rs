@@ -0,0 +1,128 @@
+// Implements the CurrentSense module, converting raw phase-current ADC readings into signed
+// values and tracking the slow drift of the current amplifier offsets.
+
+// Key Features:
+// - Removes per-channel offsets from raw current ADC readings
+// - Tracks offsets only during guaranteed zero-current windows (outputs off, rotor at rest)
+// - Requires a settle time after the outputs go off so the winding current can decay
+// - Updates offsets gradually with an exponential average to reject noise and short spikes
+// - Applies per-channel gains from the startup calibration (phase gain mismatch)
+
+// Detailed Operation:
+// Current amplifiers drift with temperature, and the offset error appears as a constant torque
+// bias of the current loop. While the outputs are disabled and the rotor stands still no current
+// can flow, so every reading in that window is a pure offset sample. The caller reports such a
+// window through the `idle` flag; once it has lasted `settle_ticks` ticks each new sample moves
+// the stored offset by 1/2^TRACK_SHIFT of the difference. Outside the windows the offsets are kept,
+// so tracking never interferes with normal operation.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the stored offsets
+const OFFSET_FRAC: u32 = 8;
+
+/// Fractional bits of the channel gains (unity gain = 1 << GAIN_FRAC)
+const GAIN_FRAC: u32 = 14;
+
+/// Tracking strength: each idle sample moves the offset by 1 / 2^TRACK_SHIFT of the difference
+const TRACK_SHIFT: u32 = 10;
+
+/// Manages phase-current readings and their offsets
+pub struct CurrentSense {
+    /// Offsets of the current channels (raw ADC units with OFFSET_FRAC fractional bits)
+    offsets: [u32; 4],
+
+    /// Channel gains (GAIN_FRAC fractional bits)
+    gains: [u16; 4],
+
+    /// Signed current readings with offsets removed (raw ADC units)
+    currents: [i16; 4],
+
+    /// Number of idle ticks required before offsets are tracked
+    settle_ticks: u16,
+
+    /// Consecutive idle ticks
+    idle_ticks: u16,
+
+    /// Number of samples used for tracking since startup
+    samples: u32,
+}
+
+impl CurrentSense {
+    /// Constructs a `CurrentSense` object with mid-scale offsets (bipolar amplifiers)
+    ///
+    /// # Arguments
+    /// * `settle_ticks` - Idle ticks required before tracking starts (winding current decay)
+    pub fn new(settle_ticks: u16) -> Self {
+        CurrentSense {
+            offsets: [(1 << 15) << OFFSET_FRAC; 4], // Left-aligned ADC, amplifier output at mid-scale
+            gains: [1 << GAIN_FRAC; 4],
+            currents: [0; 4],
+            settle_ticks,
+            idle_ticks: 0,
+            samples: 0,
+        }
+    }
+
+    /// Updates current readings and tracks offsets during zero-current windows
+    ///
+    /// # Arguments
+    /// * `adc` - Raw current ADC readings of the 4 channels
+    /// * `idle` - True if no current can flow (outputs off and rotor at rest)
+    pub fn tick(&mut self, adc: [u16; 4], idle: bool) -> &Self {
+        if idle {
+            self.idle_ticks = self.idle_ticks.saturating_add(1);
+        } else {
+            self.idle_ticks = 0;
+        }
+
+        // ########## Track offsets once the window is settled ###########################
+        if self.idle_ticks > self.settle_ticks {
+            for (offset, &sample) in self.offsets.iter_mut().zip(adc.iter()) {
+                let sample = (sample as i32) << OFFSET_FRAC;
+                let error = sample - *offset as i32;
+                *offset = (*offset as i32 + (error >> TRACK_SHIFT)) as u32;
+            }
+            self.samples = self.samples.saturating_add(1);
+        }
+
+        // ########## Remove offsets and correct gains ###########################
+        for (i, current) in self.currents.iter_mut().enumerate() {
+            let value = adc[i] as i32 - (self.offsets[i] >> OFFSET_FRAC) as i32;
+            let value = ((value as i64 * self.gains[i] as i64) >> GAIN_FRAC) as i32;
+            *current = value.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+        self
+    }
+
+    /// Retrieves signed current readings with offsets removed
+    pub fn currents(&self) -> [i16; 4] {
+        self.currents // Returns the current readings of all channels
+    }
+
+    /// Retrieves channel offsets (raw ADC units)
+    pub fn offsets(&self) -> [u16; 4] {
+        self.offsets.map(|offset| (offset >> OFFSET_FRAC) as u16)
+    }
+
+    /// Sets channel offsets (raw ADC units), e.g. restored from a startup calibration
+    pub fn set_offsets(&mut self, offsets: [u16; 4]) {
+        self.offsets = offsets.map(|offset| (offset as u32) << OFFSET_FRAC);
+    }
+
+    /// Retrieves channel gains (1 << 14 = 1.0)
+    pub fn gains(&self) -> [u16; 4] {
+        self.gains
+    }
+
+    /// Sets channel gains (1 << 14 = 1.0), e.g. from a startup calibration
+    pub fn set_gains(&mut self, gains: [u16; 4]) {
+        self.gains = gains;
+    }
+
+    /// Retrieves the number of samples used for offset tracking
+    pub fn samples(&self) -> u32 {
+        self.samples // Returns the tracking sample counter
+    }
+}
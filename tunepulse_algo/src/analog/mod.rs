@@ -1,4 +1,7 @@
 pub mod adc_correction;
 pub mod supply_voltage;
+pub mod current_sense;
+pub mod current_calibration;
+pub mod energy_meter;
+pub mod shunt_reconstruction;
 use crate::math_integer::normalization::*;
-use crate::math_integer::filters::lpf;
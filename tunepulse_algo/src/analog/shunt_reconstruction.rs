@@ -0,0 +1,179 @@
+// Implements the low-side shunt reconstruction, recovering the three phase currents from triple,
+// dual or single (DC link) shunt samples using the duties of the PWM period they were taken in.
+
+// Key Features:
+// - Triple shunt: drops the phase whose low-side window is too short and rebuilds it from the others.
+// - Dual shunt: phases A and B measured, C from Kirchhoff's law, invalid samples hold their last value.
+// - Single shunt: two DC link samples per period mapped to phases by the PWM sector (duty order).
+// - Minimum-pulse handling: samples from windows shorter than `min_pulse` are never used.
+// - Counters of periods that had to be (partly) reconstructed from held values.
+
+// Detailed Operation:
+// A low-side shunt only carries the phase current while the low-side switch of that phase conducts,
+// i.e. for (1 - duty) of a center-aligned period, and the ADC needs a minimum window after the
+// switching edge (ringing, amplifier settling). A phase with less than `min_pulse` of low-side time
+// is invalid in that period. With three shunts at most one phase is invalid for space-vector PWM
+// (only the highest duty approaches 100%), so it is rebuilt from ia + ib + ic = 0. Dual shunt boards
+// measure A and B, so only C can be rebuilt; an invalid A or B keeps its last valid value.
+// A single DC link shunt sees a phase current only during the two active vectors of the period.
+// With the duties sorted (max >= mid >= min), the first active vector (only the max phase high)
+// lasts d_max - d_mid and carries +i_max, the second (only the min phase low) lasts d_mid - d_min
+// and carries -i_min; the third phase follows from the sum. The caller samples in the middle of both
+// vectors (e.g. triggered from the duties) and passes the samples in that order. A vector shorter
+// than `min_pulse` can't be sampled; its phase keeps the last value and the rest is rebuilt from the
+// valid sample. Samples are signed currents with offsets removed, positive into the motor; duties
+// are phase duties of the period (0..32767 = 0..100%).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Selects the shunt arrangement of the power stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShuntTopology {
+    /// One low-side shunt per phase (A, B, C)
+    Triple,
+    /// Low-side shunts on phases A and B
+    Dual,
+    /// One shunt in the DC link return
+    Single,
+}
+
+/// Reconstructs phase currents from low-side shunt samples.
+pub struct ShuntReconstruction {
+    topology: ShuntTopology,
+    min_pulse: i16,      // Shortest usable sampling window (duty units, 32767 = whole period)
+    currents: [i16; 3],  // Reconstructed currents of phases A, B, C
+    rebuilt: u32,        // Periods with a phase rebuilt from Kirchhoff's law
+    held: u32,           // Periods that used a held (last valid) value
+}
+
+impl ShuntReconstruction {
+    /// Creates a reconstruction for the given topology.
+    ///
+    /// # Arguments
+    /// * `topology` - Shunt arrangement
+    /// * `min_pulse` - Shortest usable sampling window (duty units, 32767 = whole period)
+    pub fn new(topology: ShuntTopology, min_pulse: i16) -> Self {
+        Self {
+            topology,
+            min_pulse: min_pulse.max(0),
+            currents: [0; 3],
+            rebuilt: 0,
+            held: 0,
+        }
+    }
+
+    /// Reconstructs the phase currents of one PWM period.
+    ///
+    /// # Arguments
+    /// * `samples` - Triple: phase A, B, C; dual: phase A, B (third ignored);
+    ///   single: DC link in the first and second active vector (third ignored)
+    /// * `duties` - Duties of phases A, B, C in the sampled period (0..32767)
+    pub fn tick(&mut self, samples: [i16; 3], duties: [i16; 3]) -> [i16; 3] {
+        match self.topology {
+            ShuntTopology::Triple => self.triple(samples, duties),
+            ShuntTopology::Dual => self.dual(samples, duties),
+            ShuntTopology::Single => self.single(samples, duties),
+        }
+        self.currents
+    }
+
+    /// Changes the shortest usable sampling window (duty units).
+    pub fn set_min_pulse(&mut self, min_pulse: i16) {
+        self.min_pulse = min_pulse.max(0);
+    }
+
+    /// Returns the reconstructed currents of phases A, B, C.
+    pub fn currents(&self) -> [i16; 3] {
+        self.currents
+    }
+
+    /// Returns the number of periods with a phase rebuilt from the other two.
+    pub fn rebuilt_periods(&self) -> u32 {
+        self.rebuilt
+    }
+
+    /// Returns the number of periods that used a held (last valid) value.
+    pub fn held_periods(&self) -> u32 {
+        self.held
+    }
+
+    /// Returns true if the low-side window of a phase is long enough to sample.
+    #[inline(always)]
+    fn low_side_valid(&self, duty: i16) -> bool {
+        i16::MAX as i32 - duty as i32 >= self.min_pulse as i32
+    }
+
+    fn triple(&mut self, samples: [i16; 3], duties: [i16; 3]) {
+        let valid = duties.map(|duty| self.low_side_valid(duty));
+        match valid.iter().filter(|&&ok| !ok).count() {
+            0 => self.currents = samples,
+            1 => {
+                // The invalid phase is minus the sum of the two valid ones
+                let missing = valid.iter().position(|&ok| !ok).unwrap_or(0);
+                let others: i32 = (0..3).filter(|&i| i != missing).map(|i| samples[i] as i32).sum();
+                self.currents = samples;
+                self.currents[missing] = sat_i16(-others);
+                self.rebuilt = self.rebuilt.saturating_add(1);
+            }
+            _ => {
+                // Not enough information: keep the valid phase, hold the rest
+                for (i, &ok) in valid.iter().enumerate() {
+                    if ok {
+                        self.currents[i] = samples[i];
+                    }
+                }
+                self.held = self.held.saturating_add(1);
+            }
+        }
+    }
+
+    fn dual(&mut self, samples: [i16; 3], duties: [i16; 3]) {
+        let mut holding = false;
+        for ((current, &sample), &duty) in self.currents.iter_mut().zip(&samples).zip(&duties).take(2) {
+            if i16::MAX as i32 - duty as i32 >= self.min_pulse as i32 {
+                *current = sample;
+            } else {
+                holding = true;
+            }
+        }
+        self.currents[2] = sat_i16(-(self.currents[0] as i32 + self.currents[1] as i32));
+        if holding {
+            self.held = self.held.saturating_add(1);
+        }
+    }
+
+    fn single(&mut self, samples: [i16; 3], duties: [i16; 3]) {
+        // Sort phases by duty: sector of the period
+        let mut order = [0usize, 1, 2];
+        order.sort_unstable_by(|&a, &b| duties[b].cmp(&duties[a]));
+        let [max, mid, min] = order;
+
+        let first_ok = duties[max] as i32 - duties[mid] as i32 >= self.min_pulse as i32;
+        let second_ok = duties[mid] as i32 - duties[min] as i32 >= self.min_pulse as i32;
+        if first_ok {
+            self.currents[max] = samples[0];
+        }
+        if second_ok {
+            self.currents[min] = sat_i16(-(samples[1] as i32));
+        }
+        if first_ok && second_ok {
+            self.currents[mid] = sat_i16(-(self.currents[max] as i32 + self.currents[min] as i32));
+            return;
+        }
+
+        // One vector too short: the phase of the valid sample is fresh and the one without a sample
+        // is held, the middle phase keeps the three currents consistent
+        if first_ok || second_ok {
+            let fresh = self.currents[max] as i32 + self.currents[min] as i32;
+            self.currents[mid] = sat_i16(-fresh);
+        }
+        self.held = self.held.saturating_add(1);
+    }
+}
+
+/// Saturates into the i16 range.
+#[inline(always)]
+fn sat_i16(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
@@ -3,26 +3,30 @@
 
 // Key Features:
 // - Processes raw supply voltage readings from ADC
-// - Applies a low-pass filter to smooth voltage data
+// - Oversamples the readings with a power-of-two moving average to smooth voltage data
 // - Scales filtered output to obtain voltage in millivolts
 // - Provides access to normalized and scaled voltage values
 
 // Detailed Operation:
-// The SupplyVoltage struct handles raw ADC readings by passing them through a low-pass filter
-// to eliminate noise and smooth the voltage signal. The filtered output is then normalized
+// The SupplyVoltage struct handles raw ADC readings by averaging the last SUPPLY_WINDOW samples
+// to eliminate noise and smooth the voltage signal. The window cancels PWM ripple exactly and,
+// unlike the angle low-pass filter, doesn't wrap around at the ends of the ADC range. The filtered output is then normalized
 // and scaled based on the maximum expected voltage to provide accurate millivolt measurements.
 // This setup ensures reliable voltage monitoring for the system.
 
 // Licensed under the Apache License, Version 2.0
 // Copyright 2024 Anton Khrustalev, creapunk.com
 
-use super::lpf::FilterLPF; // Imports the low-pass filter implementation from the parent module
+use crate::math_integer::filters::average::MovingAverage; // Imports the moving average filter
 use super::norm_to_value; // Imports the normalization to value conversion function from the parent module
 
-/// Manages supply voltage measurements with low-pass filtering
+/// Number of averaged supply samples (power of two)
+const SUPPLY_WINDOW: usize = 16;
+
+/// Manages supply voltage measurements with moving average filtering
 pub struct SupplyVoltage {
-    /// Instance of low-pass filter for smoothing voltage measurements
-    filter: FilterLPF,
+    /// Moving average for smoothing voltage measurements
+    filter: MovingAverage<SUPPLY_WINDOW>,
 
     /// Maximum voltage in millivolts for scaling
     max_voltage_mv: i32,
@@ -35,11 +39,11 @@ pub struct SupplyVoltage {
 }
 
 impl SupplyVoltage {
-    /// Constructs a `SupplyVoltage` object with the specified filter constant and maximum voltage
-    pub fn new(k_filter: u8, max_sup_voltage: i32) -> Self {
+    /// Constructs a `SupplyVoltage` object with the specified maximum voltage
+    pub fn new(max_sup_voltage: i32) -> Self {
         SupplyVoltage {
             max_voltage_mv: max_sup_voltage, // Sets the maximum supply voltage
-            filter: FilterLPF::new(0, k_filter), // Initializes the low-pass filter with initial value and filter constant
+            filter: MovingAverage::new(0),   // Initializes the moving average with an empty window
             voltage_norm: 0,                     // Initializes the normalized voltage to zero
             voltage_mv: 0,                       // Initializes the millivolt voltage to zero
         }
@@ -47,8 +51,8 @@ impl SupplyVoltage {
 
     /// Updates the voltage measurement by processing the filter and scaling the output
     pub fn tick(&mut self, vsup_adc: u16) -> &Self {
-        self.filter.tick(vsup_adc); // Advances the filter state with the new ADC reading
-        self.voltage_norm = (self.filter.get_output() >> 1) as i16; // Retrieves and normalizes the filter output
+        let average = self.filter.tick(vsup_adc as i32); // Advances the filter state with the new ADC reading
+        self.voltage_norm = (average >> 1) as i16; // Normalizes the filter output
         self.voltage_mv = norm_to_value(self.voltage_norm, self.max_voltage_mv); // Converts normalized voltage to millivolts
         self
     }
@@ -39,6 +39,40 @@ pub struct DataInputs {
 
     /// Raw angle measurement.
     pub angle_raw: u16,
+
+    /// Limit switch states (bit 0 - negative end, bit 1 - positive end).
+    pub limit_sw: u8,
+
+    /// Encoder status flags (bit 0 - battery low, bit 1 - multi-turn lost, bit 2 - parity error,
+    /// bit 3 - magnet field out of range).
+    pub encoder_status: u8,
+
+    /// Encoder index (Z) edges counted by the HAL, wrapping.
+    pub index_count: u8,
+
+    /// Raw angle captured at the last index edge.
+    pub index_angle: u16,
+
+    /// External capture trigger edges counted by the HAL, wrapping.
+    pub capture_count: u8,
+
+    /// Raw angle of the load-side encoder (dual encoder setups).
+    pub load_angle: u16,
+
+    /// Encoder samples taken by the HAL, wrapping (sensors slower than the control loop).
+    pub angle_count: u8,
+
+    /// Cycle counter stamp of the snapshot, wrapping (tick jitter measurement).
+    pub timestamp: u32,
+
+    /// Emergency stop input, true while the e-stop circuit is open.
+    pub estop: bool,
+
+    /// Gate driver status (bit 0 - nFAULT asserted, bits 1-4 - desaturation of phases A-D).
+    pub gate_status: u8,
+
+    /// Step pulses counted by the HAL, up or down by the DIR input, wrapping.
+    pub step_count: u16,
 }
 
 impl DataInputs {
@@ -48,6 +82,17 @@ impl DataInputs {
             temper_adc: 0,
             currnt_adc: [0; 4],
             angle_raw: 0,
+            limit_sw: 0,
+            encoder_status: 0,
+            index_count: 0,
+            index_angle: 0,
+            capture_count: 0,
+            load_angle: 0,
+            angle_count: 0,
+            timestamp: 0,
+            estop: false,
+            gate_status: 0,
+            step_count: 0,
         }
     }
 }
@@ -69,6 +114,36 @@ pub enum DataInputsBit {
     /// Mask for the angle field bit.
     ANGLE = 1 << 3,
 
+    /// Mask for the limit switches field bit.
+    LIMITS = 1 << 4,
+
+    /// Mask for the encoder status field bit.
+    ENCSTATUS = 1 << 5,
+
+    /// Mask for the encoder index fields bit.
+    INDEX = 1 << 6,
+
+    /// Mask for the capture trigger field bit.
+    CAPTURE = 1 << 7,
+
+    /// Mask for the load encoder angle field bit.
+    LOADANGLE = 1 << 8,
+
+    /// Mask for the encoder sample counter field bit.
+    ANGLECOUNT = 1 << 9,
+
+    /// Mask for the timestamp field bit.
+    TIMESTAMP = 1 << 10,
+
+    /// Mask for the emergency stop field bit.
+    ESTOP = 1 << 11,
+
+    /// Mask for the gate driver status field bit.
+    GATESTATUS = 1 << 12,
+
+    /// Mask for the step counter field bit.
+    STEPCOUNT = 1 << 13,
+
     /// Mask for the lock bit at the most significant bit.
     LOCK = 1 << 31,
 }
@@ -168,6 +243,87 @@ impl<const MANDATORY_FIELDS: u32> InputsDump<MANDATORY_FIELDS> {
         self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
     }
 
+    /// Sets the `limit_sw` field in the currently updating buffer.
+    pub fn set_limit_switches(&mut self, value: u8) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].limit_sw = value; // Store the limit switch states
+        self.clear_field_bit(idx, DataInputsBit::LIMITS); // Mark the limits field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `encoder_status` field in the currently updating buffer.
+    pub fn set_encoder_status(&mut self, value: u8) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].encoder_status = value; // Store the encoder status flags
+        self.clear_field_bit(idx, DataInputsBit::ENCSTATUS); // Mark the encoder status field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `index_count` and `index_angle` fields in the currently updating buffer.
+    pub fn set_index(&mut self, count: u8, angle: u16) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].index_count = count; // Store the index edge counter
+        self.buffers[idx].index_angle = angle; // Store the angle captured at the edge
+        self.clear_field_bit(idx, DataInputsBit::INDEX); // Mark the index fields as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `capture_count` field in the currently updating buffer.
+    pub fn set_capture_count(&mut self, count: u8) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].capture_count = count; // Store the capture trigger counter
+        self.clear_field_bit(idx, DataInputsBit::CAPTURE); // Mark the capture field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `load_angle` field in the currently updating buffer.
+    pub fn set_load_angle(&mut self, value: u16) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].load_angle = value; // Store the load encoder angle
+        self.clear_field_bit(idx, DataInputsBit::LOADANGLE); // Mark the load angle field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `angle_count` field in the currently updating buffer.
+    pub fn set_angle_count(&mut self, count: u8) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].angle_count = count; // Store the encoder sample counter
+        self.clear_field_bit(idx, DataInputsBit::ANGLECOUNT); // Mark the sample counter field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `timestamp` field in the currently updating buffer.
+    pub fn set_timestamp(&mut self, timestamp: u32) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].timestamp = timestamp; // Store the cycle counter stamp
+        self.clear_field_bit(idx, DataInputsBit::TIMESTAMP); // Mark the timestamp field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `estop` field in the currently updating buffer.
+    pub fn set_estop(&mut self, active: bool) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].estop = active; // Store the emergency stop input
+        self.clear_field_bit(idx, DataInputsBit::ESTOP); // Mark the e-stop field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `gate_status` field in the currently updating buffer.
+    pub fn set_gate_status(&mut self, value: u8) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].gate_status = value; // Store the gate driver flags
+        self.clear_field_bit(idx, DataInputsBit::GATESTATUS); // Mark the gate status field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
+    /// Sets the `step_count` field in the currently updating buffer.
+    pub fn set_step_count(&mut self, count: u16) {
+        let idx = self.idx2update; // Get the currently updating buffer index
+        self.buffers[idx].step_count = count; // Store the step pulse counter
+        self.clear_field_bit(idx, DataInputsBit::STEPCOUNT); // Mark the step counter field as filled
+        self.check_fill(idx); // Check if buffer filling is complete or if we need to switch
+    }
+
     /// Checks if the data has been updated since the last read.
     #[inline(always)]
     pub fn is_updated(&self) -> bool {
@@ -0,0 +1,252 @@
+// Implements kinematic transforms for multi-axis machines where motors don't map one-to-one to
+// cartesian axes (CoreXY, H-bot) and velocity mixing for differential-drive mobile robots.
+
+// Key Features:
+// - Forward transform: cartesian X/Y target into A/B motor targets.
+// - Inverse transform: A/B motor feedback into cartesian X/Y position and velocity.
+// - Direction of each motor configurable to match the belt routing.
+// - Differential-drive / skid-steer mixing of linear and angular velocity into wheel setpoints.
+// - Per-wheel velocity limits with saturation-aware scaling that preserves the path curvature.
+// - Wheel odometry: multi-turn wheel positions into pose (x, y, heading) at a configurable rate.
+// - Integer-only, multi-turn safe (64-bit intermediates).
+
+// Detailed Operation:
+// On CoreXY and H-bot frames both motors drive one belt system: A = X + Y and B = X - Y, so
+// X = (A + B) / 2 and Y = (A - B) / 2. The transform is linear, therefore a straight line in the
+// motor space is also a straight line in X/Y and synchronized motor profiles (both axes starting
+// and finishing together) give a correct coordinated cartesian move. Motor inversion flags swap
+// the sign of the corresponding motor coordinate for frames where a motor is mounted mirrored.
+// A differential drive turns a linear velocity v and an angular velocity ω into wheel velocities
+// v ∓ ω * track / 2. If a wheel exceeds its limit, both wheels are scaled by the same factor, so the
+// robot slows down but keeps the commanded turning radius. Skid-steer platforms use the same mixing
+// with an effective track width (larger than the geometric one, accounts for wheel slip).
+// Odometry runs the inverse: every `interval` ticks the wheel travel since the last update gives the
+// travelled distance (dl + dr) / 2 and the heading change (dr - dl) / track. The displacement is
+// applied along the heading in the middle of the interval (second order integration), which keeps
+// the error of arcs small. Heading is kept with 2^32 steps per turn so small increments never get
+// lost, positions are accumulated in µm.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::trigonometry::angle2sincos_interp;
+
+/// CoreXY / H-bot transform between cartesian X/Y and A/B motor coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct CoreXY {
+    sign_a: i64, // Direction of motor A (1 or -1)
+    sign_b: i64, // Direction of motor B (1 or -1)
+}
+
+impl CoreXY {
+    /// Creates a transform.
+    ///
+    /// # Arguments
+    /// * `invert_a` - Motor A counts opposite to the standard belt routing
+    /// * `invert_b` - Motor B counts opposite to the standard belt routing
+    pub const fn new(invert_a: bool, invert_b: bool) -> Self {
+        Self {
+            sign_a: if invert_a { -1 } else { 1 },
+            sign_b: if invert_b { -1 } else { 1 },
+        }
+    }
+
+    /// Converts a cartesian position (or velocity) into motor coordinates (counts).
+    pub const fn to_motors(&self, x: i32, y: i32) -> (i32, i32) {
+        let a = (x as i64 + y as i64) * self.sign_a;
+        let b = (x as i64 - y as i64) * self.sign_b;
+        (a as i32, b as i32)
+    }
+
+    /// Converts motor coordinates (counts) into a cartesian position (or velocity).
+    pub const fn to_cartesian(&self, a: i32, b: i32) -> (i32, i32) {
+        let a = a as i64 * self.sign_a;
+        let b = b as i64 * self.sign_b;
+        (((a + b) / 2) as i32, ((a - b) / 2) as i32)
+    }
+}
+
+impl Default for CoreXY {
+    fn default() -> Self {
+        Self::new(false, false)
+    }
+}
+
+/// Differential-drive / skid-steer velocity mixing.
+#[derive(Debug, Clone, Copy)]
+pub struct DiffDrive {
+    counts_per_m: i32,  // Wheel encoder counts per meter of travel
+    track_mm: i32,      // (Effective) distance between the left and right wheels (mm)
+    limits: (i32, i32), // Maximum velocity of the left and right wheel (counts/s)
+}
+
+impl DiffDrive {
+    /// Creates a mixer.
+    ///
+    /// # Arguments
+    /// * `counts_per_m` - Wheel encoder counts per meter of travel
+    /// * `track_mm` - Distance between wheels (effective track width for skid-steer, mm)
+    /// * `max_velocity` - Maximum velocity of each wheel (counts/s)
+    pub const fn new(counts_per_m: i32, track_mm: i32, max_velocity: i32) -> Self {
+        Self {
+            counts_per_m,
+            track_mm: if track_mm <= 0 { 1 } else { track_mm },
+            limits: (max_velocity, max_velocity),
+        }
+    }
+
+    /// Sets individual maximum velocities of the left and right wheel (counts/s).
+    pub fn set_limits(&mut self, left: i32, right: i32) {
+        self.limits = (left.saturating_abs(), right.saturating_abs());
+    }
+
+    /// Converts body velocities into wheel velocity setpoints.
+    ///
+    /// # Arguments
+    /// * `linear` - Forward velocity (mm/s)
+    /// * `angular` - Counter-clockwise angular velocity (mrad/s)
+    ///
+    /// Returns (left, right) wheel velocities in counts/s, scaled down together if a limit is hit.
+    pub fn mix(&self, linear: i32, angular: i32) -> (i32, i32) {
+        // mrad/s * mm / 2 = µm/s of wheel speed difference
+        let turn_um = angular as i64 * self.track_mm as i64 / 2;
+        let left_um = linear as i64 * 1000 - turn_um;
+        let right_um = linear as i64 * 1000 + turn_um;
+        let left = left_um * self.counts_per_m as i64 / 1_000_000;
+        let right = right_um * self.counts_per_m as i64 / 1_000_000;
+
+        // Common scale keeps the ratio between wheels and therefore the turning radius
+        let limit_left = self.limits.0 as i64;
+        let limit_right = self.limits.1 as i64;
+        let mut num = 1i64;
+        let mut den = 1i64;
+        if left.abs() > limit_left {
+            (num, den) = (limit_left, left.abs());
+        }
+        if right.abs() > limit_right && limit_right * den < num * right.abs() {
+            (num, den) = (limit_right, right.abs());
+        }
+        ((left * num / den) as i32, (right * num / den) as i32)
+    }
+
+    /// Converts wheel velocities (counts/s) back into body velocities (mm/s, mrad/s).
+    pub fn unmix(&self, left: i32, right: i32) -> (i32, i32) {
+        let counts_per_m = self.counts_per_m.max(1) as i64;
+        let left_um = left as i64 * 1_000_000 / counts_per_m;
+        let right_um = right as i64 * 1_000_000 / counts_per_m;
+        let linear = (left_um + right_um) / 2000;
+        let angular = (right_um - left_um) / self.track_mm as i64;
+        (linear as i32, angular as i32)
+    }
+
+    /// Returns wheel encoder counts per meter of travel.
+    pub const fn counts_per_m(&self) -> i32 {
+        self.counts_per_m
+    }
+
+    /// Returns the (effective) track width (mm).
+    pub const fn track_mm(&self) -> i32 {
+        self.track_mm
+    }
+}
+
+/// Pose of a differential-drive robot integrated from wheel positions.
+pub struct Odometry {
+    drive: DiffDrive, // Wheel geometry
+    interval: u16,    // Ticks between pose updates
+    counter: u16,     // Ticks since the last update
+    last: (i32, i32), // Wheel positions at the last update (counts)
+
+    x_um: i64,    // X position (µm)
+    y_um: i64,    // Y position (µm)
+    heading: u32, // Heading, counter-clockwise from X (2^32 per turn)
+
+    increment: (i32, i32, i32), // Last update: dx (µm), dy (µm), dheading (65536 per turn)
+}
+
+impl Odometry {
+    /// Creates odometry at the origin.
+    ///
+    /// # Arguments
+    /// * `drive` - Wheel geometry (counts per meter and track width)
+    /// * `interval` - Number of ticks between pose updates
+    /// * `left`, `right` - Current wheel positions (counts)
+    pub fn new(drive: DiffDrive, interval: u16, left: i32, right: i32) -> Self {
+        Self {
+            drive,
+            interval: interval.max(1),
+            counter: 0,
+            last: (left, right),
+            x_um: 0,
+            y_um: 0,
+            heading: 0,
+            increment: (0, 0, 0),
+        }
+    }
+
+    /// Accumulates wheel positions, updating the pose every `interval` ticks.
+    ///
+    /// # Arguments
+    /// * `left`, `right` - Multi-turn wheel positions (counts, wrapping is allowed)
+    ///
+    /// Returns true if the pose was updated on this tick.
+    pub fn tick(&mut self, left: i32, right: i32) -> bool {
+        self.counter += 1;
+        if self.counter < self.interval {
+            return false;
+        }
+        self.counter = 0;
+
+        // ######################## WHEEL TRAVEL #####################################
+        let counts_per_m = self.drive.counts_per_m().max(1) as i64;
+        let left_um = left.wrapping_sub(self.last.0) as i64 * 1_000_000 / counts_per_m;
+        let right_um = right.wrapping_sub(self.last.1) as i64 * 1_000_000 / counts_per_m;
+        self.last = (left, right);
+
+        // ######################## POSE UPDATE ######################################
+        // dθ [turns] = (dr - dl) / (2π * track), scaled to 2^32 per turn
+        let diff = (right_um - left_um).clamp(-(1 << 20), 1 << 20);
+        let d_heading = (diff << 32) * 1000 / (self.drive.track_mm() as i64 * 6_283_185);
+        let distance = (left_um + right_um) / 2;
+
+        let mid = self.heading.wrapping_add((d_heading / 2) as u32);
+        let (sin, cos) = angle2sincos_interp((mid >> 16) as u16 as i16);
+        let dx = (distance * cos as i64 + (1 << 14)) >> 15;
+        let dy = (distance * sin as i64 + (1 << 14)) >> 15;
+
+        self.x_um += dx;
+        self.y_um += dy;
+        self.heading = self.heading.wrapping_add(d_heading as u32);
+        self.increment = (dx as i32, dy as i32, (d_heading >> 16) as i32);
+        true
+    }
+
+    /// Sets the pose (µm, µm, 65536 per turn) keeping the wheel reference.
+    pub fn set_pose(&mut self, x_um: i64, y_um: i64, heading: u16) {
+        self.x_um = x_um;
+        self.y_um = y_um;
+        self.heading = (heading as u32) << 16;
+    }
+
+    /// Restarts accumulation from the given wheel positions without a pose jump.
+    pub fn reset(&mut self, left: i32, right: i32) {
+        self.last = (left, right);
+        self.counter = 0;
+        self.increment = (0, 0, 0);
+    }
+
+    /// Returns the pose: x (µm), y (µm), heading (65536 per turn).
+    pub fn pose(&self) -> (i64, i64, u16) {
+        (self.x_um, self.y_um, (self.heading >> 16) as u16)
+    }
+
+    /// Returns the heading with full resolution (2^32 per turn).
+    pub fn heading_fine(&self) -> u32 {
+        self.heading
+    }
+
+    /// Returns the last pose increment: dx (µm), dy (µm), dheading (65536 per turn).
+    pub fn increment(&self) -> (i32, i32, i32) {
+        self.increment
+    }
+}
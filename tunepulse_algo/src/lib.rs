@@ -2,40 +2,172 @@
 
 pub mod inputs_dump;
 use inputs_dump::DataInputs;
+use params::storage::{LoadReport, StorageError, StorageWrite};
+use params::ParamError;
 
-pub mod math_integer;
+pub use tunepulse_math as math_integer; // Math layer, also usable as a standalone crate
 pub mod motor_driver;
 
 pub mod analog;
 
+pub mod kinematics;
+pub mod motor_bank;
+pub mod params;
+pub mod protocol;
+
+#[cfg(all(feature = "std", not(target_os = "none")))]
+extern crate std;
+
 use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
 
+// Host test runs log without a probe: std critical section, no timestamp
+#[cfg(all(test, not(target_os = "none")))]
+use critical_section as _;
+#[cfg(all(test, not(target_os = "none")))]
+defmt::timestamp!("");
+
+use motor_driver::field_weakening;
 use motor_driver::{
-    AngleCalibrator, ControlMode, DriverPWM, DriverStatus, Motor, MotorDriver, MotorType,
-    PhasePattern,
+    AngleCalibrator, Autotune, Backlash, BacklashConfig, CalProgress, CalStage, CalibrationConfig, Beeper, AutotuneConfig, AutotuneResult, AutotuneStage, BurstConfig, BurstReport, BurstTorque, BusPower, BusReport, FieldWeakening,
+    FieldWeakeningConfig, GateAction, GateFault, GateFaultConfig, GateFaultReport, HapticConfig, Haptics, Mtpa, MtpaConfig, DriveHealth, HealthConfig, HealthReport,
+    HealthSample, HoldCurrent, HoldCurrentConfig, Shadow, ShadowMode, ShadowReport, Cascade, CascadeMode, CommutationTrim, ControlMode, DecayMode, DriverPWM, DriverStatus,
+    BalanceReport, CurrentLoopGains, DualBridge, DualEncoder, DualEncoderConfig, DualEncoderReport, EncoderBackup, EncoderSupervisor,
+    EncoderSupervisorConfig, EncoderSupervisorReport, Homing, HomingConfig, HomingStage, IndexEvent,
+    IndexLatch, Capture, PositionCapture,
+    IdentConfig, IdentStage, kt_from_inertia, KtIdent, KtIdentConfig, KtIdentStage, KtReport, MechIdent, MechanicsReport, Motor,
+    MotorDriver, MotorType,
+    PhaseBalance, PhaseCheck, PhaseCheckReport, PhaseCheckStage, PhasePattern, QuickAlign, QuickAlignStage, SafeParams, SafeStop, SafeParamsConfig, Sensorless, SensorlessConfig,
+    SensorlessReport, SoftStart, SoftStartConfig,
+    SoftStartStage, SpeedLimit, SpeedLimitConfig, Standby, StandbyConfig, StandbyStage, StepDir, StepDirConfig, MotionSource, StopMode, StopStage, TickTimer, TickTiming, TravelLimits, TuningSet,
+    TrimReport, VelocitySource, Watchdog, WindingTemp, WindingTempConfig, WatchdogConfig, WatchdogTrip, WiringCheck, WiringReport, WiringStage,
 };
 
-use crate::math_integer::filters::lpf::FilterLPF;
+use crate::math_integer::angle::Angle16;
+use crate::math_integer::motor::coil;
+use crate::math_integer::{sqrt, transforms, trigonometry};
+use crate::math_integer::filters::median::FilterMedian;
+use crate::math_integer::filters::slew::SlewLimiter;
+use crate::math_integer::motion::gearing::ElectronicGear;
+use crate::math_integer::motion::interpolator::AngleInterpolator;
+use crate::math_integer::motion::angle_predictor::AnglePredictor;
+use crate::math_integer::motion::linear_scale::LinearScale;
+use crate::math_integer::motion::physical_units::{PhysicalUnits, PhysicalUnitsConfig};
+use crate::math_integer::motion::unit_scale::UnitScale;
+use crate::math_integer::motion::observer::LuenbergerObserver;
+use crate::math_integer::motion::pll::TrackingPLL;
 use crate::math_integer::motion::position_integrator::Position;
+use crate::math_integer::motion::profile::TrapezoidalProfile;
+use crate::math_integer::motion::pvt::{PvtInterpolation, PvtSegment, PvtStream};
+use crate::math_integer::motion::input_shaper::{InputShaper, ShaperType};
+use crate::math_integer::motion::scurve::{JerkLimiter, ProfileShape};
+
+use motor_driver::calibration::config::{CAL_ALIGNMENT, CAL_CURRENT_SENSE, CAL_PHASES, CAL_WIRING};
 
+use analog::current_calibration::{CurrentCalConfig, CurrentCalReport, CurrentCalStage, CurrentCalibration};
+use analog::current_sense::CurrentSense;
+use analog::energy_meter::{EnergyMeter, EnergyReport};
 use analog::supply_voltage::SupplyVoltage;
 
 /// The main driver struct for the motor, holding all the state required for operation and calibration.
 pub struct MotorController {
-    motor: DriverPWM,   // Motor interface using PWM signals for control
-    frequency: u16,     // Update frequency (ticks per second)
-    position: Position, // Current encoder position reading
+    motor: DriverPWM,      // Motor interface using PWM signals for control
+    motor_type: MotorType, // Motor type, selects how the torque command is commutated
+    resistance: i32,       // Winding resistance (mOhm)
+    inductance: i32,       // Winding inductance (uH), 0 - unknown
+    frequency: u16,        // Update frequency (ticks per second)
+    pwm_frequency: u32,    // PWM switching frequency (Hz), a multiple of the update frequency
+    pwm_period: u16,       // Timer counts of 100% duty, 0 - Q15 duties
+    min_off_ns: u32,       // Minimum low-side on-time of every PWM period (ns)
+    min_pulse_ns: u32,     // Shortest pulse the gate driver switches (ns)
+    timer: TickTimer,      // Measured tick period and jitter
+    watchdog: Watchdog,    // Tick stall and setpoint timeout supervision
+    safe_stop: SafeStop,   // Requested coast / brake / ramp stop
+    stop_mode: StopMode,   // Stop behavior selected for `stop` and the e-stop
+    estop: bool,           // Emergency stop latched until `reset_estop`
+    estop_input: bool,     // E-stop input of the last tick
+    speed_limit: SpeedLimit, // Maximum mechanical speed and overspeed fault
+    vel_limit: i32,        // Velocity command limit of the tuning, before the speed limit (counts/s)
+    standby: Standby,      // Reduced current and sleep of an idle axis
+    hold: HoldCurrent,     // Standstill current reduction
+    gate: GateFault,       // Gate driver fault line, desaturation and retries
+    winding: WindingTemp,  // Winding temperature from the resistance rise
+    kt_ident: KtIdent,     // Back-EMF based torque constant identification
+    position: Position,    // Current encoder position reading
+    supervisor: EncoderSupervisor, // Validation of the raw encoder samples
+    glitch: FilterMedian,  // Median filter of the raw encoder angle (bypassed by default)
+    index: IndexLatch,     // Position latch on the encoder index edge
+    capture: PositionCapture, // Position snapshots on external trigger edges
+    load: DualEncoder,     // Load-side encoder closing the position loop
+    linear: LinearScale,   // Linear encoder resolution for micrometer reporting
+    units: UnitScale,      // Gear ratio and user units of the load
+    physical: PhysicalUnits, // Torque constant, gear and lead for physical unit setpoints
 
     driver_status: DriverStatus, // Current motor status (Calibrating, Ready, or Error)
 
     angle_el: u16,  // Electrical angle of the motor (0..65535), used to control phase
+    rotor_el: u16,  // Measured electrical angle of the rotor in the last normal operation tick
     amplitude: i16, // Amplitude (voltage magnitude) used during calibration
+    amplitude_slew: SlewLimiter, // Ramps amplitude steps to avoid audible clicks
     direction: i16, // Current rotation direction (1 for forward, -1 for backward)
+    encoder_inverted: bool, // Encoder input is mirrored to count along the electrical rotation
     speed: i16,     // Speed (steps per tick) during calibration
 
     angle_calibrator: AngleCalibrator,
-    filter: FilterLPF,
+    quick_align: QuickAlign, // Offset search replacing the full sweep when pole pairs are known
+    wiring: WiringCheck,     // Coil pairing detection of steppers before the angle calibration
+    connection: PhasePattern, // Phase pattern applied to the outputs
+    phase_check: PhaseCheck, // Open / shorted winding diagnosis before the angle calibration
+    cal_config: CalibrationConfig, // Stages run by the (re)calibration
+    cal_paused: bool,              // Calibration holds with the outputs off
+    trim: CommutationTrim,
+    cascade: Cascade,
+    backlash: Backlash, // Backlash compensation of the position feedback
+    profile: TrapezoidalProfile,
+    profile_limits: (i32, i32, i32), // Requested move limits (vel_max, accel, decel) before the speed limit
+    scurve: JerkLimiter,
+    shape: ProfileShape, // Shape of the running point-to-point move
+    shaper: InputShaper, // Vibration suppression of moves and streamed paths
+    haptics: Haptics,    // Torque synthesis of a haptic knob
+    stream: PvtStream,
+    gear: ElectronicGear,
+    master_position: i32, // Position of the master axis followed by the gear (counts)
+    step_dir: StepDir,
+    motion_source: MotionSource, // Source of the position setpoint selected by the application
+    step_engaged: bool,          // The gear follows the step input
+    coupling_gain: i32,   // Cross-feed gain from the coupled axis (mA per rev/s^2)
+    coupling_accel: i32,  // Acceleration of the coupled axis (counts/s^2)
+    limits: TravelLimits,
+    bridges: DualBridge,
+    homing: Homing,
+    autotune: Autotune, // Relay experiment proposing velocity loop gains
+    ident: MechIdent,   // Torque profile fitting inertia and friction
+    backup: EncoderBackup,
+    soft_start: SoftStart,
+    safe_params: SafeParams, // Fallback to the last known good tuning
+    health: DriveHealth,     // Aggregated health score
+    shadow: Shadow,          // Dry-run / pass-through of the controller output
+    burst: BurstTorque, // Duty-cycle budget for currents above the continuous limit
+    field_weakening: FieldWeakening, // Negative d-axis current above base speed
+    mtpa: Mtpa,                      // Reluctance torque distribution of salient motors
+    sensorless: Sensorless,          // HFI / back-EMF rotor angle instead of the encoder
+    balance: PhaseBalance,
+    interpolator: AngleInterpolator, // Sub-code commutation angle of low-resolution encoders
+    predictor: AnglePredictor, // Commutation angle between samples of a slower encoder
+    async_encoder: bool,       // Encoder samples slower than the control loop (see angle_count)
+    angle_count: u8,           // Encoder sample counter of the previous tick
+    tracker: TrackingPLL, // Encoder angle tracker used for commutation
+    observer: LuenbergerObserver,
+    velocity_source: VelocitySource, // Velocity feedback of the cascade
     supply: SupplyVoltage,
+    current_sense: CurrentSense,
+    current_cal: CurrentCalibration, // Startup offset and gain calibration of the current channels
+    current_scale: i32,  // Phase current scale (uA per current sense LSB), 0 - not calibrated
+    current_gains: CurrentLoopGains, // Current-loop PI gains from the winding R / L
+    bus: BusPower,       // DC-bus current and input power estimate
+    energy: EnergyMeter, // Consumed and regenerated energy / charge
+    last_position: i32, // Encoder position of the previous tick, used for standstill detection
+    outputs_off: bool,  // All outputs were zero in the previous tick
+    command: (u16, i16), // Last (electrical angle, amplitude) sent to the PWM stage
     ticker: i32,
     sup_check: usize,
 }
@@ -58,26 +190,112 @@ impl MotorController {
         let mut motor = Motor::new(resistance);
         motor.pole_type = motor_type;
         motor.connection = connection;
-        let control_mode = ControlMode::CurrentAB;
+        let control_mode = Self::control_mode_for(motor_type);
+        let mut pwm = DriverPWM::new(motor, control_mode);
+        pwm.beeper().set_frequency(frequency);
 
         Self {
-            motor: DriverPWM::new(motor, control_mode), // Initialize MotorPWM with given type and phase connection
+            motor: pwm,                                 // PWM driver with given type and phase connection
+            motor_type,                                 // Store the motor type
+            resistance,                                 // Store the winding resistance
+            inductance: 0,                              // Unknown until configured
             frequency,                                  // Store the update frequency
+            pwm_frequency: frequency as u32,            // One control tick per PWM period
+            pwm_period: 0,                              // Q15 duties until a timer period is set
+            min_off_ns: 0,                              // Full duty range
+            min_pulse_ns: 0,                            // Any pulse width
+            timer: TickTimer::new(),                    // Nominal periods until a clock is set
+            watchdog: Watchdog::new(frequency),         // Both checks off until configured
+            safe_stop: SafeStop::new(frequency),        // Running, no stop requested
+            stop_mode: StopMode::Ramp,                  // Controlled stop by default
+            estop: false,                               // Not latched
+            estop_input: false,                         // Circuit closed
+            speed_limit: SpeedLimit::new(frequency),    // No limit until configured
+            vel_limit: (i16::MAX as i32) << 4,          // Full range of the cascade
+            standby: Standby::new(frequency),           // Active
+            hold: HoldCurrent::new(frequency),          // No reduction until configured
+            gate: GateFault::new(frequency),            // Default retry policy
+            winding: WindingTemp::new(frequency),       // Off until configured
+            kt_ident: KtIdent::new(frequency),          // Idle
             position: Position::new(),                  // Initialize encoder position to 0
+            supervisor: EncoderSupervisor::new(frequency), // All checks off until configured
+            glitch: FilterMedian::new(0, 1),            // No glitch filtering until enabled
+            index: IndexLatch::new(),                   // No index edge seen yet
+            capture: PositionCapture::new(),            // Armed, empty FIFO
+            load: DualEncoder::new(frequency),          // Motor encoder only until configured
+            linear: LinearScale::new(1000),             // 1 µm per count until configured
+            units: UnitScale::new(),                    // Counts until configured
+            physical: PhysicalUnits::new(),             // Direct drive, Kt unknown
 
             driver_status: DriverStatus::Calibrating, // Start in Calibrating mode
 
             angle_el: 0, // Initial electrical angle is 0
+            rotor_el: 0,
 
             amplitude: 0,
+            amplitude_slew: SlewLimiter::new(frequency, 2_000_000, i32::MAX), // 2 A/ms up, instant off
 
             direction: 0, // No direction initially
+            encoder_inverted: false,
             speed: 0,     // Use the predefined calibration speed
 
             angle_calibrator: AngleCalibrator::new(frequency),
-            filter: FilterLPF::new(0, 0),
+            quick_align: QuickAlign::new(frequency),
+            wiring: WiringCheck::new(frequency),
+            connection,
+            phase_check: PhaseCheck::new(frequency),
+            cal_config: CalibrationConfig::new(),
+            cal_paused: false,
+            trim: CommutationTrim::new(),
+            cascade: Cascade::new(frequency),
+            backlash: Backlash::new(frequency),
+            profile: TrapezoidalProfile::new(frequency),
+            profile_limits: (1 << 16, 1 << 18, 1 << 18), // Defaults of the profile
+            scurve: JerkLimiter::new(frequency),
+            shape: ProfileShape::Trapezoidal,
+            shaper: InputShaper::new(frequency),
+            haptics: Haptics::new(),
+            stream: PvtStream::new(frequency),
+            gear: ElectronicGear::new(),
+            master_position: 0,
+            step_dir: StepDir::new(),
+            motion_source: MotionSource::Internal,
+            step_engaged: false,
+            coupling_gain: 0,
+            coupling_accel: 0,
+            limits: TravelLimits::new(),
+            bridges: DualBridge::new(resistance),
+            homing: Homing::new(),
+            autotune: Autotune::new(),
+            ident: MechIdent::new(frequency),
+            backup: EncoderBackup::new(),
+            soft_start: SoftStart::new(),
+            safe_params: SafeParams::new(),
+            health: DriveHealth::new(),
+            shadow: Shadow::new(frequency),
+            burst: BurstTorque::new(),
+            field_weakening: FieldWeakening::new(),
+            mtpa: Mtpa::new(),
+            sensorless: Sensorless::new(frequency),
+            balance: PhaseBalance::new(frequency),
+            interpolator: AngleInterpolator::new(),
+            predictor: AnglePredictor::new(),
+            async_encoder: false,
+            angle_count: 0,
+            tracker: TrackingPLL::new(frequency, 1000),
+            observer: LuenbergerObserver::new(frequency, 200),
+            velocity_source: VelocitySource::Differentiator,
 
-            supply: SupplyVoltage::new(200, max_sup_voltage),
+            supply: SupplyVoltage::new(max_sup_voltage),
+            current_sense: CurrentSense::new(frequency / 100), // 10 ms for the winding current to decay
+            current_cal: CurrentCalibration::new(frequency),
+            current_scale: 0,
+            current_gains: CurrentLoopGains::default(),
+            bus: BusPower::new(),
+            energy: EnergyMeter::new(frequency),
+            last_position: 0,
+            outputs_off: false,
+            command: (0, 0),
             ticker: 0,
             sup_check: 100,
         }
@@ -86,29 +304,296 @@ impl MotorController {
     /// Main update method.
     ///
     /// # Arguments
-    /// * `voltg_angle` - (angle, amplitude) tuple for normal operation
-    /// * `encoder_pos` - current encoder position from the sensor
+    /// * `current` - calibration current and current limit of the control cascade (mA)
+    /// * `input` - sensor data snapshot (encoder angle, supply voltage, etc.)
     ///
     /// This method decides whether to run normal operation or calibration logic based on the motor status.
+    ///
+    /// Safety checks run before any setpoint source, in order of precedence:
+    /// 1. Setpoint watchdog: stops with its configured action, faults unless ramping down.
+    /// 2. Emergency stop input: latches and stops with the selected stop mode.
+    /// 3. Gate driver fault: cancels motion, homing and experiments, faults; a retry resets the fault.
+    /// 4. Encoder supervisor, load encoder mismatch, speed limit, supply and winding temperature: fault.
+    ///
+    /// A fault sets the status to `Error`, so the status match drives nothing. While ready, homing owns the
+    /// motor, else an active stop overrides haptics, step/dir, gearing, streams and point-to-point moves,
+    /// and auto-tuning and identification replace the torque setpoint. The standby sleep and the stop
+    /// outputs (coast, brake) replace the PWM last.
     pub fn tick(&mut self, current: i32, input: DataInputs) -> [i16; 4] {
-        self.position.tick(input.angle_raw); // Update the internal position from the sensor
+        if self.watchdog.tick() {
+            // Host stopped sending setpoints: stop in the safe state until the fault is reset
+            let action = self.watchdog.config().action;
+            self.request_stop(action);
+            if action != StopMode::Ramp {
+                self.driver_status = DriverStatus::Error; // A ramp faults once the axis is at rest
+            }
+        }
+        self.estop_input = input.estop;
+        if input.estop && !self.estop {
+            // Machine safety: latched apart from the faults, only `reset_estop` lets the axis move again
+            self.estop = true;
+            defmt::error!("ESTOP: Emergency stop");
+        }
+        if self.estop && !self.safe_stop.is_active() {
+            self.request_stop(self.stop_mode);
+        }
+        match self.gate.tick(input.gate_status) {
+            GateAction::Trip => {
+                // Gate driver stopped switching or a phase is shorted: nothing may be driven, and an
+                // automatic retry must not resume what was interrupted
+                self.cancel_motion();
+                self.driver_status = DriverStatus::Error;
+            }
+            GateAction::Retry if !self.other_fault_latched() => {
+                self.reset_fault();
+            }
+            _ => {}
+        }
+        let period = self.timer.tick(input.timestamp);
+        self.cascade.set_period(period); // Integrators and velocity follow the measured tick time
+        let fresh_angle = input.angle_count != self.angle_count; // New encoder sample since the last tick
+        self.angle_count = input.angle_count;
+        let (angle_raw, encoder_fault) =
+            self.supervisor.tick(input.angle_raw, input.encoder_status, self.cascade.current());
+        if encoder_fault {
+            // The last good angle is held, nothing may be commutated on it any more
+            self.driver_status = DriverStatus::Error;
+        }
+        let angle_raw = self.glitch.tick(angle_raw); // Drop single-sample encoder glitches
+        let angle_raw = if self.encoder_inverted { angle_raw.wrapping_neg() } else { angle_raw };
+        self.position.tick(angle_raw); // Update the internal position from the sensor
+        let index_angle = if self.encoder_inverted { input.index_angle.wrapping_neg() } else { input.index_angle };
+        if self.index.tick(input.index_count, index_angle, &mut self.position) == IndexEvent::Rezeroed {
+            // Index homing: the zero point is valid again, loops continue bumpless
+            self.backup.acknowledge();
+            self.stop_trajectory();
+            self.cascade.reset(self.loop_position());
+        }
+        let velocity = self.cascade.velocity();
+        self.capture.tick(input.capture_count, self.position.position_from_zero(), velocity);
+        let steps = self.step_dir.tick(input.step_count);
+        if self.motion_source == MotionSource::StepDir {
+            if self.driver_status != DriverStatus::Ready {
+                if self.step_engaged {
+                    self.stop_trajectory(); // Steps received while not driven must not move the axis later
+                }
+            } else if self.step_engaged {
+                self.master_position = steps;
+            } else if !self.safe_stop.is_active() && !self.homing.is_active() {
+                self.engage_step_dir();
+            }
+        }
+        if self.load.tick(input.load_angle, self.position.position_i64()) {
+            // Encoders disagree beyond the backlash: coupling slipped or an encoder miscounts
+            self.driver_status = DriverStatus::Error;
+        }
+        if self.speed_limit.tick(self.cascade.velocity()) {
+            // Runaway: wrong commutation, overhauling load or slipping encoder
+            self.stop_trajectory();
+            self.driver_status = DriverStatus::Error;
+        }
         let sup_adc = self.supply.tick(input.supply_adc).voltage_norm();
+
+        // No current can flow while the outputs are off and the rotor doesn't generate back-EMF
+        let moved = self.position.position().wrapping_sub(self.last_position);
+        self.last_position = self.position.position();
+        let idle = self.outputs_off && moved.abs() <= 1;
+        self.current_sense.tick(input.currnt_adc, idle);
+        let sensed = self.current_scale != 0 && self.motor_type.has_commutation();
+        if self.kt_ident.is_active() {
+            let (voltage, current) = self.phase_vectors();
+            let phases = if self.motor_type == MotorType::BLDC { 3 } else { 2 };
+            let velocity = self.cascade.velocity();
+            if self.kt_ident.tick(voltage, current, velocity, self.resistance, phases) {
+                self.cascade.set_velocity(0); // Spin finished, bring the axis to rest
+                if self.kt_ident.stage() == KtIdentStage::Done {
+                    self.physical.set_kt(self.kt_ident.report().kt);
+                }
+            }
+        }
+        if self.winding.is_enabled() && sensed && self.driver_status == DriverStatus::Ready {
+            let (voltage, current) = self.phase_vectors();
+            if self.winding.tick(voltage, current, self.cascade.velocity()) {
+                // Estimated winding temperature over its limit: let it cool down
+                self.stop_trajectory();
+                self.driver_status = DriverStatus::Error;
+            }
+        }
+
+        if self.backup.tick(input.encoder_status) {
+            // Position based motion can't continue on a stale absolute position: hold still
+            self.stop_trajectory();
+            self.cascade.set_velocity(0);
+        }
         self.amplitude = current as i16; // ma
                                          // let sup_adc = self.supply.voltage_norm();
         match self.driver_status {
+            DriverStatus::Ready if self.motor_type == MotorType::DUALDC => {
+                // Both bridges are driven directly, cascade and commutation are bypassed
+                self.bridges.tick(sup_adc, self.supply.max_voltage_mv());
+            }
             DriverStatus::Ready => {
                 self.ticker += 1;
 
                 // If calibration is complete, run normal operation logic
-                let filtered_pos = self.filter.tick(self.position.angle());
+                let angle = if self.async_encoder {
+                    self.predictor.tick(self.position.angle(), fresh_angle)
+                } else {
+                    self.position.angle()
+                };
+                let angle = self.interpolator.tick(angle);
+                let filtered_pos = self.tracker.tick(angle);
+
+                // Brushed motor or voice coil is never calibrated and doesn't need the rotor angle
+                let rotor_el = if !self.motor_type.has_commutation() {
+                    0
+                } else if self.sensorless.is_enabled() {
+                    let (voltage, current) = self.phase_vectors();
+                    self.sensorless.tick(voltage, current)
+                } else if self.quick_align.is_ready() {
+                    self.trim.apply(self.quick_align.rotor_el(filtered_pos))
+                } else {
+                    let rotor_el = self.angle_calibrator.get_correction(filtered_pos).1;
+                    self.trim.apply(rotor_el)
+                };
+                self.rotor_el = rotor_el;
 
-                self.angle_el = self.angle_calibrator.get_correction(filtered_pos).1;
+                if self.homing.is_active() {
+                    // Homing takes over the motor until it finishes or fails
+                    (self.angle_el, self.amplitude) = self.homing.tick(&mut self.position, rotor_el);
+                    if self.homing.stage() == HomingStage::Done {
+                        self.backup.acknowledge(); // Zero point is valid again
+                    }
+                    self.cascade.reset(self.loop_position()); // Keep loops bumpless
+                    self.stop_trajectory();
+                } else {
+                    // Haptic effects feed the torque setpoint, otherwise gearing, streamed path or
+                    // point-to-point move feeds the position loop
+                    if self.safe_stop.is_active() {
+                        // A stop request overrides every setpoint source until released
+                        let velocity = self.safe_stop.tick(self.cascade.velocity()).unwrap_or(0);
+                        self.cascade.set_velocity(velocity);
+                        if self.safe_stop.stage() == StopStage::Stopped && self.watchdog.is_tripped() {
+                            self.driver_status = DriverStatus::Error;
+                        }
+                    } else if self.haptics.is_active() {
+                        let torque = self.haptics.tick(self.loop_position(), self.cascade.velocity());
+                        self.cascade.set_torque(torque);
+                    } else if self.gear.is_engaged() {
+                        let setpoint = self.gear.tick(self.master_position);
+                        self.cascade.set_position(setpoint);
+                    } else if self.stream.is_active() {
+                        let setpoint = self.stream.tick();
+                        let setpoint = self.shaper.tick(setpoint, self.stream.velocity());
+                        self.cascade.set_position(setpoint);
+                    } else if self.profile_active() {
+                        let mut setpoint = self.profile.tick();
+                        let mut velocity = self.profile.velocity();
+                        if self.shape == ProfileShape::SCurve {
+                            // Averaging keeps running for a window after the trapezoid has finished
+                            setpoint = self.scurve.tick(setpoint, velocity);
+                            velocity = self.scurve.velocity();
+                        }
+                        let setpoint = self.shaper.tick(setpoint, velocity);
+                        self.cascade.set_position(setpoint);
+                    } else if self.shaper.is_active() {
+                        // Delayed impulses still arrive after the trajectory has finished
+                        let setpoint = self.shaper.tick(self.shaper.input(), 0);
+                        self.cascade.set_position(setpoint);
+                    }
+                    self.cascade.set_trajectory_feedforward(self.velocity_setpoint(), self.acceleration_setpoint());
+
+                    // Mechanically coupled axis (CoreXY, gantry) disturbs this one when it accelerates
+                    let coupling = (self.coupling_accel as i64 * self.coupling_gain as i64) >> 16;
+                    self.cascade.set_torque_feedforward(coupling as i32);
+
+                    let window = self.limits.tick(self.position.from_zero(), input.limit_sw);
+                    self.cascade.set_velocity_window(window);
+                    // After a fault reset the current limit is ramped while the axis is watched for sag
+                    let mut current_limit = self.burst.tick(self.cascade.current(), current);
+                    current_limit = self.winding.derate(current_limit); // Hot winding gets less current
+                    if self.soft_start.is_active() {
+                        current_limit = self.soft_start.tick(self.position.from_zero(), current_limit);
+                        if self.soft_start.stage() == SoftStartStage::Failed {
+                            self.driver_status = DriverStatus::Error;
+                        }
+                    }
+                    current_limit = self.standby.tick(current_limit); // Idle axis holds with less current
+                    let (setpoint, moving) = match self.cascade.mode() {
+                        CascadeMode::Position => (self.cascade.target_position(), false),
+                        CascadeMode::Velocity => (0, self.cascade.target_velocity() != 0),
+                        CascadeMode::Torque => (0, true), // The limit would change the torque itself
+                    };
+                    let moving = moving || self.is_moving() || self.stream.is_active() || self.gear.is_engaged();
+                    current_limit = self.hold.tick(setpoint, moving, self.cascade.velocity(), current_limit);
+                    if self.sensorless.is_enabled() && !self.sensorless.is_locked() {
+                        current_limit = 0; // Torque waits for the HFI polarity test
+                    }
+                    match self.velocity_source {
+                        VelocitySource::Differentiator => {}
+                        VelocitySource::Tracker => {
+                            self.cascade.set_measured_velocity(self.tracker.velocity());
+                        }
+                        VelocitySource::Observer => {
+                            // Raw position is used so that zeroing by homing doesn't disturb the estimate
+                            let current = self.cascade.current();
+                            let velocity = self.observer.tick(self.position.position(), current);
+                            self.cascade.set_measured_velocity(velocity);
+                        }
+                    }
+                    let motor = self.position.from_zero();
+                    let load = self.load.loop_position(motor);
+                    self.backlash.tick(self.cascade.target_position(), motor, load);
+                    let mut torque = self.cascade.tick(self.loop_position(), current_limit);
+                    if self.autotune.is_active() || self.ident.is_active() {
+                        // Experiment replaces the loop output, the axis is brought to rest afterwards
+                        let velocity = self.cascade.velocity();
+                        torque = if self.autotune.is_active() {
+                            self.autotune.tick(velocity, current_limit)
+                        } else {
+                            self.ident.tick(velocity, current_limit)
+                        };
+                        if !self.autotune.is_active() && !self.ident.is_active() {
+                            self.cascade.reset(self.loop_position());
+                            self.cascade.set_velocity(0);
+                        }
+                    }
+                    self.trim.tick(torque, self.cascade.velocity());
+                    if self.motor_type == MotorType::BLDC {
+                        self.balance.tick(self.current_sense.currents(), self.cascade.velocity());
+                    }
+                    // Salient motors get reluctance torque, above base speed the field is weakened,
+                    // the torque current gives way to keep the limit
+                    let (field, torque) = if self.motor_type.has_commutation() {
+                        let (mtpa_field, torque) = self.mtpa.split(torque);
+                        let velocity = self.cascade.velocity();
+                        let supply_mv = self.supply.voltage_mv();
+                        let weakening = self.field_weakening.tick(torque, velocity, self.resistance, supply_mv);
+                        let field = mtpa_field.saturating_add(weakening);
+                        (field.saturating_add(self.sensorless.injection()), torque)
+                    } else {
+                        (0, torque)
+                    };
+                    let torque = field_weakening::limit_torque(field, torque, current_limit);
+                    // Measured phase currents close the current loop, otherwise the driver applies V = I * R
+                    let (field, torque) = if self.current_scale != 0 {
+                        let measured = self.measured_currents(rotor_el);
+                        let supply_mv = self.supply.voltage_mv();
+                        self.cascade.regulate_current((field, torque), measured, self.resistance, supply_mv)
+                    } else {
+                        (field, torque)
+                    };
+                    (self.angle_el, self.amplitude) = self.commutate(rotor_el, field, torque);
+                }
             }
             DriverStatus::Error => {
                 // If in error state, stop driving the motor by setting amplitude to 0
                 self.amplitude = 0;
             }
             DriverStatus::Calibrating => {
+                if self.cal_config.current_ma > 0 {
+                    self.amplitude = self.amplitude.min(self.cal_config.current_ma);
+                }
                 if self.sup_check > 0 {
                     self.sup_check -= 1;
                     if self.sup_check == 0 {
@@ -121,34 +606,2307 @@ impl MotorController {
                         };
                     };
                 };
-                // If still calibrating, run the calibration logic
-                self.angle_el = self.angle_calibrator.tick(self.position.position());
-                if self.angle_calibrator.is_ready() {
-                    self.driver_status = DriverStatus::Ready
+                let current_cal_pending =
+                    matches!(self.current_cal.stage(), CurrentCalStage::Idle | CurrentCalStage::Failed);
+                if self.cal_config.runs(CAL_CURRENT_SENSE) && current_cal_pending {
+                    // Gain mismatch is only measurable with three phases sharing the current
+                    self.current_cal.start(self.motor_type == MotorType::BLDC);
+                }
+                if self.cal_paused {
+                    // Outputs off until resumed, the interrupted stage starts over
+                    self.amplitude = 0;
+                } else if self.current_cal.is_active() {
+                    // Current channels are calibrated before anything else drives the motor
+                    (self.angle_el, self.amplitude) = self.current_cal.tick(input.currnt_adc);
+                    match self.current_cal.stage() {
+                        CurrentCalStage::Done => {
+                            let report = self.current_cal.report();
+                            self.current_sense.set_offsets(report.offsets);
+                            self.current_sense.set_gains(report.gains);
+                        }
+                        CurrentCalStage::Failed => self.driver_status = DriverStatus::Error,
+                        _ => {}
+                    }
+                } else if !self.motor_type.has_commutation() || self.sensorless.is_enabled() {
+                    // Brushed motor or voice coil has no commutation, so there is nothing to calibrate,
+                    // sensorless commutation doesn't use the encoder
+                    self.enter_ready();
+                } else if self.motor_type == MotorType::STEP && self.cal_config.runs(CAL_WIRING) && !self.wiring.is_ready() {
+                    // Coil pairing is checked before anything relies on the rotation direction
+                    if self.wiring.stage() != WiringStage::Running {
+                        self.wiring.start(self.connection);
+                    }
+                    let connection;
+                    (connection, self.angle_el) = self.wiring.tick(self.current_sense.currents());
+                    if connection != self.connection {
+                        self.change_phase_mode(connection);
+                    }
+                    if self.wiring.stage() == WiringStage::Failed {
+                        self.driver_status = DriverStatus::Error;
+                    }
+                } else if self.cal_config.runs(CAL_PHASES) && !self.phase_check.is_ready() {
+                    // Windings are checked before the rotor is moved by the alignment
+                    if self.phase_check.stage() != PhaseCheckStage::Running {
+                        let amplitude = if self.current_scale != 0 {
+                            self.amplitude as i32 * 1000 / self.current_scale
+                        } else {
+                            0
+                        };
+                        self.phase_check.start(self.motor_type == MotorType::BLDC, amplitude);
+                    }
+                    self.angle_el = self.phase_check.tick(self.logical_currents());
+                    if self.phase_check.stage() == PhaseCheckStage::Failed {
+                        self.driver_status = DriverStatus::Error;
+                    }
+                } else if self.is_aligned() {
+                    // Alignment kept from the previous calibration
+                    self.enter_ready();
+                } else if self.quick_align.is_enabled() {
+                    // Few rotor locks instead of the full sweep, the encoder stays uncorrected
+                    self.angle_el = self.quick_align.tick(self.position.position());
+                    match self.quick_align.stage() {
+                        QuickAlignStage::Done => {
+                            if self.quick_align.result().1 < 0 {
+                                // Encoder counts backwards, mirror it along with the model
+                                self.invert_encoder();
+                                self.quick_align.invert();
+                            }
+                            self.enter_ready();
+                        }
+                        QuickAlignStage::Failed => self.driver_status = DriverStatus::Error,
+                        _ => {}
+                    }
+                } else {
+                    // If still calibrating, run the calibration logic
+                    self.angle_el = self.angle_calibrator.tick(self.position.position());
+                    if self.angle_calibrator.take_inversion() {
+                        self.invert_encoder();
+                    }
+                    if self.angle_calibrator.is_ready() {
+                        self.enter_ready();
+                    } else if self.angle_calibrator.stage() == CalStage::Error {
+                        self.driver_status = DriverStatus::Error;
+                    }
                 }
             }
         }
 
+        // A tuning change that made the axis unstable is rolled back
+        let faulted = self.driver_status == DriverStatus::Error;
+        if let Some(tuning) = self.safe_params.tick(self.cascade.current(), current, faulted) {
+            self.apply_tuning(tuning);
+        }
+
+        let following_error = match self.cascade.mode() {
+            CascadeMode::Position => self.cascade.target_position().wrapping_sub(self.loop_position()),
+            _ => 0,
+        };
+        self.health.tick(HealthSample {
+            temp_adc: input.temper_adc,
+            angle_raw: input.angle_raw,
+            current: self.cascade.current(),
+            current_limit: current,
+            following_error,
+            battery_low: self.backup.battery_low(),
+            rehome_required: self.backup.rehome_required(),
+        });
+
+        // Dual bridge mode runs the driver in voltage mode with a duty per channel
+        let control = match (self.motor_type, self.driver_status) {
+            (MotorType::DUALDC, DriverStatus::Ready) => self.bridges.duty(),
+            (MotorType::DUALDC, _) => (0, 0),
+            (_, DriverStatus::Error) => {
+                self.amplitude_slew.reset(0);
+                (self.angle_el as i16, 0)
+            }
+            (_, DriverStatus::Ready) if self.shadow.is_active() => {
+                // Controller output is only recorded, the motor is off or follows the external command
+                let command = (self.angle_el, self.amplitude);
+                let (angle, amplitude) = self.shadow.tick(command, self.cascade.current());
+                (angle as i16, self.amplitude_slew.tick_i16(amplitude))
+            }
+            _ => (self.angle_el as i16, self.amplitude_slew.tick_i16(self.amplitude)),
+        };
+
         // Compute the PWM signals based on the current angle_el and amplitude
-        self.motor
-            .tick_control((self.angle_el as i16, self.amplitude), sup_adc)
+        let pwm = self.motor.tick_control(control, sup_adc);
+        let pwm = if self.standby.is_sleeping() { StopMode::Coast.outputs() } else { pwm };
+        let pwm = self.safe_stop.outputs().unwrap_or(pwm); // Stopped: coast or brake pattern
+        self.command = (control.0 as u16, control.1);
+        self.outputs_off = pwm.iter().all(|&ch| ch == 0 || ch == i16::MIN);
+
+        // Measured phase currents give the exact bus current, otherwise the resistive model is used
+        let supply_mv = self.supply.voltage_mv();
+        if self.current_scale != 0 {
+            let scale = self.current_scale as i64;
+            let currents = self.current_sense.currents().map(|i| ((i as i64 * scale) / 1000) as i32);
+            self.bus.tick_measured(pwm, currents, supply_mv);
+        } else {
+            let phases = match self.motor_type {
+                MotorType::BLDC => 3,
+                MotorType::STEP => 2,
+                _ => 1,
+            };
+            let amplitude = if self.motor_type == MotorType::DUALDC { 0 } else { control.1 as i32 };
+            self.bus.tick_model(amplitude, self.resistance, phases, supply_mv);
+        }
+        let bus = self.bus.report();
+        self.energy.tick(bus.power_mw, bus.current_ma);
+        self.motor.to_timer(pwm)
+    }
+
+    /// Returns the phase currents in logical phase order, undoing the phase pattern of the outputs.
+    #[inline(always)]
+    fn logical_currents(&self) -> [i16; 4] {
+        let measured = self.current_sense.currents();
+        let mut logical = measured;
+        for (output, &current) in measured.iter().enumerate() {
+            logical[(self.connection as usize >> (2 * output)) & 0b11] = current;
+        }
+        logical
+    }
+
+    /// Returns the position closing the loop: the load encoder in motor counts if configured,
+    /// otherwise the motor encoder (relative to the zero point), backlash compensated.
+    #[inline(always)]
+    fn loop_position(&self) -> i32 {
+        let motor = self.position.from_zero();
+        self.backlash.feedback(motor, self.load.loop_position(motor))
+    }
+
+    /// Switch to normal operation with loops and the angle tracker locked onto the current position.
+    #[inline(always)]
+    fn enter_ready(&mut self) {
+        self.load.align();
+        self.cascade.reset(self.loop_position());
+        self.tracker.reset(self.position.angle());
+        self.observer.reset(self.position.position());
+        self.driver_status = DriverStatus::Ready;
+    }
+
+    /// Returns the stationary-frame (voltage in mV, current in mA) vectors of the three phases.
+    ///
+    /// The voltage is the resistive drop of the last command, which the driver applies as V = I * R.
+    #[inline(always)]
+    fn phase_vectors(&self) -> ((i32, i32), (i32, i32)) {
+        let voltage = self.command.1 as i64 * self.resistance as i64 / 1000;
+        let (sin, cos) = trigonometry::angle2sincos_interp(self.command.0 as i16);
+        let [sin, cos] = [sin, cos].map(|k| {
+            let mv = (voltage * k as i64) >> 15;
+            mv.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+        });
+        let voltage = (sin, cos);
+        let scale = self.current_scale as i64;
+        let [a, b, c, d] = self.current_sense.currents().map(|i| {
+            let ma = i as i64 * scale / 1000;
+            ma.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+        });
+        let (alpha, beta) = match self.motor_type {
+            // Each coil current is seen by the sense of both of its half bridges
+            MotorType::STEP => (coil::current::dual_bipolar(a, b), coil::current::dual_bipolar(c, d)),
+            _ => transforms::clarke(a, b, c),
+        };
+        (voltage, (alpha as i32, beta as i32))
+    }
+
+    /// Returns the measured (field, torque) currents (mA) in the frame of the rotor angle.
+    #[inline(always)]
+    fn measured_currents(&self, rotor_el: u16) -> (i32, i32) {
+        let (alpha, beta) = self.phase_vectors().1;
+        // Drive vectors are (sine, cosine) of their angle, so the cosine side is the in-phase axis
+        let (field, torque) = transforms::park(beta as i16, alpha as i16, Angle16(rotor_el).sincos());
+        (field as i32, torque as i32)
+    }
+
+    /// Converts the (field, torque) current pair into (electrical angle, amplitude).
+    ///
+    /// The torque current vector is placed 90° electrical ahead of or behind the rotor, a negative
+    /// field current turns it further away from the rotor (field weakening). DC motors have a
+    /// single coil and a zero rotor angle, so the vector sign alone selects the polarity
+    /// (for a voice coil the current is directly the force).
+    #[inline(always)]
+    fn commutate(&self, rotor_el: u16, field: i32, current: i32) -> (u16, i16) {
+        let rotor = Angle16(rotor_el);
+        if field != 0 {
+            let angle = rotor.add(Angle16(trigonometry::atan2(current, field)));
+            let amplitude = sqrt::magnitude(field, current).min(i16::MAX as u32);
+            return (angle.0, amplitude as i16);
+        }
+        let angle = if current >= 0 {
+            rotor.add(Angle16::QUARTER)
+        } else {
+            rotor.sub(Angle16::QUARTER)
+        };
+        (angle.0, current.unsigned_abs().min(i16::MAX as u32) as i16)
     }
 
     /// Change the motor type mode.
     #[inline(always)]
     pub fn change_motor_mode(&mut self, motor: MotorType) {
+        self.motor_type = motor;
         self.motor.change_motor_mode(motor); // Delegate to motor instance
+        self.motor.change_control_mode(Self::control_mode_for(motor));
     }
 
-    /// Change the phase pattern mode.
+    /// Select driver control mode: dual bridge needs raw duties, everything else uses current mode.
     #[inline(always)]
-    pub fn change_phase_mode(&mut self, connection: PhasePattern) {
-        self.motor.change_phase_mode(connection); // Delegate to motor instance
+    fn control_mode_for(motor: MotorType) -> ControlMode {
+        match motor {
+            MotorType::DUALDC => ControlMode::VoltageAB,
+            _ => ControlMode::CurrentAB,
+        }
     }
 
-    /// Get current PWM signals.
+    /// Get access to both channels of the dual H-bridge mode (setpoints and current limits).
     #[inline(always)]
-    pub fn get_pwm(&mut self) -> [i16; 4] {
-        self.motor.get_control()
+    pub fn bridges(&mut self) -> &mut DualBridge {
+        &mut self.bridges
+    }
+
+    /// Switch the cascade to torque mode with the given current setpoint (mA).
+    #[inline(always)]
+    pub fn set_torque(&mut self, current: i32) {
+        self.stop_trajectory();
+        self.cascade.set_torque(current);
+    }
+
+    /// Switch the cascade to velocity mode with the given setpoint (counts/s).
+    #[inline(always)]
+    pub fn set_velocity(&mut self, velocity: i32) {
+        self.stop_trajectory();
+        self.cascade.set_velocity(self.speed_limit.clamp(velocity));
+    }
+
+    /// Switch the cascade to position mode with the given setpoint (counts from zero).
+    ///
+    /// Ignored while homing is required (see `homing_required`).
+    #[inline(always)]
+    pub fn set_target_position(&mut self, position: i32) {
+        if !self.position_trusted() {
+            return;
+        }
+        self.stop_trajectory();
+        self.cascade.set_position(position);
+    }
+
+    /// Start a smooth point-to-point move to `target` (counts from zero) with a trapezoidal profile.
+    ///
+    /// A running move is retargeted on the fly, otherwise the move starts from the current
+    /// position setpoint (position mode) or from the measured position and velocity.
+    #[inline(always)]
+    pub fn move_to(&mut self, target: i32) {
+        self.move_to_shape(target, ProfileShape::Trapezoidal);
+    }
+
+    /// Start a point-to-point move to `target` (counts from zero) with the selected profile shape.
+    ///
+    /// A running move is retargeted on the fly and keeps its shape.
+    /// Ignored while homing is required (see `homing_required`).
+    pub fn move_to_shape(&mut self, target: i32, shape: ProfileShape) {
+        if !self.position_trusted() {
+            return;
+        }
+        self.haptics.stop();
+        self.stream.stop();
+        self.gear.disengage();
+        if self.profile_active() {
+            self.profile.retarget(target);
+            return;
+        }
+
+        let (position, velocity) = if self.shaper.is_active() {
+            // The shaper keeps its history, the new move continues from the end of the last one
+            (self.shaper.input(), 0)
+        } else {
+            let origin = self.setpoint_origin();
+            self.shaper.reset(origin.0);
+            origin
+        };
+
+        self.shape = shape;
+        let start = match shape {
+            ProfileShape::SCurve => self.scurve.start(position, velocity),
+            ProfileShape::Trapezoidal => {
+                self.scurve.stop();
+                position
+            }
+        };
+        self.profile.start(start, velocity, target);
+    }
+
+    /// Cancel every setpoint source along with homing and the experiments (auto-tuning, identification).
+    fn cancel_motion(&mut self) {
+        self.stop_trajectory();
+        if self.homing.is_active() {
+            self.homing.cancel();
+        }
+        self.autotune.cancel();
+        self.ident.cancel();
+        self.kt_ident.cancel();
+    }
+
+    /// Stop any point-to-point move, streamed path or gearing, the caller takes over the setpoint.
+    #[inline(always)]
+    fn stop_trajectory(&mut self) {
+        self.haptics.stop();
+        self.profile.stop();
+        self.scurve.stop();
+        if self.shaper.is_active() {
+            self.shaper.reset(self.shaper.position());
+        }
+        self.stream.stop();
+        self.gear.disengage();
+        if self.step_engaged {
+            // An internal command or a stop takes over from the step input
+            self.step_engaged = false;
+            self.motion_source = MotionSource::Internal;
+            defmt::info!("STEP/DIR: Released, internal motion source");
+        }
+    }
+
+    /// Check that absolute position commands can be accepted, warn otherwise.
+    #[inline(always)]
+    fn position_trusted(&self) -> bool {
+        if self.backup.rehome_required() {
+            defmt::warn!("ENCODER: Position command rejected, homing required");
+            return false;
+        }
+        true
+    }
+
+    /// Position and velocity a new trajectory starts from: the position setpoint in position mode,
+    /// otherwise the measured position and velocity.
+    #[inline(always)]
+    fn setpoint_origin(&self) -> (i32, i32) {
+        if self.cascade.mode() == CascadeMode::Position {
+            (self.cascade.target_position(), 0)
+        } else {
+            (self.loop_position(), self.cascade.velocity())
+        }
+    }
+
+    /// Start streaming a PVT path from the current position setpoint, dropping queued waypoints.
+    ///
+    /// Waypoints are queued with `push_waypoint`; the last one is held when the buffer runs empty.
+    /// Ignored while homing is required (see `homing_required`).
+    pub fn start_stream(&mut self, interpolation: PvtInterpolation) {
+        if !self.position_trusted() {
+            return;
+        }
+        let (position, _) = self.setpoint_origin();
+        self.stop_trajectory();
+        self.shaper.reset(position);
+        self.stream.set_interpolation(interpolation);
+        self.stream.start(position);
+        self.cascade.set_position(position);
+    }
+
+    /// Queue a PVT waypoint, returns false if the buffer is full or streaming is not started.
+    ///
+    /// # Arguments
+    /// * `position` - Position at the end of the segment (counts from zero)
+    /// * `velocity` - Velocity at the end of the segment (counts/s)
+    /// * `duration` - Duration of the segment (ticks)
+    pub fn push_waypoint(&mut self, position: i32, velocity: i32, duration: u32) -> bool {
+        self.stream.is_active()
+            && self.stream.push(PvtSegment {
+                position,
+                velocity,
+                duration,
+            })
+    }
+
+    /// Start following the master axis through the electronic gear.
+    ///
+    /// # Arguments
+    /// * `master` - Current master position (counts), updated later with `set_master_position`
+    /// * `num` - Gear ratio numerator (follower counts)
+    /// * `den` - Gear ratio denominator (master counts)
+    /// * `ramp_ticks` - Duration of the engagement ramp (ticks)
+    pub fn engage_gearing(&mut self, master: i32, num: i32, den: i32, ramp_ticks: u32) {
+        let (position, _) = self.setpoint_origin();
+        self.stop_trajectory();
+        self.master_position = master;
+        self.gear.set_ratio(num, den);
+        self.gear.engage(master, position, ramp_ticks);
+        self.cascade.set_position(position);
+    }
+
+    /// Update the master axis position followed by the gear (call before every tick).
+    #[inline(always)]
+    pub fn set_master_position(&mut self, position: i32) {
+        self.master_position = position;
+    }
+
+    /// Get access to the electronic gear (ratio, offset, ramp state).
+    #[inline(always)]
+    pub fn gear(&mut self) -> &mut ElectronicGear {
+        &mut self.gear
+    }
+
+    /// Set the resolution and direction of the step/dir input, applied at once while following it.
+    pub fn set_step_dir(&mut self, config: StepDirConfig) {
+        self.step_dir.configure(config);
+        if self.step_engaged {
+            let (num, den) = self.step_dir.ratio();
+            self.gear.set_ratio(num, den);
+        }
+    }
+
+    /// Get the step/dir input configuration.
+    #[inline(always)]
+    pub fn step_dir_config(&self) -> StepDirConfig {
+        self.step_dir.config()
+    }
+
+    /// Get the step position counted by the step/dir input since startup.
+    #[inline(always)]
+    pub fn step_position(&self) -> i32 {
+        self.step_dir.steps()
+    }
+
+    /// Select the source of the position setpoint: the step/dir input or the internal commands.
+    ///
+    /// The step input is followed from the next tick the controller is ready, starting at the present
+    /// setpoint. Any internal motion command or stop returns to the internal source, the axis holds.
+    pub fn set_motion_source(&mut self, source: MotionSource) {
+        if source == self.motion_source {
+            return;
+        }
+        if self.step_engaged {
+            self.stop_trajectory(); // Hold the position reached by the steps
+        }
+        self.motion_source = source;
+        defmt::info!("STEP/DIR: Motion source {}", source as u8);
+    }
+
+    /// Get the selected source of the position setpoint.
+    #[inline(always)]
+    pub fn motion_source(&self) -> MotionSource {
+        self.motion_source
+    }
+
+    /// Start following the step input at the present step position and setpoint.
+    fn engage_step_dir(&mut self) {
+        let (num, den) = self.step_dir.ratio();
+        self.engage_gearing(self.step_dir.steps(), num, den, 1);
+        self.step_engaged = true;
+        defmt::info!("STEP/DIR: Following the step input");
+    }
+
+    /// Set cross-feed gain of the axis coupling compensation (mA per rev/s^2 of the coupled axis).
+    #[inline(always)]
+    pub fn set_coupling_gain(&mut self, gain: i32) {
+        self.coupling_gain = gain;
+    }
+
+    /// Update acceleration of the mechanically coupled axis (counts/s^2, call before every tick).
+    ///
+    /// Typically the `acceleration_setpoint()` of the other axis.
+    #[inline(always)]
+    pub fn set_coupling_acceleration(&mut self, acceleration: i32) {
+        self.coupling_accel = acceleration;
+    }
+
+    /// Get velocity commanded by the running move or stream (counts/s), 0 otherwise.
+    pub fn velocity_setpoint(&self) -> i32 {
+        if self.gear.is_engaged() {
+            0
+        } else if self.shaper.is_active() {
+            self.shaper.velocity()
+        } else if self.stream.is_active() {
+            self.stream.velocity()
+        } else if self.shape == ProfileShape::SCurve && self.scurve.is_active() {
+            self.scurve.velocity()
+        } else if self.profile.is_active() {
+            self.profile.velocity()
+        } else {
+            0
+        }
+    }
+
+    /// Get acceleration commanded by the running point-to-point move (counts/s^2), 0 otherwise.
+    pub fn acceleration_setpoint(&self) -> i32 {
+        if self.gear.is_engaged() {
+            0
+        } else if self.shaper.is_active() {
+            self.shaper.acceleration()
+        } else if self.stream.is_active() {
+            0
+        } else if self.shape == ProfileShape::SCurve && self.scurve.is_active() {
+            self.scurve.acceleration()
+        } else if self.profile.is_active() {
+            self.profile.acceleration()
+        } else {
+            0
+        }
+    }
+
+    /// Get access to the PVT stream (free slots, underrun flag).
+    #[inline(always)]
+    pub fn stream(&mut self) -> &mut PvtStream {
+        &mut self.stream
+    }
+
+    /// Set point-to-point move limits.
+    ///
+    /// # Arguments
+    /// * `vel_max` - Maximum velocity (counts/s)
+    /// * `accel` - Acceleration (counts/s^2)
+    /// * `decel` - Deceleration (counts/s^2)
+    ///
+    /// The velocity is capped by the speed limit (see `set_speed_limit`).
+    #[inline(always)]
+    pub fn set_profile_limits(&mut self, vel_max: i32, accel: i32, decel: i32) {
+        self.profile_limits = (vel_max, accel, decel);
+        let vel_max = self.speed_limit.clamp(vel_max.saturating_abs());
+        self.profile.set_limits(vel_max, accel, decel);
+        self.scurve.set_accel(accel.saturating_abs().max(decel.saturating_abs()));
+    }
+
+    /// Set the maximum mechanical speed: velocity setpoints, the position loop and move profiles are
+    /// capped to it, a measured speed above it by the margin raises an overspeed fault.
+    pub fn set_speed_limit(&mut self, config: SpeedLimitConfig) {
+        self.speed_limit.configure(config);
+        self.apply_velocity_limit();
+        let (vel_max, accel, decel) = self.profile_limits;
+        self.set_profile_limits(vel_max, accel, decel);
+    }
+
+    /// Get the speed limit configuration.
+    #[inline(always)]
+    pub fn speed_limit(&self) -> SpeedLimitConfig {
+        self.speed_limit.config()
+    }
+
+    /// Returns true while an overspeed fault is latched (cleared by `reset_fault`).
+    #[inline(always)]
+    pub fn is_overspeed(&self) -> bool {
+        self.speed_limit.is_tripped()
+    }
+
+    /// Get the highest measured speed (counts/s) since the last call of `reset_peak_speed`.
+    #[inline(always)]
+    pub fn peak_speed(&self) -> i32 {
+        self.speed_limit.peak()
+    }
+
+    /// Restart the peak speed measurement.
+    #[inline(always)]
+    pub fn reset_peak_speed(&mut self) {
+        self.speed_limit.reset_peak();
+    }
+
+    /// Set jerk limit of S-curve moves (counts/s^3), applied from the next move.
+    #[inline(always)]
+    pub fn set_profile_jerk(&mut self, jerk: i32) {
+        self.scurve.set_jerk(jerk);
+    }
+
+    /// Check if a point-to-point move is in progress (including the tail of the input shaper).
+    #[inline(always)]
+    pub fn is_moving(&self) -> bool {
+        self.profile_active() || self.shaper.is_active()
+    }
+
+    /// Check if the trapezoid or its S-curve averaging is running.
+    #[inline(always)]
+    fn profile_active(&self) -> bool {
+        self.profile.is_active() || self.scurve.is_active()
+    }
+
+    /// Set the input shaper of moves and streamed paths, applied from the next move.
+    ///
+    /// # Arguments
+    /// * `kind` - ZV (delay half a period) or ZVD (full period, tolerates frequency errors), `Off`
+    /// * `resonance` - Resonance frequency of the load (0.1 Hz), 0 disables shaping
+    /// * `damping` - Damping ratio of the resonance (0.001)
+    pub fn set_input_shaper(&mut self, kind: ShaperType, resonance: u16, damping: u16) {
+        if self.is_moving() || self.stream.is_active() {
+            defmt::warn!("SHAPER: Change ignored while a trajectory is running");
+            return;
+        }
+        self.shaper.configure(kind, resonance, damping);
+        defmt::info!("SHAPER: Type {}, delay {} ticks", kind as u8, self.shaper.delay());
+    }
+
+    /// Get access to the control cascade for tuning (gains, velocity limit).
+    #[inline(always)]
+    pub fn cascade(&mut self) -> &mut Cascade {
+        &mut self.cascade
+    }
+
+    /// Set the resonance notch of the control loops (center Hz, 0 - off; depth 0..32767, 0 - full).
+    #[inline(always)]
+    pub fn set_resonance_notch(&mut self, center: u16, depth: u16) {
+        self.cascade.set_notch(center, depth);
+    }
+
+    /// Set friction feed-forward (Coulomb mA, viscous mA per rev/s, Coulomb ramp band in counts/s).
+    ///
+    /// Identified values are available from `mechanics()`.
+    #[inline(always)]
+    pub fn set_friction_feedforward(&mut self, coulomb: i32, viscous: i32, band: i32) {
+        self.cascade.set_friction_feedforward(coulomb, viscous, band);
+    }
+
+    /// Set trajectory feed-forward of the position mode (kff_v Q16, 1 << 16 = full velocity;
+    /// kff_a mA per rev/s^2).
+    ///
+    /// A good kff_a is 1000 * 65536 / `mechanics().accel_per_amp`.
+    #[inline(always)]
+    pub fn set_feedforward_gains(&mut self, kff_v: i32, kff_a: i32) {
+        self.cascade.set_feedforward_gains(kff_v, kff_a);
+    }
+
+    /// Set the disturbance observer (nominal acceleration per ampere in counts/s^2 per A, 0 - off;
+    /// Q-filter cutoff Hz; compensation limit mA).
+    ///
+    /// The nominal model is available from `mechanics().accel_per_amp`.
+    #[inline(always)]
+    pub fn set_disturbance_observer(&mut self, accel_per_amp: i32, cutoff: u16, limit: i32) {
+        self.cascade.set_disturbance_observer(accel_per_amp, cutoff, limit);
+    }
+
+    /// Get the external load estimated by the disturbance observer (mA).
+    #[inline(always)]
+    pub fn load_estimate(&self) -> i32 {
+        self.cascade.load_estimate()
+    }
+
+    /// Set the constant gravity feed-forward of a vertical axis (mA).
+    #[inline(always)]
+    pub fn set_gravity_feedforward(&mut self, current: i32) {
+        self.cascade.set_gravity_feedforward(current);
+    }
+
+    /// Get active cascade mode.
+    #[inline(always)]
+    pub fn cascade_mode(&self) -> CascadeMode {
+        self.cascade.mode()
+    }
+
+    /// Get access to soft limits and limit switch settings.
+    #[inline(always)]
+    pub fn limits(&mut self) -> &mut TravelLimits {
+        &mut self.limits
+    }
+
+    /// Get latched limit flags (see `travel_limits::LIMIT_*`).
+    #[inline(always)]
+    pub fn limit_flags(&self) -> u8 {
+        self.limits.flags()
+    }
+
+    /// Set fine-trim of the commutation offset (electrical, clamped to ±TRIM_RANGE).
+    ///
+    /// Call at boot with a previously stored value to persist the trim.
+    #[inline(always)]
+    pub fn set_commutation_trim(&mut self, offset: i16) {
+        self.trim.set_offset(offset);
+    }
+
+    /// Get fine-trim of the commutation offset (store it to persist the trim).
+    #[inline(always)]
+    pub fn commutation_trim(&self) -> i16 {
+        self.trim.offset()
+    }
+
+    /// Get live effect of the commutation trim (lower current for the same load is better).
+    #[inline(always)]
+    pub fn commutation_trim_report(&self) -> TrimReport {
+        self.trim.report()
+    }
+
+    /// Select the quick alignment (pole pairs > 0) or the full calibration sweep (0) for the next
+    /// calibration. Returns false once the driver has left calibration.
+    ///
+    /// Quick alignment locks the rotor at five electrical angles (about 175 ms, the rotor jerks
+    /// to the nearest pole first) and uses a linear encoder model without nonlinearity correction.
+    pub fn set_quick_align(&mut self, pole_pairs: u16) -> bool {
+        if self.driver_status != DriverStatus::Calibrating {
+            return false;
+        }
+        self.quick_align.configure(pole_pairs);
+        true
+    }
+
+    /// Get the winding diagnosis stage and report (verdict, current and leakage per winding).
+    #[inline(always)]
+    pub fn phase_check(&self) -> (PhaseCheckStage, PhaseCheckReport) {
+        (self.phase_check.stage(), self.phase_check.report())
+    }
+
+    /// Get the wiring check stage and report (diagnosis, selected pattern, measured currents).
+    #[inline(always)]
+    pub fn wiring_report(&self) -> (WiringStage, WiringReport) {
+        (self.wiring.stage(), self.wiring.report())
+    }
+
+    /// Mirror the encoder counting direction, the position keeps its magnitude and changes sign.
+    #[inline(always)]
+    fn invert_encoder(&mut self) {
+        self.encoder_inverted = !self.encoder_inverted;
+        self.position.invert();
+        self.load.invert();
+        self.last_position = self.position.position();
+    }
+
+    /// Check if the encoder input is inverted (found by the calibration or restored).
+    #[inline(always)]
+    pub fn encoder_inverted(&self) -> bool {
+        self.encoder_inverted
+    }
+
+    /// Restore a stored encoder polarity before the calibration (true - counts backwards).
+    ///
+    /// Returns false once the driver has left calibration.
+    pub fn set_encoder_inverted(&mut self, inverted: bool) -> bool {
+        if self.driver_status != DriverStatus::Calibrating {
+            return false;
+        }
+        if inverted != self.encoder_inverted {
+            self.invert_encoder();
+        }
+        true
+    }
+
+    /// Get the quick alignment stage and result (offset, direction).
+    #[inline(always)]
+    pub fn quick_align(&self) -> (QuickAlignStage, (u16, i32)) {
+        (self.quick_align.stage(), self.quick_align.result())
+    }
+
+    /// Get the angle calibration progress (stage, percent complete, estimated remaining ticks).
+    ///
+    /// Covers the full calibration sweep; the current sense calibration, wiring and phase checks and
+    /// the quick alignment report through their own stage getters.
+    #[inline(always)]
+    pub fn calibration_progress(&self) -> CalProgress {
+        self.angle_calibrator.progress()
+    }
+
+    /// Get encoder deviations measured by the angle calibration, returns the number of points
+    /// written (0 before calibration). Compress with `DeltaTable` / `HarmonicTable` for storage.
+    #[inline(always)]
+    pub fn calibration_deviations(&self, out: &mut [i16]) -> usize {
+        self.angle_calibrator.table_deviations(out)
+    }
+
+    /// Get phase-current readings with amplifier offsets removed (raw ADC units).
+    #[inline(always)]
+    pub fn currents(&self) -> [i16; 4] {
+        self.current_sense.currents()
+    }
+
+    /// Set current sense calibration settings, used by the next calibration (startup or fault reset).
+    #[inline(always)]
+    pub fn set_current_calibration(&mut self, config: CurrentCalConfig) {
+        self.current_cal.configure(config);
+    }
+
+    /// Get the result of the current sense calibration (offsets, gains, failed channels).
+    #[inline(always)]
+    pub fn current_calibration(&self) -> CurrentCalReport {
+        self.current_cal.report()
+    }
+
+    /// Get access to current sense offsets (tracked automatically while the outputs are off).
+    #[inline(always)]
+    pub fn current_sense(&mut self) -> &mut CurrentSense {
+        &mut self.current_sense
+    }
+
+    /// Get result of the three-phase balance diagnostic (BLDC at constant speed).
+    #[inline(always)]
+    pub fn phase_balance(&self) -> BalanceReport {
+        self.balance.report()
+    }
+
+    /// Get access to the three-phase balance diagnostic settings.
+    #[inline(always)]
+    pub fn phase_balance_config(&mut self) -> &mut PhaseBalance {
+        &mut self.balance
+    }
+
+    /// Set how fast the output amplitude may rise and fall (mA/s, i32::MAX - unlimited).
+    #[inline(always)]
+    pub fn set_amplitude_slew(&mut self, rise: i32, fall: i32) {
+        self.amplitude_slew.set_rates(rise, fall);
+    }
+
+    /// Set the watchdog timeouts (control tick stall, setpoint feed) and its safe state.
+    #[inline(always)]
+    pub fn set_watchdog(&mut self, config: WatchdogConfig) {
+        self.watchdog.configure(config);
+    }
+
+    /// Restart the setpoint timeout of the watchdog, called for every setpoint of the host.
+    #[inline(always)]
+    pub fn feed_watchdog(&mut self) {
+        self.watchdog.feed();
+    }
+
+    /// Check the control ticks from an independent time base (e.g. SysTick), `elapsed_ms` since the
+    /// previous call.
+    ///
+    /// Returns the safe PWM outputs while the watchdog is tripped, the HAL has to write them to the
+    /// timers itself: stalled ticks no longer do.
+    pub fn heartbeat(&mut self, elapsed_ms: u16) -> Option<[i16; 4]> {
+        self.watchdog.heartbeat(elapsed_ms);
+        if !self.watchdog.is_tripped() {
+            return None;
+        }
+        // Stays safe when the ticks resume, a ramp can't run without them and coasts
+        let action = match self.watchdog.config().action {
+            StopMode::Ramp => StopMode::Coast,
+            action => action,
+        };
+        self.safe_stop.request(action, 0);
+        self.driver_status = DriverStatus::Error;
+        Some(action.outputs())
+    }
+
+    /// Stop the axis: coast (all phases floating), brake (windings shorted) or ramp (decelerate under
+    /// the velocity loop, then coast). Any running move, homing or experiment is cancelled and the
+    /// stop holds until `release_stop`.
+    pub fn request_stop(&mut self, mode: StopMode) {
+        self.cancel_motion();
+        let immediate = self.driver_status != DriverStatus::Ready || self.motor_type == MotorType::DUALDC;
+        let mode = if immediate && mode == StopMode::Ramp { StopMode::Coast } else { mode };
+        self.safe_stop.request(mode, self.cascade.velocity());
+    }
+
+    /// Stop the axis with the behavior selected by `set_stop_mode`.
+    #[inline(always)]
+    pub fn stop(&mut self) {
+        self.request_stop(self.stop_mode);
+    }
+
+    /// Leave a stop, the axis holds its present position. Returns false if no stop was requested.
+    ///
+    /// Refused while the emergency stop is latched.
+    pub fn release_stop(&mut self) -> bool {
+        if !self.safe_stop.is_active() || self.estop {
+            return false;
+        }
+        self.safe_stop.release();
+        self.cascade.reset(self.loop_position());
+        self.cascade.set_position(self.loop_position());
+        defmt::info!("STOP: Released");
+        true
+    }
+
+    /// Clear a latched emergency stop and release the stop, the axis holds its present position.
+    ///
+    /// Returns false if the e-stop input is still active or nothing was latched.
+    pub fn reset_estop(&mut self) -> bool {
+        if !self.estop || self.estop_input {
+            return false;
+        }
+        self.estop = false;
+        defmt::info!("ESTOP: Reset");
+        self.release_stop();
+        true
+    }
+
+    /// Returns true while an emergency stop is latched.
+    #[inline(always)]
+    pub fn estop_latched(&self) -> bool {
+        self.estop
+    }
+
+    /// Set the current ramp, hold current and sleep timeout of the standby mode.
+    #[inline(always)]
+    pub fn set_standby(&mut self, config: StandbyConfig) {
+        self.standby.configure(config);
+    }
+
+    /// Put the idle axis into standby: the current limit ramps down to the hold current, after the
+    /// timeout the outputs switch off (see `standby_gates`). Motion commands stay limited until `wake`.
+    #[inline(always)]
+    pub fn set_idle(&mut self) {
+        self.standby.enter();
+    }
+
+    /// Leave standby without recalibration, the loops restart holding the present position.
+    ///
+    /// Returns false if the axis wasn't idle.
+    pub fn wake(&mut self) -> bool {
+        if !self.standby.wake() {
+            return false;
+        }
+        self.cascade.reset(self.loop_position());
+        self.cascade.set_position(self.loop_position());
+        true
+    }
+
+    /// Set the standstill current reduction (delay, hold percentage, rest speed), mainly for steppers.
+    #[inline(always)]
+    pub fn set_hold_current(&mut self, config: HoldCurrentConfig) {
+        self.hold.configure(config);
+    }
+
+    /// Returns true while the current is reduced at standstill.
+    #[inline(always)]
+    pub fn is_holding(&self) -> bool {
+        self.hold.is_holding()
+    }
+
+    /// Get the stage of the standby mode.
+    #[inline(always)]
+    pub fn standby_stage(&self) -> StandbyStage {
+        self.standby.stage()
+    }
+
+    /// Get the peripherals the HAL may switch off to save power (`standby::GATE_*` flags), they have
+    /// to run again before `wake`.
+    #[inline(always)]
+    pub fn standby_gates(&self) -> u8 {
+        self.standby.gates()
+    }
+
+    /// Select the behavior of `stop` and of the emergency stop (ramp by default).
+    #[inline(always)]
+    pub fn set_stop_mode(&mut self, mode: StopMode) {
+        self.stop_mode = mode;
+    }
+
+    /// Get the behavior of `stop`.
+    #[inline(always)]
+    pub fn stop_mode(&self) -> StopMode {
+        self.stop_mode
+    }
+
+    /// Set the deceleration of a ramp stop (counts/s^2) and the speed treated as standstill (counts/s).
+    #[inline(always)]
+    pub fn set_stop_ramp(&mut self, decel: i32, standstill: i32) {
+        self.safe_stop.configure(decel, standstill);
+    }
+
+    /// Get the progress of a requested stop.
+    #[inline(always)]
+    pub fn stop_stage(&self) -> StopStage {
+        self.safe_stop.stage()
+    }
+
+    /// Get the reason of a watchdog trip (cleared by `reset_fault`).
+    #[inline(always)]
+    pub fn watchdog_trip(&self) -> WatchdogTrip {
+        self.watchdog.trip()
+    }
+
+    /// Set the clock of the input timestamps (counts per second) to measure the tick period and
+    /// scale the loops by it, 0 (default) assumes the nominal period.
+    #[inline(always)]
+    pub fn set_timestamp_clock(&mut self, clock: u32) {
+        self.timer.configure(clock, self.frequency);
+    }
+
+    /// Get the measured tick timing (last, shortest, longest and average period, peak jitter).
+    #[inline(always)]
+    pub fn tick_timing(&self) -> TickTiming {
+        self.timer.timing()
+    }
+
+    /// Restart the shortest / longest period and peak jitter of the tick timing.
+    #[inline(always)]
+    pub fn reset_tick_timing(&mut self) {
+        self.timer.reset_stats();
+    }
+
+    /// Set the median window applied to raw encoder samples: 1 (off), 3 or 5.
+    /// Larger windows reject longer glitch bursts at the cost of (window - 1) / 2 ticks of delay.
+    #[inline(always)]
+    pub fn set_encoder_median(&mut self, taps: u8) {
+        self.glitch.set_taps(taps);
+    }
+
+    /// Set the plausibility checks of the raw encoder samples (jump, stuck value, status flags).
+    #[inline(always)]
+    pub fn set_encoder_supervisor(&mut self, config: EncoderSupervisorConfig) {
+        self.supervisor.configure(config);
+    }
+
+    /// Get the encoder fault and the number of rejected samples.
+    #[inline(always)]
+    pub fn encoder_supervisor(&self) -> EncoderSupervisorReport {
+        self.supervisor.report()
+    }
+
+    /// Predict the commutation angle between encoder samples for sensors updating slower than the
+    /// control loop. The HAL has to count the samples in `angle_count` of the inputs.
+    #[inline(always)]
+    pub fn set_async_encoder(&mut self, enabled: bool) {
+        self.async_encoder = enabled;
+        self.predictor.reset();
+    }
+
+    /// Set the encoder resolution (bits per turn) for interpolating the commutation angle between
+    /// code changes, e.g. 12 for a 12-bit encoder. 16 (default) turns the interpolation off.
+    #[inline(always)]
+    pub fn set_encoder_resolution(&mut self, bits: u8) {
+        self.interpolator.set_resolution(bits);
+    }
+
+    /// Set bandwidth of the encoder angle tracker used for commutation (Hz).
+    #[inline(always)]
+    pub fn set_tracking_bandwidth(&mut self, bandwidth: u16) {
+        self.tracker.set_bandwidth(bandwidth);
+    }
+
+    /// Select the velocity feedback of the cascade (built-in differentiator, PLL or observer).
+    pub fn set_velocity_source(&mut self, source: VelocitySource) {
+        if source == VelocitySource::Observer && self.velocity_source != source {
+            self.observer.reset(self.position.position());
+        }
+        self.velocity_source = source;
+    }
+
+    /// Set the observer model (acceleration per ampere of torque current, counts/s^2 per A)
+    /// and bandwidth (Hz).
+    #[inline(always)]
+    pub fn set_observer(&mut self, accel_per_amp: i32, bandwidth: u16) {
+        self.observer.set_model(accel_per_amp);
+        self.observer.set_bandwidth(bandwidth);
+    }
+
+    /// Get velocity estimated by the encoder angle tracker (counts/s).
+    #[inline(always)]
+    pub fn tracked_velocity(&self) -> i32 {
+        self.tracker.velocity()
+    }
+
+    /// Get measured velocity (counts/s).
+    #[inline(always)]
+    pub fn velocity(&self) -> i32 {
+        self.cascade.velocity()
+    }
+
+    /// Get the torque current command of the cascade (mA).
+    #[inline(always)]
+    pub fn torque_current(&self) -> i32 {
+        self.cascade.current()
+    }
+
+    /// Get the filtered supply voltage (mV).
+    #[inline(always)]
+    pub fn supply_mv(&self) -> i32 {
+        self.supply.voltage_mv()
+    }
+
+    /// Set the phase current scale (uA per current sense LSB) enabling the measured bus estimate.
+    ///
+    /// With 0 (default) the bus current is estimated from the commanded amplitude and resistance.
+    #[inline(always)]
+    pub fn set_current_scale(&mut self, ua_per_lsb: i32) {
+        self.current_scale = ua_per_lsb;
+    }
+
+    /// Set the winding inductance (uH), current-loop gains are recomputed for their bandwidth.
+    #[inline(always)]
+    pub fn set_inductance(&mut self, inductance: i32) {
+        self.inductance = inductance;
+        self.set_current_bandwidth(self.current_gains.bandwidth);
+    }
+
+    /// Compute current-loop PI gains for the requested bandwidth (Hz) from the winding R / L and load
+    /// them into the current loop of the cascade.
+    ///
+    /// Returns the computed gains (zero Kp / Ki, open loop, while the inductance is unknown).
+    #[inline(always)]
+    pub fn set_current_bandwidth(&mut self, bandwidth: u16) -> CurrentLoopGains {
+        self.current_gains = CurrentLoopGains::compute(self.resistance, self.inductance, bandwidth, self.frequency);
+        self.cascade.set_current_gains(&self.current_gains);
+        if self.current_gains.kp != 0 {
+            defmt::info!(
+                "CURRENT LOOP: {}Hz, Kp {} Ki {} (Q16)",
+                self.current_gains.bandwidth,
+                self.current_gains.kp,
+                self.current_gains.ki
+            );
+        }
+        self.current_gains
+    }
+
+    /// Restore previously computed (e.g. persisted) current-loop gains.
+    #[inline(always)]
+    pub fn set_current_loop_gains(&mut self, gains: CurrentLoopGains) {
+        self.current_gains = gains;
+        self.cascade.set_current_gains(&gains);
+    }
+
+    /// Get the current-loop PI gains.
+    #[inline(always)]
+    pub fn current_loop_gains(&self) -> CurrentLoopGains {
+        self.current_gains
+    }
+
+    /// Get the estimated DC-bus current and electrical input power.
+    #[inline(always)]
+    pub fn bus_power(&self) -> BusReport {
+        self.bus.report()
+    }
+
+    /// Get the energy and charge drawn from and fed back into the supply since startup or reset.
+    #[inline(always)]
+    pub fn energy(&self) -> EnergyReport {
+        self.energy.report()
+    }
+
+    /// Clear the energy and charge totals.
+    #[inline(always)]
+    pub fn reset_energy(&mut self) {
+        self.energy.reset();
+    }
+
+    /// Change the PWM switching frequency at runtime, trading switching loss against audible noise.
+    ///
+    /// # Arguments
+    /// * `pwm_hz` - PWM frequency (Hz), an integer multiple of the update frequency
+    /// * `period` - Timer counts of 100% duty at that frequency, the duties returned by `tick` are
+    ///   scaled to it (0 - Q15 duties)
+    ///
+    /// The control tick keeps its rate (the HAL triggers it every `pwm_hz / frequency` periods), so
+    /// loops and filters stay valid; the duty constraints of the minimum times are recomputed. Returns
+    /// false if the frequency isn't a multiple of the update frequency.
+    pub fn set_pwm_frequency(&mut self, pwm_hz: u32, period: u16) -> bool {
+        let frequency = self.frequency.max(1) as u32;
+        if pwm_hz < frequency || !pwm_hz.is_multiple_of(frequency) {
+            defmt::warn!("PWM: {}Hz is not a multiple of the {}Hz control rate", pwm_hz, frequency);
+            return false;
+        }
+        self.pwm_frequency = pwm_hz;
+        self.pwm_period = period;
+        self.apply_pwm_timing();
+        defmt::info!("PWM: {}Hz, {} counts", pwm_hz, period);
+        true
+    }
+
+    /// Set the minimum low-side on-time of every PWM period (ns), keeping bootstrap capacitors charged
+    /// and low-side shunts sampled, 0 (default) allows 100% duty.
+    #[inline(always)]
+    pub fn set_min_off_time(&mut self, min_off_ns: u32) {
+        self.min_off_ns = min_off_ns;
+        self.apply_pwm_timing();
+    }
+
+    /// Set the shortest pulse the gate driver can switch (ns), shorter ones are rounded to none or to
+    /// this width. 0 (default) allows any width.
+    #[inline(always)]
+    pub fn set_min_pulse(&mut self, min_pulse_ns: u32) {
+        self.min_pulse_ns = min_pulse_ns;
+        self.apply_pwm_timing();
+    }
+
+    /// Get the PWM switching frequency (Hz).
+    #[inline(always)]
+    pub fn pwm_frequency(&self) -> u32 {
+        self.pwm_frequency
+    }
+
+    /// Hands the duty scale and the duty constraints of the present PWM frequency to the PWM driver.
+    fn apply_pwm_timing(&mut self) {
+        // Times as a fraction of the period (Q15)
+        let fraction = |ns: u32| (ns as u64 * self.pwm_frequency as u64 * 32768) / 1_000_000_000;
+        let max_duty = (i16::MAX as u64).saturating_sub(fraction(self.min_off_ns)) as i16;
+        let min_pulse = fraction(self.min_pulse_ns).min(i16::MAX as u64) as i16;
+        self.motor.set_duty_scale(self.pwm_period, max_duty, min_pulse);
+    }
+
+    /// Get the update frequency (ticks per second).
+    #[inline(always)]
+    pub fn frequency(&self) -> u16 {
+        self.frequency
+    }
+
+    /// Returns true if the encoder-to-rotor alignment is known (full sweep or quick alignment).
+    #[inline(always)]
+    fn is_aligned(&self) -> bool {
+        self.angle_calibrator.is_ready() || self.quick_align.is_ready()
+    }
+
+    /// Set the calibration settings (stages, current, sweep speed, settle time, points per period),
+    /// used by the next calibration or `recalibrate()`.
+    ///
+    /// The calibration current is limited by the current limit passed to `tick()`. A sweep in
+    /// progress starts over with the new settings. Returns false, keeping the previous settings, if
+    /// the points per period are not a power of two within 2..=32; points of a revolution exceeding
+    /// the calibration table fail the sweep once its test motion has measured the pole pairs.
+    pub fn set_calibration(&mut self, config: CalibrationConfig) -> bool {
+        if !config.is_valid() {
+            return false;
+        }
+        self.cal_config = config;
+        if !self.angle_calibrator.is_ready() {
+            self.restart_angle_calibration();
+        }
+        true
+    }
+
+    /// Starts the full calibration sweep over with the configured settings.
+    #[inline(always)]
+    fn restart_angle_calibration(&mut self) {
+        self.angle_calibrator = AngleCalibrator::new(self.frequency);
+        self.angle_calibrator.configure(&self.cal_config);
+    }
+
+    /// Get the calibration settings.
+    #[inline(always)]
+    pub fn calibration_config(&self) -> CalibrationConfig {
+        self.cal_config
+    }
+
+    /// Run the calibration stages selected by `set_calibration()` again, the others keep their result.
+    ///
+    /// Any motion stops and the driver stays in Calibrating until the stages are done. Returns false
+    /// while a calibration is already running.
+    pub fn recalibrate(&mut self) -> bool {
+        if self.driver_status == DriverStatus::Calibrating {
+            return false;
+        }
+        self.stop_trajectory();
+        if self.cal_config.runs(CAL_CURRENT_SENSE) {
+            self.current_cal.start(self.motor_type == MotorType::BLDC);
+        }
+        if self.cal_config.runs(CAL_WIRING) {
+            self.wiring.reset();
+        }
+        if self.cal_config.runs(CAL_PHASES) {
+            self.phase_check.reset();
+        }
+        if self.cal_config.runs(CAL_ALIGNMENT) {
+            self.restart_angle_calibration();
+            self.quick_align.reset();
+        }
+        self.cal_paused = false;
+        self.driver_status = DriverStatus::Calibrating;
+        defmt::info!("CALIBRATION: Restarted, stages {:04b}", self.cal_config.stages);
+        true
+    }
+
+    /// Pause the calibration with the outputs off. Returns false if the driver isn't calibrating.
+    ///
+    /// The rotor is free while paused, so the interrupted stage starts over on resume; completed
+    /// stages are kept.
+    pub fn pause_calibration(&mut self) -> bool {
+        if self.driver_status != DriverStatus::Calibrating {
+            return false;
+        }
+        self.interrupt_calibration();
+        self.cal_paused = true;
+        defmt::info!("CALIBRATION: Paused");
+        true
+    }
+
+    /// Resume a paused calibration. Returns false if it isn't paused.
+    pub fn resume_calibration(&mut self) -> bool {
+        if self.driver_status != DriverStatus::Calibrating || !self.cal_paused {
+            return false;
+        }
+        self.cal_paused = false;
+        defmt::info!("CALIBRATION: Resumed");
+        true
+    }
+
+    /// Check if the calibration is paused.
+    #[inline(always)]
+    pub fn calibration_paused(&self) -> bool {
+        self.cal_paused
+    }
+
+    /// Abort the calibration: the outputs turn off and the driver enters the Error state.
+    ///
+    /// `reset_fault()` restarts the unfinished stages (or returns to Ready if the motor is still
+    /// aligned). Returns false if the driver isn't calibrating.
+    pub fn abort_calibration(&mut self) -> bool {
+        if self.driver_status != DriverStatus::Calibrating {
+            return false;
+        }
+        self.interrupt_calibration();
+        self.cal_paused = false;
+        self.amplitude = 0;
+        self.driver_status = DriverStatus::Error;
+        defmt::warn!("CALIBRATION: Aborted");
+        true
+    }
+
+    /// Returns the stage in progress to its beginning, finished stages keep their result.
+    fn interrupt_calibration(&mut self) {
+        if self.current_cal.is_active() {
+            self.current_cal.start(self.motor_type == MotorType::BLDC);
+        }
+        if self.wiring.is_active() {
+            // Candidate patterns are tried on the outputs, the configured one comes back
+            self.change_phase_mode(self.wiring.report().pattern);
+            self.wiring.reset();
+        }
+        if self.phase_check.stage() == PhaseCheckStage::Running {
+            self.phase_check.reset();
+        }
+        if self.quick_align.stage() == QuickAlignStage::Running {
+            self.quick_align.reset();
+        }
+        if !self.angle_calibrator.is_ready() {
+            self.restart_angle_calibration();
+        }
+    }
+
+    /// Returns true if a latched fault other than the gate driver one holds the Error state.
+    #[inline(always)]
+    fn other_fault_latched(&self) -> bool {
+        self.supervisor.is_faulted()
+            || self.watchdog.is_tripped()
+            || self.speed_limit.is_tripped()
+            || self.winding.is_tripped()
+    }
+
+    /// Start the torque constant identification: the motor spins unloaded at `config.speed` in
+    /// velocity mode, Ke and Kt follow from the back-EMF. Needs current sensing.
+    ///
+    /// Returns false unless the driver is ready with a commutated motor and no other routine runs.
+    pub fn start_kt_identification(&mut self, config: KtIdentConfig) -> bool {
+        let sensed = self.current_scale != 0 && self.motor_type.has_commutation();
+        let busy = self.homing.is_active() || self.ident.is_active() || self.autotune.is_active();
+        if self.driver_status != DriverStatus::Ready || !sensed || busy || self.safe_stop.is_active() {
+            return false;
+        }
+        self.stop_trajectory();
+        self.cascade.set_velocity(self.speed_limit.clamp(config.speed));
+        self.kt_ident.start(config);
+        true
+    }
+
+    /// Abort the torque constant identification, the velocity loop brings the axis to rest.
+    #[inline(always)]
+    pub fn cancel_kt_identification(&mut self) {
+        if self.kt_ident.is_active() {
+            self.kt_ident.cancel();
+            self.cascade.set_velocity(0);
+        }
+    }
+
+    /// Get the torque constant identification stage.
+    #[inline(always)]
+    pub fn kt_identification_stage(&self) -> KtIdentStage {
+        self.kt_ident.stage()
+    }
+
+    /// Get the identified back-EMF and torque constants.
+    #[inline(always)]
+    pub fn kt_report(&self) -> KtReport {
+        self.kt_ident.report()
+    }
+
+    /// Set the torque constant (mN·m/A), e.g. from the datasheet.
+    #[inline(always)]
+    pub fn set_torque_constant(&mut self, kt: i32) {
+        self.physical.set_kt(kt);
+    }
+
+    /// Set the torque constant from a known inertia (g·cm²) and the identified acceleration per
+    /// ampere, see `mechanics()`. Returns false without a mechanical identification.
+    pub fn set_torque_constant_from_inertia(&mut self, inertia: i32) -> bool {
+        let kt = kt_from_inertia(self.mechanics().accel_per_amp, inertia);
+        if kt == 0 {
+            return false;
+        }
+        self.physical.set_kt(kt);
+        true
+    }
+
+    /// Get the torque constant (mN·m/A), 0 - unknown.
+    #[inline(always)]
+    pub fn torque_constant(&self) -> i32 {
+        self.physical.kt()
+    }
+
+    /// Enable the winding temperature estimate from the resistance rise (needs current sensing),
+    /// derating the current above the warning and faulting above the limit temperature.
+    #[inline(always)]
+    pub fn set_winding_temperature(&mut self, config: WindingTempConfig) {
+        self.winding.configure(config, self.resistance);
+    }
+
+    /// Stop the winding temperature estimate.
+    #[inline(always)]
+    pub fn disable_winding_temperature(&mut self) {
+        self.winding.disable();
+    }
+
+    /// Get the estimated winding temperature (0.1 °C) and resistance (mOhm, 0 - no estimate yet).
+    #[inline(always)]
+    pub fn winding_temperature(&self) -> (i32, i32) {
+        (self.winding.temperature(), self.winding.resistance())
+    }
+
+    /// Set the automatic retry policy of gate driver faults (retries, cooldown, re-arm time).
+    #[inline(always)]
+    pub fn set_gate_fault_policy(&mut self, config: GateFaultConfig) {
+        self.gate.configure(config);
+    }
+
+    /// Get the diagnosis of gate driver faults (last kind, desaturated phases, count, retries left).
+    #[inline(always)]
+    pub fn gate_fault(&self) -> GateFaultReport {
+        self.gate.report()
+    }
+
+    /// Leave the Error state, re-engaging the axis with a soft-start at its current position.
+    ///
+    /// An uncalibrated motor (or failed current sense calibration) returns to calibration instead. Returns false if there is no fault.
+    pub fn reset_fault(&mut self) -> bool {
+        if self.driver_status != DriverStatus::Error {
+            return false;
+        }
+        self.supervisor.clear(); // A sensor that is still broken faults again
+        self.watchdog.clear();
+        self.speed_limit.clear();
+        self.winding.clear();
+        if self.gate.is_tripped() || self.gate.report().latched {
+            self.gate.clear(); // Reset by the application, an automatic retry keeps its count
+        }
+        if !self.estop {
+            self.safe_stop.release(); // The soft-start re-engages the axis
+        }
+        let current_cal_failed = self.current_cal.stage() == CurrentCalStage::Failed;
+        if current_cal_failed || (self.motor_type.has_commutation() && !self.is_aligned()) {
+            if self.angle_calibrator.stage() == CalStage::Error {
+                self.restart_angle_calibration(); // A failed sweep starts over
+            }
+            self.driver_status = DriverStatus::Calibrating;
+            return true;
+        }
+        self.stop_trajectory();
+        self.enter_ready();
+        self.cascade.set_position(self.loop_position()); // Hold where the axis is now
+        if self.motor_type != MotorType::DUALDC {
+            self.soft_start.start(self.position.from_zero());
+        }
+        defmt::info!("DRIVER: Fault reset");
+        true
+    }
+
+    /// Apply a new loop tuning, watched for instability and reverted to the previous set if needed.
+    ///
+    /// Gains written directly through `cascade()` are not watched.
+    pub fn set_tuning(&mut self, tuning: TuningSet) {
+        let previous = self.tuning();
+        self.apply_tuning(tuning);
+        self.safe_params.change(previous);
+    }
+
+    /// Get the active loop tuning, with the velocity limit as set (before the speed limit).
+    #[inline(always)]
+    pub fn tuning(&self) -> TuningSet {
+        TuningSet {
+            vel_limit: self.vel_limit,
+            ..TuningSet::capture(&self.cascade)
+        }
+    }
+
+    /// Write a tuning into the cascade, its velocity limit capped by the speed limit.
+    fn apply_tuning(&mut self, tuning: TuningSet) {
+        tuning.apply(&mut self.cascade);
+        self.vel_limit = tuning.vel_limit;
+        self.apply_velocity_limit();
+    }
+
+    /// Set the cascade velocity limit: the tuning limit, capped by the speed limit while enabled.
+    #[inline(always)]
+    fn apply_velocity_limit(&mut self) {
+        let config = self.speed_limit.config();
+        let limit = if self.speed_limit.is_enabled() {
+            self.vel_limit.min(config.max_speed)
+        } else {
+            self.vel_limit
+        };
+        self.cascade.set_velocity_limit(limit);
+    }
+
+    /// Read a parameter by its id (see `params::PARAMS`).
+    #[inline(always)]
+    pub fn get_param(&self, id: u16) -> Result<i32, ParamError> {
+        params::registry::get(self, id)
+    }
+
+    /// Write a parameter by its id, checked against its access and range (see `params::PARAMS`).
+    #[inline(always)]
+    pub fn set_param(&mut self, id: u16, value: i32) -> Result<(), ParamError> {
+        params::registry::set(self, id, value)
+    }
+
+    /// Save all writable parameters as a new record into the flash page image `page`.
+    ///
+    /// The HAL then programs the returned byte range, after erasing the page if requested.
+    #[inline(always)]
+    pub fn save_params(&self, page: &mut [u8]) -> Result<StorageWrite, StorageError> {
+        params::storage::save(self, page)
+    }
+
+    /// Restore the parameters of the newest valid record of the flash page `page`, e.g. at boot.
+    #[inline(always)]
+    pub fn load_params(&mut self, page: &[u8]) -> Result<LoadReport, StorageError> {
+        params::storage::load(self, page)
+    }
+
+    /// Set the instability watch used after tuning changes.
+    #[inline(always)]
+    pub fn set_safe_params(&mut self, config: SafeParamsConfig) {
+        self.safe_params.configure(config);
+    }
+
+    /// Check if a tuning change was reverted to the last known good set (latched).
+    #[inline(always)]
+    pub fn safe_params_reverted(&self) -> bool {
+        self.safe_params.reverted()
+    }
+
+    /// Clear the tuning revert flag.
+    #[inline(always)]
+    pub fn acknowledge_safe_params(&mut self) {
+        self.safe_params.acknowledge();
+    }
+
+    /// Set thresholds of the drive health score.
+    #[inline(always)]
+    pub fn set_health(&mut self, config: HealthConfig) {
+        self.health.configure(config);
+    }
+
+    /// Get the drive health score (0-100) with its contributing factors.
+    #[inline(always)]
+    pub fn health(&self) -> HealthReport {
+        self.health.report()
+    }
+
+    /// Clear peak following error and glitch counter of the health report.
+    #[inline(always)]
+    pub fn reset_health_statistics(&mut self) {
+        self.health.reset_statistics();
+    }
+
+    /// Select shadow mode: the controller keeps running but its output doesn't drive the motor.
+    #[inline(always)]
+    pub fn set_shadow_mode(&mut self, mode: ShadowMode) {
+        self.shadow.set_mode(mode);
+    }
+
+    /// Set the external (electrical angle, amplitude in mA) command driving the motor in pass-through.
+    #[inline(always)]
+    pub fn set_shadow_command(&mut self, angle_el: u16, amplitude: i16) {
+        self.shadow.set_command(angle_el, amplitude);
+    }
+
+    /// Get what the controller would have commanded while in shadow mode.
+    #[inline(always)]
+    pub fn shadow_report(&self) -> ShadowReport {
+        self.shadow.report()
+    }
+
+    /// Set field-weakening settings (maximum weakening current 0 disables it).
+    #[inline(always)]
+    pub fn set_field_weakening(&mut self, config: FieldWeakeningConfig) {
+        self.field_weakening.configure(config);
+    }
+
+    /// Get the active field-weakening (negative d-axis) current (mA).
+    #[inline(always)]
+    pub fn field_current(&self) -> i32 {
+        self.field_weakening.current()
+    }
+
+    /// Set saliency parameters of an interior magnet motor enabling MTPA (Lq <= Ld disables it).
+    #[inline(always)]
+    pub fn set_mtpa(&mut self, config: MtpaConfig) {
+        self.mtpa.configure(config);
+    }
+
+    /// Set sensorless commutation (HFI at low speed, flux observer at speed).
+    ///
+    /// Replaces the encoder angle for commutation only, position and velocity loops still use the
+    /// encoder. Needs a BLDC motor and the current scale (`set_current_scale()`); returns false and
+    /// keeps sensorless operation off otherwise. Torque is held until HFI has resolved the polarity.
+    #[inline(always)]
+    pub fn set_sensorless(&mut self, config: SensorlessConfig) -> bool {
+        if self.motor_type != MotorType::BLDC || self.current_scale == 0 {
+            defmt::warn!("SENSORLESS: Needs a BLDC motor and calibrated phase currents");
+            return false;
+        }
+        self.sensorless.configure(config, self.resistance);
+        true
+    }
+
+    /// Get the state of the sensorless estimation.
+    #[inline(always)]
+    pub fn sensorless(&self) -> SensorlessReport {
+        self.sensorless.report()
+    }
+
+    /// Set soft-start settings used when re-engaging after a fault reset.
+    #[inline(always)]
+    pub fn set_soft_start(&mut self, config: SoftStartConfig) {
+        self.soft_start.configure(config);
+    }
+
+    /// Get current soft-start stage.
+    #[inline(always)]
+    pub fn soft_start_stage(&self) -> SoftStartStage {
+        self.soft_start.stage()
+    }
+
+    /// Allow short bursts above the continuous current limit within a duty-cycle budget.
+    ///
+    /// The `current` passed to `tick()` is the continuous limit.
+    #[inline(always)]
+    pub fn set_burst_torque(&mut self, config: BurstConfig) {
+        self.burst.configure(config);
+    }
+
+    /// Get the burst budget report (remaining budget, bursts, limited time).
+    #[inline(always)]
+    pub fn burst_report(&self) -> BurstReport {
+        self.burst.report()
+    }
+
+    /// Get current driver state (Calibrating, Ready or Error).
+    #[inline(always)]
+    pub fn status(&self) -> DriverStatus {
+        self.driver_status
+    }
+
+    /// Change the phase pattern mode.
+    #[inline(always)]
+    pub fn change_phase_mode(&mut self, connection: PhasePattern) {
+        self.connection = connection;
+        self.motor.change_phase_mode(connection); // Delegate to motor instance
+    }
+
+    /// Start sensorless homing against a hard stop.
+    ///
+    /// Returns false if the driver is not calibrated yet or the motor has no commutation.
+    pub fn start_homing(&mut self, config: HomingConfig) -> bool {
+        // Load angle detection needs commutation, so single coil motors can't home this way
+        if self.driver_status != DriverStatus::Ready || !self.motor_type.has_commutation() {
+            return false;
+        }
+        // The commanded angle leads the rotor by up to 90°, homing starts from where the rotor is
+        self.homing.start(config, self.rotor_el, self.position.position());
+        true
+    }
+
+    /// Select the current decay of stepper coils (slow by default), fast and mixed decay need the HAL
+    /// to apply `inverted_channels` with every PWM update.
+    #[inline(always)]
+    pub fn set_decay_mode(&mut self, decay: DecayMode) {
+        self.motor.set_decay(decay);
+    }
+
+    /// Get the current decay of stepper coils.
+    #[inline(always)]
+    pub fn decay_mode(&self) -> DecayMode {
+        self.motor.decay()
+    }
+
+    /// Get the output channels of the last tick to run with inverted timer polarity (bit n - channel
+    /// n + 1), none while the outputs are stopped or asleep.
+    #[inline(always)]
+    pub fn inverted_channels(&self) -> u8 {
+        if self.safe_stop.outputs().is_some() || self.standby.is_sleeping() {
+            return 0;
+        }
+        self.motor.inverted_channels()
+    }
+
+    /// Set the spread-spectrum dither of the PWM edges (Q15 duty, up to 5%), 0 (default) disables it.
+    /// The coil voltages are unchanged, the EMI and the PWM tone spread over a band.
+    #[inline(always)]
+    pub fn set_pwm_dither(&mut self, depth: i16) {
+        self.motor.set_dither(depth);
+    }
+
+    /// Get the spread-spectrum dither depth (Q15 duty).
+    #[inline(always)]
+    pub fn pwm_dither(&self) -> i16 {
+        self.motor.dither()
+    }
+
+    /// Get access to the beeper playing tones through the windings (status, fault codes).
+    #[inline(always)]
+    pub fn beeper(&mut self) -> &mut Beeper {
+        self.motor.beeper()
+    }
+
+    /// Start the haptic knob: torque mode with the setpoint synthesized from the effects.
+    ///
+    /// Returns false unless the driver is ready and not homing. Any motion is stopped first,
+    /// a later motion command ends the haptics.
+    pub fn start_haptics(&mut self, config: HapticConfig) -> bool {
+        if self.driver_status != DriverStatus::Ready || self.homing.is_active() {
+            return false;
+        }
+        self.stop_trajectory();
+        self.haptics.start(config);
+        true
+    }
+
+    /// Stop the haptic knob, the axis is left in torque mode without torque.
+    #[inline(always)]
+    pub fn stop_haptics(&mut self) {
+        if self.haptics.is_active() {
+            self.haptics.stop();
+            self.cascade.set_torque(0);
+        }
+    }
+
+    /// Get the haptic knob state (synthesized torque, nearest detent).
+    #[inline(always)]
+    pub fn haptics(&self) -> &Haptics {
+        &self.haptics
+    }
+
+    /// Start relay auto-tuning of the velocity loop, the proposed gains are not applied.
+    ///
+    /// Returns false unless the driver is ready and not homing. Any motion is stopped first.
+    #[inline(always)]
+    pub fn start_autotune(&mut self, config: AutotuneConfig) -> bool {
+        if self.driver_status != DriverStatus::Ready || self.homing.is_active() || self.ident.is_active() {
+            return false;
+        }
+        self.stop_trajectory();
+        self.autotune.start(config);
+        true
+    }
+
+    /// Abort auto-tuning, the velocity loop holds the axis at rest.
+    #[inline(always)]
+    pub fn cancel_autotune(&mut self) {
+        if self.autotune.is_active() {
+            self.autotune.cancel();
+            self.cascade.reset(self.loop_position());
+            self.cascade.set_velocity(0);
+        }
+    }
+
+    /// Get the auto-tuning stage.
+    #[inline(always)]
+    pub fn autotune_stage(&self) -> AutotuneStage {
+        self.autotune.stage()
+    }
+
+    /// Get the measured limit cycle and the proposed velocity loop gains.
+    #[inline(always)]
+    pub fn autotune_result(&self) -> AutotuneResult {
+        self.autotune.result()
+    }
+
+    /// Start inertia and friction identification with a torque profile (the axis must move freely).
+    ///
+    /// Returns false unless the driver is ready and no homing or auto-tuning runs.
+    #[inline(always)]
+    pub fn start_identification(&mut self, config: IdentConfig) -> bool {
+        if self.driver_status != DriverStatus::Ready || self.homing.is_active() || self.autotune.is_active() {
+            return false;
+        }
+        self.stop_trajectory();
+        self.ident.start(config, self.cascade.velocity());
+        true
+    }
+
+    /// Abort the identification, the velocity loop holds the axis at rest.
+    #[inline(always)]
+    pub fn cancel_identification(&mut self) {
+        if self.ident.is_active() {
+            self.ident.cancel();
+            self.cascade.reset(self.loop_position());
+            self.cascade.set_velocity(0);
+        }
+    }
+
+    /// Get the identification stage.
+    #[inline(always)]
+    pub fn identification_stage(&self) -> IdentStage {
+        self.ident.stage()
+    }
+
+    /// Get the identified inertia (acceleration per ampere) and friction, e.g. for `set_observer()`.
+    #[inline(always)]
+    pub fn mechanics(&self) -> MechanicsReport {
+        self.ident.report()
+    }
+
+    /// Abort homing, leaving the position as it is.
+    #[inline(always)]
+    pub fn cancel_homing(&mut self) {
+        self.homing.cancel();
+    }
+
+    /// Check if homing is required before absolute position commands are accepted again
+    /// (encoder reported a lost multi-turn position).
+    #[inline(always)]
+    pub fn homing_required(&self) -> bool {
+        self.backup.rehome_required()
+    }
+
+    /// Check if the absolute encoder reports a low backup battery.
+    #[inline(always)]
+    pub fn encoder_battery_low(&self) -> bool {
+        self.backup.battery_low()
+    }
+
+    /// Accept the current position without homing (e.g. restored from an external reference).
+    #[inline(always)]
+    pub fn acknowledge_homing_required(&mut self) {
+        self.backup.acknowledge();
+    }
+
+    /// Get current homing stage.
+    #[inline(always)]
+    pub fn homing_stage(&self) -> HomingStage {
+        self.homing.stage()
+    }
+
+    /// Get position relative to the zero point found by homing.
+    #[inline(always)]
+    pub fn position(&self) -> i32 {
+        self.position.from_zero()
+    }
+
+    /// Get the 64-bit position relative to the zero point, never wraps on long-travel axes.
+    #[inline(always)]
+    pub fn position_i64(&self) -> i64 {
+        self.position.position_from_zero()
+    }
+
+    /// Get full turns relative to the zero point (floor).
+    #[inline(always)]
+    pub fn turns(&self) -> i64 {
+        self.position.turns_from_zero()
+    }
+
+    /// Mark the current position as zero.
+    #[inline(always)]
+    pub fn set_zero(&mut self) {
+        self.set_position_offset(0);
+    }
+
+    /// Make the current position read `position` counts from zero, e.g. an absolute position
+    /// restored after power-up. Any motion stops and the axis holds where it is.
+    pub fn set_position_offset(&mut self, position: i64) {
+        self.stop_trajectory();
+        self.position.set_offset(position);
+        self.cascade.reset(self.loop_position()); // Keep loops bumpless
+    }
+
+    /// Close the position loop on a load-side encoder (`load_angle` of the inputs), the motor
+    /// encoder keeps commutating. None returns to the motor encoder only.
+    ///
+    /// Returns false if the ratio has a zero turn count. The loop continues bumpless from the
+    /// present position; a mismatch beyond the backlash window raises a fault.
+    pub fn set_dual_encoder(&mut self, config: Option<DualEncoderConfig>) -> bool {
+        if !self.load.configure(config) {
+            return false;
+        }
+        self.stop_trajectory();
+        self.cascade.reset(self.loop_position());
+        true
+    }
+
+    /// Set the backlash compensation of the position feedback (directional offset, or crossover to
+    /// the load encoder configured with `set_dual_encoder`). The loop continues bumpless.
+    pub fn set_backlash(&mut self, config: BacklashConfig) {
+        self.backlash.configure(config);
+        self.cascade.reset(self.loop_position());
+    }
+
+    /// Get the backlash compensation configuration.
+    #[inline(always)]
+    pub fn backlash(&self) -> BacklashConfig {
+        self.backlash.config()
+    }
+
+    /// Get the cross-check of motor and load encoder (mismatch, peak, load position, fault).
+    #[inline(always)]
+    pub fn dual_encoder(&self) -> DualEncoderReport {
+        self.load.report()
+    }
+
+    /// Get the load position in motor counts relative to the zero point, the motor position if
+    /// no load encoder is configured.
+    #[inline(always)]
+    pub fn load_position(&self) -> i32 {
+        self.loop_position()
+    }
+
+    /// Arm (or disarm) moving the zero point onto the next encoder index edge, e.g. at startup or
+    /// while homing slowly towards the index. Any motion stops when the zero point moves.
+    #[inline(always)]
+    pub fn set_index_rezero(&mut self, armed: bool) {
+        self.index.arm_rezero(armed);
+    }
+
+    /// Check if the next index edge moves the zero point.
+    #[inline(always)]
+    pub fn index_rezero_armed(&self) -> bool {
+        self.index.is_armed()
+    }
+
+    /// Get the position latched at the last encoder index edge (counts from zero), None before the
+    /// first edge.
+    #[inline(always)]
+    pub fn index_position(&self) -> Option<i64> {
+        self.index.latched()
+    }
+
+    /// Get the number of encoder index edges since start.
+    #[inline(always)]
+    pub fn index_edges(&self) -> u32 {
+        self.index.edges()
+    }
+
+    /// Arm or disarm the external trigger position capture (armed by default).
+    #[inline(always)]
+    pub fn arm_capture(&mut self, armed: bool) {
+        self.capture.arm(armed);
+    }
+
+    /// Take the oldest trigger position snapshot (position from zero, velocity, tick), None if
+    /// the FIFO is empty.
+    #[inline(always)]
+    pub fn pop_capture(&mut self) -> Option<Capture> {
+        self.capture.pop()
+    }
+
+    /// Get the number of waiting snapshots and of snapshots lost to a full FIFO.
+    #[inline(always)]
+    pub fn capture_status(&self) -> (usize, u32) {
+        (self.capture.len(), self.capture.dropped())
+    }
+
+    /// Discard the waiting snapshots and clear the lost counter.
+    #[inline(always)]
+    pub fn clear_captures(&mut self) {
+        self.capture.clear();
+    }
+
+    /// Set linear encoder resolution used for micrometer conversions (nm per count).
+    #[inline(always)]
+    pub fn set_linear_resolution(&mut self, nm_per_count: i32) {
+        self.linear = LinearScale::new(nm_per_count);
+    }
+
+    /// Get position relative to the zero point in micrometers (linear actuators).
+    #[inline(always)]
+    pub fn position_um(&self) -> i32 {
+        self.counts_to_um(self.position.from_zero())
+    }
+
+    /// Set the gear ratio (motor turns : load turns) and user units per load revolution used by
+    /// `position_units()` / `velocity_units()`. Returns false if a value is zero.
+    pub fn set_unit_scale(&mut self, motor_turns: i32, load_turns: i32, units_per_rev: i32) -> bool {
+        match UnitScale::with_ratio(motor_turns, load_turns, units_per_rev) {
+            Some(units) => {
+                self.units = units;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the gear ratio and user unit scale, e.g. to convert setpoints to counts.
+    #[inline(always)]
+    pub fn unit_scale(&self) -> UnitScale {
+        self.units
+    }
+
+    /// Get position relative to the zero point in user units (exact, from the 64-bit position).
+    #[inline(always)]
+    pub fn position_units(&self) -> i64 {
+        self.units.to_units(self.position.position_from_zero())
+    }
+
+    /// Get measured velocity in user units/s.
+    #[inline(always)]
+    pub fn velocity_units(&self) -> i32 {
+        self.units.velocity_to_units(self.cascade.velocity())
+    }
+
+    /// Get measured velocity in µm/s (linear actuators).
+    #[inline(always)]
+    pub fn velocity_um(&self) -> i32 {
+        self.counts_to_um(self.cascade.velocity())
+    }
+
+    /// Switch the cascade to position mode with the setpoint in micrometers from zero.
+    #[inline(always)]
+    pub fn set_target_position_um(&mut self, position_um: i32) {
+        self.set_target_position(self.um_to_counts(position_um));
+    }
+
+    /// Switch the cascade to velocity mode with the setpoint in µm/s.
+    #[inline(always)]
+    pub fn set_velocity_um(&mut self, velocity_um: i32) {
+        self.set_velocity(self.um_to_counts(velocity_um));
+    }
+
+    /// Set soft travel limits in micrometers from zero (linear actuators).
+    #[inline(always)]
+    pub fn set_soft_limits_um(&mut self, min_um: i32, max_um: i32) {
+        let (min, max) = (self.um_to_counts(min_um), self.um_to_counts(max_um));
+        self.limits.set_soft_limits(min, max);
+    }
+
+    /// Set the physical units of the load: torque constant, gear ratio, counts per motor revolution
+    /// and lead of a screw or belt. With a lead, the micrometer API converts through it instead of
+    /// the linear encoder resolution. Returns false for an invalid configuration.
+    pub fn set_physical_units(&mut self, config: PhysicalUnitsConfig) -> bool {
+        match PhysicalUnits::with_config(config) {
+            Some(physical) => {
+                self.physical = physical;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the physical units conversion, e.g. to convert limits to counts.
+    #[inline(always)]
+    pub fn physical_units(&self) -> PhysicalUnits {
+        self.physical
+    }
+
+    /// Switch the cascade to torque mode with a load torque setpoint (mN·m).
+    ///
+    /// Returns false while the torque constant is unknown.
+    pub fn set_torque_mnm(&mut self, torque: i32) -> bool {
+        if self.physical.kt() == 0 {
+            return false;
+        }
+        self.set_torque(self.physical.torque_to_current(torque));
+        true
+    }
+
+    /// Switch the cascade to velocity mode with the load speed setpoint in mrad/s.
+    #[inline(always)]
+    pub fn set_velocity_mrad(&mut self, speed: i32) {
+        self.set_velocity(self.physical.mrad_to_velocity(speed));
+    }
+
+    /// Switch the cascade to position mode with the load angle setpoint in mrad from zero.
+    #[inline(always)]
+    pub fn set_target_position_mrad(&mut self, angle: i32) {
+        let position = self.physical.mrad_to_counts(angle as i64);
+        self.set_target_position(position.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+    }
+
+    /// Start a smooth point-to-point move to the load angle `target` (mrad from zero).
+    #[inline(always)]
+    pub fn move_to_mrad(&mut self, target: i32) {
+        let target = self.physical.mrad_to_counts(target as i64);
+        self.move_to(target.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+    }
+
+    /// Start a smooth point-to-point move to `target` (µm from zero).
+    #[inline(always)]
+    pub fn move_to_um(&mut self, target: i32) {
+        self.move_to(self.um_to_counts(target));
+    }
+
+    /// Get the load torque of the current command (mN·m), 0 while the torque constant is unknown.
+    #[inline(always)]
+    pub fn torque_mnm(&self) -> i32 {
+        self.physical.current_to_torque(self.cascade.current())
+    }
+
+    /// Get the measured load speed (mrad/s).
+    #[inline(always)]
+    pub fn velocity_mrad(&self) -> i32 {
+        self.physical.velocity_to_mrad(self.cascade.velocity())
+    }
+
+    /// Get the load angle relative to the zero point (mrad, from the 64-bit position).
+    #[inline(always)]
+    pub fn position_mrad(&self) -> i64 {
+        self.physical.counts_to_mrad(self.position.position_from_zero())
+    }
+
+    /// Convert micrometers (or µm/s) to counts through the lead, or the linear encoder without one.
+    fn um_to_counts(&self, um: i32) -> i32 {
+        if self.physical.has_lead() {
+            self.physical.um_to_counts(um as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+        } else {
+            self.linear.to_counts(um)
+        }
+    }
+
+    /// Convert counts (or counts/s) to micrometers through the lead, or the linear encoder without one.
+    fn counts_to_um(&self, counts: i32) -> i32 {
+        if self.physical.has_lead() {
+            self.physical.counts_to_um(counts as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+        } else {
+            self.linear.to_um(counts)
+        }
+    }
+
+    /// Get current PWM signals.
+    #[inline(always)]
+    pub fn get_pwm(&mut self) -> [i16; 4] {
+        self.motor.get_control()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use motor_driver::gate_fault::GATE_NFAULT;
+
+    /// Host model of an axis: a rotor with one pole pair, so encoder counts are electrical angle.
+    struct Axis {
+        driver: MotorController,
+        rotor: u16, // Rotor angle fed to the encoder input
+    }
+
+    impl Axis {
+        /// Stepper aligned by the quick alignment, with the rotor locked to the field meanwhile.
+        fn ready() -> Self {
+            let mut driver = MotorController::new(MotorType::STEP, PhasePattern::ABCD, 20000, 24000, 1500);
+            driver.set_calibration(CalibrationConfig { stages: CAL_ALIGNMENT, ..CalibrationConfig::new() });
+            assert!(driver.set_quick_align(1));
+            let mut axis = Self { driver, rotor: 0 };
+            for _ in 0..10_000 {
+                axis.tick(DataInputs::default());
+                axis.rotor = axis.driver.angle_el;
+                if axis.driver.status() == DriverStatus::Ready {
+                    break;
+                }
+            }
+            assert_eq!(axis.driver.status(), DriverStatus::Ready);
+            axis.tick(DataInputs::default());
+            axis
+        }
+
+        /// Runs one control tick with the rotor angle on the encoder input, returns the PWM outputs.
+        fn tick(&mut self, input: DataInputs) -> [i16; 4] {
+            self.driver.tick(1000, DataInputs { angle_raw: self.rotor, supply_adc: 0x8000, ..input })
+        }
+
+        /// Moves the rotor towards the field as a free rotor would, lagging by 1/256 of the distance.
+        fn follow_field(&mut self) {
+            let lag = Angle16(self.driver.angle_el).diff(Angle16(self.rotor)) as i32;
+            self.rotor = Angle16(self.rotor).offset(lag / 256).0;
+        }
+
+        /// Starts a routine and runs it for a few ticks.
+        fn start(&mut self, routine: Routine) {
+            match routine {
+                Routine::Homing => assert!(self.driver.start_homing(HomingConfig::default())),
+                Routine::StepDir => self.driver.set_motion_source(MotionSource::StepDir),
+                Routine::Autotune => assert!(self.driver.start_autotune(AutotuneConfig::default())),
+            }
+            for _ in 0..10 {
+                self.tick(DataInputs::default());
+                if routine == Routine::Homing {
+                    self.follow_field();
+                }
+            }
+            assert!(self.running(routine), "{:?} did not start", routine);
+        }
+
+        /// Returns true while the routine still drives the axis.
+        fn running(&self, routine: Routine) -> bool {
+            match routine {
+                Routine::Homing => self.driver.homing.is_active(),
+                Routine::StepDir => self.driver.step_engaged,
+                Routine::Autotune => self.driver.autotune.is_active(),
+            }
+        }
+    }
+
+    /// Routines a fault has to override.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Routine {
+        Homing,
+        StepDir,
+        Autotune,
+    }
+
+    const ROUTINES: [Routine; 3] = [Routine::Homing, Routine::StepDir, Routine::Autotune];
+
+    #[test]
+    fn homing_starts_from_the_measured_rotor_angle() {
+        let mut axis = Axis::ready();
+        // Holding still, the commanded angle leads the rotor by 90°
+        assert_eq!(Angle16(axis.driver.angle_el).diff(Angle16(axis.rotor)).unsigned_abs(), 16384);
+
+        assert!(axis.driver.start_homing(HomingConfig::default()));
+        for _ in 0..2000 {
+            axis.tick(DataInputs::default());
+            axis.follow_field();
+        }
+        assert_eq!(axis.driver.homing_stage(), HomingStage::Seeking); // Free axis: no stall
+    }
+
+    #[test]
+    fn gate_fault_overrides_the_running_routine() {
+        let faulted = DataInputs { gate_status: GATE_NFAULT, ..DataInputs::default() };
+        for routine in ROUTINES {
+            let mut axis = Axis::ready();
+            axis.driver.set_gate_fault_policy(GateFaultConfig { retries: 1, cooldown_ms: 1, rearm_ms: 10 });
+            axis.start(routine);
+
+            axis.tick(faulted);
+            assert_eq!(axis.driver.status(), DriverStatus::Error, "{:?}", routine);
+            assert!(!axis.running(routine), "{:?} survived the trip", routine);
+            assert_eq!(axis.driver.command.1, 0, "{:?}", routine);
+
+            // Flags clear: the automatic retry restarts the axis without resuming the routine
+            for _ in 0..100 {
+                axis.tick(DataInputs::default());
+            }
+            assert_eq!(axis.driver.status(), DriverStatus::Ready, "{:?}", routine);
+            assert!(!axis.running(routine), "{:?} resumed after the retry", routine);
+            assert_eq!(axis.driver.motion_source(), MotionSource::Internal);
+        }
+    }
+
+    #[test]
+    fn estop_overrides_the_running_routine() {
+        let pressed = DataInputs { estop: true, ..DataInputs::default() };
+        for routine in ROUTINES {
+            let mut axis = Axis::ready();
+            axis.start(routine);
+
+            axis.tick(pressed);
+            assert!(axis.driver.estop_latched());
+            assert!(axis.driver.safe_stop.is_active(), "{:?}", routine);
+            assert!(!axis.running(routine), "{:?} survived the e-stop", routine);
+
+            // The ramp brings the axis at rest, then the outputs coast
+            let mut outputs = [0; 4];
+            for _ in 0..2000 {
+                outputs = axis.tick(pressed);
+            }
+            assert_eq!(outputs, [i16::MIN; 4], "{:?}", routine);
+            assert!(!axis.driver.release_stop());
+            assert!(!axis.driver.reset_estop(), "{:?}: reset while the input is active", routine);
+
+            axis.tick(DataInputs::default());
+            assert!(axis.driver.estop_latched()); // Releasing the button alone does not re-arm
+            assert!(axis.driver.reset_estop());
+            axis.tick(DataInputs::default());
+            assert_eq!(axis.driver.status(), DriverStatus::Ready, "{:?}", routine);
+            assert!(!axis.driver.safe_stop.is_active());
+            assert!(!axis.running(routine), "{:?} resumed after the reset", routine);
+            assert_eq!(axis.driver.motion_source(), MotionSource::Internal);
+        }
+    }
+
+    #[test]
+    fn phase_voltage_of_a_high_resistance_winding() {
+        let mut driver = MotorController::new(MotorType::BLDC, PhasePattern::ABCD, 20000, 24000, 100_000);
+        driver.command = (16384, i16::MAX); // 32.767 A through 100 Ohm, far beyond the i32 product
+        let ((alpha, beta), _) = driver.phase_vectors();
+        assert!((alpha - 3_276_700).abs() <= 100, "alpha {} mV", alpha); // Table peak is just below 1.0
+        assert!(beta.abs() <= 1);
     }
 }
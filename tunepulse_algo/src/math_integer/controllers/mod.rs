@@ -1 +0,0 @@
-pub mod pid;
\ No newline at end of file
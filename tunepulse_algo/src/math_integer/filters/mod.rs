@@ -1 +0,0 @@
-pub mod lpf;
\ No newline at end of file
@@ -1,8 +0,0 @@
-pub mod trigonometry;
-pub mod normalization;
-pub mod ohms_law;
-pub mod filters;
-pub mod controllers;
-pub mod motion;
-pub mod fifo_buffer;
-pub mod motor;
\ No newline at end of file
@@ -1,2 +0,0 @@
-pub mod position_integrator;
-pub mod speed_estimator;
\ No newline at end of file
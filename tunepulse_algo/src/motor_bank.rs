@@ -0,0 +1,424 @@
+// Implements the motor bank, a container driving several motor controllers of a multi-axis board
+// from one control tick.
+
+// Key Features:
+// - Owns a fixed number of MotorController instances (const generic, no allocation).
+// - Ticks all axes with one shared supply voltage measurement.
+// - Aggregates driver states into a fault mask and an overall ready flag.
+// - Provides per-axis access for configuration and commands.
+// - Coordinated cartesian moves and feedback for CoreXY / H-bot axis pairs.
+// - Regen-aware coordinated stop keeping the braking power fed back to the shared bus limited.
+// - Supply power / current budget shared by all axes (priority or proportional arbitration).
+
+// Detailed Operation:
+// All axes of a board share the same supply rail, so the bank takes a single supply ADC reading and
+// substitutes it into the inputs of every axis before ticking it. This keeps the voltage
+// compensation of all axes consistent even if only one ADC channel samples the rail. After every
+// tick the bank collects the driver state of each axis: bit N of the fault mask is set while axis N
+// is in the Error state.
+// Coordinated moves scale the profile limits of both motors by their share of the travel, so both
+// profiles start and finish together and the path stays a straight line in X/Y.
+// A coordinated stop ramps the velocity of every axis down instead of stopping all of them at
+// once. The regen power of each axis is estimated from its torque current and velocity
+// (P = Kt * I * ω, positive while braking). Axes start decelerating one after another, the next one
+// only once the previous had `stagger_ticks` to build up its braking and the total is below the
+// limit. If the total still exceeds the limit, the deceleration of all braking axes is scaled down
+// by limit / total, since the braking power of a ramp is proportional to its deceleration.
+// The power arbiter splits a supply budget into per-axis current limits before each tick. A power
+// budget is converted into current with the measured supply voltage. The phase current is used as
+// an upper bound of the supply current (the bridge only steps the voltage down), so the budget is
+// conservative. Proportional mode scales all requested limits by the same factor once their sum
+// exceeds the budget. Priority mode serves axes in priority order: each one gets its requested
+// limit reduced by what the higher priority axes actually draw, so idle axes don't block others.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::inputs_dump::DataInputs;
+use crate::kinematics::CoreXY;
+use crate::motor_driver::DriverStatus;
+use crate::MotorController;
+
+/// Container of `N` motor axes ticked together.
+pub struct MotorBank<const N: usize> {
+    axes: [MotorController; N],
+    faults: u32, // Bit N is set while axis N is in the Error state
+    regen: RegenCoordinator<N>,
+    arbiter: PowerArbiter<N>,
+}
+
+impl<const N: usize> MotorBank<N> {
+    /// Creates a bank from already configured axes.
+    pub fn new(axes: [MotorController; N]) -> Self {
+        let frequency = axes.first().map_or(1, |axis| axis.frequency());
+        Self {
+            axes,
+            faults: 0,
+            regen: RegenCoordinator::new(frequency),
+            arbiter: PowerArbiter::new(),
+        }
+    }
+
+    /// Ticks all axes and returns their PWM outputs.
+    ///
+    /// # Arguments
+    /// * `currents` - Calibration current and current limit of each axis (mA)
+    /// * `inputs` - Sensor data snapshot of each axis
+    /// * `supply_adc` - Shared supply voltage ADC reading, overrides `supply_adc` of the inputs
+    pub fn tick(
+        &mut self,
+        currents: [i32; N],
+        inputs: [DataInputs; N],
+        supply_adc: u16,
+    ) -> [[i16; 4]; N] {
+        let mut pwm = [[0i16; 4]; N];
+        self.faults = 0;
+
+        // Share the supply budget using the current draw of the previous tick
+        let demands = self.axes.each_ref().map(|axis| axis.torque_current());
+        let supply_mv = self.axes.first().map_or(0, |axis| axis.supply_mv());
+        let currents = self.arbiter.allocate(currents, demands, supply_mv);
+
+        // Coordinated stop: ramp setpoints from the feedback of the previous tick
+        if self.regen.is_active() {
+            let velocities = self.axes.each_ref().map(|axis| axis.velocity());
+            let torques = self.axes.each_ref().map(|axis| axis.torque_current());
+            self.regen.tick(velocities, torques);
+            for (i, axis) in self.axes.iter_mut().enumerate() {
+                axis.set_velocity(self.regen.setpoint(i));
+            }
+        }
+
+        for (i, axis) in self.axes.iter_mut().enumerate() {
+            let mut input = inputs[i];
+            input.supply_adc = supply_adc; // Same rail for every axis
+            pwm[i] = axis.tick(currents[i], input);
+            if axis.status() == DriverStatus::Error && i < 32 {
+                self.faults |= 1 << i;
+            }
+        }
+        pwm
+    }
+
+    /// Returns the axis with the given index.
+    #[inline(always)]
+    pub fn axis(&mut self, index: usize) -> &mut MotorController {
+        &mut self.axes[index]
+    }
+
+    /// Returns all axes.
+    #[inline(always)]
+    pub fn axes(&mut self) -> &mut [MotorController; N] {
+        &mut self.axes
+    }
+
+    /// Returns the fault mask (bit N is set while axis N is in the Error state).
+    #[inline(always)]
+    pub fn faults(&self) -> u32 {
+        self.faults
+    }
+
+    /// Returns true if any axis is in the Error state.
+    #[inline(always)]
+    pub fn any_fault(&self) -> bool {
+        self.faults != 0
+    }
+
+    /// Returns true if all axes are calibrated and running.
+    pub fn is_ready(&self) -> bool {
+        self.axes.iter().all(|axis| axis.status() == DriverStatus::Ready)
+    }
+
+    /// Starts a coordinated straight move of a CoreXY / H-bot axis pair from rest.
+    ///
+    /// # Arguments
+    /// * `kinematics` - Transform of the axis pair
+    /// * `axes` - Indices of motor A and motor B
+    /// * `target` - Cartesian target (x, y) in counts from zero
+    /// * `limits` - Velocity (counts/s), acceleration and deceleration (counts/s^2) of the motor
+    ///   with the longer travel
+    pub fn move_xy(
+        &mut self,
+        kinematics: &CoreXY,
+        axes: (usize, usize),
+        target: (i32, i32),
+        limits: (i32, i32, i32),
+    ) {
+        let (target_a, target_b) = kinematics.to_motors(target.0, target.1);
+        let dist_a = target_a.wrapping_sub(self.axes[axes.0].position()).unsigned_abs() as i64;
+        let dist_b = target_b.wrapping_sub(self.axes[axes.1].position()).unsigned_abs() as i64;
+        let longest = dist_a.max(dist_b).max(1);
+
+        // The longer motor move runs at the full limits, the shorter one is slowed down to finish together
+        for (index, distance, motor_target) in [(axes.0, dist_a, target_a), (axes.1, dist_b, target_b)] {
+            let scale = |value: i32| ((value as i64 * distance) / longest).max(1) as i32;
+            let axis = &mut self.axes[index];
+            axis.set_profile_limits(scale(limits.0), scale(limits.1), scale(limits.2));
+            axis.move_to(motor_target);
+        }
+    }
+
+    /// Returns the cartesian position (x, y) of a CoreXY / H-bot axis pair (counts from zero).
+    pub fn position_xy(&self, kinematics: &CoreXY, axes: (usize, usize)) -> (i32, i32) {
+        kinematics.to_cartesian(self.axes[axes.0].position(), self.axes[axes.1].position())
+    }
+
+    /// Returns the cartesian velocity (x, y) of a CoreXY / H-bot axis pair (counts/s).
+    pub fn velocity_xy(&self, kinematics: &CoreXY, axes: (usize, usize)) -> (i32, i32) {
+        kinematics.to_cartesian(self.axes[axes.0].velocity(), self.axes[axes.1].velocity())
+    }
+
+    /// Configures the regen limit of coordinated stops.
+    ///
+    /// # Arguments
+    /// * `limit_mw` - Maximum total braking power fed back to the bus (mW)
+    /// * `kt` - Torque constant of each axis (mNm/A), 0 excludes the axis from the estimate
+    pub fn set_regen_limit(&mut self, limit_mw: i32, kt: [i32; N]) {
+        self.regen.configure(limit_mw, kt);
+    }
+
+    /// Sets the pause between deceleration starts of consecutive axes (ticks).
+    pub fn set_regen_stagger(&mut self, ticks: u32) {
+        self.regen.stagger_ticks = ticks;
+    }
+
+    /// Stops all axes with a coordinated deceleration (counts/s^2) limiting the total regen.
+    ///
+    /// Axes end in velocity mode with zero setpoint.
+    pub fn stop_all(&mut self, decel: i32) {
+        let velocities = self.axes.each_ref().map(|axis| axis.velocity());
+        self.regen.start(velocities, decel);
+    }
+
+    /// Returns true while a coordinated stop is running.
+    #[inline(always)]
+    pub fn is_stopping(&self) -> bool {
+        self.regen.is_active()
+    }
+
+    /// Returns the estimated total regen power of the last tick (mW).
+    #[inline(always)]
+    pub fn regen_power(&self) -> i32 {
+        self.regen.power
+    }
+
+    /// Selects how the supply budget is shared between axes.
+    ///
+    /// # Arguments
+    /// * `mode` - Arbitration mode
+    /// * `priority` - Priority of each axis for `ArbiterMode::Priority` (0 is served first)
+    pub fn set_arbiter(&mut self, mode: ArbiterMode, priority: [u8; N]) {
+        self.arbiter.mode = mode;
+        self.arbiter.priority = priority;
+    }
+
+    /// Sets the supply budget as a current (mA), shared by all axes.
+    pub fn set_current_budget(&mut self, budget_ma: i32) {
+        self.arbiter.budget = Budget::Current(budget_ma.max(0));
+    }
+
+    /// Sets the supply budget as a power (mW), converted with the measured supply voltage.
+    pub fn set_power_budget(&mut self, budget_mw: i32) {
+        self.arbiter.budget = Budget::Power(budget_mw.max(0));
+    }
+
+    /// Returns the current limits granted to the axes on the last tick (mA).
+    #[inline(always)]
+    pub fn granted_currents(&self) -> [i32; N] {
+        self.arbiter.granted
+    }
+
+    /// Returns the number of axes.
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns true if the bank has no axes.
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+}
+
+/// Sharing rule of the supply budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArbiterMode {
+    /// Current limits are passed through unchanged
+    Off,
+    /// Axes are served in priority order with what the others leave
+    Priority,
+    /// All limits are scaled by the same factor
+    Proportional,
+}
+
+/// Supply budget of the power arbiter.
+#[derive(Debug, Clone, Copy)]
+enum Budget {
+    Current(i32), // mA
+    Power(i32),   // mW
+}
+
+/// Distributes a supply budget into per-axis current limits.
+struct PowerArbiter<const N: usize> {
+    mode: ArbiterMode,
+    budget: Budget,
+    priority: [u8; N], // Priority of each axis (0 is served first)
+    granted: [i32; N], // Current limits granted on the last tick (mA)
+}
+
+impl<const N: usize> PowerArbiter<N> {
+    fn new() -> Self {
+        Self {
+            mode: ArbiterMode::Off,
+            budget: Budget::Current(i32::MAX),
+            priority: [0; N],
+            granted: [0; N],
+        }
+    }
+
+    /// Returns the current limits of the axes within the budget.
+    ///
+    /// # Arguments
+    /// * `requested` - Current limits requested for each axis (mA)
+    /// * `demands` - Torque current drawn by each axis on the last tick (mA)
+    /// * `supply_mv` - Supply voltage (mV)
+    fn allocate(&mut self, requested: [i32; N], demands: [i32; N], supply_mv: i32) -> [i32; N] {
+        let budget = match self.budget {
+            Budget::Current(ma) => ma as i64,
+            Budget::Power(mw) => mw as i64 * 1000 / supply_mv.max(1000) as i64,
+        };
+        let requested = requested.map(|limit| limit.max(0));
+
+        self.granted = match self.mode {
+            ArbiterMode::Off => requested,
+            ArbiterMode::Proportional => {
+                let total: i64 = requested.iter().map(|limit| *limit as i64).sum();
+                if total > budget {
+                    requested.map(|limit| (limit as i64 * budget / total) as i32)
+                } else {
+                    requested
+                }
+            }
+            ArbiterMode::Priority => {
+                let mut granted = [0i32; N];
+                let mut served = [false; N];
+                let mut remaining = budget;
+                for _ in 0..N {
+                    // Highest priority axis not served yet (lowest index on ties)
+                    let mut next = 0;
+                    let mut best = u16::MAX;
+                    for (i, (done, priority)) in served.iter().zip(self.priority).enumerate() {
+                        if !done && (priority as u16) < best {
+                            best = priority as u16;
+                            next = i;
+                        }
+                    }
+                    served[next] = true;
+                    granted[next] = (requested[next] as i64).min(remaining.max(0)) as i32;
+                    remaining -= (demands[next].saturating_abs() as i64).min(granted[next] as i64);
+                }
+                granted
+            }
+        };
+        self.granted
+    }
+}
+
+/// Coordinator of simultaneous decelerations sharing one supply bus.
+struct RegenCoordinator<const N: usize> {
+    frequency: i64,     // Update frequency (ticks per second)
+    limit_mw: i32,      // Maximum total regen power (mW)
+    kt: [i32; N],       // Torque constant of each axis (mNm/A)
+    stagger_ticks: u32, // Minimum ticks between deceleration starts of two axes
+    decel: i64,         // Nominal deceleration (counts/s^2)
+
+    active: bool,
+    started: [bool; N],   // Axis is decelerating
+    velocity: [i64; N],   // Ramp setpoint of each axis (counts/s, 16 fractional bits)
+    since_start: u32,     // Ticks since the last axis started decelerating
+    power: i32,           // Estimated total regen power (mW)
+}
+
+impl<const N: usize> RegenCoordinator<N> {
+    fn new(frequency: u16) -> Self {
+        Self {
+            frequency: frequency.max(1) as i64,
+            limit_mw: i32::MAX,
+            kt: [0; N],
+            stagger_ticks: frequency as u32 / 50, // 20 ms
+            decel: 1,
+            active: false,
+            started: [false; N],
+            velocity: [0; N],
+            since_start: 0,
+            power: 0,
+        }
+    }
+
+    fn configure(&mut self, limit_mw: i32, kt: [i32; N]) {
+        self.limit_mw = limit_mw.max(1);
+        self.kt = kt;
+    }
+
+    fn start(&mut self, velocities: [i32; N], decel: i32) {
+        self.decel = decel.saturating_abs().max(1) as i64;
+        self.velocity = velocities.map(|v| (v as i64) << 16);
+        self.started = velocities.map(|v| v == 0); // Axes at rest have nothing to brake
+        self.since_start = self.stagger_ticks;
+        self.active = true;
+        defmt::info!("REGEN: Coordinated stop started");
+    }
+
+    fn tick(&mut self, velocities: [i32; N], currents: [i32; N]) {
+        // ######################## REGEN ESTIMATE ###################################
+        // P [mW] = Kt [mNm/A] * I [A] * ω [rad/s], ω = v * 2π / 65536
+        let mut total: i64 = 0;
+        for i in 0..N {
+            let torque = self.kt[i] as i64 * currents[i] as i64; // µNm
+            let power = -((torque * velocities[i] as i64) >> 16) * 6283 / 1_000_000;
+            total += power.max(0);
+        }
+        self.power = total.min(i32::MAX as i64) as i32;
+
+        // ######################## STAGGERED START ##################################
+        self.since_start = self.since_start.saturating_add(1);
+        if self.since_start >= self.stagger_ticks && total < self.limit_mw as i64 {
+            if let Some(next) = self.started.iter().position(|started| !started) {
+                self.started[next] = true;
+                self.since_start = 0;
+            }
+        }
+
+        // ######################## RAMP DOWN ########################################
+        // Braking power scales with the deceleration, so the excess is removed proportionally
+        let scale = if total > self.limit_mw as i64 {
+            ((self.limit_mw as i64) << 16) / total
+        } else {
+            1 << 16
+        };
+        let step = ((self.decel * scale) / self.frequency).max(1);
+        for i in 0..N {
+            if self.started[i] {
+                let v = self.velocity[i];
+                self.velocity[i] = v - v.signum() * step.min(v.abs());
+            }
+        }
+
+        if self.started.iter().all(|s| *s) && self.velocity.iter().all(|v| *v == 0) {
+            self.active = false;
+            defmt::info!("REGEN: Coordinated stop finished");
+        }
+    }
+
+    #[inline(always)]
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns the ramp setpoint of the axis (counts/s).
+    #[inline(always)]
+    fn setpoint(&self, index: usize) -> i32 {
+        (self.velocity[index] >> 16) as i32
+    }
+}
@@ -0,0 +1,219 @@
+// Implements the relay (Åström–Hägglund) auto-tuning of the velocity loop, exciting a limit cycle
+// with a relay on the torque current and proposing PI gains from its period and amplitude.
+
+// Key Features:
+// - Relay with hysteresis on the measured velocity, symmetric around standstill.
+// - First cycles are discarded, the rest are averaged (period and velocity amplitude).
+// - Ultimate gain from the describing function of the relay, Ziegler–Nichols PI rules.
+// - Gains proposed in the scale of the cascade velocity loop, applying them is left to the caller.
+// - Timeout when the axis doesn't oscillate (blocked, relay too weak, hysteresis too wide).
+
+// Detailed Operation:
+// The relay drives +d mA while the velocity is below -h and -d above +h, so the loop oscillates at
+// its ultimate period Tu (phase lag of 180°). A cycle is the time between two switches to +d, its
+// velocity amplitude a is half the peak-to-peak swing. The describing function of the relay gives the
+// ultimate gain Ku = 4 * d / (π * a) (mA per count/s), and Ziegler–Nichols PI gives Kp = 0.45 * Ku and
+// Ti = Tu / 1.2. The cascade PID works on velocity errors of 16 counts/s per LSB with gains in percent,
+// so Kp = 0.45 * Ku * 16 * 100 = d * 916.7 / a, and the per-tick integral gain Ki = Kp * 1.2 / Tu (ticks).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Relay cycles ignored while the oscillation settles
+const SETTLE_CYCLES: u8 = 2;
+
+/// Settings of the relay experiment.
+#[derive(Debug, Clone, Copy)]
+pub struct AutotuneConfig {
+    /// Relay current (mA)
+    pub amplitude_ma: i32,
+    /// Relay hysteresis (counts/s)
+    pub hysteresis: i32,
+    /// Averaged cycles after the settling cycles
+    pub cycles: u8,
+    /// Maximum duration of the experiment (ticks)
+    pub timeout_ticks: u32,
+}
+
+impl Default for AutotuneConfig {
+    fn default() -> Self {
+        Self {
+            amplitude_ma: 300,
+            hysteresis: 1000,
+            cycles: 6,
+            timeout_ticks: 100_000, // 5 s at 20 kHz
+        }
+    }
+}
+
+/// Progress of the auto-tuning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutotuneStage {
+    /// Auto-tuning is not running
+    Idle,
+    /// Relay experiment in progress
+    Running,
+    /// Gains proposed
+    Done,
+    /// No usable oscillation within the timeout
+    Failed,
+}
+
+/// Measured limit cycle and proposed gains.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutotuneResult {
+    /// Ultimate period (ticks)
+    pub period_ticks: u32,
+    /// Velocity amplitude of the oscillation (counts/s)
+    pub amplitude: i32,
+    /// Proposed velocity loop Kp (PID scale, %)
+    pub kp: i32,
+    /// Proposed velocity loop Ki (PID scale, %)
+    pub ki: i32,
+}
+
+/// Relay auto-tuning of the velocity loop.
+pub struct Autotune {
+    config: AutotuneConfig,
+    stage: AutotuneStage,
+    relay: i32,           // Relay state: +1 or -1
+    ticks: u32,           // Ticks since the start
+    cycle_start: u32,     // Tick of the last switch to +d, 0 - no cycle started yet
+    extremes: (i32, i32), // (minimum, maximum) velocity of the running cycle
+    cycles: u8,           // Completed cycles
+    period_sum: u64,      // Summed periods of the averaged cycles (ticks)
+    swing_sum: i64,       // Summed peak-to-peak velocity of the averaged cycles (counts/s)
+    result: AutotuneResult,
+}
+
+impl Autotune {
+    /// Creates an idle auto-tuning.
+    pub fn new() -> Self {
+        Self {
+            config: AutotuneConfig::default(),
+            stage: AutotuneStage::Idle,
+            relay: 1,
+            ticks: 0,
+            cycle_start: 0,
+            extremes: (0, 0),
+            cycles: 0,
+            period_sum: 0,
+            swing_sum: 0,
+            result: AutotuneResult::default(),
+        }
+    }
+
+    /// Starts the relay experiment.
+    pub fn start(&mut self, config: AutotuneConfig) {
+        defmt::info!("AUTOTUNE: Relay {}mA, hysteresis {} counts/s", config.amplitude_ma, config.hysteresis);
+        *self = Self::new();
+        self.config = config;
+        self.stage = AutotuneStage::Running;
+    }
+
+    /// Stops the experiment without a result.
+    pub fn cancel(&mut self) {
+        if self.is_active() {
+            self.stage = AutotuneStage::Idle;
+        }
+    }
+
+    /// Advances the experiment and returns the relay current (mA).
+    ///
+    /// # Arguments
+    /// * `velocity` - Measured velocity (counts/s)
+    /// * `current_limit` - Maximum current amplitude (mA)
+    pub fn tick(&mut self, velocity: i32, current_limit: i32) -> i32 {
+        if !self.is_active() {
+            return 0;
+        }
+        self.ticks += 1;
+        self.extremes = (self.extremes.0.min(velocity), self.extremes.1.max(velocity));
+
+        // ####### Relay #######
+        let hysteresis = self.config.hysteresis.max(0);
+        if self.relay > 0 && velocity > hysteresis {
+            self.relay = -1;
+        } else if self.relay < 0 && velocity < -hysteresis {
+            self.relay = 1;
+            self.cycle_done();
+        }
+
+        if self.stage == AutotuneStage::Running && self.ticks >= self.config.timeout_ticks {
+            defmt::error!("AUTOTUNE: No oscillation within {} ticks", self.config.timeout_ticks);
+            self.stage = AutotuneStage::Failed;
+        }
+        if !self.is_active() {
+            return 0;
+        }
+        self.relay * self.config.amplitude_ma.clamp(0, current_limit.max(0))
+    }
+
+    /// Closes a relay cycle and finishes once enough cycles are averaged.
+    fn cycle_done(&mut self) {
+        if self.cycle_start != 0 {
+            self.cycles = self.cycles.saturating_add(1);
+            if self.cycles > SETTLE_CYCLES {
+                self.period_sum += (self.ticks - self.cycle_start) as u64;
+                self.swing_sum += self.extremes.1 as i64 - self.extremes.0 as i64;
+            }
+        }
+        self.cycle_start = self.ticks;
+        self.extremes = (i32::MAX, i32::MIN);
+
+        let averaged = self.cycles.saturating_sub(SETTLE_CYCLES);
+        if averaged >= self.config.cycles.max(1) {
+            self.finish(averaged as i64);
+        }
+    }
+
+    /// Computes the proposed gains from the averaged limit cycle.
+    fn finish(&mut self, cycles: i64) {
+        let period = (self.period_sum as i64 / cycles).max(1);
+        let amplitude = self.swing_sum / (2 * cycles);
+        if amplitude <= 0 {
+            self.stage = AutotuneStage::Failed;
+            return;
+        }
+        // Kp = 0.45 * Ku * 16 * 100 with Ku = 4 * d / (π * a)
+        let kp = self.config.amplitude_ma as i64 * 9167 / (10 * amplitude);
+        let ki = (kp * 12 + 5 * period) / (10 * period); // Rounded to nearest
+        self.result = AutotuneResult {
+            period_ticks: period as u32,
+            amplitude: amplitude.min(i32::MAX as i64) as i32,
+            kp: kp.min(10000) as i32,
+            ki: ki.min(10000) as i32,
+        };
+        self.stage = AutotuneStage::Done;
+        defmt::info!(
+            "AUTOTUNE: Period {} ticks, amplitude {} counts/s, proposed Kp {} Ki {}",
+            self.result.period_ticks,
+            self.result.amplitude,
+            self.result.kp,
+            self.result.ki
+        );
+    }
+
+    /// Returns the active stage.
+    pub fn stage(&self) -> AutotuneStage {
+        self.stage
+    }
+
+    /// Returns true while the relay drives the motor.
+    pub fn is_active(&self) -> bool {
+        self.stage == AutotuneStage::Running
+    }
+
+    /// Returns the measured limit cycle and proposed gains (valid once done).
+    pub fn result(&self) -> AutotuneResult {
+        self.result
+    }
+}
+
+impl Default for Autotune {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,178 @@
+// Implements the backlash compensation, letting geared axes reach a commanded position from either
+// direction by correcting the position feedback of the position loop.
+
+// Key Features:
+// - Directional offset: half the backlash shifts the feedback along the commanded direction.
+// - Offset ramped over a configurable time on a reversal, no torque step when the gap is crossed.
+// - Dual-loop crossover with a load encoder: motor encoder above, load encoder below a corner frequency.
+// - Direction taken from the setpoint, so the compensation doesn't chatter on feedback noise.
+
+// Detailed Operation:
+// With only the motor encoder, the load stays behind the motor by half the backlash while driven:
+// moving forward the load sits at motor - backlash / 2, moving backward at motor + backlash / 2.
+// The offset mode estimates the load as motor - direction * backlash / 2 and feeds this to the
+// position loop, which drives the motor the missing half gap further. The direction follows the sign
+// of the position setpoint steps and is held while the setpoint rests; after a reversal the offset
+// moves to the other side at backlash per `ramp_ms`, so the motor crosses the gap smoothly.
+// With a load encoder, closing the loop on the load alone makes it oscillate inside the gap. The
+// dual-loop mode feeds motor + LPF(load - motor): the motor encoder provides the fast, stiff feedback,
+// the low-passed difference pulls the axis onto the load position in steady state. The first-order
+// filter runs in Q16 with alpha = 2*pi*f / frequency; a crossover well below the mechanical resonance
+// keeps the loop stable on the motor side.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// 2 * pi in Q16
+const TWO_PI_Q16: i64 = 411_775;
+
+/// Compensation scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacklashMode {
+    /// Feedback passed through unchanged
+    Off,
+    /// Half the backlash along the commanded direction
+    Offset,
+    /// Motor encoder above, load encoder below the crossover frequency
+    DualLoop,
+}
+
+/// Configuration of the backlash compensation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BacklashConfig {
+    /// Compensation scheme
+    pub mode: BacklashMode,
+    /// Backlash of the gear train (motor counts), used by the offset mode
+    pub backlash: i32,
+    /// Time the offset needs to cross the full backlash after a reversal (ms)
+    pub ramp_ms: u16,
+    /// Crossover frequency from motor to load encoder (Hz), used by the dual-loop mode
+    pub crossover_hz: u16,
+}
+
+impl BacklashConfig {
+    /// Creates a disabled configuration, 20 ms ramp and 5 Hz crossover once enabled.
+    pub const fn new() -> Self {
+        Self {
+            mode: BacklashMode::Off,
+            backlash: 0,
+            ramp_ms: 20,
+            crossover_hz: 5,
+        }
+    }
+}
+
+impl Default for BacklashConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Backlash compensation of the position feedback.
+pub struct Backlash {
+    frequency: u16, // Update frequency (ticks per second)
+    config: BacklashConfig,
+
+    setpoint: i32,   // Position setpoint of the previous tick
+    started: bool,   // Setpoint seen at least once
+    direction: i32,  // Commanded direction (+1 / -1), held while the setpoint rests
+    offset: i32,     // Applied feedback offset (motor counts)
+    difference: i64, // Low-passed load - motor difference (Q16 counts)
+}
+
+impl Backlash {
+    /// Creates a disabled compensation.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: BacklashConfig::new(),
+            setpoint: 0,
+            started: false,
+            direction: 1,
+            offset: 0,
+            difference: 0,
+        }
+    }
+
+    /// Applies a configuration, the compensation starts over from the present position.
+    pub fn configure(&mut self, config: BacklashConfig) {
+        self.config = BacklashConfig { backlash: config.backlash.max(0), ..config };
+        self.offset = 0; // Ramped onto the commanded side from the first tick
+        self.reset();
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> BacklashConfig {
+        self.config
+    }
+
+    /// Restarts the setpoint tracking and the crossover filter from the next tick.
+    pub fn reset(&mut self) {
+        self.started = false;
+    }
+
+    /// Updates the direction, the offset and the crossover filter.
+    ///
+    /// # Arguments
+    /// * `setpoint` - Position setpoint of the loop (counts)
+    /// * `motor` - Motor encoder position (counts from zero)
+    /// * `load` - Load position in motor counts (the motor position without a load encoder)
+    pub fn tick(&mut self, setpoint: i32, motor: i32, load: i32) {
+        let half = self.config.backlash / 2;
+        let difference = (load.wrapping_sub(motor) as i64) << 16;
+        if !self.started {
+            self.started = true;
+            self.setpoint = setpoint;
+            self.difference = difference;
+            return;
+        }
+        let step = setpoint.wrapping_sub(self.setpoint);
+        self.setpoint = setpoint;
+        if step != 0 {
+            self.direction = step.signum();
+        }
+
+        match self.config.mode {
+            BacklashMode::Off => {}
+            BacklashMode::Offset => {
+                let ramp_ticks = (self.config.ramp_ms as i32 * self.frequency as i32 / 1000).max(1);
+                let rate = (self.config.backlash / ramp_ticks).max(1);
+                let error = self.direction * half - self.offset;
+                self.offset += error.clamp(-rate, rate);
+            }
+            BacklashMode::DualLoop => {
+                let alpha = TWO_PI_Q16 * self.config.crossover_hz as i64 / self.frequency.max(1) as i64;
+                let alpha = alpha.min(1 << 16);
+                self.difference += ((difference - self.difference) * alpha) >> 16;
+            }
+        }
+    }
+
+    /// Returns the position feedback of the loop (counts).
+    ///
+    /// # Arguments
+    /// * `motor` - Motor encoder position (counts from zero)
+    /// * `load` - Load position in motor counts (the motor position without a load encoder)
+    #[inline(always)]
+    pub fn feedback(&self, motor: i32, load: i32) -> i32 {
+        match self.config.mode {
+            BacklashMode::Off => load,
+            BacklashMode::Offset => load.wrapping_sub(self.offset),
+            BacklashMode::DualLoop if self.started => motor.wrapping_add((self.difference >> 16) as i32),
+            BacklashMode::DualLoop => load,
+        }
+    }
+
+    /// Returns the commanded direction (+1 / -1).
+    pub fn direction(&self) -> i32 {
+        self.direction
+    }
+
+    /// Returns the applied feedback offset (motor counts), offset mode only.
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+}
@@ -0,0 +1,154 @@
+// Implements the burst torque module, allowing short excursions above the continuous current
+// limit within an explicit duty-cycle budget.
+
+// Key Features:
+// - Peak current limit as a percentage of the continuous limit (e.g. 200% for 2x torque).
+// - Duty-cycle budget: burst duration allowed per period (e.g. 1 s at peak every 10 s).
+// - Smooth fallback to the continuous limit once the budget is used up, no hard switching.
+// - Per-axis report of the remaining budget, burst count and time spent limited.
+
+// Detailed Operation:
+// The budget is a bucket of excess charge (mA * ticks above the continuous limit). Its capacity is
+// what one full burst consumes: (peak - continuous) * burst_ticks. It is refilled at a constant
+// rate, capacity / (period_ticks - burst_ticks), so a full burst is available again after the rest
+// of the period, independent of how the load is distributed. Every tick the excess of the actual
+// current over the continuous limit is taken from the bucket, and the granted limit is the
+// continuous limit plus what is left in the bucket (up to the peak). With an empty bucket the axis
+// can only draw the excess it earns back in the same tick, so it settles at the continuous limit.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Settings of the burst torque budget.
+#[derive(Debug, Clone, Copy)]
+pub struct BurstConfig {
+    /// Peak current limit in percent of the continuous limit (100 - bursts disabled)
+    pub peak_pct: u16,
+    /// Duration of a full burst at the peak limit (ticks)
+    pub burst_ticks: u32,
+    /// Period in which one full burst is allowed (ticks)
+    pub period_ticks: u32,
+}
+
+impl Default for BurstConfig {
+    fn default() -> Self {
+        Self {
+            peak_pct: 100,          // Disabled
+            burst_ticks: 20_000,    // 1 s at 20 kHz
+            period_ticks: 200_000,  // 10 s at 20 kHz
+        }
+    }
+}
+
+/// Snapshot of the burst budget of an axis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BurstReport {
+    /// Remaining budget (0..100% of a full burst)
+    pub budget_pct: u8,
+    /// Current is above the continuous limit right now
+    pub active: bool,
+    /// Number of bursts started since startup
+    pub bursts: u32,
+    /// Ticks in which the axis was held at the continuous limit by an empty budget
+    pub limited_ticks: u32,
+}
+
+/// Duty-cycle limited current excursions above the continuous limit.
+pub struct BurstTorque {
+    config: BurstConfig,
+    bucket: i64,   // Remaining excess charge (mA * ticks)
+    capacity: i64, // Excess charge of one full burst (mA * ticks)
+    refill: i64,   // Excess charge earned back per tick (mA * ticks)
+    limit: i32,    // Continuous limit the capacity was computed for (mA)
+    report: BurstReport,
+}
+
+impl BurstTorque {
+    /// Creates a disabled burst manager.
+    pub fn new() -> Self {
+        Self {
+            config: BurstConfig::default(),
+            bucket: 0,
+            capacity: 0,
+            refill: 0,
+            limit: -1,
+            report: BurstReport::default(),
+        }
+    }
+
+    /// Sets the burst settings, the budget starts full.
+    pub fn configure(&mut self, config: BurstConfig) {
+        self.config = config;
+        self.limit = -1; // Recompute the capacity on the next tick
+    }
+
+    /// Returns the burst settings.
+    pub fn config(&self) -> BurstConfig {
+        self.config
+    }
+
+    /// Returns the current limit for this tick.
+    ///
+    /// # Arguments
+    /// * `current` - Torque current drawn on the last tick (mA)
+    /// * `continuous` - Continuous current limit (mA)
+    pub fn tick(&mut self, current: i32, continuous: i32) -> i32 {
+        let continuous = continuous.max(0);
+        let peak = (continuous as i64 * self.config.peak_pct.max(100) as i64 / 100) as i32;
+        if peak == continuous {
+            return continuous;
+        }
+        if continuous != self.limit {
+            self.rescale(continuous, peak);
+        }
+
+        // ######################## BUDGET ###########################################
+        let excess = (current.saturating_abs() - continuous).max(0) as i64;
+        if excess > 0 && !self.report.active {
+            self.report.bursts = self.report.bursts.saturating_add(1);
+        }
+        self.report.active = excess > 0;
+        self.bucket = (self.bucket - excess + self.refill).clamp(0, self.capacity);
+        if self.bucket <= self.refill && excess > 0 {
+            self.report.limited_ticks = self.report.limited_ticks.saturating_add(1);
+        }
+        self.report.budget_pct = (self.bucket * 100 / self.capacity.max(1)) as u8;
+
+        // Whatever is left in the bucket may be spent in this tick, up to the peak
+        (continuous as i64 + self.bucket).min(peak as i64) as i32
+    }
+
+    /// Recomputes the budget for a new continuous limit, keeping the relative fill.
+    fn rescale(&mut self, continuous: i32, peak: i32) {
+        let fill = if self.limit < 0 {
+            100
+        } else {
+            self.bucket * 100 / self.capacity.max(1)
+        };
+        let burst = self.config.burst_ticks.max(1) as i64;
+        let rest = (self.config.period_ticks as i64 - burst).max(1);
+        self.capacity = (peak - continuous) as i64 * burst;
+        self.refill = (self.capacity / rest).max(1);
+        self.bucket = self.capacity * fill / 100;
+        self.limit = continuous;
+        defmt::info!(
+            "BURST: {}mA for {} ticks per {} ticks",
+            peak,
+            self.config.burst_ticks,
+            self.config.period_ticks
+        );
+    }
+
+    /// Returns the budget report.
+    pub fn report(&self) -> BurstReport {
+        self.report
+    }
+}
+
+impl Default for BurstTorque {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,111 @@
+// Implements the bus power module, estimating the DC-bus current and electrical input power of an
+// axis without a bus current sensor.
+
+// Key Features:
+// - Measured estimate: bus current as the duty-weighted sum of the output currents.
+// - Model estimate: from the commanded current amplitude and winding resistance when the phase
+//   currents are not scaled (no current sense calibration to mA).
+// - Signed values: negative power is regeneration fed back into the supply.
+// - Averaged over a few PWM periods to suppress ripple.
+
+// Detailed Operation:
+// Every output leg connects its phase to the bus for its duty d of the period, so the average bus
+// current is sum(d_k * i_k) over the legs, with i_k the current flowing out of leg k into the motor.
+// This holds for any motor type and includes back-EMF (motoring or braking) and losses downstream of
+// the bridge. The model estimate is used when no current scale is set: in current mode the driver
+// applies V = I * R in phase with the current, so the input power is V * I for two coils (stepper),
+// 3/2 * V * I for three phases (amplitude-invariant vectors) and V * I for a single coil. It ignores
+// back-EMF and therefore reads too low while the motor moves fast. Power = bus current * supply.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Averaging strength: new sample weight is 1 / 2^AVG_SHIFT
+const AVG_SHIFT: u32 = 6;
+
+/// Bus current and power of an axis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BusReport {
+    /// Average DC-bus current (mA), negative while regenerating
+    pub current_ma: i32,
+    /// Average electrical input power (mW), negative while regenerating
+    pub power_mw: i32,
+    /// Values come from measured phase currents (false - resistive model)
+    pub measured: bool,
+}
+
+/// DC-bus current and input power estimation.
+pub struct BusPower {
+    current_avg: i64, // Averaged bus current (mA, AVG_SHIFT fractional bits)
+    report: BusReport,
+}
+
+impl BusPower {
+    /// Creates an estimator reporting zero.
+    pub const fn new() -> Self {
+        Self {
+            current_avg: 0,
+            report: BusReport {
+                current_ma: 0,
+                power_mw: 0,
+                measured: false,
+            },
+        }
+    }
+
+    /// Updates the estimate from measured output currents.
+    ///
+    /// # Arguments
+    /// * `duties` - Duties of the 4 outputs (0..32767 = 0..100%)
+    /// * `currents_ma` - Current flowing out of every output into the motor (mA)
+    /// * `supply_mv` - Supply voltage (mV)
+    pub fn tick_measured(&mut self, duties: [i16; 4], currents_ma: [i32; 4], supply_mv: i32) -> &BusReport {
+        let bus: i64 = duties
+            .iter()
+            .zip(currents_ma.iter())
+            .map(|(&duty, &current)| duty.max(0) as i64 * current as i64)
+            .sum();
+        self.update(bus >> 15, supply_mv, true)
+    }
+
+    /// Updates the estimate from the commanded current amplitude (resistive model).
+    ///
+    /// # Arguments
+    /// * `amplitude_ma` - Current amplitude (mA)
+    /// * `resistance` - Winding resistance (mOhm)
+    /// * `phases` - Number of motor phases (1 - single coil, 2 - stepper, 3 - BLDC)
+    /// * `supply_mv` - Supply voltage (mV)
+    pub fn tick_model(&mut self, amplitude_ma: i32, resistance: i32, phases: u8, supply_mv: i32) -> &BusReport {
+        let current = amplitude_ma.unsigned_abs() as i64;
+        let voltage = current * resistance as i64 / 1000; // mA * mOhm -> mV
+        let power = match phases {
+            3 => voltage * current * 3 / 2, // Amplitude-invariant three-phase vector
+            _ => voltage * current,
+        } / 1000; // mV * mA -> mW
+        let bus = if supply_mv > 0 { power * 1000 / supply_mv as i64 } else { 0 };
+        self.update(bus, supply_mv, false)
+    }
+
+    /// Returns the last estimate.
+    pub fn report(&self) -> BusReport {
+        self.report
+    }
+
+    /// Averages the bus current and derives the power.
+    fn update(&mut self, bus_ma: i64, supply_mv: i32, measured: bool) -> &BusReport {
+        self.current_avg += ((bus_ma << AVG_SHIFT) - self.current_avg) >> AVG_SHIFT;
+        let current = self.current_avg >> AVG_SHIFT;
+        self.report = BusReport {
+            current_ma: current.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            power_mw: (current * supply_mv.max(0) as i64 / 1000).clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            measured,
+        };
+        &self.report
+    }
+}
+
+impl Default for BusPower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
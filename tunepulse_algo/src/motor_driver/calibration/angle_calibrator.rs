@@ -1,13 +1,19 @@
 use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
 
+use super::config::CalibrationConfig;
 use super::CalibrationTable;
+use crate::math_integer::angle::Angle16;
+
+/// Points of the calibration table, a full rotation pass can't sample more
+pub const CAL_TABLE_SIZE: usize = 200;
 
 /// Represents the current stage of the calibration process.
-enum CalStage {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalStage {
     /// Test the motor's ability to respond linearly and consistently by performing a few test steps.
     Reset = 0, // Initial setup stage for calibration
     Setup = 1, // Stage where the motor is allowed to settle before starting calibration steps
-    Pass0 = 2,
+    Pass0 = 2, // Single pole test motion finding the direction and step size
     Pass1 = 3, // First pass of the calibration
     Pass2 = 4, // Second pass of the calibration
     Check = 5, // State for verifying the calibration
@@ -29,6 +35,17 @@ enum CalSamplingState {
     Sampling,
 }
 
+/// Progress of the calibration for user interfaces and protocols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalProgress {
+    /// Stage in progress
+    pub stage: CalStage,
+    /// Completed share of the sampling cycles (0..=100)
+    pub percent: u8,
+    /// Estimated ticks until the calibration is complete
+    pub remaining_ticks: u32,
+}
+
 /// The main driver struct for the motor, holding all the state required for operation and calibration.
 pub struct AngleCalibrator {
     frequency: u16,    // Update frequency (ticks per second)
@@ -48,25 +65,29 @@ pub struct AngleCalibrator {
 
     direction: isize, // Current rotation direction (1 for forward, -1 for backward)
     speed: isize,     // Speed (steps per tick) during calibration
-    settling_time: usize, // Settling time in milliseconds
+    settling_time: usize, // Settling time in ticks
+    sweep_speed: u32,     // Sweep speed (electrical angle per second)
+    points_per_period: u16, // Sampling points per electrical period of the full rotation passes
 
     init_pos: i32, // Position recorded at the start of a calibration step
     temp_pos: i32, // Temporary position for measuring movement increments
     dif_max: i32,  // Maximum difference in step measurement for consistency checks
     dif_min: i32,  // Minimum difference in step measurement for consistency checks
 
-    cal_table: CalibrationTable<200>,
+    cal_table: CalibrationTable<CAL_TABLE_SIZE>,
     el_step_idx: u16,
+
+    inverted: bool,       // Encoder was found counting against the electrical rotation
+    invert_request: bool, // Caller has to mirror the encoder input from the next tick
+    points: u16,          // Expected sampling points of a full rotation pass (estimated in Pass0)
 }
 
 // Constants used during calibration
 impl AngleCalibrator {
-    const CAL_SETTLING_TIME_US: usize = 25000; // Settling time in milliseconds
-    const CAL_SPEED_US: usize = 2500; // Speed in angle increments per millisecond
-
     const CAL_OVERSEMPLING: usize = 100; // Number of samples per oversampling period for averaging
     const CAL_FIRST_STEP_USTEPS: u16 = 16;
-    const CAL_POINTS_PER_360EL: u16 = 4;
+    const CAL_RESET_CYCLES: u32 = 11; // Sampling cycles of the Reset stage
+    const CAL_DEFAULT_POLE_PAIRS: u16 = 50; // Pole pairs assumed until Pass0 (1.8° stepper)
 
     //---------------------------------------------------------
     // Description of the Calibration Algorithm and Steps:
@@ -97,7 +118,8 @@ impl AngleCalibrator {
     /// * `connection` - Phase pattern configuration
     /// * `frequency` - Number of ticks per second
     pub fn new(frequency: u16) -> Self {
-        let settling_time = Self::calculate_settling_time(frequency, Self::CAL_SETTLING_TIME_US);
+        let config = CalibrationConfig::new();
+        let settling_time = Self::calculate_settling_time(frequency, config.settle_ms as usize);
 
         Self {
             frequency,   // Store the update frequency
@@ -117,6 +139,8 @@ impl AngleCalibrator {
             direction: 0,   // No direction initially
             speed: 1,       // Use the predefined calibration speed
             settling_time,  // Use the calculated settling time
+            sweep_speed: config.sweep_speed,
+            points_per_period: config.points_per_period,
 
             init_pos: 0,       // Initial position placeholder
             temp_pos: 0,       // Temporary position placeholder
@@ -125,10 +149,30 @@ impl AngleCalibrator {
 
             cal_table: CalibrationTable::new(),
             el_step_idx: 0,
-            
+
+            inverted: false,
+            invert_request: false,
+            points: Self::CAL_DEFAULT_POLE_PAIRS * config.points_per_period,
         }
     }
 
+    /// Applies the sweep speed, settle time and points per period of a calibration that hasn't
+    /// started its sweep yet.
+    ///
+    /// Returns false, keeping the previous settings, if the points per period are not a power of two
+    /// within 2..=32 (steps have to tile the electrical period evenly).
+    pub fn configure(&mut self, config: &CalibrationConfig) -> bool {
+        if !config.is_valid() {
+            defmt::warn!("CALIBRATION: {} points per period rejected", config.points_per_period);
+            return false;
+        }
+        self.settling_time = Self::calculate_settling_time(self.frequency, config.settle_ms as usize);
+        self.sweep_speed = config.sweep_speed;
+        self.points_per_period = config.points_per_period;
+        self.points = Self::CAL_DEFAULT_POLE_PAIRS * self.points_per_period;
+        true
+    }
+
     //---------------------------------------------------------
     // tick_calibrate() Method Steps:
     //
@@ -155,7 +199,7 @@ impl AngleCalibrator {
                     // After settling, move to the Setup stage
                     self.cal_idx = 10; // Arbitrary index setting for demonstration
                     self.calibration_stage = CalStage::Reset;
-                    self.speed = Self::calculate_speed(self.frequency, Self::CAL_SPEED_US);
+                    self.speed = Self::calculate_speed(self.frequency, self.sweep_speed);
                     return self.angle_el;
                 }
 
@@ -206,13 +250,35 @@ impl AngleCalibrator {
                         // Proceed with a known direction
                         defmt::debug!("CALIBRATION: Detected motion direction: {}", self.direction);
 
+                        // Encoder counting against the electrical rotation is mirrored by the caller,
+                        // the rest of the calibration already sees the mirrored position
+                        let mut stable_pos = stable_pos;
+                        if self.direction > 0 {
+                            defmt::warn!("CALIBRATION: Encoder counts backwards, position is inverted");
+                            self.inverted = !self.inverted;
+                            self.invert_request = true;
+                            self.direction = -1;
+                            stable_pos = stable_pos.wrapping_neg();
+                        }
+
+                        // Pass1 samples every 1/points_per_period of an electrical period
+                        let period = Self::CAL_FIRST_STEP_USTEPS as i32 * avg_step.max(1);
+                        let points = u16::MAX as i32 * self.points_per_period as i32 / period + 1;
+                        self.points = points.min(u16::MAX as i32) as u16;
+                        if points as usize > CAL_TABLE_SIZE {
+                            // Too many pole pairs for the points per period
+                            defmt::error!("CALIBRATION: {} points exceed the table of {}", points, CAL_TABLE_SIZE);
+                            self.calibration_stage = CalStage::Error;
+                            return self.angle_el;
+                        }
+
                         // Prepare for the Pass1 stage
                         self.calibration_stage = CalStage::Pass1;
-                        self.ang_el_step = u16::MAX / Self::CAL_POINTS_PER_360EL;
+                        self.ang_el_step = u16::MAX / self.points_per_period;
                         self.speed = -self.speed * self.direction; // Adjust speed direction
                         self.cal_idx = 0;
                         self.init_pos = stable_pos;
-                        self.cal_table.reset(Self::CAL_POINTS_PER_360EL);
+                        self.cal_table.reset(self.points_per_period);
                         defmt::info!(
                             "CALIBRATION: Full rotation in positive direction with sampling"
                         );
@@ -233,9 +299,11 @@ impl AngleCalibrator {
                         self.speed = -self.speed;
                         return self.angle_el;
                     }
-                    // if self.cal_idx != Self::CAL_TABLE_SIZE as u16 {
-                    self.cal_table.fill_first(self.cal_idx, stable_pos as u16);
-                    // }
+                    if !self.cal_table.fill_first(self.cal_idx, stable_pos as u16) {
+                        // More points than estimated after the test motion
+                        self.calibration_stage = CalStage::Error;
+                        return self.angle_el;
+                    }
                     // Increment index as we collect data (data storage commented out)
                     self.cal_idx += 1;
                     // if self.cal_idx == Self::CAL_TABLE_SIZE as u16 {
@@ -247,7 +315,10 @@ impl AngleCalibrator {
                 CalStage::Pass2 => {
                     // Perform a full rotation in CCW direction
                     self.cal_idx -= 1;
-                    self.cal_table.fill_second(self.cal_idx, stable_pos as u16);
+                    if !self.cal_table.fill_second(self.cal_idx, stable_pos as u16) {
+                        self.calibration_stage = CalStage::Error;
+                        return self.angle_el;
+                    }
 
                     if self.cal_idx == 0 {
                         // Once we return to zero, calibration is complete
@@ -300,9 +371,9 @@ impl AngleCalibrator {
                 self.oversampled_pos = 0; // Reset oversampling accumulator
                 self.cal_cycle_stage = CalSamplingState::Rotating; // Next state: Rotating
                 self.time_in_state = steps as usize / self.speed.abs() as usize; // Calculate how long to rotate
-                self.el_step_idx = self
-                    .angle_el
-                    .wrapping_add((steps as i32 * self.speed.signum() as i32) as u16);
+                self.el_step_idx = Angle16(self.angle_el)
+                    .offset(steps as i32 * self.speed.signum() as i32)
+                    .0;
                 i32::MIN // Not finished yet
             }
 
@@ -358,7 +429,7 @@ impl AngleCalibrator {
     /// * `increment` - how much to add to angle_el per tick
     #[inline(always)]
     fn move_at_speed(&mut self, increment: isize) {
-        let new_angle = self.angle_el.wrapping_add(increment as u16); // Wrap around if overflow
+        let new_angle = Angle16(self.angle_el).offset(increment as i32).0; // Wrap around if overflow
         self.angle_el = new_angle; // Update the motor's electrical angle
     }
 
@@ -373,6 +444,50 @@ impl AngleCalibrator {
         matches!(self.calibration_stage, CalStage::Ready) // Returns true if Ready
     }
 
+    /// Returns the active stage.
+    pub fn stage(&self) -> CalStage {
+        self.calibration_stage
+    }
+
+    /// Returns the stage, completed share and estimated remaining time of the calibration.
+    ///
+    /// The number of points of the full rotation passes is only known after the test motion, a
+    /// 50 pole pair motor is assumed before.
+    pub fn progress(&self) -> CalProgress {
+        let points = self.points as u32;
+        let pass = points + 1; // Last Pass1 cycle crosses the full turn without sampling
+        let total = 1 + Self::CAL_RESET_CYCLES + (Self::CAL_FIRST_STEP_USTEPS as u32 + 1) + 2 * pass + 1;
+        let idx = self.cal_idx as u32;
+        // Remaining sampling cycles of (Reset, Pass0, full rotation passes)
+        let (reset, pass0, rotation) = match self.calibration_stage {
+            CalStage::Setup => (Self::CAL_RESET_CYCLES + 1, Self::CAL_FIRST_STEP_USTEPS as u32 + 1, 2 * pass + 1),
+            CalStage::Reset => (idx + 1, Self::CAL_FIRST_STEP_USTEPS as u32 + 1, 2 * pass + 1),
+            CalStage::Pass0 => (0, idx + 1, 2 * pass + 1),
+            CalStage::Pass1 => (0, 0, pass.saturating_sub(idx).max(1) + idx.max(points) + 1),
+            CalStage::Pass2 => (0, 0, idx + 1),
+            CalStage::Check => (0, 0, 1),
+            CalStage::Ready | CalStage::Error => (0, 0, 0),
+        };
+
+        // Every cycle rotates, settles and oversamples
+        let speed = Self::calculate_speed(self.frequency, self.sweep_speed).unsigned_abs() as u32;
+        let rest = (self.settling_time + Self::CAL_OVERSEMPLING) as u32 + 1;
+        let pass0_step = (u16::MAX / Self::CAL_FIRST_STEP_USTEPS) as u32 / speed;
+        let rotation_step = (u16::MAX / self.points_per_period) as u32 / speed;
+        let remaining_ticks = (reset + pass0 + rotation) * rest + pass0 * pass0_step + rotation * rotation_step;
+
+        let remaining = (reset + pass0 + rotation).min(total);
+        let percent = match self.calibration_stage {
+            CalStage::Ready => 100,
+            _ => ((total - remaining) * 100 / total) as u8,
+        };
+        CalProgress {
+            stage: self.calibration_stage,
+            percent,
+            remaining_ticks,
+        }
+    }
+
     //---------------------------------------------------------
     // cal_oversampling() Method Steps:
     //
@@ -425,21 +540,40 @@ impl AngleCalibrator {
         }
     }
 
+    /// Returns true once if the caller has to mirror the encoder input (see `Position::invert`).
+    pub fn take_inversion(&mut self) -> bool {
+        core::mem::take(&mut self.invert_request)
+    }
+
+    /// Returns true if the calibration found the encoder counting against the electrical rotation.
+    pub fn encoder_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Writes the encoder deviations measured by the calibration (see `CalibrationTable::deviations`),
+    /// returns the number of points written (0 before the calibration is complete).
+    pub fn table_deviations(&self, out: &mut [i16]) -> usize {
+        if !self.is_ready() {
+            return 0;
+        }
+        self.cal_table.deviations(out)
+    }
+
     #[inline(always)]
     pub fn get_correction(&self, pos: u16) -> (u16, u16) {
         self.cal_table.correct_pos(pos)
     }
 
-    /// Calculate speed in ticks per millisecond.
+    /// Calculate the sweep speed in angle increments per tick.
     ///
     /// # Arguments
     /// * `frequency` - Number of ticks per second
-    /// * `speed_ms` - Desired speed in milliseconds
+    /// * `speed` - Desired electrical angle per second
     ///
-    /// Returns the calculated speed in ticks.
+    /// Returns the calculated increment per tick, at least 1.
     #[inline(always)]
-    fn calculate_speed(frequency: u16, speed_us: usize) -> isize {
-        ((frequency as usize * speed_us) / 1000000) as isize
+    fn calculate_speed(frequency: u16, speed: u32) -> isize {
+        (speed / frequency.max(1) as u32).clamp(1, u16::MAX as u32 / 4) as isize
     }
 
     /// Calculate settling time in ticks based on frequency and milliseconds.
@@ -450,7 +584,47 @@ impl AngleCalibrator {
     ///
     /// Returns the calculated settling time in ticks.
     #[inline(always)]
-    fn calculate_settling_time(frequency: u16, settling_us: usize) -> usize {
-        (frequency as usize * settling_us) / 1000000
+    fn calculate_settling_time(frequency: u16, settling_ms: usize) -> usize {
+        (frequency as usize * settling_ms) / 1000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drives the calibrator with an ideal motor until it leaves the test motion or gives up.
+    fn run_test_motion(pole_pairs: i32, points_per_period: u16) -> CalStage {
+        let mut calibrator = AngleCalibrator::new(10000);
+        let config = CalibrationConfig { points_per_period, settle_ms: 1, ..CalibrationConfig::new() };
+        assert!(calibrator.configure(&config));
+        let (mut electrical, mut last) = (0i64, 0u16);
+        for _ in 0..1_000_000 {
+            let angle = calibrator.tick((-electrical / pole_pairs as i64) as i32);
+            electrical += angle.wrapping_sub(last) as i16 as i64;
+            last = angle;
+            if !matches!(calibrator.stage(), CalStage::Setup | CalStage::Reset | CalStage::Pass0) {
+                break;
+            }
+        }
+        calibrator.stage()
+    }
+
+    #[test]
+    fn configure_rejects_invalid_points() {
+        let mut calibrator = AngleCalibrator::new(10000);
+        for points in [0, 1, 3, 12, 64] {
+            let config = CalibrationConfig { points_per_period: points, ..CalibrationConfig::new() };
+            assert!(!calibrator.configure(&config), "{}", points);
+        }
+        assert!(calibrator.configure(&CalibrationConfig::new()));
+    }
+
+    #[test]
+    fn points_beyond_the_table_fail_the_sweep() {
+        assert_eq!(run_test_motion(50, 4), CalStage::Pass1); // 200 points fit
+        assert_eq!(run_test_motion(12, 16), CalStage::Pass1);
+        assert_eq!(run_test_motion(50, 8), CalStage::Error);
+        assert_eq!(run_test_motion(7, 32), CalStage::Error);
     }
 }
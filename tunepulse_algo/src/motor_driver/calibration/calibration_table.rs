@@ -22,6 +22,8 @@
 
 use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
 
+use crate::math_integer::angle::Angle16;
+
 /// The main driver struct for the motor, holding all the state required for operation and calibration.
 pub struct CalibrationTable<const N: usize> {
     // state: CalibrationState, // Calibration state management (currently commented out)
@@ -95,10 +97,10 @@ impl<const N: usize> CalibrationTable<N> {
         // Check if the index is within bounds
         if idx < N {
             // Calculate the signed difference to handle potential wraparound (values near 0 or max range).
-            let dif = val.wrapping_sub(self.cal_table[idx]) as i16;
+            let dif = Angle16(val).diff(Angle16(self.cal_table[idx]));
 
             // Update the table with the midpoint of the hysteresis range.
-            let val = self.cal_table[idx].wrapping_add((dif / 2) as u16);
+            let val = Angle16(self.cal_table[idx]).offset(dif as i32 / 2).0;
             self.cal_table[idx] = val; // Store the averaged value
 
             // Every `el_angle_div` steps, check if this is the minimal offset position.
@@ -174,6 +176,17 @@ impl<const N: usize> CalibrationTable<N> {
         return true; // Indicate validation success
     }
 
+    /// Writes the signed deviation of every point from the ideal linear position (after `check()`),
+    /// starting at the zero point, and returns the number of points written.
+    pub fn deviations(&self, out: &mut [i16]) -> usize {
+        let len = self.cal_size.min(out.len());
+        for (i, deviation) in out.iter_mut().take(len).enumerate() {
+            let ideal = get_ideal(i, self.cal_size);
+            *deviation = Angle16(self.get_val_by_idx(i)).diff(Angle16(ideal));
+        }
+        len
+    }
+
     /// Corrects a given position using the calibration table.
     /// Given an actual encoder `position`, it accounts for the offset and searches near the expected index.
     /// Uses a small loop to find the segment where real_pos transitions from positive to negative difference,
@@ -197,8 +210,8 @@ impl<const N: usize> CalibrationTable<N> {
             let cal_pos2 = self.get_val_by_idx(idx); // Retrieve calibration value at new index
             let idl_pos2 = get_ideal(idx, self.cal_size); // Retrieve ideal value at new index
 
-            let diff1 = real_pos.wrapping_sub(cal_pos1) as i16; // Difference at previous index
-            let diff2 = real_pos.wrapping_sub(cal_pos2) as i16; // Difference at current index
+            let diff1 = Angle16(real_pos).diff(Angle16(cal_pos1)); // Difference at previous index
+            let diff2 = Angle16(real_pos).diff(Angle16(cal_pos2)); // Difference at current index
 
             // Once we find a boundary where diff changes sign (diff1 >= 0, diff2 < 0),
             // we interpolate the exact ideal position within that segment.
@@ -239,7 +252,7 @@ fn abs_deviation(val: u16, idx: usize, size: usize) -> u16 {
     let ideal = get_ideal(idx, size);
 
     // Compute the deviation as the absolute difference from the measured value.
-    let deviation = ((ideal as u16).wrapping_sub(val) as i16).abs() as u16;
+    let deviation = Angle16(ideal).diff(Angle16(val)).unsigned_abs();
 
     deviation // Return the calculated deviation
 }
@@ -0,0 +1,77 @@
+// Implements the calibration settings, selecting which startup stages run when the driver
+// calibrates or recalibrates and how hard and fast the rotor is driven during the sweep.
+
+// Key Features:
+// - Bitmask of the stages: current sense, stepper wiring, winding diagnosis and rotor alignment.
+// - Stages left out keep their previous result, so a recalibration can repeat only what changed.
+// - Calibration current, sweep speed, settle time and points per electrical period of the sweep.
+
+// Detailed Operation:
+// The stages run in the order of their bits. A skipped current sense stage keeps the last offsets
+// and gains, skipped wiring or winding checks keep the phase pattern and the last verdict. The rotor
+// alignment (full sweep or quick alignment) is repeated only if selected, but it always runs while
+// the motor has never been aligned, since the commutation can't work without it.
+// The driver applies the calibration current as a voltage V = I * R, so `current_ma` also sets the
+// voltage level. Small gimbal motors need a few hundred mA and a slow sweep to follow the field
+// without overshoot; large steppers need more current and a longer settle time for their inertia.
+// The sweep stores points_per_period * pole_pairs points per revolution, up to the 200 points of
+// the calibration table (50 pole pairs at 4 points, 12 pole pairs at 16 points). The pole pairs are
+// measured by the test motion of the sweep, which fails the calibration if the points don't fit.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Stage: current sense offsets and gains
+pub const CAL_CURRENT_SENSE: u8 = 1 << 0;
+/// Stage: coil pairing of steppers
+pub const CAL_WIRING: u8 = 1 << 1;
+/// Stage: open / shorted / grounded winding diagnosis
+pub const CAL_PHASES: u8 = 1 << 2;
+/// Stage: encoder-to-rotor alignment (full sweep or quick alignment)
+pub const CAL_ALIGNMENT: u8 = 1 << 3;
+/// All stages
+pub const CAL_ALL: u8 = CAL_CURRENT_SENSE | CAL_WIRING | CAL_PHASES | CAL_ALIGNMENT;
+
+/// Settings of the startup calibration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalibrationConfig {
+    /// Stages to run (`CAL_*` bits)
+    pub stages: u8,
+    /// Current of the wiring check, winding diagnosis and alignment (mA), 0 - the tick current limit
+    pub current_ma: i16,
+    /// Sweep speed (electrical angle per second, 65536 = one electrical turn)
+    pub sweep_speed: u32,
+    /// Rest at every point before the encoder is sampled (ms)
+    pub settle_ms: u16,
+    /// Measurement points per electrical period of the sweep (2..=32, a power of two)
+    pub points_per_period: u16,
+}
+
+impl CalibrationConfig {
+    /// Creates settings running every stage at the tick current limit.
+    pub fn new() -> Self {
+        Self {
+            stages: CAL_ALL,
+            current_ma: 0,
+            sweep_speed: 1_000_000, // ~15 electrical turns per second
+            settle_ms: 25,
+            points_per_period: 4,
+        }
+    }
+
+    /// Returns true if the given stage (`CAL_*` bit) is selected.
+    pub fn runs(&self, stage: u8) -> bool {
+        self.stages & stage != 0
+    }
+
+    /// Returns true if the points per period are a power of two within 2..=32.
+    pub fn is_valid(&self) -> bool {
+        (2..=32).contains(&self.points_per_period) && self.points_per_period.is_power_of_two()
+    }
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,4 +1,9 @@
 pub mod angle_calibrator;
 mod calibration_table;
+pub mod config;
+pub mod phase_check;
+pub mod quick_align;
+pub mod table_compression;
+pub mod wiring_check;
 
-use calibration_table::CalibrationTable;
\ No newline at end of file
+use calibration_table::CalibrationTable;
@@ -0,0 +1,233 @@
+// Implements the phase check, a startup diagnosis energizing every winding in turn and comparing the
+// current response to find open windings, shorted windings and leakage to ground.
+
+// Key Features:
+// - Test vectors driving one phase pair (BLDC: B-C, C-A, A-B) or one coil (stepper: A, B) at a time.
+// - Open winding: driven current below PHASE_MIN_SIGNAL.
+// - Shorted winding: current far above the other windings or above the expected current.
+// - Short to ground: current entering the winding doesn't return through the other phases.
+// - Per-winding verdict, current and leakage in the report for service diagnostics.
+
+// Detailed Operation:
+// The driver applies the test current as a voltage V = I * R along the commanded angle. Electrical
+// 0° drives B against C, 120° and 240° drive C-A and A-B; a stepper coil A is driven at 90°, coil B at
+// 0°. Every vector is held for `settle` ticks until the winding current is steady, then the phase
+// currents are averaged over `samples` ticks. The driven current is half the sum of |i| over the
+// phases of the winding (current out of one phase returns through the other), the leakage is |Σi|,
+// which stays near zero unless part of the current escapes to ground through a damaged insulation.
+// With the voltage fixed, a lower resistance means a higher current: a winding carrying more than
+// PHASE_SHORT_PCT of the weakest winding, or of the expected current if the current scale is known,
+// has shorted turns or a phase-to-phase short. Leakage above PHASE_LEAK_PCT of the driven current
+// flags a ground fault. Currents are in raw ADC units, logical phase order (after the phase pattern).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Smallest driven current of a healthy winding (raw ADC units)
+const PHASE_MIN_SIGNAL: i32 = 40;
+/// Driven current relative to the weakest winding or the expected current flagging a short (%)
+const PHASE_SHORT_PCT: i32 = 200;
+/// Leakage relative to the driven current flagging a ground fault (%)
+const PHASE_LEAK_PCT: i32 = 25;
+
+/// Verdict of one winding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseFault {
+    /// Winding not tested
+    None,
+    /// Current response as expected
+    Ok,
+    /// No current: broken wire, loose connector or blown output
+    Open,
+    /// Current too high: shorted turns or phases shorted together
+    Shorted,
+    /// Current leaks to ground
+    Ground,
+}
+
+/// Progress of the phase check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseCheckStage {
+    /// Check not started
+    Idle,
+    /// Windings are being energized
+    Running,
+    /// All windings healthy
+    Done,
+    /// At least one winding faulted (see the report)
+    Failed,
+}
+
+/// Measurements and verdicts of the phase check, index = test vector.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseCheckReport {
+    /// Verdict of every winding (BLDC: B-C, C-A, A-B; stepper: A, B)
+    pub faults: [PhaseFault; 3],
+    /// Averaged driven current of every winding (raw ADC units)
+    pub currents: [i32; 3],
+    /// Averaged current leaking to ground (raw ADC units)
+    pub leakage: [i32; 3],
+}
+
+/// Startup diagnosis of the motor windings.
+pub struct PhaseCheck {
+    settle: u32,  // Ticks a vector is held before sampling
+    samples: u32, // Averaged samples per vector
+    stage: PhaseCheckStage,
+
+    three_phase: bool, // BLDC vectors (three pairs) instead of two coils
+    expected: i32,     // Expected driven current (raw ADC units), 0 - unknown
+    vector: usize,     // Test vector in progress
+    ticks: u32,        // Ticks at the vector
+    driven: i64,       // Summed driven current
+    leakage: i64,      // Summed leakage
+    report: PhaseCheckReport,
+}
+
+impl PhaseCheck {
+    /// Creates an idle check.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            settle: frequency as u32 / 100,  // 10 ms
+            samples: frequency as u32 / 200, // 5 ms
+            stage: PhaseCheckStage::Idle,
+            three_phase: false,
+            expected: 0,
+            vector: 0,
+            ticks: 0,
+            driven: 0,
+            leakage: 0,
+            report: PhaseCheckReport {
+                faults: [PhaseFault::None; 3],
+                currents: [0; 3],
+                leakage: [0; 3],
+            },
+        }
+    }
+
+    /// Returns the check to idle, it starts again with the next calibration.
+    pub fn reset(&mut self) {
+        self.stage = PhaseCheckStage::Idle;
+    }
+
+    /// Starts the check.
+    ///
+    /// # Arguments
+    /// * `three_phase` - BLDC motor (three phase pairs), otherwise two stepper coils
+    /// * `amplitude` - Applied test current (raw ADC units), 0 if the current scale is unknown
+    pub fn start(&mut self, three_phase: bool, amplitude: i32) {
+        self.three_phase = three_phase;
+        // A pair of three phases carries sqrt(3) / 2 of the vector amplitude
+        self.expected = if three_phase { (amplitude * 28378) >> 15 } else { amplitude };
+        self.stage = PhaseCheckStage::Running;
+        self.vector = 0;
+        self.ticks = 0;
+        self.driven = 0;
+        self.leakage = 0;
+        self.report.faults = [PhaseFault::None; 3];
+    }
+
+    /// Advances the check and returns the electrical angle to apply.
+    ///
+    /// # Arguments
+    /// * `currents` - Phase currents with offsets removed, logical phase order (raw ADC units)
+    pub fn tick(&mut self, currents: [i16; 4]) -> u16 {
+        if self.stage != PhaseCheckStage::Running {
+            return 0;
+        }
+        let samples = self.samples.max(1);
+        self.ticks += 1;
+        if self.ticks > self.settle {
+            let phases: &[i16] = match (self.three_phase, self.vector) {
+                (true, _) => &currents[..3],
+                (false, 0) => &currents[..2],
+                (false, _) => &currents[2..],
+            };
+            let magnitude: i64 = phases.iter().map(|&current| (current as i64).abs()).sum();
+            let sum: i64 = phases.iter().map(|&current| current as i64).sum();
+            self.driven += magnitude / 2;
+            self.leakage += sum.abs();
+        }
+        if self.ticks >= self.settle + samples {
+            self.report.currents[self.vector] = (self.driven / samples as i64) as i32;
+            self.report.leakage[self.vector] = (self.leakage / samples as i64) as i32;
+            self.vector += 1;
+            self.ticks = 0;
+            self.driven = 0;
+            self.leakage = 0;
+            if self.vector == self.vectors() {
+                self.finish();
+                return 0;
+            }
+        }
+        self.angle()
+    }
+
+    /// Number of test vectors of the motor.
+    fn vectors(&self) -> usize {
+        if self.three_phase { 3 } else { 2 }
+    }
+
+    /// Electrical angle of the test vector in progress.
+    fn angle(&self) -> u16 {
+        match (self.three_phase, self.vector) {
+            (true, vector) => (vector as u32 * 65536 / 3) as u16, // B-C, C-A, A-B
+            (false, 0) => 1 << 14,                                // Coil A
+            (false, _) => 0,                                      // Coil B
+        }
+    }
+
+    /// Gives every winding its verdict.
+    fn finish(&mut self) {
+        let count = self.vectors();
+        let weakest = self.report.currents[..count].iter().copied().min().unwrap_or(0);
+        let reference = if self.expected > 0 { self.expected } else { weakest };
+        for index in 0..count {
+            let current = self.report.currents[index];
+            let leakage = self.report.leakage[index];
+            self.report.faults[index] = if current < PHASE_MIN_SIGNAL {
+                PhaseFault::Open
+            } else if leakage * 100 > current * PHASE_LEAK_PCT {
+                PhaseFault::Ground
+            } else if reference >= PHASE_MIN_SIGNAL && current * 100 > reference * PHASE_SHORT_PCT {
+                PhaseFault::Shorted
+            } else {
+                PhaseFault::Ok
+            };
+        }
+
+        let faulted = self.report.faults[..count].iter().any(|&fault| fault != PhaseFault::Ok);
+        if faulted {
+            defmt::error!(
+                "PHASE CHECK: Winding fault {}, currents {}, leakage {}",
+                self.report.faults.map(|fault| fault as u8),
+                self.report.currents,
+                self.report.leakage
+            );
+            self.stage = PhaseCheckStage::Failed;
+        } else {
+            defmt::info!("PHASE CHECK: Windings OK, currents {}", self.report.currents);
+            self.stage = PhaseCheckStage::Done;
+        }
+    }
+
+    /// Returns the active stage.
+    pub fn stage(&self) -> PhaseCheckStage {
+        self.stage
+    }
+
+    /// Returns true once all windings passed.
+    pub fn is_ready(&self) -> bool {
+        self.stage == PhaseCheckStage::Done
+    }
+
+    /// Returns the measurements and verdicts (valid once done or failed).
+    pub fn report(&self) -> PhaseCheckReport {
+        self.report
+    }
+}
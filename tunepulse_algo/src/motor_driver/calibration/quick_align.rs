@@ -0,0 +1,213 @@
+// Implements the quick alignment, finding the encoder-to-electrical offset by locking the rotor at a
+// few electrical angles instead of sweeping a full mechanical turn.
+
+// Key Features:
+// - Five locks a quarter of an electrical period apart, finished in about 175 ms.
+// - Encoder direction found from the motion, pole pairs given by the user and verified.
+// - Offset averaged over all locks on the circle (wrapping differences).
+// - Linear commutation model, no encoder nonlinearity table (see AngleCalibrator for that).
+
+// Detailed Operation:
+// The current vector is first locked at 0° electrical, the rotor snaps to the nearest pole (the
+// small jerk the method is known for), then it is rotated in 90° steps up to 360°. Every step is a
+// ramp of QUICK_MOVE_MS followed by QUICK_SETTLE_MS of rest and QUICK_SAMPLE_MS of averaging the
+// encoder. A healthy step moves 65536 / (4 * pole_pairs) counts; steps outside ±50% of that or with
+// an inconsistent sign mean a wrong pole count, a blocked rotor or a missing phase, and the alignment
+// fails. With the direction d the electrical angle is offset + d * pole_pairs * angle, the offset of
+// each lock is the locked angle minus d * pole_pairs * measured angle, and the locks are averaged
+// relative to the first one so the result doesn't break at the 0/65535 wrap.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::angle::Angle16;
+
+/// Number of locked angles (0°, 90°, 180°, 270°, 360° electrical)
+const QUICK_POINTS: usize = 5;
+/// Ramp time between two locks (ms)
+const QUICK_MOVE_MS: u32 = 10;
+/// Rest after a ramp before sampling (ms)
+const QUICK_SETTLE_MS: u32 = 20;
+/// Encoder averaging at every lock (ms)
+const QUICK_SAMPLE_MS: u32 = 5;
+
+/// Progress of the quick alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickAlignStage {
+    /// Alignment not started or not configured
+    Idle,
+    /// Rotor is being locked and measured
+    Running,
+    /// Offset and direction found
+    Done,
+    /// Motion didn't match the pole count
+    Failed,
+}
+
+/// Commutation offset search from a few rotor locks.
+pub struct QuickAlign {
+    frequency: u16,  // Update frequency (ticks per second)
+    pole_pairs: u16, // Pole pairs of the motor, 0 - quick alignment disabled
+    stage: QuickAlignStage,
+
+    point: usize,                   // Lock in progress
+    ticks: u32,                     // Ticks spent at the lock in progress
+    angle_el: u16,                  // Commanded electrical angle
+    sum: i64,                       // Summed encoder position of the sampling window
+    samples: u32,                   // Samples in the sampling window
+    positions: [i32; QUICK_POINTS], // Averaged position of every lock (counts)
+
+    direction: i32, // Encoder counts per electrical angle sign (+1 / -1)
+    offset: u16,    // Electrical angle at encoder angle 0
+}
+
+impl QuickAlign {
+    /// Creates a disabled alignment.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            pole_pairs: 0,
+            stage: QuickAlignStage::Idle,
+            point: 0,
+            ticks: 0,
+            angle_el: 0,
+            sum: 0,
+            samples: 0,
+            positions: [0; QUICK_POINTS],
+            direction: 1,
+            offset: 0,
+        }
+    }
+
+    /// Selects the quick alignment for a motor with the given pole pairs, 0 restores the full sweep.
+    pub fn configure(&mut self, pole_pairs: u16) {
+        self.pole_pairs = pole_pairs;
+        self.stage = QuickAlignStage::Idle;
+    }
+
+    /// Discards the result, the alignment runs again with the next calibration.
+    pub fn reset(&mut self) {
+        self.stage = QuickAlignStage::Idle;
+    }
+
+    /// Returns true if the quick alignment replaces the full calibration sweep.
+    pub fn is_enabled(&self) -> bool {
+        self.pole_pairs > 0
+    }
+
+    /// Advances the alignment and returns the electrical angle to apply.
+    ///
+    /// # Arguments
+    /// * `position` - Encoder position (counts)
+    pub fn tick(&mut self, position: i32) -> u16 {
+        match self.stage {
+            QuickAlignStage::Idle | QuickAlignStage::Failed if self.is_enabled() => {
+                defmt::info!("QUICK ALIGN: Locking rotor at {} points", QUICK_POINTS);
+                self.stage = QuickAlignStage::Running;
+                self.point = 0;
+                self.ticks = 0;
+                self.angle_el = 0;
+            }
+            QuickAlignStage::Running => {}
+            _ => return self.angle_el,
+        }
+
+        let ms = |ms: u32| (ms * self.frequency as u32 / 1000).max(1);
+        let (move_ticks, settle_ticks, sample_ticks) =
+            (ms(QUICK_MOVE_MS), ms(QUICK_SETTLE_MS), ms(QUICK_SAMPLE_MS));
+        self.ticks += 1;
+
+        // ####### Ramp to the lock #######
+        // The first lock snaps the rotor directly, the next ones are a quarter period further
+        let target = Angle16((self.point as u32 * Angle16::QUARTER.0 as u32) as u16);
+        if self.point > 0 && self.ticks <= move_ticks {
+            let start = target.sub(Angle16::QUARTER);
+            let offset = Angle16::QUARTER.0 as u32 * self.ticks / move_ticks;
+            self.angle_el = start.add(Angle16(offset as u16)).0;
+            return self.angle_el;
+        }
+        self.angle_el = target.0;
+
+        // ####### Settle and sample #######
+        let settled = if self.point > 0 { move_ticks + settle_ticks } else { settle_ticks };
+        if self.ticks == settled + 1 {
+            self.sum = 0;
+            self.samples = 0;
+        }
+        if self.ticks > settled {
+            self.sum += position as i64;
+            self.samples += 1;
+            if self.samples >= sample_ticks {
+                self.positions[self.point] = (self.sum / self.samples as i64) as i32;
+                self.point += 1;
+                self.ticks = 0;
+                if self.point == QUICK_POINTS {
+                    self.finish();
+                }
+            }
+        }
+        self.angle_el
+    }
+
+    /// Checks the measured steps and computes the direction and offset.
+    fn finish(&mut self) {
+        let pole_pairs = self.pole_pairs as i32;
+        let expected = 65536 / (4 * pole_pairs);
+        let travel = self.positions[QUICK_POINTS - 1] - self.positions[0];
+        self.direction = if travel >= 0 { 1 } else { -1 };
+        for (index, pair) in self.positions.windows(2).enumerate() {
+            let step = (pair[1] - pair[0]) * self.direction;
+            if step < expected / 2 || step > expected * 3 / 2 {
+                defmt::error!("QUICK ALIGN: Step {} moved {} counts, expected {}", index + 1, step, expected);
+                self.stage = QuickAlignStage::Failed;
+                return;
+            }
+        }
+
+        // Offset of every lock relative to the first one, averaged on the circle
+        let lock_offset = |index: usize| {
+            let lock = (index as u32 * Angle16::QUARTER.0 as u32) as u16;
+            let electrical = (self.direction * pole_pairs).wrapping_mul(self.positions[index]) as u16;
+            lock.wrapping_sub(electrical)
+        };
+        let first = lock_offset(0);
+        let spread: i32 = (0..QUICK_POINTS)
+            .map(|index| lock_offset(index).wrapping_sub(first) as i16 as i32)
+            .sum();
+        self.offset = first.wrapping_add((spread / QUICK_POINTS as i32) as u16);
+        self.stage = QuickAlignStage::Done;
+        defmt::info!("QUICK ALIGN: Offset {}, direction {}", self.offset, self.direction);
+    }
+
+    /// Mirrors the commutation model for an encoder input inverted by the caller.
+    pub fn invert(&mut self) {
+        self.direction = -self.direction;
+    }
+
+    /// Returns the electrical angle of the rotor at the given encoder angle (valid once done).
+    #[inline(always)]
+    pub fn rotor_el(&self, angle: u16) -> u16 {
+        let electrical = (self.direction * self.pole_pairs as i32).wrapping_mul(angle as i32) as u16;
+        self.offset.wrapping_add(electrical)
+    }
+
+    /// Returns the active stage.
+    pub fn stage(&self) -> QuickAlignStage {
+        self.stage
+    }
+
+    /// Returns true once the offset is known.
+    pub fn is_ready(&self) -> bool {
+        self.stage == QuickAlignStage::Done
+    }
+
+    /// Returns the (offset, direction) pair of the commutation model.
+    pub fn result(&self) -> (u16, i32) {
+        (self.offset, self.direction)
+    }
+}
@@ -0,0 +1,357 @@
+// Implements the table compression module, shrinking periodic correction tables (angle correction,
+// anti-cogging current) so full compensation fits into a few KB of spare flash.
+
+// Key Features:
+// - Delta coding: one i8 per point instead of i16, lossless for smooth tables.
+// - Harmonic coding: a handful of Fourier coefficients for the whole table, direct evaluation at
+//   any angle without decoding.
+// - Exact worst-case reconstruction error reported after encoding (documented accuracy loss).
+// - Fixed-size storage, no allocation.
+
+// Detailed Operation:
+// Both codecs work on a periodic table of i16 deviations sampled at equal angle steps (e.g. encoder
+// error against the ideal angle, or cogging current per position). Delta coding stores the first
+// value and the step to every next value. Steps are quantized to 2^shift, the smallest shift with
+// all steps in the i8 range is chosen automatically, and the quantization error is fed forward so
+// it never accumulates: the reconstruction error is at most 2^(shift-1) (0 for shift 0, which covers
+// tables with steps within +/-127). Harmonic coding keeps the mean and the first H harmonics of the
+// table (a discrete Fourier transform evaluated with the sine lookup). The error depends on the
+// content of the table: encoder eccentricity and magnet errors sit in the first few harmonics,
+// cogging at the pole and slot counts; `max_error()` reports the measured loss after fitting.
+// Storage: delta 4 + N bytes, harmonic 4 + 4 * H bytes (e.g. a 1024 point table: 1028 or 36 bytes
+// with 8 harmonics, against 2048 bytes raw).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::trigonometry::angle2sincos_interp;
+
+/// Largest delta quantization shift (covers any step of an i16 table)
+const MAX_SHIFT: u8 = 10;
+
+/// Delta coded table: one byte per point.
+pub struct DeltaTable<const N: usize> {
+    first: i16,      // First value
+    shift: u8,       // Quantization of the steps: 2^shift per LSB
+    len: u16,        // Number of stored points
+    deltas: [i8; N], // Quantized steps from the previous point (deltas[0] is unused)
+    max_error: u16,  // Worst-case reconstruction error
+}
+
+impl<const N: usize> DeltaTable<N> {
+    /// Creates an empty table.
+    pub const fn new() -> Self {
+        Self {
+            first: 0,
+            shift: 0,
+            len: 0,
+            deltas: [0; N],
+            max_error: 0,
+        }
+    }
+
+    /// Encodes a table, returns false if it is empty or longer than N points.
+    pub fn encode(&mut self, values: &[i16]) -> bool {
+        if values.is_empty() || values.len() > N || values.len() > u16::MAX as usize {
+            return false;
+        }
+        // Smallest shift where all steps fit, the last one always does
+        for shift in 0..=MAX_SHIFT {
+            if self.try_encode(values, shift) {
+                break;
+            }
+        }
+        self.len = values.len() as u16;
+        true
+    }
+
+    /// Quantizes with the given shift, returns false if a step did not fit into i8.
+    fn try_encode(&mut self, values: &[i16], shift: u8) -> bool {
+        self.first = values[0];
+        self.shift = shift;
+        self.max_error = 0;
+        let half = (1i32 << shift) >> 1;
+        let mut restored = values[0] as i32;
+        for (delta, &value) in self.deltas.iter_mut().zip(values).skip(1) {
+            // Step from the restored (not the exact) previous value, so errors don't accumulate
+            let step = value as i32 - restored;
+            let quantized = (step + half).div_euclid(1 << shift);
+            if quantized < i8::MIN as i32 || quantized > i8::MAX as i32 {
+                return false;
+            }
+            *delta = quantized as i8;
+            restored += quantized << shift;
+            self.max_error = self.max_error.max((value as i32 - restored).unsigned_abs() as u16);
+        }
+        true
+    }
+
+    /// Decodes into `out`, returns the number of points written.
+    pub fn decode(&self, out: &mut [i16]) -> usize {
+        let len = (self.len as usize).min(out.len());
+        let mut restored = self.first as i32;
+        for (index, (value, &delta)) in out.iter_mut().zip(&self.deltas).take(len).enumerate() {
+            if index > 0 {
+                restored += (delta as i32) << self.shift;
+            }
+            *value = restored.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+        len
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the worst-case reconstruction error of the stored table.
+    pub fn max_error(&self) -> u16 {
+        self.max_error
+    }
+
+    /// Returns the step quantization (2^shift per LSB).
+    pub fn shift(&self) -> u8 {
+        self.shift
+    }
+
+    /// Returns the flash needed to store the table (bytes).
+    pub fn storage_bytes(&self) -> usize {
+        4 + self.len as usize
+    }
+}
+
+impl<const N: usize> Default for DeltaTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Harmonic coded table: mean and the first H Fourier coefficients.
+pub struct HarmonicTable<const H: usize> {
+    mean: i16,               // Average value
+    len: u16,                // Number of points of the source table
+    coeffs: [(i16, i16); H], // (cosine, sine) amplitude of harmonics 1..=H
+    max_error: u16,          // Worst-case reconstruction error against the source table
+}
+
+impl<const H: usize> HarmonicTable<H> {
+    /// Creates an empty (all zero) table.
+    pub const fn new() -> Self {
+        Self {
+            mean: 0,
+            len: 0,
+            coeffs: [(0, 0); H],
+            max_error: 0,
+        }
+    }
+
+    /// Fits the harmonics to a periodic table, returns false if it is empty or too long.
+    pub fn encode(&mut self, values: &[i16]) -> bool {
+        if values.is_empty() || values.len() > u16::MAX as usize {
+            return false;
+        }
+        let len = values.len() as i64;
+        self.len = values.len() as u16;
+
+        let sum: i64 = values.iter().map(|&v| v as i64).sum();
+        self.mean = div_round(sum, len) as i16;
+
+        // Discrete Fourier transform: amplitude = 2 / N * sum(v * cos|sin(k * angle))
+        for (harmonic, coeff) in self.coeffs.iter_mut().enumerate() {
+            let order = harmonic as u64 + 1;
+            let (mut acc_cos, mut acc_sin) = (0i64, 0i64);
+            for (index, &value) in values.iter().enumerate() {
+                let (sin, cos) = angle2sincos_interp(point_angle(index, len as u64, order) as i16);
+                acc_cos += value as i64 * cos as i64;
+                acc_sin += value as i64 * sin as i64;
+            }
+            let scale = len << 14; // 2 / N and the Q15 of the table sine
+            let fit = |acc: i64| div_round(acc, scale).clamp(i16::MIN as i64, i16::MAX as i64) as i16;
+            *coeff = (fit(acc_cos), fit(acc_sin));
+        }
+
+        self.max_error = values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| (value as i32 - self.value_at(index) as i32).unsigned_abs())
+            .max()
+            .unwrap_or(0)
+            .min(u16::MAX as u32) as u16;
+        true
+    }
+
+    /// Evaluates the table at an angle (full turn of the table = 65536).
+    pub fn value(&self, angle: u16) -> i16 {
+        let mut acc = (self.mean as i64) << 15;
+        for (harmonic, &(amp_cos, amp_sin)) in self.coeffs.iter().enumerate() {
+            let angle = angle.wrapping_mul(harmonic as u16 + 1);
+            let (sin, cos) = angle2sincos_interp(angle as i16);
+            acc += amp_cos as i64 * cos as i64 + amp_sin as i64 * sin as i64;
+        }
+        ((acc + (1 << 14)) >> 15).clamp(i16::MIN as i64, i16::MAX as i64) as i16
+    }
+
+    /// Evaluates the table at a point index of the source table.
+    pub fn value_at(&self, index: usize) -> i16 {
+        self.value(point_angle(index, self.len.max(1) as u64, 1))
+    }
+
+    /// Decodes into `out` at the source table resolution, returns the number of points written.
+    pub fn decode(&self, out: &mut [i16]) -> usize {
+        let len = (self.len as usize).min(out.len());
+        for (index, value) in out.iter_mut().take(len).enumerate() {
+            *value = self.value_at(index);
+        }
+        len
+    }
+
+    /// Returns the number of points of the source table.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the worst-case reconstruction error against the source table.
+    pub fn max_error(&self) -> u16 {
+        self.max_error
+    }
+
+    /// Returns the (cosine, sine) amplitudes of harmonics 1..=H.
+    pub fn coeffs(&self) -> &[(i16, i16); H] {
+        &self.coeffs
+    }
+
+    /// Returns the flash needed to store the table (bytes).
+    pub fn storage_bytes(&self) -> usize {
+        4 + 4 * H
+    }
+}
+
+impl<const H: usize> Default for HarmonicTable<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Angle of harmonic `order` at point `index` of a `len` point table (65536 per turn).
+#[inline(always)]
+fn point_angle(index: usize, len: u64, order: u64) -> u16 {
+    (((index as u64 * order) << 16) / len) as u16
+}
+
+/// Signed division rounded to nearest (positive divisor).
+#[inline(always)]
+fn div_round(value: i64, divisor: i64) -> i64 {
+    let half = divisor >> 1;
+    if value >= 0 {
+        (value + half) / divisor
+    } else {
+        (value - half) / divisor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const POINTS: usize = 256;
+
+    /// Largest difference between the decoded and the source table.
+    fn decode_error(decoded: &[i16], values: &[i16]) -> u16 {
+        decoded.iter().zip(values).map(|(&a, &b)| (a as i32 - b as i32).unsigned_abs() as u16).max().unwrap()
+    }
+
+    /// Mean, first and third harmonic.
+    fn harmonics(index: usize) -> i16 {
+        let angle = index as f64 * 2.0 * PI / POINTS as f64;
+        (120.0 + 900.0 * angle.cos() - 400.0 * (3.0 * angle).sin()).round() as i16
+    }
+
+    /// Pseudo-random values over the full i16 range.
+    fn noise() -> [i16; POINTS] {
+        let mut state: u32 = 12345;
+        core::array::from_fn(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as i16
+        })
+    }
+
+    #[test]
+    fn delta_smooth_table_is_lossless() {
+        let values: [i16; POINTS] = core::array::from_fn(harmonics);
+        let mut table = DeltaTable::<POINTS>::new();
+        assert!(table.encode(&values));
+        let mut decoded = [0; POINTS];
+        assert_eq!(table.decode(&mut decoded), POINTS);
+        assert_eq!((table.shift(), table.max_error()), (0, 0));
+        assert_eq!(decoded, values);
+        assert_eq!(table.storage_bytes(), 4 + POINTS);
+    }
+
+    #[test]
+    fn delta_large_step_within_error_bound() {
+        let values: [i16; POINTS] = core::array::from_fn(|index| if index < POINTS / 2 { -15000 } else { 15000 });
+        let mut table = DeltaTable::<POINTS>::new();
+        assert!(table.encode(&values));
+        assert_eq!(table.shift(), 8); // 30000 / 2^8 = 117 fits into i8
+        let mut decoded = [0; POINTS];
+        table.decode(&mut decoded);
+        assert_eq!(decode_error(&decoded, &values), table.max_error());
+        assert_eq!(table.max_error(), 48); // 117 * 2^8 = 29952, no accumulation over the flat half
+        assert!(table.max_error() <= 1 << (table.shift() - 1));
+    }
+
+    #[test]
+    fn incompressible_table_reports_its_error() {
+        let values = noise();
+        let mut delta = DeltaTable::<POINTS>::new();
+        assert!(delta.encode(&values));
+        let mut decoded = [0; POINTS];
+        delta.decode(&mut decoded);
+        assert!(decode_error(&decoded, &values) <= delta.max_error());
+        assert!(delta.max_error() <= 1 << (delta.shift() - 1));
+
+        let mut harmonic = HarmonicTable::<8>::new();
+        assert!(harmonic.encode(&values));
+        harmonic.decode(&mut decoded);
+        assert_eq!(decode_error(&decoded, &values), harmonic.max_error());
+        assert!(harmonic.max_error() > 10000); // Noise has no dominant harmonics
+    }
+
+    #[test]
+    fn harmonic_only_table_round_trip() {
+        let values: [i16; POINTS] = core::array::from_fn(harmonics);
+        let mut table = HarmonicTable::<4>::new();
+        assert!(table.encode(&values));
+        let coeffs = table.coeffs();
+        assert!((coeffs[0].0 - 900).abs() <= 1 && coeffs[0].1.abs() <= 1);
+        assert!(coeffs[1].0.abs() <= 1 && coeffs[1].1.abs() <= 1);
+        assert!(coeffs[2].0.abs() <= 1 && (coeffs[2].1 + 400).abs() <= 1);
+        let mut decoded = [0; POINTS];
+        assert_eq!(table.decode(&mut decoded), POINTS);
+        assert_eq!(decode_error(&decoded, &values), table.max_error());
+        assert!(table.max_error() <= 3, "max error {}", table.max_error());
+        assert_eq!(table.storage_bytes(), 20);
+    }
+
+    #[test]
+    fn encode_rejects_empty_and_oversized_tables() {
+        let mut delta = DeltaTable::<4>::new();
+        assert!(!delta.encode(&[]));
+        assert!(!delta.encode(&[0; 5]));
+        assert!(delta.is_empty());
+        let mut harmonic = HarmonicTable::<2>::new();
+        assert!(!harmonic.encode(&[]));
+        assert!(harmonic.is_empty());
+    }
+}
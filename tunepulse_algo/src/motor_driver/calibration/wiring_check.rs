@@ -0,0 +1,238 @@
+// Implements the wiring check, finding which outputs a stepper's two coils are connected to and
+// selecting the matching phase pattern before the angle calibration.
+
+// Key Features:
+// - Tries the three possible coil pairings (ABCD, ADBC, ACDB) with a DC current in coil A only.
+// - Correct pairing leaves the outputs of coil B without current, wrong ones drive both coils.
+// - Diagnosis in the report: wiring ok, corrected, no current (open coil) or not decisive.
+// - Measured currents of every candidate kept in the report for bring-up logs.
+
+// Detailed Operation:
+// With the electrical angle at 90° only the alpha (coil A) outputs are driven differentially, the
+// beta outputs both sit at 50% duty. If the selected pattern matches the wiring, the current flows
+// through coil A between its two outputs and the beta outputs stay at zero. With a wrong pairing
+// each driven output is tied through a coil to a beta output at 50%, so all four outputs conduct
+// comparable currents. For every candidate the current is held for `settle` ticks and the absolute
+// channel currents are averaged; the idle ratio (beta current over alpha current, x1000) is lowest
+// for the correct pattern. The best candidate has to be clearly below WIRING_MAX_RATIO and the
+// driven current above WIRING_MIN_SIGNAL, otherwise the wiring is reported instead of guessed.
+// Swapping the two leads of one coil or the two coils only reverses the rotation, which the angle
+// calibration handles by inverting the encoder.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use super::super::PhasePattern;
+
+/// Candidate patterns, one per coil pairing of four outputs
+const CANDIDATES: [PhasePattern; 3] = [PhasePattern::ABCD, PhasePattern::ADBC, PhasePattern::ACDB];
+/// Idle ratio (x1000) a matching pattern has to stay below
+const WIRING_MAX_RATIO: i32 = 250;
+/// Smallest averaged current of the driven outputs (raw ADC units)
+const WIRING_MIN_SIGNAL: i32 = 40;
+/// Electrical angle driving coil A only
+const COIL_A_ANGLE: u16 = 1 << 14;
+
+/// Progress of the wiring check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WiringStage {
+    /// Check not started
+    Idle,
+    /// Candidates are being measured
+    Running,
+    /// Wiring found (as configured or corrected)
+    Done,
+    /// Coils missing or not separable (see the diagnosis)
+    Failed,
+}
+
+/// Result of the wiring check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WiringDiagnosis {
+    /// Configured pattern matches the wiring
+    Ok,
+    /// Coils were paired differently, the pattern was changed
+    Corrected,
+    /// No current on the driven outputs: coil open, motor missing or supply off
+    NoCurrent,
+    /// No candidate isolates the coils (shorted coils or damaged output)
+    Undecided,
+}
+
+/// Measurements and conclusion of the wiring check.
+#[derive(Debug, Clone, Copy)]
+pub struct WiringReport {
+    /// Outcome of the check
+    pub diagnosis: WiringDiagnosis,
+    /// Pattern selected by the check (the configured one unless corrected)
+    pub pattern: PhasePattern,
+    /// Averaged |current| of the four channels for every candidate (raw ADC units)
+    pub currents: [[i32; 4]; 3],
+    /// Idle ratio of every candidate (x1000)
+    pub ratios: [i32; 3],
+}
+
+/// Coil pairing detection of two-phase motors.
+pub struct WiringCheck {
+    settle: u32,  // Ticks a candidate is held before sampling
+    samples: u32, // Averaged samples per candidate
+    stage: WiringStage,
+    configured: PhasePattern, // Pattern set by the user
+
+    candidate: usize, // Candidate being measured
+    ticks: u32,       // Ticks at the candidate
+    sums: [i64; 4],   // Summed |current| of every channel
+    report: WiringReport,
+}
+
+impl WiringCheck {
+    /// Creates an idle check.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            settle: frequency as u32 / 100,  // 10 ms
+            samples: frequency as u32 / 200, // 5 ms
+            stage: WiringStage::Idle,
+            configured: PhasePattern::ABCD,
+            candidate: 0,
+            ticks: 0,
+            sums: [0; 4],
+            report: WiringReport {
+                diagnosis: WiringDiagnosis::Ok,
+                pattern: PhasePattern::ABCD,
+                currents: [[0; 4]; 3],
+                ratios: [0; 3],
+            },
+        }
+    }
+
+    /// Returns the check to idle, it starts again with the next calibration.
+    pub fn reset(&mut self) {
+        self.stage = WiringStage::Idle;
+    }
+
+    /// Starts the check, `configured` is kept if it already matches the wiring.
+    pub fn start(&mut self, configured: PhasePattern) {
+        self.configured = configured;
+        self.stage = WiringStage::Running;
+        self.candidate = 0;
+        self.ticks = 0;
+        self.sums = [0; 4];
+        self.report.pattern = configured;
+    }
+
+    /// Advances the check.
+    ///
+    /// # Arguments
+    /// * `currents` - Phase currents with offsets removed (raw ADC units)
+    ///
+    /// Returns the (pattern, electrical angle) to apply while running.
+    pub fn tick(&mut self, currents: [i16; 4]) -> (PhasePattern, u16) {
+        if self.stage != WiringStage::Running {
+            return (self.report.pattern, COIL_A_ANGLE);
+        }
+        self.ticks += 1;
+        if self.ticks > self.settle {
+            for (sum, &current) in self.sums.iter_mut().zip(currents.iter()) {
+                *sum += (current as i64).abs();
+            }
+        }
+        if self.ticks >= self.settle + self.samples.max(1) {
+            let count = self.samples.max(1) as i64;
+            let average = self.sums.map(|sum| (sum / count) as i32);
+            let pattern = CANDIDATES[self.candidate] as usize;
+            // Outputs carrying logical channels 0 / 1 belong to coil A
+            let (mut driven, mut idle) = (0, 0);
+            for (output, &current) in average.iter().enumerate() {
+                if (pattern >> (2 * output)) & 0b11 < 2 {
+                    driven += current;
+                } else {
+                    idle += current;
+                }
+            }
+            self.report.currents[self.candidate] = average;
+            self.report.ratios[self.candidate] = if driven > 0 { idle * 1000 / driven } else { i32::MAX };
+
+            self.candidate += 1;
+            self.ticks = 0;
+            self.sums = [0; 4];
+            if self.candidate == CANDIDATES.len() {
+                self.finish();
+            }
+        }
+        let candidate = CANDIDATES[self.candidate.min(CANDIDATES.len() - 1)];
+        (if self.stage == WiringStage::Running { candidate } else { self.report.pattern }, COIL_A_ANGLE)
+    }
+
+    /// Selects the best candidate and diagnoses the wiring.
+    fn finish(&mut self) {
+        let signal = self.report.currents.iter().map(|c| c.iter().sum::<i32>()).max().unwrap_or(0) / 2;
+        let (best, &ratio) = self
+            .report
+            .ratios
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &ratio)| ratio)
+            .unwrap_or((0, &i32::MAX));
+
+        self.report.pattern = self.configured;
+        self.report.diagnosis = if signal < WIRING_MIN_SIGNAL {
+            WiringDiagnosis::NoCurrent
+        } else if ratio > WIRING_MAX_RATIO {
+            WiringDiagnosis::Undecided
+        } else if self.coil_pairs(CANDIDATES[best]) == self.coil_pairs(self.configured) {
+            WiringDiagnosis::Ok
+        } else {
+            self.report.pattern = CANDIDATES[best];
+            WiringDiagnosis::Corrected
+        };
+        self.stage = match self.report.diagnosis {
+            WiringDiagnosis::Ok | WiringDiagnosis::Corrected => WiringStage::Done,
+            _ => WiringStage::Failed,
+        };
+        match self.report.diagnosis {
+            WiringDiagnosis::Ok => defmt::info!("WIRING: Coils connected as configured"),
+            WiringDiagnosis::Corrected => {
+                defmt::warn!("WIRING: Coils paired differently, pattern {} selected", self.report.pattern as u8)
+            }
+            WiringDiagnosis::NoCurrent => defmt::error!("WIRING: No coil current, open coil or no motor"),
+            WiringDiagnosis::Undecided => {
+                defmt::error!("WIRING: Coils not separable, idle ratios {}", self.report.ratios)
+            }
+        }
+    }
+
+    /// Bit mask of the outputs carrying coil A (the pairing, independent of polarity and order).
+    fn coil_pairs(&self, pattern: PhasePattern) -> u8 {
+        let pattern = pattern as u8;
+        let coil_a = (0..4)
+            .filter(|&output| (pattern >> (2 * output)) & 0b11 < 2)
+            .fold(0, |mask, output| mask | 1 << output);
+        // Coil B on the other two outputs is the same pairing
+        if coil_a & 1 != 0 { coil_a } else { !coil_a & 0b1111 }
+    }
+
+    /// Returns the active stage.
+    pub fn stage(&self) -> WiringStage {
+        self.stage
+    }
+
+    /// Returns true while the candidates are measured.
+    pub fn is_active(&self) -> bool {
+        self.stage == WiringStage::Running
+    }
+
+    /// Returns true once the wiring is known.
+    pub fn is_ready(&self) -> bool {
+        self.stage == WiringStage::Done
+    }
+
+    /// Returns the measurements and diagnosis (valid once done or failed).
+    pub fn report(&self) -> WiringReport {
+        self.report
+    }
+}
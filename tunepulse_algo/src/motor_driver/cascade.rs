@@ -0,0 +1,550 @@
+// Implements the control cascade module, closing the position and velocity loops on top of the
+// current (torque) command for every motor type.
+
+// Key Features:
+// - Selectable torque, velocity and position modes sharing one output: signed current in mA.
+// - Position loop feeding the velocity loop, velocity loop feeding the current command.
+// - Velocity estimation from the encoder position with the speed estimator.
+// - Independent velocity and current limits applied at every stage.
+// - External velocity window (travel limits) refusing motion in a blocked direction.
+// - Additive torque feed-forward (e.g. coupling compensation from another axis).
+// - Velocity feedback from the built-in estimator or from an external one (PLL, observer).
+// - Optional notch on the velocity error to suppress mechanical resonances (belts, leadscrews).
+// - Optional gain schedules of both loops indexed by speed or load.
+// - Friction (Coulomb, viscous) and constant gravity feed-forward in velocity and position modes.
+// - Trajectory velocity and acceleration feed-forward (kff_v, kff_a) in position mode.
+// - Optional disturbance observer cancelling the external load in velocity and position modes.
+// - Measured tick period (jitter) scaling the velocity estimate and the loop integrators.
+// - Current loop (PI per field / torque axis) turning the current command into the drive voltage.
+
+// Detailed Operation:
+// The cascade is motor agnostic: it only sees the mechanical position (encoder counts) and
+// produces a signed torque current. The position loop converts the position error into a velocity
+// command, the velocity loop converts the velocity error into a current command. Both loops use the
+// integer PID controller, which works in the i16 range, so velocities are processed with a reduced
+// resolution of 2^VEL_SHIFT counts/s per LSB. Mapping of the signed current to the electrical
+// angle and amplitude (commutation) is left to the caller, which allows DC motors to bypass it.
+// The current loop works on the full i32 range with the PI controller: with measured phase currents
+// the caller passes the (field, torque) command and measurement, the PI output (mV) is handed back
+// scaled by 1 / R, so a driver applying V = I * R outputs exactly the loop voltage. With the gains of
+// `CurrentLoopGains` the closed loop is a first-order low pass at the requested bandwidth. Without
+// gains (unknown inductance) the command passes unchanged.
+// The resonance notch filters the velocity error in both velocity and position modes (the position
+// loop output enters the velocity loop), so the loop gain is reduced only around the resonance.
+// A gain schedule replaces the fixed gains of its loop with gains interpolated at the operating point
+// (|velocity| or |current command| of the previous tick), the loop states are kept while they change.
+// Friction feed-forward follows the velocity command, so it acts before the axis moves; the Coulomb
+// term ramps linearly within ±`friction_band` around zero to avoid chattering at standstill. Gravity is
+// a constant current holding a vertical axis, independent of the direction.
+// In position mode the trajectory generator also provides its planned velocity and acceleration:
+// kff_v * velocity is added to the velocity command and kff_a * acceleration to the current command,
+// so the loops only correct the residual error instead of building it up first. With kff_v = 1.0 and
+// kff_a = J / Kt (mA per rev/s^2, the inverse of the identified acceleration per ampere) the
+// following error of a well-modeled axis stays close to zero at moderate position gains.
+// The disturbance observer compares the last current command with the measured velocity through the
+// nominal inertia; its load estimate is added to the velocity loop output, so the loops see the
+// nominal plant. It keeps estimating in torque mode, so enabling a closed loop later is bumpless.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::CurrentLoopGains;
+use crate::math_integer::controllers::gain_schedule::{GainPoint, GainSchedule};
+use crate::math_integer::controllers::pi::PI;
+use crate::math_integer::controllers::pid::PID;
+use crate::math_integer::filters::biquad::{BiquadCoeffs, FilterBiquad};
+use crate::math_integer::motion::disturbance::DisturbanceObserver;
+use crate::math_integer::motion::speed_estimator::SpeedEstimator;
+
+/// Velocity resolution used inside the loops: 2^VEL_SHIFT counts/s per LSB
+const VEL_SHIFT: u32 = 4;
+/// Default width of the resonance notch: Q x1000
+const NOTCH_Q_DEFAULT: u32 = 2000;
+
+/// Selects which setpoint drives the cascade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CascadeMode {
+    /// Current setpoint is used directly
+    Torque,
+    /// Velocity loop is closed around the velocity setpoint
+    Velocity,
+    /// Position loop is closed around the position setpoint
+    Position,
+}
+
+/// Selects the velocity feedback of the cascade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocitySource {
+    /// Position difference over a short window (built-in)
+    Differentiator,
+    /// Encoder angle tracking loop (PLL)
+    Tracker,
+    /// Model-based Luenberger observer (uses the torque command)
+    Observer,
+}
+
+/// Selects the operating point indexing the gain schedules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleIndex {
+    /// Measured |velocity| (counts/s)
+    Speed,
+    /// |Current command| (mA)
+    Load,
+}
+
+/// Position -> velocity -> current control cascade.
+pub struct Cascade {
+    mode: CascadeMode,
+    frequency: u16, // Update frequency (ticks per second)
+    period: i32,    // Measured period of the tick relative to the nominal one (Q16)
+
+    speed: SpeedEstimator,
+    pos_pid: PID,
+    vel_pid: PID,
+    pos_gains: (i32, i32, i32),    // Kp, Ki, Kd of the position loop
+    vel_gains: (i32, i32, i32),    // Kp, Ki, Kd of the velocity loop
+    pos_schedule: GainSchedule,    // Gain table of the position loop (empty - fixed gains)
+    vel_schedule: GainSchedule,    // Gain table of the velocity loop (empty - fixed gains)
+    schedule_index: ScheduleIndex, // Operating point of both gain tables
+
+    target_pos: i32,        // Position setpoint (encoder counts)
+    target_vel: i32,        // Velocity setpoint (counts/s)
+    target_cur: i32,        // Current setpoint (mA)
+    vel_limit: i32,         // Maximum velocity command (counts/s)
+    vel_window: (i32, i32), // Allowed (minimum, maximum) velocity set by travel limits (counts/s)
+    torque_ff: i32,         // Feed-forward current added to the loop output (mA)
+    coulomb_ff: i32,        // Coulomb friction feed-forward (mA)
+    viscous_ff: i32,        // Viscous friction feed-forward (mA per rev/s)
+    friction_band: i32,     // Velocity command where the Coulomb term reaches full size (counts/s)
+    gravity_ff: i32,        // Constant gravity feed-forward (mA)
+    kff_v: i32,             // Trajectory velocity feed-forward gain (Q16, 1 << 16 = full velocity)
+    kff_a: i32,             // Trajectory acceleration feed-forward gain (mA per rev/s^2)
+    traj_vel: i32,          // Planned velocity of the trajectory (counts/s)
+    traj_accel: i32,        // Planned acceleration of the trajectory (counts/s^2)
+
+    dob: DisturbanceObserver, // Load torque estimator (disabled by default)
+
+    notch: FilterBiquad,     // Resonance notch on the velocity error
+    notch_center: u16,       // Notch center frequency (Hz), 0 - disabled
+    notch_depth: u16,        // Gain at the notch center (0..32767 = 0.0..1.0)
+    notch_q: u32,            // Notch quality factor x1000
+
+    velocity: i32,             // Measured velocity (counts/s)
+    vel_external: Option<i32>, // Velocity from an external estimator for the next tick (counts/s)
+    current: i32,              // Output current command (mA)
+
+    field_pi: PI,  // Field current loop (mV), zero gains - open loop
+    torque_pi: PI, // Torque current loop (mV), zero gains - open loop
+}
+
+impl Cascade {
+    /// Creates a new cascade in torque mode with zero setpoint.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        let pos_gains = (1000, 0, 0);
+        let vel_gains = (100, 10, 0);
+        Self {
+            mode: CascadeMode::Torque,
+            frequency,
+            period: 1 << 16,
+            speed: SpeedEstimator::new(0, frequency),
+            pos_pid: PID::new(pos_gains.0, pos_gains.1, pos_gains.2, 0),
+            vel_pid: PID::new(vel_gains.0, vel_gains.1, vel_gains.2, 0),
+            pos_gains,
+            vel_gains,
+            pos_schedule: GainSchedule::new(),
+            vel_schedule: GainSchedule::new(),
+            schedule_index: ScheduleIndex::Speed,
+            target_pos: 0,
+            target_vel: 0,
+            target_cur: 0,
+            vel_limit: (i16::MAX as i32) << VEL_SHIFT,
+            vel_window: (i32::MIN, i32::MAX),
+            torque_ff: 0,
+            coulomb_ff: 0,
+            viscous_ff: 0,
+            friction_band: 1 << 12, // 1/16 rev/s
+            gravity_ff: 0,
+            kff_v: 0,
+            kff_a: 0,
+            traj_vel: 0,
+            traj_accel: 0,
+            dob: DisturbanceObserver::new(frequency),
+            notch: FilterBiquad::new(BiquadCoeffs::identity()),
+            notch_center: 0,
+            notch_depth: 0,
+            notch_q: NOTCH_Q_DEFAULT,
+            velocity: 0,
+            vel_external: None,
+            current: 0,
+            field_pi: PI::new(0, 0),
+            torque_pi: PI::new(0, 0),
+        }
+    }
+
+    /// Updates the cascade and returns the signed current command.
+    ///
+    /// # Arguments
+    /// * `position` - Current encoder position (counts)
+    /// * `current_limit` - Maximum current amplitude (mA)
+    pub fn tick(&mut self, position: i32, current_limit: i32) -> i32 {
+        // Built-in estimator keeps running so switching back to it is bumpless
+        let estimated = self.speed.tick_dt(position, self.period).get_speed();
+        self.velocity = self.vel_external.take().unwrap_or(estimated);
+        let current_limit = current_limit.clamp(0, i16::MAX as i32);
+        // The command of the previous tick is the one applied while the velocity was measured
+        let load = self.dob.tick(self.velocity, self.current);
+
+        // ######################## GAIN SCHEDULE ####################################
+        let operating = match self.schedule_index {
+            ScheduleIndex::Speed => self.velocity.saturating_abs(),
+            ScheduleIndex::Load => self.current.saturating_abs(),
+        };
+        if let Some((kp, ki, kd)) = self.pos_schedule.gains(operating) {
+            self.pos_pid.set_gains(kp, ki, kd);
+        }
+        if let Some((kp, ki, kd)) = self.vel_schedule.gains(operating) {
+            self.vel_pid.set_gains(kp, ki, kd);
+        }
+
+        self.pos_pid.set_dt(self.period);
+        self.vel_pid.set_dt(self.period);
+
+        // ######################## POSITION LOOP ####################################
+        let vel_cmd = match self.mode {
+            CascadeMode::Position => {
+                let error = fit_i16(self.target_pos.wrapping_sub(position));
+                let limit = fit_i16(self.vel_limit >> VEL_SHIFT);
+                self.pos_pid.tick(error, 0, limit);
+                let feedforward = ((self.kff_v as i64 * self.traj_vel as i64) >> 16) as i32;
+                ((self.pos_pid.output() as i32) << VEL_SHIFT)
+                    .saturating_add(feedforward)
+                    .clamp(-self.vel_limit, self.vel_limit)
+            }
+            CascadeMode::Velocity => self.target_vel.clamp(-self.vel_limit, self.vel_limit),
+            CascadeMode::Torque => 0,
+        };
+        let vel_cmd = vel_cmd.clamp(self.vel_window.0, self.vel_window.1);
+
+        // ######################## VELOCITY LOOP ####################################
+        self.current = match self.mode {
+            CascadeMode::Torque => self.target_cur,
+            _ => {
+                let mut error = vel_cmd.saturating_sub(self.velocity);
+                if self.notch_center != 0 {
+                    error = self.notch.tick(error);
+                }
+                let error = fit_i16(error >> VEL_SHIFT);
+                self.vel_pid.tick(error, 0, current_limit as i16);
+                (self.vel_pid.output() as i32)
+                    .saturating_add(self.load_feedforward(vel_cmd))
+                    .saturating_add(load)
+            }
+        };
+        self.current = self.current.saturating_add(self.torque_ff);
+        if self.mode == CascadeMode::Position {
+            let feedforward = (self.kff_a as i64 * self.traj_accel as i64) >> 16;
+            self.current = self.current.saturating_add(feedforward.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+        }
+
+        // Refuse to push further into a closed side of the velocity window (braking is allowed)
+        if self.vel_window.1 <= 0 {
+            self.current = self.current.min(0);
+        }
+        if self.vel_window.0 >= 0 {
+            self.current = self.current.max(0);
+        }
+
+        self.current = self.current.clamp(-current_limit, current_limit);
+        self.current
+    }
+
+    /// Sets the measured period of the next ticks relative to the nominal one (Q16, 1 << 16 =
+    /// nominal), the velocity estimate and the integrators scale by it.
+    pub fn set_period(&mut self, period: i32) {
+        self.period = period.max(1);
+    }
+
+    /// Re-initializes loop states around the given position, holding it in position mode.
+    pub fn reset(&mut self, position: i32) {
+        self.speed = SpeedEstimator::new(position, self.frequency);
+        self.pos_pid = PID::new(self.pos_gains.0, self.pos_gains.1, self.pos_gains.2, 0);
+        self.vel_pid = PID::new(self.vel_gains.0, self.vel_gains.1, self.vel_gains.2, 0);
+        self.target_pos = position;
+        self.velocity = 0;
+        self.notch.reset(0);
+        self.dob.reset();
+        self.field_pi.reset(0);
+        self.torque_pi.reset(0);
+    }
+
+    /// Closes the current loop and returns the (field, torque) drive command in mA of the resistive
+    /// drop, i.e. the loop voltage divided by the resistance for a driver applying V = I * R.
+    ///
+    /// The command passes unchanged without current loop gains or a known resistance.
+    ///
+    /// # Arguments
+    /// * `command` - (field, torque) current command (mA)
+    /// * `measured` - Measured (field, torque) current (mA)
+    /// * `resistance` - Winding resistance (mOhm)
+    /// * `supply_mv` - Supply voltage limiting the loop output (mV)
+    pub fn regulate_current(
+        &mut self,
+        command: (i32, i32),
+        measured: (i32, i32),
+        resistance: i32,
+        supply_mv: i32,
+    ) -> (i32, i32) {
+        if self.torque_pi.gains() == (0, 0) || resistance <= 0 {
+            return command;
+        }
+        let field = Self::current_axis(&mut self.field_pi, command.0, measured.0, resistance, supply_mv);
+        let torque = Self::current_axis(&mut self.torque_pi, command.1, measured.1, resistance, supply_mv);
+        (field, torque)
+    }
+
+    /// One axis of the current loop, see `regulate_current`.
+    #[inline(always)]
+    fn current_axis(pi: &mut PI, command: i32, measured: i32, resistance: i32, supply_mv: i32) -> i32 {
+        pi.set_limit(supply_mv);
+        let voltage = pi.tick(command.saturating_sub(measured), 0);
+        (voltage as i64 * 1000 / resistance as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+
+    /// Loads the current loop gains into both axes without a step of the output, zero gains open
+    /// the loop.
+    pub fn set_current_gains(&mut self, gains: &CurrentLoopGains) {
+        gains.apply(&mut self.field_pi);
+        gains.apply(&mut self.torque_pi);
+    }
+
+    /// Switches to torque mode with the given current setpoint (mA).
+    pub fn set_torque(&mut self, current: i32) {
+        self.target_cur = current;
+        self.mode = CascadeMode::Torque;
+    }
+
+    /// Switches to velocity mode with the given velocity setpoint (counts/s).
+    pub fn set_velocity(&mut self, velocity: i32) {
+        self.target_vel = velocity;
+        self.mode = CascadeMode::Velocity;
+    }
+
+    /// Switches to position mode with the given position setpoint (counts).
+    pub fn set_position(&mut self, position: i32) {
+        self.target_pos = position;
+        self.mode = CascadeMode::Position;
+    }
+
+    /// Sets position loop gains (same scale as the PID controller: -10000% to 10000%).
+    pub fn set_position_gains(&mut self, kp: i32, ki: i32, kd: i32) {
+        self.pos_gains = (kp, ki, kd);
+        self.pos_pid = PID::new(kp, ki, kd, 0);
+    }
+
+    /// Sets velocity loop gains (same scale as the PID controller: -10000% to 10000%).
+    pub fn set_velocity_gains(&mut self, kp: i32, ki: i32, kd: i32) {
+        self.vel_gains = (kp, ki, kd);
+        self.vel_pid = PID::new(kp, ki, kd, 0);
+    }
+
+    /// Loads the gain table of the position loop, an empty table restores the fixed gains.
+    ///
+    /// Returns false (table unchanged) for more than `SCHEDULE_POINTS` points or unsorted indexes.
+    pub fn set_position_schedule(&mut self, points: &[GainPoint]) -> bool {
+        let accepted = self.pos_schedule.configure(points);
+        if accepted && points.is_empty() {
+            self.pos_pid.set_gains(self.pos_gains.0, self.pos_gains.1, self.pos_gains.2);
+        }
+        accepted
+    }
+
+    /// Loads the gain table of the velocity loop, an empty table restores the fixed gains.
+    ///
+    /// Returns false (table unchanged) for more than `SCHEDULE_POINTS` points or unsorted indexes.
+    pub fn set_velocity_schedule(&mut self, points: &[GainPoint]) -> bool {
+        let accepted = self.vel_schedule.configure(points);
+        if accepted && points.is_empty() {
+            self.vel_pid.set_gains(self.vel_gains.0, self.vel_gains.1, self.vel_gains.2);
+        }
+        accepted
+    }
+
+    /// Selects the operating point of the gain tables (default: speed).
+    pub fn set_schedule_index(&mut self, index: ScheduleIndex) {
+        self.schedule_index = index;
+    }
+
+    /// Sets the maximum velocity command (counts/s).
+    pub fn set_velocity_limit(&mut self, limit: i32) {
+        self.vel_limit = limit.clamp(0, (i16::MAX as i32) << VEL_SHIFT);
+    }
+
+    /// Sets the allowed (minimum, maximum) velocity window (counts/s), updated every tick.
+    pub fn set_velocity_window(&mut self, window: (i32, i32)) {
+        self.vel_window = window;
+    }
+
+    /// Provides the velocity measured by an external estimator (counts/s), used for the next tick only.
+    pub fn set_measured_velocity(&mut self, velocity: i32) {
+        self.vel_external = Some(velocity);
+    }
+
+    /// Sets the feed-forward current added to the loop output (mA), updated every tick.
+    pub fn set_torque_feedforward(&mut self, current: i32) {
+        self.torque_ff = current;
+    }
+
+    /// Sets the friction feed-forward of the velocity and position modes.
+    ///
+    /// # Arguments
+    /// * `coulomb` - Coulomb friction (mA), applied with the sign of the velocity command
+    /// * `viscous` - Viscous friction (mA per rev/s of velocity command)
+    /// * `band` - Velocity command where the Coulomb term reaches full size (counts/s)
+    pub fn set_friction_feedforward(&mut self, coulomb: i32, viscous: i32, band: i32) {
+        self.coulomb_ff = coulomb;
+        self.viscous_ff = viscous;
+        self.friction_band = band.max(1);
+    }
+
+    /// Sets the constant gravity feed-forward of the velocity and position modes (mA).
+    pub fn set_gravity_feedforward(&mut self, current: i32) {
+        self.gravity_ff = current;
+    }
+
+    /// Sets the trajectory feed-forward gains of the position mode.
+    ///
+    /// # Arguments
+    /// * `kff_v` - Velocity feed-forward (Q16, 1 << 16 adds the full planned velocity)
+    /// * `kff_a` - Acceleration feed-forward (mA per rev/s^2)
+    pub fn set_feedforward_gains(&mut self, kff_v: i32, kff_a: i32) {
+        self.kff_v = kff_v;
+        self.kff_a = kff_a;
+    }
+
+    /// Returns the trajectory feed-forward gains (kff_v, kff_a).
+    pub fn feedforward_gains(&self) -> (i32, i32) {
+        (self.kff_v, self.kff_a)
+    }
+
+    /// Sets the planned velocity (counts/s) and acceleration (counts/s^2) of the trajectory,
+    /// updated every tick (zeros while no trajectory runs).
+    pub fn set_trajectory_feedforward(&mut self, velocity: i32, acceleration: i32) {
+        self.traj_vel = velocity;
+        self.traj_accel = acceleration;
+    }
+
+    /// Configures the disturbance observer of the velocity and position modes.
+    ///
+    /// # Arguments
+    /// * `accel_per_amp` - Nominal acceleration per ampere (counts/s^2 per A), 0 disables the observer
+    /// * `cutoff` - Q-filter cutoff (Hz)
+    /// * `limit` - Maximum compensation current (mA)
+    pub fn set_disturbance_observer(&mut self, accel_per_amp: i32, cutoff: u16, limit: i32) {
+        self.dob.configure(accel_per_amp, cutoff, limit);
+    }
+
+    /// Returns the estimated external load (mA of torque current), 0 while the observer is off.
+    pub fn load_estimate(&self) -> i32 {
+        self.dob.estimate()
+    }
+
+    /// Friction and gravity current for the velocity command (mA).
+    #[inline(always)]
+    fn load_feedforward(&self, vel_cmd: i32) -> i32 {
+        let band = self.friction_band as i64;
+        let coulomb = self.coulomb_ff as i64 * (vel_cmd as i64).clamp(-band, band) / band;
+        let viscous = (self.viscous_ff as i64 * vel_cmd as i64) >> 16;
+        (coulomb + viscous + self.gravity_ff as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+
+    /// Configures the resonance notch on the velocity error, updated without resetting the loops.
+    ///
+    /// # Arguments
+    /// * `center` - Resonance frequency (Hz), 0 disables the notch
+    /// * `depth` - Remaining gain at the center (0..32767 = 0.0..1.0, 0 = full rejection)
+    pub fn set_notch(&mut self, center: u16, depth: u16) {
+        self.notch_center = center.min(self.frequency / 2);
+        self.notch_depth = depth;
+        self.update_notch();
+    }
+
+    /// Sets the notch width as a quality factor x1000 (higher is narrower, default 2000).
+    pub fn set_notch_width(&mut self, q_milli: u32) {
+        self.notch_q = q_milli.max(100);
+        self.update_notch();
+    }
+
+    /// Returns the notch (center frequency Hz, depth) pair.
+    pub fn notch(&self) -> (u16, u16) {
+        (self.notch_center, self.notch_depth)
+    }
+
+    /// Returns the notch width as a quality factor x1000.
+    pub fn notch_width(&self) -> u32 {
+        self.notch_q
+    }
+
+    /// Recomputes the notch coefficients from the stored settings.
+    fn update_notch(&mut self) {
+        if self.notch_center == 0 {
+            self.notch.reset(0);
+            return;
+        }
+        let coeffs = BiquadCoeffs::band_stop(
+            self.notch_center as u32,
+            self.notch_q,
+            self.notch_depth,
+            self.frequency as u32,
+        );
+        self.notch.set_coeffs(coeffs);
+    }
+
+    /// Returns position loop gains (Kp, Ki, Kd).
+    pub fn position_gains(&self) -> (i32, i32, i32) {
+        self.pos_gains
+    }
+
+    /// Returns velocity loop gains (Kp, Ki, Kd).
+    pub fn velocity_gains(&self) -> (i32, i32, i32) {
+        self.vel_gains
+    }
+
+    /// Returns the maximum velocity command (counts/s).
+    pub fn velocity_limit(&self) -> i32 {
+        self.vel_limit
+    }
+
+    /// Returns the active mode.
+    pub fn mode(&self) -> CascadeMode {
+        self.mode
+    }
+
+    /// Returns the measured velocity (counts/s).
+    pub fn velocity(&self) -> i32 {
+        self.velocity
+    }
+
+    /// Returns the last current command (mA).
+    pub fn current(&self) -> i32 {
+        self.current
+    }
+
+    /// Returns the position setpoint (counts).
+    pub fn target_position(&self) -> i32 {
+        self.target_pos
+    }
+
+    /// Returns the velocity setpoint (counts/s).
+    pub fn target_velocity(&self) -> i32 {
+        self.target_vel
+    }
+}
+
+/// Saturates an i32 value into the i16 range used by the PID controller.
+#[inline(always)]
+fn fit_i16(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16
+}
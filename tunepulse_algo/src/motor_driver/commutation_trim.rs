@@ -0,0 +1,119 @@
+// Implements the commutation trim module, allowing a small runtime correction of the
+// electrical angle offset after calibration with live feedback of the alignment quality.
+
+// Key Features:
+// - Signed electrical angle trim limited to a small range around the calibrated offset.
+// - Live averaging of the current command and measured velocity while the motor runs.
+// - Current-per-velocity figure to peak the alignment manually (lower current = better alignment).
+// - Trim value can be read back and restored at boot to persist it.
+
+// Detailed Operation:
+// The trim is added to the calibrated rotor angle before the torque vector is placed 90° ahead of it.
+// A misaligned vector produces torque proportional to cos(error), so the cascade needs more current
+// for the same load. Averaging the current command under a steady load (constant speed or holding
+// against gravity) while sweeping the trim shows a minimum at the best alignment. Averages are reset
+// on every trim change so each reading reflects only the current setting.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::angle::Angle16;
+
+/// Maximum trim in both directions (electrical, 2048 = 11.25°)
+pub const TRIM_RANGE: i16 = 2048;
+
+/// Averaging strength: new sample weight is 1 / 2^AVG_SHIFT
+const AVG_SHIFT: u32 = 8;
+
+/// Live report of the commutation trim effect.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrimReport {
+    /// Active trim (electrical angle units)
+    pub offset: i16,
+    /// Average absolute current command (mA)
+    pub current_ma: i32,
+    /// Average absolute velocity (counts/s)
+    pub velocity: i32,
+    /// Current per velocity (mA per 1000 counts/s), 0 when standing still
+    pub current_per_velocity: i32,
+}
+
+/// Runtime fine-trim of the commutation offset.
+pub struct CommutationTrim {
+    offset: i16,       // Active trim (electrical angle units)
+    current_avg: i32,  // Averaged |current| (mA, scaled by 2^AVG_SHIFT)
+    velocity_avg: i32, // Averaged |velocity| (counts/s, scaled by 2^AVG_SHIFT)
+    samples: u32,      // Number of samples since the last trim change
+}
+
+impl CommutationTrim {
+    /// Creates a trim with zero offset.
+    pub const fn new() -> Self {
+        Self {
+            offset: 0,
+            current_avg: 0,
+            velocity_avg: 0,
+            samples: 0,
+        }
+    }
+
+    /// Applies the trim to the calibrated rotor angle.
+    #[inline(always)]
+    pub fn apply(&self, rotor_el: u16) -> u16 {
+        Angle16(rotor_el).offset(self.offset as i32).0
+    }
+
+    /// Accumulates the live effect of the trim.
+    ///
+    /// # Arguments
+    /// * `current` - Signed current command (mA)
+    /// * `velocity` - Measured velocity (counts/s)
+    pub fn tick(&mut self, current: i32, velocity: i32) {
+        let current = (current.unsigned_abs().min(i16::MAX as u32) as i32) << AVG_SHIFT;
+        let velocity = (velocity.unsigned_abs().min(1 << 22) as i32) << AVG_SHIFT;
+
+        if self.samples == 0 {
+            // Start averaging from the first sample to avoid a slow rise from zero
+            self.current_avg = current;
+            self.velocity_avg = velocity;
+        } else {
+            self.current_avg += (current - self.current_avg) >> AVG_SHIFT;
+            self.velocity_avg += (velocity - self.velocity_avg) >> AVG_SHIFT;
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// Sets the trim, clamped to `±TRIM_RANGE`, and restarts averaging.
+    pub fn set_offset(&mut self, offset: i16) {
+        self.offset = offset.clamp(-TRIM_RANGE, TRIM_RANGE);
+        self.samples = 0;
+    }
+
+    /// Returns the active trim.
+    pub fn offset(&self) -> i16 {
+        self.offset
+    }
+
+    /// Returns the live report of the trim effect.
+    pub fn report(&self) -> TrimReport {
+        let current_ma = self.current_avg >> AVG_SHIFT;
+        let velocity = self.velocity_avg >> AVG_SHIFT;
+        let current_per_velocity = if velocity > 0 {
+            ((current_ma as i64 * 1000) / velocity as i64) as i32
+        } else {
+            0
+        };
+        TrimReport {
+            offset: self.offset,
+            current_ma,
+            velocity,
+            current_per_velocity,
+        }
+    }
+}
+
+impl Default for CommutationTrim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
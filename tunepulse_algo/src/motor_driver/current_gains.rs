@@ -0,0 +1,120 @@
+// Implements the current-loop gain computation, deriving PI gains for a requested bandwidth from
+// the winding resistance and inductance.
+
+// Key Features:
+// - Pole-zero cancellation design: the PI zero cancels the electrical pole R / L.
+// - Gains in the scale of the PI controller primitive (Q16, mV per mA and per tick).
+// - Bandwidth limited to 1/10 of the update frequency (sampling and PWM update delay).
+// - Plain data, so computed gains can be persisted, inspected and restored.
+
+// Detailed Operation:
+// A winding is the first-order plant I/V = 1 / (L * s + R). With Kp = ωc * L and Ki = ωc * R the PI
+// zero at Ki / Kp = R / L cancels the plant pole and the open loop becomes ωc / s, so the closed
+// current loop is a first-order low pass with the cutoff ωc = 2π * bandwidth. The per-tick integral gain
+// is Ki / f. Units: resistance in mOhm, inductance in uH, so Kp = ωc * L (Ohm = mV/mA) and
+// Ki = ωc * R / f (mV per mA and tick), both stored with 16 fractional bits.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::controllers::pi::PI;
+
+/// 2π scaled by 2^16
+const TWO_PI_Q16: i64 = 411_775;
+
+/// PI gains of the current loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CurrentLoopGains {
+    /// Closed-loop bandwidth the gains were computed for (Hz)
+    pub bandwidth: u16,
+    /// Proportional gain (Q16, mV per mA)
+    pub kp: i32,
+    /// Integral gain (Q16, mV per mA and tick)
+    pub ki: i32,
+}
+
+impl CurrentLoopGains {
+    /// Computes the gains for a bandwidth, 0 gains (bandwidth kept for a later recompute) if the winding
+    /// is unknown.
+    ///
+    /// # Arguments
+    /// * `resistance` - Winding resistance (mOhm)
+    /// * `inductance` - Winding inductance (uH)
+    /// * `bandwidth` - Requested closed-loop bandwidth (Hz), limited to 1/10 of `frequency`
+    /// * `frequency` - Number of ticks per second
+    pub fn compute(resistance: i32, inductance: i32, bandwidth: u16, frequency: u16) -> Self {
+        let bandwidth = bandwidth.min(frequency / 10);
+        if resistance <= 0 || inductance <= 0 || bandwidth == 0 {
+            return Self { bandwidth, kp: 0, ki: 0 };
+        }
+        let omega = TWO_PI_Q16 * bandwidth as i64; // rad/s, Q16
+        let kp = omega * inductance as i64 / 1_000_000; // uH -> H
+        let ki = omega * resistance as i64 / (1000 * frequency as i64); // mOhm -> Ohm, per tick
+        Self {
+            bandwidth,
+            kp: kp.min(i32::MAX as i64) as i32,
+            ki: ki.min(i32::MAX as i64) as i32,
+        }
+    }
+
+    /// Loads the gains into a PI controller without a step of its output.
+    pub fn apply(&self, pi: &mut PI) {
+        pi.set_gains(self.kp, self.ki);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motor_driver::Cascade;
+    use std::f64::consts::PI as F64_PI;
+
+    const FREQUENCY: u16 = 20000;
+    const RESISTANCE: i32 = 1500; // mOhm
+    const INDUCTANCE: i32 = 2200; // uH
+
+    #[test]
+    fn gains_match_the_closed_form() {
+        for bandwidth in [50u16, 200, 800, 2000] {
+            let gains = CurrentLoopGains::compute(RESISTANCE, INDUCTANCE, bandwidth, FREQUENCY);
+            let omega = 2.0 * F64_PI * bandwidth as f64;
+            let kp = omega * INDUCTANCE as f64 * 1e-6 * 65536.0; // ωc * L
+            let ki = omega * RESISTANCE as f64 * 1e-3 / FREQUENCY as f64 * 65536.0; // ωc * R / f
+            assert_eq!(gains.bandwidth, bandwidth);
+            assert!((gains.kp as f64 - kp).abs() <= 1.0, "{} Hz: Kp {} against {}", bandwidth, gains.kp, kp);
+            assert!((gains.ki as f64 - ki).abs() <= 1.0, "{} Hz: Ki {} against {}", bandwidth, gains.ki, ki);
+        }
+    }
+
+    #[test]
+    fn bandwidth_limited_and_kept_without_winding() {
+        let gains = CurrentLoopGains::compute(RESISTANCE, INDUCTANCE, 5000, FREQUENCY);
+        assert_eq!(gains, CurrentLoopGains::compute(RESISTANCE, INDUCTANCE, FREQUENCY / 10, FREQUENCY));
+        let unknown = CurrentLoopGains::compute(RESISTANCE, 0, 800, FREQUENCY);
+        assert_eq!(unknown, CurrentLoopGains { bandwidth: 800, kp: 0, ki: 0 });
+    }
+
+    #[test]
+    fn cascade_current_loop_follows_the_bandwidth() {
+        let bandwidth = 200;
+        let mut cascade = Cascade::new(FREQUENCY);
+        let supply_mv = 24000;
+        assert_eq!(cascade.regulate_current((0, 1000), (0, 0), RESISTANCE, supply_mv), (0, 1000)); // No gains
+        cascade.set_current_gains(&CurrentLoopGains::compute(RESISTANCE, INDUCTANCE, bandwidth, FREQUENCY));
+
+        // R-L winding driven with V = drive * R, one Euler step per tick
+        let (r, l, dt) = (RESISTANCE as f64 * 1e-3, INDUCTANCE as f64 * 1e-6, 1.0 / FREQUENCY as f64);
+        let tau = (FREQUENCY as f64 / (2.0 * F64_PI * bandwidth as f64)).round() as usize; // Ticks
+        let mut current = 0.0;
+        let mut response = [0.0; 200];
+        for sample in response.iter_mut() {
+            let (_, drive) = cascade.regulate_current((0, 1000), (0, current as i32), RESISTANCE, supply_mv);
+            current += (drive as f64 * r - current * r) / l * dt;
+            *sample = current;
+        }
+        // First-order response: 63% after one time constant, settled after five
+        assert!((response[tau] - 632.0).abs() < 60.0, "{} mA after {} ticks", response[tau], tau);
+        assert!((response[5 * tau] - 1000.0).abs() < 20.0, "{} mA after {} ticks", response[5 * tau], 5 * tau);
+        assert!(response.iter().all(|&i| i < 1050.0));
+    }
+}
@@ -0,0 +1,165 @@
+// Implements the beeper module, playing tones and short melodies through the motor windings by
+// superimposing a small audible-frequency modulation on the phase voltages.
+
+// Key Features:
+// - Melody of up to BEEP_NOTES notes (pitch in Hz, duration in ms), 0 Hz notes are rests.
+// - Sine modulation from a phase accumulator, any pitch up to half of the PWM frequency.
+// - Amplitude limited to BEEP_MAX_DUTY, so the beep can run while the motor holds position.
+// - Fault codes as a number of short beeps, startup chirp as a ready-made melody.
+
+// Detailed Operation:
+// The windings act as a voice coil: a voltage alternating at an audible frequency makes the
+// stator and rotor vibrate at that frequency. The modulation is added along the direction of the
+// applied voltage vector, so it only changes the magnetizing current and no net torque is produced
+// (the holding position doesn't move). The phase accumulator advances by pitch * 2^32 / frequency
+// every tick, its upper 16 bits are the sine angle. The amplitude is a duty fraction (Q15) and is
+// clamped to BEEP_MAX_DUTY to keep the extra current small even at standstill, where only the
+// winding resistance and inductance limit it.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::trigonometry::angle2sincos_interp;
+
+/// Maximum number of notes in a melody
+pub const BEEP_NOTES: usize = 16;
+/// Highest beep amplitude: 10% duty (Q15)
+pub const BEEP_MAX_DUTY: i16 = 3277;
+
+/// One note of a melody.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Note {
+    /// Tone frequency (Hz), 0 - rest
+    pub pitch: u16,
+    /// Note length (ms)
+    pub duration: u16,
+}
+
+/// Rising three-tone chirp, e.g. after a successful calibration.
+pub const STARTUP_CHIRP: [Note; 3] = [
+    Note { pitch: 1047, duration: 80 },
+    Note { pitch: 1319, duration: 80 },
+    Note { pitch: 1568, duration: 120 },
+];
+
+/// Tone generator superimposed on the phase voltages.
+pub struct Beeper {
+    frequency: u16, // Update frequency (ticks per second)
+    amplitude: i16, // Modulation amplitude (Q15 duty)
+
+    melody: [Note; BEEP_NOTES],
+    length: usize,  // Notes in the melody
+    index: usize,   // Playing note
+    remaining: u32, // Ticks left of the playing note
+    phase: u32,     // Tone phase accumulator (2^32 = one period)
+    increment: u32, // Phase advance per tick
+    output: i16,    // Modulation of the last tick (Q15 duty)
+}
+
+impl Beeper {
+    /// Creates a silent beeper.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            amplitude: BEEP_MAX_DUTY / 4,
+            melody: [Note::default(); BEEP_NOTES],
+            length: 0,
+            index: 0,
+            remaining: 0,
+            phase: 0,
+            increment: 0,
+            output: 0,
+        }
+    }
+
+    /// Sets the number of ticks per second, applied from the next note.
+    pub fn set_frequency(&mut self, frequency: u16) {
+        self.frequency = frequency;
+    }
+
+    /// Sets the modulation amplitude (Q15 duty), limited to BEEP_MAX_DUTY.
+    pub fn set_amplitude(&mut self, amplitude: i16) {
+        self.amplitude = amplitude.clamp(0, BEEP_MAX_DUTY);
+    }
+
+    /// Starts a melody, a running one is replaced. Notes beyond BEEP_NOTES are dropped.
+    pub fn play(&mut self, melody: &[Note]) {
+        self.length = melody.len().min(BEEP_NOTES);
+        self.melody[..self.length].copy_from_slice(&melody[..self.length]);
+        self.start_note(0);
+    }
+
+    /// Plays a fault code as `code` short beeps (1..=BEEP_NOTES / 2).
+    ///
+    /// # Arguments
+    /// * `code` - Number of beeps
+    /// * `pitch` - Tone frequency (Hz)
+    pub fn play_code(&mut self, code: u8, pitch: u16) {
+        let beeps = (code as usize).clamp(1, BEEP_NOTES / 2);
+        self.length = 2 * beeps;
+        for pair in self.melody[..self.length].chunks_exact_mut(2) {
+            pair[0] = Note { pitch, duration: 150 };
+            pair[1] = Note { pitch: 0, duration: 150 };
+        }
+        self.start_note(0);
+    }
+
+    /// Stops the melody immediately.
+    pub fn stop(&mut self) {
+        self.length = 0;
+        self.index = 0;
+        self.remaining = 0;
+        self.output = 0;
+    }
+
+    /// Returns true while a melody is playing.
+    pub fn is_playing(&self) -> bool {
+        self.index < self.length
+    }
+
+    /// Advances the melody and returns the modulation (Q15 duty), 0 when silent.
+    pub fn tick(&mut self) -> i16 {
+        if !self.is_playing() {
+            return 0;
+        }
+        if self.remaining == 0 {
+            self.start_note(self.index + 1);
+            if !self.is_playing() {
+                self.output = 0;
+                return 0;
+            }
+        }
+        self.remaining -= 1;
+
+        self.phase = self.phase.wrapping_add(self.increment);
+        let (sin, _) = angle2sincos_interp((self.phase >> 16) as i16);
+        self.output = if self.increment == 0 {
+            0 // Rest
+        } else {
+            ((sin as i32 * self.amplitude as i32) >> 15) as i16
+        };
+        self.output
+    }
+
+    /// Returns the modulation of the last tick (Q15 duty).
+    pub fn output(&self) -> i16 {
+        self.output
+    }
+
+    /// Loads the note at `index`, past the end the melody is over.
+    fn start_note(&mut self, index: usize) {
+        self.index = index;
+        let Some(note) = self.melody[..self.length].get(index) else {
+            return;
+        };
+        let freq = self.frequency.max(1) as u64;
+        self.remaining = (note.duration as u64 * freq / 1000).max(1) as u32;
+        // Tones above half of the update frequency would alias, they are played as rests
+        let pitch = if (note.pitch as u64) < freq / 2 { note.pitch as u64 } else { 0 };
+        self.increment = ((pitch << 32) / freq) as u32;
+        self.phase = 0;
+    }
+}
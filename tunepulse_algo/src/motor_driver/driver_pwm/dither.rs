@@ -0,0 +1,91 @@
+// Implements the PWM dither, moving the switching edges by a pseudo-random common-mode offset every
+// period to spread the EMI spectrum and the tonal noise of the fixed PWM frequency.
+
+// Key Features:
+// - Runtime selectable depth in duty counts (Q15 duty), 0 disables the dither.
+// - Same offset on all driven channels: the coil voltages and the torque are not changed.
+// - Offset limited to the headroom of the duties, nothing is clipped at 0% or 100%.
+// - Disabled and antiphase (fast decay) channels are left untouched.
+
+// Detailed Operation:
+// A fixed duty repeats the same edge positions every period, so the conducted and radiated
+// emissions concentrate at the PWM frequency and its harmonics, and the windings sing at it. The
+// voltage of a coil or between two phases is the difference of their duties, so moving all duties by
+// the same amount shifts the edges without changing any of them. Every tick a xorshift generator
+// draws an offset in -depth..depth, which is then narrowed to what the lowest and highest duty leave
+// free. The spectrum spreads into a band around each harmonic, the averaged current is unchanged.
+// A channel running with inverted polarity (locked antiphase) would turn the shift into a voltage,
+// so its whole coil is excluded.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Largest dither depth: 5% duty (Q15)
+pub const DITHER_MAX_DEPTH: i16 = 1638;
+
+/// Pseudo-random common-mode offset of the PWM duties.
+pub struct Dither {
+    depth: i16, // Offset amplitude (Q15 duty), 0 - off
+    state: u32, // Xorshift generator state, never 0
+}
+
+impl Dither {
+    /// Creates a disabled dither.
+    pub const fn new() -> Self {
+        Self {
+            depth: 0,
+            state: 0x2545_F491,
+        }
+    }
+
+    /// Sets the dither depth (Q15 duty, limited to DITHER_MAX_DEPTH), 0 disables it.
+    pub fn set_depth(&mut self, depth: i16) {
+        self.depth = depth.clamp(0, DITHER_MAX_DEPTH);
+    }
+
+    /// Returns the dither depth (Q15 duty).
+    pub fn depth(&self) -> i16 {
+        self.depth
+    }
+
+    /// Shifts the driven channels by one common offset.
+    ///
+    /// # Arguments
+    /// * `channels` - Channel duties (i16::MIN - disabled)
+    /// * `skip` - Channels that must keep their duty
+    pub fn tick(&mut self, channels: [i16; 4], skip: [bool; 4]) -> [i16; 4] {
+        if self.depth == 0 {
+            return channels;
+        }
+        // Headroom of the driven channels
+        let mut low = i16::MAX as i32;
+        let mut high = i16::MAX as i32;
+        for (&duty, &skip) in channels.iter().zip(skip.iter()) {
+            if duty != i16::MIN && !skip {
+                low = low.min(duty as i32);
+                high = high.min(i16::MAX as i32 - duty as i32);
+            }
+        }
+
+        // Xorshift32
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        let span = 2 * self.depth as i32 + 1;
+        let offset = ((self.state >> 8) as i32 % span - self.depth as i32).clamp(-low, high);
+
+        let mut output = channels;
+        for (duty, &skip) in output.iter_mut().zip(skip.iter()) {
+            if *duty != i16::MIN && !skip {
+                *duty = (*duty as i32 + offset) as i16;
+            }
+        }
+        output
+    }
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,111 @@
+// Implements the duty limits, keeping the modulated channel duties inside what the gate driver can
+// switch: a minimum low-side on-time every period and no pulses shorter than the driver minimum.
+
+// Key Features:
+// - Maximum duty: the low side conducts every period, bootstrap capacitors stay charged and low-side
+//   shunts can be sampled.
+// - Minimum pulse width: shorter pulses are rounded to none or to the minimum width.
+// - Applied after modulation, as a common-mode shift first so the coil voltages are kept.
+// - Inverted (antiphase) channels are limited on their mirrored duty.
+
+// Detailed Operation:
+// A bootstrap gate driver recharges the high-side supply only while the low side is on; a channel
+// held at 100% discharges it and the high side eventually drops out. The modulation (SVPWM, centered
+// coil pattern) may request any duty, so the limits are applied to the final channel duties. If the
+// highest driven duty exceeds `max_duty`, all driven channels are moved down by the excess as far as
+// the lowest one allows, which leaves the differences (the applied voltages) untouched; only what
+// doesn't fit is clipped per channel. Pulses shorter than `min_pulse` either vanish in the driver or
+// come out distorted, so a duty between 0 and `min_pulse` becomes 0 below half of it and `min_pulse`
+// above; the maximum duty leaves at least `min_pulse` of off-time at the top. A channel with inverted
+// polarity conducts on its low side while the counter is inside the window, so it is limited on
+// MAX - duty.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Duty constraints of the gate driver.
+pub struct DutyLimits {
+    max_duty: i16,  // Highest duty (Q15)
+    min_pulse: i16, // Shortest pulse (Q15), 0 - any
+}
+
+impl DutyLimits {
+    /// Creates unlimited duties.
+    pub const fn new() -> Self {
+        Self {
+            max_duty: i16::MAX,
+            min_pulse: 0,
+        }
+    }
+
+    /// Sets the highest duty (Q15).
+    pub fn set_max_duty(&mut self, max_duty: i16) {
+        self.max_duty = max_duty.max(0);
+    }
+
+    /// Sets the shortest pulse (Q15), 0 allows any width.
+    pub fn set_min_pulse(&mut self, min_pulse: i16) {
+        self.min_pulse = min_pulse.clamp(0, i16::MAX / 4);
+    }
+
+    /// Returns the (maximum duty, minimum pulse) pair (Q15).
+    pub fn limits(&self) -> (i16, i16) {
+        (self.max_duty, self.min_pulse)
+    }
+
+    /// Returns true if no limit is set.
+    #[inline(always)]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_duty == i16::MAX && self.min_pulse == 0
+    }
+
+    /// Limits the channel duties.
+    ///
+    /// # Arguments
+    /// * `channels` - Channel duties (i16::MIN - disabled)
+    /// * `inverted` - Channels running with inverted polarity
+    pub fn apply(&self, channels: [i16; 4], inverted: [bool; 4]) -> [i16; 4] {
+        if self.is_unlimited() {
+            return channels;
+        }
+        let max_duty = self.max_duty.min(i16::MAX - self.min_pulse);
+
+        // ####### Common-mode shift #######
+        let mut high = 0;
+        let mut low = i16::MAX;
+        for (&duty, &inv) in channels.iter().zip(inverted.iter()) {
+            if duty != i16::MIN && !inv {
+                high = high.max(duty);
+                low = low.min(duty);
+            }
+        }
+        let shift = if high > max_duty { (high - max_duty).min(low) } else { 0 };
+
+        // ####### Per-channel limits #######
+        let mut output = channels;
+        for (duty, &inv) in output.iter_mut().zip(inverted.iter()) {
+            *duty = match (*duty, inv) {
+                (i16::MIN, _) => *duty,
+                (_, true) => i16::MAX - self.limit(i16::MAX - *duty, max_duty),
+                _ => self.limit(*duty - shift, max_duty),
+            };
+        }
+        output
+    }
+
+    /// Clips one duty and rounds pulses shorter than the minimum.
+    #[inline(always)]
+    fn limit(&self, duty: i16, max_duty: i16) -> i16 {
+        let duty = duty.clamp(0, max_duty);
+        if duty > 0 && duty < self.min_pulse {
+            return if duty < self.min_pulse / 2 { 0 } else { self.min_pulse };
+        }
+        duty
+    }
+}
+
+impl Default for DutyLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
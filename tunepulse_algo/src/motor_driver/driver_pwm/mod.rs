@@ -6,6 +6,11 @@
 // - Defines MotorType enum for various motor types
 // - Implements MotorPWM struct to manage motor and phase selectors
 // - Provides methods to update motor control and change motor or phase modes
+// - Superimposes beeper tones on the phase voltages (status and fault indication)
+// - Selectable current decay of stepper coils, reporting the channels to run with inverted polarity
+// - Optional spread-spectrum dither of the switching edges (EMI and tonal noise)
+// - Duties scaled to the timer period, changeable at runtime
+// - Maximum duty (bootstrap refresh) and minimum pulse width applied after modulation
 
 // Detailed Operation:
 // The motor_pwm module manages PWM signals for different motor types using MotorSelector and PhaseSelector.
@@ -13,7 +18,8 @@
 // The MotorPWM struct initializes selectors based on motor type and phase pattern,
 // and provides methods to update PWM signals based on input voltages or angles.
 // It uses mathematical transformations for voltage calculations and allows dynamic changing
-// of motor and phase modes.
+// of motor and phase modes. A playing beeper adds its modulation along the applied voltage vector
+// (the alpha axis when no vector is applied), so the tone doesn't disturb a holding position.
 
 // Licensed under the Apache License, Version 2.0
 // Copyright 2024 Anton Khrustalev, creapunk.com
@@ -21,17 +27,35 @@
 mod sel_motor; // Imports the motor_selector module
 mod sel_phase; // Imports the phase_selector module
 mod sel_current;
+pub mod beeper; // Tones and melodies through the windings
+pub mod dither; // Spread-spectrum offset of the PWM edges
+mod duty_limits; // Gate driver duty constraints
 
 use sel_motor::MotorSelector; // Imports the MotorSelector struct from motor_selector module
 use sel_phase::PhaseSelector; // Imports the PhaseSelector struct from phase_selector module
+use beeper::Beeper;
+use dither::Dither;
+use duty_limits::DutyLimits;
 
 use crate::math_integer::motor;
 
+use crate::math_integer::fixed::Q15;
 use crate::math_integer::{normalization::value_to_norm, trigonometry as math}; // Imports trigonometry module as math
 
 
 use super::{ControlMode, DriverStatus, Motor, MotorDriver, MotorType, PhasePattern};
 
+/// Current decay of stepper coils during the PWM off-time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecayMode {
+    /// Coil shorted (centered pattern): lowest ripple, slow current fall
+    Slow,
+    /// Coil reversed (locked antiphase): fast current fall, higher ripple
+    Fast,
+    /// Fast decay while the coil voltage falls, slow otherwise
+    Mixed,
+}
+
 pub struct DriverPWM {
     // COMMON
     /// Duty of brake mode
@@ -57,6 +81,17 @@ pub struct DriverPWM {
 
     ch_1234: [i16; 4],
 
+    /// Tone generator added to the phase voltages
+    beeper: Beeper,
+
+    /// Common-mode offset of the duties
+    dither: Dither,
+
+    /// Timer counts of 100% duty, 0 - duties stay Q15
+    duty_period: u16,
+    /// Maximum duty and minimum pulse of the gate driver
+    limits: DutyLimits,
+
     motor: Motor
 }
 
@@ -65,17 +100,108 @@ impl DriverPWM {
     fn normal_run(&mut self, ab: (i16, i16), supply: i16) -> (i16, i16) {
         match self.control_mode {
             ControlMode::CurrentAB => {
-                let sincos_ab = math::angle2sincos(ab.0); // Converts angle to sine and cosine voltages
+                let sincos_ab = math::angle2sincos_interp(ab.0); // Converts angle to sine and cosine voltages
                 let targ_voltage = (ab.1 as i32 * self.motor.resistance) / 1000; // ma * mOhm -> mV
                 let norm_targ_voltage = value_to_norm(targ_voltage, 69000);
-                let mut scale = ((norm_targ_voltage as i32) << 15) / supply as i32;
-                if scale > i16::MAX as i32 { scale = i16::MAX as i32};
-                let scale = scale as i16;
-                math::scale_sincos(sincos_ab, scale) // Scales sine and cosine voltages based on input
+                // Duty = target / supply, no output until the supply is measured
+                let scale = if supply > 0 {
+                    Q15::from_ratio(norm_targ_voltage as i32, supply as i32)
+                } else {
+                    Q15::ZERO
+                };
+                math::scale_sincos(sincos_ab, scale.0) // Scales sine and cosine voltages based on input
             }
             ControlMode::VoltageAB => ab,
         }
     }
+
+    /// Adds the beeper modulation along the direction of the applied voltage vector.
+    #[inline(always)]
+    fn add_beep(&mut self, voltage_ab: (i16, i16), angle: i16) -> (i16, i16) {
+        let beep = self.beeper.tick();
+        if beep == 0 {
+            return voltage_ab;
+        }
+        let (sin, cos) = match self.control_mode {
+            ControlMode::CurrentAB => math::angle2sincos_interp(angle),
+            ControlMode::VoltageAB => (i16::MAX, 0),
+        };
+        let alpha = ((beep as i32 * sin as i32) >> 15) as i16;
+        let beta = ((beep as i32 * cos as i32) >> 15) as i16;
+        (voltage_ab.0.saturating_add(alpha), voltage_ab.1.saturating_add(beta))
+    }
+
+    /// Selects the current decay of stepper coils.
+    #[inline(always)]
+    pub fn set_decay(&mut self, decay: DecayMode) {
+        self.motor_type.set_decay(decay);
+    }
+
+    /// Returns the current decay of stepper coils.
+    #[inline(always)]
+    pub fn decay(&self) -> DecayMode {
+        self.motor_type.decay()
+    }
+
+    /// Returns the output channels of the last period that need inverted timer polarity (bit n -
+    /// channel n + 1), the HAL applies it with the duties. Fast decay switches coils in antiphase.
+    pub fn inverted_channels(&self) -> u8 {
+        let inverted = self.motor_type.inverted().map(|flag| flag as i16);
+        let mapped = self.phase_sel.tick(inverted);
+        mapped.iter().enumerate().fold(0, |mask, (idx, &flag)| mask | ((flag as u8) << idx))
+    }
+
+    /// Sets the spread-spectrum dither depth of the duties (Q15 duty), 0 disables it.
+    #[inline(always)]
+    pub fn set_dither(&mut self, depth: i16) {
+        self.dither.set_depth(depth);
+    }
+
+    /// Returns the spread-spectrum dither depth (Q15 duty).
+    #[inline(always)]
+    pub fn dither(&self) -> i16 {
+        self.dither.depth()
+    }
+
+    /// Sets the timer period the duties are scaled to and the duty constraints of the gate driver.
+    ///
+    /// # Arguments
+    /// * `period` - Timer counts of 100% duty (at most i16::MAX), 0 keeps Q15 duties
+    /// * `max_duty` - Highest duty (Q15): minimum low-side on-time for bootstrap and shunt sampling
+    /// * `min_pulse` - Shortest pulse (Q15), 0 allows any width
+    pub fn set_duty_scale(&mut self, period: u16, max_duty: i16, min_pulse: i16) {
+        self.duty_period = period.min(i16::MAX as u16);
+        self.limits.set_max_duty(max_duty);
+        self.limits.set_min_pulse(min_pulse);
+    }
+
+    /// Returns the (maximum duty, minimum pulse) constraints (Q15).
+    #[inline(always)]
+    pub fn duty_limits(&self) -> (i16, i16) {
+        self.limits.limits()
+    }
+
+    /// Returns the timer counts of 100% duty, 0 - Q15 duties.
+    #[inline(always)]
+    pub fn duty_scale(&self) -> u16 {
+        self.duty_period
+    }
+
+    /// Converts Q15 channel duties into timer compare values, disabled channels stay i16::MIN.
+    #[inline(always)]
+    pub fn to_timer(&self, duties: [i16; 4]) -> [i16; 4] {
+        if self.duty_period == 0 {
+            return duties;
+        }
+        let period = self.duty_period as i32;
+        duties.map(|duty| if duty == i16::MIN { duty } else { ((duty as i32 * period) >> 15) as i16 })
+    }
+
+    /// Get access to the beeper (melodies, fault codes, amplitude).
+    #[inline(always)]
+    pub fn beeper(&mut self) -> &mut Beeper {
+        &mut self.beeper
+    }
 }
 
 impl MotorDriver for DriverPWM {
@@ -91,6 +217,10 @@ impl MotorDriver for DriverPWM {
             motor_type: MotorSelector::new(motor.pole_type), // Initializes motor selector with motor type
             phase_sel: PhaseSelector::new(motor.connection), // Initializes phase selector with phase pattern
             ch_1234: [0; 4],
+            beeper: Beeper::new(20000),
+            dither: Dither::new(),
+            duty_period: 0,
+            limits: DutyLimits::new(),
             motor,
         }
     }
@@ -102,7 +232,12 @@ impl MotorDriver for DriverPWM {
             DriverStatus::Calibrating => (0, 0),
         };
         let voltage_ab = self.normal_run(voltage_ab, supply);
+        let voltage_ab = self.add_beep(voltage_ab, ab_inpt.0);
         let motor_voltages = self.motor_type.tick(voltage_ab);
+        let inverted = self.motor_type.inverted();
+        let antiphase = [inverted[1], inverted[1], inverted[3], inverted[3]]; // Whole coil keeps its duty
+        let motor_voltages = self.dither.tick(motor_voltages, antiphase);
+        let motor_voltages = self.limits.apply(motor_voltages, inverted);
         self.ch_1234 = self.phase_sel.tick(motor_voltages);
         self.ch_1234
     }
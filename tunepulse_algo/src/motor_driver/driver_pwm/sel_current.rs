@@ -88,7 +88,7 @@ impl<const PROBES: u32> CurrentSenseAB<PROBES> {
     /// Методы для биполярного режима
     #[inline(always)]
     fn tick_bipolar_single(&mut self) {
-        self.ab_output = if let MotorType::DC = self.motor_type {
+        self.ab_output = if let MotorType::DC | MotorType::LINEAR = self.motor_type {
             (coil::current::single_bipolar(self.abcd_input[0]), 0)
         } else {
             (i16::MIN, i16::MIN)
@@ -98,11 +98,11 @@ impl<const PROBES: u32> CurrentSenseAB<PROBES> {
     fn tick_bipolar_dual(&mut self) {
         self.ab_output = match self.motor_type {
             MotorType::UNDEFINED => (i16::MIN, i16::MIN),
-            MotorType::DC => (
+            MotorType::DC | MotorType::LINEAR => (
                 coil::current::dual_bipolar(self.abcd_input[0], self.abcd_input[1]),
                 0,
             ),
-            MotorType::STEP => (
+            MotorType::STEP | MotorType::DUALDC => (
                 coil::current::single_bipolar(self.abcd_input[0]),
                 coil::current::single_bipolar(self.abcd_input[1]),
             ),
@@ -113,11 +113,11 @@ impl<const PROBES: u32> CurrentSenseAB<PROBES> {
     fn tick_bipolar_triple(&mut self) {
         self.ab_output = match self.motor_type {
             MotorType::UNDEFINED => (0, 0),
-            MotorType::DC => (
+            MotorType::DC | MotorType::LINEAR => (
                 coil::current::dual_bipolar(self.abcd_input[0], self.abcd_input[1]),
                 0,
             ),
-            MotorType::STEP => (
+            MotorType::STEP | MotorType::DUALDC => (
                 coil::current::dual_bipolar(self.abcd_input[0], self.abcd_input[1]),
                 coil::current::single_bipolar(self.abcd_input[2]),
             ),
@@ -130,11 +130,11 @@ impl<const PROBES: u32> CurrentSenseAB<PROBES> {
     fn tick_bipolar_quad(&mut self) {
         self.ab_output = match self.motor_type {
             MotorType::UNDEFINED => (0, 0),
-            MotorType::DC => (
+            MotorType::DC | MotorType::LINEAR => (
                 coil::current::dual_bipolar(self.abcd_input[0], self.abcd_input[1]),
                 0,
             ),
-            MotorType::STEP => (
+            MotorType::STEP | MotorType::DUALDC => (
                 coil::current::dual_bipolar(self.abcd_input[0], self.abcd_input[1]),
                 coil::current::dual_bipolar(self.abcd_input[2], self.abcd_input[3]),
             ),
@@ -148,11 +148,11 @@ impl<const PROBES: u32> CurrentSenseAB<PROBES> {
     fn tick_unipolar_quad(&mut self) {
         self.ab_output = match self.motor_type {
             MotorType::UNDEFINED => (0, 0),
-            MotorType::DC => (
+            MotorType::DC | MotorType::LINEAR => (
                 coil::current::dual_unipolar(self.abcd_input[0], self.abcd_input[1]),
                 0,
             ),
-            MotorType::STEP => (
+            MotorType::STEP | MotorType::DUALDC => (
                 coil::current::dual_unipolar(self.abcd_input[0], self.abcd_input[1]),
                 coil::current::dual_unipolar(self.abcd_input[2], self.abcd_input[3]),
             ),
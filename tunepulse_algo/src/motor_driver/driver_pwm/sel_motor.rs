@@ -5,18 +5,24 @@
 // - Calculates coil voltages using mathematical transformations
 // - Manages phase voltages with SVPWM algorithm
 // - Provides methods to update motor control and change motor modes
+// - Selectable current decay of stepper coils (slow / fast / mixed)
 
 // Detailed Operation:
 // The MotorSelector struct manages motor control by selecting the appropriate motor type mode
 // and calculating the required voltages. It uses mathematical functions to compute coil voltages
 // and implements the SVPWM algorithm for three-phase motors. The tick method updates the control
 // signals based on the current mode and input voltages, ensuring proper motor operation.
+// Stepper coils use the centered pattern for slow decay (off-time shorted) and locked antiphase for
+// fast decay (off-time reversed, the second channel of the coil runs with inverted polarity). Mixed
+// decay switches a coil to fast decay only while its voltage magnitude falls, so a falling current
+// follows the sine quickly and a rising one keeps the low ripple of slow decay.
 
 // Licensed under the Apache License, Version 2.0
 // Copyright 2024 Anton Khrustalev, creapunk.com
 
 use super::motor::{bldc, coil}; // Imports the inverse Clarke transform function from the parent module
 use super::MotorType; // Imports the MotorType enum from the parent module
+use super::DecayMode;
 
 /// Disabled voltage constant
 const DISBL: i16 = i16::MIN;
@@ -29,6 +35,10 @@ pub struct MotorSelector {
     mode: MotorType,
     /// Array to store voltages for four channels
     ch_abcd: [i16; 4],
+    /// Current decay of stepper coils
+    decay: DecayMode,
+    /// Coils switching in antiphase (bit 0 - coil A, bit 1 - coil B)
+    fast: u8,
 }
 
 impl MotorSelector {
@@ -38,6 +48,8 @@ impl MotorSelector {
             mode,            // Sets the motor type mode
             duty_ab: (0, 0), // Initializes alpha and beta voltages to zero
             ch_abcd: [0; 4], // Initializes channel voltages to zero
+            decay: DecayMode::Slow,
+            fast: 0,
         }
     }
 
@@ -66,6 +78,24 @@ impl MotorSelector {
         // Calculates and sets voltages for last two channels
     }
 
+    /// Handles stepper control with the selected current decay of both coils
+    #[inline(always)]
+    fn tick_stepper(&mut self, prev_ab: (i16, i16)) {
+        let coils = [(self.duty_ab.0, prev_ab.0), (self.duty_ab.1, prev_ab.1)];
+        for (idx, (voltg, prev)) in coils.into_iter().enumerate() {
+            let fast = match self.decay {
+                DecayMode::Slow => false,
+                DecayMode::Fast => true,
+                DecayMode::Mixed => voltg.saturating_abs() < prev.saturating_abs(),
+            };
+            let duty = if fast { coil::duty::antiphase(voltg) } else { coil::duty::center(voltg) };
+            (self.ch_abcd[idx * 2], self.ch_abcd[idx * 2 + 1]) = duty;
+            if fast && voltg != i16::MIN {
+                self.fast |= 1 << idx;
+            }
+        }
+    }
+
     /// Controls a 3-phase 3-wire motor using the SVPWM algorithm and sets unused phase to brake voltage
     #[inline(always)]
     fn tick3phase(&mut self) {
@@ -79,16 +109,38 @@ impl MotorSelector {
 
     /// Updates motor control based on the current mode and input voltages
     pub fn tick(&mut self, voltg_ab: (i16, i16)) -> [i16; 4] {
+        let prev_ab = self.duty_ab;
         self.duty_ab = voltg_ab; // Updates alpha and beta voltages
+        self.fast = 0;
         match self.mode {
             MotorType::UNDEFINED => self.tick0phase(), // Handles undefined motor type
             MotorType::DC => self.tick1phase(),        // Handles DC motor type
-            MotorType::STEP => self.tick2phase(),      // Handles Stepper motor type
+            MotorType::LINEAR => self.tick1phase(),    // Handles voice-coil / linear actuator type
+            MotorType::STEP => self.tick_stepper(prev_ab), // Handles Stepper motor type
+            MotorType::DUALDC => self.tick2phase(),    // Handles two independent DC motors
             MotorType::BLDC => self.tick3phase(),      // Handles BLDC motor type
         }
         self.ch_abcd // Returns the updated channel voltages
     }
 
+    /// Selects the current decay of stepper coils
+    #[inline(always)]
+    pub fn set_decay(&mut self, decay: DecayMode) {
+        self.decay = decay
+    }
+
+    /// Returns the current decay of stepper coils
+    #[inline(always)]
+    pub fn decay(&self) -> DecayMode {
+        self.decay
+    }
+
+    /// Returns the channels (before phase mapping) that need inverted polarity this period
+    #[inline(always)]
+    pub fn inverted(&self) -> [bool; 4] {
+        [false, self.fast & 1 != 0, false, self.fast & 2 != 0]
+    }
+
     /// Changes the motor type mode to the specified mode
     #[inline(always)]
     pub fn change_mode(&mut self, mode: MotorType) {
@@ -0,0 +1,97 @@
+// Implements the dual H-bridge module, splitting the 4-channel PWM output into two independent
+// brushed motor channels (e.g. left and right wheel of a differential-drive robot).
+
+// Key Features:
+// - Two channels with separate current setpoints and current limits.
+// - Per-channel winding resistance for the current to voltage conversion.
+// - Output in normalized duty (% of supply voltage) for the voltage mode of the PWM driver.
+
+// Detailed Operation:
+// Each channel converts its clamped current setpoint into the required coil voltage using Ohm's law
+// and normalizes it to the measured supply voltage, the same way the PWM driver does in current
+// mode. Channel A drives PWM outputs 1-2 and channel B drives outputs 3-4, each as a center-aligned
+// H-bridge through the DUALDC motor type.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::fixed::Q15;
+use crate::math_integer::normalization::value_to_norm;
+use crate::math_integer::ohms_law;
+
+/// Channel index of the motor on PWM outputs 1-2
+pub const BRIDGE_A: usize = 0;
+/// Channel index of the motor on PWM outputs 3-4
+pub const BRIDGE_B: usize = 1;
+
+/// Two independent brushed motor channels.
+pub struct DualBridge {
+    setpoint: [i32; 2],   // Current setpoints (mA)
+    limit: [i32; 2],      // Current limits (mA)
+    resistance: [i32; 2], // Winding resistances (mOhm)
+    duty: (i16, i16),     // Last normalized duty of both channels
+}
+
+impl DualBridge {
+    /// Creates both channels with zero setpoint and the same winding resistance (mOhm).
+    pub fn new(resistance: i32) -> Self {
+        let resistance = resistance.max(1);
+        Self {
+            setpoint: [0; 2],
+            limit: [0; 2],
+            resistance: [resistance; 2],
+            duty: (0, 0),
+        }
+    }
+
+    /// Computes normalized duties for both channels.
+    ///
+    /// # Arguments
+    /// * `supply` - Normalized supply voltage
+    /// * `max_voltage_mv` - Full scale of the normalized voltage (mV)
+    pub fn tick(&mut self, supply: i16, max_voltage_mv: i32) -> (i16, i16) {
+        let duty_a = self.channel_duty(BRIDGE_A, supply, max_voltage_mv);
+        let duty_b = self.channel_duty(BRIDGE_B, supply, max_voltage_mv);
+        self.duty = (duty_a, duty_b);
+        self.duty
+    }
+
+    /// Sets current setpoint of the channel (mA).
+    pub fn set_current(&mut self, channel: usize, current: i32) {
+        if channel < 2 {
+            self.setpoint[channel] = current;
+        }
+    }
+
+    /// Sets current limit of the channel (mA).
+    pub fn set_current_limit(&mut self, channel: usize, limit: i32) {
+        if channel < 2 {
+            self.limit[channel] = limit.max(0);
+        }
+    }
+
+    /// Sets winding resistance of the channel (mOhm).
+    pub fn set_resistance(&mut self, channel: usize, resistance: i32) {
+        if channel < 2 {
+            self.resistance[channel] = resistance.max(1);
+        }
+    }
+
+    /// Returns last normalized duties of both channels.
+    pub fn duty(&self) -> (i16, i16) {
+        self.duty
+    }
+
+    /// Converts the clamped setpoint of the channel to normalized duty.
+    #[inline(always)]
+    fn channel_duty(&self, channel: usize, supply: i16, max_voltage_mv: i32) -> i16 {
+        if supply <= 0 {
+            return 0; // No supply measured yet
+        }
+        let limit = self.limit[channel];
+        let current = self.setpoint[channel].clamp(-limit, limit);
+        let voltage = ohms_law::voltage(current, self.resistance[channel]);
+        let voltage = value_to_norm(voltage.clamp(-max_voltage_mv, max_voltage_mv), max_voltage_mv);
+        Q15::from_ratio(voltage as i32, supply as i32).0.max(-i16::MAX)
+    }
+}
@@ -0,0 +1,223 @@
+// Implements the dual encoder module, closing the position loop on a load-side encoder (output shaft
+// of a gearbox or belt) while the motor-shaft encoder keeps commutating the motor.
+
+// Key Features:
+// - Load encoder with its own position accumulator and median glitch filter.
+// - Rational motor:load ratio (negative for a reversing gearbox), load position in motor counts.
+// - Cross-check of both encoders: a mismatch beyond the backlash window for a while is a fault.
+// - Mismatch and its peak in the report for tuning the window and spotting a slipping belt.
+
+// Detailed Operation:
+// Both encoders give 65536 counts per turn of their shaft. When aligned, the position of both is
+// stored as the reference; afterwards the load travel since the reference is scaled by
+// motor_turns / load_turns into motor counts and compared with the motor travel. The difference is
+// the mismatch: backlash, belt stretch and torsion keep it inside a small window, a slipping
+// coupling, a broken belt or a miscounting encoder make it grow without bound. Converting the
+// absolute travel every tick keeps the scaling exact for any ratio. The position fed to the loop is
+// the motor position minus the mismatch, which is the load position in the motor's frame and zero
+// point: setpoints keep their units and zeroing the motor position moves both. The mismatch must stay
+// above `backlash` for `trip_ticks` consecutive ticks before the fault is raised, so short spikes
+// under a torque reversal don't trip it.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::filters::median::FilterMedian;
+use crate::math_integer::motion::position_integrator::Position;
+
+/// Configuration of the load-side encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DualEncoderConfig {
+    /// Motor revolutions per `load_turns`, negative if the load turns the other way
+    pub motor_turns: i32,
+    /// Load revolutions per `motor_turns`
+    pub load_turns: i32,
+    /// Largest tolerated mismatch (motor counts), 0 disables the cross-check
+    pub backlash: i32,
+    /// Time the mismatch may stay outside the window (ms)
+    pub trip_ms: u16,
+    /// Median window of the load encoder: 1 (off), 3 or 5
+    pub median_taps: u8,
+}
+
+impl DualEncoderConfig {
+    /// Creates a direct drive configuration (1:1), a quarter turn window tripping after 20 ms.
+    pub const fn new() -> Self {
+        Self {
+            motor_turns: 1,
+            load_turns: 1,
+            backlash: 1 << 14,
+            trip_ms: 20,
+            median_taps: 1,
+        }
+    }
+}
+
+impl Default for DualEncoderConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cross-check state of both encoders.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DualEncoderReport {
+    /// Motor travel minus scaled load travel since alignment (motor counts)
+    pub mismatch: i64,
+    /// Largest |mismatch| since alignment (motor counts)
+    pub peak: i64,
+    /// Load position since start (load counts)
+    pub load_position: i64,
+    /// Cross-check tripped
+    pub faulted: bool,
+}
+
+/// Load-side encoder feedback with a cross-check against the motor encoder.
+pub struct DualEncoder {
+    frequency: u16, // Update frequency (ticks per second)
+    enabled: bool,  // Load encoder closes the position loop
+    config: DualEncoderConfig,
+
+    load: Position,       // Load encoder position
+    glitch: FilterMedian, // Median filter of the raw load angle
+    inverted: bool,       // Load angle mirrored along with the motor encoder
+    aligned: bool,        // References valid
+    motor_ref: i64,       // Motor position at alignment (counts since start)
+    load_ref: i64,        // Load position at alignment (counts since start)
+    over: u32,            // Consecutive ticks outside the window
+    report: DualEncoderReport,
+}
+
+impl DualEncoder {
+    /// Creates a disabled load encoder.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            enabled: false,
+            config: DualEncoderConfig::new(),
+            load: Position::new(),
+            glitch: FilterMedian::new(0, 1),
+            inverted: false,
+            aligned: false,
+            motor_ref: 0,
+            load_ref: 0,
+            over: 0,
+            report: DualEncoderReport::default(),
+        }
+    }
+
+    /// Enables the load encoder with the given configuration, None disables it.
+    ///
+    /// Returns false (and leaves the encoder disabled) if a turn count is zero.
+    pub fn configure(&mut self, config: Option<DualEncoderConfig>) -> bool {
+        let Some(config) = config else {
+            self.enabled = false;
+            return true;
+        };
+        if config.motor_turns == 0 || config.load_turns == 0 {
+            self.enabled = false;
+            return false;
+        }
+        self.glitch.set_taps(config.median_taps);
+        self.config = config;
+        self.enabled = true;
+        self.align();
+        true
+    }
+
+    /// Returns true while the load encoder closes the position loop.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> DualEncoderConfig {
+        self.config
+    }
+
+    /// Takes the present positions of both encoders as matching, the mismatch and fault are cleared.
+    pub fn align(&mut self) {
+        self.aligned = false;
+        self.over = 0;
+        self.report.mismatch = 0;
+        self.report.peak = 0;
+        self.report.faulted = false;
+    }
+
+    /// Mirrors the load counting direction together with a motor encoder inverted by the caller,
+    /// the configured ratio keeps referring to the raw encoder directions.
+    pub fn invert(&mut self) {
+        self.inverted = !self.inverted;
+        self.load.invert();
+        self.load_ref = self.load_ref.wrapping_neg();
+        self.motor_ref = self.motor_ref.wrapping_neg();
+        self.report.mismatch = self.report.mismatch.wrapping_neg();
+    }
+
+    /// Updates the load position and cross-checks it against the motor.
+    ///
+    /// # Arguments
+    /// * `load_angle` - Raw load encoder angle
+    /// * `motor_position` - Motor encoder position (counts since start)
+    ///
+    /// Returns true on the tick the cross-check trips.
+    pub fn tick(&mut self, load_angle: u16, motor_position: i64) -> bool {
+        let load_angle = self.glitch.tick(load_angle);
+        let load_angle = if self.inverted { load_angle.wrapping_neg() } else { load_angle };
+        self.load.tick(load_angle);
+        self.report.load_position = self.load.position_i64();
+        if !self.enabled {
+            return false;
+        }
+        if !self.aligned {
+            self.aligned = true;
+            self.motor_ref = motor_position;
+            self.load_ref = self.load.position_i64();
+            return false;
+        }
+
+        // Load travel in motor counts, exact rational scaling of the absolute travel
+        let load_travel = self.load.position_i64().wrapping_sub(self.load_ref) as i128;
+        let scaled = load_travel * self.config.motor_turns as i128 / self.config.load_turns as i128;
+        let motor_travel = motor_position.wrapping_sub(self.motor_ref) as i128;
+        let mismatch = (motor_travel - scaled).clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+        self.report.mismatch = mismatch;
+        self.report.peak = self.report.peak.max(mismatch.saturating_abs());
+
+        if self.config.backlash <= 0 || self.report.faulted {
+            return false;
+        }
+        if mismatch.saturating_abs() <= self.config.backlash as i64 {
+            self.over = 0;
+            return false;
+        }
+        self.over += 1;
+        let trip_ticks = (self.config.trip_ms as u32 * self.frequency as u32 / 1000).max(1);
+        if self.over < trip_ticks {
+            return false;
+        }
+        self.report.faulted = true;
+        defmt::error!(
+            "DUAL ENCODER: Mismatch {} counts exceeds the backlash window {}",
+            mismatch,
+            self.config.backlash
+        );
+        true
+    }
+
+    /// Returns the loop position: the motor position minus the mismatch (motor counts).
+    #[inline(always)]
+    pub fn loop_position(&self, motor_position: i32) -> i32 {
+        if self.enabled { motor_position.wrapping_sub(self.report.mismatch as i32) } else { motor_position }
+    }
+
+    /// Returns the cross-check state.
+    pub fn report(&self) -> DualEncoderReport {
+        self.report
+    }
+}
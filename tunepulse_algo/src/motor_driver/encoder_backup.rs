@@ -0,0 +1,79 @@
+// Implements the encoder backup monitor, turning battery and multi-turn status flags reported
+// by absolute encoders into warnings and a re-homing requirement.
+
+// Key Features:
+// - Battery-low warning reported once per occurrence.
+// - Multi-turn-lost detection latching a re-homing requirement.
+// - Requirement is cleared only by a successful homing or an explicit acknowledge.
+
+// Detailed Operation:
+// Absolute multi-turn encoders keep the turn counter alive from a backup battery. A low battery
+// is only a warning, but a lost turn counter means the absolute position can't be trusted any more.
+// The flags come from the sensor read together with the angle (`DataInputs::encoder_status`).
+// The monitor reacts on rising edges, so a flag that stays set doesn't flood the log, and keeps the
+// re-homing requirement latched even after the encoder drops the flag on its next power cycle.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Encoder status flag: backup battery is low (matches `DataInputs::encoder_status`)
+pub const ENC_BATTERY_LOW: u8 = 1 << 0;
+/// Encoder status flag: multi-turn counter was lost (matches `DataInputs::encoder_status`)
+pub const ENC_MULTITURN_LOST: u8 = 1 << 1;
+
+/// Monitor of the absolute encoder backup status.
+pub struct EncoderBackup {
+    status: u8,            // Flags reported in the previous tick
+    rehome_required: bool, // Latched: absolute position is not trusted
+}
+
+impl EncoderBackup {
+    /// Creates a monitor with no pending requirement.
+    pub fn new() -> Self {
+        Self {
+            status: 0,
+            rehome_required: false,
+        }
+    }
+
+    /// Processes encoder status flags of one tick.
+    ///
+    /// Returns true if the re-homing requirement was raised in this tick.
+    pub fn tick(&mut self, status: u8) -> bool {
+        let rising = status & !self.status;
+        self.status = status;
+
+        if rising & ENC_BATTERY_LOW != 0 {
+            defmt::warn!("ENCODER: Backup battery low, replace it to keep the multi-turn position");
+        }
+        if rising & ENC_MULTITURN_LOST != 0 && !self.rehome_required {
+            self.rehome_required = true;
+            defmt::error!("ENCODER: Multi-turn position lost, homing required");
+            return true;
+        }
+        false
+    }
+
+    /// Returns true while the absolute position must not be trusted.
+    pub fn rehome_required(&self) -> bool {
+        self.rehome_required
+    }
+
+    /// Returns true if the encoder reports a low backup battery.
+    pub fn battery_low(&self) -> bool {
+        self.status & ENC_BATTERY_LOW != 0
+    }
+
+    /// Clears the re-homing requirement (after homing or when the position is restored externally).
+    pub fn acknowledge(&mut self) {
+        self.rehome_required = false;
+    }
+}
+
+impl Default for EncoderBackup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
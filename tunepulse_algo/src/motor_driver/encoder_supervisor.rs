@@ -0,0 +1,287 @@
+// Implements the encoder supervisor, validating every raw encoder sample before it reaches the
+// position accumulator and raising an encoder fault instead of commutating on garbage.
+
+// Key Features:
+// - Impossible jump detection: a step larger than the maximum speed allows is rejected.
+// - Stuck value detection: an unchanged reading while the loop drives current into the motor.
+// - Optional parity and magnet field status bits reported by the sensor with every frame.
+// - Single bad samples are replaced by the last good one, a burst of them is a fault.
+
+// Detailed Operation:
+// A sample is invalid if the sensor flagged it (parity error or magnet field out of range, selected
+// by `status_mask`) or if it moved further from the last good sample than `max_speed` allows in the
+// elapsed ticks, with a margin of 2 for acceleration and sampling jitter. Invalid samples are held at
+// the last good angle, so one corrupted SPI frame costs one tick of stale position instead of a
+// commutation jump; `max_invalid` consecutive invalid samples raise the fault. A healthy sensor
+// always shows some LSB noise, while a sensor that stopped updating (lost clock, frozen register)
+// repeats the same value: an identical reading for `stuck_ms` while the current command stays above
+// `stuck_current` is reported as stuck. The fault is latched until cleared, the faulted sensor keeps
+// holding the last good angle.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Encoder status flag: frame failed the parity / CRC check (matches `DataInputs::encoder_status`)
+pub const ENC_PARITY_ERROR: u8 = 1 << 2;
+/// Encoder status flag: magnet field too weak or too strong (matches `DataInputs::encoder_status`)
+pub const ENC_MAGNITUDE_ERROR: u8 = 1 << 3;
+
+/// Reason of an encoder fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncoderFault {
+    /// Samples valid
+    None,
+    /// Consecutive samples jumped further than the maximum speed allows
+    Jump,
+    /// Reading didn't change while the motor was driven
+    Stuck,
+    /// Consecutive frames failed the parity check
+    Parity,
+    /// Consecutive frames reported a bad magnet field
+    Magnitude,
+}
+
+/// Configuration of the encoder supervisor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderSupervisorConfig {
+    /// Highest plausible speed (counts/s), 0 disables the jump check
+    pub max_speed: i32,
+    /// Time an unchanged reading is accepted while driven (ms), 0 disables the stuck check
+    pub stuck_ms: u16,
+    /// Current command above which the rotor is expected to move or at least jitter (mA)
+    pub stuck_current: i32,
+    /// Status flags invalidating a sample (ENC_PARITY_ERROR, ENC_MAGNITUDE_ERROR)
+    pub status_mask: u8,
+    /// Consecutive invalid samples raising the fault
+    pub max_invalid: u8,
+}
+
+impl EncoderSupervisorConfig {
+    /// Creates a configuration with all checks disabled.
+    pub const fn new() -> Self {
+        Self {
+            max_speed: 0,
+            stuck_ms: 0,
+            stuck_current: 500,
+            status_mask: 0,
+            max_invalid: 3,
+        }
+    }
+}
+
+impl Default for EncoderSupervisorConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters of the encoder supervisor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderSupervisorReport {
+    /// Latched fault, None while samples are valid
+    pub fault: EncoderFault,
+    /// Invalid samples replaced since start
+    pub rejected: u32,
+}
+
+/// Plausibility check of the raw encoder samples.
+pub struct EncoderSupervisor {
+    frequency: u16, // Update frequency (ticks per second)
+    config: EncoderSupervisorConfig,
+
+    started: bool,   // A good sample was seen
+    good: u16,       // Last good sample
+    invalid: u32,    // Consecutive invalid samples
+    unchanged: u32,  // Consecutive driven ticks with an identical reading
+    last_raw: u16,   // Raw sample of the previous tick
+    report: EncoderSupervisorReport,
+}
+
+impl EncoderSupervisor {
+    /// Creates a supervisor with all checks disabled.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: EncoderSupervisorConfig::new(),
+            started: false,
+            good: 0,
+            invalid: 0,
+            unchanged: 0,
+            last_raw: 0,
+            report: EncoderSupervisorReport {
+                fault: EncoderFault::None,
+                rejected: 0,
+            },
+        }
+    }
+
+    /// Applies a configuration.
+    pub fn configure(&mut self, config: EncoderSupervisorConfig) {
+        self.config = config;
+        self.invalid = 0;
+        self.unchanged = 0;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> EncoderSupervisorConfig {
+        self.config
+    }
+
+    /// Validates one sample.
+    ///
+    /// # Arguments
+    /// * `raw` - Raw encoder angle
+    /// * `status` - Sensor status flags of the same frame
+    /// * `current` - Current command of the loop (mA)
+    ///
+    /// Returns the angle to use (the raw one if valid, otherwise the last good one) and true on the
+    /// tick a fault is raised.
+    pub fn tick(&mut self, raw: u16, status: u8, current: i32) -> (u16, bool) {
+        let repeated = raw == self.last_raw;
+        self.last_raw = raw;
+        if self.report.fault != EncoderFault::None {
+            return (self.good, false);
+        }
+        if !self.started {
+            self.started = true;
+            self.good = raw;
+            return (raw, false);
+        }
+
+        // ####### Stuck reading #######
+        let stuck_ticks = self.config.stuck_ms as u32 * self.frequency as u32 / 1000;
+        if stuck_ticks > 0 && repeated && current.saturating_abs() > self.config.stuck_current {
+            self.unchanged += 1;
+            if self.unchanged >= stuck_ticks {
+                return self.raise(EncoderFault::Stuck);
+            }
+        } else {
+            self.unchanged = 0;
+        }
+
+        // ####### Sample validity #######
+        let flags = status & self.config.status_mask;
+        let limit = 2 * self.config.max_speed as i64 * (self.invalid as i64 + 1) / self.frequency.max(1) as i64;
+        let jump = (raw.wrapping_sub(self.good) as i16 as i64).abs();
+        let reason = if flags & ENC_PARITY_ERROR != 0 {
+            EncoderFault::Parity
+        } else if flags & ENC_MAGNITUDE_ERROR != 0 {
+            EncoderFault::Magnitude
+        } else if self.config.max_speed > 0 && jump > limit.max(1) {
+            EncoderFault::Jump
+        } else {
+            self.invalid = 0;
+            self.good = raw;
+            return (raw, false);
+        };
+
+        self.invalid += 1;
+        self.report.rejected = self.report.rejected.saturating_add(1);
+        if self.invalid >= self.config.max_invalid.max(1) as u32 {
+            return self.raise(reason);
+        }
+        (self.good, false)
+    }
+
+    /// Latches a fault, the last good angle is kept.
+    fn raise(&mut self, fault: EncoderFault) -> (u16, bool) {
+        self.report.fault = fault;
+        defmt::error!("ENCODER: Fault {}, samples rejected {}", fault as u8, self.report.rejected);
+        (self.good, true)
+    }
+
+    /// Clears a latched fault, checking continues from the next sample.
+    pub fn clear(&mut self) {
+        self.report.fault = EncoderFault::None;
+        self.started = false;
+        self.invalid = 0;
+        self.unchanged = 0;
+    }
+
+    /// Returns true while a fault is latched.
+    pub fn is_faulted(&self) -> bool {
+        self.report.fault != EncoderFault::None
+    }
+
+    /// Returns the fault and counters.
+    pub fn report(&self) -> EncoderSupervisorReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQUENCY: u16 = 20000;
+
+    fn configured() -> EncoderSupervisor {
+        let mut supervisor = EncoderSupervisor::new(FREQUENCY);
+        supervisor.configure(EncoderSupervisorConfig {
+            max_speed: 2_000_000, // 200 counts per tick with the margin
+            stuck_ms: 10,
+            status_mask: ENC_PARITY_ERROR | ENC_MAGNITUDE_ERROR,
+            ..EncoderSupervisorConfig::new()
+        });
+        supervisor
+    }
+
+    #[test]
+    fn single_bad_samples_are_held() {
+        let mut supervisor = configured();
+        assert_eq!(supervisor.tick(1000, 0, 0), (1000, false));
+        assert_eq!(supervisor.tick(1150, 0, 0), (1150, false));
+        assert_eq!(supervisor.tick(30_000, 0, 0), (1150, false)); // Jump: last good angle
+        assert_eq!(supervisor.tick(1200, ENC_PARITY_ERROR, 0), (1150, false));
+        assert_eq!(supervisor.tick(1650, 0, 0), (1650, false)); // The limit grows with the held ticks
+        assert_eq!(supervisor.tick(1660, 0x01, 0), (1660, false)); // Unselected status bit
+        assert_eq!(supervisor.report(), EncoderSupervisorReport { fault: EncoderFault::None, rejected: 2 });
+    }
+
+    #[test]
+    fn burst_of_bad_samples_faults() {
+        for (status, raw, fault) in [
+            (0, 40_000, EncoderFault::Jump),
+            (ENC_PARITY_ERROR, 1000, EncoderFault::Parity),
+            (ENC_MAGNITUDE_ERROR, 1000, EncoderFault::Magnitude),
+        ] {
+            let mut supervisor = configured();
+            supervisor.tick(1000, 0, 0);
+            assert_eq!(supervisor.tick(raw, status, 0), (1000, false));
+            assert_eq!(supervisor.tick(raw, status, 0), (1000, false));
+            assert_eq!(supervisor.tick(raw, status, 0), (1000, true)); // Raised once
+            assert_eq!(supervisor.tick(1000, 0, 0), (1000, false));
+            assert_eq!(supervisor.tick(1100, 0, 0), (1000, false)); // Latched, the good angle is held
+            assert!(supervisor.is_faulted());
+            assert_eq!(supervisor.report().fault, fault);
+
+            supervisor.clear();
+            assert_eq!(supervisor.tick(5000, 0, 0), (5000, false)); // Restarts from the next sample
+            assert!(!supervisor.is_faulted());
+        }
+    }
+
+    #[test]
+    fn stuck_reading_faults_only_while_driven() {
+        let mut supervisor = configured();
+        let stuck_ticks = 10 * FREQUENCY as u32 / 1000;
+        for _ in 0..10 * stuck_ticks {
+            assert_eq!(supervisor.tick(1000, 0, 400), (1000, false)); // Below the stuck current
+        }
+        for tick in 1..=stuck_ticks {
+            assert_eq!(supervisor.tick(1000, 0, -1000).1, tick == stuck_ticks);
+        }
+        assert_eq!(supervisor.report().fault, EncoderFault::Stuck);
+
+        // LSB noise of a healthy sensor restarts the count
+        let mut supervisor = configured();
+        for tick in 0..10 * stuck_ticks {
+            assert!(!supervisor.tick(1000 + (tick / 100 % 2) as u16, 0, 1000).1);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// Implements the field-weakening stage, injecting negative d-axis current when the voltage
+// command approaches the supply so the motor keeps producing torque above its base speed.
+
+// Key Features:
+// - Voltage demand estimate from the winding resistance and back-EMF constant.
+// - Integrating controller holding the demand at a configurable share of the supply.
+// - Configurable maximum weakening current, disabled with 0 (default).
+// - Torque current is reduced so the total vector never exceeds the current limit.
+
+// Detailed Operation:
+// Above base speed the back-EMF plus the resistive drop exceed what the supply can apply, the voltage
+// command saturates and the torque collapses. A negative d-axis current opposes the magnet flux,
+// lowering the back-EMF. The stage estimates the voltage demand as I * R + ke * |velocity| and
+// integrates its excess over `threshold_pct` of the supply voltage into the d-axis current: the
+// weakening grows while the demand is too high and decays once it drops, always within
+// [-max_current_ma, 0]. An excess of 100% of the supply reaches the maximum within
+// `response_ticks`. The caller turns the (d, q) pair into a vector angle and amplitude; the angle
+// leads the rotor by more than 90° electrical, so in voltage mode this is the equivalent
+// angle advance.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::fixed::Q15;
+use crate::math_integer::sqrt::isqrt;
+
+/// Settings of the field-weakening stage.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldWeakeningConfig {
+    /// Largest weakening (negative d-axis) current (mA), 0 disables the stage
+    pub max_current_ma: i32,
+    /// Voltage demand held by the controller (% of the supply)
+    pub threshold_pct: u8,
+    /// Back-EMF constant (mV per mechanical rev/s)
+    pub ke_mv_per_rps: i32,
+    /// Ticks to reach the maximum current with a demand excess of 100% of the supply
+    pub response_ticks: u32,
+}
+
+impl Default for FieldWeakeningConfig {
+    fn default() -> Self {
+        Self {
+            max_current_ma: 0, // Disabled
+            threshold_pct: 95,
+            ke_mv_per_rps: 0,
+            response_ticks: 200, // 10 ms at 20 kHz
+        }
+    }
+}
+
+/// Field-weakening controller producing the d-axis current.
+pub struct FieldWeakening {
+    config: FieldWeakeningConfig,
+    integral: i64, // Accumulated d-axis current (mA, 15 fractional bits), <= 0
+    current: i32,  // Active d-axis current (mA), <= 0
+}
+
+impl FieldWeakening {
+    /// Creates a disabled stage.
+    pub fn new() -> Self {
+        Self {
+            config: FieldWeakeningConfig::default(),
+            integral: 0,
+            current: 0,
+        }
+    }
+
+    /// Sets the stage settings and releases any weakening.
+    pub fn configure(&mut self, config: FieldWeakeningConfig) {
+        self.config = config;
+        self.reset();
+    }
+
+    /// Updates the d-axis current.
+    ///
+    /// # Arguments
+    /// * `torque` - Torque (q-axis) current command (mA)
+    /// * `velocity` - Measured velocity (counts/s, 65536 counts per revolution)
+    /// * `resistance` - Winding resistance (mOhm)
+    /// * `supply_mv` - Supply voltage (mV)
+    ///
+    /// Returns the d-axis current (mA), zero or negative.
+    pub fn tick(&mut self, torque: i32, velocity: i32, resistance: i32, supply_mv: i32) -> i32 {
+        if self.config.max_current_ma <= 0 || supply_mv <= 0 {
+            self.reset();
+            return 0;
+        }
+
+        // ####### Voltage demand #######
+        let current = isqrt((torque as i64).pow(2) as u64 + (self.current as i64).pow(2) as u64) as i64;
+        let resistive = current * resistance as i64 / 1000; // mA * mOhm -> mV
+        let bemf = (velocity.unsigned_abs() as i64 * self.config.ke_mv_per_rps as i64) >> 16;
+        let demand = (resistive + bemf).min(i32::MAX as i64) as i32;
+        let utilization = Q15::from_ratio(demand, supply_mv).0 as i64;
+        let threshold = ((self.config.threshold_pct.min(100) as i64) << 15) / 100;
+
+        // ####### Integrate the excess #######
+        let max = (self.config.max_current_ma as i64) << 15;
+        let step = (utilization - threshold) * max / ((self.config.response_ticks.max(1) as i64) << 15);
+        self.integral = (self.integral - step).clamp(-max, 0);
+        self.current = (self.integral >> 15) as i32;
+        self.current
+    }
+
+    /// Releases the weakening immediately.
+    pub fn reset(&mut self) {
+        self.integral = 0;
+        self.current = 0;
+    }
+
+    /// Returns the active d-axis current (mA), zero or negative.
+    pub fn current(&self) -> i32 {
+        self.current
+    }
+
+    /// Returns true if the stage is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.config.max_current_ma > 0
+    }
+}
+
+impl Default for FieldWeakening {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Limits the torque current so the (d, q) vector stays within `limit` (d has priority).
+///
+/// # Arguments
+/// * `d` - d-axis current (mA)
+/// * `q` - q-axis current (mA)
+/// * `limit` - Current limit of the vector magnitude (mA)
+pub fn limit_torque(d: i32, q: i32, limit: i32) -> i32 {
+    let limit = limit.max(0) as i64;
+    let d = (d as i64).clamp(-limit, limit);
+    let q_max = isqrt((limit * limit - d * d) as u64) as i32;
+    q.clamp(-q_max, q_max)
+}
@@ -0,0 +1,283 @@
+// Implements the gate driver fault handling, turning the nFAULT line and the per-phase desaturation
+// flags of the gate driver into a drive fault with a limited automatic restart.
+
+// Key Features:
+// - Fault line and desaturation flags from the input snapshot (GATE_* bits).
+// - Diagnosis of the last fault: gate driver fault (UVLO, overtemperature) or desaturation per phase.
+// - Automatic retry: up to `retries` restarts, each after the fault cleared for `cooldown_ms`.
+// - Retries restored after `rearm_ms` without a fault, a persistent fault stays latched.
+
+// Detailed Operation:
+// The gate driver pulls nFAULT low (reported as GATE_NFAULT set) on undervoltage of its supply,
+// overtemperature or a short it detected itself, and raises a desaturation flag when a conducting
+// high-side or low-side switch leaves saturation, i.e. the phase sees a short or an overcurrent. Any
+// of them trips the drive at once. Short events like a supply dip or a single overcurrent should not
+// need an operator, so while retries are left the handler waits until all flags are clear for
+// `cooldown_ms` and then requests a fault reset. A reset that faults again uses up the next retry;
+// `rearm_ms` of fault-free operation gives all of them back. Without retries the fault is latched
+// until it is reset by the application.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Gate status bit: nFAULT line asserted
+pub const GATE_NFAULT: u8 = 1 << 0;
+/// Gate status bit: desaturation of phase A (B, C, D follow in the next bits)
+pub const GATE_DESAT_A: u8 = 1 << 1;
+/// Gate status bits: desaturation of all four phases
+pub const GATE_DESAT_MASK: u8 = 0b1111 << 1;
+
+/// Diagnosis of a gate driver fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateDiagnosis {
+    /// No fault seen
+    None,
+    /// Fault line without desaturation: undervoltage, overtemperature or internal protection
+    DriverFault,
+    /// Switch desaturation: short or overcurrent of a phase
+    Desaturation,
+}
+
+/// Response of the gate fault handling to one tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateAction {
+    /// Nothing to do
+    None,
+    /// A fault was detected, the outputs have to stop
+    Trip,
+    /// The fault cleared and a retry is left, the drive may be restarted
+    Retry,
+}
+
+/// Configuration of the gate fault handling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GateFaultConfig {
+    /// Automatic restarts before latching, 0 - always latch
+    pub retries: u8,
+    /// Time the flags have to be clear before a restart (ms)
+    pub cooldown_ms: u16,
+    /// Fault-free time restoring the retries (ms)
+    pub rearm_ms: u32,
+}
+
+impl GateFaultConfig {
+    /// Creates a configuration with 3 retries after 100 ms, restored after 10 s.
+    pub const fn new() -> Self {
+        Self {
+            retries: 3,
+            cooldown_ms: 100,
+            rearm_ms: 10_000,
+        }
+    }
+}
+
+impl Default for GateFaultConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Diagnosis of the gate driver faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GateFaultReport {
+    /// Kind of the last fault
+    pub diagnosis: GateDiagnosis,
+    /// Desaturated phases of the last fault (bit 0 - phase A)
+    pub phases: u8,
+    /// Faults since start
+    pub count: u16,
+    /// Automatic restarts left
+    pub retries_left: u8,
+    /// Fault latched until reset by the application
+    pub latched: bool,
+}
+
+/// Gate driver fault handling with automatic retry.
+pub struct GateFault {
+    frequency: u16, // Update frequency (ticks per second)
+    config: GateFaultConfig,
+
+    tripped: bool, // Fault active, waiting for a restart
+    clear: u32,    // Ticks the flags have been clear while tripped
+    healthy: u32,  // Ticks without a fault while running
+    report: GateFaultReport,
+}
+
+impl GateFault {
+    /// Creates the handling with the default retry policy.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        let config = GateFaultConfig::new();
+        Self {
+            frequency,
+            config,
+            tripped: false,
+            clear: 0,
+            healthy: 0,
+            report: GateFaultReport {
+                diagnosis: GateDiagnosis::None,
+                phases: 0,
+                count: 0,
+                retries_left: config.retries,
+                latched: false,
+            },
+        }
+    }
+
+    /// Applies a retry policy, all retries are available again.
+    pub fn configure(&mut self, config: GateFaultConfig) {
+        self.config = config;
+        self.report.retries_left = config.retries;
+    }
+
+    /// Returns the retry policy.
+    pub fn config(&self) -> GateFaultConfig {
+        self.config
+    }
+
+    /// Evaluates the gate status of one tick.
+    ///
+    /// # Arguments
+    /// * `status` - Gate status flags (GATE_* bits)
+    pub fn tick(&mut self, status: u8) -> GateAction {
+        let faulted = status & (GATE_NFAULT | GATE_DESAT_MASK) != 0;
+        if !self.tripped {
+            if !faulted {
+                let rearm = self.config.rearm_ms.saturating_mul(self.frequency as u32) / 1000;
+                self.healthy = self.healthy.saturating_add(1);
+                if self.healthy >= rearm.max(1) {
+                    self.report.retries_left = self.config.retries;
+                }
+                return GateAction::None;
+            }
+            self.trip(status);
+            return GateAction::Trip;
+        }
+
+        // ####### Tripped #######
+        if faulted || self.report.latched {
+            self.clear = 0;
+            return GateAction::None;
+        }
+        self.clear += 1;
+        let cooldown = self.config.cooldown_ms as u32 * self.frequency as u32 / 1000;
+        if self.clear < cooldown.max(1) {
+            return GateAction::None;
+        }
+        self.tripped = false;
+        self.healthy = 0;
+        self.report.retries_left -= 1;
+        defmt::info!("GATE: Retry, {} left", self.report.retries_left);
+        GateAction::Retry
+    }
+
+    /// Records a fault.
+    fn trip(&mut self, status: u8) {
+        let phases = (status & GATE_DESAT_MASK) / GATE_DESAT_A;
+        self.tripped = true;
+        self.clear = 0;
+        self.report.diagnosis = if phases != 0 { GateDiagnosis::Desaturation } else { GateDiagnosis::DriverFault };
+        self.report.phases = phases;
+        self.report.count = self.report.count.saturating_add(1);
+        self.report.latched = self.report.retries_left == 0;
+        defmt::error!(
+            "GATE: Fault ({}), desaturated phases {:04b}, latched: {}",
+            self.report.diagnosis as u8,
+            phases,
+            self.report.latched
+        );
+    }
+
+    /// Clears a latched fault on a reset by the application, the retries are available again.
+    pub fn clear(&mut self) {
+        self.tripped = false;
+        self.healthy = 0;
+        self.report.latched = false;
+        self.report.retries_left = self.config.retries;
+    }
+
+    /// Returns true while a fault is active.
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// Returns the diagnosis.
+    pub fn report(&self) -> GateFaultReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two retries after 1 ms (20 ticks) clear, restored after 10 ms (200 ticks) without a fault.
+    fn configured() -> GateFault {
+        let mut gate = GateFault::new(20000);
+        gate.configure(GateFaultConfig { retries: 2, cooldown_ms: 1, rearm_ms: 10 });
+        gate
+    }
+
+    /// Runs clear ticks until the handling asks for a retry, returns their count.
+    fn ticks_to_retry(gate: &mut GateFault) -> Option<u32> {
+        (1..=10_000).find(|_| gate.tick(0) == GateAction::Retry)
+    }
+
+    #[test]
+    fn faults_are_diagnosed() {
+        let mut gate = configured();
+        assert_eq!(gate.tick(0), GateAction::None);
+        assert_eq!(gate.tick(GATE_NFAULT), GateAction::Trip);
+        assert_eq!(gate.report().diagnosis, GateDiagnosis::DriverFault);
+        assert_eq!(gate.tick(GATE_NFAULT), GateAction::None); // Tripped once
+        assert_eq!(ticks_to_retry(&mut gate), Some(20));
+
+        assert_eq!(gate.tick(GATE_NFAULT | GATE_DESAT_A << 2), GateAction::Trip);
+        let report = gate.report();
+        assert_eq!((report.diagnosis, report.phases, report.count), (GateDiagnosis::Desaturation, 0b0100, 2));
+    }
+
+    #[test]
+    fn retries_run_out_and_latch() {
+        let mut gate = configured();
+        for retries_left in [1, 0] {
+            assert_eq!(gate.tick(GATE_DESAT_A), GateAction::Trip);
+            // The cooldown restarts while the flag comes back
+            assert!((0..10).all(|_| gate.tick(0) == GateAction::None));
+            assert_eq!(gate.tick(GATE_DESAT_A), GateAction::None);
+            assert_eq!(ticks_to_retry(&mut gate), Some(20));
+            assert_eq!(gate.report().retries_left, retries_left);
+        }
+        assert_eq!(gate.tick(GATE_DESAT_A), GateAction::Trip);
+        assert!(gate.report().latched);
+        assert_eq!(ticks_to_retry(&mut gate), None); // Only the application resets it
+        assert!(gate.is_tripped());
+
+        gate.clear();
+        assert!(!gate.is_tripped());
+        assert_eq!(gate.report(), GateFaultReport { latched: false, retries_left: 2, ..gate.report() });
+    }
+
+    #[test]
+    fn fault_free_operation_restores_the_retries() {
+        let mut gate = configured();
+        assert_eq!(gate.tick(GATE_NFAULT), GateAction::Trip);
+        ticks_to_retry(&mut gate);
+        assert_eq!(gate.report().retries_left, 1);
+        for _ in 0..199 {
+            gate.tick(0);
+        }
+        assert_eq!(gate.report().retries_left, 1);
+        gate.tick(0);
+        assert_eq!(gate.report().retries_left, 2);
+
+        // Without retries every fault latches
+        gate.configure(GateFaultConfig { retries: 0, ..gate.config() });
+        assert_eq!(gate.tick(GATE_NFAULT), GateAction::Trip);
+        assert!(gate.report().latched);
+    }
+}
@@ -0,0 +1,164 @@
+// Implements the haptics module, synthesizing torque setpoints from the measured position and
+// velocity so the axis feels like a knob with detents, springs, end stops and friction.
+
+// Key Features:
+// - Virtual detents: N sine-shaped notches per revolution pulling into the nearest detent.
+// - Spring around a center position, hard walls with their own stiffness outside a range.
+// - Friction (saturated drag around standstill) and viscous damping against the motion.
+// - All effects superposed into one torque current, limited by the caller (closed-loop torque mode).
+// - Detent index of the knob for user interfaces.
+
+// Detailed Operation:
+// The effects are springs in disguise: a detent pattern is the torque -A * sin(N * θ), which has stable
+// equilibria at every 1/N revolution, points in between are unstable and snap to a neighbour. The
+// spring torque is proportional to the displacement from its center, walls act only outside the
+// [wall_min, wall_max] range, normally with a much higher stiffness so the end feels hard. Friction
+// ramps linearly within ±`friction_band` around zero velocity, so the knob doesn't chatter at rest,
+// damping adds a torque proportional to the velocity. Stiffness values are given in mA per revolution
+// of displacement, positions in encoder counts (65536 per revolution).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::trigonometry::angle2sincos_interp;
+
+/// Effects of the haptic knob, disabled effects have a zero strength.
+#[derive(Debug, Clone, Copy)]
+pub struct HapticConfig {
+    /// Detents per revolution, 0 - none
+    pub detents: u16,
+    /// Peak torque of a detent (mA)
+    pub detent_ma: i32,
+    /// Position the spring pulls to (counts)
+    pub spring_center: i32,
+    /// Spring stiffness (mA per rev of displacement)
+    pub spring_ma: i32,
+    /// Lower end of the free range (counts)
+    pub wall_min: i32,
+    /// Upper end of the free range (counts)
+    pub wall_max: i32,
+    /// Wall stiffness (mA per rev of penetration)
+    pub wall_ma: i32,
+    /// Friction torque (mA)
+    pub friction_ma: i32,
+    /// Velocity where the friction reaches full size (counts/s)
+    pub friction_band: i32,
+    /// Viscous damping (mA per rev/s)
+    pub damping_ma: i32,
+}
+
+impl Default for HapticConfig {
+    fn default() -> Self {
+        Self {
+            detents: 24,
+            detent_ma: 150,
+            spring_center: 0,
+            spring_ma: 0,
+            wall_min: i32::MIN,
+            wall_max: i32::MAX,
+            wall_ma: 20_000,
+            friction_ma: 20,
+            friction_band: 1 << 12, // 1/16 rev/s
+            damping_ma: 0,
+        }
+    }
+}
+
+/// Torque synthesis of a haptic knob.
+pub struct Haptics {
+    config: HapticConfig,
+    active: bool,
+    torque: i32, // Synthesized torque current (mA)
+    detent: i32, // Index of the nearest detent
+}
+
+impl Haptics {
+    /// Creates an inactive generator.
+    pub fn new() -> Self {
+        Self {
+            config: HapticConfig::default(),
+            active: false,
+            torque: 0,
+            detent: 0,
+        }
+    }
+
+    /// Starts synthesizing torque with the given effects.
+    pub fn start(&mut self, config: HapticConfig) {
+        self.config = config;
+        self.active = true;
+    }
+
+    /// Stops the torque synthesis.
+    pub fn stop(&mut self) {
+        self.active = false;
+        self.torque = 0;
+    }
+
+    /// Returns true while the generator drives the torque setpoint.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Computes the torque setpoint (mA).
+    ///
+    /// # Arguments
+    /// * `position` - Measured position (counts from zero)
+    /// * `velocity` - Measured velocity (counts/s)
+    pub fn tick(&mut self, position: i32, velocity: i32) -> i32 {
+        if !self.active {
+            return 0;
+        }
+        let cfg = &self.config;
+        let position = position as i64;
+        let velocity = velocity as i64;
+        let mut torque = 0i64;
+
+        // ####### Detents #######
+        if cfg.detents != 0 {
+            let detents = cfg.detents as i64;
+            // Electrical-like angle of the detent pattern: one period per detent
+            let phase = (position * detents) as i16;
+            let (sin, _) = angle2sincos_interp(phase);
+            torque -= (cfg.detent_ma as i64 * sin as i64) >> 15;
+            self.detent = ((position * detents + (1 << 15)) >> 16) as i32;
+        }
+
+        // ####### Spring and walls #######
+        torque -= (cfg.spring_ma as i64 * (position - cfg.spring_center as i64)) >> 16;
+        if position > cfg.wall_max as i64 {
+            torque -= (cfg.wall_ma as i64 * (position - cfg.wall_max as i64)) >> 16;
+        } else if position < cfg.wall_min as i64 {
+            torque -= (cfg.wall_ma as i64 * (position - cfg.wall_min as i64)) >> 16;
+        }
+
+        // ####### Friction and damping #######
+        let band = cfg.friction_band.max(1) as i64;
+        torque -= cfg.friction_ma as i64 * velocity.clamp(-band, band) / band;
+        torque -= (cfg.damping_ma as i64 * velocity) >> 16;
+
+        self.torque = torque.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        self.torque
+    }
+
+    /// Returns the synthesized torque of the last tick (mA).
+    pub fn torque(&self) -> i32 {
+        self.torque
+    }
+
+    /// Returns the index of the nearest detent (position * detents / rev, rounded).
+    pub fn detent(&self) -> i32 {
+        self.detent
+    }
+
+    /// Returns the active effects.
+    pub fn config(&self) -> HapticConfig {
+        self.config
+    }
+}
+
+impl Default for Haptics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,212 @@
+// Implements the drive health module, condensing thermal, current, following error and encoder
+// diagnostics of an axis into one 0-100 score with a per-factor breakdown.
+
+// Key Features:
+// - Thermal margin from the temperature ADC between a nominal and a limit reading.
+// - Current margin from the average torque current relative to the current limit.
+// - Following error statistics (RMS and peak) in position mode.
+// - Encoder health from implausible sample jumps (SPI glitches) and backup status flags.
+// - Overall score that can't hide a single bad factor behind good ones.
+
+// Detailed Operation:
+// Each factor is scored 0..100 (100 = full margin). Averages are exponential with a time constant
+// of 2^AVG_SHIFT ticks (~0.2 s at 20 kHz), so short peaks don't swing the score while a trend
+// shows within seconds. The current margin is full up to half the limit on average and falls to
+// zero at a permanently saturated command. Following error is scored by its RMS against
+// `following_limit`. The encoder factor loses points per glitch rate and for a low backup battery,
+// and drops to zero while the absolute position is not trusted. The overall score is the mean of
+// the factors, capped at the worst factor + 25, so one failing factor always shows in the total.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::sqrt::isqrt;
+
+/// Averaging strength: new sample weight is 1 / 2^AVG_SHIFT
+const AVG_SHIFT: u32 = 12;
+/// Raw encoder change per tick treated as a glitch (1/16 turn, far above any real speed)
+const GLITCH_JUMP: i16 = 4096;
+
+/// Settings of the health score.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthConfig {
+    /// Temperature ADC reading at normal operating temperature (full thermal margin)
+    pub temp_adc_nominal: u16,
+    /// Temperature ADC reading at the thermal limit (no margin), either side of the nominal one
+    pub temp_adc_limit: u16,
+    /// RMS following error scored as zero (counts)
+    pub following_limit: i32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            temp_adc_nominal: 0, // Equal readings disable the thermal factor
+            temp_adc_limit: 0,
+            following_limit: 1024, // ~5.6° mechanical
+        }
+    }
+}
+
+/// Health score of an axis with its contributing factors (0..100 each, 100 = healthy).
+#[derive(Debug, Clone, Copy)]
+pub struct HealthReport {
+    /// Overall score
+    pub score: u8,
+    /// Thermal margin
+    pub thermal: u8,
+    /// Current margin
+    pub current: u8,
+    /// Following error
+    pub following: u8,
+    /// Encoder diagnostics
+    pub encoder: u8,
+    /// RMS following error (counts)
+    pub following_rms: i32,
+    /// Peak following error since the last reset (counts)
+    pub following_peak: i32,
+    /// Encoder glitches since the last reset
+    pub glitches: u32,
+}
+
+impl Default for HealthReport {
+    fn default() -> Self {
+        Self {
+            score: 100,
+            thermal: 100,
+            current: 100,
+            following: 100,
+            encoder: 100,
+            following_rms: 0,
+            following_peak: 0,
+            glitches: 0,
+        }
+    }
+}
+
+/// Snapshot of the axis state needed for one health update.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HealthSample {
+    /// Temperature ADC reading
+    pub temp_adc: u16,
+    /// Raw encoder angle
+    pub angle_raw: u16,
+    /// Torque current command (mA)
+    pub current: i32,
+    /// Current limit (mA)
+    pub current_limit: i32,
+    /// Following error, 0 outside position mode (counts)
+    pub following_error: i32,
+    /// Backup battery of the absolute encoder is low
+    pub battery_low: bool,
+    /// Absolute position is not trusted
+    pub rehome_required: bool,
+}
+
+/// Aggregated health metric of an axis.
+pub struct DriveHealth {
+    config: HealthConfig,
+    usage: i64,       // Average current usage (% of the limit, AVG_SHIFT fractional bits)
+    error_sq: i64,    // Average squared following error (counts^2, AVG_SHIFT fractional bits)
+    glitch_rate: i64, // Average glitch rate (glitches per 1000 ticks, AVG_SHIFT fractional bits)
+    last_angle: u16,  // Raw encoder angle of the previous tick
+    report: HealthReport,
+}
+
+impl DriveHealth {
+    /// Creates a health metric with default settings and a clean history.
+    pub fn new() -> Self {
+        Self {
+            config: HealthConfig::default(),
+            usage: 0,
+            error_sq: 0,
+            glitch_rate: 0,
+            last_angle: 0,
+            report: HealthReport::default(),
+        }
+    }
+
+    /// Sets the health settings.
+    pub fn configure(&mut self, config: HealthConfig) {
+        self.config = config;
+    }
+
+    /// Updates the metric with one tick of data.
+    pub fn tick(&mut self, sample: HealthSample) -> &HealthReport {
+        // ######################## THERMAL ##########################################
+        let (nominal, limit) = (self.config.temp_adc_nominal as i32, self.config.temp_adc_limit as i32);
+        self.report.thermal = if nominal == limit {
+            100
+        } else {
+            percent((limit - sample.temp_adc as i32) as i64 * 100 / (limit - nominal) as i64)
+        };
+
+        // ######################## CURRENT ##########################################
+        let usage = sample.current.saturating_abs() as i64 * 100 / sample.current_limit.max(1) as i64;
+        self.usage += ((usage.min(100) << AVG_SHIFT) - self.usage) >> AVG_SHIFT;
+        self.report.current = percent(200 - 2 * (self.usage >> AVG_SHIFT));
+
+        // ######################## FOLLOWING ERROR ##################################
+        let error = sample.following_error.saturating_abs() as i64;
+        let error_sq = (error * error).min(i64::MAX >> (AVG_SHIFT + 1));
+        self.error_sq += ((error_sq << AVG_SHIFT) - self.error_sq) >> AVG_SHIFT;
+        self.report.following_rms = isqrt((self.error_sq >> AVG_SHIFT) as u64) as i32;
+        self.report.following_peak = self.report.following_peak.max(error as i32);
+        let following_limit = self.config.following_limit.max(1) as i64;
+        self.report.following = percent(100 - self.report.following_rms as i64 * 100 / following_limit);
+
+        // ######################## ENCODER ##########################################
+        let jump = sample.angle_raw.wrapping_sub(self.last_angle) as i16;
+        self.last_angle = sample.angle_raw;
+        let glitch = jump.saturating_abs() > GLITCH_JUMP;
+        if glitch {
+            self.report.glitches = self.report.glitches.saturating_add(1);
+        }
+        let rate = if glitch { 1000i64 } else { 0 };
+        self.glitch_rate += ((rate << AVG_SHIFT) - self.glitch_rate) >> AVG_SHIFT;
+        // Every glitch per 1000 ticks costs 10 points, a low battery 30
+        let mut encoder = 100 - (self.glitch_rate >> AVG_SHIFT) * 10;
+        if sample.battery_low {
+            encoder -= 30;
+        }
+        if sample.rehome_required {
+            encoder = 0;
+        }
+        self.report.encoder = percent(encoder);
+
+        // ######################## SCORE ############################################
+        let factors = [
+            self.report.thermal,
+            self.report.current,
+            self.report.following,
+            self.report.encoder,
+        ];
+        let worst = factors.iter().copied().min().unwrap_or(100) as u16;
+        let mean = factors.iter().map(|f| *f as u16).sum::<u16>() / factors.len() as u16;
+        self.report.score = mean.min(worst + 25) as u8;
+        &self.report
+    }
+
+    /// Clears peak and counter statistics (averages keep running).
+    pub fn reset_statistics(&mut self) {
+        self.report.following_peak = 0;
+        self.report.glitches = 0;
+    }
+
+    /// Returns the last report.
+    pub fn report(&self) -> HealthReport {
+        self.report
+    }
+}
+
+impl Default for DriveHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clamps a score into 0..100.
+#[inline(always)]
+fn percent(value: i64) -> u8 {
+    value.clamp(0, 100) as u8
+}
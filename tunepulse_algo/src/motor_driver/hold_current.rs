@@ -0,0 +1,113 @@
+// Implements the standstill current reduction, lowering the current limit of an axis that has been
+// at rest for a while and restoring it as soon as motion is commanded.
+
+// Key Features:
+// - Configurable standstill delay and hold current (% of the running limit), disabled by default.
+// - Standstill from the setpoint and the measured speed, a new setpoint restores the full current
+//   within the same tick.
+// - Aimed at steppers, whose holding current is otherwise the full phase current.
+
+// Detailed Operation:
+// A stepper holds its position with the full current and dissipates the most at rest, while the
+// torque actually needed there is small. Every tick the driver reports the present setpoint and
+// whether a trajectory runs. If the setpoint doesn't change, no trajectory runs and the measured speed
+// stays below `standstill` for `delay_ms`, the current limit is scaled to `hold_percent`. Any change
+// of the setpoint, a running trajectory or the axis being pushed away faster than the standstill
+// speed restores the full limit at once, so the first tick of a move already has the full torque.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Configuration of the standstill current reduction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoldCurrentConfig {
+    /// Time at rest before reducing (ms), 0 disables the reduction
+    pub delay_ms: u16,
+    /// Current limit at rest (% of the running limit)
+    pub hold_percent: u8,
+    /// Speed treated as rest (counts/s)
+    pub standstill: i32,
+}
+
+impl HoldCurrentConfig {
+    /// Creates a disabled configuration, holding with 50% of the limit once a delay is set.
+    pub const fn new() -> Self {
+        Self {
+            delay_ms: 0,
+            hold_percent: 50,
+            standstill: 1 << 12, // 1/16 rev/s
+        }
+    }
+}
+
+impl Default for HoldCurrentConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Standstill current reduction.
+pub struct HoldCurrent {
+    frequency: u16, // Update frequency (ticks per second)
+    config: HoldCurrentConfig,
+
+    setpoint: i32, // Setpoint of the previous tick
+    rest: u32,     // Ticks at rest
+}
+
+impl HoldCurrent {
+    /// Creates a disabled reduction.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: HoldCurrentConfig::new(),
+            setpoint: 0,
+            rest: 0,
+        }
+    }
+
+    /// Applies a configuration.
+    pub fn configure(&mut self, config: HoldCurrentConfig) {
+        self.config = HoldCurrentConfig {
+            hold_percent: config.hold_percent.min(100),
+            standstill: config.standstill.saturating_abs(),
+            ..config
+        };
+        self.rest = 0;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> HoldCurrentConfig {
+        self.config
+    }
+
+    /// Returns the current limit to apply (mA).
+    ///
+    /// # Arguments
+    /// * `setpoint` - Present setpoint of the loops
+    /// * `moving` - Motion is commanded regardless of the setpoint (trajectory, velocity, torque)
+    /// * `velocity` - Measured velocity (counts/s)
+    /// * `current_limit` - Running current limit (mA)
+    pub fn tick(&mut self, setpoint: i32, moving: bool, velocity: i32, current_limit: i32) -> i32 {
+        let changed = setpoint != self.setpoint;
+        self.setpoint = setpoint;
+        if self.config.delay_ms == 0 || changed || moving || velocity.saturating_abs() > self.config.standstill {
+            self.rest = 0;
+            return current_limit;
+        }
+        self.rest = self.rest.saturating_add(1);
+        if !self.is_holding() {
+            return current_limit;
+        }
+        (current_limit as i64 * self.config.hold_percent as i64 / 100) as i32
+    }
+
+    /// Returns true while the current is reduced.
+    pub fn is_holding(&self) -> bool {
+        let delay = self.config.delay_ms as u32 * self.frequency as u32 / 1000;
+        self.config.delay_ms != 0 && self.rest >= delay.max(1)
+    }
+}
@@ -0,0 +1,269 @@
+// Implements the sensorless homing module, locating a mechanical hard stop at reduced current
+// and establishing the position zero point without any external switch.
+
+// Key Features:
+// - Drives the electrical angle open-loop at a low, configurable current and speed.
+// - Detects the hard stop through the load angle (commanded vs. measured electrical angle).
+// - Zeroes the encoder position at the stop and backs off a configurable distance.
+// - Provides stage, travel and elapsed time reporting while the routine is running.
+
+// Detailed Operation:
+// Homing is a small state machine (Idle -> Seeking -> BackingOff -> Done/Failed). While seeking,
+// the commanded electrical angle advances by `speed` every tick, starting from the measured rotor
+// angle so the start is jerk-free. With a free rotor the field drags it along with a small lag.
+// When the rotor hits the stop, the field keeps advancing and the lag (load angle) grows; once it
+// stays above `stall_angle` for `stall_ticks` consecutive ticks the stop is confirmed. The position
+// is then zeroed and the motor is moved in the opposite direction until it has travelled `backoff`
+// encoder counts. A seek that does not find a stop, or a back-off that does not cover its distance
+// (rotor blocked from both sides, field no longer dragging it), within `timeout_ticks` ends in the
+// Failed stage.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::angle::Angle16;
+use crate::math_integer::motion::position_integrator::Position;
+
+/// Settings of the homing routine.
+#[derive(Debug, Clone, Copy)]
+pub struct HomingConfig {
+    /// Electrical angle increment per tick, the sign selects the seek direction
+    pub speed: i16,
+    /// Current amplitude used during the whole routine (mA)
+    pub current_ma: i16,
+    /// Load angle treated as a stall (electrical, 16384 = 90°)
+    pub stall_angle: u16,
+    /// Number of consecutive ticks above `stall_angle` required to confirm the stop
+    pub stall_ticks: u16,
+    /// Distance to back off from the stop after zeroing (encoder counts)
+    pub backoff: i32,
+    /// Maximum duration of the seek and of the back-off before giving up (ticks)
+    pub timeout_ticks: u32,
+}
+
+impl Default for HomingConfig {
+    fn default() -> Self {
+        Self {
+            speed: 4,                // Slow seek: 4 LSB of electrical angle per tick
+            current_ma: 200,         // Low current to limit the force against the stop
+            stall_angle: 0x2AAA,     // ~60° electrical of lag
+            stall_ticks: 50,         // Filter out short load peaks
+            backoff: 2048,           // ~11° mechanical
+            timeout_ticks: 200_000,  // 10 s at 20 kHz
+        }
+    }
+}
+
+/// Represents the current stage of the homing routine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomingStage {
+    /// Homing is not running
+    Idle,
+    /// Moving towards the hard stop and watching the load angle
+    Seeking,
+    /// Stop found and position zeroed, moving away from the stop
+    BackingOff,
+    /// Homing finished successfully
+    Done,
+    /// No stop was found, or the back-off did not finish, within the timeout
+    Failed,
+}
+
+impl Default for Homing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sensorless homing state machine.
+pub struct Homing {
+    config: HomingConfig,
+    stage: HomingStage,
+
+    angle_el: u16,       // Commanded electrical angle
+    stall_counter: u16,  // Consecutive ticks with the load angle above the threshold
+    start_pos: i32,      // Position at which the current stage started
+    ticks: u32,          // Ticks elapsed in the current stage
+}
+
+impl Homing {
+    /// Creates a new idle homing routine with default settings.
+    pub fn new() -> Self {
+        Self {
+            config: HomingConfig::default(),
+            stage: HomingStage::Idle,
+            angle_el: 0,
+            stall_counter: 0,
+            start_pos: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Starts the routine from the current rotor angle.
+    ///
+    /// # Arguments
+    /// * `config` - Homing settings
+    /// * `angle_el` - Measured electrical angle of the rotor
+    /// * `position` - Current encoder position
+    pub fn start(&mut self, config: HomingConfig, angle_el: u16, position: i32) {
+        self.config = config;
+        self.angle_el = angle_el; // Start from the rotor angle to avoid a jerk
+        self.stall_counter = 0;
+        self.start_pos = position;
+        self.ticks = 0;
+        self.stage = HomingStage::Seeking;
+        defmt::info!("HOMING: Seeking hard stop");
+    }
+
+    /// Stops the routine without touching the position.
+    pub fn cancel(&mut self) {
+        self.stage = HomingStage::Idle;
+    }
+
+    /// Advances the routine by one tick.
+    ///
+    /// # Arguments
+    /// * `position` - Encoder position, zeroed by the routine once the stop is found
+    /// * `angle_el` - Measured electrical angle of the rotor
+    ///
+    /// Returns the commanded (electrical angle, current amplitude) pair.
+    pub fn tick(&mut self, position: &mut Position, angle_el: u16) -> (u16, i16) {
+        self.ticks = self.ticks.saturating_add(1);
+        match self.stage {
+            HomingStage::Seeking => {
+                self.angle_el = Angle16(self.angle_el).offset(self.config.speed as i32).0;
+
+                // Lag of the rotor behind the field in the direction of motion
+                let load = Angle16(self.angle_el).diff(Angle16(angle_el)) as i32;
+                let load = load * self.config.speed.signum() as i32;
+
+                if load > self.config.stall_angle as i32 {
+                    self.stall_counter += 1;
+                } else {
+                    self.stall_counter = 0;
+                }
+
+                if self.stall_counter >= self.config.stall_ticks {
+                    // Stop reached: zero here and release the accumulated lag
+                    position.set_zero();
+                    self.angle_el = angle_el;
+                    self.start_pos = position.position();
+                    self.ticks = 0;
+                    self.stage = HomingStage::BackingOff;
+                    defmt::info!("HOMING: Hard stop found, backing off");
+                } else if self.ticks > self.config.timeout_ticks {
+                    self.stage = HomingStage::Failed;
+                    defmt::error!("HOMING: No hard stop found within timeout");
+                }
+            }
+
+            HomingStage::BackingOff => {
+                self.angle_el = Angle16(self.angle_el).offset(-(self.config.speed as i32)).0;
+                if self.travel(position.position()).abs() >= self.config.backoff.abs() {
+                    self.stage = HomingStage::Done;
+                    defmt::info!("HOMING: Finished");
+                } else if self.ticks > self.config.timeout_ticks {
+                    // Zero point is kept, but the axis is not where homing should leave it
+                    self.stage = HomingStage::Failed;
+                    defmt::error!("HOMING: Back-off not finished within timeout");
+                }
+            }
+
+            HomingStage::Idle | HomingStage::Done | HomingStage::Failed => {
+                return (angle_el, 0);
+            }
+        }
+        (self.angle_el, self.config.current_ma)
+    }
+
+    /// Returns the current stage of the routine.
+    pub fn stage(&self) -> HomingStage {
+        self.stage
+    }
+
+    /// Returns true while the routine is driving the motor.
+    pub fn is_active(&self) -> bool {
+        matches!(self.stage, HomingStage::Seeking | HomingStage::BackingOff)
+    }
+
+    /// Returns the distance travelled since the start of the current stage.
+    pub fn travel(&self, position: i32) -> i32 {
+        position.wrapping_sub(self.start_pos)
+    }
+
+    /// Returns the number of ticks spent in the current stage.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HomingConfig {
+        HomingConfig { timeout_ticks: 20_000, ..HomingConfig::default() }
+    }
+
+    /// Runs the routine on a rotor that follows the field with a first-order lag until it reaches
+    /// `stop` (electrical angle, encoder counts with one pole pair). Returns the last stage.
+    fn run(homing: &mut Homing, position: &mut Position, rotor: &mut i32, stop: i32, ticks: u32) -> HomingStage {
+        let mut field = *rotor;
+        for _ in 0..ticks {
+            position.tick(*rotor as u16);
+            let (angle_el, _) = homing.tick(position, *rotor as u16);
+            field = field.wrapping_add(Angle16(angle_el).diff(Angle16(field as u16)) as i32);
+            *rotor = (*rotor + (field - *rotor) / 64).min(stop);
+            if !homing.is_active() {
+                break;
+            }
+        }
+        homing.stage()
+    }
+
+    #[test]
+    fn free_rotor_does_not_stall() {
+        let (mut homing, mut position, mut rotor) = (Homing::new(), Position::new(), 1000);
+        position.tick(rotor as u16);
+        homing.start(config(), rotor as u16, position.position());
+        assert_eq!(run(&mut homing, &mut position, &mut rotor, i32::MAX, 5000), HomingStage::Seeking);
+        assert!(rotor > 1000 + 4 * 4000, "rotor only reached {}", rotor);
+    }
+
+    #[test]
+    fn stop_found_zeroed_and_backed_off() {
+        let (mut homing, mut position, mut rotor) = (Homing::new(), Position::new(), 0);
+        position.tick(0);
+        homing.start(config(), 0, position.position());
+        assert_eq!(run(&mut homing, &mut position, &mut rotor, 12_000, 20_000), HomingStage::Done);
+        // Zeroed at the stop, left one back-off distance away from it
+        assert!((position.zero() - 12_000).abs() <= 4, "zeroed at {}", position.zero());
+        assert!(position.from_zero() <= -config().backoff);
+    }
+
+    #[test]
+    fn seek_times_out_without_stop() {
+        let (mut homing, mut position, mut rotor) = (Homing::new(), Position::new(), 0);
+        position.tick(0);
+        homing.start(config(), 0, position.position());
+        assert_eq!(run(&mut homing, &mut position, &mut rotor, i32::MAX, 30_000), HomingStage::Failed);
+        assert_eq!(homing.ticks(), config().timeout_ticks + 1);
+    }
+
+    #[test]
+    fn blocked_back_off_times_out() {
+        // Rotor stuck at the stop from both sides: the back-off never covers its distance
+        let (mut homing, mut position) = (Homing::new(), Position::new());
+        position.tick(0);
+        homing.start(config(), 0, position.position());
+        let mut backing_off = 0;
+        for _ in 0..config().timeout_ticks * 2 {
+            homing.tick(&mut position, 0);
+            backing_off += (homing.stage() == HomingStage::BackingOff) as u32;
+        }
+        assert_eq!(homing.stage(), HomingStage::Failed);
+        assert_eq!(backing_off, config().timeout_ticks + 1); // Including the tick the stop was found in
+    }
+}
@@ -0,0 +1,116 @@
+// Implements the index latch, capturing the exact position at the encoder index (Z) edge and
+// optionally moving the zero point onto it for repeatable homing with incremental encoders.
+
+// Key Features:
+// - Position latched from the angle the HAL captured at the edge, not the angle of the tick.
+// - Edge detection from a wrapping edge counter, so no edge is lost between two ticks.
+// - Optional re-zero on the first index after arming, then the latch keeps reporting.
+// - Latched position and number of edges for diagnostics (missed counts show as drift).
+
+// Detailed Operation:
+// The HAL counts index edges and captures the raw encoder angle at the last one, usually with a
+// timer capture triggered by the Z input. A changed counter means an edge happened since the
+// previous tick; it lies within half a turn of the present angle, so the latched position is the
+// present position plus the wrapped angle difference. Re-zeroing moves the zero point by the latched
+// position, the index edge becomes position 0 and the next index edges latch multiples of one turn
+// (any other value reveals lost counts or electrical noise on the A/B lines).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::motion::position_integrator::Position;
+
+/// Result of an index latch update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexEvent {
+    /// No index edge this tick
+    None,
+    /// Position latched
+    Latched,
+    /// Position latched and the zero point moved onto the index
+    Rezeroed,
+}
+
+/// Position latch on the encoder index edge.
+pub struct IndexLatch {
+    count: u8,     // Edge counter of the previous tick
+    started: bool, // Counter seen at least once
+    edges: u32,    // Index edges since start
+    latched: i64,  // Position at the last edge (counts from zero)
+    valid: bool,   // An edge was latched
+    rezero: bool,  // Zero point moves onto the next edge
+}
+
+impl IndexLatch {
+    /// Creates a latch without events.
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            started: false,
+            edges: 0,
+            latched: 0,
+            valid: false,
+            rezero: false,
+        }
+    }
+
+    /// Arms (or disarms) moving the zero point onto the next index edge.
+    pub fn arm_rezero(&mut self, armed: bool) {
+        self.rezero = armed;
+    }
+
+    /// Returns true while the next index edge moves the zero point.
+    pub fn is_armed(&self) -> bool {
+        self.rezero
+    }
+
+    /// Checks for a new index edge and latches the position.
+    ///
+    /// # Arguments
+    /// * `count` - Index edge counter of the HAL
+    /// * `angle` - Encoder angle at the last edge, in the counting direction of `position`
+    /// * `position` - Encoder position, its zero point moves if a re-zero is armed
+    pub fn tick(&mut self, count: u8, angle: u16, position: &mut Position) -> IndexEvent {
+        if !self.started {
+            // Edges counted before the first tick belong to no known position
+            self.started = true;
+            self.count = count;
+            return IndexEvent::None;
+        }
+        if count == self.count {
+            return IndexEvent::None;
+        }
+        self.edges = self.edges.wrapping_add(count.wrapping_sub(self.count) as u32);
+        self.count = count;
+
+        let offset = angle.wrapping_sub(position.angle()) as i16 as i64;
+        self.latched = position.position_from_zero() + offset;
+        self.valid = true;
+        if self.rezero {
+            self.rezero = false;
+            position.set_offset(-offset);
+            self.latched = 0;
+            defmt::info!("INDEX: Zero point moved onto the index");
+            return IndexEvent::Rezeroed;
+        }
+        IndexEvent::Latched
+    }
+
+    /// Returns the position at the last index edge (counts from zero), None before the first edge.
+    pub fn latched(&self) -> Option<i64> {
+        if self.valid { Some(self.latched) } else { None }
+    }
+
+    /// Returns the number of index edges since start.
+    pub fn edges(&self) -> u32 {
+        self.edges
+    }
+}
+
+impl Default for IndexLatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,293 @@
+// Implements the mechanical identification, fitting inertia plus Coulomb and viscous friction from
+// the velocity response to a torque profile.
+
+// Key Features:
+// - Torque profile: bang-bang current between two velocity limits, alternating full and half level.
+// - Integral (window) form of the motion equation, no differentiation of a noisy velocity.
+// - Least-squares fit of three parameters over all windows, solved exactly in integers.
+// - Results in the units used by the observer (acceleration per ampere) and by feed-forwards (mA).
+
+// Detailed Operation:
+// The load follows i = J * a + Fc * sign(v) + B * v (current in mA as torque). The profile drives +I
+// until the velocity exceeds `velocity_limit`, then -I until it falls below -`velocity_limit`; every
+// half cycle alternates between I and I / 2, so accelerations of different size occur at the same
+// speeds and the three terms can be separated. Over a window of N ticks the equation averages to
+// mean(i) = J * f / N * (v_end - v_start) + Fc * mean(sign(v)) + B * mean(v), which needs only
+// the velocity at the window edges. The normal equations of all windows are averaged in i128 and the
+// 3x3 system is solved with Cramer's rule; velocities are scaled down by 2^VEL_SHIFT so the
+// determinants fit. Friction must stay below half of the profile current for the axis to reverse.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Velocity resolution of the fit: 2^VEL_SHIFT counts/s per LSB
+const VEL_SHIFT: u32 = 4;
+/// Resolution of the averaged current and sign regressors: 2^AVG_FRAC per mA / per unit
+const AVG_FRAC: u32 = 10;
+
+/// Settings of the identification profile.
+#[derive(Debug, Clone, Copy)]
+pub struct IdentConfig {
+    /// Profile current (mA), alternating with half of it
+    pub current_ma: i32,
+    /// Velocity where the current reverses (counts/s)
+    pub velocity_limit: i32,
+    /// Ticks per fitted window
+    pub window_ticks: u16,
+    /// Full profile cycles (forward and back) to record
+    pub cycles: u8,
+    /// Maximum duration of the profile (ticks)
+    pub timeout_ticks: u32,
+}
+
+impl Default for IdentConfig {
+    fn default() -> Self {
+        Self {
+            current_ma: 500,
+            velocity_limit: 2 << 16, // 2 rev/s
+            window_ticks: 100,       // 5 ms at 20 kHz
+            cycles: 4,
+            timeout_ticks: 200_000, // 10 s at 20 kHz
+        }
+    }
+}
+
+/// Progress of the identification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentStage {
+    /// Identification is not running
+    Idle,
+    /// Torque profile in progress
+    Running,
+    /// Parameters fitted
+    Done,
+    /// Profile timed out or the fit was not solvable
+    Failed,
+}
+
+/// Identified mechanical parameters.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MechanicsReport {
+    /// Acceleration per ampere of torque current (counts/s^2 per A), Kt / J
+    pub accel_per_amp: i32,
+    /// Coulomb friction (mA)
+    pub coulomb_ma: i32,
+    /// Viscous friction (mA per rev/s)
+    pub viscous_ma: i32,
+    /// Number of fitted windows
+    pub windows: u32,
+}
+
+/// Inertia and friction identification.
+pub struct MechIdent {
+    config: IdentConfig,
+    frequency: u16,
+    stage: IdentStage,
+    direction: i32,   // Sign of the profile current
+    half_level: bool, // Profile runs at half current in this half cycle
+    reversals: u16,   // Current reversals since the start
+    ticks: u32,       // Ticks since the start
+
+    window: u16,       // Ticks in the running window
+    window_v0: i32,    // Velocity at the window start (counts/s)
+    sum_current: i64,  // Summed current of the running window (mA)
+    sum_sign: i64,     // Summed velocity sign of the running window
+    sum_velocity: i64, // Summed velocity of the running window (counts/s)
+
+    normal: [[i128; 3]; 3], // Summed x * x^T of all windows
+    target: [i128; 3],      // Summed x * y of all windows
+    windows: u32,
+    report: MechanicsReport,
+}
+
+impl MechIdent {
+    /// Creates an idle identification.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            config: IdentConfig::default(),
+            frequency,
+            stage: IdentStage::Idle,
+            direction: 1,
+            half_level: false,
+            reversals: 0,
+            ticks: 0,
+            window: 0,
+            window_v0: 0,
+            sum_current: 0,
+            sum_sign: 0,
+            sum_velocity: 0,
+            normal: [[0; 3]; 3],
+            target: [0; 3],
+            windows: 0,
+            report: MechanicsReport::default(),
+        }
+    }
+
+    /// Starts the torque profile.
+    pub fn start(&mut self, config: IdentConfig, velocity: i32) {
+        defmt::info!("IDENT: Profile {}mA up to {} counts/s", config.current_ma, config.velocity_limit);
+        *self = Self::new(self.frequency);
+        self.config = config;
+        self.window_v0 = velocity;
+        self.stage = IdentStage::Running;
+    }
+
+    /// Stops the profile without a result.
+    pub fn cancel(&mut self) {
+        if self.is_active() {
+            self.stage = IdentStage::Idle;
+        }
+    }
+
+    /// Advances the profile and returns the torque current (mA).
+    ///
+    /// # Arguments
+    /// * `velocity` - Measured velocity (counts/s)
+    /// * `current_limit` - Maximum current amplitude (mA)
+    pub fn tick(&mut self, velocity: i32, current_limit: i32) -> i32 {
+        if !self.is_active() {
+            return 0;
+        }
+        self.ticks += 1;
+
+        // ####### Profile #######
+        let limit = self.config.velocity_limit.max(1);
+        if (self.direction > 0 && velocity > limit) || (self.direction < 0 && velocity < -limit) {
+            self.direction = -self.direction;
+            self.half_level = !self.half_level;
+            self.reversals = self.reversals.saturating_add(1);
+        }
+        let level = self.config.current_ma.clamp(0, current_limit.max(0));
+        let current = self.direction * if self.half_level { level / 2 } else { level };
+
+        // ####### Windows #######
+        // The first reversal ends the start-up from rest, only full swings are recorded
+        if self.reversals >= 1 {
+            self.record(velocity, current);
+        } else {
+            self.window_v0 = velocity;
+        }
+
+        if self.reversals > 2 * self.config.cycles as u16 {
+            self.finish();
+        } else if self.ticks >= self.config.timeout_ticks {
+            defmt::error!("IDENT: Profile timed out after {} reversals", self.reversals);
+            self.stage = IdentStage::Failed;
+        }
+        if self.is_active() { current } else { 0 }
+    }
+
+    /// Adds the tick to the running window and the window to the fit once it is complete.
+    fn record(&mut self, velocity: i32, current: i32) {
+        self.window += 1;
+        self.sum_current += current as i64;
+        self.sum_sign += velocity.signum() as i64;
+        self.sum_velocity += velocity as i64;
+        let n = self.config.window_ticks.max(1);
+        if self.window < n {
+            return;
+        }
+
+        let n = n as i64;
+        let x = [
+            ((velocity as i64 - self.window_v0 as i64) >> VEL_SHIFT) as i128,
+            ((self.sum_sign << AVG_FRAC) / n) as i128,
+            ((self.sum_velocity / n) >> VEL_SHIFT) as i128,
+        ];
+        let y = ((self.sum_current << AVG_FRAC) / n) as i128;
+        for (row, &xi) in self.normal.iter_mut().zip(x.iter()) {
+            for (cell, &xj) in row.iter_mut().zip(x.iter()) {
+                *cell += xi * xj;
+            }
+        }
+        for (cell, &xi) in self.target.iter_mut().zip(x.iter()) {
+            *cell += xi * y;
+        }
+        self.windows += 1;
+
+        self.window = 0;
+        self.window_v0 = velocity;
+        self.sum_current = 0;
+        self.sum_sign = 0;
+        self.sum_velocity = 0;
+    }
+
+    /// Solves the least-squares fit and converts it into the report.
+    fn finish(&mut self) {
+        let count = self.windows.max(1) as i128;
+        let m = self.normal.map(|row| row.map(|cell| cell / count));
+        let b = self.target.map(|cell| cell / count);
+        let det = det3(m);
+        if self.windows < 3 || det == 0 {
+            defmt::error!("IDENT: Fit not solvable ({} windows)", self.windows);
+            self.stage = IdentStage::Failed;
+            return;
+        }
+        // Cramer's rule: coefficient k uses the matrix with column k replaced by b
+        let solve = |k: usize| -> i128 {
+            let mut mk = m;
+            for (row, &bi) in mk.iter_mut().zip(b.iter()) {
+                row[k] = bi;
+            }
+            det3(mk)
+        };
+        let (mut det, mut inertia, mut coulomb, mut viscous) = (det, solve(0), solve(1), solve(2));
+        // Only the ratios matter, reducing all four keeps the unit conversions below in range
+        while det.unsigned_abs() > 1u128 << 80 {
+            (det, inertia, coulomb, viscous) = (det >> 1, inertia >> 1, coulomb >> 1, viscous >> 1);
+        }
+
+        // inertia / det: mA << AVG_FRAC per (counts/s >> VEL_SHIFT) of velocity change in a window
+        if inertia <= 0 {
+            defmt::error!("IDENT: No inertia found");
+            self.stage = IdentStage::Failed;
+            return;
+        }
+        let n = self.config.window_ticks.max(1) as i128;
+        let f = self.frequency as i128;
+        // J = inertia * N / f (mA per counts/s^2), accel per amp = 1000 / J
+        let accel = (1000i128 << (AVG_FRAC + VEL_SHIFT)) * f * det / (inertia * n);
+        let viscous = (viscous << 16 >> VEL_SHIFT) / det; // mA << AVG_FRAC per rev/s
+        self.report = MechanicsReport {
+            accel_per_amp: accel.clamp(0, i32::MAX as i128) as i32,
+            coulomb_ma: (coulomb / det).clamp(i32::MIN as i128, i32::MAX as i128) as i32,
+            viscous_ma: (viscous >> AVG_FRAC).clamp(i32::MIN as i128, i32::MAX as i128) as i32,
+            windows: self.windows,
+        };
+        self.stage = IdentStage::Done;
+        defmt::info!(
+            "IDENT: {} counts/s^2 per A, Coulomb {}mA, viscous {}mA per rev/s ({} windows)",
+            self.report.accel_per_amp,
+            self.report.coulomb_ma,
+            self.report.viscous_ma,
+            self.report.windows
+        );
+    }
+
+    /// Returns the active stage.
+    pub fn stage(&self) -> IdentStage {
+        self.stage
+    }
+
+    /// Returns true while the profile drives the motor.
+    pub fn is_active(&self) -> bool {
+        self.stage == IdentStage::Running
+    }
+
+    /// Returns the identified parameters (valid once done).
+    pub fn report(&self) -> MechanicsReport {
+        self.report
+    }
+}
+
+/// Determinant of a 3x3 matrix.
+#[inline(always)]
+fn det3(m: [[i128; 3]; 3]) -> i128 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
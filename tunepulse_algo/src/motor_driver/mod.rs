@@ -1,9 +1,86 @@
 pub mod driver_pulse; // Module handling pulse-related logic
 pub mod driver_pwm; // Module handling PWM-related logic
 
+pub mod autotune; // Module handling relay auto-tuning of the velocity loop
+pub mod backlash; // Module handling backlash compensation of geared axes
+pub mod burst_torque; // Module handling duty-cycle limited current bursts
+pub mod bus_power; // Module handling DC-bus current and input power estimation
 pub mod calibration;
-pub use calibration::angle_calibrator::AngleCalibrator;
-pub use driver_pwm::DriverPWM;
+pub mod cascade; // Module handling position/velocity/current control loops
+pub mod commutation_trim; // Module handling runtime fine-trim of the commutation offset
+pub mod current_gains; // Module handling current-loop gains from the winding R / L
+pub mod dual_bridge; // Module handling two independent brushed motors
+pub mod dual_encoder; // Module handling load-side encoder feedback and cross-check
+pub mod encoder_backup; // Module handling absolute encoder battery and multi-turn status
+pub mod encoder_supervisor; // Module handling encoder sample validation and faults
+pub mod field_weakening; // Module handling negative d-axis current above base speed
+pub mod gate_fault; // Module handling gate driver faults and automatic retries
+pub mod haptics; // Module handling detent, spring and wall torque synthesis
+pub mod health; // Module handling the aggregated drive health score
+pub mod hold_current; // Module handling standstill current reduction
+pub mod homing; // Module handling sensorless homing against a hard stop
+pub mod index_latch; // Module handling the encoder index position latch
+pub mod mech_ident; // Module handling inertia and friction identification
+pub mod mtpa; // Module handling max-torque-per-ampere current distribution
+pub mod phase_balance; // Module handling three-phase current balance diagnostics
+pub mod position_capture; // Module handling position snapshots on external trigger edges
+pub mod safe_params; // Module handling fallback to the last known good tuning
+pub mod safe_stop; // Module handling coast, brake and ramped stops
+pub mod sensorless; // Module handling HFI and back-EMF sensorless rotor angle estimation
+pub mod shadow; // Module handling dry-run and pass-through of the controller output
+pub mod soft_start; // Module handling torque ramping after a fault reset
+pub mod speed_limit; // Module handling the maximum speed and overspeed fault
+pub mod standby; // Module handling reduced current and sleep of an idle axis
+pub mod step_dir; // Module handling the step/dir input and the motion source
+pub mod tick_timer; // Module handling tick period and jitter measurement
+pub mod torque_constant; // Module handling back-EMF and torque constant identification
+pub mod travel_limits; // Module handling soft limits and limit switches
+pub mod watchdog; // Module handling tick stall and setpoint timeout supervision
+pub mod winding_temp; // Module handling winding temperature from the resistance rise
+pub use autotune::{Autotune, AutotuneConfig, AutotuneResult, AutotuneStage};
+pub use backlash::{Backlash, BacklashConfig, BacklashMode};
+pub use burst_torque::{BurstConfig, BurstReport, BurstTorque};
+pub use bus_power::{BusPower, BusReport};
+pub use calibration::angle_calibrator::{AngleCalibrator, CalProgress, CalStage};
+pub use calibration::config::CalibrationConfig;
+pub use calibration::phase_check::{PhaseCheck, PhaseCheckReport, PhaseCheckStage, PhaseFault};
+pub use calibration::quick_align::{QuickAlign, QuickAlignStage};
+pub use calibration::table_compression::{DeltaTable, HarmonicTable};
+pub use calibration::wiring_check::{WiringCheck, WiringDiagnosis, WiringReport, WiringStage};
+pub use cascade::{Cascade, CascadeMode, ScheduleIndex, VelocitySource};
+pub use commutation_trim::{CommutationTrim, TrimReport};
+pub use current_gains::CurrentLoopGains;
+pub use driver_pwm::beeper::{Beeper, Note};
+pub use driver_pwm::{DecayMode, DriverPWM};
+pub use dual_bridge::DualBridge;
+pub use dual_encoder::{DualEncoder, DualEncoderConfig, DualEncoderReport};
+pub use encoder_backup::EncoderBackup;
+pub use encoder_supervisor::{EncoderFault, EncoderSupervisor, EncoderSupervisorConfig, EncoderSupervisorReport};
+pub use field_weakening::{FieldWeakening, FieldWeakeningConfig};
+pub use gate_fault::{GateAction, GateDiagnosis, GateFault, GateFaultConfig, GateFaultReport};
+pub use haptics::{HapticConfig, Haptics};
+pub use health::{DriveHealth, HealthConfig, HealthReport, HealthSample};
+pub use hold_current::{HoldCurrent, HoldCurrentConfig};
+pub use homing::{Homing, HomingConfig, HomingStage};
+pub use index_latch::{IndexEvent, IndexLatch};
+pub use mech_ident::{IdentConfig, IdentStage, MechIdent, MechanicsReport};
+pub use mtpa::{Mtpa, MtpaConfig};
+pub use phase_balance::{BalanceReport, PhaseBalance};
+pub use position_capture::{Capture, PositionCapture};
+pub use safe_params::{SafeParams, SafeParamsConfig, TuningSet};
+pub use safe_stop::{SafeStop, StopMode, StopStage};
+pub use sensorless::hfi::{HfiConfig, HfiStage};
+pub use sensorless::{Sensorless, SensorlessConfig, SensorlessReport, SensorlessSource};
+pub use shadow::{Shadow, ShadowMode, ShadowReport};
+pub use soft_start::{SoftStart, SoftStartConfig, SoftStartStage};
+pub use speed_limit::{SpeedLimit, SpeedLimitConfig};
+pub use standby::{Standby, StandbyConfig, StandbyStage};
+pub use step_dir::{MotionSource, StepDir, StepDirConfig};
+pub use tick_timer::{TickTimer, TickTiming};
+pub use torque_constant::{kt_from_inertia, KtIdent, KtIdentConfig, KtIdentStage, KtReport};
+pub use travel_limits::TravelLimits;
+pub use watchdog::{Watchdog, WatchdogConfig, WatchdogTrip};
+pub use winding_temp::{WindingTemp, WindingTempConfig};
 
 pub struct Motor {
     /// Motor pole count
@@ -42,9 +119,21 @@ impl Motor {
 #[repr(u32)]
 pub enum MotorType {
     UNDEFINED = 1,
+    /// Brushed motor on a single H-bridge, commutation is bypassed
     DC = u16::MAX as u32,
     BLDC = 3,
     STEP = 4,
+    /// Voice-coil / linear actuator on a single H-bridge, force = current, no commutation
+    LINEAR = 5,
+    /// Two independent brushed motors (outputs 1-2 and 3-4), no commutation
+    DUALDC = 6,
+}
+
+impl MotorType {
+    /// Returns true if the current vector has to follow the rotor angle (needs calibration)
+    pub const fn has_commutation(&self) -> bool {
+        !matches!(self, MotorType::DC | MotorType::LINEAR | MotorType::DUALDC)
+    }
 }
 
 /// PhasePattern enumeration for PWM patterns
@@ -0,0 +1,144 @@
+// Implements the phase balance diagnostic, comparing RMS currents of the three phases during
+// constant-speed operation to detect partially shorted windings or poor connections.
+
+// Key Features:
+// - Runs only while the velocity is steady and above a configurable minimum.
+// - Accumulates rectified phase currents over a window covering many electrical periods.
+// - Reports per-phase RMS current and the imbalance in percent of the average.
+// - Warns once per window when the imbalance exceeds a threshold.
+
+// Detailed Operation:
+// For sinusoidal phase currents the rectified average is proportional to the RMS value
+// (RMS = average * π / (2 * √2)), so the ratio between phases is the same and no square root
+// is needed. The diagnostic accumulates |i| of each phase while the velocity stays within a
+// tolerance of the velocity at the start of the window; any deviation restarts the window.
+// After `window_ticks` steady ticks the RMS values and the imbalance (max - min) / average
+// are latched into the report and a new window starts.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Result of the last completed balance window.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BalanceReport {
+    /// RMS current of phases A, B, C (current sense units)
+    pub rms: [i32; 3],
+    /// Difference between the highest and lowest RMS current (% of the average)
+    pub imbalance_pct: i32,
+    /// Number of completed windows, 0 until the first result is available
+    pub windows: u32,
+}
+
+/// Three-phase current balance diagnostic.
+pub struct PhaseBalance {
+    min_velocity: i32,  // Minimum velocity for a valid window (counts/s)
+    tolerance_pct: i32, // Allowed velocity deviation inside the window (%)
+    window_ticks: u32,  // Window length (ticks)
+    warn_pct: i32,      // Imbalance that triggers a warning (%)
+
+    ref_velocity: i32, // Velocity at the start of the window (counts/s)
+    sums: [i64; 3],    // Accumulated |i| of each phase
+    ticks: u32,        // Steady ticks in the current window
+
+    report: BalanceReport,
+}
+
+impl PhaseBalance {
+    /// Creates a diagnostic with a window of 1 s.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            min_velocity: 1 << 15, // 0.5 rev/s
+            tolerance_pct: 5,
+            window_ticks: frequency as u32,
+            warn_pct: 10,
+            ref_velocity: 0,
+            sums: [0; 3],
+            ticks: 0,
+            report: BalanceReport::default(),
+        }
+    }
+
+    /// Sets the diagnostic conditions.
+    ///
+    /// # Arguments
+    /// * `min_velocity` - Minimum velocity for a valid window (counts/s)
+    /// * `tolerance_pct` - Allowed velocity deviation inside the window (%)
+    /// * `window_ticks` - Window length (ticks)
+    /// * `warn_pct` - Imbalance that triggers a warning (%)
+    pub fn configure(&mut self, min_velocity: i32, tolerance_pct: i32, window_ticks: u32, warn_pct: i32) {
+        self.min_velocity = min_velocity.saturating_abs();
+        self.tolerance_pct = tolerance_pct.max(0);
+        self.window_ticks = window_ticks.max(1);
+        self.warn_pct = warn_pct.max(0);
+        self.restart(0);
+    }
+
+    /// Accumulates phase currents of one tick.
+    ///
+    /// # Arguments
+    /// * `currents` - Phase currents, channels 0..2 are phases A, B, C
+    /// * `velocity` - Measured velocity (counts/s)
+    pub fn tick(&mut self, currents: [i16; 4], velocity: i32) {
+        // ####### Steady speed check #######
+        let deviation = (velocity as i64 - self.ref_velocity as i64).abs();
+        let allowed = self.ref_velocity.unsigned_abs() as i64 * self.tolerance_pct as i64 / 100;
+        if velocity.saturating_abs() < self.min_velocity || deviation > allowed {
+            self.restart(velocity);
+            return;
+        }
+
+        // ####### Accumulate #######
+        for (sum, &current) in self.sums.iter_mut().zip(currents.iter()) {
+            *sum += current.unsigned_abs() as i64;
+        }
+        self.ticks += 1;
+
+        // ####### Evaluate window #######
+        if self.ticks >= self.window_ticks {
+            self.evaluate();
+            self.restart(velocity);
+        }
+    }
+
+    /// Returns the result of the last completed window.
+    pub fn report(&self) -> BalanceReport {
+        self.report
+    }
+
+    /// Latches RMS values and imbalance of the finished window.
+    fn evaluate(&mut self) {
+        // Rectified average to RMS: π / (2 * √2) ≈ 1.1107 = 36396 / 32768
+        let rms = self.sums.map(|sum| (((sum / self.ticks as i64) * 36396) >> 15) as i32);
+        let max = *rms.iter().max().unwrap_or(&0);
+        let min = *rms.iter().min().unwrap_or(&0);
+        let avg = rms.iter().sum::<i32>() / 3;
+        let imbalance_pct = if avg > 0 { (max - min) * 100 / avg } else { 0 };
+
+        self.report = BalanceReport {
+            rms,
+            imbalance_pct,
+            windows: self.report.windows.saturating_add(1),
+        };
+        if imbalance_pct > self.warn_pct {
+            defmt::warn!(
+                "PHASE BALANCE: {}% imbalance (A:{} B:{} C:{})",
+                imbalance_pct,
+                rms[0],
+                rms[1],
+                rms[2]
+            );
+        }
+    }
+
+    /// Starts a new window at the given reference velocity.
+    fn restart(&mut self, velocity: i32) {
+        self.ref_velocity = velocity;
+        self.sums = [0; 3];
+        self.ticks = 0;
+    }
+}
@@ -0,0 +1,215 @@
+// Implements the safe parameters module, reverting a tuning change to the last known good set
+// when the axis becomes unstable shortly after the change.
+
+// Key Features:
+// - Snapshot of the loop tuning (position/velocity gains, velocity limit, resonance notch).
+// - Probation period after every tuning change, during which the axis is watched.
+// - Instability events: faults (entering the Error state) and oscillation of the torque command.
+// - Automatic revert to the last known good set after repeated events, latched flag for the host.
+// - A set that survives its probation becomes the new last known good set.
+
+// Detailed Operation:
+// `change()` remembers the set that was active before the change as the fallback and starts the
+// probation. While it runs, every rising edge of the fault state counts as one event. Oscillation
+// is detected on the torque command: a sign change with the command above `osc_threshold_pct` of
+// the current limit counts as a flip, and `osc_flips` flips within `osc_window` ticks count as one
+// event. Regular motion rarely reverses full torque that often, while an unstable loop does it
+// every half period. Reaching `max_events` ends the probation with the fallback set handed back to
+// the caller to apply, and the reverted flag stays set until acknowledged.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use super::cascade::Cascade;
+
+/// Tuning parameters of the control loops guarded by the fallback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TuningSet {
+    /// Kp, Ki, Kd of the position loop
+    pub pos_gains: (i32, i32, i32),
+    /// Kp, Ki, Kd of the velocity loop
+    pub vel_gains: (i32, i32, i32),
+    /// Maximum velocity command (counts/s)
+    pub vel_limit: i32,
+    /// Resonance notch (center Hz, depth)
+    pub notch: (u16, u16),
+}
+
+impl TuningSet {
+    /// Reads the active tuning of the cascade.
+    pub fn capture(cascade: &Cascade) -> Self {
+        Self {
+            pos_gains: cascade.position_gains(),
+            vel_gains: cascade.velocity_gains(),
+            vel_limit: cascade.velocity_limit(),
+            notch: cascade.notch(),
+        }
+    }
+
+    /// Writes the tuning into the cascade.
+    pub fn apply(&self, cascade: &mut Cascade) {
+        let (kp, ki, kd) = self.pos_gains;
+        cascade.set_position_gains(kp, ki, kd);
+        let (kp, ki, kd) = self.vel_gains;
+        cascade.set_velocity_gains(kp, ki, kd);
+        cascade.set_velocity_limit(self.vel_limit);
+        cascade.set_notch(self.notch.0, self.notch.1);
+    }
+}
+
+/// Settings of the instability watch.
+#[derive(Debug, Clone, Copy)]
+pub struct SafeParamsConfig {
+    /// Duration of the watch after a tuning change (ticks)
+    pub probation_ticks: u32,
+    /// Number of instability events that trigger the revert
+    pub max_events: u8,
+    /// Torque command treated as large for oscillation detection (% of the current limit)
+    pub osc_threshold_pct: u8,
+    /// Large torque sign changes within the window counted as one oscillation event
+    pub osc_flips: u16,
+    /// Oscillation detection window (ticks)
+    pub osc_window: u32,
+}
+
+impl Default for SafeParamsConfig {
+    fn default() -> Self {
+        Self {
+            probation_ticks: 200_000, // 10 s at 20 kHz
+            max_events: 2,
+            osc_threshold_pct: 50,
+            osc_flips: 20,
+            osc_window: 4_000, // 0.2 s at 20 kHz, flags oscillations above ~50 Hz
+        }
+    }
+}
+
+/// Last known good tuning with automatic fallback.
+pub struct SafeParams {
+    config: SafeParamsConfig,
+    fallback: Option<TuningSet>, // Set restored on instability (None - nothing to revert to)
+
+    probation: u32, // Ticks left in the probation (0 - not watching)
+    events: u8,     // Instability events in the current probation
+    faulted: bool,  // Fault state of the previous tick
+    flips: u16,     // Large torque sign changes in the current window
+    window: u32,    // Ticks elapsed in the current oscillation window
+    last_sign: i32, // Sign of the last large torque command
+    reverted: bool, // Latched: a revert happened
+}
+
+impl SafeParams {
+    /// Creates an idle watch with default settings.
+    pub fn new() -> Self {
+        Self {
+            config: SafeParamsConfig::default(),
+            fallback: None,
+            probation: 0,
+            events: 0,
+            faulted: false,
+            flips: 0,
+            window: 0,
+            last_sign: 0,
+            reverted: false,
+        }
+    }
+
+    /// Sets the watch settings used from the next tuning change.
+    pub fn configure(&mut self, config: SafeParamsConfig) {
+        self.config = config;
+    }
+
+    /// Starts the probation of a new tuning.
+    ///
+    /// # Arguments
+    /// * `previous` - Tuning active before the change, kept as the fallback
+    pub fn change(&mut self, previous: TuningSet) {
+        // A change during probation keeps the older set, the rejected one was never proven good
+        if self.probation == 0 || self.fallback.is_none() {
+            self.fallback = Some(previous);
+        }
+        self.probation = self.config.probation_ticks.max(1);
+        self.events = 0;
+        self.flips = 0;
+        self.window = 0;
+    }
+
+    /// Watches the axis for one tick.
+    ///
+    /// # Arguments
+    /// * `torque` - Torque current command (mA)
+    /// * `current_limit` - Current limit of the cascade (mA)
+    /// * `faulted` - Axis is in the Error state
+    ///
+    /// Returns the tuning to restore if the new one was found unstable.
+    pub fn tick(&mut self, torque: i32, current_limit: i32, faulted: bool) -> Option<TuningSet> {
+        let rising_fault = faulted && !self.faulted;
+        self.faulted = faulted;
+        if self.probation == 0 {
+            return None;
+        }
+        self.probation -= 1;
+
+        // ######################## FAULTS ###########################################
+        if rising_fault {
+            self.events += 1;
+            defmt::warn!("SAFE PARAMS: Fault after tuning change");
+        }
+
+        // ######################## OSCILLATION ######################################
+        let threshold = current_limit.max(0) as i64 * self.config.osc_threshold_pct as i64 / 100;
+        if (torque.saturating_abs() as i64) > threshold {
+            let sign = torque.signum();
+            if self.last_sign != 0 && sign != self.last_sign {
+                self.flips = self.flips.saturating_add(1);
+            }
+            self.last_sign = sign;
+        }
+        self.window += 1;
+        if self.window >= self.config.osc_window {
+            self.window = 0;
+            self.flips = 0;
+        }
+        if self.flips >= self.config.osc_flips.max(1) {
+            self.flips = 0;
+            self.events += 1;
+            defmt::warn!("SAFE PARAMS: Oscillation after tuning change");
+        }
+
+        // ######################## DECISION #########################################
+        if self.events >= self.config.max_events.max(1) {
+            self.probation = 0;
+            self.reverted = true;
+            defmt::error!("SAFE PARAMS: Tuning unstable, reverted to last known good set");
+            return self.fallback;
+        }
+        if self.probation == 0 {
+            self.fallback = None; // New set proved itself, it becomes the fallback of the next change
+            defmt::info!("SAFE PARAMS: Tuning accepted");
+        }
+        None
+    }
+
+    /// Returns true while a tuning change is being watched.
+    pub fn in_probation(&self) -> bool {
+        self.probation > 0
+    }
+
+    /// Returns true if a revert happened since the last acknowledge.
+    pub fn reverted(&self) -> bool {
+        self.reverted
+    }
+
+    /// Clears the reverted flag.
+    pub fn acknowledge(&mut self) {
+        self.reverted = false;
+    }
+}
+
+impl Default for SafeParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
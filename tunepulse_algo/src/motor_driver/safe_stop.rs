@@ -0,0 +1,235 @@
+// Implements the safe stop, bringing the axis to rest with a selectable behavior and keeping the
+// power stage in that state until the stop is released.
+
+// Key Features:
+// - Coast: all half bridges off, the motor runs out on friction.
+// - Brake: all low sides on, the shorted windings brake with their back-EMF current.
+// - Ramp: controlled deceleration in velocity mode, then the outputs are switched off.
+// - Stop latched until released, later motion commands don't restart the motor.
+
+// Detailed Operation:
+// Coast and brake act at once and only replace the PWM outputs: i16::MIN disables a channel (high
+// impedance), duty 0 keeps the low side on. A ramp stop starts from the measured velocity and lowers
+// the velocity setpoint by `decel` counts/s^2 (sub-count steps kept in Q16), which the velocity loop
+// follows within the current limit. Once the setpoint is zero and the measured speed stays below
+// `standstill` for STOP_SETTLE_MS (or STOP_TIMEOUT_MS passed without it, e.g. a loaded axis that
+// can't hold), the outputs are switched off like a coast stop. Releasing the stop leaves the
+// caller to take over from the present position.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Time the speed has to stay below the standstill threshold after a ramp (ms)
+const STOP_SETTLE_MS: u32 = 20;
+/// Longest wait for standstill after the ramp before switching off anyway (ms)
+const STOP_TIMEOUT_MS: u32 = 500;
+
+/// Stop behavior of the power stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopMode {
+    /// All half bridges off, the motor floats
+    Coast,
+    /// All low sides on, the windings are shorted
+    Brake,
+    /// Decelerate under control, then coast
+    Ramp,
+}
+
+impl StopMode {
+    /// Returns the PWM outputs of the stopped power stage (a finished ramp coasts).
+    pub const fn outputs(&self) -> [i16; 4] {
+        match self {
+            StopMode::Coast | StopMode::Ramp => [i16::MIN; 4],
+            StopMode::Brake => [0; 4],
+        }
+    }
+}
+
+/// Progress of a stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopStage {
+    /// No stop requested
+    Running,
+    /// Decelerating towards standstill
+    Ramping,
+    /// Outputs in the stop state
+    Stopped,
+}
+
+/// Safe stop of the axis.
+pub struct SafeStop {
+    frequency: u16,  // Update frequency (ticks per second)
+    decel: i32,      // Ramp deceleration (counts/s^2)
+    standstill: i32, // Speed treated as standstill (counts/s)
+
+    mode: StopMode,
+    stage: StopStage,
+    velocity: i64, // Ramp velocity setpoint (counts/s, Q16)
+    ticks: u32,    // Ticks since the ramp reached zero
+    settled: u32,  // Consecutive ticks below the standstill speed
+}
+
+impl SafeStop {
+    /// Creates an inactive stop, ramping at 10 rev/s^2 when selected.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            decel: 10 << 16,
+            standstill: 1 << 12, // 1/16 rev/s
+            mode: StopMode::Coast,
+            stage: StopStage::Running,
+            velocity: 0,
+            ticks: 0,
+            settled: 0,
+        }
+    }
+
+    /// Sets the ramp deceleration (counts/s^2) and the speed treated as standstill (counts/s).
+    pub fn configure(&mut self, decel: i32, standstill: i32) {
+        self.decel = decel.saturating_abs().max(1);
+        self.standstill = standstill.saturating_abs();
+    }
+
+    /// Returns the (deceleration, standstill speed) pair.
+    pub fn config(&self) -> (i32, i32) {
+        (self.decel, self.standstill)
+    }
+
+    /// Starts a stop, a running ramp can be sharpened into a coast or brake stop.
+    ///
+    /// # Arguments
+    /// * `mode` - Stop behavior
+    /// * `velocity` - Measured velocity the ramp starts from (counts/s)
+    pub fn request(&mut self, mode: StopMode, velocity: i32) {
+        match (mode, self.stage) {
+            (StopMode::Ramp, StopStage::Running) => {
+                self.stage = StopStage::Ramping;
+                self.velocity = (velocity as i64) << 16;
+                self.ticks = 0;
+                self.settled = 0;
+            }
+            (StopMode::Ramp, _) => return, // Already ramping or stopped
+            _ => self.stage = StopStage::Stopped,
+        }
+        self.mode = mode;
+        defmt::info!("STOP: Requested ({})", mode as u8);
+    }
+
+    /// Advances a ramp stop.
+    ///
+    /// # Arguments
+    /// * `velocity` - Measured velocity (counts/s)
+    ///
+    /// Returns the velocity setpoint while ramping.
+    pub fn tick(&mut self, velocity: i32) -> Option<i32> {
+        if self.stage != StopStage::Ramping {
+            return None;
+        }
+        let step = ((self.decel as i64) << 16) / self.frequency.max(1) as i64;
+        self.velocity = if self.velocity > 0 {
+            (self.velocity - step).max(0)
+        } else {
+            (self.velocity + step).min(0)
+        };
+        if self.velocity == 0 {
+            let settle = STOP_SETTLE_MS * self.frequency as u32 / 1000;
+            let timeout = STOP_TIMEOUT_MS * self.frequency as u32 / 1000;
+            self.ticks += 1;
+            self.settled = if velocity.saturating_abs() <= self.standstill { self.settled + 1 } else { 0 };
+            if self.settled >= settle.max(1) || self.ticks >= timeout {
+                self.stage = StopStage::Stopped;
+                defmt::info!("STOP: Axis at rest, outputs off");
+                return None;
+            }
+        }
+        Some((self.velocity >> 16) as i32)
+    }
+
+    /// Leaves the stop, the caller takes over the axis.
+    pub fn release(&mut self) {
+        self.stage = StopStage::Running;
+    }
+
+    /// Returns the progress of the stop.
+    pub fn stage(&self) -> StopStage {
+        self.stage
+    }
+
+    /// Returns true while a stop is requested or held.
+    pub fn is_active(&self) -> bool {
+        self.stage != StopStage::Running
+    }
+
+    /// Returns the PWM outputs replacing the controller output, None unless stopped.
+    pub fn outputs(&self) -> Option<[i16; 4]> {
+        if self.stage == StopStage::Stopped { Some(self.mode.outputs()) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQUENCY: u16 = 20000;
+
+    #[test]
+    fn coast_and_brake_act_at_once() {
+        let mut stop = SafeStop::new(FREQUENCY);
+        assert_eq!((stop.is_active(), stop.outputs()), (false, None));
+        stop.request(StopMode::Brake, 100_000);
+        assert_eq!(stop.stage(), StopStage::Stopped);
+        assert_eq!(stop.outputs(), Some([0; 4])); // Low sides on
+        assert_eq!(stop.tick(100_000), None);
+
+        stop.request(StopMode::Ramp, 0); // A held stop isn't softened
+        assert_eq!(stop.outputs(), Some([0; 4]));
+        stop.request(StopMode::Coast, 0);
+        assert_eq!(stop.outputs(), Some([i16::MIN; 4])); // All bridges off
+
+        stop.release();
+        assert_eq!((stop.is_active(), stop.outputs()), (false, None));
+    }
+
+    #[test]
+    fn ramp_decelerates_then_switches_off() {
+        let mut stop = SafeStop::new(FREQUENCY);
+        stop.configure(-1_000_000, 100);
+        stop.request(StopMode::Ramp, -200_000); // 0.2 s of ramp
+        assert_eq!(stop.stage(), StopStage::Ramping);
+        assert_eq!(stop.outputs(), None); // The velocity loop keeps driving
+
+        // Axis following the setpoint exactly
+        let (mut velocity, mut ticks) = (-200_000, 0u32);
+        while let Some(setpoint) = stop.tick(velocity) {
+            assert!(setpoint >= velocity && setpoint <= 0, "setpoint {} after {}", setpoint, velocity);
+            velocity = setpoint;
+            ticks += 1;
+        }
+        // Ramp plus the settle time at rest
+        let expected = 4000 + STOP_SETTLE_MS * FREQUENCY as u32 / 1000;
+        assert!(ticks.abs_diff(expected) <= 2, "stopped after {} ticks", ticks);
+        assert_eq!(stop.outputs(), Some([i16::MIN; 4]));
+
+        // Sharpening a running ramp
+        let mut stop = SafeStop::new(FREQUENCY);
+        stop.request(StopMode::Ramp, 50_000);
+        stop.tick(50_000);
+        stop.request(StopMode::Brake, 50_000);
+        assert_eq!(stop.outputs(), Some([0; 4]));
+    }
+
+    #[test]
+    fn ramp_gives_up_on_an_axis_that_keeps_moving() {
+        let mut stop = SafeStop::new(FREQUENCY);
+        stop.request(StopMode::Ramp, 1000);
+        let ticks = (1..=100_000).find(|_| stop.tick(50_000).is_none()).unwrap();
+        let timeout = STOP_TIMEOUT_MS * FREQUENCY as u32 / 1000;
+        assert_eq!(ticks, timeout + 30); // Setpoint ramped to zero in 31 ticks, then the timeout
+        assert_eq!(stop.stage(), StopStage::Stopped);
+    }
+}
@@ -0,0 +1,123 @@
+// Implements the back-EMF (flux) observer, estimating the rotor angle from the applied voltage and
+// the measured current once the motor turns fast enough for the back-EMF to be measurable.
+
+// Key Features:
+// - Voltage model: stator flux integrated from v - R * i in the stationary frame.
+// - Active flux (stator flux - Lq * i) aligned with the d axis, valid for salient motors too.
+// - Leaky integration against offset drift, with the resulting phase lead compensated.
+// - Type-2 tracking loop for a smooth angle and the electrical velocity.
+
+// Detailed Operation:
+// The stator flux follows d(psi)/dt = v - R * i. Subtracting Lq * i from it leaves the active flux,
+// which points along the rotor d axis for any Ld / Lq, so its angle is the rotor angle. A pure
+// integrator would drift away on the small offsets of the voltage and current estimates, so the
+// integrator leaks 1/2^LEAK_SHIFT of its state per tick (cutoff f / 2^LEAK_SHIFT rad/s). In the steady
+// state the leak turns the flux by atan(cutoff / speed) ahead of the rotor, which is subtracted
+// using the tracked velocity; the remaining error grows towards standstill, so the observer is only
+// trusted above a handover speed of several times the cutoff. Units: mV, mA, mOhm, uH; the flux
+// is kept in nWb (uH * mA).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::motion::pll::TrackingPLL;
+use crate::math_integer::sqrt;
+use crate::math_integer::trigonometry::atan2;
+
+/// Leak of the flux integrator: 1 / 2^LEAK_SHIFT of the state per tick
+const LEAK_SHIFT: u32 = 10;
+/// 65536 / 2π: converts rad/s into counts/s
+const COUNTS_PER_RAD: i64 = 10_430;
+
+/// Rotor angle estimation from the integrated back-EMF.
+pub struct FluxObserver {
+    frequency: u16,
+    resistance: i32,   // Winding resistance (mOhm)
+    inductance: i32,   // q-axis inductance (uH)
+    flux: (i64, i64),  // Integrated stator flux (alpha, beta, nWb with LEAK_SHIFT fractional bits)
+    active: u32,       // Magnitude of the active flux (nWb)
+    pll: TrackingPLL,  // Tracks the compensated flux angle
+}
+
+impl FluxObserver {
+    /// Creates an observer without motor parameters.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    /// * `bandwidth` - Tracking loop bandwidth (Hz)
+    pub fn new(frequency: u16, bandwidth: u16) -> Self {
+        Self {
+            frequency,
+            resistance: 0,
+            inductance: 0,
+            flux: (0, 0),
+            active: 0,
+            pll: TrackingPLL::new(frequency, bandwidth),
+        }
+    }
+
+    /// Sets the motor parameters.
+    ///
+    /// # Arguments
+    /// * `resistance` - Winding resistance (mOhm)
+    /// * `inductance` - q-axis inductance (uH)
+    /// * `bandwidth` - Tracking loop bandwidth (Hz)
+    pub fn configure(&mut self, resistance: i32, inductance: i32, bandwidth: u16) {
+        self.resistance = resistance;
+        self.inductance = inductance;
+        self.pll.set_bandwidth(bandwidth);
+    }
+
+    /// Integrates one tick and returns the estimated electrical angle.
+    ///
+    /// # Arguments
+    /// * `voltage` - Applied (alpha, beta) voltage (mV), alpha along phase A
+    /// * `current` - Measured (alpha, beta) current (mA)
+    pub fn tick(&mut self, voltage: (i32, i32), current: (i32, i32)) -> u16 {
+        // ####### Stator flux #######
+        // mV per tick -> nWb: v * 1e6 / f, kept with LEAK_SHIFT fractional bits
+        let frequency = self.frequency.max(1) as i64;
+        let emf = |v: i32, i: i32| -> i64 {
+            let emf = v as i64 - i as i64 * self.resistance as i64 / 1000;
+            ((emf * 1_000_000) << LEAK_SHIFT) / frequency
+        };
+        let (emf_alpha, emf_beta) = (emf(voltage.0, current.0), emf(voltage.1, current.1));
+        self.flux.0 += emf_alpha - (self.flux.0 >> LEAK_SHIFT);
+        self.flux.1 += emf_beta - (self.flux.1 >> LEAK_SHIFT);
+
+        // ####### Active flux #######
+        let alpha = (self.flux.0 >> LEAK_SHIFT) - self.inductance as i64 * current.0 as i64;
+        let beta = (self.flux.1 >> LEAK_SHIFT) - self.inductance as i64 * current.1 as i64;
+        let (alpha, beta) = (sat_i32(alpha), sat_i32(beta));
+        self.active = sqrt::magnitude(alpha, beta);
+
+        // ####### Leak compensation #######
+        let velocity = self.pll.velocity();
+        let cutoff = (frequency * COUNTS_PER_RAD) >> LEAK_SHIFT; // counts/s
+        let lead = atan2(cutoff as i32, velocity.unsigned_abs().min(i32::MAX as u32) as i32);
+        let angle = atan2(alpha, beta);
+        let angle = if velocity < 0 { angle.wrapping_add(lead) } else { angle.wrapping_sub(lead) };
+        self.pll.tick(angle)
+    }
+
+    /// Returns the estimated electrical angle.
+    pub fn angle(&self) -> u16 {
+        self.pll.angle()
+    }
+
+    /// Returns the estimated electrical velocity (counts/s, 65536 counts per electrical revolution).
+    pub fn velocity(&self) -> i32 {
+        self.pll.velocity()
+    }
+
+    /// Returns the magnitude of the active flux (uWb), the magnet flux at zero d-axis current.
+    pub fn flux_uwb(&self) -> i32 {
+        (self.active / 1000) as i32
+    }
+}
+
+/// Saturates into the i32 range.
+#[inline(always)]
+fn sat_i32(value: i64) -> i32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
@@ -0,0 +1,264 @@
+// Implements the high-frequency injection (HFI) rotor angle estimator, tracking the inductance
+// saliency of the motor with a square wave injected on the estimated d axis.
+
+// Key Features:
+// - Square-wave injection alternating its sign every tick, works down to standstill.
+// - Demodulation by differencing consecutive current steps, rejects the slow torque current.
+// - Type-2 tracking loop on the demodulated angle error (zero lag at constant speed).
+// - Magnet polarity (N/S) test from d-axis saturation once the angle has converged.
+// - Independent of a one-period delay between the PWM update and the current sample.
+
+// Detailed Operation:
+// A voltage step on the estimated d axis changes the current within one period by an amount set by
+// the inverse inductance matrix. For a salient motor and an angle error e = rotor - estimate the
+// step in the estimated frame is di_d ~ S - D * cos(2e), di_q ~ D * sin(2e), with
+// S, D = (1/Ld +- 1/Lq) / 2: the current deflects towards the real d axis (the lower inductance).
+// The fundamental current (torque command, back-EMF) barely changes between two periods, so half
+// the difference of two consecutive steps, multiplied by the injection sign, contains only the
+// injection response. atan2(di_q, di_d) is the angle error scaled by about (Lq - Ld) / Ld, it drives
+// a `TrackingPLL` (the loop bandwidth is nominal for Lq = 2 * Ld). The saliency repeats every 180°
+// electrical, so after `settle_ticks` the polarity is tested: a positive d-axis bias adds to the magnet
+// flux and saturates the iron, lowering Ld, so the response under +bias is larger than under -bias
+// when the estimate points to the north pole. Otherwise the estimate is turned by 180°.
+// Injections are d-axis current commands (mA); the driver applies them as voltage I * R.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::motion::pll::TrackingPLL;
+use crate::math_integer::transforms::park;
+use crate::math_integer::trigonometry::{angle2sincos_interp, atan2};
+
+/// Smallest demodulated d-axis response used for tracking (mA), below it the step is noise
+const MIN_RESPONSE: i32 = 2;
+/// Ticks after a (re)start before the current steps are valid
+const WARMUP_TICKS: u8 = 2;
+
+/// Settings of the HFI estimator.
+#[derive(Debug, Clone, Copy)]
+pub struct HfiConfig {
+    /// Square-wave amplitude on the d axis (mA of the current command), 0 disables injection
+    pub injection_ma: i32,
+    /// Tracking loop bandwidth (Hz)
+    pub bandwidth: u16,
+    /// Ticks for the angle to converge before the polarity test
+    pub settle_ticks: u32,
+    /// d-axis bias current of the polarity test (mA)
+    pub polarity_ma: i32,
+    /// Ticks of each half (+bias, -bias) of the polarity test
+    pub polarity_ticks: u32,
+}
+
+impl Default for HfiConfig {
+    fn default() -> Self {
+        Self {
+            injection_ma: 0, // Disabled
+            bandwidth: 50,
+            settle_ticks: 4000,   // 200 ms at 20 kHz
+            polarity_ma: 2000,
+            polarity_ticks: 1000, // 50 ms at 20 kHz
+        }
+    }
+}
+
+/// Progress of the HFI estimator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HfiStage {
+    /// No injection
+    Off,
+    /// Injecting and tracking, the 180° ambiguity is not resolved yet
+    Converging,
+    /// Testing the magnet polarity with a d-axis bias
+    Polarity,
+    /// Angle and polarity known
+    Tracking,
+}
+
+/// Square-wave HFI rotor angle estimator.
+pub struct Hfi {
+    config: HfiConfig,
+    frequency: u16,
+    pll: TrackingPLL, // Tracks the estimated electrical angle
+    stage: HfiStage,
+    ticks: u32,       // Ticks spent in the active stage
+    warmup: u8,       // Ticks left before the current steps are valid
+    sign: i32,        // Sign of the injection applied in the last period
+    last: (i32, i32), // Current of the previous tick (alpha, beta, mA)
+    step: (i32, i32), // Current step of the previous tick (alpha, beta, mA)
+    response: [i64; 2], // Summed d-axis responses under +bias and -bias
+}
+
+impl Hfi {
+    /// Creates a disabled estimator.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        let config = HfiConfig::default();
+        Self {
+            config,
+            frequency,
+            pll: TrackingPLL::new(frequency, config.bandwidth),
+            stage: HfiStage::Off,
+            ticks: 0,
+            warmup: WARMUP_TICKS,
+            sign: 1,
+            last: (0, 0),
+            step: (0, 0),
+            response: [0; 2],
+        }
+    }
+
+    /// Sets the estimator settings and stops the injection.
+    pub fn configure(&mut self, config: HfiConfig) {
+        self.config = config;
+        self.pll.set_bandwidth(config.bandwidth);
+        self.reset();
+    }
+
+    /// Returns true if injection is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.config.injection_ma > 0
+    }
+
+    /// Processes the measured current and returns the d-axis current command for the next period.
+    ///
+    /// # Arguments
+    /// * `current` - Measured (alpha, beta) current (mA), alpha along phase A
+    pub fn tick(&mut self, current: (i32, i32)) -> i32 {
+        if !self.is_enabled() {
+            self.reset();
+            return 0;
+        }
+        if self.stage == HfiStage::Off {
+            defmt::info!("HFI: Injecting {}mA, converging", self.config.injection_ma);
+            self.start(HfiStage::Converging, 0);
+        }
+
+        // ####### Demodulation #######
+        let step = (current.0 - self.last.0, current.1 - self.last.1);
+        let previous = self.step;
+        self.last = current;
+        self.step = step;
+        if self.warmup > 0 {
+            self.warmup -= 1;
+        } else {
+            // The previous step was caused by the opposite sign, the fundamental cancels out
+            let alpha = (step.0 - previous.0) * self.sign / 2;
+            let beta = (step.1 - previous.1) * self.sign / 2;
+            let sincos = angle2sincos_interp(self.pll.angle() as i16);
+            let (d, q) = park(sat_i16(beta), sat_i16(alpha), sincos);
+            // A one-period PWM delay inverts the whole response, the d step is positive by definition
+            let (d, q) = if d < 0 { (-(d as i32), -(q as i32)) } else { (d as i32, q as i32) };
+            if d >= MIN_RESPONSE {
+                self.track(d, q);
+            }
+        }
+
+        // ####### Stage #######
+        self.ticks = self.ticks.saturating_add(1);
+        let bias = match self.stage {
+            HfiStage::Converging if self.ticks >= self.config.settle_ticks => {
+                defmt::info!("HFI: Converged, testing polarity");
+                self.start(HfiStage::Polarity, self.ticks - self.config.settle_ticks);
+                self.config.polarity_ma
+            }
+            HfiStage::Polarity => self.polarity(),
+            _ => 0,
+        };
+
+        self.sign = -self.sign;
+        self.sign * self.config.injection_ma + bias
+    }
+
+    /// Continues tracking from a known angle, injection is restarted without the polarity test.
+    ///
+    /// # Arguments
+    /// * `angle` - Electrical rotor angle (e.g. from the back-EMF observer)
+    pub fn resync(&mut self, angle: u16) {
+        self.pll.reset(angle);
+        self.start(HfiStage::Tracking, 0);
+    }
+
+    /// Stops the injection.
+    pub fn reset(&mut self) {
+        self.stage = HfiStage::Off;
+        self.ticks = 0;
+    }
+
+    /// Returns the active stage.
+    pub fn stage(&self) -> HfiStage {
+        self.stage
+    }
+
+    /// Returns the estimated electrical angle.
+    pub fn angle(&self) -> u16 {
+        self.pll.angle()
+    }
+
+    /// Returns the estimated electrical velocity (counts/s, 65536 counts per electrical revolution).
+    pub fn velocity(&self) -> i32 {
+        self.pll.velocity()
+    }
+
+    /// Feeds the angle error into the tracking loop.
+    #[inline(always)]
+    fn track(&mut self, d: i32, q: i32) {
+        let error = atan2(q, d) as i16; // Within +-90°, d is positive
+        let prediction = self.pll.velocity() / self.frequency.max(1) as i32;
+        let measured = self.pll.angle().wrapping_add(prediction as u16).wrapping_add(error as u16);
+        self.pll.tick(measured);
+        if self.stage == HfiStage::Polarity {
+            // Only the second half of every bias window is used, the bias step settles in the first
+            let window = self.config.polarity_ticks.max(2);
+            let half = (self.ticks / window) as usize;
+            if half < 2 && self.ticks % window >= window / 2 {
+                self.response[half] += d as i64;
+            }
+        }
+    }
+
+    /// Runs the polarity test and returns its d-axis bias current.
+    #[inline(always)]
+    fn polarity(&mut self) -> i32 {
+        let window = self.config.polarity_ticks.max(2);
+        if self.ticks < window {
+            return self.config.polarity_ma;
+        }
+        if self.ticks < 2 * window {
+            return -self.config.polarity_ma;
+        }
+        // The saturated (north) direction has the lower inductance and the larger response
+        let flipped = self.response[1] > self.response[0];
+        if flipped {
+            self.pll.reset(self.pll.angle().wrapping_add(32768));
+        }
+        defmt::info!(
+            "HFI: Polarity {} (responses {} / {}), tracking",
+            if flipped { "flipped" } else { "kept" },
+            self.response[0],
+            self.response[1]
+        );
+        self.start(HfiStage::Tracking, 0);
+        0
+    }
+
+    /// Enters a stage, current steps are ignored until they are valid again.
+    #[inline(always)]
+    fn start(&mut self, stage: HfiStage, ticks: u32) {
+        self.stage = stage;
+        self.ticks = ticks;
+        self.response = [0; 2];
+        if stage != HfiStage::Polarity {
+            self.warmup = WARMUP_TICKS;
+        }
+    }
+}
+
+/// Saturates into the i16 range.
+#[inline(always)]
+fn sat_i16(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
@@ -0,0 +1,197 @@
+// Implements the sensorless rotor angle estimation, combining high-frequency injection at low speed
+// with the back-EMF (flux) observer at speed.
+
+// Key Features:
+// - HFI below the handover speed: full torque at standstill for salient motors.
+// - Flux observer above it, the injection is switched off (no noise or losses at speed).
+// - Bumpless handover in both directions with hysteresis between the two speeds.
+// - Torque is held off until HFI has resolved the magnet polarity.
+
+// Detailed Operation:
+// The flux observer runs every tick so its integrator is settled when it takes over. While HFI is
+// the source its d-axis square wave is added to the field current command; once the HFI velocity
+// exceeds `handover_up` (and the polarity is known) the flux observer provides the angle. When its
+// velocity drops below `handover_down` HFI is restarted at the observer angle, skipping the polarity
+// test. Inputs are stationary-frame vectors with alpha along phase A, so this serves three-phase
+// motors measured with calibrated (mA) phase currents.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+pub mod flux_observer;
+pub mod hfi;
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use flux_observer::FluxObserver;
+use hfi::{Hfi, HfiConfig, HfiStage};
+
+/// Settings of the sensorless estimation.
+#[derive(Debug, Clone, Copy)]
+pub struct SensorlessConfig {
+    /// Injection settings, `hfi.injection_ma` = 0 disables sensorless operation
+    pub hfi: HfiConfig,
+    /// q-axis inductance of the flux observer (uH)
+    pub lq_uh: i32,
+    /// Tracking loop bandwidth of the flux observer (Hz)
+    pub flux_bandwidth: u16,
+    /// HFI to flux observer handover (electrical counts/s, 65536 per electrical revolution)
+    pub handover_up: i32,
+    /// Flux observer to HFI handover (electrical counts/s), below `handover_up`
+    pub handover_down: i32,
+}
+
+impl Default for SensorlessConfig {
+    fn default() -> Self {
+        Self {
+            hfi: HfiConfig::default(),
+            lq_uh: 0,
+            flux_bandwidth: 200,
+            handover_up: 20 << 16,   // 20 electrical rev/s
+            handover_down: 15 << 16, // 15 electrical rev/s
+        }
+    }
+}
+
+/// Estimator that provides the rotor angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorlessSource {
+    /// Sensorless operation is disabled
+    Off,
+    /// High-frequency injection
+    Hfi,
+    /// Back-EMF flux observer
+    Flux,
+}
+
+/// State of the sensorless estimation.
+#[derive(Debug, Clone, Copy)]
+pub struct SensorlessReport {
+    /// Estimator that provides the angle
+    pub source: SensorlessSource,
+    /// Stage of the HFI estimator
+    pub hfi_stage: HfiStage,
+    /// Estimated electrical angle
+    pub angle_el: u16,
+    /// Estimated electrical velocity (counts/s)
+    pub velocity_el: i32,
+    /// Active flux magnitude of the observer (uWb)
+    pub flux_uwb: i32,
+}
+
+/// Sensorless rotor angle from HFI and the flux observer.
+pub struct Sensorless {
+    config: SensorlessConfig,
+    hfi: Hfi,
+    flux: FluxObserver,
+    source: SensorlessSource,
+    injection: i32, // d-axis current command of the injection (mA)
+    angle: u16,     // Estimated electrical angle
+}
+
+impl Sensorless {
+    /// Creates a disabled estimation.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        let config = SensorlessConfig::default();
+        Self {
+            config,
+            hfi: Hfi::new(frequency),
+            flux: FluxObserver::new(frequency, config.flux_bandwidth),
+            source: SensorlessSource::Off,
+            injection: 0,
+            angle: 0,
+        }
+    }
+
+    /// Sets the estimation settings, estimation restarts with HFI on the next tick.
+    ///
+    /// # Arguments
+    /// * `config` - Estimation settings
+    /// * `resistance` - Winding resistance (mOhm)
+    pub fn configure(&mut self, config: SensorlessConfig, resistance: i32) {
+        self.config = config;
+        self.hfi.configure(config.hfi);
+        self.flux.configure(resistance, config.lq_uh, config.flux_bandwidth);
+        self.source = SensorlessSource::Off;
+        self.injection = 0;
+    }
+
+    /// Returns true if sensorless operation is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.hfi.is_enabled()
+    }
+
+    /// Returns true once the angle is usable for torque (polarity resolved).
+    pub fn is_locked(&self) -> bool {
+        match self.source {
+            SensorlessSource::Hfi => self.hfi.stage() == HfiStage::Tracking,
+            SensorlessSource::Flux => true,
+            SensorlessSource::Off => false,
+        }
+    }
+
+    /// Updates the estimate and returns the electrical rotor angle.
+    ///
+    /// # Arguments
+    /// * `voltage` - Applied (alpha, beta) voltage of the last period (mV)
+    /// * `current` - Measured (alpha, beta) current (mA)
+    pub fn tick(&mut self, voltage: (i32, i32), current: (i32, i32)) -> u16 {
+        if !self.is_enabled() {
+            self.source = SensorlessSource::Off;
+            self.injection = 0;
+            return self.angle;
+        }
+        self.flux.tick(voltage, current);
+
+        match self.source {
+            SensorlessSource::Off => self.source = SensorlessSource::Hfi,
+            SensorlessSource::Hfi if self.is_locked() && self.hfi.velocity().abs() > self.config.handover_up => {
+                defmt::info!("SENSORLESS: Flux observer at {} counts/s", self.hfi.velocity());
+                self.hfi.reset();
+                self.source = SensorlessSource::Flux;
+            }
+            SensorlessSource::Flux if self.flux.velocity().abs() < self.config.handover_down => {
+                defmt::info!("SENSORLESS: HFI at {} counts/s", self.flux.velocity());
+                self.hfi.resync(self.flux.angle());
+                self.source = SensorlessSource::Hfi;
+            }
+            _ => {}
+        }
+
+        (self.angle, self.injection) = match self.source {
+            SensorlessSource::Flux => (self.flux.angle(), 0),
+            _ => {
+                let injection = self.hfi.tick(current);
+                (self.hfi.angle(), injection)
+            }
+        };
+        self.angle
+    }
+
+    /// Returns the d-axis current command of the injection (mA), added to the field current.
+    pub fn injection(&self) -> i32 {
+        self.injection
+    }
+
+    /// Returns the estimated electrical angle.
+    pub fn angle(&self) -> u16 {
+        self.angle
+    }
+
+    /// Returns the state of the estimation.
+    pub fn report(&self) -> SensorlessReport {
+        SensorlessReport {
+            source: self.source,
+            hfi_stage: self.hfi.stage(),
+            angle_el: self.angle,
+            velocity_el: match self.source {
+                SensorlessSource::Flux => self.flux.velocity(),
+                _ => self.hfi.velocity(),
+            },
+            flux_uwb: self.flux.flux_uwb(),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Implements the shadow mode module, running the full control cascade from live sensor data while
+// the outputs stay disabled or follow an external command.
+
+// Key Features:
+// - Three modes: off (normal operation), dry run (outputs disabled), pass-through (external drive).
+// - Records what the controller would command: electrical angle, amplitude and torque current.
+// - Peak statistics of the shadow command since the last reset.
+// - Periodic defmt log of the shadow command at a configurable interval.
+
+// Detailed Operation:
+// The controller runs unchanged every tick: sensors, cascade, commutation and limits. Just before
+// the command reaches the PWM stage the shadow module records it and substitutes the output:
+// nothing in dry run (the motor is free, e.g. turned by hand or by another drive), or the command
+// given through `set_command()` in pass-through (e.g. the machine's existing controller drives the
+// motor while the new tuning and observers are compared against it). In dry run the loops see
+// no response to their commands, so their integrators wind up; the recorded values are meant for
+// comparison with the live command, not as an exact replay of closed-loop behaviour.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Selects how the controller output reaches the motor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowMode {
+    /// Normal operation, the controller drives the motor
+    Off,
+    /// Controller runs, outputs stay disabled
+    DryRun,
+    /// Controller runs, the external command drives the motor
+    PassThrough,
+}
+
+/// What the controller would have commanded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadowReport {
+    /// Electrical angle of the last command
+    pub angle_el: u16,
+    /// Amplitude of the last command (mA)
+    pub amplitude: i16,
+    /// Signed torque current of the last command (mA)
+    pub current: i32,
+    /// Largest |torque current| since the last reset (mA)
+    pub peak_current: i32,
+    /// Ticks spent in shadow mode since the last reset
+    pub ticks: u32,
+}
+
+/// Dry-run / pass-through handling of the controller output.
+pub struct Shadow {
+    mode: ShadowMode,
+    external: (u16, i16), // External (electrical angle, amplitude) command for pass-through
+    log_interval: u32,    // Ticks between log lines, 0 - no logging
+    log_counter: u32,     // Ticks since the last log line
+    report: ShadowReport,
+}
+
+impl Shadow {
+    /// Creates a shadow handler in normal operation, logging once per second when active.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            mode: ShadowMode::Off,
+            external: (0, 0),
+            log_interval: frequency as u32,
+            log_counter: 0,
+            report: ShadowReport::default(),
+        }
+    }
+
+    /// Selects the mode, statistics restart on every change.
+    pub fn set_mode(&mut self, mode: ShadowMode) {
+        if mode != self.mode {
+            defmt::info!("SHADOW: Mode {} (0 - off, 1 - dry run, 2 - pass-through)", mode as u8);
+            self.reset();
+        }
+        self.mode = mode;
+        self.external = (0, 0);
+    }
+
+    /// Sets the external (electrical angle, amplitude in mA) command used in pass-through.
+    pub fn set_command(&mut self, angle_el: u16, amplitude: i16) {
+        self.external = (angle_el, amplitude);
+    }
+
+    /// Sets the log interval (ticks), 0 disables logging.
+    pub fn set_log_interval(&mut self, ticks: u32) {
+        self.log_interval = ticks;
+    }
+
+    /// Records the controller command and returns the one that drives the motor.
+    ///
+    /// # Arguments
+    /// * `command` - (electrical angle, amplitude) computed by the controller
+    /// * `current` - Signed torque current behind the command (mA)
+    pub fn tick(&mut self, command: (u16, i16), current: i32) -> (u16, i16) {
+        if self.mode == ShadowMode::Off {
+            return command;
+        }
+        self.report.angle_el = command.0;
+        self.report.amplitude = command.1;
+        self.report.current = current;
+        self.report.peak_current = self.report.peak_current.max(current.saturating_abs());
+        self.report.ticks = self.report.ticks.saturating_add(1);
+
+        self.log_counter += 1;
+        if self.log_interval != 0 && self.log_counter >= self.log_interval {
+            self.log_counter = 0;
+            defmt::info!(
+                "SHADOW: angle {}, amplitude {}mA, current {}mA, peak {}mA",
+                command.0,
+                command.1,
+                current,
+                self.report.peak_current
+            );
+        }
+
+        match self.mode {
+            ShadowMode::PassThrough => self.external,
+            _ => (command.0, 0),
+        }
+    }
+
+    /// Returns the active mode.
+    pub fn mode(&self) -> ShadowMode {
+        self.mode
+    }
+
+    /// Returns true while the controller output is not driving the motor.
+    pub fn is_active(&self) -> bool {
+        self.mode != ShadowMode::Off
+    }
+
+    /// Returns what the controller would have commanded.
+    pub fn report(&self) -> ShadowReport {
+        self.report
+    }
+
+    /// Clears peak statistics and the tick counter.
+    pub fn reset(&mut self) {
+        self.report = ShadowReport::default();
+        self.log_counter = 0;
+    }
+}
@@ -0,0 +1,158 @@
+// Implements the soft-start module, re-engaging a loaded axis after a fault reset by ramping
+// the available torque instead of applying the full current limit at once.
+
+// Key Features:
+// - Linear ramp of the current limit up to the holding current, then a dwell at that level.
+// - Sag monitoring: the axis must stay within a tolerance of the position it was re-engaged at.
+// - Raises a fault if the axis moves too far (e.g. a vertical axis the holding current can't carry).
+// - Releases the full current limit once the dwell is over.
+
+// Detailed Operation:
+// After a fault the motor is unpowered and a loaded axis (vertical Z, tensioned belt) may be held
+// only by friction or a brake. Applying the full current at re-engagement lets the position loop
+// kick the axis. The soft-start holds the position at which the reset happened and raises the
+// current limit from zero to `hold_current_ma` over `ramp_ticks`, keeps it there for `dwell_ticks`
+// and then hands over the full limit. If the position leaves `tolerance` counts around the start
+// position at any point the stage ends as Failed and the caller raises a new fault.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Settings of the soft-start.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftStartConfig {
+    /// Current limit reached at the end of the ramp (mA)
+    pub hold_current_ma: i32,
+    /// Duration of the ramp from zero to the holding current (ticks)
+    pub ramp_ticks: u32,
+    /// Time spent at the holding current before the full limit is released (ticks)
+    pub dwell_ticks: u32,
+    /// Maximum distance from the start position during re-engagement (counts)
+    pub tolerance: i32,
+}
+
+impl Default for SoftStartConfig {
+    fn default() -> Self {
+        Self {
+            hold_current_ma: 1000,
+            ramp_ticks: 10_000, // 0.5 s at 20 kHz
+            dwell_ticks: 4_000, // 0.2 s at 20 kHz
+            tolerance: 1024,    // ~5.6° mechanical
+        }
+    }
+}
+
+/// Represents the current stage of the soft-start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftStartStage {
+    /// Soft-start is not running, full current limit is available
+    Idle,
+    /// Current limit is rising towards the holding current
+    Ramping,
+    /// Holding current is applied, waiting for the axis to settle
+    Dwell,
+    /// Axis moved beyond the tolerance during re-engagement
+    Failed,
+}
+
+/// Current limit ramp with sag monitoring.
+pub struct SoftStart {
+    config: SoftStartConfig,
+    stage: SoftStartStage,
+    start_pos: i32, // Position the axis is re-engaged at (counts)
+    ticks: u32,     // Ticks elapsed in the current stage
+}
+
+impl SoftStart {
+    /// Creates an idle soft-start with default settings.
+    pub fn new() -> Self {
+        Self {
+            config: SoftStartConfig::default(),
+            stage: SoftStartStage::Idle,
+            start_pos: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Sets soft-start settings used by the next start.
+    pub fn configure(&mut self, config: SoftStartConfig) {
+        self.config = config;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> SoftStartConfig {
+        self.config
+    }
+
+    /// Starts re-engagement at the given position.
+    pub fn start(&mut self, position: i32) {
+        self.start_pos = position;
+        self.ticks = 0;
+        self.stage = SoftStartStage::Ramping;
+        defmt::info!("SOFT START: Ramping to {}mA", self.config.hold_current_ma);
+    }
+
+    /// Advances the soft-start by one tick.
+    ///
+    /// # Arguments
+    /// * `position` - Current position (counts)
+    /// * `current_limit` - Full current limit (mA)
+    ///
+    /// Returns the current limit to apply in this tick (mA).
+    pub fn tick(&mut self, position: i32, current_limit: i32) -> i32 {
+        let hold = self.config.hold_current_ma.clamp(0, current_limit.max(0));
+        let sag = position.wrapping_sub(self.start_pos);
+        if matches!(self.stage, SoftStartStage::Ramping | SoftStartStage::Dwell)
+            && sag.saturating_abs() > self.config.tolerance
+        {
+            self.stage = SoftStartStage::Failed;
+            defmt::error!("SOFT START: Axis moved {} counts during re-engagement", sag);
+        }
+
+        self.ticks = self.ticks.saturating_add(1);
+        match self.stage {
+            SoftStartStage::Ramping => {
+                let ramp = self.config.ramp_ticks.max(1);
+                if self.ticks >= ramp {
+                    self.ticks = 0;
+                    self.stage = SoftStartStage::Dwell;
+                    return hold;
+                }
+                ((hold as i64 * self.ticks as i64) / ramp as i64) as i32
+            }
+            SoftStartStage::Dwell => {
+                if self.ticks >= self.config.dwell_ticks {
+                    self.stage = SoftStartStage::Idle;
+                    defmt::info!("SOFT START: Finished");
+                    return current_limit;
+                }
+                hold
+            }
+            SoftStartStage::Failed => 0,
+            SoftStartStage::Idle => current_limit,
+        }
+    }
+
+    /// Returns the current stage.
+    pub fn stage(&self) -> SoftStartStage {
+        self.stage
+    }
+
+    /// Returns true while the current limit is being ramped or held.
+    pub fn is_active(&self) -> bool {
+        matches!(self.stage, SoftStartStage::Ramping | SoftStartStage::Dwell)
+    }
+
+    /// Returns the distance the axis moved from the start position (counts).
+    pub fn sag(&self, position: i32) -> i32 {
+        position.wrapping_sub(self.start_pos)
+    }
+}
+
+impl Default for SoftStart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,203 @@
+// Implements the speed limit, capping the commanded mechanical speed and faulting when the measured
+// speed runs away beyond it.
+
+// Key Features:
+// - One maximum speed for velocity setpoints, the position loop output and motion profiles.
+// - Overspeed fault when the measured speed exceeds the limit by a margin for a debounce time.
+// - Peak measured speed kept for diagnostics.
+// - Disabled with a zero limit.
+
+// Detailed Operation:
+// The driver clamps every velocity setpoint and the profile cruise speed to `max_speed` and hands it to
+// the cascade as its velocity command limit, so no setpoint source can ask for more. A correctly
+// working loop can still overshoot the limit a little (load step, aggressive gains), which is what the
+// margin allows for. A rotor that keeps accelerating past limit + margin for `trip_ms` is not
+// controlled any more: a wrong commutation offset or swapped phases turn the current against the
+// angle, an overhauling load drives the motor, or the encoder slipped. The fault is latched.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Configuration of the speed limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedLimitConfig {
+    /// Maximum mechanical speed (counts/s), 0 disables the limit
+    pub max_speed: i32,
+    /// Overspeed allowed above the limit before faulting (%)
+    pub margin: u16,
+    /// Time above limit + margin before faulting (ms)
+    pub trip_ms: u16,
+}
+
+impl SpeedLimitConfig {
+    /// Creates a disabled limit with a 20% margin and a 5 ms debounce.
+    pub const fn new() -> Self {
+        Self {
+            max_speed: 0,
+            margin: 20,
+            trip_ms: 5,
+        }
+    }
+}
+
+impl Default for SpeedLimitConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Speed limit and overspeed detection.
+pub struct SpeedLimit {
+    frequency: u16, // Update frequency (ticks per second)
+    config: SpeedLimitConfig,
+
+    over: u32,     // Consecutive ticks above limit + margin
+    peak: i32,     // Highest measured |speed| (counts/s)
+    tripped: bool, // Overspeed fault latched
+}
+
+impl SpeedLimit {
+    /// Creates a disabled speed limit.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: SpeedLimitConfig::new(),
+            over: 0,
+            peak: 0,
+            tripped: false,
+        }
+    }
+
+    /// Applies a configuration.
+    pub fn configure(&mut self, config: SpeedLimitConfig) {
+        self.config = SpeedLimitConfig {
+            max_speed: config.max_speed.max(0),
+            ..config
+        };
+        self.over = 0;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> SpeedLimitConfig {
+        self.config
+    }
+
+    /// Returns true while a maximum speed is set.
+    pub fn is_enabled(&self) -> bool {
+        self.config.max_speed > 0
+    }
+
+    /// Limits a velocity (counts/s) to the maximum speed.
+    pub fn clamp(&self, velocity: i32) -> i32 {
+        if !self.is_enabled() {
+            return velocity;
+        }
+        velocity.clamp(-self.config.max_speed, self.config.max_speed)
+    }
+
+    /// Checks the measured speed.
+    ///
+    /// # Arguments
+    /// * `velocity` - Measured velocity (counts/s)
+    ///
+    /// Returns true on the tick the overspeed fault trips.
+    pub fn tick(&mut self, velocity: i32) -> bool {
+        let speed = velocity.saturating_abs();
+        self.peak = self.peak.max(speed);
+        if !self.is_enabled() || self.tripped {
+            return false;
+        }
+        let margin = self.config.max_speed as i64 * self.config.margin as i64 / 100;
+        if speed as i64 <= self.config.max_speed as i64 + margin {
+            self.over = 0;
+            return false;
+        }
+        self.over += 1;
+        let trip = self.config.trip_ms as u32 * self.frequency as u32 / 1000;
+        if self.over < trip.max(1) {
+            return false;
+        }
+        self.tripped = true;
+        defmt::error!("OVERSPEED: {} counts/s, limit {} counts/s", speed, self.config.max_speed);
+        true
+    }
+
+    /// Clears a latched fault.
+    pub fn clear(&mut self) {
+        self.tripped = false;
+        self.over = 0;
+    }
+
+    /// Returns true while the overspeed fault is latched.
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// Returns the highest measured speed (counts/s) since the last reset.
+    pub fn peak(&self) -> i32 {
+        self.peak
+    }
+
+    /// Restarts the peak speed.
+    pub fn reset_peak(&mut self) {
+        self.peak = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured() -> SpeedLimit {
+        let mut limit = SpeedLimit::new(20000);
+        limit.configure(SpeedLimitConfig { max_speed: 100_000, ..SpeedLimitConfig::new() }); // Trip at 120000
+        limit
+    }
+
+    #[test]
+    fn disabled_limit_passes_everything() {
+        let mut limit = SpeedLimit::new(20000);
+        assert!(!limit.is_enabled());
+        assert_eq!(limit.clamp(i32::MIN), i32::MIN);
+        assert!((0..1000).all(|_| !limit.tick(i32::MAX)));
+        assert_eq!(limit.peak(), i32::MAX);
+
+        limit.configure(SpeedLimitConfig { max_speed: -5, ..SpeedLimitConfig::new() });
+        assert!(!limit.is_enabled()); // Negative limit is no limit
+    }
+
+    #[test]
+    fn setpoints_are_clamped_both_ways() {
+        let limit = configured();
+        assert_eq!(limit.clamp(150_000), 100_000);
+        assert_eq!(limit.clamp(-150_000), -100_000);
+        assert_eq!(limit.clamp(-42), -42);
+    }
+
+    #[test]
+    fn overspeed_trips_after_the_debounce() {
+        let mut limit = configured();
+        assert!((0..10_000).all(|_| !limit.tick(-120_000))); // Overshoot within the margin
+        // Short peaks restart the debounce (5 ms = 100 ticks)
+        for _ in 0..10 {
+            assert!((0..99).all(|_| !limit.tick(130_000)));
+            assert!(!limit.tick(110_000));
+        }
+        assert!((0..99).all(|_| !limit.tick(-130_000)));
+        assert!(limit.tick(-130_000));
+        assert!(!limit.tick(-130_000)); // Raised once, latched
+        assert!(limit.is_tripped());
+        assert_eq!(limit.peak(), 130_000);
+
+        limit.clear();
+        assert!(!limit.is_tripped());
+        assert!(!limit.tick(130_000)); // Debounce starts over
+        limit.reset_peak();
+        assert_eq!(limit.peak(), 0);
+    }
+}
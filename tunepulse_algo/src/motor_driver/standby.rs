@@ -0,0 +1,199 @@
+// Implements the standby mode, lowering the motor current of an idle axis in steps and telling the
+// HAL which peripherals it may gate to save power.
+
+// Key Features:
+// - Current limit ramped down from the running current, no torque step when entering standby.
+// - Hold with reduced current, the position loop keeps holding the axis.
+// - Sleep after a timeout: outputs off, PWM and current sense may be gated by the HAL.
+// - Fast wake: the driver stays calibrated, the loops restart at the present position.
+
+// Detailed Operation:
+// Entering standby starts a linear ramp of the current limit from the present limit to
+// `hold_current_ma` over `ramp_ms`. The position loop keeps running at that limit, so a stepper or a
+// loaded axis still holds its position with a fraction of the dissipation. After `sleep_ms` of hold
+// (0 - never) or straight after the ramp when the hold current is 0, the stage changes to sleep: the
+// outputs are switched off and `gates` returns the peripherals that are no longer needed. The
+// encoder is only offered for gating if configured, since without it position changes during sleep
+// are lost. Waking returns to the active stage immediately; no calibration data is touched.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Gate flag: PWM timer and gate driver may be switched off
+pub const GATE_PWM: u8 = 1 << 0;
+/// Gate flag: current sense ADC and amplifiers may be switched off
+pub const GATE_CURRENT_SENSE: u8 = 1 << 1;
+/// Gate flag: encoder supply and interface may be switched off
+pub const GATE_ENCODER: u8 = 1 << 2;
+
+/// Configuration of the standby mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandbyConfig {
+    /// Duration of the current ramp down (ms)
+    pub ramp_ms: u16,
+    /// Current limit while holding (mA), 0 - sleep after the ramp
+    pub hold_current_ma: i32,
+    /// Hold time before sleeping (ms), 0 - hold until woken
+    pub sleep_ms: u32,
+    /// Encoder may be gated in sleep (position changes are lost)
+    pub gate_encoder: bool,
+}
+
+impl StandbyConfig {
+    /// Creates a configuration ramping over 200 ms to a 300 mA hold, never sleeping.
+    pub const fn new() -> Self {
+        Self {
+            ramp_ms: 200,
+            hold_current_ma: 300,
+            sleep_ms: 0,
+            gate_encoder: false,
+        }
+    }
+}
+
+impl Default for StandbyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stage of the standby mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandbyStage {
+    /// Running at the full current limit
+    Active,
+    /// Current limit ramping down
+    Ramping,
+    /// Holding with the reduced current
+    Hold,
+    /// Outputs off, peripherals gateable
+    Sleep,
+}
+
+/// Standby of an idle axis.
+pub struct Standby {
+    frequency: u16, // Update frequency (ticks per second)
+    config: StandbyConfig,
+
+    stage: StandbyStage,
+    start: i32,  // Current limit when standby was entered (mA)
+    ticks: u32,  // Ticks in the present stage
+}
+
+impl Standby {
+    /// Creates an active (not idle) standby.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: StandbyConfig::new(),
+            stage: StandbyStage::Active,
+            start: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Applies a configuration, used from the next standby.
+    pub fn configure(&mut self, config: StandbyConfig) {
+        self.config = StandbyConfig {
+            hold_current_ma: config.hold_current_ma.max(0),
+            ..config
+        };
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> StandbyConfig {
+        self.config
+    }
+
+    /// Starts the ramp down, ignored if already idle.
+    pub fn enter(&mut self) {
+        if self.stage != StandbyStage::Active {
+            return;
+        }
+        self.stage = StandbyStage::Ramping;
+        self.start = -1; // Taken from the next tick
+        self.ticks = 0;
+        defmt::info!("STANDBY: Ramping down to {}mA", self.config.hold_current_ma);
+    }
+
+    /// Returns to the active stage. Returns false if not idle.
+    pub fn wake(&mut self) -> bool {
+        if self.stage == StandbyStage::Active {
+            return false;
+        }
+        self.stage = StandbyStage::Active;
+        defmt::info!("STANDBY: Wake");
+        true
+    }
+
+    /// Advances the standby and returns the current limit to apply (mA).
+    ///
+    /// # Arguments
+    /// * `current_limit` - Current limit of the running axis (mA)
+    pub fn tick(&mut self, current_limit: i32) -> i32 {
+        let hold = self.config.hold_current_ma.min(current_limit);
+        self.ticks = self.ticks.saturating_add(1);
+        match self.stage {
+            StandbyStage::Active => current_limit,
+            StandbyStage::Ramping => {
+                if self.start < 0 {
+                    self.start = current_limit;
+                }
+                let ramp = (self.config.ramp_ms as u32 * self.frequency as u32 / 1000).max(1);
+                if self.ticks >= ramp {
+                    self.advance(if hold > 0 { StandbyStage::Hold } else { StandbyStage::Sleep });
+                    return hold;
+                }
+                let drop = (self.start - hold) as i64 * self.ticks as i64 / ramp as i64;
+                self.start - drop as i32
+            }
+            StandbyStage::Hold => {
+                let sleep = self.config.sleep_ms.saturating_mul(self.frequency as u32) / 1000;
+                if self.config.sleep_ms != 0 && self.ticks >= sleep.max(1) {
+                    self.advance(StandbyStage::Sleep);
+                    return 0;
+                }
+                hold
+            }
+            StandbyStage::Sleep => 0,
+        }
+    }
+
+    /// Moves to the next stage.
+    fn advance(&mut self, stage: StandbyStage) {
+        self.stage = stage;
+        self.ticks = 0;
+        if stage == StandbyStage::Sleep {
+            defmt::info!("STANDBY: Sleep, outputs off");
+        }
+    }
+
+    /// Returns the stage.
+    pub fn stage(&self) -> StandbyStage {
+        self.stage
+    }
+
+    /// Returns true while not active.
+    pub fn is_idle(&self) -> bool {
+        self.stage != StandbyStage::Active
+    }
+
+    /// Returns true while the outputs are off.
+    pub fn is_sleeping(&self) -> bool {
+        self.stage == StandbyStage::Sleep
+    }
+
+    /// Returns the peripherals the HAL may gate (GATE_* flags).
+    pub fn gates(&self) -> u8 {
+        if self.stage != StandbyStage::Sleep {
+            return 0;
+        }
+        let encoder = if self.config.gate_encoder { GATE_ENCODER } else { 0 };
+        GATE_PWM | GATE_CURRENT_SENSE | encoder
+    }
+}
@@ -0,0 +1,120 @@
+// Implements the step/dir input, turning the step pulse counter of the HAL into a master position
+// the axis follows through the electronic gear, next to the internal motion commands.
+
+// Key Features:
+// - Wrapping 16-bit step counter extended to a 32-bit step position.
+// - Resolution in steps per motor revolution (full steps times microsteps), optional inversion.
+// - Gear ratio from the resolution: 65536 counts per revolution over the steps per revolution.
+// - Motion source selection between the external step input and the internal profiles.
+
+// Detailed Operation:
+// The HAL counts the step pulses in a timer clocked by the STEP input, the DIR input selecting the
+// counting direction, and passes the counter with every tick. The difference to the previous tick
+// (wrapping, so up to 32767 steps per tick) is added to the step position, which keeps counting in
+// every motion source: switching to the step input engages the gear at the present step position
+// and position setpoint, so steps received before don't move the axis. The gear then turns every
+// step into 65536 / resolution counts, keeping the division remainder, so no fraction of a step
+// is lost over long moves.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Counts per motor revolution the steps are scaled to
+const COUNTS_PER_REV: i32 = 65536;
+
+/// Source of the position setpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionSource {
+    /// Commands of the application or a host protocol (moves, velocity, torque)
+    Internal,
+    /// External step/dir input
+    StepDir,
+}
+
+/// Step/dir input configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepDirConfig {
+    /// Steps per motor revolution (full steps times microsteps)
+    pub steps_per_rev: u32,
+    /// Reverse the direction of motion
+    pub invert: bool,
+}
+
+impl StepDirConfig {
+    /// 200 full steps with 16 microsteps, not inverted.
+    pub const fn new() -> Self {
+        Self {
+            steps_per_rev: 3200,
+            invert: false,
+        }
+    }
+}
+
+impl Default for StepDirConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Step/dir input decoder.
+pub struct StepDir {
+    config: StepDirConfig,
+    count: u16,    // Step counter of the previous tick
+    started: bool, // Counter seen at least once
+    steps: i32,    // Step position since start
+}
+
+impl StepDir {
+    /// Creates the decoder with the default resolution.
+    pub const fn new() -> Self {
+        Self {
+            config: StepDirConfig::new(),
+            count: 0,
+            started: false,
+            steps: 0,
+        }
+    }
+
+    /// Sets resolution and direction, a resolution of 0 is raised to 1.
+    pub fn configure(&mut self, config: StepDirConfig) {
+        self.config = StepDirConfig {
+            steps_per_rev: config.steps_per_rev.max(1),
+            ..config
+        };
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> StepDirConfig {
+        self.config
+    }
+
+    /// Updates the step position from the step counter of the HAL, returns it.
+    pub fn tick(&mut self, count: u16) -> i32 {
+        if !self.started {
+            // Steps counted before the first tick belong to no known position
+            self.started = true;
+            self.count = count;
+        }
+        let delta = count.wrapping_sub(self.count) as i16;
+        self.count = count;
+        self.steps = self.steps.wrapping_add(delta as i32);
+        self.steps
+    }
+
+    /// Returns the step position since start.
+    pub fn steps(&self) -> i32 {
+        self.steps
+    }
+
+    /// Returns the gear ratio (counts, steps) turning steps into encoder counts.
+    pub fn ratio(&self) -> (i32, i32) {
+        let counts = if self.config.invert { -COUNTS_PER_REV } else { COUNTS_PER_REV };
+        (counts, self.config.steps_per_rev.min(i32::MAX as u32) as i32)
+    }
+}
+
+impl Default for StepDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,132 @@
+// Implements the tick timer, measuring the actual period between control ticks from a free-running
+// cycle counter and reporting the jitter of the control interrupt.
+
+// Key Features:
+// - Period from the wrapping difference of two timestamps (any 32-bit cycle counter, e.g. DWT).
+// - Period ratio to the nominal tick (Q16) for integrators and the velocity estimate.
+// - Shortest, longest and average period and the peak deviation for timing diagnostics.
+// - Ratio limited to 1/4..4 of the nominal period, so a missed or doubled stamp can't upset the loops.
+
+// Detailed Operation:
+// The HAL stamps each input snapshot with the cycle counter when the sample was taken. The period is
+// the wrapping difference to the previous stamp, compared with the nominal clock / frequency cycles.
+// Interrupt latency, a higher priority ISR or a sample triggered late makes single periods longer
+// and the next one shorter; the loops integrate over time, so they use the measured ratio instead of
+// the nominal period. Without a configured clock (0) the timer reports the nominal ratio. The
+// average is a first-order filter over 2^JITTER_AVG_SHIFT ticks.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Averaging window of the mean period (2^n ticks)
+const JITTER_AVG_SHIFT: u32 = 8;
+/// Nominal period ratio (Q16)
+const NOMINAL: i32 = 1 << 16;
+
+/// Measured timing of the control ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickTiming {
+    /// Nominal period (cycles), 0 - no clock configured
+    pub nominal: u32,
+    /// Last measured period (cycles)
+    pub period: u32,
+    /// Shortest period since the last reset (cycles)
+    pub min: u32,
+    /// Longest period since the last reset (cycles)
+    pub max: u32,
+    /// Average period (cycles)
+    pub average: u32,
+    /// Largest |period - nominal| since the last reset (cycles)
+    pub jitter: u32,
+}
+
+/// Period measurement of the control ticks.
+pub struct TickTimer {
+    started: bool,  // A timestamp was seen
+    last: u32,      // Timestamp of the previous tick
+    average: i64,   // Filtered period (cycles, JITTER_AVG_SHIFT fractional bits)
+    ratio: i32,     // Last period relative to the nominal one (Q16)
+    timing: TickTiming,
+}
+
+impl TickTimer {
+    /// Creates a timer without a clock (nominal periods).
+    pub fn new() -> Self {
+        Self {
+            started: false,
+            last: 0,
+            average: 0,
+            ratio: NOMINAL,
+            timing: TickTiming {
+                nominal: 0,
+                period: 0,
+                min: 0,
+                max: 0,
+                average: 0,
+                jitter: 0,
+            },
+        }
+    }
+
+    /// Sets the clock of the timestamps, 0 ignores them.
+    ///
+    /// # Arguments
+    /// * `clock` - Timestamp counts per second
+    /// * `frequency` - Number of ticks per second
+    pub fn configure(&mut self, clock: u32, frequency: u16) {
+        self.timing.nominal = clock / frequency.max(1) as u32;
+        self.average = (self.timing.nominal as i64) << JITTER_AVG_SHIFT;
+        self.started = false;
+        self.ratio = NOMINAL;
+        self.reset_stats();
+    }
+
+    /// Measures the period ending with `timestamp` and returns its ratio to the nominal one (Q16).
+    pub fn tick(&mut self, timestamp: u32) -> i32 {
+        let nominal = self.timing.nominal;
+        if nominal == 0 {
+            return NOMINAL;
+        }
+        if !self.started {
+            self.started = true;
+            self.last = timestamp;
+            return NOMINAL;
+        }
+        let period = timestamp.wrapping_sub(self.last);
+        self.last = timestamp;
+
+        self.timing.period = period;
+        self.timing.min = self.timing.min.min(period);
+        self.timing.max = self.timing.max.max(period);
+        self.timing.jitter = self.timing.jitter.max(period.abs_diff(nominal));
+        self.average += period as i64 - (self.average >> JITTER_AVG_SHIFT);
+        self.timing.average = (self.average >> JITTER_AVG_SHIFT) as u32;
+
+        let ratio = ((period as i64) << 16) / nominal as i64;
+        self.ratio = ratio.clamp((NOMINAL / 4) as i64, (NOMINAL * 4) as i64) as i32;
+        self.ratio
+    }
+
+    /// Returns the last period relative to the nominal one (Q16).
+    pub fn ratio(&self) -> i32 {
+        self.ratio
+    }
+
+    /// Returns the measured timing.
+    pub fn timing(&self) -> TickTiming {
+        self.timing
+    }
+
+    /// Restarts the shortest / longest period and the peak jitter.
+    pub fn reset_stats(&mut self) {
+        self.timing.min = u32::MAX;
+        self.timing.max = 0;
+        self.timing.jitter = 0;
+    }
+}
+
+impl Default for TickTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
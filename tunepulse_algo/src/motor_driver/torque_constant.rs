@@ -0,0 +1,250 @@
+// Implements the torque constant identification, spinning the motor at a constant speed and
+// relating the back-EMF to the speed (Ke), from which the torque constant (Kt) follows.
+
+// Key Features:
+// - Velocity-mode spin at a configured speed, settle time before measuring.
+// - Back-EMF as the applied voltage vector minus the resistive drop of the measured current.
+// - Ke in mV per rad/s, Kt in mN·m/A (three-phase: 3/2 * Ke for amplitude-invariant vectors).
+// - Alternative Kt from the identified acceleration per ampere and a known inertia.
+
+// Detailed Operation:
+// At constant speed the winding equation is U = R * I + E (the inductive drops vanish with a constant
+// current, the cross-coupling omega * L * I stays small for the low current of a free spin). The
+// back-EMF E = U - R * I is averaged in magnitude over `measure_ms` together with the speed, then
+// Ke = |E| / omega with omega = velocity * 2 pi / 65536 rad/s. A permanent-magnet motor's torque
+// constant equals its back-EMF constant in SI units per phase; with amplitude-invariant alpha/beta
+// vectors a three-phase motor produces 3/2 of it, a two-phase stepper exactly it. The routine fails
+// if the speed is not reached within `timeout_ms` or the back-EMF is too small to measure. With a
+// known inertia J, the acceleration per ampere of the mechanical identification gives Kt = J * a / I
+// without spinning at speed.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::sqrt::magnitude;
+
+/// 2 pi in Q16
+const TWO_PI_Q16: i64 = 411_775;
+/// Smallest averaged back-EMF accepted (mV)
+const MIN_BACK_EMF_MV: i64 = 50;
+
+/// Settings of the torque constant identification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KtIdentConfig {
+    /// Spin speed (counts/s)
+    pub speed: i32,
+    /// Time for the speed to settle (ms)
+    pub settle_ms: u16,
+    /// Measuring time (ms)
+    pub measure_ms: u16,
+    /// Abort if the speed isn't reached within (ms)
+    pub timeout_ms: u16,
+}
+
+impl KtIdentConfig {
+    /// Creates a configuration spinning at 5 rev/s, 0.5 s settle and 1 s measuring.
+    pub const fn new() -> Self {
+        Self {
+            speed: 5 << 16,
+            settle_ms: 500,
+            measure_ms: 1000,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+impl Default for KtIdentConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Progress of the torque constant identification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KtIdentStage {
+    /// Not running
+    Idle,
+    /// Accelerating to the spin speed
+    Settling,
+    /// Averaging back-EMF and speed
+    Measuring,
+    /// Constants identified
+    Done,
+    /// Speed not reached or no measurable back-EMF
+    Failed,
+}
+
+/// Identified motor constants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KtReport {
+    /// Back-EMF constant (mV per rad/s)
+    pub ke: i32,
+    /// Torque constant (mN·m/A)
+    pub kt: i32,
+    /// Averaged back-EMF (mV)
+    pub back_emf_mv: i32,
+    /// Averaged speed (counts/s)
+    pub speed: i32,
+}
+
+/// Back-EMF based torque constant identification.
+pub struct KtIdent {
+    frequency: u16, // Update frequency (ticks per second)
+    config: KtIdentConfig,
+    stage: KtIdentStage,
+
+    ticks: u32,   // Ticks in the present stage
+    steady: u32,  // Consecutive ticks within 10% of the speed
+    sum_emf: i64, // Σ |E| (mV)
+    sum_vel: i64, // Σ |velocity| (counts/s)
+    samples: u32,
+    report: KtReport,
+}
+
+impl KtIdent {
+    /// Creates an idle identification.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: KtIdentConfig::new(),
+            stage: KtIdentStage::Idle,
+            ticks: 0,
+            steady: 0,
+            sum_emf: 0,
+            sum_vel: 0,
+            samples: 0,
+            report: KtReport::default(),
+        }
+    }
+
+    /// Starts the identification, the caller spins the motor at `config.speed`.
+    pub fn start(&mut self, config: KtIdentConfig) {
+        self.config = config;
+        self.stage = KtIdentStage::Settling;
+        self.ticks = 0;
+        self.steady = 0;
+        self.sum_emf = 0;
+        self.sum_vel = 0;
+        self.samples = 0;
+        defmt::info!("KT IDENT: Spinning at {} counts/s", config.speed);
+    }
+
+    /// Stops the identification without a result.
+    pub fn cancel(&mut self) {
+        if self.is_active() {
+            self.stage = KtIdentStage::Idle;
+        }
+    }
+
+    /// Advances the identification.
+    ///
+    /// # Arguments
+    /// * `voltage` - Applied alpha/beta voltage (mV)
+    /// * `current` - Measured alpha/beta current (mA)
+    /// * `velocity` - Measured velocity (counts/s)
+    /// * `resistance` - Winding resistance (mOhm)
+    /// * `phases` - Motor phases (3 - BLDC, 2 - stepper)
+    ///
+    /// Returns true on the tick the identification finishes (done or failed).
+    pub fn tick(
+        &mut self,
+        voltage: (i32, i32),
+        current: (i32, i32),
+        velocity: i32,
+        resistance: i32,
+        phases: u8,
+    ) -> bool {
+        let ms = |ms: u16| (ms as u32 * self.frequency as u32 / 1000).max(1);
+        self.ticks += 1;
+        match self.stage {
+            KtIdentStage::Settling => {
+                let error = (velocity as i64 - self.config.speed as i64).abs();
+                let steady = error * 10 <= (self.config.speed as i64).abs();
+                self.steady = if steady { self.steady + 1 } else { 0 };
+                if self.steady >= ms(self.config.settle_ms) {
+                    self.stage = KtIdentStage::Measuring;
+                    self.ticks = 0;
+                } else if self.ticks >= ms(self.config.timeout_ms) {
+                    return self.fail("speed not reached");
+                }
+                false
+            }
+            KtIdentStage::Measuring => {
+                let drop = |i: i32| (i as i64 * resistance as i64 / 1000) as i32; // mA * mOhm -> mV
+                let emf = (voltage.0 - drop(current.0), voltage.1 - drop(current.1));
+                self.sum_emf += magnitude(emf.0, emf.1) as i64;
+                self.sum_vel += (velocity as i64).abs();
+                self.samples += 1;
+                if self.ticks < ms(self.config.measure_ms) {
+                    return false;
+                }
+                self.finish(phases)
+            }
+            _ => false,
+        }
+    }
+
+    /// Computes the constants from the averages.
+    fn finish(&mut self, phases: u8) -> bool {
+        let samples = self.samples.max(1) as i64;
+        let emf = self.sum_emf / samples;
+        let speed = self.sum_vel / samples;
+        if emf < MIN_BACK_EMF_MV || speed == 0 {
+            return self.fail("back-EMF too small");
+        }
+        // omega = speed * 2 pi / 65536 rad/s, Ke = E / omega
+        let ke = emf * 65536 * 65536 / (speed * TWO_PI_Q16);
+        let kt = if phases == 3 { ke * 3 / 2 } else { ke };
+        self.report = KtReport {
+            ke: ke as i32,
+            kt: kt as i32,
+            back_emf_mv: emf as i32,
+            speed: speed as i32,
+        };
+        self.stage = KtIdentStage::Done;
+        defmt::info!(
+            "KT IDENT: Ke {} mV/(rad/s), Kt {} mNm/A",
+            self.report.ke,
+            self.report.kt
+        );
+        true
+    }
+
+    /// Ends the identification as failed.
+    fn fail(&mut self, reason: &str) -> bool {
+        self.stage = KtIdentStage::Failed;
+        defmt::warn!("KT IDENT: Failed, {}", reason);
+        true
+    }
+
+    /// Returns the stage.
+    pub fn stage(&self) -> KtIdentStage {
+        self.stage
+    }
+
+    /// Returns true while spinning.
+    pub fn is_active(&self) -> bool {
+        matches!(self.stage, KtIdentStage::Settling | KtIdentStage::Measuring)
+    }
+
+    /// Returns the identified constants.
+    pub fn report(&self) -> KtReport {
+        self.report
+    }
+}
+
+/// Torque constant (mN·m/A) from the acceleration per ampere and the inertia.
+///
+/// # Arguments
+/// * `accel_per_amp` - Acceleration per ampere (counts/s^2 per A), see `MechanicsReport`
+/// * `inertia` - Rotor and load inertia (g·cm², 10^-7 kg·m²)
+pub fn kt_from_inertia(accel_per_amp: i32, inertia: i32) -> i32 {
+    // Kt = J[kg m^2] * a[rad/s^2 per A] * 1000 = J[g cm^2] * a[counts] * 2 pi / 65536 / 10^4
+    let kt = inertia as i64 * accel_per_amp as i64 * TWO_PI_Q16 / (65536 * 65536 * 10_000);
+    kt.clamp(0, i32::MAX as i64) as i32
+}
@@ -0,0 +1,205 @@
+// Implements the travel limits module, keeping the axis inside soft position limits and
+// stopping it at limit switches without raising a hard fault.
+
+// Key Features:
+// - Configurable soft position limits (minimum and maximum, encoder counts from zero).
+// - Two limit switch inputs (negative and positive end of travel).
+// - Braking-distance based velocity window so the axis decelerates before reaching a limit.
+// - Latched flags reporting which limit was reached until explicitly cleared.
+
+// Detailed Operation:
+// Every tick the module computes the range of velocities the cascade is allowed to command.
+// For a soft limit at distance `d` with deceleration `a`, the fastest velocity that can still stop
+// in time is sqrt(2 * a * d), so the window shrinks smoothly to zero while approaching the limit.
+// A pressed switch or a crossed soft limit closes the window in that direction only, so the axis is
+// always free to move back. Each event sets a latched flag which stays set after the axis leaves the
+// limit, allowing the application to notice short contacts.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::math_integer::sqrt::isqrt;
+
+/// Latched flag: soft minimum position reached
+pub const LIMIT_SOFT_MIN: u8 = 1 << 0;
+/// Latched flag: soft maximum position reached
+pub const LIMIT_SOFT_MAX: u8 = 1 << 1;
+/// Latched flag: negative end switch triggered
+pub const LIMIT_SWITCH_MIN: u8 = 1 << 2;
+/// Latched flag: positive end switch triggered
+pub const LIMIT_SWITCH_MAX: u8 = 1 << 3;
+
+/// Switch input bit of the negative end (matches `DataInputs::limit_sw`)
+const SWITCH_MIN_BIT: u8 = 1 << 0;
+/// Switch input bit of the positive end (matches `DataInputs::limit_sw`)
+const SWITCH_MAX_BIT: u8 = 1 << 1;
+
+/// Soft limits and limit switch handling.
+pub struct TravelLimits {
+    soft_enabled: bool, // Soft limits are active
+    soft_min: i32,      // Minimum allowed position (counts)
+    soft_max: i32,      // Maximum allowed position (counts)
+    decel: i32,         // Deceleration used to approach a limit (counts/s^2)
+    switch_mask: u8,    // Enabled switch inputs
+    flags: u8,          // Latched limit flags
+}
+
+impl TravelLimits {
+    /// Creates limits with soft limits disabled and both switch inputs enabled.
+    pub fn new() -> Self {
+        Self {
+            soft_enabled: false,
+            soft_min: i32::MIN,
+            soft_max: i32::MAX,
+            decel: 1 << 20, // 16 rev/s^2
+            switch_mask: SWITCH_MIN_BIT | SWITCH_MAX_BIT,
+            flags: 0,
+        }
+    }
+
+    /// Computes the allowed velocity window.
+    ///
+    /// # Arguments
+    /// * `position` - Current position (counts from zero)
+    /// * `switches` - Limit switch states (bit 0 - negative end, bit 1 - positive end)
+    ///
+    /// Returns (minimum, maximum) allowed velocity in counts/s.
+    pub fn tick(&mut self, position: i32, switches: u8) -> (i32, i32) {
+        let mut vel_min = i32::MIN;
+        let mut vel_max = i32::MAX;
+
+        // ######################## SOFT LIMITS ######################################
+        if self.soft_enabled {
+            let to_max = self.soft_max as i64 - position as i64;
+            let to_min = position as i64 - self.soft_min as i64;
+            vel_max = self.braking_velocity(to_max);
+            vel_min = -self.braking_velocity(to_min);
+            if to_max <= 0 {
+                self.flags |= LIMIT_SOFT_MAX;
+            }
+            if to_min <= 0 {
+                self.flags |= LIMIT_SOFT_MIN;
+            }
+        }
+
+        // ######################## LIMIT SWITCHES ###################################
+        let switches = switches & self.switch_mask;
+        if switches & SWITCH_MAX_BIT != 0 {
+            vel_max = 0;
+            self.flags |= LIMIT_SWITCH_MAX;
+        }
+        if switches & SWITCH_MIN_BIT != 0 {
+            vel_min = 0;
+            self.flags |= LIMIT_SWITCH_MIN;
+        }
+
+        (vel_min, vel_max)
+    }
+
+    /// Sets and enables soft limits (counts from zero).
+    pub fn set_soft_limits(&mut self, min: i32, max: i32) {
+        self.soft_min = min.min(max);
+        self.soft_max = max.max(min);
+        self.soft_enabled = true;
+    }
+
+    /// Disables soft limits.
+    pub fn disable_soft_limits(&mut self) {
+        self.soft_enabled = false;
+    }
+
+    /// Sets the deceleration used when approaching a soft limit (counts/s^2).
+    pub fn set_decel(&mut self, decel: i32) {
+        self.decel = decel.max(1);
+    }
+
+    /// Enables or disables the switch inputs (bit 0 - negative end, bit 1 - positive end).
+    pub fn set_switch_mask(&mut self, mask: u8) {
+        self.switch_mask = mask & (SWITCH_MIN_BIT | SWITCH_MAX_BIT);
+    }
+
+    /// Returns latched limit flags.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Clears latched limit flags.
+    pub fn clear_flags(&mut self) {
+        self.flags = 0;
+    }
+
+    /// Maximum velocity that still allows stopping within `distance`: sqrt(2 * a * d).
+    #[inline(always)]
+    fn braking_velocity(&self, distance: i64) -> i32 {
+        if distance <= 0 {
+            return 0;
+        }
+        let v2 = (2 * self.decel as i64).saturating_mul(distance) as u64;
+        isqrt(v2).min(i32::MAX as u64) as i32
+    }
+}
+
+impl Default for TravelLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_close_one_direction_and_latch() {
+        let mut limits = TravelLimits::new();
+        assert_eq!(limits.tick(0, 0), (i32::MIN, i32::MAX)); // No soft limits by default
+        assert_eq!(limits.tick(0, SWITCH_MAX_BIT), (i32::MIN, 0)); // Free to move back
+        assert_eq!(limits.tick(0, SWITCH_MIN_BIT), (0, i32::MAX));
+        assert_eq!(limits.tick(0, 0), (i32::MIN, i32::MAX));
+        assert_eq!(limits.flags(), LIMIT_SWITCH_MAX | LIMIT_SWITCH_MIN); // Short contacts stay visible
+        limits.clear_flags();
+        assert_eq!(limits.flags(), 0);
+
+        limits.set_switch_mask(SWITCH_MIN_BIT);
+        assert_eq!(limits.tick(0, SWITCH_MIN_BIT | SWITCH_MAX_BIT), (0, i32::MAX)); // Masked input ignored
+        assert_eq!(limits.flags(), LIMIT_SWITCH_MIN);
+    }
+
+    #[test]
+    fn soft_limits_follow_the_braking_distance() {
+        let mut limits = TravelLimits::new();
+        limits.set_soft_limits(10_000, -10_000); // Swapped pair
+        limits.set_decel(1_000_000);
+        // v = sqrt(2 * a * d) towards each limit
+        assert_eq!(limits.tick(0, 0), (-141_421, 141_421));
+        assert_eq!(limits.tick(9_950, 0), (-199_749, 10_000));
+        assert_eq!(limits.flags(), 0);
+        assert_eq!(limits.tick(10_000, 0), (-200_000, 0));
+        assert_eq!(limits.tick(12_000, 0).1, 0); // Beyond the limit: no further, back allowed
+        assert!(limits.tick(12_000, 0).0 < 0);
+        assert_eq!(limits.flags(), LIMIT_SOFT_MAX);
+        assert_eq!(limits.tick(-10_000, 0).0, 0);
+        assert_eq!(limits.flags(), LIMIT_SOFT_MAX | LIMIT_SOFT_MIN);
+
+        limits.disable_soft_limits();
+        assert_eq!(limits.tick(20_000, 0), (i32::MIN, i32::MAX));
+    }
+
+    #[test]
+    fn axis_at_the_window_stops_at_the_limit() {
+        const FREQUENCY: i64 = 20_000;
+        let mut limits = TravelLimits::new();
+        limits.set_soft_limits(-100_000, 100_000);
+        limits.set_decel(2_000_000);
+        // Axis commanded far too fast, always clamped into the window
+        let (mut position, mut remainder) = (0i64, 0i64);
+        for _ in 0..FREQUENCY {
+            let (_, vel_max) = limits.tick(position as i32, 0);
+            let velocity = 5_000_000.min(vel_max as i64);
+            remainder += velocity;
+            position += remainder / FREQUENCY;
+            remainder %= FREQUENCY;
+        }
+        assert!((99_900..=100_000).contains(&position), "stopped at {}", position);
+    }
+}
@@ -0,0 +1,235 @@
+// Implements the control watchdog, putting the power stage into a safe state when the control ticks
+// stall or the host stops sending setpoints.
+
+// Key Features:
+// - Tick stall detection from an independent heartbeat (e.g. SysTick), which still runs when the
+//   control interrupt is blocked or no longer triggered.
+// - Setpoint timeout: the host has to feed the watchdog, a silent link stops the axis.
+// - Safe state selectable: coast (all outputs floating), active brake (windings shorted) or a
+//   ramp to standstill when the setpoints time out.
+// - Trip reason latched until the fault is reset.
+
+// Detailed Operation:
+// The control tick counts itself. The HAL calls `heartbeat` from another time base with the
+// milliseconds since its previous call; if the tick count didn't change, the stall time grows and at
+// `tick_timeout_ms` the watchdog trips and hands the safe outputs to the HAL, which has to write them
+// to the timer itself because the control tick no longer does. The setpoint timeout counts control
+// ticks since the last `feed`; a host that streams setpoints feeds with every message, so a broken
+// link, a crashed host or a hung bus stops the axis after `setpoint_timeout_ms`. Coasting disables all
+// half bridges (i16::MIN, high impedance), the motor runs out on friction. Braking switches all low
+// sides on (duty 0): the windings are shorted, back-EMF drives a braking current limited only by
+// the winding resistance, so it stops faster but loads the motor at high speed.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use super::safe_stop::StopMode;
+
+/// Reason of a watchdog trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogTrip {
+    /// Watchdog not tripped
+    None,
+    /// Control ticks stopped
+    TickStall,
+    /// No setpoint feed within the timeout
+    SetpointTimeout,
+}
+
+/// Configuration of the control watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// Time without control ticks before tripping (ms), 0 disables the check
+    pub tick_timeout_ms: u16,
+    /// Time without a setpoint feed before tripping (ms), 0 disables the check
+    pub setpoint_timeout_ms: u16,
+    /// Safe state of the outputs
+    pub action: StopMode,
+}
+
+impl WatchdogConfig {
+    /// Creates a configuration with both checks disabled, coasting when enabled.
+    pub const fn new() -> Self {
+        Self {
+            tick_timeout_ms: 0,
+            setpoint_timeout_ms: 0,
+            action: StopMode::Coast,
+        }
+    }
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Supervision of the control ticks and of the setpoint link.
+pub struct Watchdog {
+    frequency: u16, // Update frequency (ticks per second)
+    config: WatchdogConfig,
+
+    ticks: u32,      // Control ticks, wrapping
+    seen: u32,       // Tick count at the previous heartbeat
+    stalled_ms: u32, // Time since the tick count last changed
+    since_feed: u32, // Control ticks since the last setpoint feed
+    trip: WatchdogTrip,
+}
+
+impl Watchdog {
+    /// Creates a disabled watchdog.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            config: WatchdogConfig::new(),
+            ticks: 0,
+            seen: 0,
+            stalled_ms: 0,
+            since_feed: 0,
+            trip: WatchdogTrip::None,
+        }
+    }
+
+    /// Applies a configuration, the setpoint timeout starts counting now.
+    pub fn configure(&mut self, config: WatchdogConfig) {
+        self.config = config;
+        self.stalled_ms = 0;
+        self.since_feed = 0;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> WatchdogConfig {
+        self.config
+    }
+
+    /// Restarts the setpoint timeout, called for every setpoint of the host.
+    pub fn feed(&mut self) {
+        self.since_feed = 0;
+    }
+
+    /// Counts a control tick and checks the setpoint timeout.
+    ///
+    /// Returns true on the tick the watchdog trips.
+    pub fn tick(&mut self) -> bool {
+        self.ticks = self.ticks.wrapping_add(1);
+        self.since_feed = self.since_feed.saturating_add(1);
+        let timeout = self.config.setpoint_timeout_ms as u32 * self.frequency as u32 / 1000;
+        if self.trip != WatchdogTrip::None || self.config.setpoint_timeout_ms == 0 {
+            return false;
+        }
+        if self.since_feed > timeout.max(1) {
+            return self.raise(WatchdogTrip::SetpointTimeout);
+        }
+        false
+    }
+
+    /// Checks the control ticks from an independent time base.
+    ///
+    /// # Arguments
+    /// * `elapsed_ms` - Time since the previous heartbeat (ms)
+    ///
+    /// Returns true on the heartbeat the watchdog trips.
+    pub fn heartbeat(&mut self, elapsed_ms: u16) -> bool {
+        if self.ticks != self.seen {
+            self.seen = self.ticks;
+            self.stalled_ms = 0;
+            return false;
+        }
+        self.stalled_ms = self.stalled_ms.saturating_add(elapsed_ms as u32);
+        if self.trip != WatchdogTrip::None || self.config.tick_timeout_ms == 0 {
+            return false;
+        }
+        if self.stalled_ms >= self.config.tick_timeout_ms as u32 {
+            return self.raise(WatchdogTrip::TickStall);
+        }
+        false
+    }
+
+    /// Latches a trip.
+    fn raise(&mut self, trip: WatchdogTrip) -> bool {
+        self.trip = trip;
+        defmt::error!("WATCHDOG: Tripped ({}), outputs to safe state", trip as u8);
+        true
+    }
+
+    /// Clears a latched trip, the setpoint timeout starts counting now.
+    pub fn clear(&mut self) {
+        self.trip = WatchdogTrip::None;
+        self.stalled_ms = 0;
+        self.since_feed = 0;
+    }
+
+    /// Returns true while a trip is latched.
+    pub fn is_tripped(&self) -> bool {
+        self.trip != WatchdogTrip::None
+    }
+
+    /// Returns the reason of the latched trip.
+    pub fn trip(&self) -> WatchdogTrip {
+        self.trip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured(tick_timeout_ms: u16, setpoint_timeout_ms: u16) -> Watchdog {
+        let mut watchdog = Watchdog::new(20000);
+        watchdog.configure(WatchdogConfig { tick_timeout_ms, setpoint_timeout_ms, action: StopMode::Ramp });
+        watchdog
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut watchdog = Watchdog::new(20000);
+        assert!((0..100_000).all(|_| !watchdog.tick()));
+        assert!((0..100).all(|_| !watchdog.heartbeat(100)));
+        assert_eq!(watchdog.trip(), WatchdogTrip::None);
+    }
+
+    #[test]
+    fn setpoint_timeout_trips_once() {
+        let mut watchdog = configured(0, 10); // 200 ticks
+        for _ in 0..1000 {
+            for _ in 0..150 {
+                assert!(!watchdog.tick());
+            }
+            watchdog.feed(); // Host keeps sending
+        }
+        assert!((0..200).all(|_| !watchdog.tick()));
+        assert!(watchdog.tick());
+        assert!((0..1000).all(|_| !watchdog.tick())); // Latched, raised once
+        assert_eq!((watchdog.is_tripped(), watchdog.trip()), (true, WatchdogTrip::SetpointTimeout));
+        assert_eq!(watchdog.config().action, StopMode::Ramp);
+
+        watchdog.clear();
+        assert!(!watchdog.is_tripped());
+        assert!((0..200).all(|_| !watchdog.tick())); // Counting starts over at the reset
+        assert!(watchdog.tick());
+    }
+
+    #[test]
+    fn stalled_ticks_trip_the_heartbeat() {
+        let mut watchdog = configured(5, 0);
+        for _ in 0..100 {
+            watchdog.tick();
+            assert!(!watchdog.heartbeat(1)); // Ticks running
+        }
+        assert!((0..4).all(|_| !watchdog.heartbeat(1)));
+        assert!(watchdog.heartbeat(1));
+        assert_eq!(watchdog.trip(), WatchdogTrip::TickStall);
+        assert!(!watchdog.heartbeat(1));
+
+        // A single late heartbeat covers the whole stall
+        let mut watchdog = configured(5, 0);
+        watchdog.tick();
+        assert!(!watchdog.heartbeat(1));
+        assert!(watchdog.heartbeat(50));
+    }
+}
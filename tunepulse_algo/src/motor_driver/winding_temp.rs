@@ -0,0 +1,220 @@
+// Implements the winding temperature estimate, measuring the winding resistance at low speed and
+// converting its rise over the cold value into a copper temperature for thermal protection.
+
+// Key Features:
+// - Resistance from applied voltage and measured current vectors, least squares over a window.
+// - Only sampled at low speed and sufficient current, where back-EMF and inductance don't matter.
+// - Copper temperature coefficient (0.393 %/°C) relative to a reference resistance and temperature.
+// - Current derating between a warning and a limit temperature, fault above the limit.
+
+// Detailed Operation:
+// At standstill or low speed the winding is a resistance: U = R * I. Every qualifying tick adds
+// U·I and I·I (dot products of the alpha/beta vectors) to the window sums, at the end of the window R
+// = Σ(U·I) / Σ(I·I), the least-squares fit that ignores current sense noise orthogonal to the voltage.
+// Each window estimate is filtered (1/4 per window) since one window only sees a part of the
+// ripple. Copper resistance rises by ALPHA_PPM per °C, so the temperature is
+// T = T_ref + (R / R_ref - 1) / alpha. Windows at high speed or low current are discarded and the
+// last estimate is held. Between `warn_c` and `max_c` the current limit is scaled down linearly to
+// DERATE_FLOOR_PERCENT, at `max_c` the estimate trips. The voltage is the one the driver applied, so
+// dead time and switch drops add to the result; a reference taken on the cold motor with the same
+// driver cancels them.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+/// Temperature coefficient of copper (ppm/°C)
+const ALPHA_PPM: i64 = 3930;
+/// Current limit at the limit temperature (% of the running limit)
+const DERATE_FLOOR_PERCENT: i64 = 20;
+
+/// Configuration of the winding temperature estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindingTempConfig {
+    /// Winding resistance at the reference temperature (mOhm), 0 - the driver resistance
+    pub ref_resistance: i32,
+    /// Reference temperature (°C)
+    pub ref_temp_c: i16,
+    /// Temperature where the derating starts (°C)
+    pub warn_c: i16,
+    /// Temperature limit, faults above it (°C)
+    pub max_c: i16,
+    /// Window of one resistance estimate (ms)
+    pub window_ms: u16,
+    /// Smallest current amplitude sampled (mA)
+    pub min_current_ma: i32,
+    /// Highest speed sampled (counts/s)
+    pub max_speed: i32,
+}
+
+impl WindingTempConfig {
+    /// Creates a configuration for a class F winding referenced at 25 °C.
+    pub const fn new() -> Self {
+        Self {
+            ref_resistance: 0,
+            ref_temp_c: 25,
+            warn_c: 110,
+            max_c: 140,
+            window_ms: 100,
+            min_current_ma: 300,
+            max_speed: 1 << 14, // 1/4 rev/s
+        }
+    }
+}
+
+impl Default for WindingTempConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Winding temperature from the resistance rise.
+pub struct WindingTemp {
+    frequency: u16, // Update frequency (ticks per second)
+    config: WindingTempConfig,
+    enabled: bool,
+
+    sum_ui: i64,      // Σ U·I of the window (mV·mA)
+    sum_ii: i64,      // Σ I·I of the window (mA²)
+    ticks: u32,       // Ticks of the window
+    resistance: i32,  // Filtered estimate (mOhm), 0 - none yet
+    temperature: i32, // Estimated temperature (0.1 °C)
+    tripped: bool,    // Over the limit temperature
+}
+
+impl WindingTemp {
+    /// Creates a disabled estimate.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        let config = WindingTempConfig::new();
+        Self {
+            frequency,
+            config,
+            enabled: false,
+            sum_ui: 0,
+            sum_ii: 0,
+            ticks: 0,
+            resistance: 0,
+            temperature: config.ref_temp_c as i32 * 10,
+            tripped: false,
+        }
+    }
+
+    /// Applies a configuration and enables the estimate, which starts over from the reference.
+    ///
+    /// # Arguments
+    /// * `config` - Estimate settings
+    /// * `resistance` - Driver resistance (mOhm), used without a reference resistance
+    pub fn configure(&mut self, config: WindingTempConfig, resistance: i32) {
+        let ref_resistance = if config.ref_resistance > 0 { config.ref_resistance } else { resistance };
+        self.config = WindingTempConfig { ref_resistance: ref_resistance.max(1), ..config };
+        self.enabled = true;
+        self.sum_ui = 0;
+        self.sum_ii = 0;
+        self.ticks = 0;
+        self.resistance = 0;
+        self.temperature = config.ref_temp_c as i32 * 10;
+    }
+
+    /// Stops the estimate, the derating and the fault.
+    pub fn disable(&mut self) {
+        self.enabled = false;
+        self.tripped = false;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> WindingTempConfig {
+        self.config
+    }
+
+    /// Returns true while the estimate runs.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Samples one tick.
+    ///
+    /// # Arguments
+    /// * `voltage` - Applied alpha/beta voltage (mV)
+    /// * `current` - Measured alpha/beta current (mA)
+    /// * `velocity` - Measured velocity (counts/s)
+    ///
+    /// Returns true on the tick the limit temperature trips.
+    pub fn tick(&mut self, voltage: (i32, i32), current: (i32, i32), velocity: i32) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let ii = current.0 as i64 * current.0 as i64 + current.1 as i64 * current.1 as i64;
+        let min = self.config.min_current_ma as i64;
+        if velocity.saturating_abs() <= self.config.max_speed && ii >= min * min {
+            self.sum_ui += voltage.0 as i64 * current.0 as i64 + voltage.1 as i64 * current.1 as i64;
+            self.sum_ii += ii;
+        }
+        self.ticks += 1;
+        let window = self.config.window_ms as u32 * self.frequency as u32 / 1000;
+        if self.ticks < window.max(1) {
+            return false;
+        }
+
+        // ####### Window estimate #######
+        let samples = self.sum_ii;
+        let estimate = if samples > 0 { (self.sum_ui * 1000 / samples) as i32 } else { 0 };
+        self.sum_ui = 0;
+        self.sum_ii = 0;
+        self.ticks = 0;
+        if estimate <= 0 {
+            return false; // Nothing sampled, hold the last estimate
+        }
+        self.resistance = if self.resistance == 0 {
+            estimate
+        } else {
+            self.resistance + (estimate - self.resistance) / 4
+        };
+        let ref_resistance = self.config.ref_resistance as i64;
+        let rise = (self.resistance as i64 - ref_resistance) * 10_000_000 / (ref_resistance * ALPHA_PPM);
+        self.temperature = self.config.ref_temp_c as i32 * 10 + rise as i32;
+
+        if !self.tripped && self.temperature >= self.config.max_c as i32 * 10 {
+            self.tripped = true;
+            defmt::error!("WINDING: {}°C over the {}°C limit", self.temperature / 10, self.config.max_c);
+            return true;
+        }
+        false
+    }
+
+    /// Scales the current limit down between the warning and the limit temperature.
+    pub fn derate(&self, current_limit: i32) -> i32 {
+        let warn = self.config.warn_c as i64 * 10;
+        let max = (self.config.max_c as i64 * 10).max(warn + 1);
+        let temperature = self.temperature as i64;
+        if !self.enabled || temperature <= warn {
+            return current_limit;
+        }
+        let over = (temperature - warn).min(max - warn);
+        let percent = 100 - (100 - DERATE_FLOOR_PERCENT) * over / (max - warn);
+        (current_limit as i64 * percent / 100) as i32
+    }
+
+    /// Clears the fault, it trips again while the winding stays over the limit.
+    pub fn clear(&mut self) {
+        self.tripped = false;
+    }
+
+    /// Returns true while over the limit temperature.
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// Returns the estimated winding resistance (mOhm), 0 before the first estimate.
+    pub fn resistance(&self) -> i32 {
+        self.resistance
+    }
+
+    /// Returns the estimated winding temperature (0.1 °C).
+    pub fn temperature(&self) -> i32 {
+        self.temperature
+    }
+}
@@ -0,0 +1,63 @@
+// Implements the parameter descriptor export, serializing the parameter table into JSON for
+// host tools (GUIs, configuration scripts).
+
+// Key Features:
+// - Streams JSON into any `core::fmt::Write` sink (UART buffer, RTT, host string), no allocation.
+// - One object per parameter: id, name, type, unit, scale, min, max and access.
+// - Host builds (`std` feature, not bare-metal) get a `String` convenience wrapper.
+
+// Detailed Operation:
+// The output has the form {"version":2,"params":[{"id":256,"name":"pos_kp","type":"i32",
+// "unit":"%","scale":[1,1],"min":-10000,"max":10000,"writable":true},...]}. Names are plain
+// identifiers and unit symbols contain no quotes, so no escaping is needed. The `version` field
+// changes if the format does.
+// The `std` feature is enabled by default but firmware targets have no std, so the wrapper is
+// additionally limited to hosted targets.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use core::fmt::{self, Write};
+
+use super::{ParamDescriptor, PARAMS};
+
+/// Version of the descriptor format
+pub const DESCRIPTOR_VERSION: u8 = 2;
+
+/// Writes the JSON descriptor of all parameters into `out`.
+pub fn write_json<W: Write>(out: &mut W) -> fmt::Result {
+    write!(out, "{{\"version\":{},\"params\":[", DESCRIPTOR_VERSION)?;
+    for (index, param) in PARAMS.iter().enumerate() {
+        if index > 0 {
+            out.write_char(',')?;
+        }
+        write_param(out, param)?;
+    }
+    out.write_str("]}")
+}
+
+/// Writes the JSON object of one parameter into `out`.
+pub fn write_param<W: Write>(out: &mut W, param: &ParamDescriptor) -> fmt::Result {
+    write!(
+        out,
+        "{{\"id\":{},\"name\":\"{}\",\"type\":\"{}\",\"unit\":\"{}\",\"scale\":[{},{}],\
+         \"min\":{},\"max\":{},\"writable\":{}}}",
+        param.id,
+        param.name,
+        param.kind.name(),
+        param.unit.symbol(),
+        param.scale.0,
+        param.scale.1,
+        param.min,
+        param.max,
+        param.writable
+    )
+}
+
+/// Returns the JSON descriptor of all parameters (host builds only).
+#[cfg(all(feature = "std", not(target_os = "none")))]
+pub fn to_json() -> std::string::String {
+    let mut out = std::string::String::new();
+    let _ = write_json(&mut out); // Writing into a String can't fail
+    out
+}
@@ -0,0 +1,234 @@
+// Implements the parameter metadata module, one compile-time table describing every tunable
+// of the controller: numeric id, name, unit, scaling and valid range.
+
+// Key Features:
+// - Stable numeric ids grouped by subsystem (loops, motion, sensing, protection, output, motor,
+//   calibration, step/dir input).
+// - Value type, unit and scale of the raw value, so host tools can show physical values.
+// - Valid range and access of each parameter for input validation on both sides.
+// - Runtime get/set of every parameter by id on the controller, see `registry`.
+// - CRC-protected records in a flash page restored on boot, see `storage`.
+// - Machine-readable descriptor export (JSON) for host tools, see `descriptor`.
+
+// Detailed Operation:
+// The table is a `const` array, so it costs only flash and can't drift from the firmware: adding
+// a parameter means adding one line here, and a GUI reading the exported descriptor picks it up
+// automatically. Raw values are integers; the physical value is raw * scale.0 / scale.1 in `unit`.
+// Ids are grouped by the high byte: 0x01 control loops, 0x02 motion profile, 0x03 sensing,
+// 0x04 protection, 0x05 output stage, 0x06 motor, 0x07 calibration, 0x08 step/dir input. Every value travels as an i32
+// whatever its type; the type tells a host how to show it and (with the range) bounds what it
+// may write.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+pub mod descriptor;
+pub mod registry;
+pub mod storage;
+
+pub use registry::{ParamError, ParamStore};
+
+/// Value type of a parameter (all values travel as i32).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    /// 0 or 1
+    Bool,
+    U8,
+    U16,
+    I16,
+    U32,
+    I32,
+    /// Selector, the range holds the valid variants
+    Enum,
+}
+
+impl ParamType {
+    /// Returns the type name used by host tools.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ParamType::Bool => "bool",
+            ParamType::U8 => "u8",
+            ParamType::U16 => "u16",
+            ParamType::I16 => "i16",
+            ParamType::U32 => "u32",
+            ParamType::I32 => "i32",
+            ParamType::Enum => "enum",
+        }
+    }
+}
+
+/// Unit of a parameter value (after scaling).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Dimensionless count or selector
+    None,
+    /// Fraction (1.0 = 100%)
+    Ratio,
+    Percent,
+    Milliamp,
+    MilliampPerSecond,
+    Hertz,
+    Ticks,
+    Counts,
+    CountsPerSecond,
+    CountsPerSecond2,
+    CountsPerSecond3,
+    Millisecond,
+    Nanosecond,
+    Milliohm,
+    Microhenry,
+    /// Current sense scale
+    MicroampPerLsb,
+    /// Torque constant
+    MillinewtonMeterPerAmp,
+}
+
+impl Unit {
+    /// Returns the unit symbol used by host tools.
+    pub const fn symbol(&self) -> &'static str {
+        match self {
+            Unit::None => "",
+            Unit::Ratio => "1",
+            Unit::Percent => "%",
+            Unit::Milliamp => "mA",
+            Unit::MilliampPerSecond => "mA/s",
+            Unit::Hertz => "Hz",
+            Unit::Ticks => "tick",
+            Unit::Counts => "count",
+            Unit::CountsPerSecond => "count/s",
+            Unit::CountsPerSecond2 => "count/s^2",
+            Unit::CountsPerSecond3 => "count/s^3",
+            Unit::Millisecond => "ms",
+            Unit::Nanosecond => "ns",
+            Unit::Milliohm => "mOhm",
+            Unit::Microhenry => "uH",
+            Unit::MicroampPerLsb => "uA/LSB",
+            Unit::MillinewtonMeterPerAmp => "mNm/A",
+        }
+    }
+}
+
+/// Metadata of one parameter.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamDescriptor {
+    /// Stable numeric id
+    pub id: u16,
+    /// Short name (lowercase, underscores)
+    pub name: &'static str,
+    /// Value type
+    pub kind: ParamType,
+    /// Unit of the scaled value
+    pub unit: Unit,
+    /// Physical value = raw * scale.0 / scale.1
+    pub scale: (i32, i32),
+    /// Minimum raw value
+    pub min: i32,
+    /// Maximum raw value
+    pub max: i32,
+    /// Writable at runtime, false - read only
+    pub writable: bool,
+}
+
+impl ParamDescriptor {
+    const fn new(id: u16, name: &'static str, kind: ParamType, unit: Unit, min: i32, max: i32) -> Self {
+        Self {
+            id,
+            name,
+            kind,
+            unit,
+            scale: (1, 1),
+            min,
+            max,
+            writable: true,
+        }
+    }
+
+    const fn scaled(mut self, num: i32, den: i32) -> Self {
+        self.scale = (num, den);
+        self
+    }
+
+    const fn read_only(mut self) -> Self {
+        self.writable = false;
+        self
+    }
+
+    /// Returns true if the raw value is inside the valid range.
+    pub const fn accepts(&self, value: i32) -> bool {
+        value >= self.min && value <= self.max
+    }
+}
+
+use ParamType::{Bool, Enum, I16, I32, U16, U32, U8};
+
+/// Descriptors of all parameters, sorted by id.
+pub const PARAMS: &[ParamDescriptor] = &[
+    // ######################## CONTROL LOOPS (0x01xx) ###############################
+    ParamDescriptor::new(0x0100, "pos_kp", I32, Unit::Percent, -10000, 10000),
+    ParamDescriptor::new(0x0101, "pos_ki", I32, Unit::Percent, -10000, 10000),
+    ParamDescriptor::new(0x0102, "pos_kd", I32, Unit::Percent, -10000, 10000),
+    ParamDescriptor::new(0x0110, "vel_kp", I32, Unit::Percent, -10000, 10000),
+    ParamDescriptor::new(0x0111, "vel_ki", I32, Unit::Percent, -10000, 10000),
+    ParamDescriptor::new(0x0112, "vel_kd", I32, Unit::Percent, -10000, 10000),
+    ParamDescriptor::new(0x0120, "vel_limit", I32, Unit::CountsPerSecond, 0, (i16::MAX as i32) << 4),
+    ParamDescriptor::new(0x0130, "notch_center", U16, Unit::Hertz, 0, u16::MAX as i32),
+    ParamDescriptor::new(0x0131, "notch_depth", U16, Unit::Ratio, 0, i16::MAX as i32).scaled(1, 32768),
+    ParamDescriptor::new(0x0132, "notch_q", U32, Unit::Ratio, 100, 100_000).scaled(1, 1000),
+    ParamDescriptor::new(0x0140, "tracking_bandwidth", U16, Unit::Hertz, 1, u16::MAX as i32),
+    ParamDescriptor::new(0x0150, "current_bandwidth", U16, Unit::Hertz, 0, u16::MAX as i32),
+    // ######################## MOTION PROFILE (0x02xx) ##############################
+    ParamDescriptor::new(0x0200, "profile_velocity", I32, Unit::CountsPerSecond, 1, i32::MAX),
+    ParamDescriptor::new(0x0201, "profile_accel", I32, Unit::CountsPerSecond2, 1, i32::MAX),
+    ParamDescriptor::new(0x0202, "profile_decel", I32, Unit::CountsPerSecond2, 1, i32::MAX),
+    ParamDescriptor::new(0x0203, "profile_jerk", I32, Unit::CountsPerSecond3, 1, i32::MAX),
+    ParamDescriptor::new(0x0210, "stop_decel", I32, Unit::CountsPerSecond2, 1, i32::MAX),
+    ParamDescriptor::new(0x0211, "stop_standstill", I32, Unit::CountsPerSecond, 0, i32::MAX),
+    // ######################## SENSING (0x03xx) #####################################
+    ParamDescriptor::new(0x0300, "encoder_median", U8, Unit::None, 1, 5),
+    ParamDescriptor::new(0x0310, "current_scale", I32, Unit::MicroampPerLsb, 0, i32::MAX),
+    // ######################## PROTECTION (0x04xx) ##################################
+    ParamDescriptor::new(0x0400, "soft_start_current", I32, Unit::Milliamp, 0, i16::MAX as i32),
+    ParamDescriptor::new(0x0401, "soft_start_ramp", U32, Unit::Ticks, 1, i32::MAX),
+    ParamDescriptor::new(0x0402, "soft_start_dwell", U32, Unit::Ticks, 0, i32::MAX),
+    ParamDescriptor::new(0x0403, "soft_start_tolerance", I32, Unit::Counts, 0, i32::MAX),
+    ParamDescriptor::new(0x0410, "burst_peak", U16, Unit::Percent, 100, 1000),
+    ParamDescriptor::new(0x0411, "burst_duration", U32, Unit::Ticks, 1, i32::MAX),
+    ParamDescriptor::new(0x0412, "burst_period", U32, Unit::Ticks, 1, i32::MAX),
+    ParamDescriptor::new(0x0420, "speed_max", I32, Unit::CountsPerSecond, 0, i32::MAX),
+    ParamDescriptor::new(0x0421, "overspeed_margin", U16, Unit::Percent, 0, 1000),
+    ParamDescriptor::new(0x0422, "overspeed_time", U16, Unit::Millisecond, 0, u16::MAX as i32),
+    // ######################## OUTPUT STAGE (0x05xx) ################################
+    ParamDescriptor::new(0x0500, "amplitude_rise", I32, Unit::MilliampPerSecond, 1, i32::MAX),
+    ParamDescriptor::new(0x0501, "amplitude_fall", I32, Unit::MilliampPerSecond, 1, i32::MAX),
+    ParamDescriptor::new(0x0510, "decay_mode", Enum, Unit::None, 0, 2),
+    ParamDescriptor::new(0x0511, "pwm_dither", I16, Unit::Ratio, 0, 1638).scaled(1, 32768),
+    ParamDescriptor::new(0x0512, "min_off_time", U32, Unit::Nanosecond, 0, 100_000),
+    ParamDescriptor::new(0x0513, "min_pulse", U32, Unit::Nanosecond, 0, 100_000),
+    // ######################## MOTOR (0x06xx) #######################################
+    ParamDescriptor::new(0x0600, "resistance", I32, Unit::Milliohm, 0, i32::MAX).read_only(),
+    ParamDescriptor::new(0x0601, "inductance", I32, Unit::Microhenry, 0, i32::MAX),
+    ParamDescriptor::new(0x0602, "torque_constant", I32, Unit::MillinewtonMeterPerAmp, 0, i32::MAX),
+    // ######################## CALIBRATION (0x07xx) #################################
+    ParamDescriptor::new(0x0700, "cal_stages", U8, Unit::None, 0, u8::MAX as i32),
+    ParamDescriptor::new(0x0701, "cal_current", I16, Unit::Milliamp, 0, i16::MAX as i32),
+    ParamDescriptor::new(0x0702, "cal_sweep_speed", U32, Unit::CountsPerSecond, 1, i32::MAX),
+    ParamDescriptor::new(0x0703, "cal_settle", U16, Unit::Millisecond, 0, u16::MAX as i32),
+    ParamDescriptor::new(0x0704, "cal_points", U16, Unit::None, 2, 32),
+    // ######################## STEP/DIR INPUT (0x08xx) ##############################
+    ParamDescriptor::new(0x0800, "step_resolution", U32, Unit::None, 1, 1 << 20),
+    ParamDescriptor::new(0x0801, "step_invert", Bool, Unit::None, 0, 1),
+    ParamDescriptor::new(0x0802, "motion_source", Enum, Unit::None, 0, 1),
+];
+
+/// Returns the descriptor of the parameter with the given id.
+pub fn find(id: u16) -> Option<&'static ParamDescriptor> {
+    PARAMS
+        .binary_search_by_key(&id, |param| param.id)
+        .ok()
+        .map(|index| &PARAMS[index])
+}
+
+/// Returns the descriptor of the parameter with the given name.
+pub fn find_name(name: &str) -> Option<&'static ParamDescriptor> {
+    PARAMS.iter().find(|param| param.name == name)
+}
@@ -0,0 +1,256 @@
+// Implements the parameter registry, reading and writing every parameter of the table by its id at
+// runtime, so configuration protocols share one validated path to the controller.
+
+// Key Features:
+// - `ParamStore`: raw read/write of parameter values by id, implemented by the controller.
+// - `get` / `set`: lookup in the parameter table, access and range checks before the store.
+// - Writes go through the regular controller setters (tuning watch, derived gains, limits).
+// - Errors tell unknown ids, read-only parameters, out-of-range and rejected values apart.
+
+// Detailed Operation:
+// A protocol handler (UART, CAN, flash loader) calls `set(store, id, value)`. The descriptor of the
+// id gives access and range; a value passing both is handed to `ParamStore::write`, which maps the
+// id to the matching setter. Parameters sharing one setter (the three gains of a loop, the fields
+// of a config struct) are read back, modified in one place and written as a whole, so a single
+// write leaves the others untouched. A store may still refuse a value the range allows (e.g. a
+// calibration point count that isn't a power of two), reported as `Rejected`. Ids missing in the
+// table never reach the store.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::find;
+use crate::motor_driver::{DecayMode, MotionSource};
+use crate::MotorController;
+
+/// Reason a parameter access failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamError {
+    /// No parameter with this id
+    UnknownId,
+    /// The parameter can't be written
+    ReadOnly,
+    /// The value is outside the valid range
+    OutOfRange,
+    /// The value was refused by the controller
+    Rejected,
+}
+
+/// Raw access to parameter values by id, without validation (see `get` / `set`).
+pub trait ParamStore {
+    /// Returns the raw value of the parameter, None if the id isn't handled.
+    fn read(&self, id: u16) -> Option<i32>;
+
+    /// Applies a raw value, returns false if the id isn't handled or the value is refused.
+    fn write(&mut self, id: u16, value: i32) -> bool;
+}
+
+/// Reads the parameter with the given id.
+pub fn get<S: ParamStore + ?Sized>(store: &S, id: u16) -> Result<i32, ParamError> {
+    find(id).ok_or(ParamError::UnknownId)?;
+    store.read(id).ok_or(ParamError::UnknownId)
+}
+
+/// Validates and writes the parameter with the given id.
+pub fn set<S: ParamStore + ?Sized>(store: &mut S, id: u16, value: i32) -> Result<(), ParamError> {
+    let param = find(id).ok_or(ParamError::UnknownId)?;
+    if !param.writable {
+        return Err(ParamError::ReadOnly);
+    }
+    if !param.accepts(value) {
+        return Err(ParamError::OutOfRange);
+    }
+    if store.write(id, value) { Ok(()) } else { Err(ParamError::Rejected) }
+}
+
+impl ParamStore for MotorController {
+    fn read(&self, id: u16) -> Option<i32> {
+        let tuning = self.tuning();
+        let (rise, fall) = self.amplitude_slew.rates();
+        let (stop_decel, standstill) = self.safe_stop.config();
+        let soft_start = self.soft_start.config();
+        let burst = self.burst.config();
+        let speed = self.speed_limit.config();
+        let cal = &self.cal_config;
+        let step_dir = self.step_dir_config();
+        let value = match id {
+            // ####### Control loops #######
+            0x0100 => tuning.pos_gains.0,
+            0x0101 => tuning.pos_gains.1,
+            0x0102 => tuning.pos_gains.2,
+            0x0110 => tuning.vel_gains.0,
+            0x0111 => tuning.vel_gains.1,
+            0x0112 => tuning.vel_gains.2,
+            0x0120 => tuning.vel_limit,
+            0x0130 => tuning.notch.0 as i32,
+            0x0131 => tuning.notch.1 as i32,
+            0x0132 => self.cascade.notch_width() as i32,
+            0x0140 => self.tracker.bandwidth() as i32,
+            0x0150 => self.current_gains.bandwidth as i32,
+            // ####### Motion profile #######
+            0x0200 => self.profile_limits.0,
+            0x0201 => self.profile_limits.1,
+            0x0202 => self.profile_limits.2,
+            0x0203 => self.scurve.jerk(),
+            0x0210 => stop_decel,
+            0x0211 => standstill,
+            // ####### Sensing #######
+            0x0300 => self.glitch.taps() as i32,
+            0x0310 => self.current_scale,
+            // ####### Protection #######
+            0x0400 => soft_start.hold_current_ma,
+            0x0401 => soft_start.ramp_ticks as i32,
+            0x0402 => soft_start.dwell_ticks as i32,
+            0x0403 => soft_start.tolerance,
+            0x0410 => burst.peak_pct as i32,
+            0x0411 => burst.burst_ticks as i32,
+            0x0412 => burst.period_ticks as i32,
+            0x0420 => speed.max_speed,
+            0x0421 => speed.margin as i32,
+            0x0422 => speed.trip_ms as i32,
+            // ####### Output stage #######
+            0x0500 => rise,
+            0x0501 => fall,
+            0x0510 => self.decay_mode() as i32,
+            0x0511 => self.pwm_dither() as i32,
+            0x0512 => self.min_off_ns.min(i32::MAX as u32) as i32,
+            0x0513 => self.min_pulse_ns.min(i32::MAX as u32) as i32,
+            // ####### Motor #######
+            0x0600 => self.resistance,
+            0x0601 => self.inductance,
+            0x0602 => self.torque_constant(),
+            // ####### Calibration #######
+            0x0700 => cal.stages as i32,
+            0x0701 => cal.current_ma as i32,
+            0x0702 => cal.sweep_speed.min(i32::MAX as u32) as i32,
+            0x0703 => cal.settle_ms as i32,
+            0x0704 => cal.points_per_period as i32,
+            // ####### Step/dir input #######
+            0x0800 => step_dir.steps_per_rev.min(i32::MAX as u32) as i32,
+            0x0801 => step_dir.invert as i32,
+            0x0802 => self.motion_source() as i32,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn write(&mut self, id: u16, value: i32) -> bool {
+        let mut tuning = self.tuning();
+        let mut soft_start = self.soft_start.config();
+        let mut burst = self.burst.config();
+        let mut speed = self.speed_limit.config();
+        let mut cal = self.cal_config;
+        let mut step_dir = self.step_dir_config();
+        let (rise, fall) = self.amplitude_slew.rates();
+        let (stop_decel, standstill) = self.safe_stop.config();
+        let (vel_max, accel, decel) = self.profile_limits;
+        match id {
+            // ####### Control loops #######
+            0x0100..=0x0131 => {
+                match id {
+                    0x0100 => tuning.pos_gains.0 = value,
+                    0x0101 => tuning.pos_gains.1 = value,
+                    0x0102 => tuning.pos_gains.2 = value,
+                    0x0110 => tuning.vel_gains.0 = value,
+                    0x0111 => tuning.vel_gains.1 = value,
+                    0x0112 => tuning.vel_gains.2 = value,
+                    0x0120 => tuning.vel_limit = value,
+                    0x0130 => tuning.notch.0 = value as u16,
+                    0x0131 => tuning.notch.1 = value as u16,
+                    _ => return false,
+                }
+                self.set_tuning(tuning);
+            }
+            0x0132 => self.cascade.set_notch_width(value as u32),
+            0x0140 => self.set_tracking_bandwidth(value as u16),
+            0x0150 => {
+                self.set_current_bandwidth(value as u16);
+            }
+            // ####### Motion profile #######
+            0x0200 => self.set_profile_limits(value, accel, decel),
+            0x0201 => self.set_profile_limits(vel_max, value, decel),
+            0x0202 => self.set_profile_limits(vel_max, accel, value),
+            0x0203 => self.set_profile_jerk(value),
+            0x0210 => self.set_stop_ramp(value, standstill),
+            0x0211 => self.set_stop_ramp(stop_decel, value),
+            // ####### Sensing #######
+            0x0300 => {
+                if value % 2 == 0 {
+                    return false; // Median windows are odd
+                }
+                self.set_encoder_median(value as u8);
+            }
+            0x0310 => self.set_current_scale(value),
+            // ####### Protection #######
+            0x0400..=0x0403 => {
+                match id {
+                    0x0400 => soft_start.hold_current_ma = value,
+                    0x0401 => soft_start.ramp_ticks = value as u32,
+                    0x0402 => soft_start.dwell_ticks = value as u32,
+                    _ => soft_start.tolerance = value,
+                }
+                self.set_soft_start(soft_start);
+            }
+            0x0410..=0x0412 => {
+                match id {
+                    0x0410 => burst.peak_pct = value as u16,
+                    0x0411 => burst.burst_ticks = value as u32,
+                    _ => burst.period_ticks = value as u32,
+                }
+                self.set_burst_torque(burst);
+            }
+            0x0420..=0x0422 => {
+                match id {
+                    0x0420 => speed.max_speed = value,
+                    0x0421 => speed.margin = value as u16,
+                    _ => speed.trip_ms = value as u16,
+                }
+                self.set_speed_limit(speed);
+            }
+            // ####### Output stage #######
+            0x0500 => self.set_amplitude_slew(value, fall),
+            0x0501 => self.set_amplitude_slew(rise, value),
+            0x0510 => {
+                let decay = match value {
+                    0 => DecayMode::Slow,
+                    1 => DecayMode::Fast,
+                    _ => DecayMode::Mixed,
+                };
+                self.set_decay_mode(decay);
+            }
+            0x0511 => self.set_pwm_dither(value as i16),
+            0x0512 => self.set_min_off_time(value as u32),
+            0x0513 => self.set_min_pulse(value as u32),
+            // ####### Motor #######
+            0x0601 => self.set_inductance(value),
+            0x0602 => self.set_torque_constant(value),
+            // ####### Calibration #######
+            0x0700..=0x0704 => {
+                match id {
+                    0x0700 => cal.stages = value as u8,
+                    0x0701 => cal.current_ma = value as i16,
+                    0x0702 => cal.sweep_speed = value as u32,
+                    0x0703 => cal.settle_ms = value as u16,
+                    _ => cal.points_per_period = value as u16,
+                }
+                if !self.set_calibration(cal) {
+                    return false; // Points have to divide the electrical period evenly
+                }
+            }
+            // ####### Step/dir input #######
+            0x0800..=0x0801 => {
+                match id {
+                    0x0800 => step_dir.steps_per_rev = value as u32,
+                    _ => step_dir.invert = value != 0,
+                }
+                self.set_step_dir(step_dir);
+            }
+            0x0802 => {
+                let source = if value == 0 { MotionSource::Internal } else { MotionSource::StepDir };
+                self.set_motion_source(source);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
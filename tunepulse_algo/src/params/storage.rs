@@ -0,0 +1,273 @@
+// Implements the parameter storage codec, serializing the writable parameters into records of a
+// flash page provided by the HAL and restoring the newest valid record on boot.
+
+// Key Features:
+// - Versioned record: magic, format version, save counter, (id, value) pairs, CRC-16.
+// - Append-only page use: each save goes behind the last record, the page is erased only when full.
+// - Torn or corrupted records are detected by the CRC and skipped, the previous one stays in use.
+// - Restore through the registry: every value is range checked, unknown ids are skipped.
+
+// Detailed Operation:
+// Flash bits can only be cleared by programming; setting them back needs an erase of the whole
+// page, which wears it. Records are therefore appended into the erased (0xFF) space of the page,
+// each one padded to RECORD_ALIGN so it starts on a programmable double word. Layout, little endian:
+// magic u16 | format u8 | reserved u8 | sequence u32 | count u16 | count x (id u16, value i32) |
+// crc u16 over everything before it. On restore the page is walked from the start: a valid record
+// replaces the previous candidate, a bad CRC (power lost while programming) is stepped over, erased
+// space ends the walk. `save` returns the byte range to program and whether the page has to be
+// erased first (no room left or unreadable content); the page buffer already holds the result.
+// Ids stay stable between firmware versions, so a record written by an older firmware restores
+// every parameter both know; values the new ranges reject are skipped and counted. Parameters
+// bounded by others (loop tuning and profile velocity by the speed limit, current bandwidth by the
+// winding) are restored in a second pass, after the values they depend on.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use super::registry::{get, set, ParamStore};
+use super::PARAMS;
+use crate::math_integer::interface::crc::{crc16_ccitt, CRC16_CCITT_INIT};
+
+/// Record marker ("TP")
+const RECORD_MAGIC: u16 = 0x5054;
+/// Version of the record format
+pub const STORAGE_FORMAT: u8 = 1;
+/// Alignment of the records in the page (bytes, flash double word)
+pub const RECORD_ALIGN: usize = 8;
+/// Header size: magic, format, reserved, sequence, count (bytes)
+const HEADER_LEN: usize = 10;
+/// Size of one (id, value) entry (bytes)
+const ENTRY_LEN: usize = 6;
+/// Size of the CRC (bytes)
+const CRC_LEN: usize = 2;
+/// Value of erased flash
+const ERASED: u8 = 0xFF;
+
+/// Reason a storage operation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// No valid record in the page
+    NoRecord,
+    /// The page can't hold one record
+    PageTooSmall,
+}
+
+/// Flash operation required after a save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageWrite {
+    /// Erase the page before programming
+    pub erase: bool,
+    /// Offset of the bytes to program (bytes)
+    pub offset: usize,
+    /// Number of bytes to program, a multiple of RECORD_ALIGN
+    pub len: usize,
+    /// Save counter of the written record
+    pub sequence: u32,
+}
+
+/// Outcome of a restore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Save counter of the restored record
+    pub sequence: u32,
+    /// Parameters applied
+    pub applied: u16,
+    /// Parameters skipped (unknown id, read only, out of range or rejected)
+    pub skipped: u16,
+}
+
+/// Result of walking the page.
+struct Scan {
+    latest: Option<(usize, u32)>, // Offset and sequence of the newest valid record
+    free: Option<usize>,          // Start of the erased space, None - full or unreadable
+}
+
+/// Serializes all writable parameters of `store` into a new record of `page`.
+///
+/// The caller programs `page[offset..offset + len]` into flash, after erasing it if requested.
+pub fn save<S: ParamStore + ?Sized>(store: &S, page: &mut [u8]) -> Result<StorageWrite, StorageError> {
+    let count = PARAMS.iter().filter(|param| param.writable && get(store, param.id).is_ok()).count();
+    let len = padded(HEADER_LEN + count * ENTRY_LEN + CRC_LEN);
+    if len > page.len() {
+        return Err(StorageError::PageTooSmall);
+    }
+    let scan = scan(page);
+    let sequence = scan.latest.map_or(1, |(_, sequence)| sequence.wrapping_add(1));
+    let offset = scan
+        .free
+        .filter(|&free| free + len <= page.len() && page[free..free + len].iter().all(|&byte| byte == ERASED));
+    let erase = offset.is_none();
+    let offset = offset.unwrap_or(0);
+    if erase {
+        page.fill(ERASED);
+    }
+
+    // ####### Record #######
+    let record = &mut page[offset..offset + len];
+    record[0..2].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+    record[2] = STORAGE_FORMAT;
+    record[3] = 0;
+    record[4..8].copy_from_slice(&sequence.to_le_bytes());
+    record[8..10].copy_from_slice(&(count as u16).to_le_bytes());
+    let mut index = HEADER_LEN;
+    for param in PARAMS.iter().filter(|param| param.writable) {
+        if let Ok(value) = get(store, param.id) {
+            record[index..index + 2].copy_from_slice(&param.id.to_le_bytes());
+            record[index + 2..index + ENTRY_LEN].copy_from_slice(&value.to_le_bytes());
+            index += ENTRY_LEN;
+        }
+    }
+    let crc = crc16_ccitt(&record[..index], CRC16_CCITT_INIT);
+    record[index..index + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    defmt::info!("PARAMS: Saved {} parameters, record {} at {}", count, sequence, offset);
+    Ok(StorageWrite { erase, offset, len, sequence })
+}
+
+/// Restores the parameters of the newest valid record of `page` into `store`.
+pub fn load<S: ParamStore + ?Sized>(store: &mut S, page: &[u8]) -> Result<LoadReport, StorageError> {
+    let (offset, sequence) = scan(page).latest.ok_or(StorageError::NoRecord)?;
+    let count = read_u16(page, offset + 8) as usize;
+    let mut report = LoadReport { sequence, applied: 0, skipped: 0 };
+    for late in [false, true] {
+        for entry in 0..count {
+            let index = offset + HEADER_LEN + entry * ENTRY_LEN;
+            let id = read_u16(page, index);
+            if restored_late(id) != late {
+                continue;
+            }
+            let value = i32::from_le_bytes([page[index + 2], page[index + 3], page[index + 4], page[index + 5]]);
+            match set(store, id, value) {
+                Ok(()) => report.applied += 1,
+                Err(_) => report.skipped += 1,
+            }
+        }
+    }
+    defmt::info!("PARAMS: Restored record {}, {} applied, {} skipped", sequence, report.applied, report.skipped);
+    Ok(report)
+}
+
+/// Walks the records of the page.
+fn scan(page: &[u8]) -> Scan {
+    let mut scan = Scan { latest: None, free: None };
+    let mut offset = 0;
+    while offset + HEADER_LEN + CRC_LEN <= page.len() {
+        let header = &page[offset..offset + HEADER_LEN];
+        if header.iter().all(|&byte| byte == ERASED) {
+            scan.free = Some(offset);
+            return scan;
+        }
+        if read_u16(page, offset) != RECORD_MAGIC {
+            return scan; // Not a record: unreadable from here on
+        }
+        let body = HEADER_LEN + read_u16(page, offset + 8) as usize * ENTRY_LEN;
+        let len = padded(body + CRC_LEN);
+        if offset + len > page.len() {
+            return scan;
+        }
+        let crc = crc16_ccitt(&page[offset..offset + body], CRC16_CCITT_INIT);
+        if crc == read_u16(page, offset + body) && page[offset + 2] == STORAGE_FORMAT {
+            let sequence = u32::from_le_bytes([page[offset + 4], page[offset + 5], page[offset + 6], page[offset + 7]]);
+            scan.latest = Some((offset, sequence));
+        }
+        offset += len;
+    }
+    scan
+}
+
+/// Returns true for parameters applied after the values they depend on.
+#[inline(always)]
+const fn restored_late(id: u16) -> bool {
+    matches!(id, 0x0100..=0x0132 | 0x0150 | 0x0200..=0x0202)
+}
+
+/// Rounds a record size up to RECORD_ALIGN.
+#[inline(always)]
+const fn padded(len: usize) -> usize {
+    len.div_ceil(RECORD_ALIGN) * RECORD_ALIGN
+}
+
+/// Reads a little endian u16.
+#[inline(always)]
+fn read_u16(page: &[u8], index: usize) -> u16 {
+    u16::from_le_bytes([page[index], page[index + 1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motor_driver::{MotorType, PhasePattern};
+    use crate::MotorController;
+
+    fn controller() -> MotorController {
+        MotorController::new(MotorType::STEP, PhasePattern::ABCD, 20000, 24000, 1500)
+    }
+
+    /// Writes a non-default value to every writable parameter.
+    fn configure(store: &mut MotorController) {
+        // Coupled values: a tuning limit above the speed limit, a bandwidth needing the inductance
+        for (id, value) in [(0x0120, 1000), (0x0200, 5000), (0x0150, 800), (0x0420, 1), (0x0601, 2200)] {
+            set(store, id, value).unwrap();
+        }
+        for param in PARAMS.iter().filter(|param| param.writable) {
+            if matches!(param.id, 0x0120 | 0x0150 | 0x0200 | 0x0420 | 0x0601) {
+                continue;
+            }
+            let value = match param.id {
+                0x0300 => 5,   // Odd median window
+                0x0704 => 16,  // Power of two
+                0x0802 => 0,   // Internal source, no step input engaged
+                _ => param.min + (param.max - param.min) / 3,
+            };
+            let _ = set(store, param.id, value);
+        }
+    }
+
+    #[test]
+    fn save_load_restores_every_parameter() {
+        let mut source = controller();
+        configure(&mut source);
+        let mut page = [ERASED; 1024];
+        let write = save(&source, &mut page).unwrap();
+        assert_eq!((write.erase, write.offset), (false, 0));
+
+        let mut target = controller();
+        let report = load(&mut target, &page).unwrap();
+        assert_eq!(report.sequence, write.sequence);
+        assert_eq!(report.skipped, 0);
+        for param in PARAMS.iter() {
+            assert_eq!(get(&target, param.id), get(&source, param.id), "parameter {:#06x}", param.id);
+        }
+        assert_eq!(get(&target, 0x0120), Ok(1000));
+        assert_eq!(get(&target, 0x0150), Ok(800));
+    }
+
+    #[test]
+    fn newest_record_wins() {
+        let mut store = controller();
+        let mut page = [ERASED; 1024];
+        set(&mut store, 0x0420, 100).unwrap();
+        save(&store, &mut page).unwrap();
+        set(&mut store, 0x0420, 200).unwrap();
+        let write = save(&store, &mut page).unwrap();
+        assert!(!write.erase);
+        assert_eq!(write.sequence, 2);
+
+        let mut target = controller();
+        load(&mut target, &page).unwrap();
+        assert_eq!(get(&target, 0x0420), Ok(200));
+
+        // A torn second record leaves the first one in use
+        page[write.offset + HEADER_LEN] ^= 0xFF;
+        let mut target = controller();
+        assert_eq!(load(&mut target, &page).unwrap().sequence, 1);
+        assert_eq!(get(&target, 0x0420), Ok(100));
+    }
+
+    #[test]
+    fn empty_page_has_no_record() {
+        let mut store = controller();
+        assert_eq!(load(&mut store, &[ERASED; 64]), Err(StorageError::NoRecord));
+    }
+}
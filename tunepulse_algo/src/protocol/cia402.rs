@@ -0,0 +1,598 @@
+// Implements the CANopen CiA 402 drive profile, the device state machine and the standard drive
+// objects on top of `MotorController`, with the CAN transport (SDO/PDO framing) left to the HAL.
+
+// Key Features:
+// - CiA 402 power state machine driven by the controlword, reported in the statusword.
+// - Fault reaction on drive errors and the e-stop, fault reset through controlword bit 7.
+// - Modes of operation: profile position, profile velocity, profile torque, homing and the cyclic
+//   synchronous position, velocity and torque modes.
+// - Standard objects (0x6040..0x6502) and the parameter registry at 0x2000 + (id >> 8), sub-index
+//   id & 0xFF, with the CiA 301 SDO abort codes.
+
+// Detailed Operation:
+// The HAL decodes SDO and PDO frames and calls `read` / `write` with the object index, sub-index and
+// the value as i32 (sign or zero extended from the object size, see `object_size`); `tick` runs once
+// per control tick after the received PDOs were written. A new drive boots in Not Ready To Switch On
+// while the controller calibrates and then waits in Switch On Disabled with the outputs off.
+// Shutdown, Switch On and Enable Operation commands walk it to Operation Enabled, where the setpoint
+// objects of the selected mode are applied every tick; in every other state the axis coasts. A
+// quick stop stops with the behavior selected by the quick stop option code (coast, ramp with the
+// quick stop deceleration, or brake), codes 0..4 then disable the drive, 5..8 stay in Quick Stop
+// Active. A driver error or a latched e-stop enters Fault Reaction Active and, once the axis is
+// stopped, Fault; a rising edge of the fault reset bit resets the controller and returns to Switch
+// On Disabled. Units are the controller's: positions in counts (65536 per revolution), velocities in
+// counts/s, torques in per mille of the rated current (0x6075), the factor group is not supported.
+// The profile velocity, acceleration and quick stop deceleration objects are aliases of the
+// corresponding registry parameters, so they are stored with the other parameters.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::motor_driver::{DriverStatus, HomingConfig, HomingStage, StopMode, StopStage};
+use crate::params::{self, ParamError};
+use crate::MotorController;
+
+/// Device type: CiA 402 servo drive
+pub const DEVICE_TYPE: u32 = 0x0002_0192;
+/// Supported drive modes (0x6502): PP, PV, TQ, HM, CSP, CSV, CST
+pub const SUPPORTED_MODES: u32 = (1 << 0) | (1 << 2) | (1 << 3) | (1 << 5) | (1 << 7) | (1 << 8) | (1 << 9);
+/// First index of the manufacturer objects mapping the parameter registry
+pub const REGISTRY_BASE: u16 = 0x2000;
+/// Band around the target velocity reported as reached (counts/s)
+const VELOCITY_WINDOW: i32 = 1 << 12;
+
+/// Controlword bit: switch on
+const CW_SWITCH_ON: u16 = 1 << 0;
+/// Controlword bit: enable voltage
+const CW_ENABLE_VOLTAGE: u16 = 1 << 1;
+/// Controlword bit: quick stop (active low)
+const CW_QUICK_STOP: u16 = 1 << 2;
+/// Controlword bit: enable operation
+const CW_ENABLE_OPERATION: u16 = 1 << 3;
+/// Controlword bit: new set-point (profile position) / homing start
+const CW_NEW_SETPOINT: u16 = 1 << 4;
+/// Controlword bit: relative target (profile position)
+const CW_RELATIVE: u16 = 1 << 6;
+/// Controlword bit: fault reset (rising edge)
+const CW_FAULT_RESET: u16 = 1 << 7;
+/// Controlword bit: halt
+const CW_HALT: u16 = 1 << 8;
+
+/// Statusword bit: voltage enabled
+const SW_VOLTAGE_ENABLED: u16 = 1 << 4;
+/// Statusword bit: remote (controlword processed)
+const SW_REMOTE: u16 = 1 << 9;
+/// Statusword bit: target reached
+const SW_TARGET_REACHED: u16 = 1 << 10;
+/// Statusword bit: internal limit active
+const SW_INTERNAL_LIMIT: u16 = 1 << 11;
+/// Statusword bit: operation mode specific (set-point acknowledge, speed zero, homing attained, ...)
+const SW_MODE_SPECIFIC: u16 = 1 << 12;
+/// Statusword bit: operation mode specific (homing error, following error)
+const SW_MODE_ERROR: u16 = 1 << 13;
+
+/// State of the CiA 402 power state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cia402State {
+    /// Drive initializing (calibrating)
+    NotReadyToSwitchOn,
+    /// Outputs off, waiting for Shutdown
+    SwitchOnDisabled,
+    /// Outputs off, waiting for Switch On
+    ReadyToSwitchOn,
+    /// Outputs off, waiting for Enable Operation
+    SwitchedOn,
+    /// Setpoints applied
+    OperationEnabled,
+    /// Stopping or stopped by a quick stop
+    QuickStopActive,
+    /// Stopping after a fault
+    FaultReactionActive,
+    /// Stopped after a fault, waiting for a fault reset
+    Fault,
+}
+
+impl Cia402State {
+    /// Returns the state bits of the statusword (bits 0..3, 5, 6).
+    pub const fn bits(&self) -> u16 {
+        match self {
+            Cia402State::NotReadyToSwitchOn => 0x00,
+            Cia402State::SwitchOnDisabled => 0x40,
+            Cia402State::ReadyToSwitchOn => 0x21,
+            Cia402State::SwitchedOn => 0x23,
+            Cia402State::OperationEnabled => 0x27,
+            Cia402State::QuickStopActive => 0x07,
+            Cia402State::FaultReactionActive => 0x0F,
+            Cia402State::Fault => 0x08,
+        }
+    }
+}
+
+/// Mode of operation (0x6060).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationMode {
+    /// Profile position: point-to-point moves on a new set-point
+    ProfilePosition = 1,
+    /// Profile velocity
+    ProfileVelocity = 3,
+    /// Profile torque
+    ProfileTorque = 4,
+    /// Homing against a hard stop
+    Homing = 6,
+    /// Cyclic synchronous position
+    CyclicPosition = 8,
+    /// Cyclic synchronous velocity
+    CyclicVelocity = 9,
+    /// Cyclic synchronous torque
+    CyclicTorque = 10,
+}
+
+impl OperationMode {
+    /// Returns the mode for its object value, None if not supported.
+    pub const fn from_value(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(OperationMode::ProfilePosition),
+            3 => Some(OperationMode::ProfileVelocity),
+            4 => Some(OperationMode::ProfileTorque),
+            6 => Some(OperationMode::Homing),
+            8 => Some(OperationMode::CyclicPosition),
+            9 => Some(OperationMode::CyclicVelocity),
+            10 => Some(OperationMode::CyclicTorque),
+            _ => None,
+        }
+    }
+}
+
+/// SDO abort reasons (CiA 301).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdoAbort {
+    /// Object does not exist in the object dictionary
+    NoObject,
+    /// Sub-index does not exist
+    NoSubIndex,
+    /// Attempt to write a read only object
+    ReadOnly,
+    /// Value range of parameter exceeded
+    ValueRange,
+    /// Data cannot be transferred or stored to the application
+    Rejected,
+    /// Data cannot be transferred because of the present device state
+    DeviceState,
+}
+
+impl SdoAbort {
+    /// Returns the abort code sent in the SDO abort frame.
+    pub const fn code(&self) -> u32 {
+        match self {
+            SdoAbort::NoObject => 0x0602_0000,
+            SdoAbort::NoSubIndex => 0x0609_0011,
+            SdoAbort::ReadOnly => 0x0601_0002,
+            SdoAbort::ValueRange => 0x0609_0030,
+            SdoAbort::Rejected => 0x0800_0020,
+            SdoAbort::DeviceState => 0x0800_0022,
+        }
+    }
+}
+
+impl From<ParamError> for SdoAbort {
+    fn from(error: ParamError) -> Self {
+        match error {
+            ParamError::UnknownId => SdoAbort::NoSubIndex,
+            ParamError::ReadOnly => SdoAbort::ReadOnly,
+            ParamError::OutOfRange => SdoAbort::ValueRange,
+            ParamError::Rejected => SdoAbort::Rejected,
+        }
+    }
+}
+
+/// Command decoded from the controlword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    DisableVoltage,
+    QuickStop,
+    Shutdown,
+    SwitchOn,
+    EnableOperation,
+}
+
+impl Command {
+    /// Decodes the state machine command of a controlword.
+    const fn decode(controlword: u16) -> Self {
+        if controlword & CW_ENABLE_VOLTAGE == 0 {
+            Command::DisableVoltage
+        } else if controlword & CW_QUICK_STOP == 0 {
+            Command::QuickStop
+        } else if controlword & CW_SWITCH_ON == 0 {
+            Command::Shutdown
+        } else if controlword & CW_ENABLE_OPERATION == 0 {
+            Command::SwitchOn
+        } else {
+            Command::EnableOperation
+        }
+    }
+}
+
+/// CiA 402 drive profile on top of the controller.
+pub struct Cia402 {
+    state: Cia402State,
+    controlword: u16,
+    previous: u16, // Controlword of the last tick, for edge detection
+    mode: OperationMode,
+
+    target_position: i32,   // 0x607A (counts)
+    target_velocity: i32,   // 0x60FF (counts/s)
+    target_torque: i16,     // 0x6071 (per mille of the rated current)
+    rated_current: u32,     // 0x6075 (mA)
+    quick_stop_option: i16, // 0x605A
+    homing: HomingConfig,   // Settings of the homing mode
+    setpoint_ack: bool,     // New set-point accepted, until the bit is cleared
+    moved: bool,            // A profile move or homing was started since entering the mode
+}
+
+impl Cia402 {
+    /// Creates the profile in Not Ready To Switch On, profile position mode, 1 A rated current.
+    pub fn new() -> Self {
+        Self {
+            state: Cia402State::NotReadyToSwitchOn,
+            controlword: 0,
+            previous: 0,
+            mode: OperationMode::ProfilePosition,
+            target_position: 0,
+            target_velocity: 0,
+            target_torque: 0,
+            rated_current: 1000,
+            quick_stop_option: 2,
+            homing: HomingConfig::default(),
+            setpoint_ack: false,
+            moved: false,
+        }
+    }
+
+    /// Sets the homing routine started by the homing mode.
+    pub fn set_homing(&mut self, config: HomingConfig) {
+        self.homing = config;
+    }
+
+    /// Returns the state of the state machine.
+    pub fn state(&self) -> Cia402State {
+        self.state
+    }
+
+    /// Returns the active mode of operation.
+    pub fn mode(&self) -> OperationMode {
+        self.mode
+    }
+
+    /// Advances the state machine and applies the setpoints, once per control tick.
+    pub fn tick(&mut self, drive: &mut MotorController) {
+        let rising = self.controlword & !self.previous;
+        self.previous = self.controlword;
+        let faulted = drive.status() == DriverStatus::Error || drive.estop_latched();
+
+        // ####### Faults #######
+        match self.state {
+            Cia402State::Fault => {
+                if rising & CW_FAULT_RESET != 0 {
+                    drive.reset_estop();
+                    drive.reset_fault();
+                    if drive.status() != DriverStatus::Error && !drive.estop_latched() {
+                        self.enter(Cia402State::SwitchOnDisabled);
+                    }
+                }
+                return;
+            }
+            Cia402State::FaultReactionActive => {
+                if drive.stop_stage() != StopStage::Ramping {
+                    self.enter(Cia402State::Fault);
+                }
+                return;
+            }
+            Cia402State::NotReadyToSwitchOn => {}
+            _ if faulted => {
+                self.enter(Cia402State::FaultReactionActive);
+                return;
+            }
+            _ => {}
+        }
+
+        // ####### State transitions #######
+        let command = Command::decode(self.controlword);
+        let next = match (self.state, command) {
+            (Cia402State::NotReadyToSwitchOn, _) => match drive.status() {
+                DriverStatus::Calibrating => Cia402State::NotReadyToSwitchOn,
+                DriverStatus::Ready => Cia402State::SwitchOnDisabled,
+                DriverStatus::Error => Cia402State::FaultReactionActive,
+            },
+            (Cia402State::SwitchOnDisabled, Command::Shutdown) => Cia402State::ReadyToSwitchOn,
+            (Cia402State::ReadyToSwitchOn, Command::SwitchOn) => Cia402State::SwitchedOn,
+            (Cia402State::ReadyToSwitchOn, Command::EnableOperation) => Cia402State::OperationEnabled,
+            (Cia402State::ReadyToSwitchOn | Cia402State::SwitchedOn, Command::DisableVoltage | Command::QuickStop) => {
+                Cia402State::SwitchOnDisabled
+            }
+            (Cia402State::SwitchedOn, Command::Shutdown) => Cia402State::ReadyToSwitchOn,
+            (Cia402State::SwitchedOn, Command::EnableOperation) => Cia402State::OperationEnabled,
+            (Cia402State::OperationEnabled, Command::SwitchOn) => Cia402State::SwitchedOn,
+            (Cia402State::OperationEnabled, Command::Shutdown) => Cia402State::ReadyToSwitchOn,
+            (Cia402State::OperationEnabled, Command::DisableVoltage) => Cia402State::SwitchOnDisabled,
+            (Cia402State::OperationEnabled, Command::QuickStop) => Cia402State::QuickStopActive,
+            (Cia402State::QuickStopActive, Command::DisableVoltage) => Cia402State::SwitchOnDisabled,
+            (Cia402State::QuickStopActive, Command::EnableOperation) if self.quick_stop_option >= 5 => {
+                Cia402State::OperationEnabled
+            }
+            (state, _) => state,
+        };
+        if next != self.state {
+            self.transition(drive, next);
+        }
+
+        // ####### Outputs #######
+        match self.state {
+            Cia402State::OperationEnabled => self.apply(drive, rising),
+            Cia402State::QuickStopActive => {
+                let stopped = drive.stop_stage() == StopStage::Stopped;
+                if stopped && self.quick_stop_option < 5 {
+                    self.enter(Cia402State::SwitchOnDisabled);
+                }
+            }
+            Cia402State::NotReadyToSwitchOn | Cia402State::FaultReactionActive | Cia402State::Fault => {}
+            _ => {
+                if drive.status() == DriverStatus::Ready && drive.stop_stage() == StopStage::Running {
+                    drive.request_stop(StopMode::Coast); // Outputs stay off outside Operation Enabled
+                }
+            }
+        }
+    }
+
+    /// Performs the actions of a transition.
+    fn transition(&mut self, drive: &mut MotorController, next: Cia402State) {
+        match next {
+            Cia402State::OperationEnabled => {
+                if drive.stop_stage() != StopStage::Running && !drive.release_stop() {
+                    return; // The drive refused (e.g. e-stop latched), stay where we are
+                }
+                self.moved = false;
+                self.setpoint_ack = false;
+            }
+            Cia402State::QuickStopActive => {
+                let mode = match self.quick_stop_option {
+                    0 => StopMode::Coast,
+                    1 | 2 | 5 | 6 => StopMode::Ramp,
+                    _ => StopMode::Brake,
+                };
+                drive.request_stop(mode);
+            }
+            _ => {} // Outputs of the disabled states are handled every tick
+        }
+        self.enter(next);
+    }
+
+    /// Changes the state.
+    fn enter(&mut self, state: Cia402State) {
+        defmt::info!("CIA402: State {} -> {}", self.state as u8, state as u8);
+        self.state = state;
+    }
+
+    /// Applies the setpoints of the active mode in Operation Enabled.
+    fn apply(&mut self, drive: &mut MotorController, rising: u16) {
+        let halt = self.controlword & CW_HALT != 0;
+        match self.mode {
+            OperationMode::ProfilePosition => {
+                if rising & CW_NEW_SETPOINT != 0 && !halt {
+                    let relative = self.controlword & CW_RELATIVE != 0;
+                    let target = if relative {
+                        drive.position().saturating_add(self.target_position)
+                    } else {
+                        self.target_position
+                    };
+                    drive.move_to(target);
+                    self.setpoint_ack = true;
+                    self.moved = true;
+                } else if self.controlword & CW_NEW_SETPOINT == 0 {
+                    self.setpoint_ack = false;
+                }
+                if halt && drive.is_moving() {
+                    drive.set_velocity(0);
+                }
+            }
+            OperationMode::Homing => {
+                if rising & CW_NEW_SETPOINT != 0 && !halt && drive.start_homing(self.homing) {
+                    self.moved = true;
+                }
+            }
+            OperationMode::ProfileVelocity | OperationMode::CyclicVelocity => {
+                drive.set_velocity(if halt { 0 } else { self.target_velocity });
+            }
+            OperationMode::ProfileTorque | OperationMode::CyclicTorque => {
+                drive.set_torque(if halt { 0 } else { self.torque_current() });
+            }
+            OperationMode::CyclicPosition => {
+                if halt {
+                    drive.set_velocity(0);
+                } else {
+                    drive.set_target_position(self.target_position);
+                }
+            }
+        }
+    }
+
+    /// Returns the target torque as a current (mA).
+    fn torque_current(&self) -> i32 {
+        (self.target_torque as i64 * self.rated_current as i64 / 1000) as i32
+    }
+
+    /// Returns the statusword (0x6041).
+    pub fn statusword(&self, drive: &MotorController) -> u16 {
+        let mut status = self.state.bits() | SW_REMOTE;
+        if drive.supply_mv() > 0 {
+            status |= SW_VOLTAGE_ENABLED;
+        }
+        if drive.is_overspeed() {
+            status |= SW_INTERNAL_LIMIT;
+        }
+        if self.state != Cia402State::OperationEnabled {
+            return status;
+        }
+        let halt = self.controlword & CW_HALT != 0;
+        let velocity = drive.velocity();
+        match self.mode {
+            OperationMode::ProfilePosition => {
+                if !drive.is_moving() && (self.moved || halt) {
+                    status |= SW_TARGET_REACHED;
+                }
+                if self.setpoint_ack {
+                    status |= SW_MODE_SPECIFIC;
+                }
+            }
+            OperationMode::ProfileVelocity | OperationMode::CyclicVelocity => {
+                let target = if halt { 0 } else { self.target_velocity };
+                if (velocity - target).saturating_abs() <= VELOCITY_WINDOW {
+                    status |= SW_TARGET_REACHED;
+                }
+                let zero = velocity.saturating_abs() <= VELOCITY_WINDOW;
+                if self.mode == OperationMode::CyclicVelocity || zero {
+                    status |= SW_MODE_SPECIFIC; // PV: speed zero, CSV: following the target
+                }
+            }
+            OperationMode::Homing => match drive.homing_stage() {
+                HomingStage::Done => status |= SW_TARGET_REACHED | SW_MODE_SPECIFIC,
+                HomingStage::Failed => status |= SW_TARGET_REACHED | SW_MODE_ERROR,
+                HomingStage::Idle => status |= SW_TARGET_REACHED,
+                _ => {}
+            },
+            OperationMode::ProfileTorque => status |= SW_TARGET_REACHED,
+            OperationMode::CyclicPosition | OperationMode::CyclicTorque => {
+                status |= SW_TARGET_REACHED | SW_MODE_SPECIFIC; // Following the target
+            }
+        }
+        status
+    }
+
+    /// Returns the size of an object (bytes), None if it doesn't exist.
+    pub fn object_size(index: u16, sub: u8) -> Option<u8> {
+        if index >> 8 == REGISTRY_BASE >> 8 {
+            return params::find(registry_id(index, sub)?).map(|_| 4);
+        }
+        if sub != 0 {
+            return None;
+        }
+        match index {
+            0x1000 | 0x6064 | 0x606C | 0x6075 | 0x607A => Some(4),
+            0x6081 | 0x6083 | 0x6084 | 0x6085 | 0x60FF | 0x6502 => Some(4),
+            0x6040 | 0x6041 | 0x605A | 0x6071 | 0x6077 => Some(2),
+            0x6060 | 0x6061 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Reads an object (SDO upload or TPDO), the value is sign or zero extended to i32.
+    pub fn read(&self, drive: &MotorController, index: u16, sub: u8) -> Result<i32, SdoAbort> {
+        if index >> 8 == REGISTRY_BASE >> 8 {
+            let id = registry_id(index, sub).ok_or(SdoAbort::NoObject)?;
+            return drive.get_param(id).map_err(SdoAbort::from);
+        }
+        if Self::object_size(index, sub).is_none() {
+            return Err(if sub != 0 && Self::object_size(index, 0).is_some() {
+                SdoAbort::NoSubIndex
+            } else {
+                SdoAbort::NoObject
+            });
+        }
+        let value = match index {
+            0x1000 => DEVICE_TYPE as i32,
+            0x6040 => self.controlword as i32,
+            0x6041 => self.statusword(drive) as i32,
+            0x605A => self.quick_stop_option as i32,
+            0x6060 | 0x6061 => self.mode as i32,
+            0x6064 => drive.position(),
+            0x606C => drive.velocity(),
+            0x6071 => self.target_torque as i32,
+            0x6075 => self.rated_current as i32,
+            0x6077 => {
+                let torque = drive.torque_current() as i64 * 1000 / self.rated_current.max(1) as i64;
+                torque.clamp(i16::MIN as i64, i16::MAX as i64) as i32
+            }
+            0x607A => self.target_position,
+            0x6081 => drive.get_param(0x0200)?,
+            0x6083 => drive.get_param(0x0201)?,
+            0x6084 => drive.get_param(0x0202)?,
+            0x6085 => drive.get_param(0x0210)?,
+            0x60FF => self.target_velocity,
+            0x6502 => SUPPORTED_MODES as i32,
+            _ => return Err(SdoAbort::NoObject),
+        };
+        Ok(value)
+    }
+
+    /// Writes an object (SDO download or RPDO), the value is sign or zero extended to i32.
+    pub fn write(
+        &mut self,
+        drive: &mut MotorController,
+        index: u16,
+        sub: u8,
+        value: i32,
+    ) -> Result<(), SdoAbort> {
+        if index >> 8 == REGISTRY_BASE >> 8 {
+            let id = registry_id(index, sub).ok_or(SdoAbort::NoObject)?;
+            return drive.set_param(id, value).map_err(SdoAbort::from);
+        }
+        if Self::object_size(index, sub).is_none() {
+            return Err(SdoAbort::NoObject);
+        }
+        let u16_value = || u16::try_from(value).map_err(|_| SdoAbort::ValueRange);
+        let i16_value = || i16::try_from(value).map_err(|_| SdoAbort::ValueRange);
+        match index {
+            0x6040 => self.controlword = u16_value()?,
+            0x605A => {
+                let option = i16_value()?;
+                if !(0..=8).contains(&option) {
+                    return Err(SdoAbort::ValueRange);
+                }
+                if self.state == Cia402State::QuickStopActive {
+                    return Err(SdoAbort::DeviceState);
+                }
+                self.quick_stop_option = option;
+            }
+            0x6060 => {
+                let mode = OperationMode::from_value(value).ok_or(SdoAbort::ValueRange)?;
+                if mode != self.mode {
+                    self.mode = mode;
+                    self.moved = false;
+                    self.setpoint_ack = false;
+                }
+            }
+            0x6071 => self.target_torque = i16_value()?,
+            0x6075 => {
+                if value <= 0 {
+                    return Err(SdoAbort::ValueRange);
+                }
+                self.rated_current = value as u32;
+            }
+            0x607A => self.target_position = value,
+            0x6081 => drive.set_param(0x0200, value)?,
+            0x6083 => drive.set_param(0x0201, value)?,
+            0x6084 => drive.set_param(0x0202, value)?,
+            0x6085 => drive.set_param(0x0210, value)?,
+            0x60FF => self.target_velocity = value,
+            _ => return Err(SdoAbort::ReadOnly),
+        }
+        Ok(())
+    }
+}
+
+impl Default for Cia402 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the registry id of a manufacturer object.
+#[inline(always)]
+fn registry_id(index: u16, sub: u8) -> Option<u16> {
+    let group = index.checked_sub(REGISTRY_BASE)?;
+    if group > 0xFF {
+        return None;
+    }
+    Some(group << 8 | sub as u16)
+}
@@ -0,0 +1,458 @@
+// Implements DroneCAN (UAVCAN v0) ESC and actuator support, so a TunePulse board acts as a smart
+// servo or ESC on UAV buses, with the CAN transport (frames, tail bytes, node id) left to the HAL.
+
+// Key Features:
+// - DSDL bit stream codec: MSB-first bits, little endian fields, tail array optimization.
+// - Decoders of uavcan.equipment.esc.RawCommand and uavcan.equipment.actuator.ArrayCommand.
+// - Encoders of uavcan.equipment.esc.Status and uavcan.equipment.actuator.Status.
+// - Commands mapped onto the controller setpoints, each accepted command feeds the watchdog.
+// - Telemetry: supply voltage, bus current, winding temperature, speed, angle, force, load.
+// - Transfer CRC (CRC-16/CCITT seeded with the data type signature) for multi-frame transfers.
+
+// Detailed Operation:
+// The HAL reassembles a transfer and calls `on_message` with its data type id and payload. An ESC
+// raw command carries one int14 per ESC of the vehicle; the entry at `esc_index` is scaled from
+// -8192..8191 to `raw_full_scale` and applied as a velocity (counts/s) or torque current (mA). An
+// actuator array command carries (id, type, float16 value) entries; the one matching `actuator_id`
+// is applied as load angle (rad), torque (N·m), speed (rad/s) or a unitless -1..1 position over
+// `unitless_span` counts. Commands are ignored while the controller isn't ready; refused ones
+// (e.g. torque without a torque constant) are counted as errors and reported in the ESC status.
+// `tick` runs once per control tick and returns true every `status_period` ticks, the HAL then
+// publishes the ESC and actuator status encoded by `esc_status` / `actuator_status`. Fields whose
+// bit length isn't a multiple of 8 place their last byte in its upper bits, following libcanard,
+// so int14 / int18 values interoperate with the reference implementation. Only the DSDL v0 layout
+// is covered, Cyphal (UAVCAN v1) messages use a different serialization.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::interface::crc::{crc16_ccitt, CRC16_CCITT_INIT};
+use crate::math_integer::interface::float16::{from_f16, to_f16};
+use crate::motor_driver::DriverStatus;
+use crate::MotorController;
+
+/// Data type id of uavcan.equipment.esc.RawCommand
+pub const ESC_RAW_COMMAND_ID: u16 = 1030;
+/// Data type signature of uavcan.equipment.esc.RawCommand
+pub const ESC_RAW_COMMAND_SIGNATURE: u64 = 0x217F_5C87_D7EC_951D;
+/// Data type id of uavcan.equipment.esc.Status
+pub const ESC_STATUS_ID: u16 = 1034;
+/// Data type signature of uavcan.equipment.esc.Status
+pub const ESC_STATUS_SIGNATURE: u64 = 0xA9AF_28AE_A2FB_B254;
+/// Data type id of uavcan.equipment.actuator.ArrayCommand
+pub const ACTUATOR_ARRAY_COMMAND_ID: u16 = 1010;
+/// Data type signature of uavcan.equipment.actuator.ArrayCommand
+pub const ACTUATOR_ARRAY_COMMAND_SIGNATURE: u64 = 0xD8A7_4862_38EC_3AF3;
+/// Data type id of uavcan.equipment.actuator.Status
+pub const ACTUATOR_STATUS_ID: u16 = 1011;
+/// Data type signature of uavcan.equipment.actuator.Status
+pub const ACTUATOR_STATUS_SIGNATURE: u64 = 0x5E9B_BA44_FAF1_EA04;
+
+/// Maximum number of ESC entries of a raw command
+pub const RAW_COMMAND_MAX: usize = 20;
+/// Raw command value of the full scale setpoint
+pub const RAW_COMMAND_FULL_SCALE: i32 = 8191;
+/// Maximum number of entries of an actuator array command
+pub const ACTUATOR_COMMAND_MAX: usize = 15;
+/// Encoded size of the ESC status (bytes, 110 bits)
+pub const ESC_STATUS_LEN: usize = 14;
+/// Encoded size of the actuator status (bytes)
+pub const ACTUATOR_STATUS_LEN: usize = 8;
+
+/// Bits of one raw command entry
+const RAW_COMMAND_BITS: usize = 14;
+/// Bits of one actuator command entry
+const ACTUATOR_COMMAND_BITS: usize = 32;
+
+/// Computes the CRC of a multi-frame transfer: CRC-16/CCITT over the signature and the payload.
+pub fn transfer_crc(signature: u64, payload: &[u8]) -> u16 {
+    crc16_ccitt(payload, crc16_ccitt(&signature.to_le_bytes(), CRC16_CCITT_INIT))
+}
+
+// ####### Bit stream #######
+
+/// Writes DSDL fields into a payload buffer.
+struct BitWriter<'a> {
+    buffer: &'a mut [u8],
+    offset: usize, // Next bit to write
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    /// Writes the lower `bits` (1..=64) of `value`.
+    fn write(&mut self, value: u64, bits: usize) {
+        let mut storage = value.to_le_bytes();
+        if !bits.is_multiple_of(8) {
+            storage[bits / 8] <<= 8 - bits % 8; // libcanard: last byte in the upper bits
+        }
+        for bit in 0..bits {
+            let set = (storage[bit / 8] >> (7 - bit % 8)) & 1 != 0;
+            let position = self.offset + bit;
+            let mask = 0x80 >> (position % 8);
+            if set {
+                self.buffer[position / 8] |= mask;
+            } else {
+                self.buffer[position / 8] &= !mask;
+            }
+        }
+        self.offset += bits;
+    }
+}
+
+/// Reads DSDL fields from a payload, bits past its end read as zero.
+struct BitReader<'a> {
+    payload: &'a [u8],
+    offset: usize, // Next bit to read
+}
+
+impl<'a> BitReader<'a> {
+    fn new(payload: &'a [u8]) -> Self {
+        Self { payload, offset: 0 }
+    }
+
+    /// Reads an unsigned field of `bits` (1..=64).
+    fn read(&mut self, bits: usize) -> u64 {
+        let mut storage = [0u8; 8];
+        for bit in 0..bits {
+            let position = self.offset + bit;
+            let set = self.payload.get(position / 8).is_some_and(|byte| byte & (0x80 >> (position % 8)) != 0);
+            if set {
+                storage[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+        if !bits.is_multiple_of(8) {
+            storage[bits / 8] >>= 8 - bits % 8;
+        }
+        self.offset += bits;
+        u64::from_le_bytes(storage)
+    }
+
+    /// Reads a two's complement field of `bits` (1..=64).
+    fn read_signed(&mut self, bits: usize) -> i64 {
+        let shift = 64 - bits as u32;
+        ((self.read(bits) << shift) as i64) >> shift
+    }
+}
+
+// ####### Messages #######
+
+/// uavcan.equipment.esc.RawCommand: one setpoint per ESC of the vehicle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawCommand {
+    /// Number of valid entries
+    pub count: usize,
+    /// Setpoints (-8192..8191, negative - reverse)
+    pub cmd: [i16; RAW_COMMAND_MAX],
+}
+
+/// Decodes an ESC raw command (tail array: the entry count follows from the payload length).
+pub fn decode_raw_command(payload: &[u8]) -> RawCommand {
+    let count = (payload.len() * 8 / RAW_COMMAND_BITS).min(RAW_COMMAND_MAX);
+    let mut reader = BitReader::new(payload);
+    let mut cmd = [0; RAW_COMMAND_MAX];
+    for value in cmd.iter_mut().take(count) {
+        *value = reader.read_signed(RAW_COMMAND_BITS) as i16;
+    }
+    RawCommand { count, cmd }
+}
+
+/// Meaning of an actuator command value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActuatorCommandType {
+    /// Normalized -1..1
+    Unitless,
+    /// Angle (rad) or travel (m)
+    Position,
+    /// Torque (N·m) or force (N)
+    Force,
+    /// Angular (rad/s) or linear (m/s) speed
+    Speed,
+}
+
+impl ActuatorCommandType {
+    /// Returns the type for its DSDL value, None if not supported.
+    pub const fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ActuatorCommandType::Unitless),
+            1 => Some(ActuatorCommandType::Position),
+            2 => Some(ActuatorCommandType::Force),
+            3 => Some(ActuatorCommandType::Speed),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of uavcan.equipment.actuator.ArrayCommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActuatorCommand {
+    /// Addressed actuator
+    pub actuator_id: u8,
+    /// Meaning of the value (see ActuatorCommandType)
+    pub command_type: u8,
+    /// Setpoint as binary16
+    pub value: u16,
+}
+
+/// Decodes the entries of an actuator array command into `commands`, returns their number.
+pub fn decode_array_command(payload: &[u8], commands: &mut [ActuatorCommand; ACTUATOR_COMMAND_MAX]) -> usize {
+    let count = (payload.len() * 8 / ACTUATOR_COMMAND_BITS).min(ACTUATOR_COMMAND_MAX);
+    let mut reader = BitReader::new(payload);
+    for command in commands.iter_mut().take(count) {
+        *command = ActuatorCommand {
+            actuator_id: reader.read(8) as u8,
+            command_type: reader.read(8) as u8,
+            value: reader.read(16) as u16,
+        };
+    }
+    count
+}
+
+/// uavcan.equipment.esc.Status contents, in controller units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscStatus {
+    /// Refused commands since startup
+    pub error_count: u32,
+    /// Supply voltage (mV)
+    pub voltage_mv: i32,
+    /// Supply current (mA)
+    pub current_ma: i32,
+    /// Temperature (0.1 °C)
+    pub temperature: i32,
+    /// Motor speed (rpm)
+    pub rpm: i32,
+    /// Current relative to the maximum (0..127 %)
+    pub power_rating_pct: u8,
+    /// Index of the ESC in raw commands
+    pub esc_index: u8,
+}
+
+/// Encodes an ESC status into `buffer`.
+pub fn encode_esc_status(status: &EscStatus, buffer: &mut [u8; ESC_STATUS_LEN]) {
+    let kelvin = status.temperature as i64 * 10 + 27315; // 0.01 K
+    let mut writer = BitWriter::new(buffer);
+    writer.write(status.error_count as u64, 32);
+    writer.write(to_f16(status.voltage_mv as i64, 1000) as u64, 16);
+    writer.write(to_f16(status.current_ma as i64, 1000) as u64, 16);
+    writer.write(to_f16(kelvin, 100) as u64, 16);
+    writer.write(status.rpm.clamp(-(1 << 17), (1 << 17) - 1) as u64 & 0x3FFFF, 18);
+    writer.write(status.power_rating_pct.min(127) as u64, 7);
+    writer.write(status.esc_index as u64 & 0x1F, 5);
+    writer.write(0, 2); // Padding to the byte boundary
+}
+
+/// uavcan.equipment.actuator.Status contents, in controller units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActuatorStatus {
+    /// Reporting actuator
+    pub actuator_id: u8,
+    /// Load angle (mrad)
+    pub position_mrad: i32,
+    /// Load torque (mN·m)
+    pub force_mnm: i32,
+    /// Load speed (mrad/s)
+    pub speed_mrad: i32,
+    /// Current relative to the maximum (0..127 %)
+    pub power_rating_pct: u8,
+}
+
+/// Encodes an actuator status into `buffer`.
+pub fn encode_actuator_status(status: &ActuatorStatus, buffer: &mut [u8; ACTUATOR_STATUS_LEN]) {
+    let mut writer = BitWriter::new(buffer);
+    writer.write(status.actuator_id as u64, 8);
+    writer.write(to_f16(status.position_mrad as i64, 1000) as u64, 16);
+    writer.write(to_f16(status.force_mnm as i64, 1000) as u64, 16);
+    writer.write(to_f16(status.speed_mrad as i64, 1000) as u64, 16);
+    writer.write(0, 1); // void1
+    writer.write(status.power_rating_pct.min(127) as u64, 7);
+}
+
+// ####### Node #######
+
+/// Meaning of the ESC raw command setpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawCommandMode {
+    /// Velocity, full scale in counts/s
+    Velocity,
+    /// Torque current, full scale in mA
+    Torque,
+}
+
+/// DroneCAN node configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DroneCanConfig {
+    /// Index of this ESC in raw commands (0..19)
+    pub esc_index: u8,
+    /// Actuator id matched in array commands
+    pub actuator_id: u8,
+    /// Setpoint type of raw commands
+    pub raw_mode: RawCommandMode,
+    /// Setpoint of a raw command of 8191 (counts/s or mA)
+    pub raw_full_scale: i32,
+    /// Travel of a unitless command from 0 to 1 (counts)
+    pub unitless_span: i32,
+    /// Current reported as 100 % power rating (mA)
+    pub max_current_ma: i32,
+    /// Status publication period (control ticks, 0 - never)
+    pub status_period: u32,
+}
+
+impl DroneCanConfig {
+    /// ESC 0 / actuator 0, velocity raw commands up to 10 rev/s, half a turn unitless span.
+    pub const fn new() -> Self {
+        Self {
+            esc_index: 0,
+            actuator_id: 0,
+            raw_mode: RawCommandMode::Velocity,
+            raw_full_scale: 10 * 65536,
+            unitless_span: 32768,
+            max_current_ma: 1000,
+            status_period: 0,
+        }
+    }
+}
+
+impl Default for DroneCanConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// DroneCAN ESC and actuator node.
+pub struct DroneCan {
+    config: DroneCanConfig,
+    error_count: u32, // Refused commands
+    ticks: u32,       // Ticks since the last status
+}
+
+impl DroneCan {
+    /// Creates the node with the given configuration.
+    pub const fn new(config: DroneCanConfig) -> Self {
+        Self { config, error_count: 0, ticks: 0 }
+    }
+
+    /// Replaces the configuration.
+    pub fn set_config(&mut self, config: DroneCanConfig) {
+        self.config = config;
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> DroneCanConfig {
+        self.config
+    }
+
+    /// Returns the number of refused commands.
+    pub fn error_count(&self) -> u32 {
+        self.error_count
+    }
+
+    /// Counts one control tick, returns true when the status is due for publication.
+    pub fn tick(&mut self) -> bool {
+        if self.config.status_period == 0 {
+            return false;
+        }
+        self.ticks += 1;
+        if self.ticks < self.config.status_period {
+            return false;
+        }
+        self.ticks = 0;
+        true
+    }
+
+    /// Handles a received transfer, returns true if it carried a command for this node.
+    pub fn on_message(&mut self, drive: &mut MotorController, data_type_id: u16, payload: &[u8]) -> bool {
+        if drive.status() != DriverStatus::Ready {
+            return false;
+        }
+        let applied = match data_type_id {
+            ESC_RAW_COMMAND_ID => self.raw_command(drive, payload),
+            ACTUATOR_ARRAY_COMMAND_ID => self.array_command(drive, payload),
+            _ => return false,
+        };
+        match applied {
+            Some(true) => {
+                drive.feed_watchdog();
+                true
+            }
+            Some(false) => {
+                self.error_count = self.error_count.wrapping_add(1);
+                defmt::warn!("DRONECAN: Command of type {} refused", data_type_id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the raw command entry of this ESC, None if the command has none.
+    fn raw_command(&mut self, drive: &mut MotorController, payload: &[u8]) -> Option<bool> {
+        let command = decode_raw_command(payload);
+        let index = self.config.esc_index as usize;
+        if index >= command.count {
+            return None;
+        }
+        let scaled = command.cmd[index] as i64 * self.config.raw_full_scale as i64 / RAW_COMMAND_FULL_SCALE as i64;
+        let setpoint = scaled.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        match self.config.raw_mode {
+            RawCommandMode::Velocity => drive.set_velocity(setpoint),
+            RawCommandMode::Torque => drive.set_torque(setpoint),
+        }
+        Some(true)
+    }
+
+    /// Applies the array command entry of this actuator, None if the command has none.
+    fn array_command(&mut self, drive: &mut MotorController, payload: &[u8]) -> Option<bool> {
+        let mut commands = [ActuatorCommand { actuator_id: 0, command_type: 0, value: 0 }; ACTUATOR_COMMAND_MAX];
+        let count = decode_array_command(payload, &mut commands);
+        let command = commands[..count].iter().find(|command| command.actuator_id == self.config.actuator_id)?;
+        let Some(kind) = ActuatorCommandType::from_value(command.command_type) else {
+            return Some(false);
+        };
+        match kind {
+            ActuatorCommandType::Unitless => {
+                let span = self.config.unitless_span as i64;
+                let target = (from_f16(command.value, 1 << 16) as i64 * span) >> 16;
+                drive.set_target_position(target.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+            }
+            ActuatorCommandType::Position => drive.set_target_position_mrad(from_f16(command.value, 1000)),
+            ActuatorCommandType::Force => return Some(drive.set_torque_mnm(from_f16(command.value, 1000))),
+            ActuatorCommandType::Speed => drive.set_velocity_mrad(from_f16(command.value, 1000)),
+        }
+        Some(true)
+    }
+
+    /// Returns the load of the torque current command relative to the maximum current (0..127 %).
+    fn power_rating(&self, drive: &MotorController) -> u8 {
+        let max = self.config.max_current_ma.max(1) as i64;
+        (drive.torque_current().unsigned_abs() as i64 * 100 / max).min(127) as u8
+    }
+
+    /// Encodes the ESC status of the drive into `buffer`.
+    pub fn esc_status(&self, drive: &MotorController, buffer: &mut [u8; ESC_STATUS_LEN]) {
+        let status = EscStatus {
+            error_count: self.error_count,
+            voltage_mv: drive.supply_mv(),
+            current_ma: drive.bus_power().current_ma,
+            temperature: drive.winding_temperature().0,
+            rpm: (drive.velocity() as i64 * 60 / 65536) as i32,
+            power_rating_pct: self.power_rating(drive),
+            esc_index: self.config.esc_index,
+        };
+        encode_esc_status(&status, buffer);
+    }
+
+    /// Encodes the actuator status of the drive into `buffer`.
+    pub fn actuator_status(&self, drive: &MotorController, buffer: &mut [u8; ACTUATOR_STATUS_LEN]) {
+        let status = ActuatorStatus {
+            actuator_id: self.config.actuator_id,
+            position_mrad: drive.position_mrad().clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            force_mnm: drive.torque_mnm(),
+            speed_mrad: drive.velocity_mrad(),
+            power_rating_pct: self.power_rating(drive),
+        };
+        encode_actuator_status(&status, buffer);
+    }
+}
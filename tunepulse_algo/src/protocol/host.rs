@@ -0,0 +1,407 @@
+// Implements the host protocol, a compact binary command set in COBS framed packets with a CRC-16,
+// independent of the byte transport (UART, USB CDC, RTT down-channel).
+
+// Key Features:
+// - COBS framing: frames end at a 0x00 delimiter, a lost byte only costs the frame it was in.
+// - Packets: command, sequence number, payload and CRC-16/CCITT, little endian fields.
+// - Commands: ping, parameter get/set by registry id, stop/release/fault reset, torque,
+//   velocity, position and move setpoints, telemetry subscription.
+// - Telemetry packets with a selectable set of channels at a period in control ticks.
+// - Usable next to the step/dir input: parameters and telemetry don't touch the motion source.
+
+// Detailed Operation:
+// The HAL pushes every received byte into `receive`, which collects them until a delimiter, then
+// decodes the COBS frame and checks the CRC; broken or oversized frames are dropped silently, the
+// host retries on the missing response. A valid request is executed and answered with a packet of
+// the same command and sequence number: status byte (HostStatus), then the response data. `tick`
+// runs once per control tick and builds a telemetry packet when the subscription period elapsed.
+// Both return the number of bytes written to `tx` (a complete frame with its delimiter), 0 if there
+// is nothing to send. Packet layout before COBS encoding:
+// command u8 | sequence u8 | payload (0..MAX_PAYLOAD) | crc u16 over command, sequence and payload.
+// Requests, payload -> response data:
+// 0x01 ping -> protocol version u8
+// 0x10 get parameter: id u16 -> value i32
+// 0x11 set parameter: id u16, value i32
+// 0x20 stop: mode u8 (0 coast, 1 brake, 2 ramp); 0x21 release stop; 0x22 reset e-stop and fault
+// 0x30 torque (mA), 0x31 velocity (counts/s), 0x32 position (counts), 0x33 move to (counts): i32
+// 0x40 subscribe: channels u16 (TLM_* bits), period u16 (ticks, 0 - off)
+// Telemetry: command 0x80, running sequence, channels u16, then one i32 per set bit, lowest first.
+// In step/dir operation parameter access and telemetry leave the step input in control; a stop or
+// setpoint command takes over (the motion source returns to internal), and writing parameter
+// 0x0802 "motion_source" switches between the two at run time.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::interface::crc::{crc16_ccitt, CRC16_CCITT_INIT};
+use crate::motor_driver::{DriverStatus, MotionSource, StopMode};
+use crate::params::{self, ParamError};
+use crate::MotorController;
+
+/// Version of the command set, reported by ping
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Maximum payload of a packet (bytes)
+pub const MAX_PAYLOAD: usize = 48;
+/// Maximum packet size: command, sequence, payload, CRC (bytes)
+pub const MAX_PACKET: usize = MAX_PAYLOAD + 4;
+/// Maximum frame size: COBS overhead and delimiter included (bytes)
+pub const MAX_FRAME: usize = MAX_PACKET + MAX_PACKET / 254 + 2;
+
+/// Command: ping
+pub const CMD_PING: u8 = 0x01;
+/// Command: get a parameter
+pub const CMD_GET_PARAM: u8 = 0x10;
+/// Command: set a parameter
+pub const CMD_SET_PARAM: u8 = 0x11;
+/// Command: stop the axis
+pub const CMD_STOP: u8 = 0x20;
+/// Command: release the stop
+pub const CMD_RELEASE: u8 = 0x21;
+/// Command: reset a latched e-stop and a driver fault
+pub const CMD_RESET: u8 = 0x22;
+/// Command: torque setpoint
+pub const CMD_TORQUE: u8 = 0x30;
+/// Command: velocity setpoint
+pub const CMD_VELOCITY: u8 = 0x31;
+/// Command: position setpoint
+pub const CMD_POSITION: u8 = 0x32;
+/// Command: point-to-point move
+pub const CMD_MOVE: u8 = 0x33;
+/// Command: telemetry subscription
+pub const CMD_SUBSCRIBE: u8 = 0x40;
+/// Packet: telemetry (sent by the controller)
+pub const CMD_TELEMETRY: u8 = 0x80;
+
+/// Telemetry channel: position from zero (counts)
+pub const TLM_POSITION: u16 = 1 << 0;
+/// Telemetry channel: measured velocity (counts/s)
+pub const TLM_VELOCITY: u16 = 1 << 1;
+/// Telemetry channel: torque current command (mA)
+pub const TLM_CURRENT: u16 = 1 << 2;
+/// Telemetry channel: supply voltage (mV)
+pub const TLM_SUPPLY: u16 = 1 << 3;
+/// Telemetry channel: DC-bus current (mA)
+pub const TLM_BUS_CURRENT: u16 = 1 << 4;
+/// Telemetry channel: winding temperature (0.1 °C)
+pub const TLM_TEMPERATURE: u16 = 1 << 5;
+/// Telemetry channel: driver status (0 calibrating, 1 ready, 2 error, +4 e-stop latched, +8 step/dir)
+pub const TLM_STATUS: u16 = 1 << 6;
+/// Telemetry channel: step position of the step/dir input (steps)
+pub const TLM_STEPS: u16 = 1 << 7;
+/// Number of telemetry channels
+const TLM_CHANNELS: u32 = 8;
+
+/// Status byte of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostStatus {
+    /// Executed
+    Ok = 0,
+    /// Unknown command
+    UnknownCommand = 1,
+    /// Payload length doesn't match the command
+    BadLength = 2,
+    /// No parameter with this id
+    UnknownId = 3,
+    /// The parameter can't be written
+    ReadOnly = 4,
+    /// The value is outside the valid range
+    OutOfRange = 5,
+    /// The value was refused by the controller
+    Rejected = 6,
+    /// The command isn't possible in the present state (e.g. setpoint while not ready)
+    Refused = 7,
+}
+
+impl From<ParamError> for HostStatus {
+    fn from(error: ParamError) -> Self {
+        match error {
+            ParamError::UnknownId => HostStatus::UnknownId,
+            ParamError::ReadOnly => HostStatus::ReadOnly,
+            ParamError::OutOfRange => HostStatus::OutOfRange,
+            ParamError::Rejected => HostStatus::Rejected,
+        }
+    }
+}
+
+// ####### Framing #######
+
+/// COBS encodes `packet` into `frame` followed by the delimiter, returns the frame length.
+///
+/// Returns None if the frame doesn't fit.
+pub fn encode_frame(packet: &[u8], frame: &mut [u8]) -> Option<usize> {
+    if frame.len() < packet.len() + packet.len() / 254 + 2 {
+        return None;
+    }
+    let mut code_index = 0; // Position of the pending code byte
+    let mut index = 1;
+    let mut code = 1u8;
+    for &byte in packet {
+        if byte != 0 {
+            frame[index] = byte;
+            index += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            frame[code_index] = code;
+            code_index = index;
+            index += 1;
+            code = 1;
+        }
+    }
+    frame[code_index] = code;
+    frame[index] = 0;
+    Some(index + 1)
+}
+
+/// Decodes a COBS frame (without the delimiter) in place, returns the packet length.
+///
+/// Returns None on a malformed frame.
+pub fn decode_frame(frame: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    let mut write = 0;
+    while read < frame.len() {
+        let code = frame[read] as usize;
+        if code == 0 || read + code > frame.len() {
+            return None;
+        }
+        for offset in 1..code {
+            frame[write] = frame[read + offset];
+            write += 1;
+        }
+        read += code;
+        if code != 0xFF && read < frame.len() {
+            frame[write] = 0;
+            write += 1;
+        }
+    }
+    Some(write)
+}
+
+// ####### Packet builder #######
+
+/// Collects a packet, then frames it into the transmit buffer.
+struct Packet {
+    data: [u8; MAX_PACKET],
+    len: usize,
+}
+
+impl Packet {
+    fn new(command: u8, sequence: u8) -> Self {
+        let mut data = [0; MAX_PACKET];
+        data[0] = command;
+        data[1] = sequence;
+        Self { data, len: 2 }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.data[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    /// Appends the CRC and encodes the frame into `tx`, returns its length.
+    fn finish(mut self, tx: &mut [u8; MAX_FRAME]) -> usize {
+        let crc = crc16_ccitt(&self.data[..self.len], CRC16_CCITT_INIT);
+        self.push(&crc.to_le_bytes());
+        encode_frame(&self.data[..self.len], tx).unwrap_or(0)
+    }
+}
+
+// ####### Protocol #######
+
+/// Host protocol endpoint.
+pub struct HostProtocol {
+    rx: [u8; MAX_FRAME], // Bytes of the frame being received
+    rx_len: usize,       // Received bytes
+    overflow: bool,      // Frame too long, dropped up to the next delimiter
+    channels: u16,       // Subscribed telemetry channels
+    period: u16,         // Telemetry period (ticks, 0 - off)
+    ticks: u16,          // Ticks since the last telemetry packet
+    sequence: u8,        // Sequence number of the telemetry packets
+}
+
+impl HostProtocol {
+    /// Creates the endpoint without telemetry subscription.
+    pub const fn new() -> Self {
+        Self {
+            rx: [0; MAX_FRAME],
+            rx_len: 0,
+            overflow: false,
+            channels: 0,
+            period: 0,
+            ticks: 0,
+            sequence: 0,
+        }
+    }
+
+    /// Processes one received byte, returns the length of the response frame written to `tx`.
+    pub fn receive(&mut self, drive: &mut MotorController, byte: u8, tx: &mut [u8; MAX_FRAME]) -> usize {
+        if byte != 0 {
+            if self.rx_len == MAX_FRAME {
+                self.overflow = true;
+            } else {
+                self.rx[self.rx_len] = byte;
+                self.rx_len += 1;
+            }
+            return 0;
+        }
+        let len = self.rx_len;
+        let overflow = self.overflow;
+        self.rx_len = 0;
+        self.overflow = false;
+        if overflow || len == 0 {
+            return 0;
+        }
+        let Some(len) = decode_frame(&mut self.rx[..len]) else {
+            return 0;
+        };
+        if !(4..=MAX_PACKET).contains(&len) {
+            return 0;
+        }
+        let crc = u16::from_le_bytes([self.rx[len - 2], self.rx[len - 1]]);
+        if crc != crc16_ccitt(&self.rx[..len - 2], CRC16_CCITT_INIT) {
+            defmt::warn!("HOST: CRC error");
+            return 0;
+        }
+        let mut payload = [0; MAX_PAYLOAD];
+        payload[..len - 4].copy_from_slice(&self.rx[2..len - 2]);
+        let (command, sequence) = (self.rx[0], self.rx[1]);
+        let mut response = Packet::new(command, sequence);
+        response.push(&[0]); // Status, set below
+        let status = self.execute(drive, command, &payload[..len - 4], &mut response);
+        response.data[2] = status as u8;
+        response.finish(tx)
+    }
+
+    /// Counts one control tick, returns the length of the telemetry frame written to `tx`.
+    pub fn tick(&mut self, drive: &MotorController, tx: &mut [u8; MAX_FRAME]) -> usize {
+        if self.period == 0 || self.channels == 0 {
+            return 0;
+        }
+        self.ticks += 1;
+        if self.ticks < self.period {
+            return 0;
+        }
+        self.ticks = 0;
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut packet = Packet::new(CMD_TELEMETRY, self.sequence);
+        packet.push(&self.channels.to_le_bytes());
+        for channel in 0..TLM_CHANNELS {
+            if self.channels & (1 << channel) != 0 {
+                packet.push(&Self::channel(drive, 1 << channel).to_le_bytes());
+            }
+        }
+        packet.finish(tx)
+    }
+
+    /// Returns the value of one telemetry channel.
+    fn channel(drive: &MotorController, channel: u16) -> i32 {
+        match channel {
+            TLM_POSITION => drive.position(),
+            TLM_VELOCITY => drive.velocity(),
+            TLM_CURRENT => drive.torque_current(),
+            TLM_SUPPLY => drive.supply_mv(),
+            TLM_BUS_CURRENT => drive.bus_power().current_ma,
+            TLM_TEMPERATURE => drive.winding_temperature().0,
+            TLM_STEPS => drive.step_position(),
+            _ => {
+                let status = match drive.status() {
+                    DriverStatus::Calibrating => 0,
+                    DriverStatus::Ready => 1,
+                    DriverStatus::Error => 2,
+                };
+                let estop = if drive.estop_latched() { 4 } else { 0 };
+                let step_dir = if drive.motion_source() == MotionSource::StepDir { 8 } else { 0 };
+                status | estop | step_dir
+            }
+        }
+    }
+
+    /// Executes a request, response data goes into `response`.
+    fn execute(
+        &mut self,
+        drive: &mut MotorController,
+        command: u8,
+        payload: &[u8],
+        response: &mut Packet,
+    ) -> HostStatus {
+        let expected = match command {
+            CMD_PING | CMD_RELEASE | CMD_RESET => 0,
+            CMD_GET_PARAM => 2,
+            CMD_SET_PARAM => 6,
+            CMD_STOP => 1,
+            CMD_TORQUE | CMD_VELOCITY | CMD_POSITION | CMD_MOVE | CMD_SUBSCRIBE => 4,
+            _ => return HostStatus::UnknownCommand,
+        };
+        if payload.len() != expected {
+            return HostStatus::BadLength;
+        }
+        let word = |index: usize| u16::from_le_bytes([payload[index], payload[index + 1]]);
+        let long = |index: usize| {
+            i32::from_le_bytes([payload[index], payload[index + 1], payload[index + 2], payload[index + 3]])
+        };
+        match command {
+            CMD_PING => response.push(&[PROTOCOL_VERSION]),
+            // ####### Parameters #######
+            CMD_GET_PARAM => match params::registry::get(drive, word(0)) {
+                Ok(value) => response.push(&value.to_le_bytes()),
+                Err(error) => return error.into(),
+            },
+            CMD_SET_PARAM => {
+                if let Err(error) = params::registry::set(drive, word(0), long(2)) {
+                    return error.into();
+                }
+            }
+            // ####### Mode changes #######
+            CMD_STOP => {
+                let mode = match payload[0] {
+                    0 => StopMode::Coast,
+                    1 => StopMode::Brake,
+                    2 => StopMode::Ramp,
+                    _ => return HostStatus::OutOfRange,
+                };
+                drive.request_stop(mode);
+            }
+            CMD_RELEASE => {
+                if !drive.release_stop() {
+                    return HostStatus::Refused;
+                }
+            }
+            CMD_RESET => {
+                let estop = drive.reset_estop();
+                if !drive.reset_fault() && !estop {
+                    return HostStatus::Refused;
+                }
+            }
+            // ####### Setpoints #######
+            CMD_TORQUE | CMD_VELOCITY | CMD_POSITION | CMD_MOVE => {
+                if drive.status() != DriverStatus::Ready {
+                    return HostStatus::Refused;
+                }
+                let value = long(0);
+                match command {
+                    CMD_TORQUE => drive.set_torque(value),
+                    CMD_VELOCITY => drive.set_velocity(value),
+                    CMD_POSITION => drive.set_target_position(value),
+                    _ => drive.move_to(value),
+                }
+                drive.feed_watchdog();
+            }
+            // ####### Telemetry #######
+            _ => {
+                self.channels = word(0) & ((1 << TLM_CHANNELS) - 1);
+                self.period = word(2);
+                self.ticks = 0;
+                defmt::info!("HOST: Telemetry channels {:#x} every {} ticks", self.channels, self.period);
+            }
+        }
+        HostStatus::Ok
+    }
+}
+
+impl Default for HostProtocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
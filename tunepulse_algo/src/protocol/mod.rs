@@ -0,0 +1,20 @@
+// Implements the protocol module, the transport independent command layers between a host and
+// the controller: fieldbus drive profiles and their object dictionaries.
+
+// Key Features:
+// - CANopen CiA 402 drive profile: state machine, control/status words, standard objects.
+// - DroneCAN ESC and actuator messages for smart servos on UAV buses, with status telemetry.
+// - Binary host protocol: COBS framed packets with CRC-16 over any byte stream.
+// - Modbus RTU slave with a register map of parameters, setpoints and telemetry.
+// - Text shell with G-code-like commands for bring-up over a serial terminal.
+// - Setpoints and objects mapped onto `MotorController` and the parameter registry.
+// - no_std and allocation free, frames are moved in and out by the HAL.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+pub mod cia402;
+pub mod dronecan;
+pub mod host;
+pub mod modbus;
+pub mod text;
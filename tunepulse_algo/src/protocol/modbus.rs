@@ -0,0 +1,379 @@
+// Implements a Modbus RTU slave, the frame codec and a register map bridging holding and input
+// registers to the parameter registry, setpoints and telemetry for PLC integration.
+
+// Key Features:
+// - RTU frames: slave address, function, data, CRC-16/MODBUS; broadcast writes without reply.
+// - Functions 0x03 / 0x04 read holding / input registers, 0x06 / 0x10 write single / multiple.
+// - Holding registers: command, move / velocity / torque / position setpoints, all parameters.
+// - Input registers: driver status, position, velocity, currents, supply voltage, temperature.
+// - Exception responses for unknown functions, addresses and refused values.
+
+// Detailed Operation:
+// The HAL detects the end of a frame (3.5 character times of silence) and passes the complete frame
+// to `handle`, which checks address and CRC, executes it and writes the response into `response`,
+// returning its length (0 - no response: other slave, broadcast or corrupted frame). 32-bit values
+// occupy two registers, high word at the lower address, and have to be written in one request
+// covering both; a request touching half a value is refused with ILLEGAL_DATA_ADDRESS before
+// anything is applied. Writes are applied in address order, a value refused by the registry stops
+// the request with ILLEGAL_DATA_VALUE (the registers before it stay written). Setpoints are
+// refused while the controller isn't ready (SERVER_DEVICE_FAILURE) and feed the watchdog.
+// Holding registers:
+// 0x0000 command (write): 1 enable, 2 stop ramp, 3 coast, 4 brake, 5 fault reset, 6 set zero
+// 0x0010 move to (counts), 0x0012 velocity (counts/s), 0x0014 torque (mA), 0x0016 position (counts)
+// 0x1000 + 2 * id: registry parameter `id`
+// Input registers:
+// 0x0000 status (0 calibrating, 1 ready, 2 error, +4 e-stop latched), 0x0001 position (counts),
+// 0x0003 velocity (counts/s), 0x0005 torque current (mA), 0x0007 supply (mV),
+// 0x0008 bus current (mA), 0x0009 winding temperature (0.1 °C)
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use defmt_rtt as _; // Use the defmt_rtt crate for logging via RTT (Real-Time Transfer)
+
+use crate::math_integer::interface::crc::crc16_modbus;
+use crate::motor_driver::{DriverStatus, StopMode};
+use crate::params::{self, ParamError};
+use crate::MotorController;
+
+/// Maximum size of an RTU frame (bytes)
+pub const MAX_ADU: usize = 256;
+/// Broadcast slave address
+pub const BROADCAST: u8 = 0;
+
+/// Function: read holding registers
+pub const FN_READ_HOLDING: u8 = 0x03;
+/// Function: read input registers
+pub const FN_READ_INPUT: u8 = 0x04;
+/// Function: write single register
+pub const FN_WRITE_SINGLE: u8 = 0x06;
+/// Function: write multiple registers
+pub const FN_WRITE_MULTIPLE: u8 = 0x10;
+
+/// Holding register: command
+pub const HR_COMMAND: u16 = 0x0000;
+/// Holding registers: point-to-point move target (i32)
+pub const HR_MOVE: u16 = 0x0010;
+/// Holding registers: velocity setpoint (i32)
+pub const HR_VELOCITY: u16 = 0x0012;
+/// Holding registers: torque current setpoint (i32)
+pub const HR_TORQUE: u16 = 0x0014;
+/// Holding registers: position setpoint (i32)
+pub const HR_POSITION: u16 = 0x0016;
+/// Holding registers: first registry parameter (id 0), two registers per id
+pub const HR_PARAM_BASE: u16 = 0x1000;
+
+/// Input register: driver status
+pub const IR_STATUS: u16 = 0x0000;
+/// Input registers: position from zero (i32)
+pub const IR_POSITION: u16 = 0x0001;
+/// Input registers: measured velocity (i32)
+pub const IR_VELOCITY: u16 = 0x0003;
+/// Input registers: torque current command (i32)
+pub const IR_CURRENT: u16 = 0x0005;
+/// Input register: supply voltage
+pub const IR_SUPPLY: u16 = 0x0007;
+/// Input register: DC-bus current
+pub const IR_BUS_CURRENT: u16 = 0x0008;
+/// Input register: winding temperature
+pub const IR_TEMPERATURE: u16 = 0x0009;
+
+/// Maximum registers of one read request
+const MAX_READ: u16 = 125;
+/// Maximum registers of one write request
+const MAX_WRITE: u16 = 123;
+/// Number of setpoint registers behind HR_MOVE
+const SETPOINT_REGISTERS: u16 = 8;
+
+/// Modbus exception code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusException {
+    /// Function not supported
+    IllegalFunction = 0x01,
+    /// Register not mapped, read only, or half of a 32-bit value
+    IllegalDataAddress = 0x02,
+    /// Value refused or malformed request
+    IllegalDataValue = 0x03,
+    /// Not possible in the present state (e.g. setpoint while not ready)
+    ServerDeviceFailure = 0x04,
+}
+
+impl From<ParamError> for ModbusException {
+    fn from(error: ParamError) -> Self {
+        match error {
+            ParamError::UnknownId | ParamError::ReadOnly => ModbusException::IllegalDataAddress,
+            ParamError::OutOfRange | ParamError::Rejected => ModbusException::IllegalDataValue,
+        }
+    }
+}
+
+/// A mapped register: a 16-bit one, or one half of a 32-bit value starting at `base`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    Single,
+    High(u16),
+    Low(u16),
+}
+
+/// Modbus RTU slave.
+pub struct ModbusRtu {
+    address: u8,         // Slave address (1..247)
+    setpoints: [i32; 4], // Last written move, velocity, torque and position setpoints
+}
+
+impl ModbusRtu {
+    /// Creates the slave with the given address.
+    pub const fn new(address: u8) -> Self {
+        Self { address, setpoints: [0; 4] }
+    }
+
+    /// Changes the slave address.
+    pub fn set_address(&mut self, address: u8) {
+        self.address = address;
+    }
+
+    /// Returns the slave address.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Handles one received frame, returns the length of the response written to `response`.
+    pub fn handle(&mut self, drive: &mut MotorController, frame: &[u8], response: &mut [u8; MAX_ADU]) -> usize {
+        if frame.len() < 4 || frame.len() > MAX_ADU {
+            return 0;
+        }
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        if crc16_modbus(body) != u16::from_le_bytes([crc[0], crc[1]]) {
+            defmt::warn!("MODBUS: CRC error");
+            return 0;
+        }
+        let broadcast = body[0] == BROADCAST;
+        if body[0] != self.address && !broadcast {
+            return 0;
+        }
+        let function = body[1];
+        response[0] = self.address;
+        response[1] = function;
+        let result = match function {
+            FN_READ_HOLDING | FN_READ_INPUT if !broadcast => self.read(drive, function, &body[2..], response),
+            FN_WRITE_SINGLE | FN_WRITE_MULTIPLE => self.write(drive, function, &body[2..], response),
+            _ => Err(ModbusException::IllegalFunction),
+        };
+        let len = match result {
+            _ if broadcast => return 0,
+            Ok(len) => len,
+            Err(exception) => {
+                response[1] = function | 0x80;
+                response[2] = exception as u8;
+                3
+            }
+        };
+        let crc = crc16_modbus(&response[..len]);
+        response[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+        len + 2
+    }
+
+    // ####### Functions #######
+
+    /// Reads holding or input registers into the response, returns its length before the CRC.
+    fn read(
+        &self,
+        drive: &MotorController,
+        function: u8,
+        data: &[u8],
+        response: &mut [u8; MAX_ADU],
+    ) -> Result<usize, ModbusException> {
+        if data.len() != 4 {
+            return Err(ModbusException::IllegalDataValue);
+        }
+        let start = u16::from_be_bytes([data[0], data[1]]);
+        let count = u16::from_be_bytes([data[2], data[3]]);
+        if count == 0 || count > MAX_READ {
+            return Err(ModbusException::IllegalDataValue);
+        }
+        response[2] = (count * 2) as u8;
+        for offset in 0..count {
+            let address = start.checked_add(offset).ok_or(ModbusException::IllegalDataAddress)?;
+            let value = if function == FN_READ_INPUT {
+                Self::input_register(drive, address)?
+            } else {
+                self.holding_register(drive, address)?
+            };
+            let index = 3 + offset as usize * 2;
+            response[index..index + 2].copy_from_slice(&value.to_be_bytes());
+        }
+        Ok(3 + count as usize * 2)
+    }
+
+    /// Writes one or more holding registers, returns the response length before the CRC.
+    fn write(
+        &mut self,
+        drive: &mut MotorController,
+        function: u8,
+        data: &[u8],
+        response: &mut [u8; MAX_ADU],
+    ) -> Result<usize, ModbusException> {
+        let mut values = [0u16; MAX_WRITE as usize];
+        let (start, count) = if function == FN_WRITE_SINGLE {
+            if data.len() != 4 {
+                return Err(ModbusException::IllegalDataValue);
+            }
+            values[0] = u16::from_be_bytes([data[2], data[3]]);
+            (u16::from_be_bytes([data[0], data[1]]), 1)
+        } else {
+            if data.len() < 5 {
+                return Err(ModbusException::IllegalDataValue);
+            }
+            let count = u16::from_be_bytes([data[2], data[3]]);
+            let bytes = count as usize * 2;
+            if count == 0 || count > MAX_WRITE || data[4] as usize != bytes || data.len() != 5 + bytes {
+                return Err(ModbusException::IllegalDataValue);
+            }
+            for (index, value) in values.iter_mut().take(count as usize).enumerate() {
+                *value = u16::from_be_bytes([data[5 + index * 2], data[6 + index * 2]]);
+            }
+            (u16::from_be_bytes([data[0], data[1]]), count)
+        };
+        let values = &values[..count as usize];
+
+        // Validate the whole request first: only complete 32-bit values
+        let mut offset = 0;
+        while offset < count {
+            let address = start.checked_add(offset).ok_or(ModbusException::IllegalDataAddress)?;
+            offset += match Self::holding_map(address) {
+                Some(Register::Single) => 1,
+                Some(Register::High(_)) if offset + 1 < count => 2,
+                _ => return Err(ModbusException::IllegalDataAddress),
+            };
+        }
+
+        let mut offset = 0;
+        while offset < count {
+            let index = offset as usize;
+            match Self::holding_map(start + offset) {
+                Some(Register::Single) => {
+                    Self::command(drive, values[index])?;
+                    offset += 1;
+                }
+                Some(Register::High(base)) => {
+                    let value = ((values[index] as u32) << 16 | values[index + 1] as u32) as i32;
+                    self.write_value(drive, base, value)?;
+                    offset += 2;
+                }
+                _ => return Err(ModbusException::IllegalDataAddress),
+            }
+        }
+        // Both functions echo the start address and the count or value
+        response[2..4].copy_from_slice(&start.to_be_bytes());
+        let echo = if function == FN_WRITE_SINGLE { values[0] } else { count };
+        response[4..6].copy_from_slice(&echo.to_be_bytes());
+        Ok(6)
+    }
+
+    // ####### Register map #######
+
+    /// Returns the holding register at `address`, None if not mapped.
+    fn holding_map(address: u16) -> Option<Register> {
+        match address {
+            HR_COMMAND => Some(Register::Single),
+            _ if (HR_MOVE..HR_MOVE + SETPOINT_REGISTERS).contains(&address) => {
+                let base = address & !1;
+                if address == base { Some(Register::High(base)) } else { Some(Register::Low(base)) }
+            }
+            _ if address >= HR_PARAM_BASE => {
+                let id = (address - HR_PARAM_BASE) / 2;
+                params::find(id)?;
+                let base = HR_PARAM_BASE + id * 2;
+                if address == base { Some(Register::High(base)) } else { Some(Register::Low(base)) }
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads one holding register.
+    fn holding_register(&self, drive: &MotorController, address: u16) -> Result<u16, ModbusException> {
+        let (base, high) = match Self::holding_map(address).ok_or(ModbusException::IllegalDataAddress)? {
+            Register::Single => return Ok(0),
+            Register::High(base) => (base, true),
+            Register::Low(base) => (base, false),
+        };
+        let value = if base < HR_PARAM_BASE {
+            self.setpoints[((base - HR_MOVE) / 2) as usize]
+        } else {
+            params::registry::get(drive, (base - HR_PARAM_BASE) / 2)?
+        };
+        Ok(Self::half(value, high))
+    }
+
+    /// Applies a 32-bit holding value.
+    fn write_value(&mut self, drive: &mut MotorController, base: u16, value: i32) -> Result<(), ModbusException> {
+        if base >= HR_PARAM_BASE {
+            return Ok(params::registry::set(drive, (base - HR_PARAM_BASE) / 2, value)?);
+        }
+        if drive.status() != DriverStatus::Ready {
+            return Err(ModbusException::ServerDeviceFailure);
+        }
+        match base {
+            HR_MOVE => drive.move_to(value),
+            HR_VELOCITY => drive.set_velocity(value),
+            HR_TORQUE => drive.set_torque(value),
+            _ => drive.set_target_position(value),
+        }
+        drive.feed_watchdog();
+        self.setpoints[((base - HR_MOVE) / 2) as usize] = value;
+        Ok(())
+    }
+
+    /// Executes a value written to the command register.
+    fn command(drive: &mut MotorController, command: u16) -> Result<(), ModbusException> {
+        match command {
+            1 => {
+                if drive.estop_latched() || drive.status() != DriverStatus::Ready {
+                    return Err(ModbusException::ServerDeviceFailure);
+                }
+                drive.release_stop();
+            }
+            2 => drive.request_stop(StopMode::Ramp),
+            3 => drive.request_stop(StopMode::Coast),
+            4 => drive.request_stop(StopMode::Brake),
+            5 => {
+                let estop = drive.reset_estop();
+                if !drive.reset_fault() && !estop && drive.status() == DriverStatus::Error {
+                    return Err(ModbusException::ServerDeviceFailure);
+                }
+            }
+            6 => drive.set_zero(),
+            _ => return Err(ModbusException::IllegalDataValue),
+        }
+        Ok(())
+    }
+
+    /// Reads one input register.
+    fn input_register(drive: &MotorController, address: u16) -> Result<u16, ModbusException> {
+        let pair = |value: i32, base: u16| Self::half(value, address == base);
+        let value = match address {
+            IR_STATUS => {
+                let status = match drive.status() {
+                    DriverStatus::Calibrating => 0,
+                    DriverStatus::Ready => 1,
+                    DriverStatus::Error => 2,
+                };
+                status | if drive.estop_latched() { 4 } else { 0 }
+            }
+            0x0001..=0x0002 => pair(drive.position(), IR_POSITION),
+            0x0003..=0x0004 => pair(drive.velocity(), IR_VELOCITY),
+            0x0005..=0x0006 => pair(drive.torque_current(), IR_CURRENT),
+            IR_SUPPLY => drive.supply_mv().clamp(0, u16::MAX as i32) as u16,
+            IR_BUS_CURRENT => drive.bus_power().current_ma.clamp(i16::MIN as i32, i16::MAX as i32) as u16,
+            IR_TEMPERATURE => drive.winding_temperature().0.clamp(i16::MIN as i32, i16::MAX as i32) as u16,
+            _ => return Err(ModbusException::IllegalDataAddress),
+        };
+        Ok(value)
+    }
+
+    /// Returns the high or low word of a 32-bit value.
+    #[inline(always)]
+    fn half(value: i32, high: bool) -> u16 {
+        if high { (value as u32 >> 16) as u16 } else { value as u16 }
+    }
+}
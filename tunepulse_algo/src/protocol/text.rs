@@ -0,0 +1,374 @@
+// Implements the text command interpreter, a line based G-code-like shell for bring-up over a
+// serial terminal: parameters by name, motion commands and status reports, no host software needed.
+
+// Key Features:
+// - G-code words: G0 / G1 moves, G28 homing, G92 set position, M3 / M5 velocity, M17 / M18 / M84
+//   enable and disable, M112 emergency brake, M114 position report, M999 fault reset.
+// - Plain commands: SET / GET of registry parameters by name or id, VEL, TRQ, STOP, STATUS.
+// - Case insensitive, ';' starts a comment, decimal or 0x hexadecimal numbers.
+// - One reply line per command: "ok" with optional values or "error: <reason>".
+
+// Detailed Operation:
+// The HAL pushes received characters into `receive`; a CR or LF ends the line, which is parsed and
+// executed, and the reply is written to `out` (terminated by "\r\n", the return value is its
+// length; 0 for empty lines). Units are the controller's: positions in counts (65536 per
+// revolution), velocities in counts/s, torque currents in mA. "G1 P<target> F<velocity>" starts a
+// profiled move, F updates the profile velocity parameter for this and the following moves; G0
+// sets the position target directly. Setpoints are refused while the controller isn't ready and
+// feed the watchdog. Parameter writes go through the registry, so they are range checked exactly
+// like the binary protocols. Lines longer than MAX_LINE are discarded with an error.
+// Examples: "M17", "G1 P65536 F20000", "SET vel_kp 120", "GET 0x0100", "STOP brake".
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::motor_driver::{DriverStatus, HomingConfig, StopMode};
+use crate::params::{self, ParamError};
+use crate::MotorController;
+
+/// Maximum length of a command line (characters)
+pub const MAX_LINE: usize = 64;
+/// Maximum length of a reply line (characters)
+pub const MAX_REPLY: usize = 64;
+/// Maximum number of words of a line
+const MAX_WORDS: usize = 8;
+/// Registry id of the profile velocity set by F words
+const PROFILE_VELOCITY_ID: u16 = 0x0200;
+
+/// Reason a command failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextError {
+    UnknownCommand,
+    BadArgument,
+    UnknownParameter,
+    ReadOnly,
+    OutOfRange,
+    Rejected,
+    NotReady,
+    LineTooLong,
+}
+
+impl TextError {
+    const fn message(&self) -> &'static str {
+        match self {
+            TextError::UnknownCommand => "unknown command",
+            TextError::BadArgument => "bad argument",
+            TextError::UnknownParameter => "unknown parameter",
+            TextError::ReadOnly => "read only",
+            TextError::OutOfRange => "out of range",
+            TextError::Rejected => "rejected",
+            TextError::NotReady => "not ready",
+            TextError::LineTooLong => "line too long",
+        }
+    }
+}
+
+impl From<ParamError> for TextError {
+    fn from(error: ParamError) -> Self {
+        match error {
+            ParamError::UnknownId => TextError::UnknownParameter,
+            ParamError::ReadOnly => TextError::ReadOnly,
+            ParamError::OutOfRange => TextError::OutOfRange,
+            ParamError::Rejected => TextError::Rejected,
+        }
+    }
+}
+
+// ####### Reply #######
+
+/// Reply line under construction, truncated at MAX_REPLY.
+struct Reply<'a> {
+    out: &'a mut [u8; MAX_REPLY],
+    len: usize,
+}
+
+impl Reply<'_> {
+    fn byte(&mut self, byte: u8) {
+        if self.len < MAX_REPLY {
+            self.out[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        for &byte in text.as_bytes() {
+            self.byte(byte);
+        }
+    }
+
+    fn number(&mut self, value: i64) {
+        let mut digits = [0u8; 20];
+        let mut count = 0;
+        let mut rest = value.unsigned_abs();
+        loop {
+            digits[count] = b'0' + (rest % 10) as u8;
+            count += 1;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        if value < 0 {
+            self.byte(b'-');
+        }
+        for &digit in digits[..count].iter().rev() {
+            self.byte(digit);
+        }
+    }
+
+    /// Appends " <letter><value>".
+    fn field(&mut self, letter: &str, value: i64) {
+        self.byte(b' ');
+        self.text(letter);
+        self.number(value);
+    }
+}
+
+// ####### Parsing #######
+
+/// Parses a decimal or 0x hexadecimal signed number.
+fn parse_number(word: &str) -> Option<i32> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word.strip_prefix('+').unwrap_or(word)),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i64>().ok()?,
+    };
+    let value = if negative { -value } else { value };
+    i32::try_from(value).ok()
+}
+
+/// Returns the value of the first `letter` word of a G-code line (e.g. P of "G1 P100 F20").
+fn letter_value(words: &[&str], letter: u8) -> Result<Option<i32>, TextError> {
+    for word in words {
+        let bytes = word.as_bytes();
+        if bytes[0].eq_ignore_ascii_case(&letter) {
+            return parse_number(&word[1..]).map(Some).ok_or(TextError::BadArgument);
+        }
+    }
+    Ok(None)
+}
+
+/// Resolves a parameter given by name or id.
+fn param_id(word: &str) -> Result<u16, TextError> {
+    if let Some(id) = parse_number(word) {
+        return u16::try_from(id).map_err(|_| TextError::UnknownParameter);
+    }
+    let mut name = [0u8; MAX_LINE];
+    let name = &mut name[..word.len()];
+    name.copy_from_slice(word.as_bytes());
+    name.make_ascii_lowercase();
+    let name = core::str::from_utf8(name).map_err(|_| TextError::UnknownParameter)?;
+    params::find_name(name).map(|param| param.id).ok_or(TextError::UnknownParameter)
+}
+
+// ####### Shell #######
+
+/// Text command interpreter.
+pub struct TextShell {
+    line: [u8; MAX_LINE], // Characters of the line being received
+    len: usize,           // Received characters
+    overflow: bool,       // Line too long, discarded up to its end
+    homing: HomingConfig, // Routine started by G28
+}
+
+impl TextShell {
+    /// Creates the interpreter, G28 runs the default homing routine.
+    pub fn new() -> Self {
+        Self {
+            line: [0; MAX_LINE],
+            len: 0,
+            overflow: false,
+            homing: HomingConfig::default(),
+        }
+    }
+
+    /// Sets the homing routine started by G28.
+    pub fn set_homing(&mut self, config: HomingConfig) {
+        self.homing = config;
+    }
+
+    /// Processes one received character, returns the length of the reply written to `out`.
+    pub fn receive(&mut self, drive: &mut MotorController, byte: u8, out: &mut [u8; MAX_REPLY]) -> usize {
+        if byte != b'\r' && byte != b'\n' {
+            if self.len == MAX_LINE {
+                self.overflow = true;
+            } else {
+                self.line[self.len] = byte;
+                self.len += 1;
+            }
+            return 0;
+        }
+        let len = self.len;
+        let overflow = self.overflow;
+        self.len = 0;
+        self.overflow = false;
+
+        let mut reply = Reply { out, len: 0 };
+        reply.text("ok");
+        let result = if overflow {
+            Err(TextError::LineTooLong)
+        } else {
+            let line = core::str::from_utf8(&self.line[..len]).unwrap_or("");
+            let line = line.split(';').next().unwrap_or("");
+            let mut words = [""; MAX_WORDS];
+            let mut count = 0;
+            for word in line.split_ascii_whitespace() {
+                if count == MAX_WORDS {
+                    return Self::error(reply, TextError::BadArgument);
+                }
+                words[count] = word;
+                count += 1;
+            }
+            if count == 0 {
+                return 0;
+            }
+            self.execute(drive, &words[..count], &mut reply)
+        };
+        match result {
+            Ok(()) => {
+                reply.text("\r\n");
+                reply.len
+            }
+            Err(error) => Self::error(reply, error),
+        }
+    }
+
+    /// Replaces the reply by an error message, returns its length.
+    fn error(mut reply: Reply, error: TextError) -> usize {
+        reply.len = 0;
+        reply.text("error: ");
+        reply.text(error.message());
+        reply.text("\r\n");
+        reply.len
+    }
+
+    /// Executes one command line, values of the reply go into `reply` as " <field>" items.
+    fn execute(&self, drive: &mut MotorController, words: &[&str], reply: &mut Reply) -> Result<(), TextError> {
+        let command = words[0];
+        let args = &words[1..];
+        let head = command.as_bytes()[0].to_ascii_uppercase();
+        if (head == b'G' || head == b'M') && command.len() > 1 {
+            let code = parse_number(&command[1..]).ok_or(TextError::UnknownCommand)?;
+            return if head == b'G' {
+                self.g_code(drive, code, args)
+            } else {
+                Self::m_code(drive, code, args, reply)
+            };
+        }
+        let argument = |index: usize| args.get(index).copied().ok_or(TextError::BadArgument);
+        let number = |index: usize| argument(index).and_then(|word| parse_number(word).ok_or(TextError::BadArgument));
+        if command.eq_ignore_ascii_case("SET") {
+            params::registry::set(drive, param_id(argument(0)?)?, number(1)?)?;
+        } else if command.eq_ignore_ascii_case("GET") {
+            let value = params::registry::get(drive, param_id(argument(0)?)?)?;
+            reply.text(" ");
+            reply.number(value as i64);
+        } else if command.eq_ignore_ascii_case("VEL") {
+            let velocity = number(0)?;
+            Self::setpoint(drive, |drive| drive.set_velocity(velocity))?;
+        } else if command.eq_ignore_ascii_case("TRQ") {
+            let current = number(0)?;
+            Self::setpoint(drive, |drive| drive.set_torque(current))?;
+        } else if command.eq_ignore_ascii_case("STOP") {
+            let mode = match args.first() {
+                None => StopMode::Ramp,
+                Some(mode) if mode.eq_ignore_ascii_case("RAMP") => StopMode::Ramp,
+                Some(mode) if mode.eq_ignore_ascii_case("COAST") => StopMode::Coast,
+                Some(mode) if mode.eq_ignore_ascii_case("BRAKE") => StopMode::Brake,
+                Some(_) => return Err(TextError::BadArgument),
+            };
+            drive.request_stop(mode);
+        } else if command.eq_ignore_ascii_case("STATUS") {
+            reply.text(match drive.status() {
+                DriverStatus::Calibrating => " CALIBRATING",
+                DriverStatus::Ready => " READY",
+                DriverStatus::Error => " ERROR",
+            });
+            if drive.estop_latched() {
+                reply.text(" ESTOP");
+            }
+            reply.field("U", drive.supply_mv() as i64);
+            reply.field("T", drive.winding_temperature().0 as i64);
+        } else {
+            return Err(TextError::UnknownCommand);
+        }
+        Ok(())
+    }
+
+    /// Executes a G command.
+    fn g_code(&self, drive: &mut MotorController, code: i32, args: &[&str]) -> Result<(), TextError> {
+        let target = letter_value(args, b'P')?;
+        match code {
+            0 => {
+                let target = target.ok_or(TextError::BadArgument)?;
+                Self::setpoint(drive, |drive| drive.set_target_position(target))
+            }
+            1 => {
+                let target = target.ok_or(TextError::BadArgument)?;
+                if let Some(velocity) = letter_value(args, b'F')? {
+                    params::registry::set(drive, PROFILE_VELOCITY_ID, velocity)?;
+                }
+                Self::setpoint(drive, |drive| drive.move_to(target))
+            }
+            28 => {
+                if drive.start_homing(self.homing) { Ok(()) } else { Err(TextError::NotReady) }
+            }
+            92 => {
+                drive.set_position_offset(target.unwrap_or(0) as i64);
+                Ok(())
+            }
+            _ => Err(TextError::UnknownCommand),
+        }
+    }
+
+    /// Executes an M command.
+    fn m_code(drive: &mut MotorController, code: i32, args: &[&str], reply: &mut Reply) -> Result<(), TextError> {
+        match code {
+            3 => {
+                let velocity = letter_value(args, b'S')?.ok_or(TextError::BadArgument)?;
+                Self::setpoint(drive, |drive| drive.set_velocity(velocity))?;
+            }
+            5 => Self::setpoint(drive, |drive| drive.set_velocity(0))?,
+            17 => {
+                if drive.estop_latched() || drive.status() != DriverStatus::Ready {
+                    return Err(TextError::NotReady);
+                }
+                drive.release_stop();
+            }
+            18 | 84 => drive.request_stop(StopMode::Coast),
+            112 => drive.request_stop(StopMode::Brake),
+            114 => {
+                reply.field("P", drive.position() as i64);
+                reply.field("V", drive.velocity() as i64);
+                reply.field("I", drive.torque_current() as i64);
+            }
+            999 => {
+                let estop = drive.reset_estop();
+                if !drive.reset_fault() && !estop && drive.status() == DriverStatus::Error {
+                    return Err(TextError::Rejected);
+                }
+            }
+            _ => return Err(TextError::UnknownCommand),
+        }
+        Ok(())
+    }
+
+    /// Applies a setpoint if the controller is ready.
+    fn setpoint(drive: &mut MotorController, apply: impl FnOnce(&mut MotorController)) -> Result<(), TextError> {
+        if drive.status() != DriverStatus::Ready {
+            return Err(TextError::NotReady);
+        }
+        apply(drive);
+        drive.feed_watchdog();
+        Ok(())
+    }
+}
+
+impl Default for TextShell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
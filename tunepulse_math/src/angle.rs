@@ -0,0 +1,167 @@
+// Implements the Angle16 module, a wrapping angle type for electrical and mechanical angles
+// (one full turn = 65536 LSB).
+
+// Key Features:
+// - Wrapping add/sub of signed offsets and of other angles.
+// - Shortest-arc signed difference between two angles.
+// - Conversions from/to degrees, millidegrees and milliradians.
+// - Electrical <-> mechanical conversion with a pole pair count.
+// - Sine/cosine lookup through the trigonometry module.
+
+// Detailed Operation:
+// A u16 covers exactly one turn, so the natural integer overflow implements the wrap-around for
+// free. The difference of two angles interpreted as i16 is the shortest signed arc between them
+// (-180°..180°). An electrical angle repeats `pole_pairs` times per mechanical turn, so the
+// electrical angle is the mechanical one multiplied by the pole pair count (with wrap). The way back
+// needs to know in which electrical period (sector) of the mechanical turn the rotor is.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::trigonometry::angle2sincos_interp;
+
+/// Angle with 65536 LSB per turn, wrapping on overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Angle16(pub u16);
+
+impl Angle16 {
+    /// Zero angle
+    pub const ZERO: Angle16 = Angle16(0);
+    /// Quarter turn: 90°
+    pub const QUARTER: Angle16 = Angle16(1 << 14);
+    /// Half turn: 180°
+    pub const HALF: Angle16 = Angle16(1 << 15);
+
+    /// Returns the angle advanced by a signed offset (LSB).
+    #[inline(always)]
+    pub const fn offset(self, delta: i32) -> Angle16 {
+        Angle16(self.0.wrapping_add(delta as u16))
+    }
+
+    /// Returns the sum of two angles.
+    #[inline(always)]
+    pub const fn add(self, other: Angle16) -> Angle16 {
+        Angle16(self.0.wrapping_add(other.0))
+    }
+
+    /// Returns the angle rotated back by another one.
+    #[inline(always)]
+    pub const fn sub(self, other: Angle16) -> Angle16 {
+        Angle16(self.0.wrapping_sub(other.0))
+    }
+
+    /// Returns the shortest signed arc from `other` to `self` (-32768..32767 = -180°..180°).
+    #[inline(always)]
+    pub const fn diff(self, other: Angle16) -> i16 {
+        self.0.wrapping_sub(other.0) as i16
+    }
+
+    /// Creates an angle from degrees (any value, wraps).
+    pub const fn from_degrees(degrees: i32) -> Angle16 {
+        Self::from_millidegrees((degrees % 360) * 1000) // Whole turns dropped first, no overflow
+    }
+
+    /// Creates an angle from millidegrees (any value, wraps).
+    pub const fn from_millidegrees(millidegrees: i32) -> Angle16 {
+        let turns = (millidegrees as i64 * 65536) / 360_000;
+        Angle16(turns as u16)
+    }
+
+    /// Returns the angle in millidegrees (0..359999).
+    pub const fn to_millidegrees(self) -> i32 {
+        ((self.0 as i64 * 360_000) >> 16) as i32
+    }
+
+    /// Creates an angle from milliradians (any value, wraps).
+    pub const fn from_mrad(mrad: i32) -> Angle16 {
+        // 65536 / (2π * 1000) LSB per mrad, whole turns (2π * 10^9 nrad) dropped before the scaling
+        let nrad = (mrad as i64 * 1_000_000) % 6_283_185_307;
+        let turns = (nrad * 65536) / 6_283_185_307;
+        Angle16(turns as u16)
+    }
+
+    /// Returns the angle in milliradians (0..6283).
+    pub const fn to_mrad(self) -> i32 {
+        ((self.0 as i64 * 6_283_185_307) / (65536 * 1_000_000)) as i32
+    }
+
+    /// Converts a mechanical angle into the electrical angle of a motor with `pole_pairs`.
+    pub const fn to_electrical(self, pole_pairs: u16) -> Angle16 {
+        Angle16(self.0.wrapping_mul(pole_pairs))
+    }
+
+    /// Converts an electrical angle into the mechanical angle.
+    ///
+    /// # Arguments
+    /// * `pole_pairs` - Number of pole pairs (electrical periods per turn)
+    /// * `sector` - Electrical period the rotor is in (0..pole_pairs-1)
+    pub const fn to_mechanical(self, pole_pairs: u16, sector: u16) -> Angle16 {
+        if pole_pairs == 0 {
+            return self;
+        }
+        let total = (sector % pole_pairs) as u32 * 65536 + self.0 as u32;
+        Angle16((total / pole_pairs as u32) as u16)
+    }
+
+    /// Returns sine and cosine of the angle (Q15).
+    #[inline(always)]
+    pub const fn sincos(self) -> (i16, i16) {
+        angle2sincos_interp(self.0 as i16)
+    }
+}
+
+impl From<u16> for Angle16 {
+    fn from(value: u16) -> Self {
+        Angle16(value)
+    }
+}
+
+impl From<Angle16> for u16 {
+    fn from(value: Angle16) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exact reference: value * 65536 / per_turn, truncated, wrapped to one turn.
+    fn reference(value: i32, scale: i128, per_turn: i128) -> u16 {
+        (value as i128 * scale * 65536 / per_turn) as u16
+    }
+
+    #[test]
+    fn from_degrees_any_value() {
+        assert_eq!(Angle16::from_degrees(90), Angle16::QUARTER);
+        assert_eq!(Angle16::from_degrees(-180), Angle16::HALF);
+        assert_eq!(Angle16::from_degrees(360 * 1000 + 90), Angle16::QUARTER);
+        for degrees in [i32::MIN, i32::MIN + 1, -2_147_483, 2_147_484, 123_456_789, i32::MAX] {
+            assert_eq!(Angle16::from_degrees(degrees).0, reference(degrees, 1000, 360_000), "{}", degrees);
+        }
+    }
+
+    #[test]
+    fn from_mrad_any_value() {
+        assert_eq!(Angle16::from_mrad(0), Angle16::ZERO);
+        assert_eq!(Angle16::from_mrad(3142).0, 32772);
+        for mrad in [i32::MIN, -140_001, -6283, 6284, 140_001, 1_000_000_007, i32::MAX] {
+            assert_eq!(Angle16::from_mrad(mrad).0, reference(mrad, 1_000_000, 6_283_185_307), "{}", mrad);
+        }
+        let mut mrad: i32 = 1;
+        while mrad < i32::MAX / 3 {
+            assert_eq!(Angle16::from_mrad(mrad).0, reference(mrad, 1_000_000, 6_283_185_307));
+            assert_eq!(Angle16::from_mrad(-mrad).0, reference(-mrad, 1_000_000, 6_283_185_307));
+            mrad = mrad * 3 + 7;
+        }
+    }
+
+    #[test]
+    fn millidegrees_round_trip() {
+        for raw in (0..=u16::MAX).step_by(97) {
+            let angle = Angle16(raw);
+            assert!(Angle16::from_millidegrees(angle.to_millidegrees()).diff(angle).abs() <= 1);
+            assert!(Angle16::from_mrad(angle.to_mrad()).diff(angle).abs() <= 11);
+        }
+    }
+}
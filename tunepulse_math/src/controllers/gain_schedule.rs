@@ -0,0 +1,107 @@
+// Implements the gain schedule, interpolating controller gains from a small table indexed by an
+// operating point such as speed or load.
+
+// Key Features:
+// - Up to SCHEDULE_POINTS table points of (index, kp, ki, kd), sorted by index.
+// - Linear interpolation between neighbouring points, held constant outside the table.
+// - Empty table means no scheduling, the caller keeps its fixed gains.
+// - Integer math on i64 intermediates.
+
+// Detailed Operation:
+// A single tune rarely fits the whole operating range: friction and stiction dominate at standstill,
+// back-EMF and measurement delay at speed, saturation and compliance under load. The caller picks
+// the index (e.g. |velocity| in counts/s or |current| in mA) and asks for the gains of every tick;
+// the table is searched linearly, which is cheaper than a binary search for a handful of points.
+// Gains change continuously with the index, so the controller state can be kept across updates.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Maximum number of table points
+pub const SCHEDULE_POINTS: usize = 4;
+
+/// One point of the gain table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GainPoint {
+    /// Operating point of the gains (e.g. |velocity| or |current|)
+    pub index: i32,
+    /// Proportional gain at the point
+    pub kp: i32,
+    /// Integral gain at the point
+    pub ki: i32,
+    /// Derivative gain at the point
+    pub kd: i32,
+}
+
+/// Table of gains interpolated by an operating point.
+pub struct GainSchedule {
+    points: [GainPoint; SCHEDULE_POINTS],
+    count: usize, // Number of used points, 0 - no scheduling
+}
+
+impl GainSchedule {
+    /// Creates an empty schedule.
+    pub const fn new() -> Self {
+        Self {
+            points: [GainPoint {
+                index: 0,
+                kp: 0,
+                ki: 0,
+                kd: 0,
+            }; SCHEDULE_POINTS],
+            count: 0,
+        }
+    }
+
+    /// Loads the table, returns false (and keeps the old table) if it has too many points or the
+    /// indexes are not strictly increasing.
+    pub fn configure(&mut self, points: &[GainPoint]) -> bool {
+        if points.len() > SCHEDULE_POINTS || points.windows(2).any(|pair| pair[0].index >= pair[1].index) {
+            return false;
+        }
+        self.points[..points.len()].copy_from_slice(points);
+        self.count = points.len();
+        true
+    }
+
+    /// Removes all points, scheduling is off.
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    /// Returns true if the table has points.
+    pub fn is_enabled(&self) -> bool {
+        self.count > 0
+    }
+
+    /// Returns the used table points.
+    pub fn points(&self) -> &[GainPoint] {
+        &self.points[..self.count]
+    }
+
+    /// Returns the (kp, ki, kd) gains at the operating point, None for an empty table.
+    pub fn gains(&self, index: i32) -> Option<(i32, i32, i32)> {
+        let points = self.points();
+        let first = points.first()?;
+        if index <= first.index {
+            return Some((first.kp, first.ki, first.kd));
+        }
+        for pair in points.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if index < high.index {
+                let span = high.index as i64 - low.index as i64;
+                let offset = index as i64 - low.index as i64;
+                let lerp = |a: i32, b: i32| (a as i64 + (b as i64 - a as i64) * offset / span) as i32;
+                return Some((lerp(low.kp, high.kp), lerp(low.ki, high.ki), lerp(low.kd, high.kd)));
+            }
+        }
+        let last = points[points.len() - 1];
+        Some((last.kp, last.ki, last.kd))
+    }
+}
+
+impl Default for GainSchedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,3 @@
+pub mod gain_schedule;
+pub mod pi;
+pub mod pid;
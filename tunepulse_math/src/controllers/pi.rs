@@ -0,0 +1,238 @@
+// Implements the PI controller primitive with back-calculation anti-windup, output clamping and
+// bumpless gain updates, working on the full i32 range (currents, voltages, velocities).
+
+// Key Features:
+// - Fixed-point gains with 16 fractional bits, i64 intermediates.
+// - Asymmetric output clamp, changeable at runtime (e.g. follows the bus voltage).
+// - Back-calculation anti-windup: the clamped excess is fed back into the integrator.
+// - Bumpless gain changes and preloading: the output doesn't jump when the tune changes.
+
+// Detailed Operation:
+// The integrator stores the integral term itself (output units, Q16), so a new ki only affects
+// future errors; a new kp would change the proportional term at once, so the integrator absorbs
+// the difference kp_old * e - kp_new * e of the last error. Every tick the unclamped output
+// u = kp * e + integral + feed-forward is clamped to [min, max], and kb * (clamped - u) is added to the
+// integrator. With kb = 1 (default) the integrator is pulled back so the output sits exactly at the
+// clamp, which means it recovers as soon as the error changes sign instead of unwinding a stored
+// excess first (e.g. when the PWM saturates at low bus voltage). Smaller kb lets it wind up partly.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of gains and of the integrator state
+const GAIN_FRAC: u32 = 16;
+
+/// Proportional-integral controller with anti-windup.
+pub struct PI {
+    kp: i32, // Proportional gain (Q16, output per error)
+    ki: i32, // Integral gain (Q16, output per error and tick)
+    kb: i32, // Back-calculation gain (Q16, 1 << 16 = full excess)
+
+    min: i32, // Lower output clamp
+    max: i32, // Upper output clamp
+
+    integral: i64,   // Integral term (output units, Q16)
+    last_error: i32, // Error of the previous tick
+    output: i32,     // Clamped output
+    saturated: bool, // Output was clamped in the last tick
+}
+
+impl PI {
+    /// Creates a controller with an unlimited output and an empty integrator.
+    ///
+    /// # Arguments
+    /// * `kp` - Proportional gain (Q16)
+    /// * `ki` - Integral gain per tick (Q16)
+    pub const fn new(kp: i32, ki: i32) -> Self {
+        Self {
+            kp,
+            ki,
+            kb: 1 << GAIN_FRAC,
+            min: i32::MIN,
+            max: i32::MAX,
+            integral: 0,
+            last_error: 0,
+            output: 0,
+            saturated: false,
+        }
+    }
+
+    /// Sets the output clamp, an inverted pair is swapped.
+    pub fn set_limits(&mut self, min: i32, max: i32) {
+        (self.min, self.max) = if min <= max { (min, max) } else { (max, min) };
+    }
+
+    /// Sets a symmetric output clamp of ±`limit`.
+    pub fn set_limit(&mut self, limit: i32) {
+        let limit = limit.saturating_abs();
+        self.set_limits(-limit, limit);
+    }
+
+    /// Changes the gains without a step of the output.
+    ///
+    /// # Arguments
+    /// * `kp` - Proportional gain (Q16)
+    /// * `ki` - Integral gain per tick (Q16)
+    pub fn set_gains(&mut self, kp: i32, ki: i32) {
+        // The integrator takes over the change of the proportional term at the last error
+        let delta = (self.kp as i64 - kp as i64) * self.last_error as i64;
+        self.integral = self.integral.saturating_add(delta);
+        self.kp = kp;
+        self.ki = ki;
+    }
+
+    /// Sets the back-calculation gain (Q16), 0 disables anti-windup.
+    pub fn set_back_calculation(&mut self, kb: i32) {
+        self.kb = kb.max(0);
+    }
+
+    /// Updates the controller and returns the clamped output.
+    ///
+    /// # Arguments
+    /// * `error` - Setpoint minus measurement
+    /// * `feedforward` - Value added to the output before clamping
+    pub fn tick(&mut self, error: i32, feedforward: i32) -> i32 {
+        // ####### Integral #######
+        self.integral = self.integral.saturating_add(error as i64 * self.ki as i64);
+        self.last_error = error;
+
+        // ####### Output and clamp #######
+        let proportional = error as i64 * self.kp as i64;
+        let unclamped = (proportional + self.integral + ((feedforward as i64) << GAIN_FRAC)) >> GAIN_FRAC;
+        let output = unclamped.clamp(self.min as i64, self.max as i64);
+        self.saturated = output != unclamped;
+
+        // ####### Anti-windup #######
+        if self.saturated {
+            let excess = (output - unclamped) * self.kb as i64; // Q16
+            self.integral = self.integral.saturating_add(excess);
+        }
+        self.output = output as i32;
+        self.output
+    }
+
+    /// Preloads the integrator so the output equals `output` at zero error (bumpless start).
+    pub fn reset(&mut self, output: i32) {
+        let output = output.clamp(self.min, self.max);
+        self.integral = (output as i64) << GAIN_FRAC;
+        self.last_error = 0;
+        self.output = output;
+        self.saturated = false;
+    }
+
+    /// Returns the clamped output of the last tick.
+    pub fn output(&self) -> i32 {
+        self.output
+    }
+
+    /// Returns the integral term (output units).
+    pub fn integral(&self) -> i32 {
+        (self.integral >> GAIN_FRAC).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+
+    /// Returns true if the output was clamped in the last tick.
+    pub fn is_saturated(&self) -> bool {
+        self.saturated
+    }
+
+    /// Returns the (kp, ki) gains (Q16).
+    pub fn gains(&self) -> (i32, i32) {
+        (self.kp, self.ki)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// First-order plant: the output follows the input with a time constant of 16 ticks.
+    fn plant(state: &mut i32, input: i32) -> i32 {
+        *state += (input - *state) / 16;
+        *state
+    }
+
+    #[test]
+    fn step_response_settles_without_error() {
+        let mut pi = PI::new(1 << 15, 1 << 12); // Kp 0.5, Ki 1/16 per tick
+        pi.set_limit(5000);
+        let (mut state, mut peak) = (0, 0);
+        for _ in 0..400 {
+            let output = pi.tick(1000 - state, 0);
+            peak = peak.max(plant(&mut state, output));
+        }
+        assert!((state - 1000).abs() <= 16, "settled at {}", state);
+        assert!(peak < 1150, "overshoot to {}", peak);
+        assert!(!pi.is_saturated());
+    }
+
+    #[test]
+    fn output_saturates_at_the_limits() {
+        let mut pi = PI::new(1 << 16, 1 << 14);
+        pi.set_limits(-50, 100);
+        for _ in 0..1000 {
+            assert_eq!(pi.tick(1000, 0), 100);
+        }
+        assert!(pi.is_saturated());
+        assert!(pi.integral() <= 100); // Excess fed back, nothing stored beyond the clamp
+        assert_eq!(pi.tick(-1000, 0), -50);
+        pi.set_limits(200, -200); // Swapped pair
+        assert_eq!(pi.tick(1000, 0), 200);
+    }
+
+    #[test]
+    fn recovers_from_windup_at_once() {
+        let ticks_to_leave = |kb: i32| {
+            let mut pi = PI::new(1 << 16, 1 << 14);
+            pi.set_limit(100);
+            pi.set_back_calculation(kb);
+            for _ in 0..1000 {
+                pi.tick(500, 0);
+            }
+            (1..=10_000).find(|_| pi.tick(-10, 0) < 100).unwrap_or(usize::MAX)
+        };
+        assert_eq!(ticks_to_leave(1 << 16), 1); // Back-calculation: leaves the clamp with the error sign
+        assert!(ticks_to_leave(0) > 100); // Without it the stored excess unwinds first
+    }
+
+    #[test]
+    fn no_windup_when_the_bus_voltage_is_too_low() {
+        // Current loop: the plant needs 1000 mV for the setpoint, the bus only gives 600 mV
+        let settle_after_step_down = |kb: i32| {
+            let mut pi = PI::new(1 << 15, 1 << 12);
+            pi.set_back_calculation(kb);
+            pi.set_limit(600);
+            let mut state = 0;
+            for _ in 0..2000 {
+                let output = pi.tick(1000 - state, 0);
+                plant(&mut state, output);
+            }
+            assert_eq!((pi.output(), state), (600, 600 - 15)); // Pinned at the bus, integer plant lag
+            (1..=10_000).find(|_| {
+                let output = pi.tick(300 - state, 0);
+                (plant(&mut state, output) - 300).abs() <= 16
+            })
+        };
+        let with_back_calculation = settle_after_step_down(1 << 16).unwrap();
+        let without = settle_after_step_down(0).unwrap();
+        // Follows within two plant time constants, a stored excess would first have to unwind
+        assert!(with_back_calculation <= 32, "settled after {} ticks", with_back_calculation);
+        assert!(without > 20 * with_back_calculation, "settled after {} ticks", without);
+    }
+
+    #[test]
+    fn gain_change_and_preload_are_bumpless() {
+        let mut pi = PI::new(1 << 16, 0);
+        assert_eq!(pi.tick(100, 0), 100);
+        pi.set_gains(1 << 17, 0);
+        assert_eq!(pi.tick(100, 0), 100); // Proportional step taken over by the integrator
+        assert_eq!(pi.gains(), (1 << 17, 0));
+
+        pi.set_limit(1000);
+        pi.reset(300);
+        assert_eq!((pi.output(), pi.integral()), (300, 300));
+        assert_eq!(pi.tick(0, 0), 300);
+        pi.reset(5000);
+        assert_eq!(pi.output(), 1000); // Preload is clamped
+        assert_eq!(pi.tick(0, 25), 1000);
+    }
+}
@@ -3,7 +3,7 @@
 ///
 /// **Note**
 /// - Based on integer implementation and works with i16 range
-/// - Works with constant dt only
+/// - Assumes the nominal period unless the measured one is given with `set_dt`
 /// - Has integral anti-windup
 pub struct PID {
     /// Proportional gain coefficient: -10000% to 10000%.
@@ -26,6 +26,8 @@ pub struct PID {
     integral: i32,
     /// Stores the previous error value for derivative and integral calculation
     previous_error: i32,
+    /// Measured period relative to the nominal one (Q16, 1 << 16 = nominal)
+    dt: i32,
     /// The PID controller output
     output: i16,
 }
@@ -56,6 +58,7 @@ impl PID {
             kff,
             integral: 0,       // Initialize the integral accumulator
             previous_error: 0, // Initialize the previous error
+            dt: 1 << 16,       // Nominal period
             output: 0,         // Initialize the output
         }
     }
@@ -80,7 +83,8 @@ impl PID {
 
         // ########################## INTEGRAL TERM ###################################
         // Tustin's method (trapezoidal rule) for integrating the error with smoothing
-        self.integral += (error + self.previous_error) >> 1;
+        let increment = (error + self.previous_error) >> 1;
+        self.integral += ((increment as i64 * self.dt as i64) >> 16) as i32;
 
         // Clamp integral to avoid with anti-windup
         self.integral = Self::clamp(self.integral, limit); // Maximum accumulation: ±2^15
@@ -91,6 +95,8 @@ impl PID {
         // ######################### DERIVATIVE TERM ##################################
         // Calculate derivative by finding the difference in error
         let derivative = error - self.previous_error; // Maximum value: ±2 * ±2^15 = ±2^16
+        let derivative = ((derivative as i64) << 16) / self.dt as i64; // Rate per nominal period
+        let derivative = derivative.clamp(-(1 << 17), 1 << 17) as i32;
 
         // Calculate derivative term
         let d = Self::apply_coef(derivative, self.kd); // Maximum possible value: ±100 * ±2^16
@@ -119,6 +125,31 @@ impl PID {
         self.output
     }
 
+    /// Update the gain coefficients while keeping the integral and previous error
+    ///
+    /// # Arguments
+    /// * `kp` - Proportional gain coefficient
+    /// * `ki` - Integral gain coefficient
+    /// * `kd` - Derivative gain coefficient
+    ///
+    /// Used for gain scheduling, where the gains change a little every tick.
+    pub fn set_gains(&mut self, kp: i32, ki: i32, kd: i32) {
+        self.kp = Self::fit_coef(kp);
+        self.ki = Self::fit_coef(ki);
+        self.kd = Self::fit_coef(kd);
+    }
+
+    /// Set the measured period of the next updates relative to the nominal one
+    ///
+    /// # Arguments
+    /// * `dt` - Period ratio (Q16, 1 << 16 = nominal), limited to 1/16..16
+    ///
+    /// The integral grows with the elapsed time and the derivative is the rate per nominal period,
+    /// so the gains keep their meaning when the update rate jitters.
+    pub fn set_dt(&mut self, dt: i32) {
+        self.dt = dt.clamp(1 << 12, 1 << 20);
+    }
+
     // Constants controlling fast vs. slow math operations
     const FAST_MATH: bool = true;
     const SLOW_MATH_SCALE: i32 = 2; // Do not change!
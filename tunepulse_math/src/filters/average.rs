@@ -0,0 +1,147 @@
+// Implements the averaging filters module, providing a power-of-two moving average and a CIC
+// decimator for ADC oversampling.
+
+// Key Features:
+// - Moving average over 2^k samples with a running sum: one add, one subtract and a shift per sample.
+// - Exact result, no accumulated rounding drift (the sum is always the sum of the window).
+// - CIC (cascaded integrator-comb) decimator of configurable order and power-of-two ratio.
+// - CIC uses wrapping arithmetic only, no multiplications or coefficient tables.
+// - Window and ratio are const generics, buffers are fixed-size arrays.
+
+// Detailed Operation:
+// The moving average keeps the last N samples in a ring buffer together with their sum. Each new
+// sample replaces the oldest one in the sum, and the output is the sum shifted right by log2(N).
+// Unlike the exponential low-pass, a step settles completely after N samples and periodic noise
+// with a period dividing N (e.g. PWM ripple) is cancelled exactly.
+// The CIC decimator of order M runs M integrators at the input rate and M combs (differences) at
+// the output rate, one output per R input samples. It equals M cascaded moving averages of length
+// R with decimation, the gain R^M is removed with a shift of M * log2(R). Integrators overflow by
+// design; wrapping arithmetic makes the combs cancel that exactly, as long as the input bits plus
+// M * log2(R) fit into 32 bits (e.g. 16-bit ADC, order 3, ratio 16 -> 28 bits).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Moving average over `N` samples (`N` must be a power of two).
+pub struct MovingAverage<const N: usize> {
+    samples: [i32; N], // Ring buffer of the window
+    index: usize,      // Position of the oldest sample
+    sum: i64,          // Sum of the window
+}
+
+impl<const N: usize> MovingAverage<N> {
+    /// Log2 of the window length
+    const SHIFT: u32 = {
+        assert!(N.is_power_of_two(), "window length must be a power of two");
+        N.trailing_zeros()
+    };
+
+    /// Creates a filter with the window filled with `input_default`.
+    pub fn new(input_default: i32) -> Self {
+        let _ = Self::SHIFT;
+        Self {
+            samples: [input_default; N],
+            index: 0,
+            sum: input_default as i64 * N as i64,
+        }
+    }
+
+    /// Adds a sample and returns the average of the window.
+    pub fn tick(&mut self, input: i32) -> i32 {
+        self.sum += input as i64 - self.samples[self.index] as i64;
+        self.samples[self.index] = input;
+        self.index = (self.index + 1) & (N - 1);
+        self.output()
+    }
+
+    /// Refills the window with `value`.
+    pub fn reset(&mut self, value: i32) {
+        self.samples = [value; N];
+        self.sum = value as i64 * N as i64;
+    }
+
+    /// Returns the average of the window.
+    pub fn output(&self) -> i32 {
+        (self.sum >> Self::SHIFT) as i32
+    }
+
+    /// Returns the sum of the window (average with log2(N) extra bits of resolution).
+    pub fn sum(&self) -> i64 {
+        self.sum
+    }
+}
+
+/// CIC decimator of order `M` with decimation ratio `R` (`R` must be a power of two).
+pub struct CicDecimator<const M: usize, const R: usize> {
+    integrators: [i32; M], // Integrator states (input rate, wrapping)
+    combs: [i32; M],       // Delayed comb inputs (output rate)
+    count: usize,          // Input samples since the last output
+    output: i32,           // Last output
+}
+
+impl<const M: usize, const R: usize> CicDecimator<M, R> {
+    /// Gain compensation: M * log2(R)
+    const SHIFT: u32 = {
+        assert!(R.is_power_of_two(), "decimation ratio must be a power of two");
+        assert!(M >= 1 && M as u32 * R.trailing_zeros() < 32, "CIC register too wide");
+        M as u32 * R.trailing_zeros()
+    };
+
+    /// Creates a decimator with zero state.
+    pub fn new() -> Self {
+        let _ = Self::SHIFT;
+        Self {
+            integrators: [0; M],
+            combs: [0; M],
+            count: 0,
+            output: 0,
+        }
+    }
+
+    /// Feeds one input sample, returns the new output once every `R` samples.
+    pub fn tick(&mut self, input: i32) -> Option<i32> {
+        // ######################## INTEGRATORS (input rate) #########################
+        let mut value = input;
+        for integrator in self.integrators.iter_mut() {
+            *integrator = integrator.wrapping_add(value);
+            value = *integrator;
+        }
+
+        self.count += 1;
+        if self.count < R {
+            return None;
+        }
+        self.count = 0;
+
+        // ######################## COMBS (output rate) ##############################
+        for delayed in self.combs.iter_mut() {
+            let diff = value.wrapping_sub(*delayed);
+            *delayed = value;
+            value = diff;
+        }
+        self.output = value >> Self::SHIFT;
+        Some(self.output)
+    }
+
+    /// Feeds a block of samples (e.g. one DMA buffer), returns the last output produced.
+    pub fn process(&mut self, samples: &[u16]) -> Option<i32> {
+        let mut result = None;
+        for &sample in samples {
+            if let Some(out) = self.tick(sample as i32) {
+                result = Some(out);
+            }
+        }
+        result
+    }
+
+    /// Returns the last output.
+    pub fn output(&self) -> i32 {
+        self.output
+    }
+}
+
+impl<const M: usize, const R: usize> Default for CicDecimator<M, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
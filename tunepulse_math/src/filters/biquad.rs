@@ -0,0 +1,193 @@
+// Implements the biquad filter module, a second-order IIR section for shaping current,
+// velocity and position loop signals.
+
+// Key Features:
+// - Direct form I structure with 64-bit accumulator and error feedback (no limit cycles).
+// - Fixed-point coefficients with 28 fractional bits (covers the ±2 range of a1 with margin).
+// - Design helpers for low-pass, notch and band-stop (finite depth notch) sections.
+// - Integer-only design math, usable at runtime to retune the filter.
+
+// Detailed Operation:
+// The section computes y = b0*x + b1*x1 + b2*x2 - a1*y1 - a2*y2 with coefficients normalized
+// by a0. Direct form I keeps the state in the signal domain, so it can't overflow internally and
+// coefficient changes at runtime don't cause large transients. The fractional part dropped when
+// scaling the accumulator back is added to the next sample (error feedback), which keeps low
+// cutoff filters accurate and free of dead bands. The design helpers follow the bilinear transform
+// formulas (RBJ cookbook): w0 = 2π * fc / fs, α = sin(w0) / (2Q). The band-stop variant keeps a
+// fraction `depth` of the signal at the center frequency (depth = 0 is a full notch).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the filter coefficients
+const COEF_FRAC: u32 = 28;
+/// Fractional bits of the design math
+const DESIGN_FRAC: u32 = 30;
+/// 2π scaled by 2^30
+const TWO_PI_Q30: i64 = 6_746_518_852;
+
+/// Normalized coefficients of a biquad section (28 fractional bits).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BiquadCoeffs {
+    pub b0: i32,
+    pub b1: i32,
+    pub b2: i32,
+    pub a1: i32,
+    pub a2: i32,
+}
+
+impl BiquadCoeffs {
+    /// Pass-through section.
+    pub const fn identity() -> Self {
+        Self {
+            b0: 1 << COEF_FRAC,
+            b1: 0,
+            b2: 0,
+            a1: 0,
+            a2: 0,
+        }
+    }
+
+    /// Designs a second-order low-pass section.
+    ///
+    /// # Arguments
+    /// * `cutoff` - Cutoff frequency (Hz)
+    /// * `q_milli` - Quality factor x1000 (707 = Butterworth)
+    /// * `frequency` - Sampling frequency (ticks per second)
+    pub fn lowpass(cutoff: u32, q_milli: u32, frequency: u32) -> Self {
+        let (_, cos, alpha) = Self::prewarp(cutoff, q_milli, frequency);
+        let one = 1i64 << DESIGN_FRAC;
+        let b1 = one - cos;
+        Self::normalize([b1 / 2, b1, b1 / 2, -2 * cos, one - alpha], one + alpha)
+    }
+
+    /// Designs a notch section (full rejection at the center frequency).
+    ///
+    /// # Arguments
+    /// * `center` - Center frequency (Hz)
+    /// * `q_milli` - Quality factor x1000, higher is narrower
+    /// * `frequency` - Sampling frequency (ticks per second)
+    pub fn notch(center: u32, q_milli: u32, frequency: u32) -> Self {
+        Self::band_stop(center, q_milli, 0, frequency)
+    }
+
+    /// Designs a band-stop section with finite depth.
+    ///
+    /// # Arguments
+    /// * `center` - Center frequency (Hz)
+    /// * `q_milli` - Quality factor x1000, higher is narrower
+    /// * `depth` - Gain at the center frequency (0..32767 = 0.0..1.0, 0 = full notch)
+    /// * `frequency` - Sampling frequency (ticks per second)
+    pub fn band_stop(center: u32, q_milli: u32, depth: u16, frequency: u32) -> Self {
+        let (_, cos, alpha) = Self::prewarp(center, q_milli, frequency);
+        let one = 1i64 << DESIGN_FRAC;
+        let depth_alpha = (alpha * depth.min(i16::MAX as u16) as i64) >> 15;
+        Self::normalize(
+            [one + depth_alpha, -2 * cos, one - depth_alpha, -2 * cos, one - alpha],
+            one + alpha,
+        )
+    }
+
+    /// Computes sin(w0), cos(w0) and α = sin(w0) / (2Q) in Q30.
+    fn prewarp(fc: u32, q_milli: u32, frequency: u32) -> (i64, i64, i64) {
+        let frequency = frequency.max(1) as i64;
+        let fc = (fc as i64).clamp(1, frequency / 2 - 1).max(1);
+        let w0 = TWO_PI_Q30 * fc / frequency;
+        let (sin, cos) = sincos_q30(w0);
+        let alpha = sin * 1000 / (2 * q_milli.max(1) as i64);
+        (sin, cos, alpha)
+    }
+
+    /// Divides [b0, b1, b2, a1, a2] by a0 and converts them to coefficient format.
+    fn normalize(coeffs: [i64; 5], a0: i64) -> Self {
+        let c = coeffs.map(|c| ((c << COEF_FRAC) / a0) as i32);
+        Self {
+            b0: c[0],
+            b1: c[1],
+            b2: c[2],
+            a1: c[3],
+            a2: c[4],
+        }
+    }
+}
+
+/// Second-order IIR filter section (direct form I).
+pub struct FilterBiquad {
+    coeffs: BiquadCoeffs,
+    x: [i32; 2], // Previous inputs
+    y: [i32; 2], // Previous outputs
+    error: i64,  // Fraction dropped from the last output (error feedback)
+}
+
+impl FilterBiquad {
+    /// Creates a filter with the given coefficients and zero state.
+    pub const fn new(coeffs: BiquadCoeffs) -> Self {
+        Self {
+            coeffs,
+            x: [0; 2],
+            y: [0; 2],
+            error: 0,
+        }
+    }
+
+    /// Replaces the coefficients keeping the state (bumpless retuning).
+    pub fn set_coeffs(&mut self, coeffs: BiquadCoeffs) {
+        self.coeffs = coeffs;
+    }
+
+    /// Sets the state as if the input had been constant at `value` for a long time.
+    pub fn reset(&mut self, value: i32) {
+        self.x = [value; 2];
+        self.y = [value; 2];
+        self.error = 0;
+    }
+
+    /// Filters one sample.
+    pub fn tick(&mut self, input: i32) -> i32 {
+        let c = &self.coeffs;
+        let acc = c.b0 as i64 * input as i64
+            + c.b1 as i64 * self.x[0] as i64
+            + c.b2 as i64 * self.x[1] as i64
+            - c.a1 as i64 * self.y[0] as i64
+            - c.a2 as i64 * self.y[1] as i64
+            + self.error;
+
+        let output = acc >> COEF_FRAC;
+        self.error = acc - (output << COEF_FRAC);
+        let output = output.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+
+    /// Returns the last output.
+    pub fn output(&self) -> i32 {
+        self.y[0]
+    }
+}
+
+/// Integer sine and cosine of an angle in radians (Q30, 0..π) for filter design.
+///
+/// Taylor series on the half angle (|x| <= π/2) followed by the double angle formulas.
+fn sincos_q30(angle: i64) -> (i64, i64) {
+    let one = 1i64 << DESIGN_FRAC;
+    let x = angle / 2;
+    let x2 = (x * x) >> DESIGN_FRAC;
+
+    // sin(x) = x - x^3/3! + x^5/5! - ..., cos(x) = 1 - x^2/2! + x^4/4! - ...
+    let mut sin = x;
+    let mut cos = one;
+    let mut term_sin = x;
+    let mut term_cos = one;
+    for n in 1..8i64 {
+        term_sin = -((term_sin * x2) >> DESIGN_FRAC) / ((2 * n) * (2 * n + 1));
+        term_cos = -((term_cos * x2) >> DESIGN_FRAC) / ((2 * n - 1) * (2 * n));
+        sin += term_sin;
+        cos += term_cos;
+    }
+
+    let sin2 = (2 * sin * cos) >> DESIGN_FRAC;
+    let cos2 = one - ((2 * sin * sin) >> DESIGN_FRAC);
+    (sin2, cos2)
+}
@@ -0,0 +1,244 @@
+// Implements the alpha-beta-gamma filter module, a steady-state Kalman filter for a
+// constant-acceleration model fusing position samples with a commanded acceleration.
+
+// Key Features:
+// - Three-state estimate: position, velocity and acceleration.
+// - Optional commanded acceleration input used in the prediction step.
+// - Gains given directly (alpha, beta, gamma) or derived from a single bandwidth.
+// - Integer-only math, wrapping position error for multi-turn encoders.
+
+// Detailed Operation:
+// For a constant-acceleration model with a constant noise ratio the Kalman gains converge to fixed
+// values, so the filter reduces to the alpha-beta-gamma form without covariance propagation.
+// Prediction: x += v + (a + u) / 2, v += a + u, where `u` is the commanded acceleration of the
+// tick. The residual `r` between the measured and predicted position corrects the states:
+// x += α * r, v += β * r, a += 2γ * r. When the commanded acceleration is accurate `a` only
+// tracks the residual acceleration (load, friction), which keeps the velocity lag close to zero
+// during profiled moves. `from_bandwidth` places all three poles at p = 1 - 2π * bw / fs:
+// α = 1 - p³, β = 1.5 * (1 - p)² * (1 + p), γ = (1 - p)³. Gains are kept in Q32, as γ of a
+// 10 Hz filter at 20 kHz is only about 3e-8.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the position state
+const FRAC: u32 = 16;
+/// Fractional bits of the gains
+const GAIN_FRAC: u32 = 32;
+/// Fractional bits of the velocity state (counts/tick)
+const VEL_FRAC: u32 = 32;
+/// Fractional bits of the acceleration state (counts/tick^2)
+const ACC_FRAC: u32 = 40;
+/// 2π scaled by 2^32
+const TWO_PI_Q32: i128 = 26_986_075_409;
+
+/// Steady-state Kalman filter for position, velocity and acceleration.
+pub struct AlphaBetaGamma {
+    frequency: u16, // Update frequency (ticks per second)
+    alpha: i64,     // Position gain (Q32)
+    beta: i64,      // Velocity gain (Q32)
+    gamma: i64,     // Acceleration gain (Q32)
+
+    position: i64,     // Estimated position (counts, Q16)
+    velocity: i64,     // Estimated velocity (counts/tick, Q32)
+    acceleration: i64, // Estimated residual acceleration (counts/tick^2, Q40)
+    commanded: i64,    // Commanded acceleration of the current tick (counts/tick^2, Q40)
+}
+
+impl AlphaBetaGamma {
+    /// Creates a filter with gains given directly.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    /// * `alpha`, `beta`, `gamma` - Filter gains (0..65536 = 0.0..1.0)
+    pub fn new(frequency: u16, alpha: u16, beta: u16, gamma: u16) -> Self {
+        Self {
+            frequency,
+            alpha: (alpha as i64) << (GAIN_FRAC - 16),
+            beta: (beta as i64) << (GAIN_FRAC - 16),
+            gamma: (gamma as i64) << (GAIN_FRAC - 16),
+            position: 0,
+            velocity: 0,
+            acceleration: 0,
+            commanded: 0,
+        }
+    }
+
+    /// Creates a filter with a triple pole at the given bandwidth (Hz).
+    ///
+    /// The bandwidth is limited to 1/8 of the update frequency for stability.
+    pub fn from_bandwidth(frequency: u16, bandwidth: u16) -> Self {
+        let freq = frequency.max(8) as i128;
+        let bandwidth = (bandwidth as i128).clamp(1, freq / 8);
+        let one = 1i128 << GAIN_FRAC;
+        let a = TWO_PI_Q32 * bandwidth / freq; // 1 - p (Q32)
+        let p = one - a;
+        let a2 = (a * a) >> GAIN_FRAC;
+        let a3 = (a2 * a) >> GAIN_FRAC;
+        let p3 = (((p * p) >> GAIN_FRAC) * p) >> GAIN_FRAC;
+
+        let mut filter = Self::new(frequency, 0, 0, 0);
+        filter.alpha = (one - p3) as i64;
+        filter.beta = ((3 * a2 * (one + p)) >> (GAIN_FRAC + 1)) as i64;
+        filter.gamma = a3 as i64;
+        filter
+    }
+
+    /// Sets the estimate to the given position at rest.
+    pub fn reset(&mut self, position: i32) {
+        self.position = (position as i64) << FRAC;
+        self.velocity = 0;
+        self.acceleration = 0;
+    }
+
+    /// Sets the commanded acceleration used by the next predictions (counts/s^2).
+    pub fn set_commanded_acceleration(&mut self, acceleration: i32) {
+        let freq = self.frequency.max(1) as i64;
+        let per_tick = ((acceleration as i64) << (ACC_FRAC - 16)) / (freq * freq);
+        self.commanded = per_tick << 16;
+    }
+
+    /// Advances the filter with a new measured position and returns the estimated position.
+    pub fn tick(&mut self, position: i32) -> i32 {
+        // ####### Prediction #######
+        let accel = self.acceleration + self.commanded;
+        self.position += (self.velocity >> (VEL_FRAC - FRAC)) + (accel >> (ACC_FRAC - FRAC + 1));
+        self.velocity += accel >> (ACC_FRAC - VEL_FRAC);
+
+        // ####### Correction #######
+        let estimate = (self.position >> FRAC) as i32;
+        let residual_counts = position.wrapping_sub(estimate) as i64;
+        let residual = (residual_counts << FRAC) - (self.position & ((1 << FRAC) - 1));
+        // Q32 gains times a Q16 residual, wide enough for any residual of the i32 position
+        let residual = residual as i128;
+        self.position += ((self.alpha as i128 * residual) >> GAIN_FRAC) as i64;
+        self.velocity += ((self.beta as i128 * residual) >> (GAIN_FRAC + FRAC - VEL_FRAC)) as i64;
+        self.acceleration += ((2 * self.gamma as i128 * residual) >> (GAIN_FRAC + FRAC - ACC_FRAC)) as i64;
+
+        self.position()
+    }
+
+    /// Returns the estimated position (counts, rounded).
+    pub fn position(&self) -> i32 {
+        // Rounding keeps a settled estimate from toggling around an integer by its last fraction bit
+        ((self.position + (1 << (FRAC - 1))) >> FRAC) as i32
+    }
+
+    /// Returns the estimated velocity (counts/s).
+    pub fn velocity(&self) -> i32 {
+        ((self.velocity * self.frequency as i64) >> VEL_FRAC) as i32
+    }
+
+    /// Returns the estimated total acceleration including the commanded one (counts/s^2).
+    pub fn acceleration(&self) -> i32 {
+        let freq = self.frequency as i64;
+        let accel = (self.acceleration + self.commanded) >> (ACC_FRAC - 24);
+        ((accel * freq * freq) >> 24) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::f64::consts::PI;
+
+    const FS: u16 = 20000;
+
+    /// Samples of a constant acceleration from rest (counts/s^2), truncated to whole counts.
+    fn accelerating(accel: i64, t: i64) -> i32 {
+        (accel * t * t / (2 * FS as i64 * FS as i64)) as i32
+    }
+
+    #[test]
+    fn step_converges_without_steady_state_error() {
+        let mut filter = AlphaBetaGamma::from_bandwidth(FS, 200);
+        filter.reset(0);
+        for _ in 0..40_000 {
+            filter.tick(1000);
+            assert!(filter.position() <= 1250); // A type 3 loop overshoots a step by about 23%
+        }
+        assert_eq!(filter.position(), 1000);
+        assert_eq!(filter.velocity(), 0);
+        assert!(filter.acceleration().abs() <= 50, "acceleration {}", filter.acceleration()); // Last bit
+    }
+
+    #[test]
+    fn ramp_velocity_is_tracked() {
+        let mut filter = AlphaBetaGamma::from_bandwidth(FS, 200);
+        filter.reset(0);
+        let speed = 50; // counts/tick
+        let mut position = 0i32;
+        for _ in 0..4000 {
+            position = position.wrapping_add(speed);
+            filter.tick(position);
+        }
+        assert_eq!(filter.position(), position);
+        assert!((filter.velocity() - speed * FS as i32).abs() <= 1, "velocity {}", filter.velocity());
+    }
+
+    #[test]
+    fn acceleration_is_tracked_without_lag() {
+        let accel = 2_000_000; // counts/s^2
+        for bandwidth in [20, 200] {
+            // Constant acceleration is inside the model: no steady-state lag even unassisted
+            let mut filter = AlphaBetaGamma::from_bandwidth(FS, bandwidth);
+            filter.reset(0);
+            let (mut velocity_error, mut acceleration) = (0, 0);
+            for t in 1..=40_000 {
+                filter.tick(accelerating(accel, t));
+                if t > 20_000 {
+                    // Sample truncation modulates the estimates, the mean is what tracks
+                    assert!((filter.position() - accelerating(accel, t)).abs() <= 1);
+                    velocity_error += filter.velocity() as i64 - accel * t / FS as i64;
+                    acceleration += filter.acceleration() as i64;
+                }
+            }
+            let (velocity_error, acceleration) = (velocity_error / 20_000, acceleration / 20_000);
+            assert!(velocity_error.abs() <= accel / FS as i64, "{} Hz: velocity lag {}", bandwidth, velocity_error);
+            assert!((acceleration - accel).abs() <= accel / 1000, "{} Hz: acceleration {}", bandwidth, acceleration);
+        }
+
+        // With the commanded acceleration the estimate doesn't even lag while settling
+        let mut filter = AlphaBetaGamma::from_bandwidth(FS, 200);
+        filter.reset(0);
+        filter.set_commanded_acceleration(accel as i32);
+        for t in 1..=4000 {
+            let lag = accelerating(accel, t) - filter.tick(accelerating(accel, t));
+            assert!(lag.abs() <= 1, "lag {} at tick {}", lag, t);
+        }
+    }
+
+    #[test]
+    fn bandwidth_places_a_triple_pole() {
+        for bandwidth in [10, 200, 2500] {
+            let filter = AlphaBetaGamma::from_bandwidth(FS, bandwidth);
+            let a = 2.0 * PI * bandwidth as f64 / FS as f64; // 1 - p
+            let expected = [1.0 - (1.0 - a).powi(3), 1.5 * a * a * (2.0 - a), a.powi(3)];
+            for (gain, expected) in [filter.alpha, filter.beta, filter.gamma].into_iter().zip(expected) {
+                // Even γ of the slowest filter (~133 LSB) is resolved to a fraction of a percent
+                let expected = expected * (1u64 << GAIN_FRAC) as f64;
+                let error = gain as f64 - expected;
+                assert!(error.abs() <= expected * 1e-3 + 1.0, "{} Hz: {} vs {}", bandwidth, gain, expected);
+            }
+        }
+        // Limited to 1/8 of the update frequency
+        let (limited, fastest) = (AlphaBetaGamma::from_bandwidth(FS, 10_000), AlphaBetaGamma::from_bandwidth(FS, 2500));
+        assert_eq!((limited.alpha, limited.beta, limited.gamma), (fastest.alpha, fastest.beta, fastest.gamma));
+    }
+
+    #[test]
+    fn reset_restarts_at_rest() {
+        let mut filter = AlphaBetaGamma::from_bandwidth(FS, 200);
+        filter.reset(0);
+        for t in 1..=1000 {
+            filter.tick(accelerating(2_000_000, t));
+        }
+        filter.reset(-5000);
+        assert_eq!((filter.position(), filter.velocity(), filter.acceleration()), (-5000, 0, 0));
+        for _ in 0..100 {
+            filter.tick(-5000);
+        }
+        assert_eq!((filter.position(), filter.velocity()), (-5000, 0));
+    }
+}
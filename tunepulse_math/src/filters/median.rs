@@ -0,0 +1,97 @@
+// Implements the median filter module, removing single-sample glitches from sensor data
+// (e.g. corrupted SPI encoder frames) while keeping edges sharp.
+
+// Key Features:
+// - Runtime selectable window of 1 (bypass), 3 or 5 samples.
+// - Wrap-aware for angle inputs (u16 full turn), works across the 0/65535 boundary.
+// - Rejects up to 1 (window 3) or 2 (window 5) consecutive outliers of any magnitude.
+// - Fixed buffer, no allocation, insertion sort of at most 5 elements.
+
+// Detailed Operation:
+// Unlike a low-pass filter, the median ignores outliers completely instead of averaging them into
+// the output, so a single corrupted sample has no effect on the result. Angles wrap, so samples are
+// compared as signed distances from the newest sample; the median distance is then added back to the
+// newest sample. This is exact as long as the motion within the window stays below half a turn. The
+// filter delays steps by (window - 1) / 2 samples, 1 tick for the 3-sample window.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Largest supported window
+const MEDIAN_MAX: usize = 5;
+
+/// Median filter for wrapping u16 angles.
+pub struct FilterMedian {
+    samples: [u16; MEDIAN_MAX], // Ring buffer of the latest samples
+    index: usize,               // Position of the newest sample
+    taps: usize,                // Window length (1, 3 or 5)
+    output: u16,                // Last output
+}
+
+impl FilterMedian {
+    /// Creates a filter pre-filled with `input_default`.
+    ///
+    /// # Arguments
+    /// * `input_default` - Initial sample value
+    /// * `taps` - Window length: 1 (bypass), 3 or 5 (other values are rounded down to these)
+    pub fn new(input_default: u16, taps: u8) -> Self {
+        let mut filter = Self {
+            samples: [input_default; MEDIAN_MAX],
+            index: 0,
+            taps: 1,
+            output: input_default,
+        };
+        filter.set_taps(taps);
+        filter
+    }
+
+    /// Changes the window length (1, 3 or 5), the history is refilled with the last output.
+    pub fn set_taps(&mut self, taps: u8) {
+        self.taps = match taps {
+            0..=2 => 1,
+            3..=4 => 3,
+            _ => 5,
+        };
+        self.samples = [self.output; MEDIAN_MAX];
+    }
+
+    /// Returns the window length.
+    pub fn taps(&self) -> u8 {
+        self.taps as u8
+    }
+
+    /// Filters one sample.
+    pub fn tick(&mut self, input: u16) -> u16 {
+        self.index = (self.index + 1) % MEDIAN_MAX;
+        self.samples[self.index] = input;
+        if self.taps == 1 {
+            self.output = input;
+            return input;
+        }
+
+        // Signed distances of the window samples from the newest one
+        let mut window = [0i16; MEDIAN_MAX];
+        for (n, slot) in window.iter_mut().take(self.taps).enumerate() {
+            let sample = self.samples[(self.index + MEDIAN_MAX - n) % MEDIAN_MAX];
+            *slot = sample.wrapping_sub(input) as i16;
+        }
+
+        // Insertion sort (at most 5 elements)
+        let window = &mut window[..self.taps];
+        for i in 1..window.len() {
+            let mut j = i;
+            while j > 0 && window[j - 1] > window[j] {
+                window.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+
+        self.output = input.wrapping_add(window[self.taps / 2] as u16);
+        self.output
+    }
+
+    /// Returns the last output.
+    pub fn output(&self) -> u16 {
+        self.output
+    }
+}
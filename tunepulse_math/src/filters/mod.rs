@@ -0,0 +1,6 @@
+pub mod lpf;
+pub mod kalman;
+pub mod biquad;
+pub mod median;
+pub mod slew;
+pub mod average;
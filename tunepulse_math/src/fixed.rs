@@ -0,0 +1,268 @@
+// Implements the fixed-point module, wrapping i16 / i32 values into Q15 / Q31 types with
+// saturating arithmetic so fractional math can't silently overflow.
+
+// Key Features:
+// - Q15 (i16, 15 fractional bits) and Q31 (i32, 31 fractional bits) newtypes, range [-1.0, 1.0).
+// - Saturating add, sub, mul, div and multiply-accumulate (mac), rounded to nearest.
+// - Ratio constructor: num / den as a fraction, saturating instead of overflowing (and on den = 0).
+// - Scaling of plain integers by a fraction and lossless Q15 <-> Q31 conversions.
+// - All operations are const fns on 32/64-bit intermediates, no floating point.
+
+// Detailed Operation:
+// Raw `(a << 15) / b` style expressions overflow the i16 range as soon as |a| >= |b|, and the
+// result wraps into the opposite sign when it is cast back. The types here compute every product
+// and quotient in the next wider integer and clamp it to the representable range, so the worst case
+// is a saturated value. The only value outside [-1.0, 1.0) is never produced: -1.0 * -1.0 gives
+// the largest positive value instead. The inner value is public to allow cheap interop with the
+// existing i16 normalized signals (`Q15(value)` / `value.0`).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fraction with 15 fractional bits stored in i16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Q15(pub i16);
+
+/// Fraction with 31 fractional bits stored in i32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Q31(pub i32);
+
+impl Q15 {
+    /// Largest value (1.0 - 2^-15)
+    pub const ONE: Q15 = Q15(i16::MAX);
+    /// Zero
+    pub const ZERO: Q15 = Q15(0);
+    /// Smallest value (-1.0)
+    pub const MINUS_ONE: Q15 = Q15(i16::MIN);
+
+    /// Creates num / den as a fraction, saturated to [-1.0, 1.0) (den = 0 saturates by sign of num).
+    pub const fn from_ratio(num: i32, den: i32) -> Q15 {
+        Q15(sat_i16(div_round(num as i64, den as i64, 15)))
+    }
+
+    /// Saturating addition.
+    pub const fn sat_add(self, rhs: Q15) -> Q15 {
+        Q15(self.0.saturating_add(rhs.0))
+    }
+
+    /// Saturating subtraction.
+    pub const fn sat_sub(self, rhs: Q15) -> Q15 {
+        Q15(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Saturating multiplication, rounded to nearest.
+    pub const fn sat_mul(self, rhs: Q15) -> Q15 {
+        let product = self.0 as i32 * rhs.0 as i32;
+        Q15(sat_i16(((product + (1 << 14)) >> 15) as i64))
+    }
+
+    /// Saturating division, rounded to nearest.
+    pub const fn sat_div(self, rhs: Q15) -> Q15 {
+        Q15::from_ratio(self.0 as i32, rhs.0 as i32)
+    }
+
+    /// Multiply-accumulate: self + a * b, saturated once at the end.
+    pub const fn mac(self, a: Q15, b: Q15) -> Q15 {
+        let product = a.0 as i32 * b.0 as i32;
+        let sum = ((self.0 as i32) << 15) + product;
+        Q15(sat_i16(((sum + (1 << 14)) >> 15) as i64))
+    }
+
+    /// Scales an integer by the fraction (value * self), rounded to nearest.
+    pub const fn scale(self, value: i32) -> i32 {
+        ((value as i64 * self.0 as i64 + (1 << 14)) >> 15) as i32
+    }
+
+    /// Absolute value (saturating for -1.0).
+    pub const fn abs(self) -> Q15 {
+        Q15(self.0.saturating_abs())
+    }
+
+    /// Converts to Q31 without loss.
+    pub const fn to_q31(self) -> Q31 {
+        Q31((self.0 as i32) << 16)
+    }
+}
+
+impl Q31 {
+    /// Largest value (1.0 - 2^-31)
+    pub const ONE: Q31 = Q31(i32::MAX);
+    /// Zero
+    pub const ZERO: Q31 = Q31(0);
+    /// Smallest value (-1.0)
+    pub const MINUS_ONE: Q31 = Q31(i32::MIN);
+
+    /// Creates num / den as a fraction, saturated to [-1.0, 1.0) (den = 0 saturates by sign of num).
+    pub const fn from_ratio(num: i32, den: i32) -> Q31 {
+        Q31(sat_i32(div_round(num as i64, den as i64, 31)))
+    }
+
+    /// Saturating addition.
+    pub const fn sat_add(self, rhs: Q31) -> Q31 {
+        Q31(self.0.saturating_add(rhs.0))
+    }
+
+    /// Saturating subtraction.
+    pub const fn sat_sub(self, rhs: Q31) -> Q31 {
+        Q31(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Saturating multiplication, rounded to nearest.
+    pub const fn sat_mul(self, rhs: Q31) -> Q31 {
+        let product = self.0 as i64 * rhs.0 as i64;
+        Q31(sat_i32((product + (1 << 30)) >> 31))
+    }
+
+    /// Saturating division, rounded to nearest.
+    pub const fn sat_div(self, rhs: Q31) -> Q31 {
+        Q31::from_ratio(self.0, rhs.0)
+    }
+
+    /// Multiply-accumulate: self + a * b, saturated once at the end.
+    pub const fn mac(self, a: Q31, b: Q31) -> Q31 {
+        let product = a.0 as i64 * b.0 as i64;
+        let sum = ((self.0 as i64) << 31).saturating_add(product);
+        Q31(sat_i32((sum.saturating_add(1 << 30)) >> 31))
+    }
+
+    /// Scales an integer by the fraction (value * self), rounded to nearest.
+    pub const fn scale(self, value: i32) -> i32 {
+        ((value as i64 * self.0 as i64 + (1 << 30)) >> 31) as i32
+    }
+
+    /// Absolute value (saturating for -1.0).
+    pub const fn abs(self) -> Q31 {
+        Q31(self.0.saturating_abs())
+    }
+
+    /// Converts to Q15, rounded to nearest.
+    pub const fn to_q15(self) -> Q15 {
+        Q15(sat_i16((self.0 as i64 + (1 << 15)) >> 16))
+    }
+}
+
+impl From<Q15> for Q31 {
+    fn from(value: Q15) -> Q31 {
+        value.to_q31()
+    }
+}
+
+impl From<Q31> for Q15 {
+    fn from(value: Q31) -> Q15 {
+        value.to_q15()
+    }
+}
+
+/// (num << shift) / den rounded to nearest, den = 0 gives the saturated sign of num.
+#[inline(always)]
+const fn div_round(num: i64, den: i64, shift: u32) -> i64 {
+    if den == 0 {
+        return if num > 0 { i64::MAX } else if num < 0 { i64::MIN } else { 0 };
+    }
+    // |num| < 2^31 and shift <= 31, so the shifted numerator fits into i64
+    let num = num << shift;
+    let half = den.abs() / 2;
+    // Numerator moved away from zero by half the divisor, so the truncation rounds half away from zero
+    if num < 0 {
+        (num - half) / den
+    } else {
+        (num + half) / den
+    }
+}
+
+#[inline(always)]
+const fn sat_i16(value: i64) -> i16 {
+    if value > i16::MAX as i64 {
+        i16::MAX
+    } else if value < i16::MIN as i64 {
+        i16::MIN
+    } else {
+        value as i16
+    }
+}
+
+#[inline(always)]
+const fn sat_i32(value: i64) -> i32 {
+    if value > i32::MAX as i64 {
+        i32::MAX
+    } else if value < i32::MIN as i64 {
+        i32::MIN
+    } else {
+        value as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values spread over the i16 range, including both ends.
+    fn samples() -> impl Iterator<Item = i16> {
+        (i16::MIN..=i16::MAX).step_by(251).chain([i16::MIN + 1, -1, 0, 1, i16::MAX])
+    }
+
+    #[test]
+    fn q15_mul_rounds_and_saturates() {
+        for a in samples() {
+            for b in samples() {
+                let exact = (a as f64 * b as f64 / 32768.0).round().clamp(i16::MIN as f64, i16::MAX as f64);
+                assert!((Q15(a).sat_mul(Q15(b)).0 as f64 - exact).abs() <= 1.0, "{} * {}", a, b);
+            }
+        }
+        assert_eq!(Q15::MINUS_ONE.sat_mul(Q15::MINUS_ONE), Q15::ONE); // 1.0 isn't representable
+        assert_eq!(Q15(16384).sat_mul(Q15(16384)), Q15(8192)); // 0.5 * 0.5
+    }
+
+    #[test]
+    fn q15_ratio_and_division() {
+        assert_eq!(Q15::from_ratio(1, 2), Q15(16384));
+        assert_eq!(Q15::from_ratio(-1, 3), Q15(-10923)); // Rounded to nearest
+        assert_eq!(Q15::from_ratio(1, -3), Q15(-10923));
+        assert_eq!(Q15::from_ratio(-1, -3), Q15(10923));
+        assert_eq!(Q15::from_ratio(3, 2), Q15::ONE);
+        assert_eq!(Q15::from_ratio(-3, 2), Q15::MINUS_ONE);
+        assert_eq!(Q15::from_ratio(5, 0), Q15::ONE);
+        assert_eq!(Q15::from_ratio(-5, 0), Q15::MINUS_ONE);
+        assert_eq!(Q15::from_ratio(0, 0), Q15::ZERO);
+        assert_eq!(Q15::from_ratio(i32::MIN, -1), Q15::ONE);
+        assert_eq!(Q15(8192).sat_div(Q15(16384)), Q15(16384)); // 0.25 / 0.5
+        assert_eq!(Q15(16384).sat_div(Q15(8192)), Q15::ONE);
+    }
+
+    #[test]
+    fn q15_add_mac_scale_saturate() {
+        assert_eq!(Q15::ONE.sat_add(Q15::ONE), Q15::ONE);
+        assert_eq!(Q15::MINUS_ONE.sat_sub(Q15::ONE), Q15::MINUS_ONE);
+        assert_eq!(Q15::ONE.mac(Q15::MINUS_ONE, Q15::MINUS_ONE), Q15::ONE);
+        assert_eq!(Q15::MINUS_ONE.mac(Q15::MINUS_ONE, Q15::ONE), Q15::MINUS_ONE);
+        assert_eq!(Q15(16384).mac(Q15(16384), Q15(-16384)), Q15(8192)); // 0.5 - 0.25
+        assert_eq!(Q15::MINUS_ONE.abs(), Q15::ONE);
+        assert_eq!(Q15(16384).scale(1000), 500);
+        assert_eq!(Q15(-16384).scale(i32::MAX), -(1 << 30) + 1); // -0.5 * (2^31 - 1), half rounded up
+    }
+
+    #[test]
+    fn q31_arithmetic() {
+        assert_eq!(Q31::from_ratio(1, 4), Q31(1 << 29));
+        assert_eq!(Q31::from_ratio(i32::MIN, i32::MIN), Q31::ONE);
+        assert_eq!(Q31::from_ratio(i32::MIN, i32::MAX), Q31::MINUS_ONE);
+        assert_eq!(Q31::from_ratio(7, 0), Q31::ONE);
+        assert_eq!(Q31::MINUS_ONE.sat_mul(Q31::MINUS_ONE), Q31::ONE);
+        assert_eq!(Q31(1 << 30).sat_mul(Q31(-(1 << 30))), Q31(-(1 << 29)));
+        assert_eq!(Q31::ONE.mac(Q31::ONE, Q31::ONE), Q31::ONE);
+        assert_eq!(Q31::MINUS_ONE.mac(Q31::MINUS_ONE, Q31::ONE), Q31::MINUS_ONE);
+        assert_eq!(Q31(1 << 30).sat_div(Q31(-(1 << 30))), Q31::MINUS_ONE);
+        assert_eq!(Q31(1 << 30).scale(-1000), -500);
+        assert_eq!(Q31::MINUS_ONE.abs(), Q31::ONE);
+    }
+
+    #[test]
+    fn q15_q31_conversions() {
+        for value in samples() {
+            assert_eq!(Q15(value).to_q31().to_q15(), Q15(value));
+            assert_eq!(Q15::from(Q31::from(Q15(value))), Q15(value));
+        }
+        assert_eq!(Q31((1 << 16) + (1 << 15)).to_q15(), Q15(2)); // Rounded to nearest
+        assert_eq!(Q31::ONE.to_q15(), Q15::ONE); // Saturated instead of wrapping
+    }
+}
@@ -0,0 +1,82 @@
+// Implements the AS5047 decoder, checking the SPI angle frame and turning the diagnostic
+// registers (DIAAGC, MAG) into frame flags and a magnet health value.
+
+// Key Features:
+// - Angle frame: even parity over the whole word, error flag, 14-bit angle.
+// - DIAAGC: magnet too strong (MAGH) / too weak (MAGL), CORDIC overflow, AGC loop state.
+// - Magnet health 0..100 % from the AGC value, which measures how far the field is from ideal.
+
+// Detailed Operation:
+// Every 16-bit SPI answer carries the data in bits 13..0, the error flag EF in bit 14 (the previous
+// command failed, details in ERRFL) and even parity over bits 14..0 in bit 15. The AGC regulates the
+// Hall front end gain so the magnet looks nominal: a weak magnet (large gap) pushes it towards 255,
+// a strong one (small gap) towards 0. Half scale leaves the largest margin, so the health drops
+// linearly from 100 % at AGC 128 to 0 % at either end, where MAGL / MAGH are set and the angle
+// loses accuracy. A CORDIC overflow means the angle output is invalid.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::{EncoderFrame, MagnetField, MagnetHealth, FRAME_CRC_ERROR, FRAME_ERROR, FRAME_WARNING};
+
+/// Angle register with dynamic compensation (ANGLECOM)
+pub const AS5047_ANGLECOM: u16 = 0x3FFF;
+/// Diagnostic and AGC register
+pub const AS5047_DIAAGC: u16 = 0x3FFC;
+/// CORDIC magnitude register
+pub const AS5047_MAG: u16 = 0x3FFD;
+
+/// Builds a read command for `register` (read bit and even parity set).
+pub const fn read_command(register: u16) -> u16 {
+    let command = (register & 0x3FFF) | 1 << 14;
+    command | (parity(command) << 15)
+}
+
+/// Decodes an angle frame.
+pub const fn decode_angle(word: u16) -> EncoderFrame {
+    let mut status = 0;
+    if parity(word & 0x7FFF) != word >> 15 {
+        status |= FRAME_CRC_ERROR;
+    }
+    if word & (1 << 14) != 0 {
+        status |= FRAME_WARNING; // Previous command failed, the angle itself is fine
+    }
+    EncoderFrame { angle: (word & 0x3FFF) << 2, turns: 0, status }
+}
+
+/// Decodes the DIAAGC register answer.
+pub const fn decode_diagnostics(word: u16) -> MagnetHealth {
+    let agc = (word & 0xFF) as i32;
+    let lf = word & (1 << 8) != 0; // Offset compensation finished
+    let cof = word & (1 << 9) != 0; // CORDIC overflow
+    let magh = word & (1 << 10) != 0;
+    let magl = word & (1 << 11) != 0;
+
+    let field = if magl {
+        MagnetField::Weak
+    } else if magh {
+        MagnetField::Strong
+    } else {
+        MagnetField::Ok
+    };
+    let distance = if agc >= 128 { agc - 128 } else { 128 - agc };
+    let health = if distance >= 128 { 0 } else { (100 - distance * 100 / 128) as u8 };
+    let mut status = 0;
+    if cof || magh || magl {
+        status |= FRAME_ERROR;
+    }
+    if !lf {
+        status |= FRAME_WARNING;
+    }
+    MagnetHealth { field, health, status }
+}
+
+/// Returns the field magnitude of the MAG register answer (CORDIC units, 14 bits).
+pub const fn decode_magnitude(word: u16) -> u16 {
+    word & 0x3FFF
+}
+
+/// Even parity bit of `word`.
+const fn parity(word: u16) -> u16 {
+    (word.count_ones() & 1) as u16
+}
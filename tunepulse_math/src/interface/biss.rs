@@ -0,0 +1,96 @@
+// Implements the BiSS-C frame decoder, locating the frame in the raw capture, checking its CRC6
+// and extracting the position with the error and warning bits.
+
+// Key Features:
+// - Start bit search after the acknowledge, any line delay within the captured word.
+// - Multi-turn and single-turn bits of any split (up to 32 each).
+// - Inverted CRC6 (x^6 + x + 1) over position and status bits.
+// - Active-low error (nE) and warning (nW) bits mapped to FRAME_ERROR / FRAME_WARNING.
+
+// Detailed Operation:
+// After the master starts clocking, the slave holds the line high, pulls it low while converting
+// (acknowledge, a line-delay dependent number of bits), then sends the start bit (1), the CDS bit
+// (0), the position (multi-turn then single-turn), nE, nW and the inverted CRC6, MSB first. The HAL
+// passes the captured bits MSB first and right aligned in a word of `raw_bits`; the decoder skips the
+// leading high bits, the acknowledge low bits and the start and CDS bits, so the frame may start
+// anywhere as long as the whole frame was captured. A missing start bit, a truncated frame or a CRC
+// mismatch marks the frame invalid. The CRC covers the position and both status bits.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::crc::crc6_biss;
+use super::{field, scale_angle, EncoderFrame, FRAME_CRC_ERROR, FRAME_ERROR, FRAME_WARNING};
+
+/// CRC bits at the end of the frame
+const BISS_CRC_BITS: u32 = 6;
+
+/// Layout of a BiSS-C single-cycle data frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BissFormat {
+    /// Multi-turn bits (0 for single-turn encoders)
+    pub turn_bits: u32,
+    /// Single-turn resolution bits
+    pub bits: u32,
+}
+
+impl BissFormat {
+    /// Creates a single-turn layout.
+    ///
+    /// # Arguments
+    /// * `bits` - Single-turn resolution bits (1..=32)
+    pub const fn new(bits: u32) -> Self {
+        Self { turn_bits: 0, bits }
+    }
+
+    /// Returns the bits from the start bit to the end of the CRC.
+    pub const fn frame_bits(&self) -> u32 {
+        2 + self.turn_bits + self.bits + 2 + BISS_CRC_BITS
+    }
+
+    /// Decodes a raw capture.
+    ///
+    /// # Arguments
+    /// * `raw` - Captured bits, first received bit highest, last received bit in bit 0
+    /// * `raw_bits` - Number of captured bits (up to 64)
+    pub fn decode(&self, raw: u64, raw_bits: u32) -> EncoderFrame {
+        let invalid = EncoderFrame { angle: 0, turns: 0, status: FRAME_CRC_ERROR };
+        let raw_bits = raw_bits.min(64);
+        let bit = |index: u32| (raw >> index) & 1;
+
+        // Skip the idle high bits, then the acknowledge low bits, to reach the start bit
+        let mut index = raw_bits;
+        while index > 0 && bit(index - 1) == 1 {
+            index -= 1;
+        }
+        while index > 0 && bit(index - 1) == 0 {
+            index -= 1;
+        }
+        // Start bit at index - 1, CDS below it, then the data
+        let data_bits = self.turn_bits + self.bits + 2;
+        if index < 2 + data_bits + BISS_CRC_BITS {
+            return invalid;
+        }
+        let end = index - 2 - data_bits - BISS_CRC_BITS; // Bit following the CRC
+        let crc = field(raw, end, BISS_CRC_BITS) as u8;
+        let data = raw >> (end + BISS_CRC_BITS);
+        let data = if data_bits >= 64 { data } else { data & ((1u64 << data_bits) - 1) };
+        if crc6_biss(data, data_bits) != crc {
+            return invalid;
+        }
+
+        let mut status = 0;
+        if data & 0b10 == 0 {
+            status |= FRAME_ERROR; // nE is active low
+        }
+        if data & 0b01 == 0 {
+            status |= FRAME_WARNING; // nW is active low
+        }
+        let position = data >> 2;
+        EncoderFrame {
+            angle: scale_angle(field(position, 0, self.bits.min(32)), self.bits.clamp(1, 32)),
+            turns: field(position, self.bits.min(63), self.turn_bits.min(32)),
+            status,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Implements the CRC module, bitwise cyclic redundancy checks over frames that are not a whole
+// number of bytes, as used by serial encoder protocols, and over byte buffers.
+
+// Key Features:
+// - Generic CRC of up to 8 bits over up to 64 frame bits, MSB first.
+// - BiSS-C CRC6 (polynomial 0x43) with the inverted transmission of the protocol.
+// - CRC-16/CCITT-FALSE over byte slices for stored records and serial links, chainable.
+// - CRC-16/MODBUS (reflected, LSB first) for Modbus RTU frames.
+
+// Detailed Operation:
+// The bits are shifted in MSB first; whenever the bit leaving the register differs from the
+// incoming data bit the polynomial (without its leading term) is XORed in. Encoder frames are
+// short (typically 20..40 bits), so the bitwise form costs a few hundred cycles at most and needs
+// no lookup table. Byte buffers (stored configuration, host frames) use the same shift and XOR per
+// bit with a 16-bit register; passing the previous result as `init` continues a CRC over data
+// split into several slices. Modbus shifts the bytes LSB first, its reflected polynomial is XORed
+// in when the bit leaving the low end of the register is set.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// BiSS-C CRC polynomial x^6 + x + 1 without the leading term
+pub const CRC6_BISS_POLY: u8 = 0x03;
+/// CCITT CRC polynomial x^16 + x^12 + x^5 + 1 without the leading term
+pub const CRC16_CCITT_POLY: u16 = 0x1021;
+/// Start value of CRC-16/CCITT-FALSE
+pub const CRC16_CCITT_INIT: u16 = 0xFFFF;
+/// Modbus CRC polynomial 0x8005, bit reflected
+pub const CRC16_MODBUS_POLY: u16 = 0xA001;
+
+/// Computes a CRC over the low `bits` bits of `data`, MSB first.
+///
+/// # Arguments
+/// * `data` - Frame bits, right aligned
+/// * `bits` - Number of frame bits (0..=64)
+/// * `poly` - Polynomial without its leading term
+/// * `width` - CRC width (1..=8)
+/// * `init` - Start value of the register
+pub const fn crc_bits(data: u64, bits: u32, poly: u8, width: u32, init: u8) -> u8 {
+    let mask = ((1u16 << width) - 1) as u8;
+    let mut crc = init & mask;
+    let mut index = bits;
+    while index > 0 {
+        index -= 1;
+        let bit = ((data >> index) & 1) as u8;
+        let feedback = ((crc >> (width - 1)) & 1) ^ bit;
+        crc = (crc << 1) & mask;
+        if feedback != 0 {
+            crc ^= poly & mask;
+        }
+    }
+    crc
+}
+
+/// Computes the BiSS-C CRC6 as transmitted (inverted) over the low `bits` bits of `data`.
+#[inline(always)]
+pub const fn crc6_biss(data: u64, bits: u32) -> u8 {
+    !crc_bits(data, bits, CRC6_BISS_POLY, 6, 0) & 0x3F
+}
+
+/// Computes the CRC-16/CCITT-FALSE of `data`, MSB first, no final XOR.
+///
+/// # Arguments
+/// * `data` - Bytes to check
+/// * `init` - CRC16_CCITT_INIT, or the result over the preceding bytes
+pub const fn crc16_ccitt(data: &[u8], init: u16) -> u16 {
+    let mut crc = init;
+    let mut index = 0;
+    while index < data.len() {
+        crc ^= (data[index] as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ CRC16_CCITT_POLY } else { crc << 1 };
+            bit += 1;
+        }
+        index += 1;
+    }
+    crc
+}
+
+/// Computes the CRC-16/MODBUS of `data` (start value 0xFFFF, sent low byte first).
+pub const fn crc16_modbus(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    let mut index = 0;
+    while index < data.len() {
+        crc ^= data[index] as u16;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ CRC16_MODBUS_POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        index += 1;
+    }
+    crc
+}
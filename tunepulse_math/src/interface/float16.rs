@@ -0,0 +1,71 @@
+// Implements the half-precision float module, converting between IEEE 754 binary16 values used by
+// fieldbus messages and scaled integers, without floating point hardware.
+
+// Key Features:
+// - Integer value with a scale (e.g. mV with scale 1000 for volts) to binary16, round to nearest.
+// - binary16 to a scaled integer, saturating at the i32 range.
+// - Subnormals, infinities (saturated) and NaN (read as 0) handled.
+
+// Detailed Operation:
+// A normal binary16 value is (1024 + mantissa) * 2^(exponent - 25) with the biased exponent 1..30,
+// a subnormal one mantissa * 2^-24. Encoding searches the smallest exponent whose mantissa, the
+// rounded value * 2^(25 - exponent), fits in 11 bits; the search goes from the finest step up, so
+// the result uses the full precision. Values above 65504 encode as infinity. Decoding multiplies the
+// mantissa by the scale and shifts by the exponent, rounding half away from zero.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// binary16 positive infinity
+pub const F16_INFINITY: u16 = 0x7C00;
+
+/// Encodes `value / scale` as binary16.
+///
+/// # Arguments
+/// * `value` - Scaled value (e.g. mV)
+/// * `scale` - Units per one (e.g. 1000 for mV to V), > 0
+pub fn to_f16(value: i64, scale: i64) -> u16 {
+    let sign = if value < 0 { 0x8000 } else { 0 };
+    let magnitude = value.unsigned_abs() as u128;
+    let scale = scale.max(1) as u128;
+    if magnitude == 0 {
+        return sign;
+    }
+    for exponent in 1..=30u32 {
+        let mantissa = if exponent <= 25 {
+            ((magnitude << (25 - exponent)) + scale / 2) / scale
+        } else {
+            let divisor = scale << (exponent - 25);
+            (magnitude + divisor / 2) / divisor
+        };
+        if mantissa < 2048 {
+            if mantissa < 1024 {
+                return sign | mantissa as u16; // Subnormal (only reached from exponent 1)
+            }
+            return sign | ((exponent as u16) << 10) | (mantissa as u16 & 0x3FF);
+        }
+    }
+    sign | F16_INFINITY
+}
+
+/// Decodes binary16 into a value scaled by `scale` (e.g. 1000 for V to mV).
+pub fn from_f16(bits: u16, scale: i64) -> i32 {
+    let negative = bits & 0x8000 != 0;
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let fraction = (bits & 0x3FF) as i128;
+    let magnitude = match exponent {
+        31 if fraction != 0 => return 0, // NaN
+        31 => i32::MAX as i128,          // Infinity
+        _ => {
+            let (mantissa, shift) = if exponent == 0 { (fraction, -24) } else { (fraction | 1024, exponent - 25) };
+            let scaled = mantissa * scale as i128;
+            if shift >= 0 {
+                scaled << shift
+            } else {
+                (scaled + (1i128 << (-shift - 1))) >> -shift
+            }
+        }
+    };
+    let magnitude = magnitude.min(i32::MAX as i128) as i32;
+    if negative { -magnitude } else { magnitude }
+}
@@ -0,0 +1,87 @@
+// Implements the interface module, turning raw frames of serial position encoders into a clean
+// angle and status word, so the HAL only moves raw bits into the algo crate.
+
+// Key Features:
+// - Common decoded frame: 16-bit angle, multi-turn count and status flags.
+// - Status flags laid out like `DataInputs::encoder_status` of tunepulse_algo.
+// - SSI (binary or Gray, optional parity) and BiSS-C (CRC6, error / warning bits) decoders.
+// - AS5047 / MT6701 status fields (AGC, magnitude, MAGL / MAGH) with a magnet health value.
+// - binary16 (half float) conversion of scaled integers for fieldbus messages.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+pub mod crc;
+pub mod ssi;
+pub mod biss;
+pub mod as5047;
+pub mod mt6701;
+pub mod float16;
+
+/// Frame flag: CRC / parity mismatch or no frame found (same bit as the algo's ENC_PARITY_ERROR)
+pub const FRAME_CRC_ERROR: u8 = 1 << 2;
+/// Frame flag: the sensor reported an error, e.g. magnet lost (same bit as ENC_MAGNITUDE_ERROR)
+pub const FRAME_ERROR: u8 = 1 << 3;
+/// Frame flag: the sensor reported a warning, position still valid
+pub const FRAME_WARNING: u8 = 1 << 4;
+
+/// Position decoded from one encoder frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EncoderFrame {
+    /// Single-turn angle scaled to 16 bits (65536 = one turn)
+    pub angle: u16,
+    /// Multi-turn count, 0 for single-turn encoders
+    pub turns: u32,
+    /// FRAME_CRC_ERROR, FRAME_ERROR and FRAME_WARNING flags
+    pub status: u8,
+}
+
+impl EncoderFrame {
+    /// Returns true if the position can be used (no CRC or sensor error).
+    #[inline(always)]
+    pub const fn is_valid(&self) -> bool {
+        self.status & (FRAME_CRC_ERROR | FRAME_ERROR) == 0
+    }
+}
+
+/// Field strength seen by a magnetic encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagnetField {
+    /// Within the working range
+    Ok,
+    /// Too weak: magnet missing, gap too large
+    Weak,
+    /// Too strong: gap too small
+    Strong,
+}
+
+/// Magnet condition decoded from the sensor diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MagnetHealth {
+    /// Field strength classification
+    pub field: MagnetField,
+    /// Margin to the ends of the working range (0..100 %)
+    pub health: u8,
+    /// FRAME_ERROR / FRAME_WARNING flags for the encoder status
+    pub status: u8,
+}
+
+/// Scales a single-turn position of `bits` resolution (1..=32) to a 16-bit angle.
+#[inline(always)]
+pub(crate) const fn scale_angle(position: u32, bits: u32) -> u16 {
+    if bits >= 16 {
+        (position >> (bits - 16)) as u16
+    } else {
+        (position << (16 - bits)) as u16
+    }
+}
+
+/// Extracts `count` bits (0..=32) of `word` above bit `shift`.
+#[inline(always)]
+pub(crate) const fn field(word: u64, shift: u32, count: u32) -> u32 {
+    if count == 0 {
+        0
+    } else {
+        ((word >> shift) & ((1u64 << count) - 1)) as u32
+    }
+}
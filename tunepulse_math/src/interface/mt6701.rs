@@ -0,0 +1,52 @@
+// Implements the MT6701 decoder, checking the SSI frame CRC and turning the magnetic field status
+// bits into frame flags and a magnet health value.
+
+// Key Features:
+// - 24-bit SSI frame: 14-bit angle, 4 status bits, CRC6 (x^6 + x + 1) over angle and status.
+// - Field too strong / too weak and loss of track mapped to the common frame flags.
+// - Magnet health as a 100 / 50 / 0 % level, the sensor has no AGC readout.
+
+// Detailed Operation:
+// The frame is sent MSB first: angle in bits 23..10, status Mg[3:0] in bits 9..6 and the CRC in bits
+// 5..0, computed over the 18 bits above it and sent as is (not inverted like BiSS). Mg[1:0] reports
+// the field (00 normal, 01 too strong, 10 too weak), Mg[2] is the push button function and Mg[3]
+// the loss of track when the rotor turns faster than the tracking loop can follow. Out of range field
+// and loss of track make the angle unreliable (FRAME_ERROR).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::crc::{crc_bits, CRC6_BISS_POLY};
+use super::{EncoderFrame, MagnetField, MagnetHealth, FRAME_CRC_ERROR, FRAME_ERROR};
+
+/// Number of SSI frame bits
+pub const MT6701_FRAME_BITS: u32 = 24;
+
+/// Decodes an SSI frame (24 bits, right aligned) into the angle and frame flags.
+pub const fn decode_frame(raw: u32) -> EncoderFrame {
+    let data = (raw >> 6) & 0x3FFFF;
+    let crc = (raw & 0x3F) as u8;
+    let mut status = decode_status(raw).status;
+    if crc_bits(data as u64, 18, CRC6_BISS_POLY, 6, 0) != crc {
+        status |= FRAME_CRC_ERROR;
+    }
+    EncoderFrame { angle: ((data >> 4) << 2) as u16, turns: 0, status }
+}
+
+/// Decodes the status bits of an SSI frame (24 bits, right aligned).
+pub const fn decode_status(raw: u32) -> MagnetHealth {
+    let mg = (raw >> 6) & 0xF;
+    let field = match mg & 0b11 {
+        0b00 => MagnetField::Ok,
+        0b01 => MagnetField::Strong,
+        _ => MagnetField::Weak,
+    };
+    let track_lost = mg & 0b1000 != 0;
+    let health = match (field, track_lost) {
+        (MagnetField::Ok, false) => 100,
+        (MagnetField::Ok, true) => 50,
+        _ => 0,
+    };
+    let status = if matches!(field, MagnetField::Ok) && !track_lost { 0 } else { FRAME_ERROR };
+    MagnetHealth { field, health, status }
+}
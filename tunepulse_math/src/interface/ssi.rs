@@ -0,0 +1,133 @@
+// Implements the SSI frame decoder, extracting the multi-turn count, the single-turn angle and the
+// status bits from a raw synchronous serial interface word.
+
+// Key Features:
+// - Any split of multi-turn and single-turn bits (up to 32 each, 64 frame bits in total).
+// - Binary or Gray coded position, optional even / odd parity bit at the end of the frame.
+// - Status bits after the position with configurable error and warning masks.
+// - Broken data line (all ones) reported as an invalid frame.
+
+// Detailed Operation:
+// The HAL clocks the frame in MSB first and passes the word right aligned, so the last transmitted
+// bit is bit 0. From the end the frame holds the optional parity bit, `status_bits` status bits,
+// `bits` single-turn bits and `turn_bits` multi-turn bits; bits above are ignored (leading start or
+// padding bits of some encoders). Gray coded encoders code turns and angle as one number, which is
+// converted to binary before it is split. The parity covers every preceding frame bit. An idle data
+// line is pulled high, so a frame of only ones means no encoder answered.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use super::{field, scale_angle, EncoderFrame, FRAME_CRC_ERROR, FRAME_ERROR, FRAME_WARNING};
+
+/// Parity bit at the end of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsiParity {
+    /// No parity bit
+    None,
+    /// Number of ones including the parity bit is even
+    Even,
+    /// Number of ones including the parity bit is odd
+    Odd,
+}
+
+/// Layout of an SSI frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SsiFormat {
+    /// Multi-turn bits (0 for single-turn encoders)
+    pub turn_bits: u32,
+    /// Single-turn resolution bits
+    pub bits: u32,
+    /// Status bits after the position
+    pub status_bits: u32,
+    /// Status bits reporting an error (set = error)
+    pub error_mask: u8,
+    /// Status bits reporting a warning (set = warning)
+    pub warning_mask: u8,
+    /// Position coded in Gray code
+    pub gray: bool,
+    /// Parity bit at the end of the frame
+    pub parity: SsiParity,
+}
+
+impl SsiFormat {
+    /// Creates a binary single-turn layout without status and parity bits.
+    ///
+    /// # Arguments
+    /// * `bits` - Single-turn resolution bits (1..=32)
+    pub const fn new(bits: u32) -> Self {
+        Self {
+            turn_bits: 0,
+            bits,
+            status_bits: 0,
+            error_mask: 0,
+            warning_mask: 0,
+            gray: false,
+            parity: SsiParity::None,
+        }
+    }
+
+    /// Returns the number of frame bits the HAL has to clock in.
+    pub const fn frame_bits(&self) -> u32 {
+        let parity = if matches!(self.parity, SsiParity::None) { 0 } else { 1 };
+        self.turn_bits + self.bits + self.status_bits + parity
+    }
+
+    /// Decodes a raw frame (right aligned, last transmitted bit in bit 0).
+    pub fn decode(&self, raw: u64) -> EncoderFrame {
+        let total = self.frame_bits().min(64);
+        let mut status = 0;
+        let frame = if total == 64 { raw } else { raw & ((1u64 << total) - 1) };
+        if total > 0 && frame.count_ones() == total {
+            status |= FRAME_CRC_ERROR; // Data line stuck high: no encoder
+        }
+
+        let mut shift = 0;
+        if !matches!(self.parity, SsiParity::None) {
+            let ones = frame.count_ones();
+            let expected = if matches!(self.parity, SsiParity::Even) { 0 } else { 1 };
+            if ones % 2 != expected {
+                status |= FRAME_CRC_ERROR;
+            }
+            shift = 1;
+        }
+
+        let flags = field(raw, shift, self.status_bits.min(8)) as u8;
+        if flags & self.error_mask != 0 {
+            status |= FRAME_ERROR;
+        }
+        if flags & self.warning_mask != 0 {
+            status |= FRAME_WARNING;
+        }
+        shift += self.status_bits;
+
+        let position_bits = (self.turn_bits + self.bits).min(64);
+        let mut position = if position_bits == 0 || shift >= 64 {
+            0
+        } else if position_bits == 64 {
+            raw >> shift
+        } else {
+            (raw >> shift) & ((1u64 << position_bits) - 1)
+        };
+        if self.gray {
+            position = gray_to_binary(position);
+        }
+        EncoderFrame {
+            angle: scale_angle(field(position, 0, self.bits.min(32)), self.bits.clamp(1, 32)),
+            turns: field(position, self.bits.min(63), self.turn_bits.min(32)),
+            status,
+        }
+    }
+}
+
+/// Converts a Gray coded number to binary.
+#[inline(always)]
+pub const fn gray_to_binary(gray: u64) -> u64 {
+    let mut binary = gray;
+    let mut shift = 1;
+    while shift < 64 {
+        binary ^= binary >> shift;
+        shift <<= 1;
+    }
+    binary
+}
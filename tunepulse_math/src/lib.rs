@@ -0,0 +1,37 @@
+// Implements the TunePulse math layer, integer-only building blocks for motor control that can
+// be used without the motor driver.
+
+// Key Features:
+// - Trigonometry (quarter-wave sine LUT, atan2), square roots, wrapping angles and Q15/Q31 types.
+// - Clarke / Park reference frame transforms.
+// - Filters: low-pass, biquad, median, moving average / CIC, slew-rate limiter, alpha-beta-gamma.
+// - Controllers: integer PID.
+// - Motion primitives: position integrator, speed estimation, profiles, PVT, gearing, PLL, observer,
+//   disturbance observer, input shaper, gear ratio / user unit scaling, angle interpolation and prediction.
+// - Encoder interfaces: SSI and BiSS-C frame decoding with parity / CRC6 checks.
+// - no_std, no allocation, no floating point, optional defmt diagnostics (`defmt` feature).
+
+// Detailed Operation:
+// The crate is re-exported by tunepulse_algo as `math_integer`, so driver code and this crate
+// share one implementation. Public items follow semver: items reachable from this root only change
+// incompatibly with a minor version bump while the crate is 0.x (major from 1.0), additions are
+// allowed in patch releases. Module paths are part of the API.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+#![no_std]
+
+pub mod trigonometry;
+pub mod sqrt;
+pub mod angle;
+pub mod fixed;
+pub mod transforms;
+pub mod normalization;
+pub mod ohms_law;
+pub mod filters;
+pub mod controllers;
+pub mod motion;
+pub mod fifo_buffer;
+pub mod motor;
+pub mod interface;
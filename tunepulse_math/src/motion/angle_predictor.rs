@@ -0,0 +1,94 @@
+// Implements the angle predictor, extrapolating the encoder angle between samples of a sensor that
+// updates slower than the control loop (e.g. 8 kHz encoder, 40 kHz PWM).
+
+// Key Features:
+// - New samples marked by the caller, so a resting rotor isn't mistaken for a stale sensor.
+// - Velocity from the last two samples and the ticks between them, any sample to tick rate ratio.
+// - Prediction angle + velocity * dt every tick, wrapping over the full turn.
+// - Extrapolation stops after two missed sample intervals, the angle holds instead of running away.
+
+// Detailed Operation:
+// Every fresh sample restarts the prediction from the measured angle; the velocity is the wrapped
+// difference to the previous sample divided by the ticks between them (Q16 counts per tick), so
+// a jittering sample rate is handled sample by sample. Between samples the angle advances by
+// velocity * ticks since the sample. A constant speed is followed without the staircase of the
+// slow sensor, at the cost of the acceleration error of one sample period. If the sensor stops
+// delivering (read error, bus busy) the prediction runs for at most twice the last interval and then
+// holds, so the commutation never drifts further than two samples of motion.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Extrapolation of a slowly sampled angle.
+pub struct AnglePredictor {
+    started: bool, // A sample was seen
+    sample: u16,   // Last sampled angle
+    interval: u32, // Ticks between the last two samples
+    ticks: u32,    // Ticks since the last sample
+    velocity: i64, // Counts per tick (Q16)
+    output: u16,   // Predicted angle
+}
+
+impl AnglePredictor {
+    /// Creates a predictor without samples.
+    pub const fn new() -> Self {
+        Self {
+            started: false,
+            sample: 0,
+            interval: 1,
+            ticks: 0,
+            velocity: 0,
+            output: 0,
+        }
+    }
+
+    /// Forgets the velocity, the next sample starts over.
+    pub fn reset(&mut self) {
+        self.started = false;
+    }
+
+    /// Advances the prediction by one tick.
+    ///
+    /// # Arguments
+    /// * `angle` - Angle of the last sensor sample
+    /// * `fresh` - A new sample arrived since the previous tick
+    pub fn tick(&mut self, angle: u16, fresh: bool) -> u16 {
+        self.ticks = self.ticks.saturating_add(1);
+        if !self.started {
+            self.started = true;
+            self.sample = angle;
+            self.velocity = 0;
+            self.ticks = 0;
+            self.output = angle;
+            return angle;
+        }
+        if fresh {
+            let step = angle.wrapping_sub(self.sample) as i16 as i64;
+            self.velocity = (step << 16) / self.ticks as i64;
+            self.interval = self.ticks;
+            self.ticks = 0;
+            self.sample = angle;
+            self.output = angle;
+            return angle;
+        }
+        let ticks = self.ticks.min(self.interval.saturating_mul(2)) as i64;
+        self.output = self.sample.wrapping_add(((self.velocity * ticks) >> 16) as u16);
+        self.output
+    }
+
+    /// Returns the estimated velocity (Q16 counts per tick).
+    pub fn velocity(&self) -> i64 {
+        self.velocity
+    }
+
+    /// Returns the predicted angle of the last tick.
+    pub fn output(&self) -> u16 {
+        self.output
+    }
+}
+
+impl Default for AnglePredictor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,121 @@
+// Implements the disturbance observer (DOB) module, estimating the external load torque from the
+// torque current command and the measured velocity with a nominal inertia model.
+
+// Key Features:
+// - Nominal plant given as acceleration per ampere, the same model as the Luenberger observer.
+// - First-order Q-filter with a configurable cutoff, limited to 1/8 of the update frequency.
+// - Derivative-free form: the velocity is never differentiated, only filtered.
+// - Estimate in mA of torque current, clamped to a configurable compensation limit.
+
+// Detailed Operation:
+// The nominal plant is J * a = Kt * u, so any current that didn't produce the expected acceleration
+// went into an external load: d = u - m * a with m = J / Kt = 1000 / accel_per_amp (mA per counts/s^2).
+// The estimate is low-pass filtered by Q(s) = g / (s + g), g = 2π * cutoff, which also bounds the
+// noise gain. Rewriting Q * m * s * v = m * g * (v - Q * v) gives d = Q * (u + m * g * v) - m * g * v,
+// so a single filter state z is updated with z += α * (u + m * g * v - z), α = g / fs, and the
+// estimate is z - m * g * v. Adding the estimate to the command cancels the load within the Q-filter
+// bandwidth: the axis behaves like the nominal inertia, which stiffens direct-drive axes against
+// load changes without raising the loop gains. A model error only scales the result, an inertia that
+// is set too small makes the compensation too weak, a too large one may let the loop oscillate.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the filter state and gains
+const FRAC: u32 = 16;
+/// 2π scaled by 2^16
+const TWO_PI_Q16: i64 = 411_775;
+
+/// Load torque estimator with a first-order Q-filter.
+pub struct DisturbanceObserver {
+    frequency: u16,     // Update frequency (ticks per second)
+    accel_per_amp: i32, // Nominal plant: acceleration per ampere (counts/s^2 per A), 0 - disabled
+    cutoff: u16,        // Q-filter cutoff (Hz)
+    alpha: i64,         // Q-filter coefficient g / fs (Q16)
+    gain: i64,          // m * g: mA per counts/s (Q16)
+    limit: i32,         // Maximum estimate amplitude (mA)
+
+    state: i64,    // Q-filter state z (mA, Q16)
+    estimate: i32, // Clamped load estimate (mA)
+}
+
+impl DisturbanceObserver {
+    /// Creates a disabled observer.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            accel_per_amp: 0,
+            cutoff: 0,
+            alpha: 0,
+            gain: 0,
+            limit: 0,
+            state: 0,
+            estimate: 0,
+        }
+    }
+
+    /// Configures the observer, an `accel_per_amp` or `cutoff` of 0 disables it.
+    ///
+    /// # Arguments
+    /// * `accel_per_amp` - Nominal acceleration per ampere of torque current (counts/s^2 per A)
+    /// * `cutoff` - Q-filter cutoff (Hz), limited to 1/8 of `frequency`
+    /// * `limit` - Maximum compensation current (mA)
+    pub fn configure(&mut self, accel_per_amp: i32, cutoff: u16, limit: i32) {
+        let freq = self.frequency.max(8);
+        self.accel_per_amp = accel_per_amp.max(0);
+        self.cutoff = cutoff.min(freq / 8);
+        self.limit = limit.max(0);
+        if !self.is_enabled() {
+            self.alpha = 0;
+            self.gain = 0;
+            self.reset();
+            return;
+        }
+        let omega = TWO_PI_Q16 * self.cutoff as i64; // rad/s, Q16
+        self.alpha = omega / freq as i64;
+        self.gain = 1000 * omega / self.accel_per_amp as i64;
+    }
+
+    /// Returns true if the observer is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.accel_per_amp > 0 && self.cutoff > 0
+    }
+
+    /// Clears the estimate (e.g. after the loops were reset).
+    pub fn reset(&mut self) {
+        self.state = 0;
+        self.estimate = 0;
+    }
+
+    /// Advances the observer and returns the load estimate (mA), 0 while disabled.
+    ///
+    /// # Arguments
+    /// * `velocity` - Measured velocity (counts/s)
+    /// * `current` - Torque current command applied during the last tick (mA)
+    pub fn tick(&mut self, velocity: i32, current: i32) -> i32 {
+        if !self.is_enabled() {
+            return 0;
+        }
+        // m * g * v, mA (Q16)
+        let velocity_term = self.gain * velocity as i64;
+        let input = ((current as i64) << FRAC) + velocity_term;
+        self.state += ((input - self.state) * self.alpha) >> FRAC;
+
+        let estimate = (self.state - velocity_term) >> FRAC;
+        self.estimate = estimate.clamp(-self.limit as i64, self.limit as i64) as i32;
+        self.estimate
+    }
+
+    /// Returns the load estimate of the last tick (mA).
+    pub fn estimate(&self) -> i32 {
+        self.estimate
+    }
+
+    /// Returns the (acceleration per ampere, cutoff Hz, limit mA) configuration.
+    pub fn config(&self) -> (i32, u16, i32) {
+        (self.accel_per_amp, self.cutoff, self.limit)
+    }
+}
@@ -0,0 +1,146 @@
+// Implements the electronic gearing module, making an axis follow the position of a master axis
+// with a configurable ratio and offset (electronic gearbox, gantry follower).
+
+// Key Features:
+// - Rational gear ratio (numerator / denominator) including negative ratios.
+// - Position offset between the axes, changeable while engaged.
+// - Engagement ramping of the ratio and offset to avoid torque spikes.
+// - Integer-only math with 16 fractional bits of follower position.
+
+// Detailed Operation:
+// The follower setpoint is built incrementally: every tick the master displacement since the last
+// tick is multiplied by the ratio and by the engagement factor, then added to the follower setpoint.
+// On engagement the factor ramps from zero to one, so the follower accelerates smoothly to the
+// geared velocity instead of jumping to it; the phase lost during the ramp is not caught up.
+// The offset is ramped in the same way, over the same number of ticks, whenever it changes.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the follower setpoint and ramp factor
+const FRAC: u32 = 16;
+
+/// Electronic gear between a master and a follower axis.
+pub struct ElectronicGear {
+    ratio_num: i32, // Gear ratio numerator
+    ratio_den: i32, // Gear ratio denominator (always positive)
+
+    ramp_ticks: u32,  // Duration of engagement and offset ramps (ticks)
+    ramp: u32,        // Ticks elapsed in the engagement ramp
+    offset: i32,      // Requested offset (counts)
+    offset_from: i64, // Offset at the start of the offset ramp (counts, Q16)
+    offset_ramp: u32, // Ticks elapsed in the offset ramp
+    offset_now: i64,  // Applied offset (counts, Q16)
+
+    master_last: i32, // Master position of the previous tick (counts)
+    follower: i64,    // Geared follower setpoint without offset (counts, Q16)
+    remainder: i64,   // Remainder of the ratio division, keeps long-term gearing exact
+    engaged: bool,
+}
+
+impl ElectronicGear {
+    /// Creates a disengaged 1:1 gear.
+    pub fn new() -> Self {
+        Self {
+            ratio_num: 1,
+            ratio_den: 1,
+            ramp_ticks: 1,
+            ramp: 0,
+            offset: 0,
+            offset_from: 0,
+            offset_ramp: 0,
+            offset_now: 0,
+            master_last: 0,
+            follower: 0,
+            remainder: 0,
+            engaged: false,
+        }
+    }
+
+    /// Sets the gear ratio, follower counts per master count = `num / den`.
+    pub fn set_ratio(&mut self, num: i32, den: i32) {
+        let den = if den == 0 { 1 } else { den };
+        self.ratio_num = num * den.signum();
+        self.ratio_den = den.abs();
+    }
+
+    /// Sets the follower offset (counts), ramped in over the engagement ramp time.
+    pub fn set_offset(&mut self, offset: i32) {
+        self.offset_from = self.offset_now;
+        self.offset = offset;
+        self.offset_ramp = 0;
+    }
+
+    /// Engages the gear.
+    ///
+    /// # Arguments
+    /// * `master` - Current master position (counts)
+    /// * `follower` - Current follower position setpoint (counts)
+    /// * `ramp_ticks` - Duration of the engagement ramp (ticks)
+    pub fn engage(&mut self, master: i32, follower: i32, ramp_ticks: u32) {
+        self.master_last = master;
+        self.follower = (follower as i64) << FRAC;
+        self.remainder = 0;
+        self.ramp_ticks = ramp_ticks.max(1);
+        self.ramp = 0;
+        self.offset_from = 0;
+        self.offset_now = 0;
+        self.offset_ramp = 0;
+        self.engaged = true;
+    }
+
+    /// Disengages the gear (the caller takes over the setpoint).
+    pub fn disengage(&mut self) {
+        self.engaged = false;
+    }
+
+    /// Advances the gear by one tick and returns the follower position setpoint (counts).
+    ///
+    /// # Arguments
+    /// * `master` - Current master position (counts)
+    pub fn tick(&mut self, master: i32) -> i32 {
+        if !self.engaged {
+            return self.position();
+        }
+
+        // ######################## ENGAGEMENT RAMP ##################################
+        self.ramp = (self.ramp + 1).min(self.ramp_ticks);
+        let factor = ((self.ramp as i64) << FRAC) / self.ramp_ticks as i64;
+
+        // ######################## GEARED MOTION ####################################
+        let delta = master.wrapping_sub(self.master_last) as i64;
+        self.master_last = master;
+        let geared = (((delta << FRAC) * self.ratio_num as i64 * factor) >> FRAC) + self.remainder;
+        self.follower += geared / self.ratio_den as i64;
+        self.remainder = geared % self.ratio_den as i64;
+
+        // ######################## OFFSET RAMP ######################################
+        self.offset_ramp = (self.offset_ramp + 1).min(self.ramp_ticks);
+        let target = (self.offset as i64) << FRAC;
+        let progress = ((self.offset_ramp as i64) << FRAC) / self.ramp_ticks as i64;
+        self.offset_now = self.offset_from + (((target - self.offset_from) * progress) >> FRAC);
+
+        self.position()
+    }
+
+    /// Returns true while the gear drives the follower.
+    pub fn is_engaged(&self) -> bool {
+        self.engaged
+    }
+
+    /// Returns true while the engagement or offset ramp is running.
+    pub fn is_ramping(&self) -> bool {
+        self.engaged && (self.ramp < self.ramp_ticks || self.offset_ramp < self.ramp_ticks)
+    }
+
+    /// Returns the follower position setpoint including the offset (counts).
+    pub fn position(&self) -> i32 {
+        ((self.follower + self.offset_now) >> FRAC) as i32
+    }
+}
+
+impl Default for ElectronicGear {
+    fn default() -> Self {
+        Self::new()
+    }
+}
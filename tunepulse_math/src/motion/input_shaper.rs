@@ -0,0 +1,266 @@
+// Implements the input shaper module, convolving position setpoints with a ZV or ZVD impulse
+// sequence so that a flexible load (belt, camera slider, long arm) ends a move without ringing.
+
+// Key Features:
+// - Zero Vibration (2 impulses) and Zero Vibration Derivative (3 impulses, robust to frequency errors).
+// - Impulse times and amplitudes derived from the resonance frequency and damping ratio.
+// - Decimated history of SHAPER_TAPS samples with linear interpolation, long delays fit in little RAM.
+// - Shapes the velocity setpoint together with the position, feed-forwards stay consistent.
+// - Exact end position: the impulse amplitudes sum up to one.
+
+// Detailed Operation:
+// A lightly damped mode with natural frequency fn and damping ζ rings with the damped period
+// Td = 1 / (fn * sqrt(1 - ζ²)). Splitting every setpoint change into impulses half a period apart,
+// with amplitudes in the ratio of the decay K = exp(-ζπ / sqrt(1 - ζ²)), lets the oscillation excited by
+// the first impulse be cancelled by the next one. ZV uses amplitudes 1/(1+K), K/(1+K) at 0 and Td/2,
+// ZVD uses 1/(1+K)², 2K/(1+K)², K²/(1+K)² at 0, Td/2 and Td, which widens the notch around fn at the
+// cost of a longer delay. The output is the weighted sum of the input delayed by each impulse time.
+// The history stores one sample every `step` ticks, step chosen so that Td fits in SHAPER_TAPS
+// samples; delayed values are interpolated between samples, which is exact for constant-velocity
+// segments and smooth enough elsewhere as step stays far below the resonance period.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::sqrt::isqrt;
+
+/// Samples of the setpoint history
+pub const SHAPER_TAPS: usize = 256;
+/// π scaled by 2^16
+const PI_Q16: i64 = 205_887;
+
+/// Impulse sequence of the shaper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaperType {
+    /// Setpoints pass unchanged
+    Off,
+    /// Zero Vibration: two impulses, delay Td / 2
+    Zv,
+    /// Zero Vibration Derivative: three impulses, delay Td
+    Zvd,
+}
+
+/// ZV / ZVD input shaper for position setpoints.
+pub struct InputShaper {
+    frequency: u16, // Update frequency (ticks per second)
+    kind: ShaperType,
+    resonance: u16, // Resonance frequency (0.1 Hz)
+    damping: u16,   // Damping ratio (0.001)
+
+    delays: [u32; 3],  // Impulse delays (ticks)
+    weights: [i64; 3], // Impulse amplitudes (Q16, sum = 1 << 16)
+    step: u32,         // Ticks per history sample
+    phase: u32,        // Ticks since the newest history sample
+
+    positions: [i32; SHAPER_TAPS],  // Input position history (counts)
+    velocities: [i32; SHAPER_TAPS], // Input velocity history (counts/s)
+    head: usize,                    // Index of the newest sample
+
+    input: i32,        // Last input position (counts)
+    position: i32,     // Shaped position setpoint (counts)
+    velocity: i32,     // Shaped velocity setpoint (counts/s)
+    acceleration: i32, // Shaped acceleration setpoint (counts/s^2)
+    settle: u32,       // Ticks left until the output reaches a constant input
+}
+
+impl InputShaper {
+    /// Creates a disabled shaper.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            kind: ShaperType::Off,
+            resonance: 0,
+            damping: 0,
+            delays: [0; 3],
+            weights: [1 << 16, 0, 0],
+            step: 1,
+            phase: 0,
+            positions: [0; SHAPER_TAPS],
+            velocities: [0; SHAPER_TAPS],
+            head: 0,
+            input: 0,
+            position: 0,
+            velocity: 0,
+            acceleration: 0,
+            settle: 0,
+        }
+    }
+
+    /// Configures the impulse sequence and restarts the shaper at its output position.
+    ///
+    /// # Arguments
+    /// * `kind` - Impulse sequence, `Off` passes setpoints unchanged
+    /// * `resonance` - Resonance frequency of the load (0.1 Hz), 0 disables the shaper
+    /// * `damping` - Damping ratio of the resonance (0.001), limited to 0.9
+    pub fn configure(&mut self, kind: ShaperType, resonance: u16, damping: u16) {
+        self.resonance = resonance;
+        self.damping = damping.min(900);
+        self.kind = if resonance == 0 { ShaperType::Off } else { kind };
+        self.delays = [0; 3];
+        self.weights = [1 << 16, 0, 0];
+        self.step = 1;
+        if self.kind != ShaperType::Off {
+            // sqrt(1 - ζ²) and the decay per half period K (Q16)
+            let zeta = self.damping as u64;
+            let root = isqrt(((1_000_000 - zeta * zeta) << 32) / 1_000_000).max(1) as i64;
+            let exponent = ((zeta as i64) << 16) / 1000 * PI_Q16 / root;
+            let k = exp_neg_q16(exponent);
+
+            // Damped period Td (ticks)
+            let period = ((self.frequency as i64 * 10) << 16) / (self.resonance as i64 * root);
+            let half = (period / 2) as u32;
+            let one = 1i64 << 16;
+            match self.kind {
+                ShaperType::Zv => {
+                    let first = (one << 16) / (one + k);
+                    self.delays = [0, half, 0];
+                    self.weights = [first, one - first, 0];
+                }
+                _ => {
+                    let denominator = ((one + k) * (one + k)) >> 16;
+                    let first = (one << 16) / denominator;
+                    let second = ((2 * k) << 16) / denominator;
+                    self.delays = [0, half, 2 * half];
+                    self.weights = [first, second, one - first - second];
+                }
+            }
+            self.step = self.delay().div_ceil(SHAPER_TAPS as u32 - 2).max(1);
+        }
+        self.reset(self.position);
+    }
+
+    /// Restarts the shaper at rest at the given position.
+    pub fn reset(&mut self, position: i32) {
+        self.positions.fill(position);
+        self.velocities.fill(0);
+        self.phase = 0;
+        self.input = position;
+        self.position = position;
+        self.velocity = 0;
+        self.acceleration = 0;
+        self.settle = 0;
+    }
+
+    /// Shapes the next setpoint and returns the shaped position setpoint (counts).
+    ///
+    /// # Arguments
+    /// * `position` - Position setpoint (counts)
+    /// * `velocity` - Velocity setpoint (counts/s)
+    pub fn tick(&mut self, position: i32, velocity: i32) -> i32 {
+        if position != self.input {
+            self.settle = self.delay() + self.step;
+        } else {
+            self.settle = self.settle.saturating_sub(1);
+        }
+        self.input = position;
+        if self.kind == ShaperType::Off {
+            (self.position, self.velocity, self.acceleration) = (position, velocity, 0);
+            return position;
+        }
+
+        // ####### History #######
+        if self.phase == 0 {
+            self.head = (self.head + 1) % SHAPER_TAPS;
+            self.positions[self.head] = position;
+            self.velocities[self.head] = velocity;
+        }
+
+        // ####### Impulse sum #######
+        // Offsets from the newest input keep the sum valid across position wrapping
+        let mut offset = 0i64;
+        let mut shaped_vel = 0i64;
+        for (&delay, &weight) in self.delays.iter().zip(self.weights.iter()) {
+            let (delayed_pos, delayed_vel) = self.delayed(delay, position, velocity);
+            offset += delayed_pos.wrapping_sub(position) as i64 * weight;
+            shaped_vel += delayed_vel as i64 * weight;
+        }
+        self.position = position.wrapping_add(((offset + (1 << 15)) >> 16) as i32);
+        let shaped_vel = (shaped_vel >> 16) as i32;
+        self.acceleration = shaped_vel.wrapping_sub(self.velocity).saturating_mul(self.frequency as i32);
+        self.velocity = shaped_vel;
+
+        self.phase = (self.phase + 1) % self.step;
+        self.position
+    }
+
+    /// Input (position, velocity) `delay` ticks ago, interpolated between history samples.
+    fn delayed(&self, delay: u32, position: i32, velocity: i32) -> (i32, i32) {
+        let sample = |age: u32| {
+            let index = (self.head + SHAPER_TAPS - age as usize % SHAPER_TAPS) % SHAPER_TAPS;
+            (self.positions[index], self.velocities[index])
+        };
+        // Newest sample is `phase` ticks old, the running input is in between
+        let (newer, older, fraction, span) = if delay <= self.phase {
+            if self.phase == 0 {
+                return (position, velocity);
+            }
+            ((position, velocity), sample(0), delay, self.phase)
+        } else {
+            let back = delay - self.phase;
+            let index = back / self.step;
+            (sample(index), sample(index + 1), back % self.step, self.step)
+        };
+        let lerp = |a: i32, b: i32| {
+            a.wrapping_add((b.wrapping_sub(a) as i64 * fraction as i64 / span as i64) as i32)
+        };
+        (lerp(newer.0, older.0), lerp(newer.1, older.1))
+    }
+
+    /// Returns true if an impulse sequence is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.kind != ShaperType::Off
+    }
+
+    /// Returns true until the shaped output reaches the last input.
+    pub fn is_active(&self) -> bool {
+        self.is_enabled() && self.settle > 0
+    }
+
+    /// Returns the longest impulse delay (ticks).
+    pub fn delay(&self) -> u32 {
+        self.delays.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the last input position (counts).
+    pub fn input(&self) -> i32 {
+        self.input
+    }
+
+    /// Returns the shaped position setpoint (counts).
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Returns the shaped velocity setpoint (counts/s).
+    pub fn velocity(&self) -> i32 {
+        self.velocity
+    }
+
+    /// Returns the shaped acceleration setpoint (counts/s^2).
+    pub fn acceleration(&self) -> i32 {
+        self.acceleration
+    }
+
+    /// Returns the (type, resonance 0.1 Hz, damping 0.001) configuration.
+    pub fn config(&self) -> (ShaperType, u16, u16) {
+        (self.kind, self.resonance, self.damping)
+    }
+}
+
+/// exp(-x) for x >= 0, both in Q16.
+fn exp_neg_q16(x: i64) -> i64 {
+    if x >= 16 << 16 {
+        return 0;
+    }
+    // exp(-x) = exp(-x / 256)^256, the series is accurate for the small argument; Q30 inside
+    let y = x.max(0) << (14 - 8);
+    let square = (y * y) >> 30;
+    let mut result = (1 << 30) - y + (square >> 1) - square * y / (6 << 30);
+    for _ in 0..8 {
+        result = (result * result) >> 30;
+    }
+    result >> 14
+}
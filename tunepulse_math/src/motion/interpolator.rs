@@ -0,0 +1,117 @@
+// Implements the angle interpolator, synthesizing intermediate angles of a low-resolution encoder
+// (e.g. 12-bit) between its code changes so the commutation angle moves smoothly at the PWM rate.
+
+// Key Features:
+// - Configurable encoder resolution (bits per turn), 16 bits or more bypasses the interpolation.
+// - Velocity from the time between code edges, exact at constant speed for any resolution.
+// - Output kept inside the cell of the present code, it never runs ahead of the encoder.
+// - Standstill detection: without a new edge for twice the last interval the angle holds.
+
+// Detailed Operation:
+// A 12-bit encoder steps by 16 counts of the 16-bit angle, so the commutation angle jumps every few
+// PWM periods at low speed and the current controller sees a staircase, which shows up as torque
+// ripple at the code rate. The code changes exactly when the rotor crosses a cell edge: moving
+// forward it enters a cell at its lower edge, backward at its upper edge. At every code change the
+// interpolator restarts from that edge and takes the velocity as the edge distance over the ticks
+// since the previous edge (Q16 counts per tick). Between edges the angle advances by the velocity,
+// clamped to the present cell so a slowing rotor can't be overtaken. If no edge follows within twice
+// the last interval the rotor is taken as stopped and the angle is held where it is.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Sub-cell interpolation of a quantized angle.
+pub struct AngleInterpolator {
+    lsb: u16, // Counts of the 16-bit angle per encoder code, 1 - bypass
+
+    started: bool, // A sample was seen
+    cell: u16,     // Lower edge of the present cell
+    edge: u16,     // Angle where the present cell was entered
+    interval: u32, // Ticks between the last two edges
+    ticks: u32,    // Ticks since the last edge
+    velocity: i64, // Counts per tick (Q16)
+    output: u16,   // Interpolated angle
+}
+
+impl AngleInterpolator {
+    /// Creates a bypassed interpolator (16-bit encoder).
+    pub const fn new() -> Self {
+        Self {
+            lsb: 1,
+            started: false,
+            cell: 0,
+            edge: 0,
+            interval: 0,
+            ticks: 0,
+            velocity: 0,
+            output: 0,
+        }
+    }
+
+    /// Sets the encoder resolution in bits per turn, 16 or more bypasses the interpolation.
+    pub fn set_resolution(&mut self, bits: u8) {
+        self.lsb = if bits >= 16 || bits == 0 { 1 } else { 1 << (16 - bits as u32) };
+        self.started = false;
+    }
+
+    /// Returns the encoder resolution in bits per turn.
+    pub fn resolution(&self) -> u8 {
+        16 - self.lsb.trailing_zeros() as u8
+    }
+
+    /// Returns true while intermediate angles are synthesized.
+    pub fn is_enabled(&self) -> bool {
+        self.lsb > 1
+    }
+
+    /// Interpolates one sample and returns the synthesized angle.
+    ///
+    /// # Arguments
+    /// * `angle` - Encoder angle (16-bit turn), quantized to the encoder resolution
+    pub fn tick(&mut self, angle: u16) -> u16 {
+        if self.lsb <= 1 {
+            return angle;
+        }
+        let cell = angle & !(self.lsb - 1);
+        if !self.started {
+            self.started = true;
+            self.cell = cell;
+            self.edge = cell;
+            self.velocity = 0;
+            self.ticks = 0;
+            self.interval = u32::MAX / 2;
+            self.output = cell.wrapping_add(self.lsb / 2); // Best guess: middle of the cell
+            return self.output;
+        }
+
+        self.ticks = self.ticks.saturating_add(1);
+        if cell != self.cell {
+            // ####### Code edge #######
+            let step = cell.wrapping_sub(self.cell) as i16 as i64;
+            self.edge = if step > 0 { cell } else { cell.wrapping_add(self.lsb - 1) };
+            self.velocity = (step << 16) / self.ticks as i64;
+            self.interval = self.ticks;
+            self.ticks = 0;
+            self.cell = cell;
+            self.output = self.edge;
+            return self.output;
+        }
+
+        // ####### Between edges #######
+        if self.ticks > self.interval.saturating_mul(2) {
+            self.velocity = 0; // Stopped: hold the last angle
+        }
+        if self.velocity != 0 {
+            let travel = (self.velocity * self.ticks as i64) >> 16;
+            let offset = (self.edge.wrapping_sub(self.cell) as i64 + travel).clamp(0, self.lsb as i64 - 1);
+            self.output = self.cell.wrapping_add(offset as u16);
+        }
+        self.output
+    }
+}
+
+impl Default for AngleInterpolator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,40 @@
+// Implements the linear scale module, converting linear encoder counts into micrometers
+// for voice-coil and linear actuator applications.
+
+// Key Features:
+// - Configurable encoder resolution in nanometers per count.
+// - Position and velocity conversion in both directions.
+// - 64-bit intermediate math to avoid overflow on long travels.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Conversion between linear encoder counts and micrometers.
+pub struct LinearScale {
+    nm_per_count: i32, // Linear encoder resolution (nm per count)
+}
+
+impl LinearScale {
+    /// Creates a new scale with the given encoder resolution (nm per count).
+    pub const fn new(nm_per_count: i32) -> Self {
+        let nm_per_count = if nm_per_count <= 0 { 1 } else { nm_per_count };
+        Self { nm_per_count }
+    }
+
+    /// Converts counts (or counts/s) to micrometers (or µm/s).
+    #[inline(always)]
+    pub const fn to_um(&self, counts: i32) -> i32 {
+        ((counts as i64 * self.nm_per_count as i64) / 1000) as i32
+    }
+
+    /// Converts micrometers (or µm/s) to counts (or counts/s).
+    #[inline(always)]
+    pub const fn to_counts(&self, um: i32) -> i32 {
+        ((um as i64 * 1000) / self.nm_per_count as i64) as i32
+    }
+
+    /// Returns the encoder resolution (nm per count).
+    pub const fn nm_per_count(&self) -> i32 {
+        self.nm_per_count
+    }
+}
@@ -0,0 +1,15 @@
+pub mod position_integrator;
+pub mod speed_estimator;
+pub mod linear_scale;
+pub mod unit_scale;
+pub mod profile;
+pub mod scurve;
+pub mod pvt;
+pub mod gearing;
+pub mod pll;
+pub mod interpolator;
+pub mod angle_predictor;
+pub mod observer;
+pub mod disturbance;
+pub mod input_shaper;
+pub mod physical_units;
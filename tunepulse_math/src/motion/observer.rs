@@ -0,0 +1,111 @@
+// Implements the Luenberger observer module, estimating position and velocity from the encoder
+// and the commanded torque with a simple inertia model.
+
+// Key Features:
+// - Two-state model (position, velocity) driven by the torque current command.
+// - Observer gains derived from a single bandwidth parameter (critically damped).
+// - Model gain given as acceleration per ampere, matching the motor and load inertia.
+// - Multi-turn position input with wrapping arithmetic, integer-only math.
+
+// Detailed Operation:
+// A pure tracking loop (PLL) only sees the encoder and has to infer every acceleration from the
+// position error, so its velocity lags during fast moves. The observer additionally integrates the
+// commanded current through the model `accel = k * current` (k = Kt / J), so the predicted velocity
+// follows accelerations immediately and the encoder correction only has to remove model errors
+// and load torque. Prediction: position += velocity, velocity += k * current. Correction with the
+// wrapped position error `e`: position += l1 * e, velocity += l2 * e, where for the bandwidth
+// a = 2π * bw / fs the gains are l1 = 2a and l2 = a². With k = 0 the observer equals the PLL.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the position state
+const POS_FRAC: u32 = 16;
+/// Fractional bits of the second gain and of the velocity state on top of the position state
+const VEL_FRAC: u32 = 24;
+/// 2π scaled by 2^24
+const TWO_PI_Q24: i64 = 105_414_357;
+
+/// Model-based position and velocity observer.
+pub struct LuenbergerObserver {
+    frequency: u16, // Update frequency (ticks per second)
+    l1: i64,        // Position correction gain (Q16)
+    l2: i64,        // Velocity correction gain (Q24)
+    model: i64,     // Acceleration per mA (counts/tick^2, Q40)
+
+    position: i64, // Estimated position (counts, Q16)
+    velocity: i64, // Estimated velocity (counts/tick, Q40)
+}
+
+impl LuenbergerObserver {
+    /// Creates an observer without a torque model.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    /// * `bandwidth` - Observer bandwidth (Hz)
+    pub fn new(frequency: u16, bandwidth: u16) -> Self {
+        let mut observer = Self {
+            frequency,
+            l1: 0,
+            l2: 0,
+            model: 0,
+            position: 0,
+            velocity: 0,
+        };
+        observer.set_bandwidth(bandwidth);
+        observer
+    }
+
+    /// Sets the observer bandwidth (Hz), limited to 1/8 of the update frequency for stability.
+    pub fn set_bandwidth(&mut self, bandwidth: u16) {
+        let freq = self.frequency.max(8) as i64;
+        let bandwidth = (bandwidth as i64).clamp(1, freq / 8);
+        let a = TWO_PI_Q24 * bandwidth / freq; // Normalized bandwidth (Q24)
+        self.l1 = (2 * a) >> (VEL_FRAC - POS_FRAC);
+        self.l2 = (a * a) >> VEL_FRAC;
+    }
+
+    /// Sets the torque model: acceleration produced by 1 A of torque current (counts/s^2 per A).
+    pub fn set_model(&mut self, accel_per_amp: i32) {
+        let freq = self.frequency.max(1) as i64;
+        let scale = 1000 * freq * freq; // per mA and per tick^2
+        let model = ((accel_per_amp as i128) << (POS_FRAC + VEL_FRAC)) / scale as i128;
+        self.model = model as i64;
+    }
+
+    /// Sets the estimate to the given position at rest.
+    pub fn reset(&mut self, position: i32) {
+        self.position = (position as i64) << POS_FRAC;
+        self.velocity = 0;
+    }
+
+    /// Advances the observer by one tick and returns the estimated velocity (counts/s).
+    ///
+    /// # Arguments
+    /// * `position` - Measured position (counts)
+    /// * `current` - Torque current command applied during the last tick (mA)
+    pub fn tick(&mut self, position: i32, current: i32) -> i32 {
+        // ####### Prediction #######
+        self.position += self.velocity >> VEL_FRAC;
+        self.velocity += self.model * current as i64;
+
+        // ####### Correction #######
+        let estimate = (self.position >> POS_FRAC) as i32;
+        let error_counts = position.wrapping_sub(estimate) as i64;
+        let error = (error_counts << POS_FRAC) - (self.position & ((1 << POS_FRAC) - 1));
+        self.position += (error * self.l1) >> POS_FRAC;
+        self.velocity += error * self.l2;
+
+        self.velocity()
+    }
+
+    /// Returns the estimated position (counts).
+    pub fn position(&self) -> i32 {
+        (self.position >> POS_FRAC) as i32
+    }
+
+    /// Returns the estimated velocity (counts/s).
+    pub fn velocity(&self) -> i32 {
+        ((self.velocity * self.frequency as i64) >> (POS_FRAC + VEL_FRAC)) as i32
+    }
+}
@@ -0,0 +1,207 @@
+// Implements the physical units module, converting load torque, angular speed, angle and travel
+// in SI-derived integer units into the current and count setpoints of the controller.
+
+// Key Features:
+// - Torque in mN·m at the load, through the gear ratio and the torque constant into mA.
+// - Angle in mrad and angular speed in mrad/s at the load, into counts and counts/s.
+// - Travel in µm and speed in µm/s through the lead of a screw or belt, into counts and counts/s.
+// - Configurable counts per motor revolution, rational gear ratio, all integer math.
+
+// Detailed Operation:
+// The gear ratio is motor_turns : load_turns, so one load revolution is
+// counts_per_rev * motor_turns / load_turns counts and the load torque needs
+// load_turns / motor_turns of it at the motor (losses ignored). The current for a load torque is
+// I[mA] = T[mN·m] * 1000 * load_turns / (motor_turns * kt). Angles use 2 pi = 710 / 113 (error
+// below 0.1 ppm), so counts = mrad * counts_per_rev * motor_turns * 113 / (710000 * load_turns).
+// Travel uses the lead (µm per load revolution): counts = µm * counts_per_rev * motor_turns /
+// (lead_um * load_turns). Products are formed in 128 bits and rounded towards zero, results
+// saturate at the range of the return type. Without a torque constant or a lead the respective
+// conversions return 0.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// 2 pi as a rational, numerator
+const TWO_PI_NUM: i128 = 710;
+/// 2 pi as a rational, denominator
+const TWO_PI_DEN: i128 = 113;
+
+/// Settings of the physical units conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalUnitsConfig {
+    /// Motor torque constant (mN·m/A), 0 - unknown
+    pub kt: i32,
+    /// Motor revolutions per `load_turns`, negative for a reversing gearbox
+    pub motor_turns: i32,
+    /// Load revolutions per `motor_turns`
+    pub load_turns: i32,
+    /// Position counts per motor revolution
+    pub counts_per_rev: i32,
+    /// Travel per load revolution (µm), 0 - rotary load
+    pub lead_um: i32,
+}
+
+impl PhysicalUnitsConfig {
+    /// Creates a direct drive rotary configuration with 65536 counts per revolution.
+    pub const fn new() -> Self {
+        Self {
+            kt: 0,
+            motor_turns: 1,
+            load_turns: 1,
+            counts_per_rev: 1 << 16,
+            lead_um: 0,
+        }
+    }
+}
+
+impl Default for PhysicalUnitsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Conversion between physical load units and controller setpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalUnits {
+    config: PhysicalUnitsConfig,
+}
+
+impl PhysicalUnits {
+    /// Creates a direct drive conversion without a torque constant.
+    pub const fn new() -> Self {
+        Self { config: PhysicalUnitsConfig::new() }
+    }
+
+    /// Creates a conversion from the settings.
+    ///
+    /// Returns None if the gear ratio or the counts per revolution are zero, or a value negative
+    /// where it can't be.
+    pub fn with_config(config: PhysicalUnitsConfig) -> Option<Self> {
+        let valid = config.motor_turns != 0 && config.load_turns > 0 && config.counts_per_rev > 0;
+        if !valid || config.kt < 0 || config.lead_um < 0 {
+            return None;
+        }
+        Some(Self { config })
+    }
+
+    /// Returns the settings.
+    pub const fn config(&self) -> PhysicalUnitsConfig {
+        self.config
+    }
+
+    /// Replaces the torque constant (mN·m/A), 0 - unknown.
+    pub fn set_kt(&mut self, kt: i32) {
+        self.config.kt = kt.max(0);
+    }
+
+    /// Returns the torque constant (mN·m/A), 0 - unknown.
+    #[inline(always)]
+    pub const fn kt(&self) -> i32 {
+        self.config.kt
+    }
+
+    /// Returns true if a lead is configured and travel conversions are available.
+    #[inline(always)]
+    pub const fn has_lead(&self) -> bool {
+        self.config.lead_um > 0
+    }
+
+    /// Converts a load torque (mN·m) to the motor current (mA), 0 without a torque constant.
+    pub fn torque_to_current(&self, torque: i32) -> i32 {
+        let c = &self.config;
+        let numerator = torque as i128 * 1000 * c.load_turns as i128;
+        ratio(numerator, c.motor_turns as i128 * c.kt as i128) as i32
+    }
+
+    /// Converts a motor current (mA) to the load torque (mN·m), 0 without a torque constant.
+    pub fn current_to_torque(&self, current: i32) -> i32 {
+        let c = &self.config;
+        let numerator = current as i128 * c.kt as i128 * c.motor_turns as i128;
+        ratio(numerator, 1000 * c.load_turns as i128) as i32
+    }
+
+    /// Converts a load angle (mrad) to counts.
+    pub fn mrad_to_counts(&self, angle: i64) -> i64 {
+        let c = &self.config;
+        let numerator = angle as i128 * c.counts_per_rev as i128 * c.motor_turns as i128 * TWO_PI_DEN;
+        ratio_i64(numerator, 1000 * TWO_PI_NUM * c.load_turns as i128)
+    }
+
+    /// Converts counts to a load angle (mrad).
+    pub fn counts_to_mrad(&self, counts: i64) -> i64 {
+        let c = &self.config;
+        let numerator = counts as i128 * 1000 * TWO_PI_NUM * c.load_turns as i128;
+        ratio_i64(numerator, c.counts_per_rev as i128 * c.motor_turns as i128 * TWO_PI_DEN)
+    }
+
+    /// Converts a load angular speed (mrad/s) to counts/s.
+    #[inline(always)]
+    pub fn mrad_to_velocity(&self, speed: i32) -> i32 {
+        saturate(self.mrad_to_counts(speed as i64))
+    }
+
+    /// Converts counts/s to a load angular speed (mrad/s).
+    #[inline(always)]
+    pub fn velocity_to_mrad(&self, velocity: i32) -> i32 {
+        saturate(self.counts_to_mrad(velocity as i64))
+    }
+
+    /// Converts a load travel (µm) to counts, 0 without a lead.
+    pub fn um_to_counts(&self, travel: i64) -> i64 {
+        let c = &self.config;
+        let numerator = travel as i128 * c.counts_per_rev as i128 * c.motor_turns as i128;
+        ratio_i64(numerator, c.lead_um as i128 * c.load_turns as i128)
+    }
+
+    /// Converts counts to a load travel (µm), 0 without a lead.
+    pub fn counts_to_um(&self, counts: i64) -> i64 {
+        if !self.has_lead() {
+            return 0;
+        }
+        let c = &self.config;
+        let numerator = counts as i128 * c.lead_um as i128 * c.load_turns as i128;
+        ratio_i64(numerator, c.counts_per_rev as i128 * c.motor_turns as i128)
+    }
+
+    /// Converts a load speed (µm/s) to counts/s, 0 without a lead.
+    #[inline(always)]
+    pub fn um_to_velocity(&self, speed: i32) -> i32 {
+        saturate(self.um_to_counts(speed as i64))
+    }
+
+    /// Converts counts/s to a load speed (µm/s), 0 without a lead.
+    #[inline(always)]
+    pub fn velocity_to_um(&self, velocity: i32) -> i32 {
+        saturate(self.counts_to_um(velocity as i64))
+    }
+}
+
+impl Default for PhysicalUnits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Quotient rounded towards zero and saturated to i32, 0 for a zero denominator.
+#[inline(always)]
+fn ratio(numerator: i128, denominator: i128) -> i128 {
+    if denominator == 0 {
+        return 0;
+    }
+    (numerator / denominator).clamp(i32::MIN as i128, i32::MAX as i128)
+}
+
+/// Quotient rounded towards zero and saturated to i64, 0 for a zero denominator.
+#[inline(always)]
+fn ratio_i64(numerator: i128, denominator: i128) -> i64 {
+    if denominator == 0 {
+        return 0;
+    }
+    (numerator / denominator).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Saturates to i32.
+#[inline(always)]
+fn saturate(value: i64) -> i32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
@@ -0,0 +1,108 @@
+// Implements the tracking PLL module, following the encoder angle with a type-2 loop to provide
+// a low-latency, low-noise angle and velocity estimate.
+
+// Key Features:
+// - Type-2 loop: zero steady-state angle error at constant velocity (no lag while spinning).
+// - Gains derived from a single bandwidth parameter (critically damped).
+// - Sub-count angle resolution (16 fractional bits) between encoder samples.
+// - Integer-only math, wrapping angle arithmetic over the full turn.
+
+// Detailed Operation:
+// The tracker keeps an angle (Q16 over the 16-bit turn) and a velocity (counts/tick, Q24 fraction
+// bits on top of the Q16 angle). Every tick the angle is first advanced by the velocity (prediction),
+// then the wrapped difference to the measured angle corrects it: the proportional path
+// `kp * error` moves the angle, the integral path `ki * error` updates the velocity. With the loop
+// bandwidth a = 2π * bw / fs the critically damped gains are kp = 2a and ki = a². The velocity is the
+// integrator state, so it is already filtered by the loop and needs no differentiation of the angle.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Fractional bits of the angle state on top of the 16-bit angle
+const ANGLE_FRAC: u32 = 16;
+/// Fractional bits of the integral gain and of the velocity state on top of the angle state
+const KI_FRAC: u32 = 24;
+/// 2π scaled by 2^24
+const TWO_PI_Q24: i64 = 105_414_357;
+
+/// Type-2 angle tracking loop.
+pub struct TrackingPLL {
+    frequency: u16, // Update frequency (ticks per second)
+    kp: i64,        // Proportional gain (Q16)
+    ki: i64,        // Integral gain (Q24)
+    bandwidth: u16, // Loop bandwidth (Hz), after limiting
+
+    angle: u32,    // Tracked angle (Q16 over the 16-bit turn, wrapping)
+    velocity: i64, // Tracked velocity (counts/tick, Q40)
+}
+
+impl TrackingPLL {
+    /// Creates a tracker at zero angle and velocity.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    /// * `bandwidth` - Loop bandwidth (Hz)
+    pub fn new(frequency: u16, bandwidth: u16) -> Self {
+        let mut pll = Self {
+            frequency,
+            kp: 0,
+            ki: 0,
+            bandwidth: 0,
+            angle: 0,
+            velocity: 0,
+        };
+        pll.set_bandwidth(bandwidth);
+        pll
+    }
+
+    /// Sets the loop bandwidth (Hz), limited to 1/8 of the update frequency for stability.
+    pub fn set_bandwidth(&mut self, bandwidth: u16) {
+        let freq = self.frequency.max(8) as i64;
+        let bandwidth = (bandwidth as i64).clamp(1, freq / 8);
+        let a = TWO_PI_Q24 * bandwidth / freq; // Normalized bandwidth (Q24)
+        self.kp = (2 * a) >> (KI_FRAC - ANGLE_FRAC);
+        self.ki = (a * a) >> KI_FRAC;
+        self.bandwidth = bandwidth as u16;
+    }
+
+    /// Returns the loop bandwidth (Hz), after limiting.
+    pub fn bandwidth(&self) -> u16 {
+        self.bandwidth
+    }
+
+    /// Locks the tracker onto the given angle at rest (use after a sensor jump or a restart).
+    pub fn reset(&mut self, angle: u16) {
+        self.angle = (angle as u32) << ANGLE_FRAC;
+        self.velocity = 0;
+    }
+
+    /// Advances the tracker with a new measured angle and returns the tracked angle.
+    pub fn tick(&mut self, angle: u16) -> u16 {
+        // ####### Prediction #######
+        let step = (self.velocity >> KI_FRAC) as i32;
+        self.angle = self.angle.wrapping_add(step as u32);
+
+        // ####### Correction #######
+        let error = ((angle as u32) << ANGLE_FRAC).wrapping_sub(self.angle) as i32 as i64;
+        self.velocity += error * self.ki;
+        let correction = (error * self.kp) >> ANGLE_FRAC;
+        self.angle = self.angle.wrapping_add(correction as i32 as u32);
+
+        self.angle()
+    }
+
+    /// Returns the tracked angle.
+    pub fn angle(&self) -> u16 {
+        (self.angle >> ANGLE_FRAC) as u16
+    }
+
+    /// Returns the tracked angle with 16 fractional bits.
+    pub fn angle_fine(&self) -> u32 {
+        self.angle
+    }
+
+    /// Returns the tracked velocity (counts/s).
+    pub fn velocity(&self) -> i32 {
+        ((self.velocity * self.frequency as i64) >> (KI_FRAC + ANGLE_FRAC)) as i32
+    }
+}
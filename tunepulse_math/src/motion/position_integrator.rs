@@ -0,0 +1,99 @@
+/// EncoderPosition manages and calculates the absolute position and speed of the encoder.
+///
+/// The position is accumulated in 64 bits (48-bit turn count + 16-bit angle), so it never wraps in
+/// practice; the i32 getters return the low 32 bits, which wrap every 32768 turns but stay exact
+/// for differences taken with wrapping arithmetic.
+pub struct Position {
+    position: i64, // Combined value (rotations + angle)
+    zero: i64,     // Raw position treated as the zero point (set by homing)
+}
+
+impl Position {
+    /// Creates new encoder handler instance
+    pub fn new() -> Self {
+        // Init filter with input values as default
+        Self { position: 0, zero: 0 }
+    }
+
+    /// Updates the encoder state, including position filtering, zero-cross detection, and speed estimation.
+    pub fn tick(&mut self, input_pos: u16) -> &Self {
+        // Retrieve the previous angle by casting the current position to u16
+        let prev_angle: u16 = self.position as u16;
+
+        // Calculate the difference between the current and the previous angle with wrapping
+        let dif = input_pos.wrapping_sub(prev_angle) as i16;
+
+        // Update the current position by adding the difference, ensuring it wraps around correctly
+        self.position = self.position.wrapping_add(dif as i64);
+
+        self
+    }
+
+    /// Getter for angle
+    pub fn angle(&self) -> u16 {
+        self.position as u16
+    }
+
+    /// Getter for rotations
+    pub fn rotations(&self) -> i16 {
+        (self.position >> 16) as i16
+    }
+
+    /// Getter for full turns since start (floor, negative below the start position)
+    pub fn turns(&self) -> i64 {
+        self.position >> 16
+    }
+
+    /// Getter for full turns relative to the zero point (floor)
+    pub fn turns_from_zero(&self) -> i64 {
+        self.position_from_zero() >> 16
+    }
+
+    /// Getter for position, returns i32 (i16 rotations + u16 angle)
+    pub fn position(&self) -> i32 {
+        self.position as i32
+    }
+
+    /// Getter for position relative to the zero point set by `set_zero()`
+    pub fn from_zero(&self) -> i32 {
+        self.position.wrapping_sub(self.zero) as i32
+    }
+
+    /// Getter for the 64-bit position (counts since start)
+    pub fn position_i64(&self) -> i64 {
+        self.position
+    }
+
+    /// Getter for the 64-bit position relative to the zero point
+    pub fn position_from_zero(&self) -> i64 {
+        self.position.wrapping_sub(self.zero)
+    }
+
+    /// Marks the current position as zero without disturbing the raw angle tracking
+    pub fn set_zero(&mut self) {
+        self.zero = self.position;
+    }
+
+    /// Moves the zero point so that the current position reads `offset` (counts from zero),
+    /// e.g. to restore an absolute position after power-up
+    pub fn set_offset(&mut self, offset: i64) {
+        self.zero = self.position.wrapping_sub(offset);
+    }
+
+    /// Getter for the zero point (counts since start)
+    pub fn zero(&self) -> i64 {
+        self.zero
+    }
+
+    /// Mirrors the counting direction, the following `tick()` calls must get the mirrored angle
+    pub fn invert(&mut self) {
+        self.position = self.position.wrapping_neg();
+        self.zero = self.zero.wrapping_neg();
+    }
+
+    // Call this if ABZ encoder is used at it hit zero very first time
+    pub fn reset(&mut self) {
+        self.position = 0;
+        self.zero = 0;
+    }
+}
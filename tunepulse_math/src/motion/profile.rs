@@ -0,0 +1,161 @@
+// Implements the motion profile module, generating smooth point-to-point position setpoints
+// with a trapezoidal velocity profile for the position loop.
+
+// Key Features:
+// - Configurable maximum velocity, acceleration and deceleration.
+// - Integer-only math with 24 fractional bits to reach very low accelerations.
+// - Retargeting on the fly, keeping the current velocity (no jerk at the new command).
+// - Provides position, velocity and acceleration setpoints for feed-forward use.
+
+// Detailed Operation:
+// The generator keeps its own position and velocity in fixed point (counts and counts/tick with
+// 24 fractional bits). Every tick it compares the remaining distance with the braking distance
+// v^2 / (2 * decel): while the target is far enough it accelerates up to the velocity limit,
+// otherwise it decelerates. Moving away from the target (after a retarget) always brakes first.
+// Once the remaining distance and velocity fit into a single tick, the setpoint snaps onto
+// the target and the profile is finished.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Number of fractional bits of the internal position, velocity and acceleration
+const FRAC: u32 = 24;
+
+/// Trapezoidal velocity profile generator.
+pub struct TrapezoidalProfile {
+    frequency: u16, // Update frequency (ticks per second)
+
+    vel_max: i64, // Velocity limit (counts/tick, Q24)
+    accel: i64,   // Acceleration (counts/tick^2, Q24)
+    decel: i64,   // Deceleration (counts/tick^2, Q24)
+
+    target: i64,    // Target position (counts, Q24)
+    position: i64,  // Position setpoint (counts, Q24)
+    velocity: i64,  // Velocity setpoint (counts/tick, Q24)
+    accel_out: i64, // Acceleration applied in the last tick (counts/tick^2, Q24)
+    active: bool,   // Profile is still moving
+}
+
+impl TrapezoidalProfile {
+    /// Creates a new idle profile generator.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        let mut profile = Self {
+            frequency,
+            vel_max: 0,
+            accel: 0,
+            decel: 0,
+            target: 0,
+            position: 0,
+            velocity: 0,
+            accel_out: 0,
+            active: false,
+        };
+        profile.set_limits(1 << 16, 1 << 18, 1 << 18); // 1 rev/s, 4 rev/s^2
+        profile
+    }
+
+    /// Sets profile limits.
+    ///
+    /// # Arguments
+    /// * `vel_max` - Maximum velocity (counts/s)
+    /// * `accel` - Acceleration (counts/s^2)
+    /// * `decel` - Deceleration (counts/s^2)
+    pub fn set_limits(&mut self, vel_max: i32, accel: i32, decel: i32) {
+        let freq = self.frequency.max(1) as i64;
+        self.vel_max = ((vel_max.unsigned_abs() as i64) << FRAC) / freq;
+        self.accel = (((accel.unsigned_abs() as i64) << FRAC) / (freq * freq)).max(1);
+        self.decel = (((decel.unsigned_abs() as i64) << FRAC) / (freq * freq)).max(1);
+    }
+
+    /// Starts a move towards `target` from the given position and velocity.
+    ///
+    /// # Arguments
+    /// * `position` - Start position (counts)
+    /// * `velocity` - Start velocity (counts/s)
+    /// * `target` - Target position (counts)
+    pub fn start(&mut self, position: i32, velocity: i32, target: i32) {
+        self.position = (position as i64) << FRAC;
+        self.velocity = ((velocity as i64) << FRAC) / self.frequency.max(1) as i64;
+        self.retarget(target);
+    }
+
+    /// Changes the target of a running move keeping the current position and velocity.
+    pub fn retarget(&mut self, target: i32) {
+        self.target = (target as i64) << FRAC;
+        self.active = true;
+    }
+
+    /// Stops generating setpoints immediately (the caller takes over the setpoint).
+    pub fn stop(&mut self) {
+        self.active = false;
+        self.velocity = 0;
+        self.accel_out = 0;
+    }
+
+    /// Advances the profile by one tick and returns the position setpoint (counts).
+    pub fn tick(&mut self) -> i32 {
+        if !self.active {
+            return self.position();
+        }
+
+        let remaining = self.target - self.position;
+        let dir = remaining.signum();
+        let speed = self.velocity.abs();
+
+        // Finish when the target is reachable within one tick at a stoppable speed
+        if remaining.abs() <= speed.max(self.decel) && speed <= self.decel.max(self.accel) {
+            self.position = self.target;
+            self.velocity = 0;
+            self.accel_out = 0;
+            self.active = false;
+            return self.position();
+        }
+
+        // Braking distance at the current speed: v^2 / (2 * decel)
+        let braking = (speed * speed) / (2 * self.decel);
+
+        let prev_velocity = self.velocity;
+        if self.velocity * dir < 0 || remaining.abs() <= braking + speed {
+            // Moving away from the target or close enough to start braking
+            let step = self.decel.min(speed);
+            self.velocity -= self.velocity.signum() * step;
+        } else {
+            // Accelerate towards the target, respecting the velocity limit
+            self.velocity += dir * self.accel;
+            self.velocity = self.velocity.clamp(-self.vel_max, self.vel_max);
+        }
+        self.accel_out = self.velocity - prev_velocity;
+
+        self.position += self.velocity;
+        self.position()
+    }
+
+    /// Returns true while the profile is moving.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns the position setpoint (counts).
+    pub fn position(&self) -> i32 {
+        (self.position >> FRAC) as i32
+    }
+
+    /// Returns the velocity setpoint (counts/s).
+    pub fn velocity(&self) -> i32 {
+        ((self.velocity * self.frequency as i64) >> FRAC) as i32
+    }
+
+    /// Returns the acceleration setpoint (counts/s^2).
+    pub fn acceleration(&self) -> i32 {
+        let freq = self.frequency as i64;
+        ((self.accel_out * freq * freq) >> FRAC) as i32
+    }
+
+    /// Returns the target position (counts).
+    pub fn target(&self) -> i32 {
+        (self.target >> FRAC) as i32
+    }
+}
@@ -0,0 +1,249 @@
+// Implements the PVT streaming module, interpolating timed position/velocity waypoints
+// streamed by a host into per-tick position setpoints.
+
+// Key Features:
+// - Small ring buffer of PVT segments (target position, target velocity, duration).
+// - Linear or cubic (Hermite) interpolation between consecutive waypoints.
+// - Integer-only interpolation with 16 fractional bits of segment time.
+// - Underrun detection: the last waypoint is held and a latched flag is raised.
+
+// Detailed Operation:
+// Each segment describes the state the axis must reach at the end of it: a position (counts),
+// a velocity (counts/s) and a duration (ticks). The host pushes segments ahead of time, the tick
+// pops them one by one, so the control timing never depends on the host link. Cubic interpolation
+// uses a Hermite polynomial matching position and velocity at both ends of the segment, which
+// keeps the velocity continuous across segments. Linear interpolation ignores velocities and moves
+// with a constant speed inside each segment. When the buffer runs empty the last waypoint is held;
+// if the axis was still expected to move at that point an underrun is reported.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Number of segments the stream can hold
+pub const PVT_CAPACITY: usize = 16;
+
+/// Fractional bits of the normalized segment time
+const TIME_FRAC: u32 = 16;
+
+/// Single timed waypoint of a streamed path.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PvtSegment {
+    /// Position at the end of the segment (counts)
+    pub position: i32,
+    /// Velocity at the end of the segment (counts/s)
+    pub velocity: i32,
+    /// Duration of the segment (ticks)
+    pub duration: u32,
+}
+
+/// Interpolation used inside segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PvtInterpolation {
+    /// Constant velocity between waypoints (velocities are ignored)
+    Linear,
+    /// Hermite polynomial matching positions and velocities at both ends
+    Cubic,
+}
+
+/// Ring buffer of PVT segments with per-tick interpolation.
+pub struct PvtStream {
+    frequency: u16, // Update frequency (ticks per second)
+    interpolation: PvtInterpolation,
+
+    buffer: [PvtSegment; PVT_CAPACITY],
+    head: usize, // Index of the oldest queued segment
+    len: usize,  // Number of queued segments
+
+    start_pos: i32,               // Position at the start of the running segment (counts)
+    start_vel: i32,               // Velocity at the start of the running segment (counts/s)
+    segment: Option<PvtSegment>,  // Running segment
+    elapsed: u32,                 // Ticks elapsed in the running segment
+
+    position: i32, // Position setpoint (counts)
+    velocity: i32, // Velocity setpoint (counts/s)
+    active: bool,  // Stream drives the setpoint
+    underrun: bool, // Latched: buffer ran empty while moving
+}
+
+impl PvtStream {
+    /// Creates a new idle stream with cubic interpolation.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            interpolation: PvtInterpolation::Cubic,
+            buffer: [PvtSegment::default(); PVT_CAPACITY],
+            head: 0,
+            len: 0,
+            start_pos: 0,
+            start_vel: 0,
+            segment: None,
+            elapsed: 0,
+            position: 0,
+            velocity: 0,
+            active: false,
+            underrun: false,
+        }
+    }
+
+    /// Starts streaming from the given position at rest, dropping queued segments.
+    pub fn start(&mut self, position: i32) {
+        self.len = 0;
+        self.segment = None;
+        self.start_pos = position;
+        self.start_vel = 0;
+        self.position = position;
+        self.velocity = 0;
+        self.underrun = false;
+        self.active = true;
+    }
+
+    /// Stops streaming and drops queued segments (the caller takes over the setpoint).
+    pub fn stop(&mut self) {
+        self.len = 0;
+        self.segment = None;
+        self.velocity = 0;
+        self.active = false;
+    }
+
+    /// Selects interpolation inside segments.
+    pub fn set_interpolation(&mut self, interpolation: PvtInterpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Queues a segment, returns false if the buffer is full.
+    pub fn push(&mut self, segment: PvtSegment) -> bool {
+        if self.len >= PVT_CAPACITY {
+            return false;
+        }
+        let idx = (self.head + self.len) % PVT_CAPACITY;
+        self.buffer[idx] = PvtSegment {
+            duration: segment.duration.max(1),
+            ..segment
+        };
+        self.len += 1;
+        true
+    }
+
+    /// Advances the stream by one tick and returns the position setpoint (counts).
+    pub fn tick(&mut self) -> i32 {
+        if !self.active {
+            return self.position;
+        }
+
+        // ######################## LOAD NEXT SEGMENT ################################
+        if self.segment.is_none() {
+            if self.len == 0 {
+                // Hold the last waypoint until the host sends more
+                if self.velocity != 0 {
+                    self.underrun = true;
+                    #[cfg(feature = "defmt")]
+                    defmt::warn!("PVT: Buffer underrun at position {}", self.position);
+                }
+                self.velocity = 0;
+                return self.position;
+            }
+            self.segment = Some(self.buffer[self.head]);
+            self.head = (self.head + 1) % PVT_CAPACITY;
+            self.len -= 1;
+            self.elapsed = 0;
+        }
+
+        // ######################## INTERPOLATE ######################################
+        if let Some(segment) = self.segment {
+            self.elapsed += 1;
+            let duration = segment.duration as i64;
+            let s = ((self.elapsed as i64) << TIME_FRAC) / duration;
+            let distance = segment.position.wrapping_sub(self.start_pos) as i64;
+            let freq = self.frequency.max(1) as i64;
+
+            let (offset, rate) = match self.interpolation {
+                PvtInterpolation::Linear => ((distance * s) >> TIME_FRAC, distance << TIME_FRAC),
+                PvtInterpolation::Cubic => {
+                    // Start and end velocities scaled to the segment length (counts)
+                    let m0 = self.start_vel as i64 * duration / freq;
+                    let m1 = segment.velocity as i64 * duration / freq;
+                    hermite(s, distance, m0, m1)
+                }
+            };
+
+            self.position = self.start_pos.wrapping_add(offset as i32);
+            // Rate is the derivative over the normalized time, convert it to counts/s
+            self.velocity = ((rate / duration * freq) >> TIME_FRAC) as i32;
+
+            if self.elapsed >= segment.duration {
+                // Land exactly on the waypoint and chain the next segment from it
+                self.position = segment.position;
+                self.start_pos = segment.position;
+                self.start_vel = match self.interpolation {
+                    PvtInterpolation::Linear => 0,
+                    PvtInterpolation::Cubic => segment.velocity,
+                };
+                self.velocity = segment.velocity;
+                self.segment = None;
+            }
+        }
+        self.position
+    }
+
+    /// Returns true while the stream drives the setpoint.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns true while a segment is running or queued.
+    pub fn is_running(&self) -> bool {
+        self.segment.is_some() || self.len > 0
+    }
+
+    /// Returns the number of free slots in the buffer.
+    pub fn free(&self) -> usize {
+        PVT_CAPACITY - self.len
+    }
+
+    /// Returns true if the buffer ran empty while the axis was moving.
+    pub fn underrun(&self) -> bool {
+        self.underrun
+    }
+
+    /// Clears the latched underrun flag.
+    pub fn clear_underrun(&mut self) {
+        self.underrun = false;
+    }
+
+    /// Returns the position setpoint (counts).
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Returns the velocity setpoint (counts/s).
+    pub fn velocity(&self) -> i32 {
+        self.velocity
+    }
+}
+
+/// Evaluates a Hermite segment starting at zero with zero-based normalized time `s` (Q16).
+///
+/// Returns (position offset, derivative over the normalized time in Q16).
+#[inline(always)]
+fn hermite(s: i64, distance: i64, m0: i64, m1: i64) -> (i64, i64) {
+    const ONE: i64 = 1 << TIME_FRAC;
+    let s2 = (s * s) >> TIME_FRAC;
+    let s3 = (s2 * s) >> TIME_FRAC;
+
+    // Basis functions (h00 + h01 = 1, so the start position is the reference)
+    let h10 = s3 - 2 * s2 + s;
+    let h01 = 3 * s2 - 2 * s3;
+    let h11 = s3 - s2;
+    let offset = (h01 * distance + h10 * m0 + h11 * m1) >> TIME_FRAC;
+
+    // Derivatives of the basis functions
+    let d10 = 3 * s2 - 4 * s + ONE;
+    let d01 = 6 * s - 6 * s2;
+    let d11 = 3 * s2 - 2 * s;
+    let rate = d01 * distance + d10 * m0 + d11 * m1;
+
+    (offset, rate)
+}
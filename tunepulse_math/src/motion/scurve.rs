@@ -0,0 +1,176 @@
+// Implements the S-curve module, turning a trapezoidal position profile into a jerk-limited
+// one by averaging its setpoints over a sliding time window.
+
+// Key Features:
+// - Configurable jerk limit, converted into the averaging window length.
+// - Integer-only moving average with an exact end position (no drift, no overshoot).
+// - Jerk-free start from a moving axis by pre-filling the window along the current velocity.
+// - Provides position, velocity and acceleration setpoints for feed-forward use.
+
+// Detailed Operation:
+// A moving average of length T applied to a trapezoidal profile convolves its acceleration steps
+// with a box of width T, turning each step into a linear ramp. The result is an S-curve with
+// jerk = accel / T, the same end position and a movement time longer by T. The window length is
+// therefore derived from the acceleration and the requested jerk, limited by SCURVE_TAPS
+// (the lowest reachable jerk is accel / SCURVE_TAPS ticks). The window length is latched at the
+// start of a move; the averaged output keeps changing for T ticks after the trapezoid has finished.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Maximum averaging window (ticks), 25.6 ms at 20 kHz
+pub const SCURVE_TAPS: usize = 512;
+
+/// Shape of a point-to-point move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileShape {
+    /// Velocity ramps with infinite jerk
+    Trapezoidal,
+    /// Acceleration ramps with limited jerk
+    SCurve,
+}
+
+/// Moving average jerk limiter for trapezoidal position setpoints.
+pub struct JerkLimiter {
+    frequency: u16, // Update frequency (ticks per second)
+    accel: i32,     // Highest profile acceleration (counts/s^2)
+    jerk: i32,      // Requested jerk limit (counts/s^3)
+
+    positions: [i32; SCURVE_TAPS],  // Last input positions (counts)
+    velocities: [i32; SCURVE_TAPS], // Last input velocities (counts/s)
+    taps: usize,                    // Active window length (ticks)
+    index: usize,                   // Position of the oldest sample in the window
+    pos_sum: i64,                   // Sum of the positions in the window
+    vel_sum: i64,                   // Sum of the velocities in the window
+    settle: usize,                  // Ticks left until the window holds a constant value
+    acceleration: i32,              // Acceleration setpoint (counts/s^2)
+}
+
+impl JerkLimiter {
+    /// Creates a new idle limiter.
+    ///
+    /// # Arguments
+    /// * `frequency` - Number of ticks per second
+    pub fn new(frequency: u16) -> Self {
+        Self {
+            frequency,
+            accel: 1 << 18, // Matches the default profile acceleration
+            jerk: 1 << 22,  // 64 rev/s^3
+            positions: [0; SCURVE_TAPS],
+            velocities: [0; SCURVE_TAPS],
+            taps: 1,
+            index: 0,
+            pos_sum: 0,
+            vel_sum: 0,
+            settle: 0,
+            acceleration: 0,
+        }
+    }
+
+    /// Sets the highest acceleration of the profile (counts/s^2), used to derive the window.
+    pub fn set_accel(&mut self, accel: i32) {
+        self.accel = accel.saturating_abs().max(1);
+    }
+
+    /// Sets the jerk limit (counts/s^3), applied from the next move.
+    pub fn set_jerk(&mut self, jerk: i32) {
+        self.jerk = jerk.saturating_abs().max(1);
+    }
+
+    /// Returns the jerk limit (counts/s^3).
+    pub fn jerk(&self) -> i32 {
+        self.jerk
+    }
+
+    /// Starts averaging from the given position and velocity.
+    ///
+    /// # Arguments
+    /// * `position` - Start position (counts)
+    /// * `velocity` - Start velocity (counts/s)
+    ///
+    /// Returns the position the trapezoid has to start from (ahead by half of the window).
+    pub fn start(&mut self, position: i32, velocity: i32) -> i32 {
+        let freq = self.frequency.max(1) as i64;
+
+        // Ramp time accel / jerk expressed in ticks
+        let taps = (self.accel as i64 * freq) / self.jerk as i64;
+        self.taps = taps.clamp(1, SCURVE_TAPS as i64) as usize;
+
+        // Fill the window along the current velocity so the average equals the start position
+        let half = (self.taps as i64 - 1) / 2;
+        self.pos_sum = 0;
+        for (i, sample) in self.positions[..self.taps].iter_mut().enumerate() {
+            let offset = (velocity as i64 * (i as i64 - half)) / freq;
+            *sample = position.wrapping_add(offset as i32);
+            self.pos_sum += *sample as i64;
+        }
+        self.velocities[..self.taps].fill(velocity);
+        self.vel_sum = velocity as i64 * self.taps as i64;
+        self.index = 0;
+        self.settle = self.taps;
+        self.acceleration = 0;
+
+        self.positions[self.taps - 1]
+    }
+
+    /// Stops averaging immediately.
+    pub fn stop(&mut self) {
+        self.settle = 0;
+        self.velocities[..self.taps].fill(0);
+        self.vel_sum = 0;
+        self.acceleration = 0;
+    }
+
+    /// Adds the next trapezoidal setpoint and returns the averaged position setpoint (counts).
+    ///
+    /// # Arguments
+    /// * `position` - Trapezoidal position setpoint (counts)
+    /// * `velocity` - Trapezoidal velocity setpoint (counts/s)
+    pub fn tick(&mut self, position: i32, velocity: i32) -> i32 {
+        let oldest_pos = self.positions[self.index];
+        let oldest_vel = self.velocities[self.index];
+        self.positions[self.index] = position;
+        self.velocities[self.index] = velocity;
+        self.index = (self.index + 1) % self.taps;
+        self.pos_sum += position as i64 - oldest_pos as i64;
+        self.vel_sum += velocity as i64 - oldest_vel as i64;
+
+        // Output stays in motion until the whole window holds the same value
+        if position != oldest_pos {
+            self.settle = self.taps;
+        } else {
+            self.settle = self.settle.saturating_sub(1);
+        }
+
+        // Derivative of the averaged velocity: (newest - oldest) / window
+        let accel = (velocity as i64 - oldest_vel as i64) * self.frequency as i64;
+        self.acceleration = (accel / self.taps as i64) as i32;
+
+        self.position()
+    }
+
+    /// Returns true until the averaged output reaches the last input.
+    pub fn is_active(&self) -> bool {
+        self.settle > 0
+    }
+
+    /// Returns the averaged position setpoint (counts).
+    pub fn position(&self) -> i32 {
+        self.pos_sum.div_euclid(self.taps as i64) as i32
+    }
+
+    /// Returns the velocity setpoint (counts/s).
+    pub fn velocity(&self) -> i32 {
+        (self.vel_sum / self.taps as i64) as i32
+    }
+
+    /// Returns the acceleration setpoint (counts/s^2).
+    pub fn acceleration(&self) -> i32 {
+        self.acceleration
+    }
+
+    /// Returns the active window length (ticks).
+    pub fn taps(&self) -> usize {
+        self.taps
+    }
+}
@@ -7,6 +7,8 @@ pub struct SpeedEstimator {
     freq: u16,            // Sampling frequency
     speed: i32,           // Calculated speed
     pos_buffer: [i32; SIZE], // Circular buffer for position samples
+    dt_buffer: [i32; SIZE],  // Circular buffer of the periods ending at each sample (Q16 of nominal)
+    dt_sum: i64,             // Sum of the buffered periods (Q16 of nominal)
     idx: usize,           // Current index in circular buffer
 }
 
@@ -17,6 +19,8 @@ impl SpeedEstimator {
             freq,
             speed: 0,
             pos_buffer: [init_position; SIZE],
+            dt_buffer: [1 << 16; SIZE],
+            dt_sum: (SIZE as i64) << 16,
             idx: 0,
         }
     }
@@ -31,12 +35,29 @@ impl SpeedEstimator {
 
         // Update buffer
         self.pos_buffer[self.idx] = new_position;
+        self.dt_sum += (1 << 16) - self.dt_buffer[self.idx] as i64;
+        self.dt_buffer[self.idx] = 1 << 16;
 
         // Update index value
         self.idx = (self.idx + 1) % SIZE;
         self
     }
 
+    // Math call with the measured period since the previous sample (Q16, 1 << 16 = nominal)
+    pub fn tick_dt(&mut self, new_position: i32, dt: i32) -> &Self {
+        // Window time is the sum of the last SIZE periods instead of SIZE nominal ones
+        let dt = dt.max(1);
+        self.dt_sum += dt as i64 - self.dt_buffer[self.idx] as i64;
+        self.dt_buffer[self.idx] = dt;
+        let difference = new_position.wrapping_sub(self.pos_buffer[self.idx]) as i64;
+        let speed = (difference * self.freq as i64 * (1 << 16)) / self.dt_sum.max(1);
+        self.speed = speed.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+
+        self.pos_buffer[self.idx] = new_position;
+        self.idx = (self.idx + 1) % SIZE;
+        self
+    }
+
     // Getter for instant speed
     pub fn get_speed(&self) -> i32 {
         self.speed
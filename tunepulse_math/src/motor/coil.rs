@@ -35,6 +35,20 @@ pub mod duty {
             return (voltg_ref, 0);
         }
     }
+
+    /// Calculates coil duties for locked-antiphase switching (fast decay)
+    /// The second channel has to run with inverted output polarity: both half-bridges then always switch
+    /// opposite, the coil sees only direct and reverse polarity and the current decays through the supply
+    #[inline(always)]
+    pub fn antiphase(voltg_ref: i16) -> (i16, i16) {
+        if voltg_ref == i16::MIN {
+            return (voltg_ref, voltg_ref); // Returns disabled voltage if reference is disabled
+        }
+        // Average coil voltage is (2 * duty - 1) of the supply, 50% duty is zero voltage
+        const MIDPOINT: i16 = i16::MAX >> 1; // Defines the midpoint for PWM alignment
+        let duty = MIDPOINT + (voltg_ref >> 1); // Calculates duty cycle based on reference voltage
+        (duty, duty)
+    }
 }
 
 pub mod current {
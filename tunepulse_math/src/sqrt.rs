@@ -0,0 +1,139 @@
+// Implements the square root module, integer square roots and vector magnitude helpers for
+// current limiting and braking distance calculations.
+
+// Key Features:
+// - Exact integer square root (floor) of u64 and u32 values, bit-by-bit method.
+// - Magnitude of a 2D vector (e.g. alpha/beta current or voltage), no overflow for any i32 input.
+// - Vector magnitude limiting that keeps the direction.
+
+// Detailed Operation:
+// The bit-by-bit method finds one result bit per iteration using only shifts, additions and
+// comparisons, so it has a fixed worst case (32 iterations for u64) and needs no division or
+// initial estimate. The result is always floor(sqrt(value)). The vector limiter computes the
+// magnitude and scales both components by limit / magnitude, so the vector is shortened along its
+// own direction instead of clipping each component separately (which would rotate it).
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+/// Integer square root of a u64 value: floor(sqrt(value)).
+pub const fn isqrt(value: u64) -> u64 {
+    let mut rest = value;
+    let mut root: u64 = 0;
+    let mut bit: u64 = 1 << 62; // Highest power of four within u64
+
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rest >= root + bit {
+            rest -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Integer square root of a u32 value: floor(sqrt(value)).
+pub const fn isqrt_u32(value: u32) -> u16 {
+    let mut rest = value;
+    let mut root: u32 = 0;
+    let mut bit: u32 = 1 << 30; // Highest power of four within u32
+
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rest >= root + bit {
+            rest -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root as u16
+}
+
+/// Magnitude of the vector (a, b): floor(sqrt(a^2 + b^2)).
+pub const fn magnitude(a: i32, b: i32) -> u32 {
+    let a = a.unsigned_abs() as u64;
+    let b = b.unsigned_abs() as u64;
+    isqrt(a * a + b * b) as u32 // At most 2 * 2^62, sqrt below 2^32
+}
+
+/// Shortens the vector (a, b) to at most `limit` keeping its direction.
+///
+/// # Arguments
+/// * `vector` - Components of the vector
+/// * `limit` - Maximum magnitude (negative values are treated as 0)
+pub const fn limit_magnitude(vector: (i32, i32), limit: i32) -> (i32, i32) {
+    let limit = if limit < 0 { 0 } else { limit as i64 };
+    let length = magnitude(vector.0, vector.1) as i64;
+    if length <= limit {
+        return vector;
+    }
+    (
+        (vector.0 as i64 * limit / length) as i32,
+        (vector.1 as i64 * limit / length) as i32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isqrt_u32_exact_at_every_square() {
+        for root in 0..=u16::MAX as u32 {
+            let square = root * root;
+            assert_eq!(isqrt_u32(square) as u32, root);
+            if root > 0 {
+                assert_eq!(isqrt_u32(square - 1) as u32, root - 1);
+            }
+            let next = ((root as u64 + 1) * (root as u64 + 1) - 1).min(u32::MAX as u64) as u32;
+            assert_eq!(isqrt_u32(next) as u32, root);
+        }
+    }
+
+    #[test]
+    fn isqrt_floor_over_u64() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(u64::MAX), u32::MAX as u64);
+        let mut root: u64 = 1;
+        while root <= u32::MAX as u64 {
+            let square = root * root;
+            assert_eq!(isqrt(square), root);
+            assert_eq!(isqrt(square - 1), root - 1);
+            root = root * 3 + 1;
+        }
+        for value in 0..100_000u64 {
+            let root = isqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value);
+        }
+    }
+
+    #[test]
+    fn magnitude_edges() {
+        assert_eq!(magnitude(0, 0), 0);
+        assert_eq!(magnitude(3, -4), 5);
+        assert_eq!(magnitude(i32::MIN, 0), 1 << 31);
+        assert_eq!(magnitude(0, i32::MAX), i32::MAX as u32);
+        assert_eq!(magnitude(i32::MIN, i32::MIN), 3_037_000_499); // floor(2^31 * sqrt(2))
+        assert_eq!(magnitude(i32::MAX, i32::MIN), 3_037_000_499);
+    }
+
+    #[test]
+    fn limit_magnitude_keeps_direction() {
+        assert_eq!(limit_magnitude((3, 4), 10), (3, 4));
+        assert_eq!(limit_magnitude((300, -400), 50), (30, -40));
+        assert_eq!(limit_magnitude((5, 5), -1), (0, 0));
+        let (a, b) = limit_magnitude((i32::MIN, i32::MIN), 1000);
+        assert_eq!((a, b), (-707, -707));
+        assert!(magnitude(a, b) <= 1000);
+    }
+}
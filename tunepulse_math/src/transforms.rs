@@ -0,0 +1,208 @@
+// Implements the reference frame transforms of field oriented control: Clarke (three-phase to
+// stationary alpha/beta) and Park (stationary to rotating d/q), both ways, in Q15.
+
+// Key Features:
+// - Amplitude-invariant Clarke transform from three phases or from two phases (a + b + c = 0).
+// - Inverse Clarke transform back to three phase values.
+// - Park and inverse Park transforms using a (sine, cosine) pair of the electrical angle.
+// - Rounded to nearest and saturated to the i16 range, no floating point.
+
+// Detailed Operation:
+// All values are i1.15 (Q15) fractions of a common full scale, so the same functions serve
+// currents, voltages and duties. The amplitude-invariant scaling keeps the alpha/beta magnitude
+// equal to the phase amplitude. The Park functions take the angle as the (sine, cosine) pair
+// returned by `trigonometry::angle2sincos_interp()`, so one lookup serves both directions of a
+// control cycle. Table sines are not exactly unit length, so both Park directions divide by the
+// length of the given pair and are pure rotations: a round trip in either order reproduces the input
+// magnitude within 1 LSB, also at full scale (products are rounded, not truncated), and so does a
+// Clarke round trip. A Park transform costs a 64-bit square root and two 64-bit divisions for that.
+
+// Licensed under the Apache License, Version 2.0
+// Copyright 2024 Anton Khrustalev, creapunk.com
+
+use crate::sqrt::isqrt;
+
+/// Fractional bits of the transform constants
+const K_FRAC: u32 = 30;
+/// 1/sqrt(3) in Q30
+const INV_SQRT3: i64 = 619_925_131;
+/// sqrt(3)/2 in Q30
+const SQRT3_DIV2: i64 = 929_887_697;
+/// 1/3 in Q30
+const ONE_THIRD: i64 = 357_913_941;
+
+/// Clarke transform of three phase values into (alpha, beta).
+///
+/// # Arguments
+/// * `a`, `b`, `c` - Phase values (Q15), any common-mode component is removed
+pub const fn clarke(a: i16, b: i16, c: i16) -> (i16, i16) {
+    let (a, b, c) = (a as i32, b as i32, c as i32);
+    // alpha = (2a - b - c) / 3, written as a minus the common mode so it is exact when a + b + c = 0
+    let alpha = a - mul_k(a + b + c, ONE_THIRD);
+    let beta = mul_k(b - c, INV_SQRT3);
+    (sat_i16(alpha), sat_i16(beta))
+}
+
+/// Clarke transform of two measured phases into (alpha, beta), assuming a + b + c = 0.
+///
+/// # Arguments
+/// * `a`, `b` - Phase values (Q15)
+pub const fn clarke2(a: i16, b: i16) -> (i16, i16) {
+    let (a, b) = (a as i32, b as i32);
+    let beta = mul_k(a + 2 * b, INV_SQRT3);
+    (a as i16, sat_i16(beta))
+}
+
+/// Inverse Clarke transform of (alpha, beta) into three phase values (a, b, c).
+pub const fn inverse_clarke(alpha: i16, beta: i16) -> (i16, i16, i16) {
+    // Both terms at full precision and rounded once, so b and c carry no bias from halving alpha
+    let half_alpha = -((alpha as i64) << (K_FRAC - 1));
+    let beta = beta as i64 * SQRT3_DIV2;
+    let b = round_k(half_alpha + beta);
+    let c = round_k(half_alpha - beta);
+    (alpha, sat_i16(b), sat_i16(c))
+}
+
+/// Park transform of (alpha, beta) into the rotating frame (d, q).
+///
+/// # Arguments
+/// * `alpha`, `beta` - Stationary frame values (Q15)
+/// * `sincos` - (sine, cosine) of the electrical angle (Q15)
+pub const fn park(alpha: i16, beta: i16, sincos: (i16, i16)) -> (i16, i16) {
+    let (alpha, beta) = (alpha as i64, beta as i64);
+    let (sin, cos) = (sincos.0 as i64, sincos.1 as i64);
+    let length = length_q30(sin, cos);
+    let d = div_round((alpha * cos + beta * sin) << 15, length);
+    let q = div_round((beta * cos - alpha * sin) << 15, length);
+    (sat_i16(d), sat_i16(q))
+}
+
+/// Inverse Park transform of (d, q) into the stationary frame (alpha, beta).
+///
+/// # Arguments
+/// * `d`, `q` - Rotating frame values (Q15)
+/// * `sincos` - (sine, cosine) of the electrical angle (Q15)
+pub const fn inverse_park(d: i16, q: i16, sincos: (i16, i16)) -> (i16, i16) {
+    let (d, q) = (d as i64, q as i64);
+    let (sin, cos) = (sincos.0 as i64, sincos.1 as i64);
+    let length = length_q30(sin, cos);
+    let alpha = div_round((d * cos - q * sin) << 15, length);
+    let beta = div_round((d * sin + q * cos) << 15, length);
+    (sat_i16(alpha), sat_i16(beta))
+}
+
+/// Length of a Q15 (sine, cosine) pair with 15 more fractional bits (Q30).
+#[inline(always)]
+const fn length_q30(sin: i64, cos: i64) -> i64 {
+    isqrt(((sin * sin + cos * cos) as u64) << 30) as i64
+}
+
+/// Multiplies by a Q30 constant, rounded to nearest.
+#[inline(always)]
+const fn mul_k(value: i32, factor: i64) -> i32 {
+    round_k(value as i64 * factor)
+}
+
+/// Drops the fractional bits of a constant product, rounded to nearest.
+#[inline(always)]
+const fn round_k(value: i64) -> i32 {
+    ((value + (1 << (K_FRAC - 1))) >> K_FRAC) as i32
+}
+
+/// Divides by a positive divisor, rounded to nearest (a zero divisor gives zero).
+#[inline(always)]
+const fn div_round(value: i64, divisor: i64) -> i32 {
+    if divisor <= 0 {
+        return 0;
+    }
+    let half = divisor >> 1;
+    if value >= 0 {
+        ((value + half) / divisor) as i32
+    } else {
+        ((value - half) / divisor) as i32
+    }
+}
+
+/// Saturates into the i16 range.
+#[inline(always)]
+const fn sat_i16(value: i32) -> i16 {
+    if value > i16::MAX as i32 {
+        i16::MAX
+    } else if value < i16::MIN as i32 {
+        i16::MIN
+    } else {
+        value as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::trigonometry::angle2sincos_interp;
+    use std::f64::consts::PI;
+
+    const AMPLITUDES: [i32; 7] = [1, 100, 5000, 20000, 32767, -32767, -1];
+
+    /// Angles over the full turn (65536 per turn), including all axes.
+    fn angles() -> impl Iterator<Item = u16> {
+        (0..=u16::MAX).step_by(37).chain([0, 16384, 32768, 49152, u16::MAX])
+    }
+
+    /// Components of a vector of the given amplitude and angle, rounded to the i16 range.
+    fn vector(amplitude: i32, angle: u16, offset: f64) -> f64 {
+        let radians = angle as f64 * 2.0 * PI / 65536.0 - offset;
+        (amplitude as f64 * radians.cos()).round().clamp(i16::MIN as f64, i16::MAX as f64)
+    }
+
+    fn magnitude(x: i16, y: i16) -> f64 {
+        (x as f64).hypot(y as f64)
+    }
+
+    #[test]
+    fn clarke_round_trip() {
+        for amplitude in AMPLITUDES {
+            for angle in angles() {
+                // Balanced phases: forward, then back to the three phases
+                let [a, b, c] = [0.0, 2.0 * PI / 3.0, -2.0 * PI / 3.0].map(|k| vector(amplitude, angle, k) as i16);
+                let (alpha, beta) = clarke(a, b, c);
+                let (ra, rb, rc) = inverse_clarke(alpha, beta);
+                for (phase, back) in [(a, ra), (b, rb), (c, rc)] {
+                    assert!((phase as i32 - back as i32).abs() <= 1, "{} at {}: {:?}", amplitude, angle, (a, b, c));
+                }
+
+                // Stationary vector: to the three phases and back
+                let (alpha, beta) = (vector(amplitude, angle, 0.0) as i16, vector(amplitude, angle, PI / 2.0) as i16);
+                let (a, b, c) = inverse_clarke(alpha, beta);
+                let (ra, rb) = clarke(a, b, c);
+                assert!((magnitude(ra, rb) - magnitude(alpha, beta)).abs() <= 1.0, "{} at {}", amplitude, angle);
+                assert!((ra as i32 - alpha as i32).abs() <= 1 && (rb as i32 - beta as i32).abs() <= 1);
+                let (ra, rb) = clarke2(a, b); // Two phases are enough for a balanced set
+                assert!(ra == alpha && (rb as i32 - beta as i32).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn park_round_trip() {
+        for amplitude in AMPLITUDES {
+            for angle in angles() {
+                let (alpha, beta) = (vector(amplitude, angle, 0.0) as i16, vector(amplitude, angle, PI / 2.0) as i16);
+                for rotor in [0u16, 5461, 16384, 40000, angle, angle.wrapping_add(12345)] {
+                    let sincos = angle2sincos_interp(rotor as i16);
+                    // Stationary to rotating and back
+                    let (d, q) = park(alpha, beta, sincos);
+                    let (ra, rb) = inverse_park(d, q, sincos);
+                    let error = magnitude(ra, rb) - magnitude(alpha, beta);
+                    assert!(error.abs() <= 1.0, "{} at {}, rotor {}: {}", amplitude, angle, rotor, error);
+
+                    // Rotating to stationary and back
+                    let (a, b) = inverse_park(alpha, beta, sincos);
+                    let (rd, rq) = park(a, b, sincos);
+                    assert!((rd as i32 - alpha as i32).abs() <= 1 && (rq as i32 - beta as i32).abs() <= 1);
+                    assert!((magnitude(rd, rq) - magnitude(alpha, beta)).abs() <= 1.0);
+                }
+            }
+        }
+    }
+}